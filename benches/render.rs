@@ -0,0 +1,59 @@
+//! Benchmarks of the rendering pipeline stages.
+//!
+//! Run with `cargo bench`; use `wechat_pub_rs::profile::profile_render` to get the stage
+//! timings of a single article.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::collections::HashMap;
+use std::hint::black_box;
+use wechat_pub_rs::CssVariableProcessor;
+use wechat_pub_rs::markdown::MarkdownParser;
+use wechat_pub_rs::theme::{BuiltinTheme, ThemeManager};
+
+const ARTICLE: &str = include_str!("../fixtures/example.md");
+
+/// Builds a long post by repeating the example article.
+fn long_article() -> String {
+    let parser = MarkdownParser::new();
+    let body = parser.parse(ARTICLE).unwrap().content;
+    body.repeat(10)
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let parser = MarkdownParser::new();
+    c.bench_function("parse", |b| b.iter(|| parser.parse(black_box(ARTICLE))));
+}
+
+fn bench_render(c: &mut Criterion) {
+    let manager = ThemeManager::new();
+    let content = long_article();
+    let metadata = HashMap::new();
+
+    let mut group = c.benchmark_group("render");
+    group.sample_size(20);
+    for theme in [
+        BuiltinTheme::Default,
+        BuiltinTheme::Lapis,
+        BuiltinTheme::Rainbow,
+    ] {
+        group.bench_function(theme.as_str(), |b| {
+            b.iter(|| {
+                manager
+                    .render(black_box(&content), theme.as_str(), "github", &metadata)
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_css_vars(c: &mut Criterion) {
+    let processor = CssVariableProcessor::new();
+    let css = include_str!("../themes/rainbow.css");
+    c.bench_function("css_vars", |b| {
+        b.iter(|| processor.process_css(black_box(css)).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_parse, bench_render, bench_css_vars);
+criterion_main!(benches);
@@ -0,0 +1,220 @@
+//! Managing several Official Accounts from one process.
+//!
+//! An [`AccountManager`] holds a [`WeChatClient`] per account name and routes calls to
+//! the client of the named account, so agencies publishing to several Official Accounts
+//! don't have to juggle app IDs and secrets themselves. The accounts are usually loaded
+//! from a JSON profiles file that maps account names to credentials and, optionally, a
+//! [`Config`]:
+//!
+//! ```json
+//! {
+//!   "tech-blog": { "app_id": "wx1234567890123456", "app_secret": "..." },
+//!   "company-news": { "app_id": "wx6543210987654321", "app_secret": "..." }
+//! }
+//! ```
+//!
+//! ```rust,no_run
+//! use wechat_pub_rs::accounts::AccountManager;
+//!
+//! # async fn example() -> wechat_pub_rs::Result<()> {
+//! let manager = AccountManager::from_file("accounts.json").await?;
+//! let draft_id = manager.upload("tech-blog", "article.md").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Drafts and materials only exist in the account they were uploaded to, so every
+//! account needs content directories of its own when uploading with state tracking or
+//! write-back (uploads of another account to a tracked directory are rejected, see
+//! [`PublishState`]). A `cache.material_cache_path` can be shared, as the cache file
+//! keeps the materials of each app ID apart, and so can the store of processed images,
+//! which is keyed by content.
+//!
+//! [`PublishState`]: crate::state::PublishState
+
+use crate::client::{UploadOptions, WeChatClient};
+use crate::config::Config;
+use crate::error::{Result, WeChatError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Credentials and configuration of an Official Account in a profiles file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountProfile {
+    /// App ID of the account
+    pub app_id: String,
+    /// App secret of the account
+    pub app_secret: String,
+    /// Configuration of the account's client, the default configuration when `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<Config>,
+}
+
+/// Clients of several Official Accounts keyed by account name.
+#[derive(Debug, Default)]
+pub struct AccountManager {
+    clients: BTreeMap<String, WeChatClient>,
+}
+
+impl AccountManager {
+    /// Creates a manager without accounts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a manager with a client per profile.
+    pub async fn from_profiles(
+        profiles: impl IntoIterator<Item = (String, AccountProfile)>,
+    ) -> Result<Self> {
+        let mut manager = Self::new();
+        for (name, profile) in profiles {
+            let client = WeChatClient::with_config(
+                profile.app_id,
+                profile.app_secret,
+                profile.config.unwrap_or_default(),
+            )
+            .await
+            .map_err(|e| WeChatError::config_error(format!("Account {name}: {e}")))?;
+            manager.add(name, client);
+        }
+        Ok(manager)
+    }
+
+    /// Loads the accounts from a JSON profiles file mapping account names to
+    /// [`AccountProfile`]s.
+    pub async fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| WeChatError::file_error(path.display().to_string(), e.to_string()))?;
+        let profiles: BTreeMap<String, AccountProfile> =
+            serde_json::from_str(&json).map_err(|e| {
+                WeChatError::file_error(
+                    path.display().to_string(),
+                    format!("Invalid profiles file: {e}"),
+                )
+            })?;
+        Self::from_profiles(profiles).await
+    }
+
+    /// Adds an account, replacing the client of an account with the same name.
+    pub fn add(&mut self, name: impl Into<String>, client: WeChatClient) {
+        self.clients.insert(name.into(), client);
+    }
+
+    /// Removes an account and returns its client.
+    pub fn remove(&mut self, name: &str) -> Option<WeChatClient> {
+        self.clients.remove(name)
+    }
+
+    /// Gets the names of the accounts in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.clients.keys().map(String::as_str)
+    }
+
+    /// Gets the client of an account.
+    pub fn client(&self, name: &str) -> Result<&WeChatClient> {
+        self.clients
+            .get(name)
+            .ok_or_else(|| WeChatError::config_error(format!("Unknown account: {name}")))
+    }
+
+    /// Uploads a markdown file as a draft of an account with default options, see
+    /// [`WeChatClient::upload`].
+    pub async fn upload(&self, name: &str, markdown_path: &str) -> Result<String> {
+        self.client(name)?.upload(markdown_path).await
+    }
+
+    /// Uploads a markdown file as a draft of an account with custom options, see
+    /// [`WeChatClient::upload_with_options`].
+    pub async fn upload_with_options(
+        &self,
+        name: &str,
+        markdown_path: &str,
+        options: UploadOptions,
+    ) -> Result<String> {
+        self.client(name)?
+            .upload_with_options(markdown_path, options)
+            .await
+    }
+
+    /// Shuts down the clients of all accounts, see [`WeChatClient::shutdown`].
+    ///
+    /// Every client is shut down even if another fails; the first error is returned.
+    pub async fn shutdown(&self) -> Result<()> {
+        let mut result = Ok(());
+        for client in self.clients.values() {
+            if let Err(e) = client.shutdown().await
+                && result.is_ok()
+            {
+                result = Err(e);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_account_manager_routing() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let profiles_path = temp_dir.path().join("accounts.json");
+        std::fs::write(
+            &profiles_path,
+            r#"{
+                "tech-blog": {
+                    "app_id": "wx1234567890123456",
+                    "app_secret": "12345678901234567890123456789012"
+                },
+                "company-news": {
+                    "app_id": "wx6543210987654321",
+                    "app_secret": "21098765432109876543210987654321"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let manager = AccountManager::from_file(&profiles_path).await.unwrap();
+        assert_eq!(
+            manager.names().collect::<Vec<_>>(),
+            ["company-news", "tech-blog"]
+        );
+        assert!(manager.client("tech-blog").is_ok());
+        assert!(matches!(
+            manager.client("personal"),
+            Err(WeChatError::Config { .. })
+        ));
+
+        std::fs::write(temp_dir.path().join("cover.png"), b"cover").unwrap();
+        let markdown_path = temp_dir.path().join("post.md");
+        std::fs::write(
+            &markdown_path,
+            "---\ntitle: Routed\ncover: cover.png\n---\n\n# Hello\n",
+        )
+        .unwrap();
+        let report = manager
+            .client("tech-blog")
+            .unwrap()
+            .upload_with_report(
+                &markdown_path.display().to_string(),
+                UploadOptions::default().dry_run(true),
+            )
+            .await
+            .unwrap();
+        assert!(report.dry_run.unwrap().html.contains("Hello"));
+        assert!(
+            manager
+                .upload("personal", &markdown_path.display().to_string())
+                .await
+                .is_err()
+        );
+        manager.shutdown().await.unwrap();
+
+        std::fs::write(&profiles_path, r#"{"bad": {"app_id": "wx1"}}"#).unwrap();
+        assert!(AccountManager::from_file(&profiles_path).await.is_err());
+    }
+}
@@ -0,0 +1,421 @@
+//! Article and follower statistics from the `datacube` API.
+//!
+//! WeChat reports read, share and favorite counts and follower growth per day, up to
+//! yesterday (China time). Each statistic is queried over a [`DateRange`], which is
+//! split into the longest span the endpoint allows per call (one day for article
+//! statistics, three days for the read overview, seven days for followers), so any
+//! range can be passed. An [`AnalyticsManager`]
+//! makes the calls; [`WeChatClient`](crate::WeChatClient) exposes them, e.g.
+//! [`get_article_summary`](crate::WeChatClient::get_article_summary):
+//!
+//! ```rust,no_run
+//! use wechat_pub_rs::analytics::DateRange;
+//!
+//! # async fn example(client: wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+//! for summary in client.get_article_summary(DateRange::last_days(7)).await? {
+//!     println!(
+//!         "{} {}: {} reads, {} shares",
+//!         summary.ref_date, summary.title, summary.stats.reads, summary.stats.shares
+//!     );
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::auth::TokenManager;
+use crate::endpoints::{self, Endpoint};
+use crate::error::{Result, WeChatError};
+use crate::http::{self, WeChatHttpClient, WeChatResponse};
+use crate::locale::TimeZone;
+use chrono::{NaiveDate, TimeDelta};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Longest range of [`AnalyticsManager::article_summary`] and
+/// [`AnalyticsManager::article_total`] per call, in days.
+pub const ARTICLE_MAX_DAYS: u32 = 1;
+
+/// Longest range of [`AnalyticsManager::user_read`] per call, in days.
+pub const USER_READ_MAX_DAYS: u32 = 3;
+
+/// Longest range of [`AnalyticsManager::user_summary`] and
+/// [`AnalyticsManager::user_cumulate`] per call, in days.
+pub const USER_MAX_DAYS: u32 = 7;
+
+/// `user_source` of [`UserRead`] rows counting all sources.
+pub const ALL_SOURCES: u32 = 99999999;
+
+/// An inclusive range of days.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    /// First day
+    pub begin: NaiveDate,
+    /// Last day
+    pub end: NaiveDate,
+}
+
+impl DateRange {
+    /// Creates a range from `begin` to `end`, both included.
+    pub fn new(begin: NaiveDate, end: NaiveDate) -> Result<Self> {
+        if end < begin {
+            return Err(WeChatError::config_error(format!(
+                "Date range ends ({end}) before it begins ({begin})"
+            )));
+        }
+        Ok(Self { begin, end })
+    }
+
+    /// Creates a range of a single day.
+    pub fn day(date: NaiveDate) -> Self {
+        Self {
+            begin: date,
+            end: date,
+        }
+    }
+
+    /// Creates a range of the last `days` days (at least one) up to yesterday in China
+    /// time, the latest day WeChat has statistics for.
+    pub fn last_days(days: u32) -> Self {
+        let end = TimeZone::CHINA.today() - TimeDelta::days(1);
+        Self {
+            begin: end - TimeDelta::days(i64::from(days.max(1)) - 1),
+            end,
+        }
+    }
+
+    /// Gets the number of days in the range.
+    pub fn days(&self) -> u32 {
+        (self.end - self.begin).num_days() as u32 + 1
+    }
+
+    /// Splits the range into consecutive ranges of at most `max_days` days.
+    pub fn chunks(&self, max_days: u32) -> Vec<DateRange> {
+        let span = TimeDelta::days(i64::from(max_days.max(1)) - 1);
+        let mut chunks = Vec::new();
+        let mut begin = self.begin;
+        while begin <= self.end {
+            let end = (begin + span).min(self.end);
+            chunks.push(DateRange { begin, end });
+            begin = end + TimeDelta::days(1);
+        }
+        chunks
+    }
+}
+
+/// Read, share and favorite counts shared by all statistics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadStats {
+    /// Readers of the article page
+    #[serde(rename = "int_page_read_user", default)]
+    pub read_users: u64,
+    /// Reads of the article page
+    #[serde(rename = "int_page_read_count", default)]
+    pub reads: u64,
+    /// Readers of the original article ("read more" link)
+    #[serde(rename = "ori_page_read_user", default)]
+    pub original_read_users: u64,
+    /// Reads of the original article
+    #[serde(rename = "ori_page_read_count", default)]
+    pub original_reads: u64,
+    /// Users who shared the article
+    #[serde(rename = "share_user", default)]
+    pub share_users: u64,
+    /// Shares of the article
+    #[serde(rename = "share_count", default)]
+    pub shares: u64,
+    /// Users who added the article to their favorites
+    #[serde(rename = "add_to_fav_user", default)]
+    pub favorite_users: u64,
+    /// Additions to favorites
+    #[serde(rename = "add_to_fav_count", default)]
+    pub favorites: u64,
+}
+
+/// Statistics of an article on one day (`datacube/getarticlesummary` endpoint).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArticleSummary {
+    /// Day of the statistics
+    pub ref_date: NaiveDate,
+    /// Message and article position, as `<msg_data_id>_<index>`
+    #[serde(rename = "msgid")]
+    pub msg_id: String,
+    /// Title of the article
+    #[serde(default)]
+    pub title: String,
+    /// Counts of the day
+    #[serde(flatten)]
+    pub stats: ReadStats,
+}
+
+/// Cumulative statistics of an article published on a day (`datacube/getarticletotal`
+/// endpoint).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArticleTotal {
+    /// Day the article was published
+    pub ref_date: NaiveDate,
+    /// Message and article position, as `<msg_data_id>_<index>`
+    #[serde(rename = "msgid")]
+    pub msg_id: String,
+    /// Title of the article
+    #[serde(default)]
+    pub title: String,
+    /// Counts up to each day since publishing, at most 7 days
+    #[serde(default)]
+    pub details: Vec<ArticleTotalDetail>,
+}
+
+/// Counts of an article up to a day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArticleTotalDetail {
+    /// Day the counts run up to
+    pub stat_date: NaiveDate,
+    /// Followers the article was sent to
+    #[serde(default)]
+    pub target_user: u64,
+    /// Counts since publishing
+    #[serde(flatten)]
+    pub stats: ReadStats,
+}
+
+/// Reads of all articles on one day from a source (`datacube/getuserread` endpoint).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserRead {
+    /// Day of the statistics
+    pub ref_date: NaiveDate,
+    /// Where the reads came from, [`ALL_SOURCES`] for all of them
+    #[serde(default = "all_sources")]
+    pub user_source: u32,
+    /// Counts of the day
+    #[serde(flatten)]
+    pub stats: ReadStats,
+}
+
+/// Followers gained and lost on one day through a source (`datacube/getusersummary`
+/// endpoint).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserSummary {
+    /// Day of the statistics
+    pub ref_date: NaiveDate,
+    /// How the followers found the account, e.g. 0 for other sources, 1 for search,
+    /// 17 for business cards, 30 for QR codes
+    #[serde(default)]
+    pub user_source: u32,
+    /// New followers
+    #[serde(rename = "new_user", default)]
+    pub new_users: u64,
+    /// Followers who unfollowed
+    #[serde(rename = "cancel_user", default)]
+    pub cancelled_users: u64,
+}
+
+impl UserSummary {
+    /// Gets the net follower growth of the day and source.
+    pub fn net_growth(&self) -> i64 {
+        self.new_users as i64 - self.cancelled_users as i64
+    }
+}
+
+/// Total number of followers at the end of a day (`datacube/getusercumulate` endpoint).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserCumulate {
+    /// Day of the statistics
+    pub ref_date: NaiveDate,
+    /// Followers at the end of the day
+    #[serde(rename = "cumulate_user", default)]
+    pub total_users: u64,
+}
+
+fn all_sources() -> u32 {
+    ALL_SOURCES
+}
+
+/// Rows of a `datacube` response.
+#[derive(Debug, Deserialize)]
+struct ListResponse<T> {
+    #[serde(default = "Vec::new")]
+    list: Vec<T>,
+}
+
+/// Queries article statistics.
+#[derive(Debug)]
+pub struct AnalyticsManager {
+    http_client: Arc<WeChatHttpClient>,
+    token_manager: Arc<TokenManager>,
+}
+
+impl AnalyticsManager {
+    /// Creates a new analytics manager.
+    pub fn new(http_client: Arc<WeChatHttpClient>, token_manager: Arc<TokenManager>) -> Self {
+        Self {
+            http_client,
+            token_manager,
+        }
+    }
+
+    /// Gets the statistics of the articles read on each day of a range.
+    pub async fn article_summary(&self, range: DateRange) -> Result<Vec<ArticleSummary>> {
+        self.list(endpoints::DATACUBE_ARTICLE_SUMMARY, range, ARTICLE_MAX_DAYS)
+            .await
+    }
+
+    /// Gets the cumulative statistics of the articles published on each day of a range.
+    pub async fn article_total(&self, range: DateRange) -> Result<Vec<ArticleTotal>> {
+        self.list(endpoints::DATACUBE_ARTICLE_TOTAL, range, ARTICLE_MAX_DAYS)
+            .await
+    }
+
+    /// Gets the reads of all articles by day and source.
+    pub async fn user_read(&self, range: DateRange) -> Result<Vec<UserRead>> {
+        self.list(endpoints::DATACUBE_USER_READ, range, USER_READ_MAX_DAYS)
+            .await
+    }
+
+    /// Gets the followers gained and lost by day and source.
+    pub async fn user_summary(&self, range: DateRange) -> Result<Vec<UserSummary>> {
+        self.list(endpoints::DATACUBE_USER_SUMMARY, range, USER_MAX_DAYS)
+            .await
+    }
+
+    /// Gets the total number of followers by day.
+    pub async fn user_cumulate(&self, range: DateRange) -> Result<Vec<UserCumulate>> {
+        self.list(endpoints::DATACUBE_USER_CUMULATE, range, USER_MAX_DAYS)
+            .await
+    }
+
+    /// Queries a range in chunks of at most `max_days` days and joins the rows.
+    async fn list<T: DeserializeOwned + std::fmt::Debug>(
+        &self,
+        endpoint: Endpoint,
+        range: DateRange,
+        max_days: u32,
+    ) -> Result<Vec<T>> {
+        let mut rows = Vec::new();
+        for chunk in range.chunks(max_days) {
+            let access_token = self.token_manager.get_access_token().await?;
+            let request = range_request(chunk);
+            let response = self
+                .http_client
+                .post_json_with_token(endpoint.path, &access_token, &request)
+                .await?;
+            let response: WeChatResponse<ListResponse<T>> = http::read_json(response).await?;
+            rows.extend(response.into_result()?.list);
+        }
+        Ok(rows)
+    }
+}
+
+/// Builds the request body of a date range.
+fn range_request(range: DateRange) -> serde_json::Value {
+    serde_json::json!({
+        "begin_date": range.begin.format("%Y-%m-%d").to_string(),
+        "end_date": range.end.format("%Y-%m-%d").to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, day).unwrap()
+    }
+
+    #[test]
+    fn test_date_range() {
+        let range = DateRange::new(date(1), date(7)).unwrap();
+        assert_eq!(range.days(), 7);
+        assert_eq!(
+            range.chunks(USER_READ_MAX_DAYS),
+            [
+                DateRange::new(date(1), date(3)).unwrap(),
+                DateRange::new(date(4), date(6)).unwrap(),
+                DateRange::day(date(7)),
+            ]
+        );
+        assert_eq!(range.chunks(ARTICLE_MAX_DAYS).len(), 7);
+        assert!(DateRange::new(date(2), date(1)).is_err());
+        assert_eq!(
+            range_request(DateRange::day(date(1))),
+            serde_json::json!({ "begin_date": "2024-05-01", "end_date": "2024-05-01" })
+        );
+
+        let week = DateRange::last_days(7);
+        assert_eq!(week.days(), 7);
+        assert!(week.end < TimeZone::CHINA.today());
+    }
+
+    #[test]
+    fn test_statistics_responses() {
+        let summary: WeChatResponse<ListResponse<ArticleSummary>> = serde_json::from_str(
+            r#"{"list":[{"ref_date":"2024-05-01","msgid":"2247483674_1","title":"Hello",
+            "int_page_read_user":120,"int_page_read_count":150,"ori_page_read_user":3,
+            "ori_page_read_count":4,"share_user":10,"share_count":12,
+            "add_to_fav_user":5,"add_to_fav_count":6}]}"#,
+        )
+        .unwrap();
+        let summary = summary.into_result().unwrap().list;
+        assert_eq!(summary[0].ref_date, date(1));
+        assert_eq!(summary[0].msg_id, "2247483674_1");
+        assert_eq!(
+            summary[0].stats,
+            ReadStats {
+                read_users: 120,
+                reads: 150,
+                original_read_users: 3,
+                original_reads: 4,
+                share_users: 10,
+                shares: 12,
+                favorite_users: 5,
+                favorites: 6,
+            }
+        );
+
+        let total: ListResponse<ArticleTotal> = serde_json::from_str(
+            r#"{"list":[{"ref_date":"2024-05-01","msgid":"2247483674_1","title":"Hello",
+            "details":[{"stat_date":"2024-05-01","target_user":1000,"int_page_read_count":150},
+                       {"stat_date":"2024-05-02","target_user":1000,"int_page_read_count":210}]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(total.list[0].details[1].stat_date, date(2));
+        assert_eq!(total.list[0].details[1].stats.reads, 210);
+
+        let reads: ListResponse<UserRead> = serde_json::from_str(
+            r#"{"list":[{"ref_date":"2024-05-01","user_source":99999999,"int_page_read_count":300},
+            {"ref_date":"2024-05-01","user_source":2,"int_page_read_count":80}]}"#,
+        )
+        .unwrap();
+        assert_eq!(reads.list[0].user_source, ALL_SOURCES);
+        assert_eq!(reads.list[1].stats.reads, 80);
+
+        let empty: ListResponse<UserRead> = serde_json::from_str("{}").unwrap();
+        assert!(empty.list.is_empty());
+    }
+
+    #[test]
+    fn test_follower_responses() {
+        let summary: ListResponse<UserSummary> = serde_json::from_str(
+            r#"{"list":[{"ref_date":"2024-05-01","user_source":0,"new_user":12,"cancel_user":3},
+            {"ref_date":"2024-05-01","user_source":30,"new_user":1,"cancel_user":4}]}"#,
+        )
+        .unwrap();
+        assert_eq!(summary.list[0].new_users, 12);
+        assert_eq!(summary.list[0].net_growth(), 9);
+        assert_eq!(summary.list[1].user_source, 30);
+        assert_eq!(summary.list[1].net_growth(), -3);
+
+        let cumulate: ListResponse<UserCumulate> = serde_json::from_str(
+            r#"{"list":[{"ref_date":"2024-05-01","cumulate_user":1024},
+            {"ref_date":"2024-05-02","cumulate_user":1030}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            cumulate.list[1],
+            UserCumulate {
+                ref_date: date(2),
+                total_users: 1030,
+            }
+        );
+        assert_eq!(DateRange::last_days(30).chunks(USER_MAX_DAYS).len(), 5);
+    }
+}
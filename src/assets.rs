@@ -0,0 +1,171 @@
+//! Content-addressed store of processed assets.
+//!
+//! Rendering Mermaid charts and converting images are slow, and their output only depends
+//! on their input. When enabled through
+//! [`UploadOptions::cache_assets`](crate::UploadOptions::cache_assets), processed artifacts
+//! are kept in `.wechat-pub/assets/<hash>` next to the markdown file, keyed by a hash of
+//! the input and the processing settings, so repeated runs reuse them instead of
+//! recomputing.
+//!
+//! ```rust
+//! use wechat_pub_rs::assets::AssetStore;
+//!
+//! // Keys depend on both the kind of processing and the input
+//! let key = AssetStore::key("mermaid", b"graph TD; A-->B");
+//! assert_ne!(key, AssetStore::key("image", b"graph TD; A-->B"));
+//! assert_eq!(key, AssetStore::key("mermaid", b"graph TD; A-->B"));
+//! ```
+
+use crate::error::{Result, WeChatError};
+use crate::state::STATE_DIR;
+use std::path::{Path, PathBuf};
+
+/// Directory (inside [`STATE_DIR`]) holding the stored assets.
+pub const ASSETS_DIR: &str = "assets";
+
+/// A directory of processed assets named by their key.
+#[derive(Debug, Clone)]
+pub struct AssetStore {
+    root: PathBuf,
+}
+
+impl AssetStore {
+    /// Creates a store in `root`. The directory is created on the first write.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Creates the store for a content directory (`<dir>/.wechat-pub/assets`).
+    pub fn for_content_dir(content_dir: &Path) -> Self {
+        Self::new(content_dir.join(STATE_DIR).join(ASSETS_DIR))
+    }
+
+    /// Gets the directory of the store.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Computes the key of an artifact from the kind of processing (including any
+    /// settings that affect the output) and its input.
+    pub fn key(kind: &str, input: &[u8]) -> String {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(kind.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(input);
+        hasher.finalize().to_hex().to_string()
+    }
+
+    /// Gets the path an artifact is stored at.
+    pub fn path(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+
+    /// Reads an artifact, returning `None` if it isn't stored.
+    pub async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.path(key);
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(WeChatError::file_error(
+                path.display().to_string(),
+                e.to_string(),
+            )),
+        }
+    }
+
+    /// Stores an artifact, replacing the file atomically.
+    pub async fn put(&self, key: &str, data: &[u8]) -> Result<PathBuf> {
+        let path = self.path(key);
+        let tmp_path = self.tmp_path(key).await?;
+        let write_error =
+            |e: std::io::Error| WeChatError::file_error(path.display().to_string(), e.to_string());
+
+        tokio::fs::write(&tmp_path, data)
+            .await
+            .map_err(write_error)?;
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .map_err(write_error)?;
+
+        Ok(path)
+    }
+
+    /// Stores a copy of the file at `source` as an artifact.
+    pub async fn put_file(&self, key: &str, source: &Path) -> Result<PathBuf> {
+        let path = self.path(key);
+        let tmp_path = self.tmp_path(key).await?;
+        let write_error =
+            |e: std::io::Error| WeChatError::file_error(path.display().to_string(), e.to_string());
+
+        tokio::fs::copy(source, &tmp_path)
+            .await
+            .map_err(|e| WeChatError::file_error(source.display().to_string(), e.to_string()))?;
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .map_err(write_error)?;
+
+        Ok(path)
+    }
+
+    /// Copies a stored artifact to `dest`, returning `false` if it isn't stored.
+    pub async fn copy_to(&self, key: &str, dest: &Path) -> Result<bool> {
+        let path = self.path(key);
+        match tokio::fs::copy(&path, dest).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(WeChatError::file_error(
+                dest.display().to_string(),
+                e.to_string(),
+            )),
+        }
+    }
+
+    /// Creates the store directory and gets a unique temporary path for writing `key`.
+    async fn tmp_path(&self, key: &str) -> Result<PathBuf> {
+        tokio::fs::create_dir_all(&self.root)
+            .await
+            .map_err(|e| WeChatError::file_error(self.root.display().to_string(), e.to_string()))?;
+        Ok(self
+            .root
+            .join(format!("{key}.{}.tmp", uuid::Uuid::new_v4())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_asset_store_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let store = AssetStore::for_content_dir(temp_dir.path());
+        assert_eq!(
+            store.root(),
+            temp_dir.path().join(".wechat-pub").join("assets")
+        );
+
+        let key = AssetStore::key("image", b"original");
+        assert_eq!(store.get(&key).await.unwrap(), None);
+
+        let path = store.put(&key, b"processed").await.unwrap();
+        assert_eq!(path, store.root().join(&key));
+        assert_eq!(
+            store.get(&key).await.unwrap().as_deref(),
+            Some(&b"processed"[..])
+        );
+
+        // Files can be stored and restored by copying
+        let source = temp_dir.path().join("chart.png");
+        std::fs::write(&source, b"png").unwrap();
+        let chart_key = AssetStore::key("mermaid", b"graph TD; A-->B");
+        store.put_file(&chart_key, &source).await.unwrap();
+
+        let dest = temp_dir.path().join("restored.png");
+        assert!(store.copy_to(&chart_key, &dest).await.unwrap());
+        assert_eq!(std::fs::read(&dest).unwrap(), b"png");
+        assert!(!store.copy_to("missing", &dest).await.unwrap());
+
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(store.root()).unwrap().count(), 2);
+    }
+}
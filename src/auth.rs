@@ -1,7 +1,9 @@
 //! Authentication module for managing WeChat access tokens.
 //!
 //! This module handles the complex process of WeChat access token management,
-//! including automatic refresh, caching, and thread-safe access.
+//! including automatic refresh, caching, and thread-safe access. The JS-SDK (`jsapi`)
+//! and card (`wx_card`) tickets are cached the same way, and [`JsSdkConfig`] signs web
+//! pages for the JS-SDK.
 //!
 //! ## Features
 //!
@@ -71,12 +73,15 @@
 //! # }
 //! ```
 
+use crate::endpoints::{self, Endpoint};
 use crate::error::Result;
-use crate::http::{AccessTokenResponse, WeChatHttpClient, WeChatResponse};
+use crate::http::{self, AccessTokenResponse, TicketResponse, WeChatHttpClient, WeChatResponse};
+use crate::traits::TokenProvider;
+use crate::utils::sha1_hex;
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use tracing::info;
 
 /// Access token with expiration information.
@@ -115,6 +120,20 @@ pub struct TokenManager {
     http_client: Arc<WeChatHttpClient>,
     token_cache: Arc<RwLock<Option<AccessToken>>>,
     refresh_lock: Arc<tokio::sync::Mutex<()>>,
+    jsapi_ticket_cache: Arc<RwLock<Option<AccessToken>>>,
+    jsapi_ticket_lock: Arc<tokio::sync::Mutex<()>>,
+    wx_card_ticket_cache: Arc<RwLock<Option<AccessToken>>>,
+    wx_card_ticket_lock: Arc<tokio::sync::Mutex<()>>,
+    provider: Option<Provider>,
+}
+
+/// Custom source of access tokens replacing the token endpoint.
+struct Provider(Arc<dyn TokenProvider>);
+
+impl std::fmt::Debug for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Provider")
+    }
 }
 
 impl TokenManager {
@@ -130,13 +149,38 @@ impl TokenManager {
             http_client,
             token_cache: Arc::new(RwLock::new(None)),
             refresh_lock: Arc::new(tokio::sync::Mutex::new(())),
+            jsapi_ticket_cache: Arc::new(RwLock::new(None)),
+            jsapi_ticket_lock: Arc::new(tokio::sync::Mutex::new(())),
+            wx_card_ticket_cache: Arc::new(RwLock::new(None)),
+            wx_card_ticket_lock: Arc::new(tokio::sync::Mutex::new(())),
+            provider: None,
         }
     }
 
+    /// Gets access tokens from a custom provider instead of the token endpoint, e.g. a
+    /// central token service when several deployments share an account (WeChat
+    /// invalidates the previous token whenever a new one is fetched).
+    ///
+    /// The provider handles caching and refreshing; jsapi and card tickets are still
+    /// fetched and cached here.
+    pub fn with_provider(mut self, provider: Arc<dyn TokenProvider>) -> Self {
+        self.provider = Some(Provider(provider));
+        self
+    }
+
+    /// Gets the app ID of the account.
+    pub fn app_id(&self) -> &str {
+        &self.app_id
+    }
+
     /// Gets a valid access token, refreshing if necessary.
     ///
     /// This method is thread-safe and will prevent concurrent token refreshes.
     pub async fn get_access_token(&self) -> Result<String> {
+        if let Some(Provider(provider)) = &self.provider {
+            return provider.get_token().await;
+        }
+
         // Check cache first (fast path)
         if let Some(token) = self.get_cached_token().await {
             return Ok(token);
@@ -171,15 +215,16 @@ impl TokenManager {
         info!("Refreshing WeChat access token");
 
         // Make API call to get new token
-        let url = format!(
-            "https://api.weixin.qq.com/cgi-bin/token?grant_type=client_credential&appid={}&secret={}",
-            self.app_id, self.app_secret
+        let endpoint = format!(
+            "{}?grant_type=client_credential&appid={}&secret={}",
+            endpoints::TOKEN.path,
+            self.app_id,
+            self.app_secret
         );
 
-        let response_bytes = self.http_client.download(&url).await?;
+        let response = self.http_client.get(&endpoint).await?;
 
-        let api_response: WeChatResponse<AccessTokenResponse> =
-            serde_json::from_slice(&response_bytes)?;
+        let api_response: WeChatResponse<AccessTokenResponse> = http::read_json(response).await?;
 
         let token_response = api_response.into_result()?;
 
@@ -197,8 +242,91 @@ impl TokenManager {
         Ok(token_string)
     }
 
+    /// Gets a valid JS-SDK (`jsapi`) ticket, fetching it if necessary.
+    ///
+    /// Tickets are cached like access tokens and share the same expiry buffer.
+    pub async fn get_jsapi_ticket(&self) -> Result<String> {
+        self.get_ticket(
+            &self.jsapi_ticket_cache,
+            &self.jsapi_ticket_lock,
+            endpoints::JSAPI_TICKET,
+        )
+        .await
+    }
+
+    /// Gets a valid card (`wx_card`) ticket for the card JS-SDK APIs, fetching it if
+    /// necessary.
+    pub async fn get_wx_card_ticket(&self) -> Result<String> {
+        self.get_ticket(
+            &self.wx_card_ticket_cache,
+            &self.wx_card_ticket_lock,
+            endpoints::WX_CARD_TICKET,
+        )
+        .await
+    }
+
+    /// Gets a ticket from its cache, or fetches it from its endpoint.
+    async fn get_ticket(
+        &self,
+        cache: &RwLock<Option<AccessToken>>,
+        lock: &Mutex<()>,
+        endpoint: Endpoint,
+    ) -> Result<String> {
+        if let Some(ticket) = cached_ticket(cache).await {
+            return Ok(ticket);
+        }
+
+        let _guard = lock.lock().await;
+
+        // Double-check after acquiring lock
+        if let Some(ticket) = cached_ticket(cache).await {
+            return Ok(ticket);
+        }
+
+        info!("Fetching WeChat {} ticket", endpoint.name);
+
+        let access_token = self.get_access_token().await?;
+        let response = self
+            .http_client
+            .get_with_token(endpoint.path, &access_token)
+            .await?;
+
+        let api_response: WeChatResponse<TicketResponse> = http::read_json(response).await?;
+        let ticket_response = api_response.into_result()?;
+
+        let ticket = AccessToken::new(ticket_response.ticket, ticket_response.expires_in);
+        let ticket_string = ticket.token.clone();
+
+        *cache.write().await = Some(ticket);
+
+        info!("Successfully fetched WeChat {} ticket", endpoint.name);
+        Ok(ticket_string)
+    }
+
+    /// Creates the `wx.config` parameters for a web page using the JS-SDK, with a fresh
+    /// nonce and the current time.
+    ///
+    /// `url` is the full URL of the page (any `#` fragment is ignored).
+    pub async fn js_sdk_config(&self, url: &str) -> Result<JsSdkConfig> {
+        let ticket = self.get_jsapi_ticket().await?;
+        let nonce_str: String = std::iter::repeat_with(fastrand::alphanumeric)
+            .take(16)
+            .collect();
+        Ok(JsSdkConfig::sign(
+            &self.app_id,
+            &ticket,
+            &nonce_str,
+            Utc::now().timestamp(),
+            url,
+        ))
+    }
+
     /// Forces a token refresh (useful for testing or when token is known to be invalid).
     pub async fn force_refresh(&self) -> Result<String> {
+        if let Some(Provider(provider)) = &self.provider {
+            return provider.refresh_token().await;
+        }
+
         // Clear cache first
         {
             let mut cache = self.token_cache.write().await;
@@ -210,6 +338,15 @@ impl TokenManager {
 
     /// Gets token information for debugging purposes.
     pub async fn get_token_info(&self) -> Option<TokenInfo> {
+        if let Some(Provider(provider)) = &self.provider {
+            let expires_at = provider.token_expires_at().await?;
+            return Some(TokenInfo {
+                is_expired: provider.is_token_expired().await,
+                expires_at,
+                time_until_expiry: expires_at - Utc::now(),
+            });
+        }
+
         let cache = self.token_cache.read().await;
         cache.as_ref().map(|token| TokenInfo {
             is_expired: token.is_expired(0),
@@ -218,10 +355,56 @@ impl TokenManager {
         })
     }
 
-    /// Clears the token cache.
+    /// Clears the token cache (and any cached jsapi or card ticket).
     pub async fn clear_cache(&self) {
         let mut cache = self.token_cache.write().await;
         *cache = None;
+        *self.jsapi_ticket_cache.write().await = None;
+        *self.wx_card_ticket_cache.write().await = None;
+    }
+}
+
+/// Gets a cached ticket if it's still valid.
+async fn cached_ticket(cache: &RwLock<Option<AccessToken>>) -> Option<String> {
+    cache
+        .read()
+        .await
+        .as_ref()
+        .filter(|ticket| !ticket.is_expired(60))
+        .map(|ticket| ticket.token.clone())
+}
+
+/// Parameters of the JS-SDK's `wx.config` for a web page, serialized with the
+/// JS-SDK's field names (`appId`, `timestamp`, `nonceStr`, `signature`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsSdkConfig {
+    /// App ID of the account
+    pub app_id: String,
+    /// Unix timestamp the signature was made at
+    pub timestamp: i64,
+    /// Random string the signature was made with
+    pub nonce_str: String,
+    /// SHA-1 signature of the ticket, nonce, timestamp and page URL
+    pub signature: String,
+}
+
+impl JsSdkConfig {
+    /// Signs a page URL with a jsapi ticket, nonce and timestamp.
+    ///
+    /// The signature is the hex SHA-1 of
+    /// `jsapi_ticket=...&noncestr=...&timestamp=...&url=...`, with any `#` fragment
+    /// removed from the URL.
+    pub fn sign(app_id: &str, ticket: &str, nonce_str: &str, timestamp: i64, url: &str) -> Self {
+        let url = url.split('#').next().unwrap_or_default();
+        let signed =
+            format!("jsapi_ticket={ticket}&noncestr={nonce_str}&timestamp={timestamp}&url={url}");
+        Self {
+            app_id: app_id.to_string(),
+            timestamp,
+            nonce_str: nonce_str.to_string(),
+            signature: sha1_hex(signed.as_bytes()),
+        }
     }
 }
 
@@ -298,6 +481,82 @@ mod tests {
         assert!(manager.get_cached_token().await.is_none());
     }
 
+    #[tokio::test]
+    async fn test_cached_jsapi_ticket_retrieval() {
+        let http_client = Arc::new(WeChatHttpClient::new().unwrap());
+        let manager = TokenManager::new("test_app_id", "test_app_secret", http_client);
+
+        {
+            let mut cache = manager.jsapi_ticket_cache.write().await;
+            *cache = Some(AccessToken::new("cached_ticket".to_string(), 7200));
+        }
+
+        // Served from cache without touching the network
+        let ticket = manager.get_jsapi_ticket().await.unwrap();
+        assert_eq!(ticket, "cached_ticket");
+
+        // Clearing the cache drops the ticket as well
+        manager.clear_cache().await;
+        assert!(cached_ticket(&manager.jsapi_ticket_cache).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cached_wx_card_ticket_and_js_sdk_config() {
+        let http_client = Arc::new(WeChatHttpClient::new().unwrap());
+        let manager = TokenManager::new("wx_app", "test_app_secret", http_client);
+
+        *manager.wx_card_ticket_cache.write().await =
+            Some(AccessToken::new("card_ticket".to_string(), 7200));
+        *manager.jsapi_ticket_cache.write().await =
+            Some(AccessToken::new("jsapi_ticket".to_string(), 7200));
+
+        // Each ticket has its own cache
+        assert_eq!(manager.get_wx_card_ticket().await.unwrap(), "card_ticket");
+        assert_eq!(manager.get_jsapi_ticket().await.unwrap(), "jsapi_ticket");
+
+        let config = manager
+            .js_sdk_config("https://example.com/page#top")
+            .await
+            .unwrap();
+        assert_eq!(config.app_id, "wx_app");
+        assert_eq!(config.nonce_str.len(), 16);
+        assert_eq!(
+            config,
+            JsSdkConfig::sign(
+                "wx_app",
+                "jsapi_ticket",
+                &config.nonce_str,
+                config.timestamp,
+                "https://example.com/page",
+            )
+        );
+
+        manager.clear_cache().await;
+        assert!(cached_ticket(&manager.wx_card_ticket_cache).await.is_none());
+    }
+
+    #[test]
+    fn test_js_sdk_signature() {
+        // Example from the JS-SDK documentation
+        let config = JsSdkConfig::sign(
+            "wx_app",
+            "sM4AOVdWfPE4DxkXGEs8VMCPGGVi4C3VM0P37wVUCFvkVAy_90u5h9nbSlYy3-Sl-HhTdfl2fzFy1AOcHKP7qg",
+            "Wm3WZYTPz0wzccnW",
+            1414587457,
+            "http://mp.weixin.qq.com?params=value",
+        );
+        assert_eq!(config.signature, "0f9de62fce790f9a083d5c99e95740ceb90c27ed");
+        assert_eq!(
+            serde_json::to_value(&config).unwrap(),
+            serde_json::json!({
+                "appId": "wx_app",
+                "timestamp": 1414587457,
+                "nonceStr": "Wm3WZYTPz0wzccnW",
+                "signature": "0f9de62fce790f9a083d5c99e95740ceb90c27ed",
+            })
+        );
+    }
+
     #[tokio::test]
     async fn test_token_info() {
         let http_client = Arc::new(WeChatHttpClient::new().unwrap());
@@ -0,0 +1,190 @@
+//! Synchronous client for code that doesn't run an async runtime (requires the
+//! `blocking` feature).
+//!
+//! [`WeChatClient`] owns a single-threaded Tokio runtime and runs each call of the async
+//! [`crate::WeChatClient`] to completion on it, so build scripts and hooks of static
+//! site generators can upload articles without setting up Tokio themselves:
+//!
+//! ```rust,no_run
+//! use wechat_pub_rs::blocking::WeChatClient;
+//!
+//! # fn main() -> wechat_pub_rs::Result<()> {
+//! let client = WeChatClient::new("your_app_id", "your_app_secret")?;
+//! let draft_id = client.upload("article.md")?;
+//! println!("Created draft {draft_id}");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Methods without a blocking counterpart are available through
+//! [`block_on`](WeChatClient::block_on). Like other blocking clients, it panics when
+//! called from within an async runtime; use the async client there.
+
+use crate::client::{DirUploadReport, UploadOptions, UploadReport};
+use crate::config::Config;
+use crate::error::Result;
+use crate::sync::{SyncOptions, SyncReport};
+use crate::upload::DraftInfo;
+use std::future::Future;
+use std::path::Path;
+use tokio::runtime::Runtime;
+
+/// Blocking wrapper of [`crate::WeChatClient`].
+#[derive(Debug)]
+pub struct WeChatClient {
+    inner: crate::WeChatClient,
+    runtime: Runtime,
+}
+
+impl WeChatClient {
+    /// Creates a new blocking client with app credentials, see
+    /// [`crate::WeChatClient::new`].
+    pub fn new(app_id: impl Into<String>, app_secret: impl Into<String>) -> Result<Self> {
+        Self::with_config(app_id, app_secret, Config::default())
+    }
+
+    /// Creates a new blocking client with app credentials and a custom configuration,
+    /// see [`crate::WeChatClient::with_config`].
+    pub fn with_config(
+        app_id: impl Into<String>,
+        app_secret: impl Into<String>,
+        config: Config,
+    ) -> Result<Self> {
+        let runtime = new_runtime()?;
+        let inner =
+            runtime.block_on(crate::WeChatClient::with_config(app_id, app_secret, config))?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// Wraps an async client, e.g. one created with hooks or an upload queue.
+    pub fn from_async(inner: crate::WeChatClient) -> Result<Self> {
+        Ok(Self {
+            inner,
+            runtime: new_runtime()?,
+        })
+    }
+
+    /// Gets the async client.
+    pub fn inner(&self) -> &crate::WeChatClient {
+        &self.inner
+    }
+
+    /// Runs a future to completion on the client's runtime, for calls without a
+    /// blocking counterpart:
+    ///
+    /// ```rust,no_run
+    /// # fn example(client: wechat_pub_rs::blocking::WeChatClient) -> wechat_pub_rs::Result<()> {
+    /// let overview = client.block_on(client.inner().account_overview())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Uploads a markdown file as a draft with default options, see
+    /// [`crate::WeChatClient::upload`].
+    pub fn upload(&self, markdown_path: &str) -> Result<String> {
+        self.block_on(self.inner.upload(markdown_path))
+    }
+
+    /// Uploads a markdown file with custom options, see
+    /// [`crate::WeChatClient::upload_with_options`].
+    pub fn upload_with_options(
+        &self,
+        markdown_path: &str,
+        options: UploadOptions,
+    ) -> Result<String> {
+        self.block_on(self.inner.upload_with_options(markdown_path, options))
+    }
+
+    /// Uploads a markdown file and reports what was uploaded, see
+    /// [`crate::WeChatClient::upload_with_report`].
+    pub fn upload_with_report(
+        &self,
+        markdown_path: &str,
+        options: UploadOptions,
+    ) -> Result<UploadReport> {
+        self.block_on(self.inner.upload_with_report(markdown_path, options))
+    }
+
+    /// Uploads every markdown file directly in a directory, see
+    /// [`crate::WeChatClient::upload_dir`].
+    pub fn upload_dir(
+        &self,
+        dir: impl AsRef<Path>,
+        options: UploadOptions,
+    ) -> Result<DirUploadReport> {
+        self.block_on(self.inner.upload_dir(dir, options))
+    }
+
+    /// Compares a directory of markdown files with the drafts, see
+    /// [`crate::WeChatClient::sync_dir`].
+    pub fn sync_dir(&self, dir: impl AsRef<Path>, options: SyncOptions) -> Result<SyncReport> {
+        self.block_on(self.inner.sync_dir(dir, options))
+    }
+
+    /// Gets a draft by media ID.
+    pub fn get_draft(&self, media_id: &str) -> Result<DraftInfo> {
+        self.block_on(self.inner.get_draft(media_id))
+    }
+
+    /// Updates an existing draft with new content, see
+    /// [`crate::WeChatClient::update_draft_with_options`].
+    pub fn update_draft_with_options(
+        &self,
+        media_id: &str,
+        markdown_path: &str,
+        options: UploadOptions,
+    ) -> Result<()> {
+        self.block_on(
+            self.inner
+                .update_draft_with_options(media_id, markdown_path, options),
+        )
+    }
+
+    /// Deletes a draft.
+    pub fn delete_draft(&self, media_id: &str) -> Result<()> {
+        self.block_on(self.inner.delete_draft(media_id))
+    }
+
+    /// Waits for running operations and saves the material cache, see
+    /// [`crate::WeChatClient::shutdown`].
+    pub fn shutdown(&self) -> Result<()> {
+        self.block_on(self.inner.shutdown())
+    }
+}
+
+fn new_runtime() -> Result<Runtime> {
+    Ok(tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocking_dry_run() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("cover.png"), b"cover").unwrap();
+        let markdown_path = temp_dir.path().join("post.md");
+        std::fs::write(
+            &markdown_path,
+            "---\ntitle: Blocking\ncover: cover.png\n---\n\n# Hello\n",
+        )
+        .unwrap();
+
+        let client =
+            WeChatClient::new("wx1234567890123456", "12345678901234567890123456789012").unwrap();
+        let report = client
+            .upload_with_report(
+                &markdown_path.display().to_string(),
+                UploadOptions::default().dry_run(true),
+            )
+            .unwrap();
+        assert!(report.dry_run.unwrap().html.contains("Hello"));
+        client.shutdown().unwrap();
+    }
+}
@@ -0,0 +1,319 @@
+//! Mass sending of drafts to followers.
+//!
+//! Publishing makes an article public, but followers only get it in their chat list
+//! when it is broadcast. A [`Broadcaster`] sends a draft to all followers, the followers
+//! with a tag (`message/mass/sendall`) or a list of followers (`message/mass/send`),
+//! looks up how a send job went and deletes sent articles again.
+//! [`WeChatClient`](crate::WeChatClient) exposes it as
+//! [`broadcast`](crate::WeChatClient::broadcast),
+//! [`broadcast_status`](crate::WeChatClient::broadcast_status) and
+//! [`delete_broadcast`](crate::WeChatClient::delete_broadcast):
+//!
+//! ```rust,no_run
+//! use wechat_pub_rs::broadcast::{BroadcastOptions, BroadcastTarget};
+//!
+//! # async fn example(client: wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+//! let draft_id = client.upload("article.md").await?;
+//! let job = client
+//!     .broadcast(
+//!         &draft_id,
+//!         BroadcastTarget::Tag(2),
+//!         BroadcastOptions::default().client_msg_id("article-2024-05-01"),
+//!     )
+//!     .await?;
+//! println!("Status: {:?}", client.broadcast_status(job.msg_id).await?);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Broadcasts count against a small quota (one a day for subscription accounts, four a
+//! month for service accounts); WeChat rejects sends beyond it with an API error. Every
+//! send carries a `clientmsgid`, generated per call if none is given, so that a request
+//! retried after a network error is not broadcast twice.
+
+use crate::auth::TokenManager;
+use crate::endpoints;
+use crate::error::{Result, WeChatError};
+use crate::http::{self, WeChatHttpClient, WeChatResponse};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::info;
+
+/// Followers a draft is sent to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BroadcastTarget {
+    /// All followers
+    All,
+    /// Followers with a tag, by tag ID
+    Tag(u64),
+    /// Followers by `openid`; WeChat requires 2 to 10,000 of them
+    OpenIds(Vec<String>),
+}
+
+/// Options of a broadcast.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BroadcastOptions {
+    /// Whether to send articles WeChat considers reprints anyway, as reprints of the
+    /// original (default: false, the send job stops)
+    pub send_ignore_reprint: bool,
+    /// ID making the send idempotent: WeChat rejects a second send with the same ID
+    /// within 24 hours (default: a new ID per call, which only deduplicates retries of
+    /// that call)
+    pub client_msg_id: Option<String>,
+}
+
+impl BroadcastOptions {
+    /// Sets whether articles considered reprints are sent anyway.
+    pub fn send_ignore_reprint(mut self, ignore: bool) -> Self {
+        self.send_ignore_reprint = ignore;
+        self
+    }
+
+    /// Sets the ID making the send idempotent, at most 64 characters.
+    pub fn client_msg_id(mut self, id: impl Into<String>) -> Self {
+        self.client_msg_id = Some(id.into());
+        self
+    }
+}
+
+/// A submitted send job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct BroadcastJob {
+    /// ID of the job, for [`Broadcaster::status`] and [`Broadcaster::delete`]
+    pub msg_id: u64,
+    /// ID of the sent articles in the statistics APIs
+    #[serde(default)]
+    pub msg_data_id: Option<u64>,
+}
+
+/// State of a send job, by WeChat's `msg_status`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BroadcastStatus {
+    /// WeChat is still sending (`SENDING`)
+    Sending,
+    /// The message was sent (`SEND_SUCCESS`)
+    Sent,
+    /// Sending failed (`SEND_FAIL`)
+    Failed,
+    /// The message was deleted after sending (`DELETE`)
+    Deleted,
+    /// A status this SDK doesn't know
+    Unknown(String),
+}
+
+impl From<&str> for BroadcastStatus {
+    fn from(status: &str) -> Self {
+        match status {
+            "SENDING" => BroadcastStatus::Sending,
+            "SEND_SUCCESS" => BroadcastStatus::Sent,
+            "SEND_FAIL" => BroadcastStatus::Failed,
+            "DELETE" => BroadcastStatus::Deleted,
+            other => BroadcastStatus::Unknown(other.to_string()),
+        }
+    }
+}
+
+/// Send job status response (`message/mass/get` endpoint).
+#[derive(Debug, Deserialize)]
+struct StatusResponse {
+    msg_status: String,
+}
+
+/// Sends drafts to followers and manages the send jobs.
+#[derive(Debug)]
+pub struct Broadcaster {
+    http_client: Arc<WeChatHttpClient>,
+    token_manager: Arc<TokenManager>,
+}
+
+impl Broadcaster {
+    /// Creates a new broadcaster.
+    pub fn new(http_client: Arc<WeChatHttpClient>, token_manager: Arc<TokenManager>) -> Self {
+        Self {
+            http_client,
+            token_manager,
+        }
+    }
+
+    /// Sends a draft to followers.
+    ///
+    /// Only submits the job: WeChat sends the message in the background, see
+    /// [`status`](Self::status).
+    pub async fn send(
+        &self,
+        media_id: &str,
+        target: BroadcastTarget,
+        options: BroadcastOptions,
+    ) -> Result<BroadcastJob> {
+        info!("Broadcasting draft {media_id} to {target:?}");
+
+        let (endpoint, request) = send_request(media_id, &target, &options)?;
+        let access_token = self.token_manager.get_access_token().await?;
+        let response = self
+            .http_client
+            .post_json_with_token(endpoint.path, &access_token, &request)
+            .await?;
+
+        let send_response: WeChatResponse<BroadcastJob> = http::read_json(response).await?;
+        let job = send_response.into_result()?;
+        info!("Submitted broadcast job {}", job.msg_id);
+        Ok(job)
+    }
+
+    /// Gets the state of a send job.
+    pub async fn status(&self, msg_id: u64) -> Result<BroadcastStatus> {
+        let request = serde_json::json!({ "msg_id": msg_id.to_string() });
+        let access_token = self.token_manager.get_access_token().await?;
+        let response = self
+            .http_client
+            .post_json_with_token(endpoints::MESSAGE_GET.path, &access_token, &request)
+            .await?;
+
+        let status_response: WeChatResponse<StatusResponse> = http::read_json(response).await?;
+        Ok(BroadcastStatus::from(
+            status_response.into_result()?.msg_status.as_str(),
+        ))
+    }
+
+    /// Deletes a sent message, so followers see it as deleted.
+    ///
+    /// `article` is the 1-based position of a single article to delete; `None` deletes
+    /// all articles of the message.
+    pub async fn delete(&self, msg_id: u64, article: Option<u32>) -> Result<()> {
+        info!("Deleting broadcast {msg_id}");
+
+        let request = serde_json::json!({ "msg_id": msg_id, "article_idx": article.unwrap_or(0) });
+        let access_token = self.token_manager.get_access_token().await?;
+        let response = self
+            .http_client
+            .post_json_with_token(endpoints::MESSAGE_DELETE.path, &access_token, &request)
+            .await?;
+
+        let delete_response: WeChatResponse<serde_json::Value> = http::read_json(response).await?;
+        delete_response.into_result()?;
+        Ok(())
+    }
+}
+
+/// Builds the endpoint and request body of a send job.
+fn send_request(
+    media_id: &str,
+    target: &BroadcastTarget,
+    options: &BroadcastOptions,
+) -> Result<(endpoints::Endpoint, serde_json::Value)> {
+    if let BroadcastTarget::OpenIds(openids) = target
+        && !(2..=10_000).contains(&openids.len())
+    {
+        return Err(WeChatError::config_error(format!(
+            "Broadcasts to followers by openid need 2 to 10,000 of them, got {}",
+            openids.len()
+        )));
+    }
+
+    let client_msg_id = options
+        .client_msg_id
+        .clone()
+        .unwrap_or_else(|| generate_client_msg_id(media_id, target));
+    let mut request = serde_json::json!({
+        "mpnews": { "media_id": media_id },
+        "msgtype": "mpnews",
+        "send_ignore_reprint": u8::from(options.send_ignore_reprint),
+        "clientmsgid": client_msg_id,
+    });
+
+    let endpoint = match target {
+        BroadcastTarget::All => {
+            request["filter"] = serde_json::json!({ "is_to_all": true });
+            endpoints::MESSAGE_SENDALL
+        }
+        BroadcastTarget::Tag(tag_id) => {
+            request["filter"] = serde_json::json!({ "is_to_all": false, "tag_id": tag_id });
+            endpoints::MESSAGE_SENDALL
+        }
+        BroadcastTarget::OpenIds(openids) => {
+            request["touser"] = openids.clone().into();
+            endpoints::MESSAGE_SEND
+        }
+    };
+    Ok((endpoint, request))
+}
+
+/// Generates a `clientmsgid` for a single send from the draft, the target and a random
+/// nonce, 32 hex characters.
+fn generate_client_msg_id(media_id: &str, target: &BroadcastTarget) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(media_id.as_bytes());
+    hasher.update(format!("{target:?}").as_bytes());
+    hasher.update(&fastrand::u64(..).to_le_bytes());
+    hasher.finalize().to_hex()[..32].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_request() {
+        let (endpoint, request) = send_request(
+            "draft_id",
+            &BroadcastTarget::Tag(2),
+            &BroadcastOptions::default().client_msg_id("post-1"),
+        )
+        .unwrap();
+        assert_eq!(endpoint, endpoints::MESSAGE_SENDALL);
+        assert_eq!(
+            request,
+            serde_json::json!({
+                "filter": { "is_to_all": false, "tag_id": 2 },
+                "mpnews": { "media_id": "draft_id" },
+                "msgtype": "mpnews",
+                "send_ignore_reprint": 0,
+                "clientmsgid": "post-1",
+            })
+        );
+
+        let openids = vec!["o1".to_string(), "o2".to_string()];
+        let (endpoint, request) = send_request(
+            "draft_id",
+            &BroadcastTarget::OpenIds(openids),
+            &BroadcastOptions::default().send_ignore_reprint(true),
+        )
+        .unwrap();
+        assert_eq!(endpoint, endpoints::MESSAGE_SEND);
+        assert_eq!(request["touser"], serde_json::json!(["o1", "o2"]));
+        assert_eq!(request["send_ignore_reprint"], 1);
+        assert!(request.get("filter").is_none());
+
+        // Without an ID, every call gets its own so that retries are deduplicated
+        let generated = request["clientmsgid"].as_str().unwrap();
+        assert_eq!(generated.len(), 32);
+        let (_, other) = send_request(
+            "draft_id",
+            &BroadcastTarget::All,
+            &BroadcastOptions::default(),
+        )
+        .unwrap();
+        assert_ne!(other["clientmsgid"].as_str().unwrap(), generated);
+
+        for count in [1, 10_001] {
+            let openids = vec!["o".to_string(); count];
+            assert!(
+                send_request(
+                    "draft_id",
+                    &BroadcastTarget::OpenIds(openids),
+                    &BroadcastOptions::default()
+                )
+                .is_err()
+            );
+        }
+
+        let job: BroadcastJob =
+            serde_json::from_str(r#"{"msg_id":34182,"msg_data_id":206227730}"#).unwrap();
+        assert_eq!(job.msg_data_id, Some(206227730));
+        assert_eq!(BroadcastStatus::from("SEND_SUCCESS"), BroadcastStatus::Sent);
+        assert_eq!(
+            BroadcastStatus::from("PAUSED"),
+            BroadcastStatus::Unknown("PAUSED".to_string())
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Size budget of long articles.
+//!
+//! WeChat rejects article content of 20,000 characters or more, or over 1 MB, and the
+//! inline styles added by themes make the rendered HTML much longer than the markdown.
+//! A [`SizeReport`] measures the rendered article section by section (see
+//! [`MarkdownContent::sections`](crate::markdown::MarkdownContent::sections)) and
+//! suggests where to split it into a multi-article draft:
+//!
+//! ```rust,no_run
+//! use wechat_pub_rs::UploadOptions;
+//!
+//! # async fn example(client: wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+//! let report = client
+//!     .analyze_size("long-read.md", &UploadOptions::default())
+//!     .await?;
+//! println!("{report}");
+//!
+//! // Or split automatically when the article doesn't fit
+//! let options = UploadOptions::default().split_long_articles(true);
+//! client.upload_with_options("long-read.md", options).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ops::Range;
+
+/// Maximum number of characters of an article's content.
+pub const MAX_CONTENT_CHARS: usize = 20_000;
+
+/// Maximum size of an article's content in bytes.
+pub const MAX_CONTENT_BYTES: usize = 1024 * 1024;
+
+/// Size limits of an article's rendered content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentBudget {
+    /// Maximum number of characters, exclusive
+    pub max_chars: usize,
+    /// Maximum number of bytes, inclusive
+    pub max_bytes: usize,
+}
+
+impl Default for ContentBudget {
+    fn default() -> Self {
+        Self {
+            max_chars: MAX_CONTENT_CHARS,
+            max_bytes: MAX_CONTENT_BYTES,
+        }
+    }
+}
+
+impl ContentBudget {
+    /// Checks whether content of the given size fits the budget.
+    pub fn fits(&self, chars: usize, bytes: usize) -> bool {
+        chars < self.max_chars && bytes <= self.max_bytes
+    }
+
+    /// Checks whether rendered HTML fits the budget.
+    pub fn fits_html(&self, html: &str) -> bool {
+        self.fits(html.chars().count(), html.len())
+    }
+}
+
+/// Rendered size of one section of an article.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionSize {
+    /// Heading of the section, `None` for the text before the first heading
+    pub title: Option<String>,
+    /// Number of characters of the rendered section
+    pub chars: usize,
+    /// Number of bytes of the rendered section
+    pub bytes: usize,
+}
+
+/// Rendered size of an article, in total and per section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeReport {
+    /// Budget the article is measured against
+    pub budget: ContentBudget,
+    /// Number of characters of the whole rendered article
+    pub chars: usize,
+    /// Number of bytes of the whole rendered article
+    pub bytes: usize,
+    /// Sizes of the sections, in document order
+    pub sections: Vec<SectionSize>,
+}
+
+impl SizeReport {
+    /// Checks whether the article is too large for a single draft article.
+    pub fn exceeds_budget(&self) -> bool {
+        !self.budget.fits(self.chars, self.bytes)
+    }
+
+    /// Groups consecutive sections into parts that each fit the budget.
+    ///
+    /// Every part is also charged the size the article has beyond its sections (the
+    /// template around the content). A section too large on its own gets a part of its
+    /// own, which still exceeds the budget.
+    pub fn suggested_parts(&self) -> Vec<Range<usize>> {
+        let (section_chars, section_bytes) =
+            self.sections.iter().fold((0, 0), |(chars, bytes), s| {
+                (chars + s.chars, bytes + s.bytes)
+            });
+        let overhead_chars = self.chars.saturating_sub(section_chars);
+        let overhead_bytes = self.bytes.saturating_sub(section_bytes);
+
+        let mut parts = Vec::new();
+        let mut start = 0;
+        let (mut chars, mut bytes) = (overhead_chars, overhead_bytes);
+        for (index, section) in self.sections.iter().enumerate() {
+            let fits = self
+                .budget
+                .fits(chars + section.chars, bytes + section.bytes);
+            if !fits && index > start {
+                parts.push(start..index);
+                start = index;
+                (chars, bytes) = (overhead_chars, overhead_bytes);
+            }
+            chars += section.chars;
+            bytes += section.bytes;
+        }
+        if start < self.sections.len() {
+            parts.push(start..self.sections.len());
+        }
+        parts
+    }
+
+    /// Describes how to bring the article within budget, e.g. for error messages.
+    pub fn hint(&self) -> String {
+        if !self.exceeds_budget() {
+            return "the article fits in a single draft article".to_string();
+        }
+
+        let parts = self.suggested_parts();
+        if parts.len() < 2 {
+            return format!(
+                "the article has no headings to split at; shorten it below {} characters",
+                self.budget.max_chars
+            );
+        }
+        let headings: Vec<_> = parts[1..]
+            .iter()
+            .map(|part| {
+                let title = self.sections[part.start].title.as_deref().unwrap_or("?");
+                format!("'{title}'")
+            })
+            .collect();
+        format!(
+            "split it into {} articles before {}",
+            parts.len(),
+            headings.join(", ")
+        )
+    }
+}
+
+impl std::fmt::Display for SizeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Article: {} characters, {} bytes (limit {} characters, {} bytes)",
+            self.chars, self.bytes, self.budget.max_chars, self.budget.max_bytes
+        )?;
+        for section in &self.sections {
+            writeln!(
+                f,
+                "  {}: {} characters",
+                section.title.as_deref().unwrap_or("(before first heading)"),
+                section.chars
+            )?;
+        }
+        write!(f, "Hint: {}", self.hint())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(title: &str, chars: usize) -> SectionSize {
+        SectionSize {
+            title: Some(title.to_string()),
+            chars,
+            bytes: chars,
+        }
+    }
+
+    #[test]
+    fn test_suggested_parts() {
+        let budget = ContentBudget {
+            max_chars: 100,
+            max_bytes: 1000,
+        };
+        let report = SizeReport {
+            budget,
+            chars: 160,
+            bytes: 160,
+            sections: vec![
+                section("A", 40),
+                section("B", 40),
+                section("C", 30),
+                section("D", 40),
+            ],
+        };
+
+        // 10 characters of template overhead go with every part
+        assert!(report.exceeds_budget());
+        assert_eq!(report.suggested_parts(), vec![0..2, 2..4]);
+        assert_eq!(report.hint(), "split it into 2 articles before 'C'");
+        assert!(report.to_string().contains("  B: 40 characters"));
+
+        // A single oversized section can't be split further
+        let report = SizeReport {
+            budget,
+            chars: 150,
+            bytes: 150,
+            sections: vec![section("A", 150)],
+        };
+        assert_eq!(report.suggested_parts(), vec![0..1]);
+        assert!(report.hint().contains("no headings to split at"));
+
+        let report = SizeReport {
+            budget,
+            chars: 50,
+            bytes: 50,
+            sections: vec![section("A", 50)],
+        };
+        assert!(!report.exceeds_budget());
+        assert!(budget.fits_html("短文"));
+    }
+}
@@ -1,57 +1,206 @@
 //! Main WeChat client implementation.
 
-use tracing::{debug, info};
+use chrono::{DateTime, Utc};
+use tracing::{Instrument, Span, debug, info, info_span, warn};
 
-use crate::auth::TokenManager;
+use crate::analytics::{
+    AnalyticsManager, ArticleSummary, ArticleTotal, DateRange, UserCumulate, UserRead, UserSummary,
+};
+use crate::assets::AssetStore;
+use crate::auth::{JsSdkConfig, TokenManager};
+use crate::broadcast::{
+    BroadcastJob, BroadcastOptions, BroadcastStatus, BroadcastTarget, Broadcaster,
+};
+use crate::budget::{ContentBudget, SectionSize, SizeReport};
+use crate::comments::{CommentFilter, CommentManager, CommentPage, CommentTarget};
+use crate::config::Config;
+use crate::diff::{self, DraftDiff};
+use crate::endpoints;
 use crate::error::{Result, WeChatError};
-use crate::http::WeChatHttpClient;
-use crate::markdown::{MarkdownContent, MarkdownParser};
-use crate::mermaid::MermaidProcessor;
-use crate::theme::ThemeManager;
-use crate::upload::{Article, DraftInfo, DraftManager, ImageUploader};
-use crate::utils;
+use crate::health::{HealthDiagnosis, HealthReport};
+use crate::highlight::{HighlightRegistry, SyntaxRegistry};
+use crate::http::{self, IpListResponse, WeChatHttpClient, WeChatResponse};
+use crate::image_processing::{ImageFormatPolicy, ImageProcessing};
+use crate::markdown::{self, AuthorFormat, DateFormat, ImageRef, MarkdownContent, MarkdownParser};
+use crate::media_map::MediaMap;
+use crate::mermaid::{MermaidNaming, MermaidOutput, MermaidProcessor};
+use crate::oauth::OAuthManager;
+use crate::overview::{self, AccountOverview, CountResponse, MaterialCounts, QuotaResponse};
+use crate::pipeline::ArticlePipeline;
+use crate::prepare::{PlannedImage, PreparedUpload, UploadPlan, ValidationReport};
+use crate::publish::{
+    PublishHistoryEntry, PublishStatus, PublishStatusResponse, PublishSubmitResponse,
+    PublishedArticle, PublishedArticleResponse, PublishedListResponse,
+};
+use crate::queue::OperationQueue;
+use crate::sanitize::HtmlNormalization;
+use crate::slug::{SLUG_KEY, SlugStrategy, slugify};
+use crate::state::{self, ConflictReport, FileStatus, PublishState};
+use crate::subscribe::{SubscribeMessage, SubscribeMessenger, SubscribeTemplate};
+use crate::sync::{self, LocalArticle, SyncOptions, SyncReport, SyncState};
+use crate::temp::TempFiles;
+use crate::theme::{self, ThemeId, ThemeInfo, ThemeManager};
+use crate::traits::{ContentRenderer, HttpClient, TokenProvider, UploadHooks};
+use crate::upload::{
+    Article, CoverUpload, DeleteOptions, DeletionReport, DraftInfo, DraftManager,
+    ExistingDraftPolicy, ImageUploader, MaterialItem, MaterialNaming, SyncedDraft, UploadResult,
+};
+use crate::upload_queue::{self, QueuedImage, QueuedPayload, QueuedUpload, UploadQueue};
+use crate::utils::{self, PathPolicy};
+use futures::StreamExt;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::task::JoinHandle;
+
+/// Function applied to the rendered HTML before it is sent to WeChat.
+///
+/// Cheap to clone; the function is shared between clones.
+#[derive(Clone)]
+pub struct HtmlTransform(Arc<dyn Fn(String) -> Result<String> + Send + Sync>);
+
+impl HtmlTransform {
+    /// Wraps a transform function.
+    pub fn new(transform: impl Fn(String) -> Result<String> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(transform))
+    }
+
+    /// Applies the transform to rendered HTML.
+    pub fn apply(&self, html: String) -> Result<String> {
+        (self.0)(html)
+    }
+}
+
+impl std::fmt::Debug for HtmlTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("HtmlTransform(..)")
+    }
+}
 
 /// Upload options for customizing the upload behavior.
 #[derive(Debug, Clone)]
 pub struct UploadOptions {
-    /// Theme name to use for rendering
-    pub theme: String,
+    /// Theme to use for rendering
+    pub theme: ThemeId,
     /// Custom title (overrides extracted title)
     pub title: Option<String>,
     /// Custom author (overrides extracted author)
     pub author: Option<String>,
+    /// How multiple frontmatter authors are joined into the article's author
+    pub author_format: AuthorFormat,
+    /// Article summary shown in share cards, overriding the frontmatter and the
+    /// generated summary
+    pub digest: Option<String>,
+    /// How the frontmatter `date` is written in the article; `None` for the format of
+    /// the client's [`Locale`](crate::locale::Locale)
+    pub date_format: Option<DateFormat>,
     /// Path to cover image file
     pub cover_image: Option<String>,
     /// Whether to show cover image in content
     pub show_cover: bool,
+    /// Whether the template shows the cover image above the content
+    pub hero_image: bool,
+    /// Whether the template shows an author and date row above the content
+    pub meta_row: bool,
+    /// Whether the template shows the frontmatter tags above the content
+    pub tags_row: bool,
     /// Whether to enable comments
     pub enable_comments: bool,
     /// Whether only fans can comment
     pub fans_only_comments: bool,
-    /// Source URL for the article
+    /// Source URL for the article, where `{slug}` is replaced with the document slug
     pub source_url: Option<String>,
+    /// Overall deadline for the whole upload operation
+    pub deadline: Option<Duration>,
+    /// Maximum time spent on a single image (download and upload)
+    pub image_timeout: Option<Duration>,
+    /// Processing applied to images before upload (e.g. EXIF stripping)
+    pub image_processing: ImageProcessing,
+    /// Image formats accepted for upload, and what happens to the others
+    pub image_formats: ImageFormatPolicy,
+    /// How uploaded images are named in the media library
+    pub material_naming: MaterialNaming,
+    /// Whether to keep processed images and Mermaid charts in `.wechat-pub/assets`
+    pub cache_assets: bool,
+    /// Where generated Mermaid chart images are written
+    pub mermaid_output: MermaidOutput,
+    /// How generated Mermaid chart images are named
+    pub mermaid_naming: MermaidNaming,
+    /// Whether previews embed Mermaid charts as data URIs instead of linking the files
+    pub inline_diagrams: bool,
+    /// Transform applied to the rendered HTML before the draft is created
+    pub html_transform: Option<HtmlTransform>,
+    /// Whether to record uploads in the local state file (`.wechat-pub/state.json`)
+    pub track_state: bool,
+    /// Whether to overwrite a draft even if it was edited remotely since the last sync
+    pub force: bool,
+    /// What to do when a draft with the same title exists
+    pub on_existing_draft: ExistingDraftPolicy,
+    /// Whether to write the draft's media ID and URL back into the file's frontmatter
+    pub write_back: bool,
+    /// Whether to look up the link of a newly created or updated draft for
+    /// [`UploadReport::draft_url`] and the logs, at the cost of a `draft/get` call
+    pub lookup_draft_url: bool,
+    /// Character normalization applied to the rendered HTML
+    pub normalization: HtmlNormalization,
+    /// Whether an unknown `code:` theme is an error instead of falling back to the
+    /// default; `None` uses [`RenderConfig::strict_code_themes`](crate::config::RenderConfig::strict_code_themes)
+    pub strict_code_theme: Option<bool>,
+    /// Size limits the rendered article is checked against before the draft is created
+    pub content_budget: ContentBudget,
+    /// Whether an article over the budget is split at its headings into a multi-article
+    /// draft instead of failing with [`WeChatError::ContentTooLarge`]
+    pub split_long_articles: bool,
+    /// Whether to render the article and plan its upload without calling WeChat
+    pub dry_run: bool,
 }
 
 impl Default for UploadOptions {
     fn default() -> Self {
         Self {
-            theme: "default".to_string(),
+            theme: ThemeId::default(),
             title: None,
             author: None,
+            author_format: AuthorFormat::default(),
+            digest: None,
+            date_format: None,
             cover_image: None,
             show_cover: true,
+            hero_image: false,
+            meta_row: false,
+            tags_row: false,
             enable_comments: false,
             fans_only_comments: false,
             source_url: None,
+            deadline: None,
+            image_timeout: None,
+            image_processing: ImageProcessing::default(),
+            image_formats: ImageFormatPolicy::default(),
+            material_naming: MaterialNaming::default(),
+            cache_assets: false,
+            mermaid_output: MermaidOutput::default(),
+            mermaid_naming: MermaidNaming::default(),
+            inline_diagrams: true,
+            html_transform: None,
+            track_state: false,
+            force: false,
+            on_existing_draft: ExistingDraftPolicy::default(),
+            write_back: false,
+            lookup_draft_url: false,
+            normalization: HtmlNormalization::default(),
+            strict_code_theme: None,
+            content_budget: ContentBudget::default(),
+            split_long_articles: false,
+            dry_run: false,
         }
     }
 }
 
 impl UploadOptions {
     /// Creates upload options with a specific theme.
-    pub fn with_theme(theme: impl Into<String>) -> Self {
+    pub fn with_theme(theme: impl Into<ThemeId>) -> Self {
         Self {
             theme: theme.into(),
             ..Default::default()
@@ -70,18 +219,72 @@ impl UploadOptions {
         self
     }
 
-    /// Sets the cover image path.
+    /// Sets the summary shown in share cards.
+    ///
+    /// Takes precedence over the frontmatter `digest:` and `description:` entries and
+    /// the summary generated from the first paragraph.
+    pub fn digest(mut self, digest: impl Into<String>) -> Self {
+        self.digest = Some(digest.into());
+        self
+    }
+
+    /// Sets how multiple frontmatter authors are joined.
+    pub fn author_format(mut self, format: AuthorFormat) -> Self {
+        self.author_format = format;
+        self
+    }
+
+    /// Sets how the frontmatter `date` is written in the article, e.g.
+    /// [`DateFormat::EnglishLong`] for `May 1, 2024` instead of `2024年5月1日`,
+    /// overriding the client's locale.
+    pub fn date_format(mut self, format: DateFormat) -> Self {
+        self.date_format = Some(format);
+        self
+    }
+
+    /// Sets the cover image path, overriding the frontmatter `cover:` entry.
+    ///
+    /// Relative paths are resolved against the markdown file's directory.
     pub fn cover_image(mut self, path: impl Into<String>) -> Self {
         self.cover_image = Some(path.into());
         self
     }
 
+    /// Sets the cover image from a filesystem path, e.g. one passed on the command line,
+    /// like [`cover_image`](Self::cover_image).
+    pub fn cover_path(self, path: impl AsRef<Path>) -> Self {
+        self.cover_image(path.as_ref().to_string_lossy())
+    }
+
     /// Sets whether to show the cover image in content.
     pub fn show_cover(mut self, show: bool) -> Self {
         self.show_cover = show;
         self
     }
 
+    /// Sets whether the article template shows the cover image as a hero image above
+    /// the content, like `hero: true` in the frontmatter.
+    ///
+    /// WeChat shows the cover on its own unless [`show_cover`](Self::show_cover) is off.
+    pub fn hero_image(mut self, enable: bool) -> Self {
+        self.hero_image = enable;
+        self
+    }
+
+    /// Sets whether the article template shows an author and date row above the
+    /// content, like `meta_row: true` in the frontmatter.
+    pub fn meta_row(mut self, enable: bool) -> Self {
+        self.meta_row = enable;
+        self
+    }
+
+    /// Sets whether the article template shows the frontmatter `tags` above the
+    /// content, like `tags_row: true` in the frontmatter.
+    pub fn tags_row(mut self, enable: bool) -> Self {
+        self.tags_row = enable;
+        self
+    }
+
     /// Sets comment options.
     pub fn comments(mut self, enable: bool, fans_only: bool) -> Self {
         self.enable_comments = enable;
@@ -90,12 +293,428 @@ impl UploadOptions {
     }
 
     /// Sets the source URL.
+    ///
+    /// `{slug}` is replaced with the document slug, picked by the configured
+    /// [`SlugStrategy`](crate::slug::SlugStrategy), e.g.
+    /// `https://blog.example.com/posts/{slug}`. A `slug:` in the frontmatter is also
+    /// stored in the URL fragment (`#wechat-slug=...`), so later uploads find the draft
+    /// by slug after the title changed.
     pub fn source_url(mut self, url: impl Into<String>) -> Self {
         self.source_url = Some(url.into());
         self
     }
+
+    /// Sets an overall deadline; the operation fails with a timeout once it passes.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the maximum time spent on a single image.
+    pub fn image_timeout(mut self, timeout: Duration) -> Self {
+        self.image_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the processing applied to images before upload, e.g.
+    /// `ImageProcessing::default().strip_metadata(true)` to remove EXIF data from phone
+    /// photos and bake in their orientation.
+    pub fn image_processing(mut self, processing: ImageProcessing) -> Self {
+        self.image_processing = processing;
+        self
+    }
+
+    /// Sets the image formats accepted for upload, e.g.
+    /// `ImageFormatPolicy::default().forbid(ImageFormat::Bmp)` to reject BMP images, or
+    /// with `.convert_to(ImageFormat::Jpeg)` to convert them instead.
+    pub fn image_formats(mut self, policy: ImageFormatPolicy) -> Self {
+        self.image_formats = policy;
+        self
+    }
+
+    /// Sets how uploaded images are named in the media library (default: after their
+    /// content hash), e.g. [`MaterialNaming::OriginalName`] to keep the library readable.
+    pub fn material_naming(mut self, naming: MaterialNaming) -> Self {
+        self.material_naming = naming;
+        self
+    }
+
+    /// Sets whether to keep processed images and generated Mermaid charts in a
+    /// content-addressed store (`.wechat-pub/assets` next to the markdown file), so
+    /// repeated runs reuse them instead of processing them again.
+    pub fn cache_assets(mut self, enable: bool) -> Self {
+        self.cache_assets = enable;
+        self
+    }
+
+    /// Sets whether an unknown `code:` theme in the frontmatter fails with
+    /// [`WeChatError::CodeThemeNotFound`] instead of falling back to the default
+    /// highlight theme, overriding the client configuration.
+    pub fn strict_code_theme(mut self, strict: bool) -> Self {
+        self.strict_code_theme = Some(strict);
+        self
+    }
+
+    /// Sets the size limits the rendered article is checked against, e.g. to leave
+    /// room for edits in the WeChat editor.
+    pub fn content_budget(mut self, budget: ContentBudget) -> Self {
+        self.content_budget = budget;
+        self
+    }
+
+    /// Sets whether an article over the content budget is split at its headings into a
+    /// multi-article draft, with the parts titled "Title (1/3)" and so on.
+    pub fn split_long_articles(mut self, enable: bool) -> Self {
+        self.split_long_articles = enable;
+        self
+    }
+
+    /// Sets where generated Mermaid chart images are written, e.g.
+    /// [`MermaidOutput::Temp`] to keep them out of the source tree.
+    pub fn mermaid_output(mut self, output: MermaidOutput) -> Self {
+        self.mermaid_output = output;
+        self
+    }
+
+    /// Sets how generated Mermaid chart images are named.
+    pub fn mermaid_naming(mut self, naming: MermaidNaming) -> Self {
+        self.mermaid_naming = naming;
+        self
+    }
+
+    /// Sets whether [`render_preview`](WeChatClient::render_preview) and
+    /// [`render_to_file`](WeChatClient::render_to_file) embed Mermaid charts as base64
+    /// data URIs (the default), making the preview a single self-contained HTML file.
+    ///
+    /// Embedded charts are written to [`MermaidOutput::Temp`] unless another
+    /// [`mermaid_output`](Self::mermaid_output) is set, leaving the source tree untouched.
+    pub fn inline_diagrams(mut self, enable: bool) -> Self {
+        self.inline_diagrams = enable;
+        self
+    }
+
+    /// Sets a transform applied to the rendered HTML after rendering and before the
+    /// draft is created, e.g. to inject tracking pixels or enforce content policies.
+    ///
+    /// Returning an error aborts the upload.
+    pub fn html_transform(
+        mut self,
+        transform: impl Fn(String) -> Result<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.html_transform = Some(HtmlTransform::new(transform));
+        self
+    }
+
+    /// Sets whether to record uploads in the local state file.
+    ///
+    /// With state tracking on, re-running an upload whose content is unchanged since a
+    /// completed previous run reuses that draft instead of creating a duplicate.
+    pub fn track_state(mut self, enable: bool) -> Self {
+        self.track_state = enable;
+        self
+    }
+
+    /// Sets whether to overwrite drafts edited remotely since the last sync.
+    ///
+    /// With state tracking on, updating a draft that was changed in the WeChat editor
+    /// after the last upload fails with [`WeChatError::DraftConflict`] unless forced.
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Sets what to do when a draft with the same title exists: update it (the default),
+    /// create a new draft anyway, or fail with [`WeChatError::DraftExists`].
+    pub fn on_existing_draft(mut self, policy: ExistingDraftPolicy) -> Self {
+        self.on_existing_draft = policy;
+        self
+    }
+
+    /// Sets whether to write `wechat_media_id` and `wechat_url` back into the frontmatter.
+    ///
+    /// Uploads of a file whose frontmatter has a `wechat_media_id` update that draft
+    /// directly instead of looking for a draft with the same title.
+    pub fn write_back(mut self, enable: bool) -> Self {
+        self.write_back = enable;
+        self
+    }
+
+    /// Sets whether to look up the draft's link after writing it.
+    ///
+    /// Drafts that are looked up anyway (write-back, state tracking or a known media
+    /// ID) always report their link.
+    pub fn lookup_draft_url(mut self, enable: bool) -> Self {
+        self.lookup_draft_url = enable;
+        self
+    }
+
+    /// Sets the character normalization applied to the rendered HTML.
+    ///
+    /// By default the HTML is normalized to NFC and invisible characters the WeChat
+    /// editor would mangle are encoded as entities.
+    pub fn normalization(mut self, normalization: HtmlNormalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Sets whether to render the article without uploading anything.
+    ///
+    /// A dry run parses the markdown, renders Mermaid charts and the theme and finds
+    /// the images, but makes no WeChat API calls: the report's
+    /// [`dry_run`](UploadReport::dry_run) plan has the final HTML and the images that
+    /// would be uploaded. State tracking, write-back and the upload queue are skipped,
+    /// and the hooks aren't notified. Only calls returning an [`UploadReport`] support
+    /// dry runs; those returning the draft ID fail with [`WeChatError::Config`].
+    pub fn dry_run(mut self, enable: bool) -> Self {
+        self.dry_run = enable;
+        self
+    }
+}
+
+/// Summary of a completed upload.
+#[derive(Debug, Clone)]
+pub struct UploadReport {
+    /// Media ID of the created draft
+    pub draft_id: String,
+    /// Upload results for every image referenced in the content, in document order
+    pub images: Vec<UploadResult>,
+    /// Media ID of the cover image
+    pub cover_media_id: String,
+    /// Whether the cover reused an existing material instead of being uploaded again
+    pub cover_reused: bool,
+    /// Link to the draft on mp.weixin.qq.com, for opening it from CI logs before
+    /// finishing it in the editor; `None` if the draft wasn't looked up (see
+    /// [`UploadOptions::lookup_draft_url`]) or WeChat didn't report one
+    pub draft_url: Option<String>,
+    /// Whether the content already matched a draft created by a previous run, so
+    /// nothing was uploaded (only possible with [`UploadOptions::track_state`])
+    pub unchanged: bool,
+    /// What the upload would have done, for uploads with [`UploadOptions::dry_run`],
+    /// which leave the draft ID and cover media ID empty
+    pub dry_run: Option<UploadPlan>,
+}
+
+/// Rejects [dry runs](UploadOptions::dry_run) in calls that return the draft ID, as a
+/// dry run creates no draft.
+pub(crate) fn reject_dry_run(options: &UploadOptions) -> Result<()> {
+    if options.dry_run {
+        return Err(WeChatError::config_error(
+            "Dry runs create no draft; use upload_with_report and read its dry_run plan",
+        ));
+    }
+    Ok(())
+}
+
+/// Outcome of a pass over the upload queue, see
+/// [`WeChatClient::drain_upload_queue`].
+#[derive(Debug, Default)]
+pub struct DrainReport {
+    /// Uploads that were sent, with their reports
+    pub uploaded: Vec<(QueuedUpload, UploadReport)>,
+    /// Uploads that failed permanently and were moved to the failed entries
+    pub failed: Vec<(QueuedUpload, WeChatError)>,
+    /// Number of uploads left in the queue, waiting for their next attempt
+    pub pending: usize,
+}
+
+/// Outcome of uploading the markdown files of a directory, see
+/// [`WeChatClient::upload_dir`].
+#[derive(Debug, Default)]
+pub struct DirUploadReport {
+    /// Files that were uploaded, with their reports, sorted by path
+    pub uploaded: Vec<(PathBuf, UploadReport)>,
+    /// Files that failed, with their errors, sorted by path
+    pub failed: Vec<(PathBuf, WeChatError)>,
+}
+
+impl DirUploadReport {
+    /// Whether every file was uploaded.
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// Builder of a [`WeChatClient`] with custom components, see [`WeChatClient::builder`].
+///
+/// Components that aren't set are the built-in ones: reqwest for HTTP, the token
+/// endpoint with the app credentials for tokens, and the themes for rendering.
+#[derive(Default)]
+pub struct WeChatClientBuilder {
+    app_id: String,
+    app_secret: String,
+    config: Config,
+    http_client: Option<Arc<dyn HttpClient>>,
+    token_provider: Option<Arc<dyn TokenProvider>>,
+    renderer: Option<Arc<dyn ContentRenderer>>,
+}
+
+impl std::fmt::Debug for WeChatClientBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeChatClientBuilder")
+            .field("app_id", &self.app_id)
+            .field("config", &self.config)
+            .field("http_client", &self.http_client.is_some())
+            .field("token_provider", &self.token_provider.is_some())
+            .field("renderer", &self.renderer.is_some())
+            .finish()
+    }
+}
+
+impl WeChatClientBuilder {
+    /// Sets the app credentials, required unless a token provider is set.
+    pub fn credentials(mut self, app_id: impl Into<String>, app_secret: impl Into<String>) -> Self {
+        self.app_id = app_id.into();
+        self.app_secret = app_secret.into();
+        self
+    }
+
+    /// Sets the configuration, see [`WeChatClient::with_config`].
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sends API requests and downloads through a custom HTTP client, see
+    /// [`WeChatHttpClient::with_transport`].
+    pub fn http_client(mut self, http_client: impl HttpClient + 'static) -> Self {
+        self.http_client = Some(Arc::new(http_client));
+        self
+    }
+
+    /// Gets access tokens from a custom provider instead of the token endpoint, see
+    /// [`TokenManager::with_provider`].
+    pub fn token_provider(mut self, provider: impl TokenProvider + 'static) -> Self {
+        self.token_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Renders articles with a custom renderer instead of the themes.
+    ///
+    /// The renderer gets the markdown, the theme and code theme names and the header
+    /// metadata (title, author, date, ...), and validates the theme names; everything
+    /// around rendering, like image handling and the content budget, is unchanged.
+    pub fn renderer(mut self, renderer: impl ContentRenderer + 'static) -> Self {
+        self.renderer = Some(Arc::new(renderer));
+        self
+    }
+
+    /// Creates the client, validating the configuration and, without a token provider,
+    /// the credentials.
+    pub fn build(self) -> Result<WeChatClient> {
+        self.config.validate()?;
+        let mut http_client = WeChatHttpClient::with_config(self.config)?;
+        if let Some(transport) = self.http_client {
+            http_client = http_client.with_transport(transport);
+        }
+
+        let mut client = WeChatClient::with_http_client(
+            self.app_id,
+            self.app_secret,
+            http_client,
+            self.token_provider,
+        )?;
+        client.renderer = self.renderer.map(CustomRenderer);
+        Ok(client)
+    }
+}
+
+/// Outcome of checking the state file before an upload.
+enum TrackedUpload {
+    /// The same content was already uploaded; nothing else to do
+    Completed(Box<UploadReport>),
+    /// The upload is recorded as pending under the file's key and should go ahead
+    Started(PublishState, String),
+}
+
+/// Article built by the upload pipeline, ready to be sent as a draft.
+struct PreparedArticle {
+    /// The article, or its parts if it was split to fit the content budget
+    articles: Vec<Article>,
+    known_media_id: Option<String>,
+    images: Vec<UploadResult>,
+    cover: CoverUpload,
+}
+
+/// Article rendered with placeholders for its image URLs, before any image is uploaded.
+struct RenderedUpload {
+    content: MarkdownContent,
+    /// Directory image and cover references are relative to
+    base_dir: PathBuf,
+    /// Placeholder of every image URL in the content
+    placeholders: HashMap<String, String>,
+    /// The article, with placeholders in its content and no cover yet
+    article: Article,
+    /// Cover reference, from the options or the frontmatter
+    cover: String,
+    slug: String,
+    /// Size of the content if it exceeds the budget
+    oversize: Option<SizeReport>,
+}
+
+/// Upload hooks registered on a client.
+#[derive(Clone, Default)]
+struct HookList(Vec<Arc<dyn UploadHooks>>);
+
+impl std::fmt::Debug for HookList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HookList({} hooks)", self.0.len())
+    }
+}
+
+/// How long a submitted publish job is remembered for its `PublishJobFinish` event;
+/// WeChat finishes jobs within minutes, so a job still unreported after a day has lost
+/// its event.
+const PUBLISH_JOB_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Maximum number of publish jobs remembered at once; the oldest are forgotten first.
+const MAX_PUBLISH_JOBS: usize = 1024;
+
+/// Markdown files of the publish jobs submitted by a client, by publish ID, with the
+/// time they were submitted.
+#[derive(Debug, Default)]
+struct PublishJobs(HashMap<String, (String, Instant)>);
+
+impl PublishJobs {
+    /// Remembers a job, forgetting expired jobs and, at the limit, the oldest one.
+    fn insert(&mut self, publish_id: String, markdown_path: String) {
+        self.0
+            .retain(|_, (_, submitted)| submitted.elapsed() < PUBLISH_JOB_TTL);
+        if self.0.len() >= MAX_PUBLISH_JOBS
+            && let Some(oldest) = self
+                .0
+                .iter()
+                .min_by_key(|(_, (_, submitted))| *submitted)
+                .map(|(publish_id, _)| publish_id.clone())
+        {
+            self.0.remove(&oldest);
+        }
+        self.0.insert(publish_id, (markdown_path, Instant::now()));
+    }
+
+    /// Forgets a job and gets its markdown file, unless it expired.
+    #[cfg(feature = "server")]
+    fn remove(&mut self, publish_id: &str) -> Option<String> {
+        self.0
+            .remove(publish_id)
+            .filter(|(_, submitted)| submitted.elapsed() < PUBLISH_JOB_TTL)
+            .map(|(markdown_path, _)| markdown_path)
+    }
+}
+
+/// Renderer set with [`WeChatClientBuilder::renderer`].
+#[derive(Clone)]
+struct CustomRenderer(Arc<dyn ContentRenderer>);
+
+impl std::fmt::Debug for CustomRenderer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CustomRenderer")
+    }
 }
 
+/// Maximum number of operations in progress at once, far above any practical count
+/// (and within `Semaphore::MAX_PERMITS` on every platform).
+const MAX_OPERATIONS: u32 = u32::MAX >> 3;
+
 /// Main WeChat Official Account client.
 #[derive(Debug)]
 pub struct WeChatClient {
@@ -103,48 +722,234 @@ pub struct WeChatClient {
     token_manager: Arc<TokenManager>,
     image_uploader: ImageUploader,
     draft_manager: DraftManager,
+    broadcaster: Broadcaster,
+    messenger: SubscribeMessenger,
+    comment_manager: CommentManager,
+    analytics: AnalyticsManager,
+    oauth: OAuthManager,
     markdown_parser: MarkdownParser,
-    theme_manager: ThemeManager,
+    theme_manager: Arc<ThemeManager>,
+    hooks: HookList,
+    /// Markdown files of the publish jobs submitted with
+    /// [`publish_draft`](Self::publish_draft), by publish ID
+    publish_jobs: Mutex<PublishJobs>,
+    /// One permit per operation in progress; closed by [`shutdown`](Self::shutdown)
+    operations: Semaphore,
+    /// One permit per Mermaid chart being rendered, shared by all uploads
+    mermaid_renders: Arc<Semaphore>,
+    /// Where temporary files are written
+    temp_files: TempFiles,
+    /// Removal of stale temporary files started on construction, waited for by
+    /// [`shutdown`](Self::shutdown)
+    temp_cleanup: Mutex<Option<JoinHandle<()>>>,
+    /// Queue uploads go through, if registered with
+    /// [`with_upload_queue`](Self::with_upload_queue)
+    upload_queue: Option<UploadQueue>,
+    /// Renderer replacing the theme manager, set with [`WeChatClientBuilder::renderer`]
+    renderer: Option<CustomRenderer>,
 }
 
 impl WeChatClient {
-    /// Creates a new WeChat client with app credentials.
+    /// Creates a new WeChat client with app credentials and the default configuration.
     pub async fn new(app_id: impl Into<String>, app_secret: impl Into<String>) -> Result<Self> {
-        let app_id = app_id.into();
-        let app_secret = app_secret.into();
+        Self::with_config(app_id, app_secret, Config::default()).await
+    }
+
+    /// Creates a new WeChat client with app credentials and a custom configuration.
+    ///
+    /// The configuration is validated, then applies to every component: HTTP timeouts,
+    /// headers, base URLs and retries, upload and download size limits, blocked file
+    /// extensions, concurrency limits, the material cache and temporary files.
+    ///
+    /// ```rust,no_run
+    /// use wechat_pub_rs::config::{Config, HttpConfig, PerformanceConfig};
+    /// use wechat_pub_rs::WeChatClient;
+    ///
+    /// # async fn example() -> wechat_pub_rs::Result<()> {
+    /// let config = Config::builder()
+    ///     .http(HttpConfig::builder().request_timeout_secs(60).build())
+    ///     .performance(PerformanceConfig::builder().max_concurrent_uploads(2).build())
+    ///     .build();
+    /// let client = WeChatClient::with_config("app_id", "app_secret", config).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn with_config(
+        app_id: impl Into<String>,
+        app_secret: impl Into<String>,
+        config: Config,
+    ) -> Result<Self> {
+        config.validate()?;
+        let http_client = WeChatHttpClient::with_config(config)?;
+        Self::with_http_client(app_id.into(), app_secret.into(), http_client, None)
+    }
+
+    /// Creates a builder for a client with custom components, such as an HTTP transport,
+    /// token provider or renderer.
+    ///
+    /// ```rust,no_run
+    /// use wechat_pub_rs::WeChatClient;
+    /// # use wechat_pub_rs::traits::TokenProvider;
+    ///
+    /// # async fn example(tokens: impl TokenProvider + 'static) -> wechat_pub_rs::Result<()> {
+    /// // Tokens come from a central token service instead of the app secret
+    /// let client = WeChatClient::builder().token_provider(tokens).build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder() -> WeChatClientBuilder {
+        WeChatClientBuilder::default()
+    }
 
-        // Validate credentials format
-        utils::validate_app_credentials(&app_id, &app_secret).map_err(WeChatError::config_error)?;
+    /// Creates a client whose API traffic is recorded to a fixture or replayed from
+    /// one, for testing upload flows offline (requires the `recording` feature).
+    ///
+    /// See [`recording`](crate::recording) for details.
+    #[cfg(feature = "recording")]
+    pub async fn with_recorder(
+        app_id: impl Into<String>,
+        app_secret: impl Into<String>,
+        recorder: crate::recording::Recorder,
+    ) -> Result<Self> {
+        let http_client = WeChatHttpClient::new()?.with_recorder(recorder);
+        Self::with_http_client(app_id.into(), app_secret.into(), http_client, None)
+    }
+
+    fn with_http_client(
+        app_id: String,
+        app_secret: String,
+        http_client: WeChatHttpClient,
+        token_provider: Option<Arc<dyn TokenProvider>>,
+    ) -> Result<Self> {
+        // Validate credentials format; a token provider doesn't need them
+        if token_provider.is_none() {
+            utils::validate_app_credentials(&app_id, &app_secret)
+                .map_err(WeChatError::config_error)?;
+        }
 
-        // Create HTTP client
-        let http_client = Arc::new(WeChatHttpClient::new()?);
+        let http_client = Arc::new(http_client);
+
+        let oauth = OAuthManager::new(app_id.clone(), app_secret.clone(), Arc::clone(&http_client));
 
         // Create token manager
-        let token_manager = Arc::new(TokenManager::new(
-            app_id,
-            app_secret,
-            Arc::clone(&http_client),
-        ));
+        let mut token_manager = TokenManager::new(app_id, app_secret, Arc::clone(&http_client));
+        if let Some(provider) = token_provider {
+            token_manager = token_manager.with_provider(provider);
+        }
+        let token_manager = Arc::new(token_manager);
 
         // Create service components
         let image_uploader =
             ImageUploader::new(Arc::clone(&http_client), Arc::clone(&token_manager));
 
         let draft_manager = DraftManager::new(Arc::clone(&http_client), Arc::clone(&token_manager));
+        let broadcaster = Broadcaster::new(Arc::clone(&http_client), Arc::clone(&token_manager));
+        let messenger =
+            SubscribeMessenger::new(Arc::clone(&http_client), Arc::clone(&token_manager));
+        let comment_manager =
+            CommentManager::new(Arc::clone(&http_client), Arc::clone(&token_manager));
+        let analytics = AnalyticsManager::new(Arc::clone(&http_client), Arc::clone(&token_manager));
+
+        let markdown_parser =
+            MarkdownParser::new().with_timezone(http_client.config().locale.timezone);
+        let theme_manager = Arc::new(ThemeManager::new());
+        let mermaid_renders = Arc::new(Semaphore::new(
+            http_client
+                .config()
+                .performance
+                .max_concurrent_mermaid_renders,
+        ));
 
-        let markdown_parser = MarkdownParser::new();
-        let theme_manager = ThemeManager::new();
+        // Remove what earlier runs left behind, off the async executor
+        let temp_files = TempFiles::new(&http_client.config().temp);
+        let mut temp_cleanup = None;
+        if let Some(max_age) = http_client.config().temp_max_age() {
+            let garbage = temp_files.clone();
+            let collect = move || {
+                if let Err(e) = garbage.collect_garbage(max_age) {
+                    warn!("Failed to remove stale temporary files: {e}");
+                }
+            };
+            match tokio::runtime::Handle::try_current() {
+                Ok(runtime) => temp_cleanup = Some(runtime.spawn_blocking(collect)),
+                Err(_) => collect(),
+            }
+        }
 
         Ok(Self {
             http_client,
             token_manager,
             image_uploader,
             draft_manager,
+            broadcaster,
+            messenger,
+            comment_manager,
+            analytics,
+            oauth,
             markdown_parser,
             theme_manager,
+            hooks: HookList::default(),
+            publish_jobs: Mutex::default(),
+            operations: Semaphore::new(MAX_OPERATIONS as usize),
+            mermaid_renders,
+            temp_files,
+            temp_cleanup: Mutex::new(temp_cleanup),
+            upload_queue: None,
+            renderer: None,
         })
     }
 
+    /// Creates a pipeline for uploading articles concurrently with other pipelines.
+    ///
+    /// Each upload through the pipeline writes its generated files (Mermaid chart
+    /// images) to its own temporary directory instead of `images/` next to the markdown
+    /// file, so concurrent uploads never overwrite each other's files. The directories
+    /// are removed when the pipeline is cleaned up, or when it is dropped as the
+    /// configured [`CleanupPolicy`](crate::temp::CleanupPolicy) allows.
+    pub fn pipeline(&self) -> Result<ArticlePipeline<'_>> {
+        ArticlePipeline::new(self)
+    }
+
+    /// Gets where temporary files are written.
+    pub(crate) fn temp_files(&self) -> &TempFiles {
+        &self.temp_files
+    }
+
+    /// Registers hooks that are notified after every upload of a markdown file succeeds
+    /// or fails.
+    ///
+    /// The hooks are notified by [`upload`](Self::upload) and the other `upload_*` calls,
+    /// [`update_draft`](Self::update_draft), [`sync_dir`](Self::sync_dir), the upload
+    /// queue and uploads from [`prepare`](Self::prepare) when they are committed. Dry runs
+    /// and calls that don't start from a markdown file, like
+    /// [`upload_html`](Self::upload_html) and [`create_draft`](Self::create_draft), don't
+    /// notify them. Publish jobs are reported separately, see
+    /// [`UploadHooks::on_publish_result`].
+    ///
+    /// Can be called multiple times; hooks run in registration order.
+    pub fn with_hooks(mut self, hooks: impl UploadHooks + 'static) -> Self {
+        self.hooks.0.push(Arc::new(hooks));
+        self
+    }
+
+    /// Sends uploads through a durable queue, for unreliable networks.
+    ///
+    /// Uploads then write the rendered article and copies of its images to the queue
+    /// before sending it. If sending fails with a retryable error, the upload fails with
+    /// [`WeChatError::Queued`] and the entry is retried by
+    /// [`drain_upload_queue`](Self::drain_upload_queue) or
+    /// [`run_upload_queue`](Self::run_upload_queue), even after a restart. See
+    /// [`upload_queue`](crate::upload_queue) for details.
+    ///
+    /// Queued uploads don't support [`UploadOptions::track_state`],
+    /// [`UploadOptions::write_back`] or [`UploadOptions::split_long_articles`], and
+    /// [`UploadOptions::deadline`] and [`UploadOptions::image_timeout`] don't apply.
+    /// The [`UploadOptions::html_transform`] sees placeholders instead of image URLs.
+    pub fn with_upload_queue(mut self, queue: UploadQueue) -> Self {
+        self.upload_queue = Some(queue);
+        self
+    }
+
     /// Uploads a markdown file as a WeChat draft article.
     ///
     /// This is the main convenience method that handles the entire workflow:
@@ -171,92 +976,372 @@ impl WeChatClient {
     /// * `options` - Upload options for customization
     ///
     /// # Returns
-    /// Returns the media ID of the created draft
+    /// Returns the media ID of the created draft. [Dry runs](UploadOptions::dry_run)
+    /// are rejected, see [`upload_with_report`](Self::upload_with_report).
     pub async fn upload_with_options(
         &self,
         markdown_path: &str,
         options: UploadOptions,
     ) -> Result<String> {
-        let markdown_path = Path::new(markdown_path);
+        reject_dry_run(&options)?;
+        let report = self.upload_with_report(markdown_path, options).await?;
+        Ok(report.draft_id)
+    }
 
-        // Validate input
-        self.validate_upload_input(markdown_path, &options).await?;
+    /// Uploads a markdown file and reports what was uploaded.
+    ///
+    /// Behaves like [`upload_with_options`](Self::upload_with_options) but returns an
+    /// [`UploadReport`] with the image results and cover details alongside the draft ID.
+    pub async fn upload_with_report(
+        &self,
+        markdown_path: &str,
+        options: UploadOptions,
+    ) -> Result<UploadReport> {
+        self.upload_in(markdown_path, options, None).await
+    }
 
-        info!("Starting upload process for: {}", markdown_path.display());
+    /// Uploads every markdown file (`*.md`, `*.markdown`) directly in a directory.
+    ///
+    /// Files are uploaded concurrently, up to `performance.max_concurrent_uploads` at a
+    /// time (one at a time if `enable_parallel_processing` is off), with the same
+    /// options. A failing file doesn't stop the others: the report lists the draft of
+    /// every uploaded file and the error of every failed one. Only listing the directory
+    /// fails the call.
+    pub async fn upload_dir(
+        &self,
+        dir: impl AsRef<Path>,
+        options: UploadOptions,
+    ) -> Result<DirUploadReport> {
+        let dir = dir.as_ref();
+        let files = markdown_files(dir).await?;
+        let performance = &self.http_client.config().performance;
+        let limit = if performance.enable_parallel_processing {
+            performance.max_concurrent_uploads.max(1)
+        } else {
+            1
+        };
+        info!(
+            "Uploading {} markdown files from {}",
+            files.len(),
+            dir.display()
+        );
 
-        // Step 1: Parse markdown content
-        let mut content = self.parse_markdown_file(markdown_path).await?;
-        debug!("Found {} images in content", content.images.len());
+        let results: Vec<_> = futures::stream::iter(files)
+            .map(|path| {
+                let options = options.clone();
+                async move {
+                    let result = self
+                        .upload_in(&path.display().to_string(), options, None)
+                        .await;
+                    (path, result)
+                }
+            })
+            .buffered(limit)
+            .collect()
+            .await;
 
-        // Step 1.5: Process Mermaid charts
-        let base_dir = utils::get_base_directory(markdown_path).unwrap_or_else(|| Path::new("."));
-        let document_slug = MermaidProcessor::extract_slug_from_path(markdown_path);
-        let mermaid_processor = MermaidProcessor::new(base_dir.to_path_buf(), document_slug);
+        let mut report = DirUploadReport::default();
+        for (path, result) in results {
+            match result {
+                Ok(upload_report) => report.uploaded.push((path, upload_report)),
+                Err(e) => {
+                    warn!("Failed to upload {}: {e}", path.display());
+                    report.failed.push((path, e));
+                }
+            }
+        }
+        info!(
+            "Uploaded {} of {} markdown files from {}",
+            report.uploaded.len(),
+            report.uploaded.len() + report.failed.len(),
+            dir.display()
+        );
+        Ok(report)
+    }
 
-        let (modified_content, mermaid_images) = mermaid_processor
-            .process_mermaid_content_with_source_path(
-                &content.content,
-                base_dir,
-                Some(markdown_path),
-            )
-            .await?;
+    /// Uploads a markdown file, writing generated files to `work_dir` if given, and
+    /// notifies the hooks.
+    pub(crate) async fn upload_in(
+        &self,
+        markdown_path: &str,
+        options: UploadOptions,
+        work_dir: Option<&Path>,
+    ) -> Result<UploadReport> {
+        let dry_run = options.dry_run;
+        let result = self.run_upload(markdown_path, options, work_dir).await;
+        if !dry_run {
+            self.notify_hooks(markdown_path, &result).await;
+        }
+        result
+    }
 
-        // Update content with Mermaid-processed version
-        content.content = modified_content;
+    /// Notifies the hooks of the outcome of an upload.
+    async fn notify_hooks(&self, markdown_path: &str, result: &Result<UploadReport>) {
+        for hooks in &self.hooks.0 {
+            match result {
+                Ok(report) => hooks.on_success(markdown_path, report).await,
+                Err(e) => hooks.on_failure(markdown_path, e).await,
+            }
+        }
+    }
 
-        // Add Mermaid-generated images to the image list
-        content.images.extend(mermaid_images.clone());
+    /// Runs the upload pipeline for [`upload_with_report`](Self::upload_with_report).
+    async fn run_upload(
+        &self,
+        markdown_path: &str,
+        options: UploadOptions,
+        work_dir: Option<&Path>,
+    ) -> Result<UploadReport> {
+        let _operation = self.begin_operation().await?;
+        let markdown_path = Path::new(markdown_path);
 
-        debug!(
-            "Total images to upload (including Mermaid): {}",
-            content.images.len()
+        let span = info_span!(
+            "upload",
+            path = %markdown_path.display(),
+            draft_id = tracing::field::Empty
         );
 
-        // Step 2: Upload images concurrently
-        let upload_results = self
-            .image_uploader
-            .upload_images(content.images.clone(), base_dir)
-            .await?;
-        info!("Completed uploading {} images", upload_results.len());
+        let upload = async {
+            info!("Starting upload process for: {}", markdown_path.display());
 
-        // Step 3: Replace image URLs in content
-        let url_mapping = self.draft_manager.create_url_mapping(&upload_results);
-        content.replace_image_urls(&url_mapping)?;
+            if options.dry_run {
+                return self.dry_run_upload(markdown_path, &options, work_dir).await;
+            }
 
-        // Step 4: Upload cover image (from options or frontmatter)
-        let cover_path = options
-            .cover_image
-            .as_ref()
-            .or(content.cover.as_ref())
-            .expect("Cover image should be available from validation");
+            if let Some(queue) = &self.upload_queue {
+                let mut upload = self
+                    .enqueue_article(queue, markdown_path, &options, work_dir)
+                    .await?;
+                let _sending = queue.lock().await;
+                let report = self.deliver_queued(queue, &mut upload).await?;
+                Span::current().record("draft_id", report.draft_id.as_str());
+                return Ok(report);
+            }
 
-        info!("Starting to upload cover image: {}", cover_path);
-        let cover_media_id = Some(self.upload_cover_image(cover_path, base_dir).await?);
-        info!("Completed uploading cover image");
+            with_deadline(options.deadline, async {
+                if options.write_back && !options.track_state {
+                    self.check_content_dir_account(markdown_path).await?;
+                }
+                let mut tracked = None;
+                if options.track_state {
+                    match self.begin_tracked_upload(markdown_path).await? {
+                        TrackedUpload::Completed(report) => return Ok(*report),
+                        TrackedUpload::Started(state, key) => tracked = Some((state, key)),
+                    }
+                }
 
-        // Step 5: Render content with theme (from frontmatter, options, or default)
-        let theme = content
-            .theme
-            .as_ref()
-            .or(Some(&options.theme))
-            .map(|t| t.as_str())
-            .unwrap_or("default");
-
-        // Validate theme exists
-        if !self.theme_manager.has_theme(theme) {
-            return Err(WeChatError::ThemeNotFound {
-                theme: theme.to_string(),
-            });
+                let prepared = self
+                    .prepare_article(markdown_path, &options, work_dir)
+                    .await?;
+
+                // Step 6: Create draft (checking for remote edits when state is tracked)
+                let articles = prepared.articles;
+                let synced =
+                    if tracked.is_some() || options.write_back || prepared.known_media_id.is_some()
+                    {
+                        let last_synced = tracked
+                            .as_ref()
+                            .and_then(|(state, key)| state.entry(key))
+                            .and_then(|entry| entry.synced_draft());
+                        self.draft_manager
+                            .sync_draft(
+                                articles,
+                                prepared.known_media_id.as_deref(),
+                                last_synced.as_ref(),
+                                options.force,
+                                options.on_existing_draft,
+                            )
+                            .instrument(info_span!("draft"))
+                            .await?
+                    } else {
+                        let media_id = self
+                            .draft_manager
+                            .create_draft_with_policy(articles, options.on_existing_draft)
+                            .instrument(info_span!("draft"))
+                            .await?;
+                        self.created_draft(media_id, options.lookup_draft_url).await
+                    };
+                let draft_id = synced.media_id;
+
+                Span::current().record("draft_id", draft_id.as_str());
+                info!("Successfully created draft with ID: {draft_id}");
+                log_draft_url(synced.url.as_deref());
+
+                let written_back = if options.write_back {
+                    write_back_frontmatter(markdown_path, &draft_id, synced.url.as_deref()).await?
+                } else {
+                    None
+                };
+
+                if let Some((_, key)) = tracked {
+                    PublishState::update(content_dir(markdown_path), |state| {
+                        let entry = state.entry_mut(&key);
+                        if let Some(raw) = &written_back {
+                            // Record the file as rewritten so it doesn't show up as modified
+                            entry.idempotency_key = state::idempotency_key(&key, raw);
+                            entry.content_hash = state::content_hash(raw);
+                        }
+                        entry.media_id = Some(draft_id.clone());
+                        entry.remote_update_time = synced.update_time;
+                        entry.thumb_media_id = Some(prepared.cover.media_id.clone());
+                        entry.pending_since = None;
+                        entry.updated_at = Some(Utc::now());
+                    })
+                    .await?;
+                }
+
+                Ok(UploadReport {
+                    draft_id,
+                    images: prepared.images,
+                    cover_media_id: prepared.cover.media_id,
+                    cover_reused: prepared.cover.reused,
+                    draft_url: synced.url,
+                    unchanged: false,
+                    dry_run: None,
+                })
+            })
+            .await
+        };
+
+        upload.instrument(span).await
+    }
+
+    /// Renders an article and plans its upload for a [dry run](UploadOptions::dry_run).
+    async fn dry_run_upload(
+        &self,
+        markdown_path: &Path,
+        options: &UploadOptions,
+        work_dir: Option<&Path>,
+    ) -> Result<UploadReport> {
+        let (plan, _, _) = with_deadline(
+            options.deadline,
+            self.plan_upload(markdown_path, options, work_dir),
+        )
+        .await?;
+        for issue in &plan.validation.issues {
+            warn!("Dry run of {}: {issue}", markdown_path.display());
         }
+        info!(
+            "Dry run of {} would upload {} images and the cover",
+            markdown_path.display(),
+            plan.images.len()
+        );
+
+        Ok(UploadReport {
+            draft_id: String::new(),
+            images: Vec::new(),
+            cover_media_id: String::new(),
+            cover_reused: false,
+            draft_url: None,
+            unchanged: false,
+            dry_run: Some(plan),
+        })
+    }
+
+    /// Renders a markdown file and plans its upload without calling WeChat, see
+    /// [`prepare`](crate::prepare).
+    pub async fn prepare(&self, markdown_path: &str) -> Result<PreparedUpload<'_>> {
+        self.prepare_with_options(markdown_path, UploadOptions::default())
+            .await
+    }
+
+    /// Renders a markdown file with custom options and plans its upload without calling
+    /// WeChat. The options apply to the commit as well.
+    ///
+    /// Fails like an upload for invalid input, e.g. a missing cover or unknown theme;
+    /// problems the upload would only run into later, like missing images, are listed
+    /// in the [`validation`](PreparedUpload::validation) report.
+    pub async fn prepare_with_options(
+        &self,
+        markdown_path: &str,
+        options: UploadOptions,
+    ) -> Result<PreparedUpload<'_>> {
+        let markdown_path = Path::new(markdown_path);
+        self.prepare_article_upload(markdown_path, &options)
+            .instrument(info_span!("prepare", path = %markdown_path.display()))
+            .await
+    }
+
+    /// Renders a markdown file into the upload queue without sending it, and returns
+    /// the ID of the queue entry.
+    ///
+    /// Requires an upload queue registered with
+    /// [`with_upload_queue`](Self::with_upload_queue).
+    pub async fn enqueue_upload(
+        &self,
+        markdown_path: &str,
+        options: UploadOptions,
+    ) -> Result<String> {
+        let _operation = self.begin_operation().await?;
+        let queue = self.require_upload_queue()?;
+        let upload = self
+            .enqueue_article(queue, Path::new(markdown_path), &options, None)
+            .await?;
+        Ok(upload.id)
+    }
+
+    /// Sends the queued uploads that are due, oldest first, and notifies the hooks of
+    /// the uploads that were sent or failed permanently.
+    ///
+    /// The pass stops at the first upload failing with a retryable error, as the others
+    /// would most likely fail the same way; they are tried again by the next pass.
+    pub async fn drain_upload_queue(&self) -> Result<DrainReport> {
+        let _operation = self.begin_operation().await?;
+        let queue = self.require_upload_queue()?;
+        let _sending = queue.lock().await;
+
+        let now = Utc::now();
+        let mut report = DrainReport::default();
+        let mut uploads = queue.pending().await?.into_iter();
+        while let Some(mut upload) = uploads.next() {
+            if !upload.is_due(now) {
+                report.pending += 1;
+                continue;
+            }
 
-        let html_content = self.render_content(&content, theme, &options)?;
+            let markdown_path = upload.markdown_path.display().to_string();
+            match self.deliver_queued(queue, &mut upload).await {
+                Ok(upload_report) => {
+                    for hooks in &self.hooks.0 {
+                        hooks.on_success(&markdown_path, &upload_report).await;
+                    }
+                    report.uploaded.push((upload, upload_report));
+                }
+                Err(WeChatError::Queued { .. }) => {
+                    report.pending += 1 + uploads.len();
+                    break;
+                }
+                Err(e) => {
+                    for hooks in &self.hooks.0 {
+                        hooks.on_failure(&markdown_path, &e).await;
+                    }
+                    report.failed.push((upload, e));
+                }
+            }
+        }
 
-        // Step 6: Create article and draft
-        let article = self.create_article(&content, &options, html_content, cover_media_id);
-        let draft_id = self.draft_manager.create_draft(vec![article]).await?;
+        info!(
+            "Drained upload queue: {} sent, {} failed, {} pending",
+            report.uploaded.len(),
+            report.failed.len(),
+            report.pending
+        );
+        Ok(report)
+    }
 
-        info!("Successfully created draft with ID: {draft_id}");
-        Ok(draft_id)
+    /// Drains the upload queue every `poll_interval` until the client is shut down.
+    ///
+    /// Failing passes are logged and don't stop the worker.
+    pub async fn run_upload_queue(&self, poll_interval: Duration) -> Result<()> {
+        self.require_upload_queue()?;
+        loop {
+            match self.drain_upload_queue().await {
+                Ok(_) => {}
+                Err(WeChatError::ShutDown) => return Ok(()),
+                Err(e) => warn!("Failed to drain the upload queue: {e}"),
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
     }
 
     /// Gets a draft by media ID.
@@ -264,6 +1349,129 @@ impl WeChatClient {
         self.draft_manager.get_draft(media_id).await
     }
 
+    /// Compares the local render of a markdown file with a draft, e.g. to review edits
+    /// made in the WeChat editor before overwriting them.
+    pub async fn diff_draft(&self, markdown_path: &str, media_id: &str) -> Result<DraftDiff> {
+        self.diff_draft_with_options(markdown_path, media_id, UploadOptions::default())
+            .await
+    }
+
+    /// Compares the local render of a markdown file with a draft using custom options.
+    ///
+    /// The file is rendered like a preview, without uploading anything. The articles of
+    /// a multi-article draft are compared as one document.
+    pub async fn diff_draft_with_options(
+        &self,
+        markdown_path: &str,
+        media_id: &str,
+        options: UploadOptions,
+    ) -> Result<DraftDiff> {
+        let markdown_path = Path::new(markdown_path);
+        let base_dir = utils::get_base_directory(markdown_path).unwrap_or_else(|| Path::new("."));
+        let local = self
+            .render_local_preview(markdown_path, base_dir, &options)
+            .await?;
+
+        let draft = self.draft_manager.get_draft(media_id).await?;
+        Ok(diff::diff(&local, &sync::draft_html(&draft)))
+    }
+
+    /// Compares the markdown files directly in a directory with the drafts on WeChat,
+    /// and with [`SyncOptions::push`] uploads the new and changed ones.
+    ///
+    /// Files are rendered without uploading anything and matched to drafts as described
+    /// in [`sync`](crate::sync). Pushing uploads new files as new drafts and updates the
+    /// matched drafts of changed files, sending what was rendered for the comparison like
+    /// a [prepared upload](crate::prepare); like those, pushing doesn't support
+    /// `track_state`, `write_back` or `split_long_articles`. A file that fails to render
+    /// or push doesn't stop the others; only listing the directory or the drafts fails
+    /// the call.
+    pub async fn sync_dir(
+        &self,
+        dir: impl AsRef<Path>,
+        options: SyncOptions,
+    ) -> Result<SyncReport> {
+        let _operation = self.begin_operation().await?;
+        if options.push {
+            reject_deferred_options(&options.upload, "Pushed")?;
+        }
+        let dir = dir.as_ref();
+        let files = markdown_files(dir).await?;
+        let drafts = self.draft_manager.list_all_drafts().await?;
+        info!(
+            "Comparing {} markdown files from {} with {} drafts",
+            files.len(),
+            dir.display(),
+            drafts.len()
+        );
+
+        let mut report = SyncReport::default();
+        let mut locals = Vec::new();
+        let mut planned = HashMap::new();
+        for path in files {
+            match self.plan_upload(&path, &options.upload, None).await {
+                Ok((plan, payload, base_dir)) => {
+                    locals.push(LocalArticle {
+                        known_media_id: payload.known_media_id.clone(),
+                        title: payload.article.title.clone(),
+                        source_url: payload.article.content_source_url.clone(),
+                        html: payload.article.content.clone(),
+                        path: path.clone(),
+                    });
+                    planned.insert(path, (plan, payload, base_dir));
+                }
+                Err(e) => {
+                    warn!("Failed to render {}: {e}", path.display());
+                    report.failed.push((path, e));
+                }
+            }
+        }
+        report.entries = sync::compare(locals, &drafts);
+
+        if options.push {
+            for entry in &report.entries {
+                let Some(path) = &entry.path else { continue };
+                let Some((plan, mut payload, base_dir)) = planned.remove(path) else {
+                    continue;
+                };
+                match (entry.state, &entry.media_id) {
+                    (SyncState::New, _) => {}
+                    (SyncState::Changed, Some(media_id)) => {
+                        payload.known_media_id = Some(media_id.clone());
+                    }
+                    _ => continue,
+                }
+
+                // The operation permit is already held, so the payload is sent directly
+                let markdown_path = path.display().to_string();
+                let result = if plan.validation.is_ok() {
+                    info!("Pushing {markdown_path}");
+                    self.send_payload(&payload, &base_dir).await
+                } else {
+                    Err(WeChatError::config_error(format!(
+                        "{markdown_path} failed validation: {}",
+                        plan.validation.issues.join("; ")
+                    )))
+                };
+                self.notify_hooks(&markdown_path, &result).await;
+                match result {
+                    Ok(upload) => report.pushed.push((path.clone(), upload.draft_id)),
+                    Err(e) => {
+                        warn!("Failed to push {}: {e}", path.display());
+                        report.failed.push((path.clone(), e));
+                    }
+                }
+            }
+            info!(
+                "Pushed {} files from {}",
+                report.pushed.len(),
+                dir.display()
+            );
+        }
+
+        Ok(report)
+    }
+
     /// Updates an existing draft with new content.
     pub async fn update_draft(&self, media_id: &str, markdown_path: &str) -> Result<()> {
         let options = UploadOptions::default();
@@ -271,15 +1479,32 @@ impl WeChatClient {
             .await
     }
 
-    /// Updates an existing draft with custom options.
+    /// Updates an existing draft with custom options, and notifies the hooks.
+    ///
+    /// Fails if the draft holds a different number of articles than the markdown renders
+    /// to, e.g. when [`UploadOptions::split_long_articles`] splits it into more parts.
     pub async fn update_draft_with_options(
         &self,
         media_id: &str,
         markdown_path: &str,
         options: UploadOptions,
     ) -> Result<()> {
-        let markdown_path = Path::new(markdown_path);
-        self.validate_upload_input(markdown_path, &options).await?;
+        let result = self
+            .run_update_draft(media_id, Path::new(markdown_path), options)
+            .await;
+        self.notify_hooks(markdown_path, &result).await;
+        result.map(|_| ())
+    }
+
+    /// Updates an existing draft, see
+    /// [`update_draft_with_options`](Self::update_draft_with_options).
+    async fn run_update_draft(
+        &self,
+        media_id: &str,
+        markdown_path: &Path,
+        options: UploadOptions,
+    ) -> Result<UploadReport> {
+        let _operation = self.begin_operation().await?;
 
         info!(
             "Updating draft {} with: {}",
@@ -287,249 +1512,1954 @@ impl WeChatClient {
             markdown_path.display()
         );
 
-        // Parse and process content (same as upload)
-        let mut content = self.parse_markdown_file(markdown_path).await?;
-        let base_dir = utils::get_base_directory(markdown_path).unwrap_or_else(|| Path::new("."));
+        with_deadline(options.deadline, async {
+            if options.track_state || options.write_back {
+                self.check_content_dir_account(markdown_path).await?;
+            }
 
-        // Process Mermaid charts
-        let document_slug = MermaidProcessor::extract_slug_from_path(markdown_path);
-        let mermaid_processor = MermaidProcessor::new(base_dir.to_path_buf(), document_slug);
+            // Parse and process content (same as upload)
+            let prepared = self.prepare_article(markdown_path, &options, None).await?;
 
-        let (modified_content, mermaid_images) = mermaid_processor
-            .process_mermaid_content_with_source_path(
-                &content.content,
-                base_dir,
-                Some(markdown_path),
-            )
-            .await?;
+            self.draft_manager
+                .update_draft(media_id, prepared.articles)
+                .await?;
 
-        // Update content with Mermaid-processed version
-        content.content = modified_content;
+            let mut draft_url = None;
+            if options.write_back || options.lookup_draft_url {
+                draft_url = self.draft_manager.lookup_draft(media_id).await.url;
+                log_draft_url(draft_url.as_deref());
+            }
+            if options.write_back {
+                write_back_frontmatter(markdown_path, media_id, draft_url.as_deref()).await?;
+            }
 
-        // Add Mermaid-generated images to the image list
-        content.images.extend(mermaid_images);
+            if options.track_state {
+                let raw = tokio::fs::read(markdown_path).await.map_err(|e| {
+                    WeChatError::file_error(markdown_path.display().to_string(), e.to_string())
+                })?;
+                let content_dir = content_dir(markdown_path);
+                let key = state::document_key(markdown_path, &raw, self.slug_strategy());
+                let app_id = self.token_manager.app_id();
+                PublishState::update(content_dir, |state| {
+                    state.app_id.get_or_insert_with(|| app_id.to_string());
+                    state.record_upload(&key, &raw, media_id);
+                    state.entry_mut(&key).thumb_media_id = Some(prepared.cover.media_id.clone());
+                })
+                .await?;
+            }
+
+            info!("Successfully updated draft: {media_id}");
+            Ok(UploadReport {
+                draft_id: media_id.to_string(),
+                images: prepared.images,
+                cover_media_id: prepared.cover.media_id,
+                cover_reused: prepared.cover.reused,
+                draft_url,
+                unchanged: false,
+                dry_run: None,
+            })
+        })
+        .await
+    }
+
+    /// Reports which markdown files in a directory are out of date with WeChat.
+    ///
+    /// Compares each file against the local state file written by uploads made with
+    /// [`UploadOptions::track_state`].
+    pub async fn status(&self, content_dir: impl AsRef<Path>) -> Result<Vec<FileStatus>> {
+        state::status(content_dir.as_ref(), self.slug_strategy()).await
+    }
+
+    /// Reports, per markdown file in a directory, whether the file, its draft or both
+    /// changed since the last upload.
+    ///
+    /// Like [`status`](Self::status), but also compares the drafts' current `update_time`
+    /// with the one recorded at the last upload, so edits made in the WeChat editor show
+    /// up as [`RemotelyModified`](crate::state::ConflictStatus::RemotelyModified) or
+    /// [`Conflicting`](crate::state::ConflictStatus::Conflicting).
+    pub async fn conflict_report(
+        &self,
+        content_dir: impl AsRef<Path>,
+    ) -> Result<Vec<ConflictReport>> {
+        let statuses = state::status(content_dir.as_ref(), self.slug_strategy()).await?;
+        let media_ids: HashSet<String> = statuses
+            .iter()
+            .filter_map(|file| file.media_id.clone())
+            .collect();
+
+        let remote_update_times = if media_ids.is_empty() {
+            HashMap::new()
+        } else {
+            self.draft_manager.draft_update_times(&media_ids).await?
+        };
+
+        Ok(ConflictReport::from_statuses(
+            statuses,
+            &remote_update_times,
+        ))
+    }
+
+    /// Exports the material cache and draft mappings to a JSON file.
+    ///
+    /// Together with [`import_media_map`](Self::import_media_map) this lets runners with
+    /// ephemeral filesystems (e.g. CI) share deduplication state between runs.
+    pub async fn export_media_map(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let map = MediaMap {
+            materials: self.image_uploader.export_material_cache().await,
+            drafts: self.draft_manager.export_draft_titles().await,
+        };
+
+        map.save(path).await?;
+        info!(
+            "Exported {} materials and {} drafts to {}",
+            map.materials.len(),
+            map.drafts.len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    /// Saves the material cache to the configured `cache.material_cache_path` right away.
+    ///
+    /// The cache is also saved when the client is dropped; call this on shutdown paths
+    /// where the client may not be dropped (e.g. `std::process::exit`).
+    pub async fn save_material_cache(&self) -> Result<()> {
+        self.image_uploader.save_material_cache().await
+    }
+
+    /// Shuts the client down, e.g. when a service stops.
+    ///
+    /// Uploads and draft operations already in progress are waited for, and new ones fail
+    /// with [`WeChatError::ShutDown`]. Then image uploads spawned by cancelled operations
+    /// are waited for and the material cache is saved (see
+    /// [`save_material_cache`](Self::save_material_cache)). State files are written as
+    /// part of each upload, so nothing else is pending. The removal of stale temporary
+    /// files started on construction is waited for too; it is the client's only
+    /// background task, tokens are refreshed on use.
+    ///
+    /// Calling it again only saves the material cache.
+    pub async fn shutdown(&self) -> Result<()> {
+        info!("Shutting down client");
+        if let Ok(_operations) = self.operations.acquire_many(MAX_OPERATIONS).await {
+            self.operations.close();
+        }
+        let temp_cleanup = self.temp_cleanup.lock().unwrap().take();
+        if let Some(cleanup) = temp_cleanup
+            && let Err(e) = cleanup.await
+        {
+            warn!("Removing stale temporary files failed: {e}");
+        }
+        self.image_uploader.shutdown().await?;
+        info!("Client shut down");
+        Ok(())
+    }
 
-        let upload_results = self
+    /// Registers an operation that [`shutdown`](Self::shutdown) waits for.
+    async fn begin_operation(&self) -> Result<SemaphorePermit<'_>> {
+        self.operations
+            .acquire()
+            .await
+            .map_err(|_| WeChatError::ShutDown)
+    }
+
+    /// Imports a media map written by [`export_media_map`](Self::export_media_map).
+    ///
+    /// A missing file imports nothing, so the first run of a pipeline needs no special
+    /// casing. Entries already known to this client take precedence.
+    pub async fn import_media_map(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let map = MediaMap::load(path).await?;
+
+        let materials = self
             .image_uploader
-            .upload_images(content.images.clone(), base_dir)
-            .await?;
+            .import_material_cache(map.materials)
+            .await;
+        let drafts = self.draft_manager.import_draft_titles(map.drafts).await;
+        info!(
+            "Imported {materials} materials and {drafts} drafts from {}",
+            path.display()
+        );
+        Ok(())
+    }
 
-        let url_mapping = self.draft_manager.create_url_mapping(&upload_results);
-        content.replace_image_urls(&url_mapping)?;
+    /// Renders a markdown file to themed HTML without uploading anything.
+    ///
+    /// Local images are mapped to paths relative to the markdown file so the preview
+    /// shows them straight from disk; remote images keep their original URLs. Mermaid
+    /// charts are embedded as data URIs unless
+    /// [`inline_diagrams`](UploadOptions::inline_diagrams) is turned off. No cover image
+    /// is required.
+    pub async fn render_preview(
+        &self,
+        markdown_path: &str,
+        options: UploadOptions,
+    ) -> Result<String> {
+        let markdown_path = Path::new(markdown_path);
+        let base_dir = utils::get_base_directory(markdown_path).unwrap_or_else(|| Path::new("."));
+        self.render_local_preview(markdown_path, base_dir, &options)
+            .await
+    }
 
-        let cover_path = options
-            .cover_image
-            .as_ref()
-            .or(content.cover.as_ref())
-            .expect("Cover image should be available from validation");
+    /// Measures the rendered size of a markdown file, in total and per section, against
+    /// the options' content budget.
+    ///
+    /// Images are measured with their local paths, which are usually a bit shorter than
+    /// the URLs WeChat assigns on upload. Uploads check the same budget before creating
+    /// the draft.
+    pub async fn analyze_size(
+        &self,
+        markdown_path: &str,
+        options: &UploadOptions,
+    ) -> Result<SizeReport> {
+        let markdown_path = Path::new(markdown_path);
+        self.validate_markdown_path(markdown_path).await?;
 
-        let cover_media_id = Some(self.upload_cover_image(cover_path, base_dir).await?);
+        let (content, _) = self.load_content(markdown_path, options, None).await?;
+        let theme = self.resolve_theme(&content, options)?;
+        let finish = |html: String| apply_html_transform(html, options);
+        let html = finish(self.render_content(&content, theme, options, None).await?)?;
+        self.measure_size(&content, theme, options, &html, &finish)
+            .await
+    }
 
-        let theme = content
-            .theme
-            .as_ref()
-            .or(Some(&options.theme))
-            .map(|t| t.as_str())
-            .unwrap_or("default");
-
-        // Validate theme exists
-        if !self.theme_manager.has_theme(theme) {
-            return Err(WeChatError::ThemeNotFound {
-                theme: theme.to_string(),
-            });
+    /// Checks the frontmatter of a markdown file against the configured
+    /// [`frontmatter_schema`](crate::config::RenderConfig::frontmatter_schema).
+    ///
+    /// Fails with [`WeChatError::FrontmatterInvalid`] listing every violation. Succeeds
+    /// without reading the file if no schema is configured. Uploads run the same check
+    /// before parsing the file.
+    pub async fn validate_frontmatter(&self, markdown_path: impl AsRef<Path>) -> Result<()> {
+        let markdown_path = markdown_path.as_ref();
+        let Some(schema) = &self.http_client.config().render.frontmatter_schema else {
+            return Ok(());
+        };
+
+        let markdown = tokio::fs::read_to_string(markdown_path)
+            .await
+            .map_err(|e| {
+                WeChatError::file_error(markdown_path.display().to_string(), e.to_string())
+            })?;
+        let violations = schema.validate(&markdown);
+        if violations.is_empty() {
+            return Ok(());
         }
+        Err(WeChatError::FrontmatterInvalid {
+            path: markdown_path.display().to_string(),
+            violations,
+        })
+    }
 
-        let html_content = self.render_content(&content, theme, &options)?;
-        let article = self.create_article(&content, &options, html_content, cover_media_id);
+    /// Renders a preview of a markdown file and writes the HTML to `output_path`.
+    ///
+    /// Image paths are rewritten relative to the output file's directory.
+    pub async fn render_to_file(
+        &self,
+        markdown_path: &str,
+        output_path: impl AsRef<Path>,
+        options: UploadOptions,
+    ) -> Result<()> {
+        let output_path = output_path.as_ref();
+        let output_dir = utils::get_base_directory(output_path)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
 
-        self.draft_manager
-            .update_draft(media_id, vec![article])
+        let html = self
+            .render_local_preview(Path::new(markdown_path), output_dir, &options)
             .await?;
 
-        info!("Successfully updated draft: {media_id}");
-        Ok(())
+        tokio::fs::write(output_path, html).await.map_err(|e| {
+            WeChatError::file_error(output_path.display().to_string(), e.to_string())
+        })?;
+
+        info!("Wrote preview to: {}", output_path.display());
+        Ok(())
+    }
+
+    /// Deletes a draft by media ID.
+    pub async fn delete_draft(&self, media_id: &str) -> Result<()> {
+        let _operation = self.begin_operation().await?;
+        self.draft_manager.delete_draft(media_id).await
+    }
+
+    /// Sends a draft to a follower's phone as a preview, e.g. for a last check by an
+    /// editor before the draft is published.
+    ///
+    /// The follower is identified by their `openid` for this account and must follow
+    /// it. WeChat allows 100 previews per day.
+    pub async fn preview_draft(&self, media_id: &str, openid: &str) -> Result<()> {
+        let _operation = self.begin_operation().await?;
+        self.draft_manager.preview_draft(media_id, openid).await
+    }
+
+    /// Sends a draft to followers as a mass message, see [`broadcast`](crate::broadcast).
+    pub async fn broadcast(
+        &self,
+        media_id: &str,
+        target: BroadcastTarget,
+        options: BroadcastOptions,
+    ) -> Result<BroadcastJob> {
+        let _operation = self.begin_operation().await?;
+        self.broadcaster.send(media_id, target, options).await
+    }
+
+    /// Gets the state of a mass message send job.
+    pub async fn broadcast_status(&self, msg_id: u64) -> Result<BroadcastStatus> {
+        self.broadcaster.status(msg_id).await
+    }
+
+    /// Deletes a sent mass message, or a single article of it (1-based).
+    pub async fn delete_broadcast(&self, msg_id: u64, article: Option<u32>) -> Result<()> {
+        let _operation = self.begin_operation().await?;
+        self.broadcaster.delete(msg_id, article).await
+    }
+
+    /// Sends a subscription message to a follower, see [`subscribe`](crate::subscribe).
+    pub async fn send_subscribe_message(&self, message: &SubscribeMessage) -> Result<()> {
+        let _operation = self.begin_operation().await?;
+        self.messenger.send(message).await
+    }
+
+    /// Lists the account's subscription message templates.
+    pub async fn list_subscribe_templates(&self) -> Result<Vec<SubscribeTemplate>> {
+        self.messenger.templates().await
+    }
+
+    /// Opens comments on a published article, see [`comments`](crate::comments).
+    pub async fn open_comments(&self, article: CommentTarget) -> Result<()> {
+        let _operation = self.begin_operation().await?;
+        self.comment_manager.open(article).await
+    }
+
+    /// Closes comments on a published article.
+    pub async fn close_comments(&self, article: CommentTarget) -> Result<()> {
+        let _operation = self.begin_operation().await?;
+        self.comment_manager.close(article).await
+    }
+
+    /// Lists comments on a published article, at most 50 per page.
+    pub async fn list_comments(
+        &self,
+        article: CommentTarget,
+        begin: u32,
+        count: u32,
+        filter: CommentFilter,
+    ) -> Result<CommentPage> {
+        self.comment_manager
+            .list(article, begin, count, filter)
+            .await
+    }
+
+    /// Replies to a comment, replacing any earlier reply.
+    pub async fn reply_to_comment(
+        &self,
+        article: CommentTarget,
+        comment_id: u64,
+        content: &str,
+    ) -> Result<()> {
+        let _operation = self.begin_operation().await?;
+        self.comment_manager
+            .reply(article, comment_id, content)
+            .await
+    }
+
+    /// Deletes the reply to a comment.
+    pub async fn delete_comment_reply(
+        &self,
+        article: CommentTarget,
+        comment_id: u64,
+    ) -> Result<()> {
+        let _operation = self.begin_operation().await?;
+        self.comment_manager.delete_reply(article, comment_id).await
+    }
+
+    /// Elects a comment, showing it to all readers.
+    pub async fn elect_comment(&self, article: CommentTarget, comment_id: u64) -> Result<()> {
+        let _operation = self.begin_operation().await?;
+        self.comment_manager.elect(article, comment_id).await
+    }
+
+    /// Unelects a comment, hiding it from readers other than its author.
+    pub async fn unelect_comment(&self, article: CommentTarget, comment_id: u64) -> Result<()> {
+        let _operation = self.begin_operation().await?;
+        self.comment_manager.unelect(article, comment_id).await
+    }
+
+    /// Deletes a comment.
+    pub async fn delete_comment(&self, article: CommentTarget, comment_id: u64) -> Result<()> {
+        let _operation = self.begin_operation().await?;
+        self.comment_manager.delete(article, comment_id).await
+    }
+
+    /// Gets the daily statistics of the articles read in a range, see
+    /// [`analytics`](crate::analytics).
+    pub async fn get_article_summary(&self, range: DateRange) -> Result<Vec<ArticleSummary>> {
+        self.analytics.article_summary(range).await
+    }
+
+    /// Gets the cumulative statistics of the articles published in a range.
+    pub async fn get_article_total(&self, range: DateRange) -> Result<Vec<ArticleTotal>> {
+        self.analytics.article_total(range).await
+    }
+
+    /// Gets the daily reads of all articles in a range by source.
+    pub async fn get_user_read(&self, range: DateRange) -> Result<Vec<UserRead>> {
+        self.analytics.user_read(range).await
+    }
+
+    /// Gets the followers gained and lost in a range by day and source.
+    pub async fn get_user_summary(&self, range: DateRange) -> Result<Vec<UserSummary>> {
+        self.analytics.user_summary(range).await
+    }
+
+    /// Gets the total number of followers on each day of a range.
+    pub async fn get_user_cumulate(&self, range: DateRange) -> Result<Vec<UserCumulate>> {
+        self.analytics.user_cumulate(range).await
+    }
+
+    /// Lists drafts with pagination.
+    pub async fn list_drafts(&self, offset: u32, count: u32) -> Result<Vec<DraftInfo>> {
+        self.draft_manager.list_drafts(offset, count).await
+    }
+
+    /// Deletes all drafts for which `predicate` returns true, e.g. to clean up drafts
+    /// left behind by test uploads:
+    ///
+    /// ```rust,no_run
+    /// use wechat_pub_rs::upload::DeleteOptions;
+    ///
+    /// # async fn example(client: wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+    /// let is_test = |draft: &wechat_pub_rs::upload::DraftInfo| {
+    ///     draft.content.news_item.iter().any(|article| article.title.starts_with("[test]"))
+    /// };
+    /// let preview = client
+    ///     .delete_drafts_matching(is_test, DeleteOptions::default().dry_run(true))
+    ///     .await?;
+    /// println!("{preview}");
+    /// client.delete_drafts_matching(is_test, DeleteOptions::default()).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Drafts are deleted one at a time with a pause in between; a draft that can't be
+    /// deleted is reported in [`DeletionReport::failed`] without stopping the others.
+    pub async fn delete_drafts_matching(
+        &self,
+        predicate: impl Fn(&DraftInfo) -> bool,
+        options: DeleteOptions,
+    ) -> Result<DeletionReport> {
+        let _operation = self.begin_operation().await?;
+        self.draft_manager
+            .delete_drafts_matching(predicate, options)
+            .await
+    }
+
+    /// Finds image materials whose name matches a wildcard pattern (`*` and `?`,
+    /// ignoring case), e.g. to reuse or clean up assets uploaded by hand.
+    pub async fn find_materials_by_name(&self, pattern: &str) -> Result<Vec<MaterialItem>> {
+        self.image_uploader.find_materials_by_name(pattern).await
+    }
+
+    /// Deletes all image materials last updated before `cutoff`, paced and reported
+    /// like [`delete_drafts_matching`](Self::delete_drafts_matching).
+    ///
+    /// Materials still used by drafts or published articles are deleted too, so check
+    /// with a dry run first.
+    pub async fn delete_materials_older_than(
+        &self,
+        cutoff: DateTime<Utc>,
+        options: DeleteOptions,
+    ) -> Result<DeletionReport> {
+        let _operation = self.begin_operation().await?;
+        self.image_uploader
+            .delete_materials_older_than(cutoff, options)
+            .await
+    }
+
+    /// Uploads a single image file and returns the WeChat URL.
+    pub async fn upload_image(&self, image_path: &str) -> Result<String> {
+        let _operation = self.begin_operation().await?;
+        let image_path = Path::new(image_path);
+
+        if !self.path_policy().file_exists(image_path).await {
+            return Err(WeChatError::FileNotFound {
+                path: image_path.display().to_string(),
+            });
+        }
+
+        if !utils::is_image_file(image_path) {
+            return Err(WeChatError::config_error(
+                "File is not a supported image format",
+            ));
+        }
+
+        // Create a dummy image reference for uploading
+        let image_ref = crate::markdown::ImageRef::new(
+            "Uploaded image".to_string(),
+            image_path.display().to_string(),
+            (0, 0),
+        );
+
+        let base_dir = utils::get_base_directory(image_path).unwrap_or_else(|| Path::new("."));
+
+        let results = self
+            .image_uploader
+            .upload_images(vec![image_ref], base_dir)
+            .await?;
+
+        Ok(results.into_iter().next().unwrap().url)
+    }
+
+    /// Creates a draft with custom articles.
+    ///
+    /// For HTML rendered outside the SDK; covers and images must already be uploaded,
+    /// e.g. with [`upload_image`](Self::upload_image).
+    pub async fn create_draft(&self, articles: Vec<Article>) -> Result<String> {
+        let _operation = self.begin_operation().await?;
+        self.draft_manager.create_draft(articles).await
+    }
+
+    /// Creates a draft from HTML rendered outside the SDK, uploading the cover image
+    /// first. Returns the draft's media ID.
+    ///
+    /// The HTML is sent as is: images in it must already point at WeChat URLs, e.g.
+    /// from [`upload_image`](Self::upload_image). Use [`create_draft`](Self::create_draft)
+    /// to set the author, digest or other fields.
+    ///
+    /// ```rust,no_run
+    /// # async fn example(client: wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+    /// let draft_id = client
+    ///     .upload_html("Weekly Digest", "<p>Hello from my own renderer</p>", "cover.jpg")
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn upload_html(
+        &self,
+        title: impl Into<String>,
+        html: impl Into<String>,
+        cover: impl AsRef<Path>,
+    ) -> Result<String> {
+        let _operation = self.begin_operation().await?;
+        let cover = cover.as_ref();
+
+        if !self.path_policy().file_exists(cover).await {
+            return Err(WeChatError::FileNotFound {
+                path: cover.display().to_string(),
+            });
+        }
+        if !utils::is_image_file(cover) {
+            return Err(WeChatError::config_error(
+                "Cover file is not a supported image format",
+            ));
+        }
+
+        let cover = self.image_uploader.upload_cover(cover).await?;
+        let article =
+            Article::new(title.into(), String::new(), html.into()).with_cover_image(cover.media_id);
+        self.draft_manager.create_draft(vec![article]).await
+    }
+
+    /// Gets the list of available themes.
+    pub fn available_themes(&self) -> Vec<&String> {
+        self.theme_manager.available_themes()
+    }
+
+    /// Gets the names of the available code highlight themes, sorted.
+    pub fn available_code_themes(&self) -> Vec<&String> {
+        self.theme_manager.available_code_themes()
+    }
+
+    /// Gets the name of the syntect theme a code highlight theme is paired with.
+    pub fn code_theme_pairing(&self, code_theme: &str) -> Option<&str> {
+        self.theme_manager
+            .highlights()
+            .syntect_theme_name(code_theme)
+    }
+
+    /// Gets every code highlight theme with the syntect theme it is paired with, sorted
+    /// by code theme.
+    pub fn code_theme_pairings(&self) -> Vec<(&str, &str)> {
+        self.theme_manager.highlights().pairings()
+    }
+
+    /// Checks if a theme exists.
+    pub fn has_theme(&self, theme: &str) -> bool {
+        self.theme_manager.has_theme(theme)
+    }
+
+    /// Gets the metadata of a theme, e.g. for a theme picker.
+    pub fn theme_info(&self, theme: &str) -> Option<&ThemeInfo> {
+        self.theme_manager.theme_info(theme)
+    }
+
+    /// Gets the metadata of all themes, sorted by name.
+    pub fn themes_with_info(&self) -> Vec<&ThemeInfo> {
+        self.theme_manager.themes_with_info()
+    }
+
+    /// Gets the registry of code highlight themes, e.g. to register custom pairs.
+    pub fn highlights_mut(&mut self) -> &mut HighlightRegistry {
+        Arc::make_mut(&mut self.theme_manager).highlights_mut()
+    }
+
+    /// Gets the registry resolving code fence languages, e.g. to add aliases or syntaxes.
+    pub fn syntaxes_mut(&mut self) -> &mut SyntaxRegistry {
+        Arc::make_mut(&mut self.theme_manager).syntaxes_mut()
+    }
+
+    /// Gets access token information for debugging.
+    pub async fn get_token_info(&self) -> Option<crate::auth::TokenInfo> {
+        self.token_manager.get_token_info().await
+    }
+
+    /// Pre-fetches the access token so the first real call doesn't pay for it.
+    ///
+    /// Useful in request-handling services, right after the client is created.
+    pub async fn warm_up(&self) -> Result<()> {
+        self.token_manager.get_access_token().await?;
+        info!("Access token warmed up");
+        Ok(())
+    }
+
+    /// Pre-fetches both the access token and the JS-SDK (`jsapi`) ticket.
+    pub async fn warm_up_with_jsapi_ticket(&self) -> Result<()> {
+        self.warm_up().await?;
+        self.token_manager.get_jsapi_ticket().await?;
+        info!("jsapi ticket warmed up");
+        Ok(())
+    }
+
+    /// Gets a valid JS-SDK (`jsapi`) ticket, fetching it if necessary.
+    pub async fn get_jsapi_ticket(&self) -> Result<String> {
+        self.token_manager.get_jsapi_ticket().await
+    }
+
+    /// Gets a valid card (`wx_card`) ticket, fetching it if necessary.
+    pub async fn get_wx_card_ticket(&self) -> Result<String> {
+        self.token_manager.get_wx_card_ticket().await
+    }
+
+    /// Creates the signed `wx.config` parameters for a web page using the JS-SDK, see
+    /// [`JsSdkConfig`](crate::auth::JsSdkConfig).
+    ///
+    /// ```rust,no_run
+    /// # async fn example(client: wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+    /// let config = client.js_sdk_config("https://example.com/share?id=1").await?;
+    /// // Hand it to the page, e.g. as JSON for `wx.config({ ...config, jsApiList })`
+    /// let json = serde_json::to_string(&config).unwrap();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn js_sdk_config(&self, url: &str) -> Result<JsSdkConfig> {
+        self.token_manager.js_sdk_config(url).await
+    }
+
+    /// Checks that credentials and the network environment are usable.
+    ///
+    /// Fetches an access token and calls a cheap API endpoint, then reports a typed
+    /// [`HealthDiagnosis`]. In particular, an egress IP missing from the account's IP
+    /// whitelist (errcode 40164) is reported as [`HealthDiagnosis::IpNotWhitelisted`].
+    pub async fn health_check(&self) -> HealthReport {
+        let started = Instant::now();
+
+        let access_token = match self.token_manager.get_access_token().await {
+            Ok(token) => token,
+            Err(e) => {
+                return HealthReport {
+                    token_ok: false,
+                    api_ok: false,
+                    diagnosis: HealthDiagnosis::from_error(&e),
+                    elapsed: started.elapsed(),
+                };
+            }
+        };
+
+        let probe = self.fetch_ip_list(endpoints::CALLBACK_IP.path, &access_token);
+
+        let diagnosis = match probe.await {
+            Ok(_) => HealthDiagnosis::Healthy,
+            Err(e) => HealthDiagnosis::from_error(&e),
+        };
+
+        HealthReport {
+            token_ok: true,
+            api_ok: diagnosis.is_healthy(),
+            diagnosis,
+            elapsed: started.elapsed(),
+        }
+    }
+
+    /// Summarizes the account in one call, e.g. for status pages.
+    ///
+    /// Gets the material counts, the number of drafts and published articles, the
+    /// remaining daily quota of [`overview::QUOTA_ENDPOINTS`] and the token expiry. The
+    /// quota API is restricted on some accounts, so quotas WeChat won't report are left
+    /// out instead of failing the overview.
+    pub async fn account_overview(&self) -> Result<AccountOverview> {
+        let access_token = self.token_manager.get_access_token().await?;
+
+        let materials = async {
+            let response = self
+                .http_client
+                .get_with_token(endpoints::MATERIAL_COUNT.path, &access_token)
+                .await?;
+            let api_response: WeChatResponse<MaterialCounts> = http::read_json(response).await?;
+            api_response.into_result()
+        };
+        let drafts = async {
+            let response = self
+                .http_client
+                .get_with_token(endpoints::DRAFT_COUNT.path, &access_token)
+                .await?;
+            let api_response: WeChatResponse<CountResponse> = http::read_json(response).await?;
+            Ok(api_response.into_result()?.total_count)
+        };
+        let published = async {
+            let request = serde_json::json!({ "offset": 0, "count": 1, "no_content": 1 });
+            let response = self
+                .http_client
+                .post_json_with_token(endpoints::PUBLISH_BATCHGET.path, &access_token, &request)
+                .await?;
+            let api_response: WeChatResponse<CountResponse> = http::read_json(response).await?;
+            Ok(api_response.into_result()?.total_count)
+        };
+        let (materials, drafts, published) = tokio::try_join!(materials, drafts, published)?;
+
+        let mut quotas = BTreeMap::new();
+        for endpoint in overview::QUOTA_ENDPOINTS {
+            match self.fetch_quota(endpoint.path, &access_token).await {
+                Ok(quota) => {
+                    quotas.insert(endpoint.name.to_string(), quota);
+                }
+                Err(e) => warn!("Failed to get quota of {}: {e}", endpoint.name),
+            }
+        }
+
+        Ok(AccountOverview {
+            materials,
+            drafts,
+            published,
+            quotas,
+            token_expires_at: self
+                .token_manager
+                .get_token_info()
+                .await
+                .map(|info| info.expires_at),
+        })
+    }
+
+    /// Lists published articles, newest first (`freepublish/batchget`).
+    ///
+    /// `offset` and `count` page through publish jobs, at most 20 per call; a job with
+    /// several articles yields one [`PublishHistoryEntry`] each, and articles deleted
+    /// after publishing are left out.
+    pub async fn list_published(
+        &self,
+        offset: u32,
+        count: u32,
+    ) -> Result<Vec<PublishHistoryEntry>> {
+        let access_token = self.token_manager.get_access_token().await?;
+        let request = serde_json::json!({ "offset": offset, "count": count, "no_content": 1 });
+        let response = self
+            .http_client
+            .post_json_with_token(endpoints::PUBLISH_BATCHGET.path, &access_token, &request)
+            .await?;
+        let api_response: WeChatResponse<PublishedListResponse> = http::read_json(response).await?;
+        Ok(api_response.into_result()?.into_articles())
+    }
+
+    /// Gets the articles of a publish job by the article ID of its [`PublishedArticle`] or
+    /// [`PublishHistoryEntry`]s (`freepublish/getarticle`), leaving out deleted articles.
+    pub async fn get_published_articles(
+        &self,
+        article_id: &str,
+    ) -> Result<Vec<PublishHistoryEntry>> {
+        let access_token = self.token_manager.get_access_token().await?;
+        let request = serde_json::json!({ "article_id": article_id });
+        let response = self
+            .http_client
+            .post_json_with_token(endpoints::PUBLISH_GETARTICLE.path, &access_token, &request)
+            .await?;
+        let api_response: WeChatResponse<PublishedArticleResponse> =
+            http::read_json(response).await?;
+        Ok(api_response.into_result()?.into_articles(article_id))
+    }
+
+    /// Gets the permanent `mp.weixin.qq.com` URL of a published article by its article
+    /// ID, e.g. to post it to a chat or social media after publishing.
+    ///
+    /// For a job with several articles this is the URL of the first one that wasn't
+    /// deleted; `None` if all of them were.
+    ///
+    /// ```rust,no_run
+    /// # async fn example(client: wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+    /// if let Some(url) = client.get_article_url("b5O2OUs25HBxRceL7hfReg").await? {
+    ///     println!("Read it at {url}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_article_url(&self, article_id: &str) -> Result<Option<String>> {
+        let articles = self.get_published_articles(article_id).await?;
+        Ok(articles.into_iter().next().map(|article| article.url))
+    }
+
+    /// Publishes a draft (`freepublish/submit`) and returns the ID of its publish job.
+    ///
+    /// `markdown_path` is the file the draft was uploaded from, if any. The job is then
+    /// remembered for a day: when the callback server receives its `PublishJobFinish`
+    /// event, [`handle_publish_event`](Self::handle_publish_event) passes the outcome with
+    /// the path to the [`on_publish_result`](UploadHooks::on_publish_result) hook. Without
+    /// a path or a callback server, follow the job with
+    /// [`wait_until_published`](Self::wait_until_published).
+    pub async fn publish_draft(
+        &self,
+        media_id: &str,
+        markdown_path: Option<&Path>,
+    ) -> Result<String> {
+        let _operation = self.begin_operation().await?;
+        let access_token = self.token_manager.get_access_token().await?;
+        let request = serde_json::json!({ "media_id": media_id });
+        let response = self
+            .http_client
+            .post_json_with_token(endpoints::PUBLISH_SUBMIT.path, &access_token, &request)
+            .await?;
+        let api_response: WeChatResponse<PublishSubmitResponse> = http::read_json(response).await?;
+        let publish_id = api_response.into_result()?.publish_id;
+
+        info!(publish_id, media_id, "Submitted draft for publishing");
+        if let Some(markdown_path) = markdown_path {
+            self.publish_jobs
+                .lock()
+                .unwrap()
+                .insert(publish_id.clone(), markdown_path.display().to_string());
+        }
+        Ok(publish_id)
+    }
+
+    /// Notifies the hooks of the outcome of a publish job submitted with
+    /// [`publish_draft`](Self::publish_draft) from its `PublishJobFinish` event.
+    ///
+    /// Returns whether the event finished one of the client's jobs. Jobs are tracked in
+    /// memory for a day, at most the 1024 most recent ones, so the events of jobs
+    /// submitted without a markdown path, by another process or before a restart, and all
+    /// other events, are ignored.
+    ///
+    /// ```rust,no_run
+    /// use wechat_pub_rs::server::{Message, MessageKind};
+    ///
+    /// # async fn example(client: wechat_pub_rs::WeChatClient, body: &str) -> wechat_pub_rs::Result<()> {
+    /// let message = Message::parse(body)?;
+    /// if let MessageKind::Event(event) = &message.kind {
+    ///     client.handle_publish_event(event).await;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "server")]
+    pub async fn handle_publish_event(&self, event: &crate::server::Event) -> bool {
+        let crate::server::Event::PublishJobFinish { publish_id, status } = event else {
+            return false;
+        };
+        let Some(markdown_path) = self.publish_jobs.lock().unwrap().remove(publish_id) else {
+            debug!(publish_id, "Ignoring the result of an unknown publish job");
+            return false;
+        };
+
+        for hooks in &self.hooks.0 {
+            hooks
+                .on_publish_result(&markdown_path, publish_id, status)
+                .await;
+        }
+        true
+    }
+
+    /// Gets the status of a publish job (`freepublish/get`).
+    pub async fn get_publish_status(&self, publish_id: &str) -> Result<PublishStatus> {
+        let access_token = self.token_manager.get_access_token().await?;
+        let request = serde_json::json!({ "publish_id": publish_id });
+        let response = self
+            .http_client
+            .post_json_with_token(endpoints::PUBLISH_GET.path, &access_token, &request)
+            .await?;
+        let api_response: WeChatResponse<PublishStatusResponse> = http::read_json(response).await?;
+        Ok(api_response.into_result()?.into_status())
+    }
+
+    /// Polls a publish job until WeChat finished it and returns the published article.
+    ///
+    /// The first poll is immediate; the pause between polls starts at the retry config's
+    /// base delay and grows by its backoff factor up to its maximum delay. Fails with
+    /// [`WeChatError::PublishFailed`] if the job failed, or [`WeChatError::Timeout`] if
+    /// it is still running after `timeout`.
+    pub async fn wait_until_published(
+        &self,
+        publish_id: &str,
+        timeout: Duration,
+    ) -> Result<PublishedArticle> {
+        let config = self.http_client.config();
+        let deadline = Instant::now() + timeout;
+        let mut delay = config.retry_base_delay();
+
+        loop {
+            match self.get_publish_status(publish_id).await? {
+                PublishStatus::Published(article) => return Ok(article),
+                PublishStatus::Failed {
+                    reason,
+                    failed_articles,
+                } => {
+                    return Err(WeChatError::PublishFailed {
+                        publish_id: publish_id.to_string(),
+                        reason,
+                        failed_articles,
+                    });
+                }
+                PublishStatus::Publishing => {}
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(WeChatError::Timeout);
+            }
+            debug!(publish_id, "Still publishing, checking again in {delay:?}");
+            tokio::time::sleep(delay.min(remaining)).await;
+            delay = delay
+                .mul_f64(config.retry.backoff_factor)
+                .min(config.retry_max_delay());
+        }
+    }
+
+    /// Gets the IP addresses WeChat's API domain resolves to (`get_api_domain_ip`).
+    ///
+    /// Useful for keeping egress firewall allowlists up to date.
+    pub async fn get_api_domain_ips(&self) -> Result<Vec<String>> {
+        let access_token = self.token_manager.get_access_token().await?;
+        self.fetch_ip_list(endpoints::API_DOMAIN_IP.path, &access_token)
+            .await
+    }
+
+    /// Gets the IP ranges WeChat sends callbacks from (`getcallbackip`).
+    ///
+    /// Useful for keeping ingress firewall allowlists up to date.
+    pub async fn get_callback_ips(&self) -> Result<Vec<String>> {
+        let access_token = self.token_manager.get_access_token().await?;
+        self.fetch_ip_list(endpoints::CALLBACK_IP.path, &access_token)
+            .await
+    }
+
+    /// Forces a token refresh.
+    pub async fn refresh_token(&self) -> Result<String> {
+        self.token_manager.force_refresh().await
+    }
+
+    /// Calls an API endpoint with a JSON body and returns the raw JSON response.
+    ///
+    /// An escape hatch for endpoints the SDK doesn't wrap yet. The access token is
+    /// appended to `endpoint` (which may carry its own query string), failed requests
+    /// are retried like every other call, and a nonzero `errcode` in the response is
+    /// returned as [`WeChatError::WeChatApi`]. If the token was rejected, it is
+    /// refreshed and the call is made once more.
+    ///
+    /// ```rust,no_run
+    /// # async fn example(client: &wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+    /// let count = client
+    ///     .raw_post("/cgi-bin/draft/count", serde_json::json!({}))
+    ///     .await?;
+    /// println!("Drafts: {}", count["total_count"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn raw_post(
+        &self,
+        endpoint: &str,
+        body: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        self.raw_call(endpoint, Some(&body)).await
+    }
+
+    /// Calls an API endpoint with a GET request and returns the raw JSON response.
+    ///
+    /// See [`raw_post`](Self::raw_post) for how tokens, retries and errors are handled.
+    pub async fn raw_get(&self, endpoint: &str) -> Result<serde_json::Value> {
+        self.raw_call(endpoint, None).await
+    }
+
+    /// Gets the web authorization helper, which logs followers in to web pages with the
+    /// client's app credentials, see [`oauth`](crate::oauth).
+    pub fn oauth(&self) -> &OAuthManager {
+        &self.oauth
+    }
+
+    /// Gets the queue pacing quota-expensive calls (material uploads, draft writes) made by
+    /// this client, e.g. to limit material uploads across parallel article uploads:
+    /// `client.operation_queue().pace(QuotaClass::Material, 30)`.
+    pub fn operation_queue(&self) -> &OperationQueue {
+        self.http_client.operation_queue()
+    }
+
+    /// Gets the underlying HTTP client for advanced usage.
+    pub fn http_client(&self) -> &WeChatHttpClient {
+        &self.http_client
+    }
+
+    // Private helper methods
+
+    /// Rules for the paths of local files, from the security configuration.
+    fn path_policy(&self) -> PathPolicy {
+        PathPolicy::from(&self.http_client.config().security)
+    }
+
+    /// Strategy naming documents, from the client configuration.
+    fn slug_strategy(&self) -> SlugStrategy {
+        self.http_client.config().render.slug_strategy
+    }
+
+    /// Checks that the content directory of a markdown file isn't published to another
+    /// account, see [`PublishState::check_account`].
+    async fn check_content_dir_account(&self, markdown_path: &Path) -> Result<()> {
+        PublishState::load(content_dir(markdown_path))
+            .await?
+            .check_account(self.token_manager.app_id())
+    }
+
+    /// Checks the state file before an upload and marks the upload as pending.
+    ///
+    /// Returns [`TrackedUpload::Completed`] if the same content was already uploaded by
+    /// a previous run and its draft still exists.
+    async fn begin_tracked_upload(&self, markdown_path: &Path) -> Result<TrackedUpload> {
+        self.validate_markdown_path(markdown_path).await?;
+
+        let raw = tokio::fs::read(markdown_path).await.map_err(|e| {
+            WeChatError::file_error(markdown_path.display().to_string(), e.to_string())
+        })?;
+        let file_key = state::document_key(markdown_path, &raw, self.slug_strategy());
+        let key = state::idempotency_key(&file_key, &raw);
+
+        let content_dir = content_dir(markdown_path);
+        let state = PublishState::load(content_dir).await?;
+        let app_id = self.token_manager.app_id();
+        state.check_account(app_id)?;
+
+        if let Some(entry) = state.entry(&file_key) {
+            if entry.is_complete_for(&key) {
+                let media_id = entry.media_id.clone().unwrap_or_default();
+                match self.draft_manager.get_draft(&media_id).await {
+                    Ok(draft) => {
+                        info!(
+                            "Content unchanged since draft {media_id} was created, skipping upload"
+                        );
+                        let draft_url = draft
+                            .content
+                            .news_item
+                            .into_iter()
+                            .next()
+                            .and_then(|article| article.url);
+                        log_draft_url(draft_url.as_deref());
+                        return Ok(TrackedUpload::Completed(Box::new(UploadReport {
+                            draft_id: media_id,
+                            images: Vec::new(),
+                            cover_media_id: entry.thumb_media_id.clone().unwrap_or_default(),
+                            cover_reused: true,
+                            draft_url,
+                            unchanged: true,
+                            dry_run: None,
+                        })));
+                    }
+                    Err(e) if e.is_invalid_media_id() => {
+                        warn!("Recorded draft {media_id} is gone ({e}), uploading again");
+                    }
+                    Err(e) => return Err(e),
+                }
+            } else if entry.idempotency_key == key && entry.pending_since.is_some() {
+                // The draft may have been created even though the previous attempt failed;
+                // draft creation matches existing drafts by title, so no duplicate is made.
+                warn!(
+                    "Previous upload of {} did not complete, checking for its draft by title",
+                    markdown_path.display()
+                );
+            }
+        }
+
+        PublishState::update(content_dir, |state| {
+            state.app_id.get_or_insert_with(|| app_id.to_string());
+            let entry = state.entry_mut(&file_key);
+            entry.idempotency_key = key;
+            entry.content_hash = state::content_hash(&raw);
+            entry.pending_since = Some(Utc::now());
+        })
+        .await?;
+
+        Ok(TrackedUpload::Started(state, file_key))
+    }
+
+    async fn raw_call(
+        &self,
+        endpoint: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        if !endpoint.starts_with('/') {
+            return Err(WeChatError::config_error(format!(
+                "API endpoint must start with '/': {endpoint}"
+            )));
+        }
+
+        let mut access_token = self.token_manager.get_access_token().await?;
+        let mut refreshed = false;
+
+        loop {
+            let response = match body {
+                Some(body) => {
+                    self.http_client
+                        .post_json_with_token(endpoint, &access_token, body)
+                        .await?
+                }
+                None => {
+                    self.http_client
+                        .get_with_token(endpoint, &access_token)
+                        .await?
+                }
+            };
+
+            match http::check_raw_response(http::read_json(response).await?) {
+                Err(e) if e.is_access_token_error() && !refreshed => {
+                    warn!("Access token rejected by {endpoint}, refreshing and retrying");
+                    access_token = self.token_manager.force_refresh().await?;
+                    refreshed = true;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn fetch_ip_list(&self, endpoint: &str, access_token: &str) -> Result<Vec<String>> {
+        let response = self
+            .http_client
+            .get_with_token(endpoint, access_token)
+            .await?;
+        let api_response: WeChatResponse<IpListResponse> = http::read_json(response).await?;
+        Ok(api_response.into_result()?.ip_list)
+    }
+
+    async fn fetch_quota(
+        &self,
+        endpoint: &str,
+        access_token: &str,
+    ) -> Result<overview::QuotaUsage> {
+        let request = serde_json::json!({ "cid_or_uri": endpoint });
+        let response = self
+            .http_client
+            .post_json_with_token(endpoints::QUOTA_GET.path, access_token, &request)
+            .await?;
+        let api_response: WeChatResponse<QuotaResponse> = http::read_json(response).await?;
+        Ok(api_response.into_result()?.quota)
+    }
+
+    async fn validate_markdown_path(&self, markdown_path: &Path) -> Result<()> {
+        // Check if markdown file exists
+        if !self.path_policy().file_exists(markdown_path).await {
+            return Err(WeChatError::FileNotFound {
+                path: markdown_path.display().to_string(),
+            });
+        }
+
+        // Check if it's a markdown file
+        if !utils::is_markdown_file(markdown_path) {
+            return Err(WeChatError::config_error(
+                "File is not a markdown file (.md or .markdown)",
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn validate_upload_input(
+        &self,
+        markdown_path: &Path,
+        options: &UploadOptions,
+    ) -> Result<()> {
+        self.validate_markdown_path(markdown_path).await?;
+
+        // Before parsing, so an invalid date is reported along with the other violations
+        self.validate_frontmatter(markdown_path).await?;
+
+        // Theme validation will happen later when we determine the actual theme to use
+
+        // Parse markdown to check for frontmatter cover
+        let content = self.parse_markdown_file(markdown_path).await?;
+
+        // Check that cover image is provided either via options or frontmatter
+        let has_cover_option = options.cover_image.is_some();
+        let has_cover_frontmatter = content.cover.is_some();
+
+        if !has_cover_option && !has_cover_frontmatter {
+            return Err(WeChatError::config_error(
+                "Cover image is required. Please provide via --cover-image option or 'cover:' in frontmatter",
+            ));
+        }
+
+        // Validate cover image from options if specified
+        if let Some(cover_path) = &options.cover_image {
+            let base_dir =
+                utils::get_base_directory(markdown_path).unwrap_or_else(|| Path::new("."));
+
+            let resolved_cover_path = utils::join_reference(base_dir, cover_path);
+
+            if !self.path_policy().file_exists(&resolved_cover_path).await {
+                return Err(WeChatError::FileNotFound {
+                    path: resolved_cover_path.display().to_string(),
+                });
+            }
+
+            if !utils::is_image_file(&resolved_cover_path) {
+                return Err(WeChatError::config_error(
+                    "Cover file is not a supported image format",
+                ));
+            }
+        }
+
+        // Validate cover image from frontmatter if specified
+        if let Some(cover_path) = &content.cover {
+            let base_dir =
+                utils::get_base_directory(markdown_path).unwrap_or_else(|| Path::new("."));
+
+            let resolved_cover_path = utils::join_reference(base_dir, cover_path);
+
+            if !self.path_policy().file_exists(&resolved_cover_path).await {
+                return Err(WeChatError::FileNotFound {
+                    path: resolved_cover_path.display().to_string(),
+                });
+            }
+
+            if !utils::is_image_file(&resolved_cover_path) {
+                return Err(WeChatError::config_error(
+                    "Cover file specified in frontmatter is not a supported image format",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the shared upload pipeline and builds the article without touching drafts.
+    async fn prepare_article(
+        &self,
+        markdown_path: &Path,
+        options: &UploadOptions,
+        work_dir: Option<&Path>,
+    ) -> Result<PreparedArticle> {
+        // Validate input
+        self.validate_upload_input(markdown_path, options).await?;
+
+        // Step 1: Parse markdown content and process Mermaid charts
+        let (mut content, _) = self.load_content(markdown_path, options, work_dir).await?;
+        let known_media_id = content.wechat_media_id().map(str::to_string);
+        let base_dir = utils::get_base_directory(markdown_path).unwrap_or_else(|| Path::new("."));
+        let slug = self.slug_strategy().slug(markdown_path, &content);
+
+        // Step 2: Resolve the theme up front so a bad theme fails before any upload
+        let theme = self.resolve_theme(&content, options)?;
+
+        // Step 3: Point images at placeholders so rendering doesn't wait for uploads
+        let placeholders = image_placeholders(&content);
+        let mut render_source = content.clone();
+        render_source.replace_image_urls(&placeholders)?;
+
+        let image_uploader = self
+            .image_uploader
+            .clone()
+            .with_image_processing(options.image_processing)
+            .with_format_policy(options.image_formats.clone())
+            .with_material_naming(options.material_naming)
+            .with_document_slug(slug.clone());
+        let image_uploader = match options.image_timeout {
+            Some(timeout) => image_uploader.with_image_timeout(timeout),
+            None => image_uploader,
+        };
+        let image_uploader = if options.cache_assets {
+            image_uploader.with_asset_store(AssetStore::for_content_dir(base_dir))
+        } else {
+            image_uploader
+        };
+
+        // Step 4: Upload images (then the cover) while the theme renders. The cover goes
+        // after the body images so that a cover reused in the body hits the material cache.
+        let cover_path = options
+            .cover_image
+            .as_ref()
+            .or(content.cover.as_ref())
+            .expect("Cover image should be available from validation");
+
+        let images_span = info_span!("images", image_count = content.images.len());
+        let uploads = async {
+            let upload_results = image_uploader
+                .upload_images(content.images.clone(), base_dir)
+                .await?;
+            info!("Completed uploading {} images", upload_results.len());
+
+            info!("Starting to upload cover image: {}", cover_path);
+            let cover = self
+                .upload_cover_image(&image_uploader, cover_path, base_dir)
+                .await?;
+            info!("Completed uploading cover image (reused: {})", cover.reused);
+
+            Ok::<_, WeChatError>((upload_results, cover))
+        }
+        .instrument(images_span);
+        // Uploads run on spawned tasks and rendering on the blocking thread pool, so both
+        // make progress at once; the upload future is polled first to get them started.
+        let render_span = info_span!(
+            "render",
+            theme = theme.as_str(),
+            html_bytes = tracing::field::Empty
+        );
+        let render = async {
+            let html = self
+                .render_content(&render_source, theme, options, Some(HERO_PLACEHOLDER))
+                .await?;
+            Span::current().record("html_bytes", html.len());
+            Ok::<_, WeChatError>(html)
+        }
+        .instrument(render_span);
+
+        let (uploads, rendered) = tokio::join!(uploads, render);
+        let (upload_results, cover) = uploads?;
+        let rendered = rendered?;
+
+        // Step 5: Swap placeholders for the uploaded URLs
+        let url_mapping = self.draft_manager.create_url_mapping(&upload_results);
+        let finish = |html: String| {
+            let html = fill_image_placeholders(html, &placeholders, &url_mapping)
+                .replace(HERO_PLACEHOLDER, &escape_attribute(&cover.url));
+            apply_html_transform(html, options)
+        };
+        let html_content = finish(rendered)?;
+
+        // Step 6: Check the size budget, splitting the article at its headings if allowed
+        let parts = if options.content_budget.fits_html(&html_content) {
+            vec![html_content]
+        } else {
+            let report = self
+                .measure_size(&render_source, theme, options, &html_content, &finish)
+                .await?;
+            if !options.split_long_articles {
+                return Err(content_too_large(&report));
+            }
+
+            let sections = render_source.sections();
+            let mut parts = Vec::new();
+            for (index, range) in report.suggested_parts().into_iter().enumerate() {
+                let mut part = render_source.clone();
+                part.content = sections[range]
+                    .iter()
+                    .map(|section| section.markdown.as_str())
+                    .collect();
+                let hero_image = (index == 0).then_some(HERO_PLACEHOLDER);
+                let html = finish(
+                    self.render_content(&part, theme, options, hero_image)
+                        .await?,
+                )?;
+                if !options.content_budget.fits_html(&html) {
+                    return Err(content_too_large(&report));
+                }
+                parts.push(html);
+            }
+            info!(
+                "Split article into {} parts to fit the size budget",
+                parts.len()
+            );
+            parts
+        };
+        content.replace_image_urls(&url_mapping)?;
+
+        let part_count = parts.len();
+        let articles = parts
+            .into_iter()
+            .enumerate()
+            .map(|(index, html_content)| {
+                let mut article = self.create_article(
+                    &content,
+                    options,
+                    html_content,
+                    Some(cover.media_id.clone()),
+                    &slug,
+                );
+                if part_count > 1 {
+                    article.title = format!("{} ({}/{part_count})", article.title, index + 1);
+                }
+                article
+            })
+            .collect();
+
+        Ok(PreparedArticle {
+            articles,
+            known_media_id,
+            images: upload_results,
+            cover,
+        })
+    }
+
+    /// Gets the registered upload queue.
+    fn require_upload_queue(&self) -> Result<&UploadQueue> {
+        self.upload_queue.as_ref().ok_or_else(|| {
+            WeChatError::config_error("No upload queue registered, see with_upload_queue")
+        })
+    }
+
+    /// Renders an article into the upload queue, copying its local images and cover.
+    async fn enqueue_article(
+        &self,
+        queue: &UploadQueue,
+        markdown_path: &Path,
+        options: &UploadOptions,
+        work_dir: Option<&Path>,
+    ) -> Result<QueuedUpload> {
+        reject_deferred_options(options, "Queued")?;
+        let rendered = self
+            .render_for_send(markdown_path, options, work_dir)
+            .await?;
+        if let Some(report) = &rendered.oversize {
+            return Err(content_too_large(report));
+        }
+
+        // Local images (generated charts included) are copied, as they may be gone by
+        // the time the upload is sent
+        let path_policy = self.path_policy();
+        let mut images: Vec<QueuedImage> = Vec::new();
+        let mut files = Vec::new();
+        for image in &rendered.content.images {
+            let placeholder = &rendered.placeholders[&image.original_url];
+            if images
+                .iter()
+                .any(|queued| &queued.placeholder == placeholder)
+            {
+                continue;
+            }
+            let source = if image.is_local {
+                let path = image.resolve_path_with(&rendered.base_dir, &path_policy)?;
+                let blob = upload_queue::blob_path(&images.len().to_string(), &path);
+                files.push((blob.clone(), path));
+                blob
+            } else {
+                image.original_url.clone()
+            };
+            images.push(QueuedImage {
+                placeholder: placeholder.clone(),
+                source,
+                title: image.title.clone(),
+            });
+        }
+
+        let cover_path = utils::join_reference(&rendered.base_dir, &rendered.cover);
+        let cover = upload_queue::blob_path("cover", &cover_path);
+        files.push((cover.clone(), cover_path));
+
+        let payload = queued_payload(rendered, images, cover, options);
+        queue.push(markdown_path, payload, files).await
+    }
+
+    /// Renders an article for [`prepare_with_options`](Self::prepare_with_options).
+    async fn prepare_article_upload(
+        &self,
+        markdown_path: &Path,
+        options: &UploadOptions,
+    ) -> Result<PreparedUpload<'_>> {
+        reject_deferred_options(options, "Prepared")?;
+        let (plan, payload, base_dir) = self.plan_upload(markdown_path, options, None).await?;
+        Ok(PreparedUpload {
+            client: self,
+            markdown_path: markdown_path.to_path_buf(),
+            base_dir,
+            payload,
+            plan,
+        })
+    }
+
+    /// Renders an article, planning its image uploads and checking it. Returns the plan,
+    /// what to send and the directory its references are relative to.
+    async fn plan_upload(
+        &self,
+        markdown_path: &Path,
+        options: &UploadOptions,
+        work_dir: Option<&Path>,
+    ) -> Result<(UploadPlan, QueuedPayload, PathBuf)> {
+        let rendered = self
+            .render_for_send(markdown_path, options, work_dir)
+            .await?;
+
+        let mut issues = Vec::new();
+        // Split articles only have to fit the budget part by part
+        if let Some(report) = &rendered.oversize
+            && !options.split_long_articles
+        {
+            issues.push(content_too_large(report).to_string());
+        }
+
+        let path_policy = self.path_policy();
+        let mut planned = Vec::new();
+        let mut images: Vec<QueuedImage> = Vec::new();
+        for image in &rendered.content.images {
+            let placeholder = &rendered.placeholders[&image.original_url];
+            if images
+                .iter()
+                .any(|queued| &queued.placeholder == placeholder)
+            {
+                continue;
+            }
+            let path = if image.is_local {
+                match image.resolve_path_with(&rendered.base_dir, &path_policy) {
+                    Ok(path) => Some(path),
+                    Err(e) => {
+                        issues.push(e.to_string());
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            planned.push(plan_image(image.original_url.clone(), path, &mut issues).await);
+            // References stay relative to the markdown file, as in a direct upload
+            images.push(QueuedImage {
+                placeholder: placeholder.clone(),
+                source: image.original_url.clone(),
+                title: image.title.clone(),
+            });
+        }
+
+        let cover_path = utils::join_reference(&rendered.base_dir, &rendered.cover);
+        let cover = plan_image(rendered.cover.clone(), Some(cover_path), &mut issues).await;
+
+        let html = fill_image_placeholders(
+            rendered.article.content.clone(),
+            &rendered.placeholders,
+            &HashMap::new(),
+        )
+        .replace(HERO_PLACEHOLDER, &escape_attribute(&rendered.cover));
+        let validation = ValidationReport {
+            chars: rendered.article.content.chars().count(),
+            bytes: rendered.article.content.len(),
+            budget: options.content_budget,
+            issues,
+        };
+        let base_dir = rendered.base_dir.clone();
+        let cover_reference = rendered.cover.clone();
+        let payload = queued_payload(rendered, images, cover_reference, options);
+
+        let plan = UploadPlan {
+            html,
+            images: planned,
+            cover,
+            validation,
+        };
+        Ok((plan, payload, base_dir))
     }
 
-    /// Deletes a draft by media ID.
-    pub async fn delete_draft(&self, media_id: &str) -> Result<()> {
-        self.draft_manager.delete_draft(media_id).await
-    }
+    /// Sends a prepared upload, see [`PreparedUpload::commit`].
+    pub(crate) async fn commit_prepared(
+        &self,
+        prepared: &PreparedUpload<'_>,
+    ) -> Result<UploadReport> {
+        let markdown_path = prepared.markdown_path.display().to_string();
+        let result = async {
+            let _operation = self.begin_operation().await?;
+            let validation = &prepared.plan.validation;
+            if !validation.is_ok() {
+                return Err(WeChatError::config_error(format!(
+                    "Prepared upload of {markdown_path} failed validation: {}",
+                    validation.issues.join("; ")
+                )));
+            }
+            info!("Committing prepared upload of {markdown_path}");
+            self.send_payload(&prepared.payload, &prepared.base_dir)
+                .await
+        }
+        .instrument(info_span!("upload", path = %markdown_path))
+        .await;
 
-    /// Lists drafts with pagination.
-    pub async fn list_drafts(&self, offset: u32, count: u32) -> Result<Vec<DraftInfo>> {
-        self.draft_manager.list_drafts(offset, count).await
+        self.notify_hooks(&markdown_path, &result).await;
+        result
     }
 
-    /// Uploads a single image file and returns the WeChat URL.
-    pub async fn upload_image(&self, image_path: &str) -> Result<String> {
-        let image_path = Path::new(image_path);
+    /// Renders an article with placeholders for its image URLs, for sending it later.
+    async fn render_for_send(
+        &self,
+        markdown_path: &Path,
+        options: &UploadOptions,
+        work_dir: Option<&Path>,
+    ) -> Result<RenderedUpload> {
+        self.validate_upload_input(markdown_path, options).await?;
 
-        if !utils::file_exists(image_path).await {
-            return Err(WeChatError::FileNotFound {
-                path: image_path.display().to_string(),
-            });
-        }
+        let (content, _) = self.load_content(markdown_path, options, work_dir).await?;
+        let base_dir = utils::get_base_directory(markdown_path).unwrap_or_else(|| Path::new("."));
+        let slug = self.slug_strategy().slug(markdown_path, &content);
+        let theme = self.resolve_theme(&content, options)?;
 
-        if !utils::is_image_file(image_path) {
-            return Err(WeChatError::config_error(
-                "File is not a supported image format",
-            ));
+        let placeholders = image_placeholders(&content);
+        let mut render_source = content.clone();
+        render_source.replace_image_urls(&placeholders)?;
+        let html = apply_html_transform(
+            self.render_content(&render_source, theme, options, Some(HERO_PLACEHOLDER))
+                .await?,
+            options,
+        )?;
+        let oversize = if options.content_budget.fits_html(&html) {
+            None
+        } else {
+            let finish = |html: String| apply_html_transform(html, options);
+            Some(
+                self.measure_size(&render_source, theme, options, &html, &finish)
+                    .await?,
+            )
+        };
+
+        let cover = options
+            .cover_image
+            .as_ref()
+            .or(content.cover.as_ref())
+            .expect("Cover image should be available from validation")
+            .clone();
+        let article = self.create_article(&content, options, html, None, &slug);
+
+        Ok(RenderedUpload {
+            content,
+            base_dir: base_dir.to_path_buf(),
+            placeholders,
+            article,
+            cover,
+            slug,
+            oversize,
+        })
+    }
+
+    /// Sends a queued upload, removing it from the queue if it was sent and recording
+    /// the failure otherwise. Retryable failures are reported as
+    /// [`WeChatError::Queued`].
+    ///
+    /// The caller holds the queue's lock.
+    async fn deliver_queued(
+        &self,
+        queue: &UploadQueue,
+        upload: &mut QueuedUpload,
+    ) -> Result<UploadReport> {
+        info!(
+            "Sending queued upload {} of {}",
+            upload.id,
+            upload.markdown_path.display()
+        );
+        match self.send_queued(queue, upload).await {
+            Ok(report) => {
+                queue.complete(&upload.id).await?;
+                Ok(report)
+            }
+            Err(e) => {
+                queue.record_failure(upload, &e).await?;
+                if e.is_retryable() {
+                    Err(WeChatError::Queued {
+                        id: upload.id.clone(),
+                        reason: e.to_string(),
+                    })
+                } else {
+                    Err(e)
+                }
+            }
         }
+    }
 
-        // Create a dummy image reference for uploading
-        let image_ref = crate::markdown::ImageRef::new(
-            "Uploaded image".to_string(),
-            image_path.display().to_string(),
-            (0, 0),
+    /// Uploads the images and cover of a queued upload and creates its draft.
+    async fn send_queued(
+        &self,
+        queue: &UploadQueue,
+        upload: &QueuedUpload,
+    ) -> Result<UploadReport> {
+        let report = self
+            .send_payload(&upload.payload, &queue.entry_dir(&upload.id))
+            .await?;
+        info!(
+            "Created draft {} from queued upload {}",
+            report.draft_id, upload.id
         );
+        Ok(report)
+    }
 
-        let base_dir = utils::get_base_directory(image_path).unwrap_or_else(|| Path::new("."));
+    /// Describes a newly created draft, looking up its link only if requested.
+    async fn created_draft(&self, media_id: String, lookup_url: bool) -> SyncedDraft {
+        if lookup_url {
+            self.draft_manager.lookup_draft(&media_id).await
+        } else {
+            SyncedDraft {
+                media_id,
+                update_time: None,
+                url: None,
+            }
+        }
+    }
 
-        let results = self
+    /// Uploads the images and cover of a rendered article and creates its draft. Image
+    /// and cover paths are relative to `base_dir`.
+    async fn send_payload(&self, payload: &QueuedPayload, base_dir: &Path) -> Result<UploadReport> {
+        let image_uploader = self
             .image_uploader
-            .upload_images(vec![image_ref], base_dir)
-            .await?;
+            .clone()
+            .with_image_processing(payload.image_processing)
+            .with_format_policy(payload.image_formats.clone())
+            .with_material_naming(payload.material_naming);
+        let image_uploader = match &payload.document_slug {
+            Some(slug) => image_uploader.with_document_slug(slug.clone()),
+            None => image_uploader,
+        };
 
-        Ok(results.into_iter().next().unwrap().url)
-    }
+        let images = payload.images.iter().map(QueuedImage::image_ref).collect();
+        let upload_results = image_uploader.upload_images(images, base_dir).await?;
+        let cover = image_uploader
+            .upload_cover(&utils::join_reference(base_dir, &payload.cover))
+            .await?;
 
-    /// Creates a draft with custom articles.
-    pub async fn create_draft(&self, articles: Vec<Article>) -> Result<String> {
-        self.draft_manager.create_draft(articles).await
-    }
+        let url_mapping = self.draft_manager.create_url_mapping(&upload_results);
+        let placeholders = payload
+            .images
+            .iter()
+            .map(|image| (image.source.clone(), image.placeholder.clone()))
+            .collect();
+        let mut article = payload.article.clone();
+        article.content = fill_image_placeholders(article.content, &placeholders, &url_mapping)
+            .replace(HERO_PLACEHOLDER, &escape_attribute(&cover.url));
+        article.thumb_media_id = Some(cover.media_id.clone());
 
-    /// Gets the list of available themes.
-    pub fn available_themes(&self) -> Vec<&String> {
-        self.theme_manager.available_themes()
-    }
+        let synced = match &payload.known_media_id {
+            Some(media_id) => {
+                self.draft_manager
+                    .sync_draft(
+                        vec![article],
+                        Some(media_id),
+                        None,
+                        payload.force,
+                        payload.on_existing_draft,
+                    )
+                    .await?
+            }
+            None => {
+                let media_id = self
+                    .draft_manager
+                    .create_draft_with_policy(vec![article], payload.on_existing_draft)
+                    .await?;
+                self.created_draft(media_id, payload.lookup_draft_url).await
+            }
+        };
+        let draft_id = synced.media_id;
+        log_draft_url(synced.url.as_deref());
 
-    /// Checks if a theme exists.
-    pub fn has_theme(&self, theme: &str) -> bool {
-        self.theme_manager.has_theme(theme)
+        Ok(UploadReport {
+            draft_id,
+            images: upload_results,
+            cover_media_id: cover.media_id,
+            cover_reused: cover.reused,
+            draft_url: synced.url,
+            unchanged: false,
+            dry_run: None,
+        })
     }
 
-    /// Gets access token information for debugging.
-    pub async fn get_token_info(&self) -> Option<crate::auth::TokenInfo> {
-        self.token_manager.get_token_info().await
-    }
+    /// Measures rendered content per section. `finish` turns rendered HTML into the
+    /// content sent to WeChat, and `html` is the finished whole article.
+    async fn measure_size(
+        &self,
+        content: &MarkdownContent,
+        theme: &ThemeId,
+        options: &UploadOptions,
+        html: &str,
+        finish: &(dyn Fn(String) -> Result<String> + Sync),
+    ) -> Result<SizeReport> {
+        let render = async |markdown: &str| {
+            let mut part = content.clone();
+            part.content = markdown.to_string();
+            finish(self.render_content(&part, theme, options, None).await?)
+        };
 
-    /// Forces a token refresh.
-    pub async fn refresh_token(&self) -> Result<String> {
-        self.token_manager.force_refresh().await
-    }
+        // Sections are measured without the template around them
+        let empty = render("").await?;
+        let mut sections = Vec::new();
+        for section in content.sections() {
+            let html = render(&section.markdown).await?;
+            sections.push(SectionSize {
+                title: section.title,
+                chars: html.chars().count().saturating_sub(empty.chars().count()),
+                bytes: html.len().saturating_sub(empty.len()),
+            });
+        }
 
-    /// Gets the underlying HTTP client for advanced usage.
-    pub fn http_client(&self) -> &WeChatHttpClient {
-        &self.http_client
+        Ok(SizeReport {
+            budget: options.content_budget,
+            chars: html.chars().count(),
+            bytes: html.len(),
+            sections,
+        })
     }
 
-    // Private helper methods
-
-    async fn validate_upload_input(
+    /// Parses a markdown file and replaces Mermaid charts with generated images, written
+    /// to `work_dir` if given and according to the options otherwise. Returns the
+    /// content along with the references to the generated images.
+    async fn load_content(
         &self,
         markdown_path: &Path,
         options: &UploadOptions,
-    ) -> Result<()> {
-        // Check if markdown file exists
-        if !utils::file_exists(markdown_path).await {
-            return Err(WeChatError::FileNotFound {
-                path: markdown_path.display().to_string(),
-            });
-        }
+        work_dir: Option<&Path>,
+    ) -> Result<(MarkdownContent, Vec<ImageRef>)> {
+        let parse_span = info_span!("parse", image_count = tracing::field::Empty);
+        let mut content = self
+            .parse_markdown_file(markdown_path)
+            .instrument(parse_span.clone())
+            .await?;
+        parse_span.record("image_count", content.images.len());
+        debug!("Found {} images in content", content.images.len());
 
-        // Check if it's a markdown file
-        if !utils::is_markdown_file(markdown_path) {
-            return Err(WeChatError::config_error(
-                "File is not a markdown file (.md or .markdown)",
-            ));
+        let base_dir = utils::get_base_directory(markdown_path).unwrap_or_else(|| Path::new("."));
+        let document_slug = self.slug_strategy().slug(markdown_path, &content);
+        let mut mermaid_processor = MermaidProcessor::new(base_dir.to_path_buf(), document_slug)
+            .with_output(options.mermaid_output.clone())
+            .with_naming(options.mermaid_naming)
+            .with_render_limit(Arc::clone(&self.mermaid_renders))
+            .with_temp_files(self.temp_files.clone());
+        if options.cache_assets {
+            mermaid_processor =
+                mermaid_processor.with_asset_store(AssetStore::for_content_dir(base_dir));
+        }
+        if let Some(work_dir) = work_dir {
+            mermaid_processor = mermaid_processor.with_images_dir(work_dir.to_path_buf());
         }
 
-        // Theme validation will happen later when we determine the actual theme to use
+        let mermaid_span = info_span!("mermaid", chart_count = tracing::field::Empty);
+        let (modified_content, mermaid_images) = mermaid_processor
+            .process_mermaid_content_with_source_path(
+                &content.content,
+                base_dir,
+                Some(markdown_path),
+            )
+            .instrument(mermaid_span.clone())
+            .await?;
+        mermaid_span.record("chart_count", mermaid_images.len());
 
-        // Parse markdown to check for frontmatter cover
-        let content = self.parse_markdown_file(markdown_path).await?;
+        // Update content with Mermaid-processed version
+        content.content = modified_content;
 
-        // Check that cover image is provided either via options or frontmatter
-        let has_cover_option = options.cover_image.is_some();
-        let has_cover_frontmatter = content.cover.is_some();
+        // Add Mermaid-generated images to the image list
+        content.images.extend(mermaid_images.iter().cloned());
 
-        if !has_cover_option && !has_cover_frontmatter {
-            return Err(WeChatError::config_error(
-                "Cover image is required. Please provide via --cover-image option or 'cover:' in frontmatter",
-            ));
-        }
+        debug!(
+            "Total images to upload (including Mermaid): {}",
+            content.images.len()
+        );
 
-        // Validate cover image from options if specified
-        if let Some(cover_path) = &options.cover_image {
-            let base_dir =
-                utils::get_base_directory(markdown_path).unwrap_or_else(|| Path::new("."));
+        Ok((content, mermaid_images))
+    }
+
+    /// Renders a markdown file with local images mapped relative to `output_dir`.
+    async fn render_local_preview(
+        &self,
+        markdown_path: &Path,
+        output_dir: &Path,
+        options: &UploadOptions,
+    ) -> Result<String> {
+        self.validate_markdown_path(markdown_path).await?;
 
-            let resolved_cover_path = if Path::new(cover_path).is_absolute() {
-                PathBuf::from(cover_path)
+        // Keep charts that are embedded anyway out of the source tree
+        let preview_options;
+        let options =
+            if options.inline_diagrams && options.mermaid_output == MermaidOutput::ImagesDir {
+                preview_options = options.clone().mermaid_output(MermaidOutput::Temp);
+                &preview_options
             } else {
-                base_dir.join(cover_path)
+                options
             };
 
-            if !utils::file_exists(&resolved_cover_path).await {
-                return Err(WeChatError::FileNotFound {
-                    path: resolved_cover_path.display().to_string(),
-                });
-            }
+        let (mut content, diagrams) = self.load_content(markdown_path, options, None).await?;
+        let base_dir = utils::get_base_directory(markdown_path).unwrap_or_else(|| Path::new("."));
 
-            if !utils::is_image_file(&resolved_cover_path) {
-                return Err(WeChatError::config_error(
-                    "Cover file is not a supported image format",
-                ));
+        let path_policy = self.path_policy();
+        let mut url_mapping = content.local_preview_mapping(base_dir, output_dir, &path_policy);
+        if options.inline_diagrams {
+            for diagram in &diagrams {
+                let path = diagram.resolve_path_with(base_dir, &path_policy)?;
+                let data = tokio::fs::read(&path).await.map_err(|e| {
+                    WeChatError::file_error(path.display().to_string(), e.to_string())
+                })?;
+                url_mapping.insert(
+                    diagram.original_url.clone(),
+                    utils::data_uri("image/png", &data),
+                );
             }
         }
+        content.replace_image_urls(&url_mapping)?;
 
-        // Validate cover image from frontmatter if specified
-        if let Some(cover_path) = &content.cover {
-            let base_dir =
-                utils::get_base_directory(markdown_path).unwrap_or_else(|| Path::new("."));
+        let hero_image = options
+            .cover_image
+            .as_ref()
+            .or(content.cover.as_ref())
+            .and_then(|cover| {
+                let path = path_policy.resolve(base_dir, cover).ok()?;
+                let path = path.canonicalize().unwrap_or(path);
+                let output_dir = output_dir
+                    .canonicalize()
+                    .unwrap_or_else(|_| output_dir.to_path_buf());
+                utils::relative_path(&output_dir, &path)
+            });
 
-            let resolved_cover_path = if Path::new(cover_path).is_absolute() {
-                PathBuf::from(cover_path)
-            } else {
-                base_dir.join(cover_path)
-            };
+        let theme = self.resolve_theme(&content, options)?;
+        let html = self
+            .render_content(&content, theme, options, hero_image.as_deref())
+            .await?;
+        apply_html_transform(html, options)
+    }
 
-            if !utils::file_exists(&resolved_cover_path).await {
-                return Err(WeChatError::FileNotFound {
-                    path: resolved_cover_path.display().to_string(),
-                });
-            }
+    /// Picks the theme from frontmatter or options and checks that it exists.
+    fn resolve_theme<'a>(
+        &self,
+        content: &'a MarkdownContent,
+        options: &'a UploadOptions,
+    ) -> Result<&'a ThemeId> {
+        let theme = content.theme.as_ref().unwrap_or(&options.theme);
+        self.renderer().validate_theme(theme.as_str())?;
+        Ok(theme)
+    }
 
-            if !utils::is_image_file(&resolved_cover_path) {
-                return Err(WeChatError::config_error(
-                    "Cover file specified in frontmatter is not a supported image format",
-                ));
-            }
+    /// Gets the renderer articles are rendered with: the custom one, if set, otherwise
+    /// the theme manager.
+    fn renderer(&self) -> &dyn ContentRenderer {
+        match &self.renderer {
+            Some(CustomRenderer(renderer)) => renderer.as_ref(),
+            None => self.theme_manager.as_ref(),
         }
+    }
 
-        Ok(())
+    /// Gets a handle on the renderer that can be moved to another thread.
+    fn shared_renderer(&self) -> Arc<dyn ContentRenderer> {
+        match &self.renderer {
+            Some(CustomRenderer(renderer)) => Arc::clone(renderer),
+            None => Arc::clone(&self.theme_manager) as Arc<dyn ContentRenderer>,
+        }
     }
 
     async fn parse_markdown_file(&self, path: &Path) -> Result<MarkdownContent> {
         self.markdown_parser.parse_file(path).await
     }
 
-    async fn upload_cover_image(&self, cover_path: &str, base_dir: &Path) -> Result<String> {
-        let cover_path = if Path::new(cover_path).is_absolute() {
-            PathBuf::from(cover_path)
-        } else {
-            base_dir.join(cover_path)
-        };
+    async fn upload_cover_image(
+        &self,
+        image_uploader: &ImageUploader,
+        cover_path: &str,
+        base_dir: &Path,
+    ) -> Result<CoverUpload> {
+        let cover_path = utils::join_reference(base_dir, cover_path);
 
         // Upload cover image as permanent material
-        self.image_uploader.upload_cover_material(&cover_path).await
+        image_uploader.upload_cover(&cover_path).await
     }
 
-    fn render_content(
+    async fn render_content(
         &self,
         content: &MarkdownContent,
-        theme: &str,
+        theme: &ThemeId,
         options: &UploadOptions,
+        hero_image: Option<&str>,
     ) -> Result<String> {
         let mut metadata = content.metadata.clone();
 
@@ -537,8 +3467,8 @@ impl WeChatClient {
         if let Some(title) = content.title.as_ref() {
             metadata.insert("title".to_string(), title.clone());
         }
-        if let Some(author) = content.author.as_ref() {
-            metadata.insert("author".to_string(), author.clone());
+        if let Some(author) = options.author_format.format(&content.authors) {
+            metadata.insert("author".to_string(), author);
         }
 
         // Override with options if provided
@@ -548,13 +3478,62 @@ impl WeChatClient {
         if let Some(author) = &options.author {
             metadata.insert("author".to_string(), author.clone());
         }
+        if let Some(date) = content.date {
+            let locale_format;
+            let date_format = match &options.date_format {
+                Some(format) => format,
+                None => {
+                    locale_format = self.http_client.config().locale.locale.date_format();
+                    &locale_format
+                }
+            };
+            metadata.insert("date".to_string(), date_format.format(date));
+        }
+        metadata.insert(
+            "description".to_string(),
+            content
+                .description
+                .clone()
+                .or_else(|| options.digest.clone())
+                .unwrap_or_else(|| content.digest()),
+        );
 
-        self.theme_manager.render(
-            &content.content,
-            theme,
-            content.code.as_deref().unwrap_or("vscode"),
-            &metadata,
-        )
+        // Header slots of the article template
+        if let Some(hero_image) = hero_image
+            && (options.hero_image || theme::metadata_flag(&metadata, theme::HERO_KEY)?)
+        {
+            metadata.insert(theme::HERO_IMAGE_KEY.to_string(), hero_image.to_string());
+        }
+        if options.meta_row {
+            metadata.insert(theme::META_ROW_KEY.to_string(), "true".to_string());
+        }
+        if options.tags_row {
+            metadata.insert(theme::TAGS_ROW_KEY.to_string(), "true".to_string());
+        }
+
+        let code_theme = content.code.as_deref().unwrap_or("vscode");
+        let strict_code_theme = options
+            .strict_code_theme
+            .unwrap_or(self.http_client.config().render.strict_code_themes);
+        if strict_code_theme {
+            self.theme_manager.validate_code_theme(code_theme)?;
+        }
+
+        // Markdown parsing, highlighting and CSS inlining are CPU-bound, so they run on
+        // the blocking thread pool rather than stalling the uploads on this executor
+        let renderer = self.shared_renderer();
+        let markdown = content.content.clone();
+        let theme = theme.as_str().to_string();
+        let code_theme = code_theme.to_string();
+        let normalization = options.normalization;
+        tokio::task::spawn_blocking(move || {
+            let html = renderer.render_content(&markdown, &theme, &code_theme, &metadata)?;
+            Ok(normalization.apply(&html))
+        })
+        .await
+        .map_err(|e| WeChatError::Internal {
+            message: format!("Render task failed: {e}"),
+        })?
     }
 
     fn create_article(
@@ -563,6 +3542,7 @@ impl WeChatClient {
         options: &UploadOptions,
         html_content: String,
         cover_media_id: Option<String>,
+        slug: &str,
     ) -> Article {
         // Determine title and author
         let title = options
@@ -574,14 +3554,12 @@ impl WeChatClient {
         let author = options
             .author
             .clone()
-            .or_else(|| content.author.clone())
+            .or_else(|| options.author_format.format(&content.authors))
             .unwrap_or_else(|| "Anonymous".to_string());
 
-        // Use description from frontmatter if available, otherwise generate summary
-        let digest = content
-            .description
-            .clone()
-            .unwrap_or_else(|| content.get_summary(120));
+        // The option, then `digest:` and `description:` from the frontmatter, otherwise a
+        // summary
+        let digest = options.digest.clone().unwrap_or_else(|| content.digest());
 
         // Create article
         let mut article = Article::new(title, author, html_content)
@@ -593,11 +3571,211 @@ impl WeChatClient {
             article = article.with_cover_image(media_id);
         }
 
-        if let Some(source_url) = &options.source_url {
-            article = article.with_source_url(source_url.clone());
-        }
+        if let Some(source_url) = &options.source_url {
+            article = article.with_source_url(source_url.replace(SLUG_PLACEHOLDER, slug));
+        }
+
+        // A `slug:` in the frontmatter identifies the draft even after the title changed
+        if let Some(frontmatter_slug) = content.metadata.get(SLUG_KEY).and_then(|s| slugify(s)) {
+            article = article.with_slug(&frontmatter_slug);
+        }
+
+        article
+    }
+}
+
+/// Builds the error for an article over its content budget.
+fn content_too_large(report: &SizeReport) -> WeChatError {
+    WeChatError::ContentTooLarge {
+        chars: report.chars,
+        bytes: report.bytes,
+        hint: report.hint(),
+    }
+}
+
+/// Placeholder in [`UploadOptions::source_url`] replaced with the document slug.
+const SLUG_PLACEHOLDER: &str = "{slug}";
+
+/// Placeholder for the hero image, which is the cover and only known after its upload.
+const HERO_PLACEHOLDER: &str = "wechat-pub-hero-placeholder";
+
+/// Maps every image URL in the content to a unique placeholder URL.
+///
+/// Placeholders are plain ASCII so they survive markdown rendering unchanged, and carry a
+/// suffix so that no placeholder is a prefix of another.
+fn image_placeholders(content: &MarkdownContent) -> HashMap<String, String> {
+    let mut placeholders = HashMap::new();
+    for image in &content.images {
+        let next = placeholders.len();
+        placeholders
+            .entry(image.original_url.clone())
+            .or_insert_with(|| format!("wechat-pub-image-{next}-placeholder"));
+    }
+    placeholders
+}
+
+/// Fails for upload options that need the markdown file at send time, which queued
+/// and prepared uploads send without.
+fn reject_deferred_options(options: &UploadOptions, kind: &str) -> Result<()> {
+    if options.track_state || options.write_back || options.split_long_articles {
+        return Err(WeChatError::config_error(format!(
+            "{kind} uploads don't support track_state, write_back or split_long_articles"
+        )));
+    }
+    Ok(())
+}
+
+/// Builds what is sent for a rendered article.
+fn queued_payload(
+    rendered: RenderedUpload,
+    images: Vec<QueuedImage>,
+    cover: String,
+    options: &UploadOptions,
+) -> QueuedPayload {
+    QueuedPayload {
+        known_media_id: rendered.content.wechat_media_id().map(str::to_string),
+        article: rendered.article,
+        images,
+        cover,
+        force: options.force,
+        on_existing_draft: options.on_existing_draft,
+        lookup_draft_url: options.lookup_draft_url,
+        image_processing: options.image_processing,
+        image_formats: options.image_formats.clone(),
+        material_naming: options.material_naming,
+        document_slug: Some(rendered.slug),
+    }
+}
+
+/// Describes an image a prepared upload will upload, recording missing files as
+/// issues.
+async fn plan_image(
+    reference: String,
+    path: Option<PathBuf>,
+    issues: &mut Vec<String>,
+) -> PlannedImage {
+    let mut size = None;
+    if let Some(path) = &path {
+        match tokio::fs::metadata(path).await {
+            Ok(metadata) => size = Some(metadata.len()),
+            Err(_) => issues.push(format!("Image not found: {}", path.display())),
+        }
+    }
+    PlannedImage {
+        reference,
+        path,
+        size,
+    }
+}
+
+/// Replaces image placeholders in rendered HTML with their uploaded URLs.
+fn fill_image_placeholders(
+    mut html: String,
+    placeholders: &HashMap<String, String>,
+    url_mapping: &HashMap<String, String>,
+) -> String {
+    for (original_url, placeholder) in placeholders {
+        let url = url_mapping.get(original_url).unwrap_or(original_url);
+        html = html.replace(placeholder.as_str(), &escape_attribute(url));
+    }
+    html
+}
+
+/// Logs the link to a draft, so it can be opened straight from CI logs.
+fn log_draft_url(url: Option<&str>) {
+    match url {
+        Some(url) => info!("Open the draft in WeChat: {url}"),
+        None => debug!("WeChat reported no URL for the draft"),
+    }
+}
+
+/// Escapes a URL for an attribute value, like the renderer would.
+fn escape_attribute(url: &str) -> String {
+    url.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Lists the markdown files directly in a directory, sorted by path.
+async fn markdown_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let read_error =
+        |e: std::io::Error| WeChatError::file_error(dir.display().to_string(), e.to_string());
+
+    let mut files = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await.map_err(read_error)?;
+    while let Some(entry) = entries.next_entry().await.map_err(read_error)? {
+        let path = entry.path();
+        if path.is_file() && utils::is_markdown_file(&path) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Directory holding a markdown file, where its state file lives.
+fn content_dir(markdown_path: &Path) -> &Path {
+    utils::get_base_directory(markdown_path)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."))
+}
+
+/// Writes the draft's media ID and URL into a markdown file's frontmatter.
+///
+/// Returns the new file content, or `None` if the frontmatter was already up to date.
+async fn write_back_frontmatter(
+    markdown_path: &Path,
+    media_id: &str,
+    url: Option<&str>,
+) -> Result<Option<Vec<u8>>> {
+    let file_error = |e: std::io::Error| {
+        WeChatError::file_error(markdown_path.display().to_string(), e.to_string())
+    };
+
+    let markdown = tokio::fs::read_to_string(markdown_path)
+        .await
+        .map_err(file_error)?;
+
+    let mut fields = vec![(markdown::FRONTMATTER_MEDIA_ID, media_id)];
+    if let Some(url) = url {
+        fields.push((markdown::FRONTMATTER_URL, url));
+    }
+
+    let updated = markdown::update_frontmatter(&markdown, &fields);
+    if updated == markdown {
+        return Ok(None);
+    }
+
+    tokio::fs::write(markdown_path, &updated)
+        .await
+        .map_err(file_error)?;
+    debug!(
+        "Wrote draft {media_id} back into {}",
+        markdown_path.display()
+    );
+
+    Ok(Some(updated.into_bytes()))
+}
 
-        article
+/// Applies the user-supplied HTML transform, if any.
+fn apply_html_transform(html: String, options: &UploadOptions) -> Result<String> {
+    match &options.html_transform {
+        Some(transform) => transform.apply(html),
+        None => Ok(html),
+    }
+}
+
+/// Runs `operation`, failing with [`WeChatError::Timeout`] if `deadline` elapses first.
+async fn with_deadline<T>(
+    deadline: Option<Duration>,
+    operation: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout(deadline, operation)
+            .await
+            .map_err(|_| WeChatError::Timeout)?,
+        None => operation.await,
     }
 }
 
@@ -613,7 +3791,8 @@ mod tests {
             .cover_image("cover.jpg")
             .show_cover(false)
             .comments(true, true)
-            .source_url("https://example.com");
+            .source_url("https://example.com")
+            .digest("Share blurb");
 
         assert_eq!(options.theme, "github");
         assert_eq!(options.title, Some("Test Title".to_string()));
@@ -623,6 +3802,7 @@ mod tests {
         assert!(options.enable_comments);
         assert!(options.fans_only_comments);
         assert_eq!(options.source_url, Some("https://example.com".to_string()));
+        assert_eq!(options.digest, Some("Share blurb".to_string()));
     }
 
     #[test]
@@ -637,6 +3817,634 @@ mod tests {
         assert!(!options.enable_comments);
         assert!(!options.fans_only_comments);
         assert_eq!(options.source_url, None);
+        assert_eq!(options.deadline, None);
+        assert_eq!(options.image_timeout, None);
+        assert!(options.inline_diagrams);
+    }
+
+    #[test]
+    fn test_image_placeholders_round_trip() {
+        let parser = MarkdownParser::new();
+        let markdown: String = (0..12)
+            .map(|i| format!("![img{i}](images/{i}.png)\n\n"))
+            .chain(std::iter::once("![again](images/1.png)\n".to_string()))
+            .collect();
+        let content = parser.parse(&markdown).unwrap();
+
+        let placeholders = image_placeholders(&content);
+        assert_eq!(placeholders.len(), 12);
+
+        let mut render_source = content.clone();
+        render_source.replace_image_urls(&placeholders).unwrap();
+        let html = ThemeManager::new()
+            .render(&render_source.content, "default", "github", &HashMap::new())
+            .unwrap();
+
+        let url_mapping: HashMap<String, String> = (0..12)
+            .map(|i| {
+                (
+                    format!("images/{i}.png"),
+                    format!("https://mmbiz.qpic.cn/{i}?wx_fmt=png&from=appmsg"),
+                )
+            })
+            .collect();
+        let html = fill_image_placeholders(html, &placeholders, &url_mapping);
+
+        assert!(!html.contains("placeholder"));
+        assert!(html.contains("https://mmbiz.qpic.cn/1?wx_fmt=png&amp;from=appmsg"));
+        assert!(html.contains("https://mmbiz.qpic.cn/10?wx_fmt=png&amp;from=appmsg"));
+        assert!(!html.contains("images/"));
+    }
+
+    #[tokio::test]
+    async fn test_html_transform_applied_to_render() {
+        let client = WeChatClient::new("wx1234567890123456", "12345678901234567890123456789012")
+            .await
+            .unwrap();
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let markdown_path = temp_dir.path().join("post.md");
+        std::fs::write(&markdown_path, "# Hello\n\nBody text\n").unwrap();
+        let markdown_path = markdown_path.to_str().unwrap();
+
+        let options = UploadOptions::default().html_transform(|html| {
+            Ok(format!(
+                r#"{html}<img src="https://example.com/pixel.gif">"#
+            ))
+        });
+        assert!(format!("{options:?}").contains("HtmlTransform(..)"));
+
+        let html = client.render_preview(markdown_path, options).await.unwrap();
+        assert!(html.ends_with(r#"<img src="https://example.com/pixel.gif">"#));
+
+        let failing = UploadOptions::default()
+            .html_transform(|_| Err(WeChatError::config_error("policy violation")));
+        let result = client.render_preview(markdown_path, failing).await;
+        assert!(result.unwrap_err().to_string().contains("policy violation"));
+    }
+
+    #[tokio::test]
+    async fn test_strict_code_theme() {
+        let client = WeChatClient::new("wx1234567890123456", "12345678901234567890123456789012")
+            .await
+            .unwrap();
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let markdown_path = temp_dir.path().join("post.md");
+        std::fs::write(&markdown_path, "---\ncode: nonexistent\n---\n\n# Hello\n").unwrap();
+        let markdown_path = markdown_path.to_str().unwrap();
+
+        // Falls back to the default highlight theme unless strict
+        assert!(
+            client
+                .render_preview(markdown_path, UploadOptions::default())
+                .await
+                .is_ok()
+        );
+
+        let strict = UploadOptions::default().strict_code_theme(true);
+        match client.render_preview(markdown_path, strict).await {
+            Err(WeChatError::CodeThemeNotFound { theme }) => assert_eq!(theme, "nonexistent"),
+            other => panic!("Expected CodeThemeNotFound, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_operations() {
+        let client = WeChatClient::new("wx1234567890123456", "12345678901234567890123456789012")
+            .await
+            .unwrap();
+
+        // Shutdown waits while an operation is in progress
+        let operation = client.begin_operation().await.unwrap();
+        let shutdown = client.shutdown();
+        tokio::pin!(shutdown);
+        let waited = tokio::time::timeout(Duration::from_millis(50), &mut shutdown).await;
+        assert!(waited.is_err());
+
+        drop(operation);
+        shutdown.await.unwrap();
+
+        // New operations are refused; shutting down again is harmless
+        let result = client.upload("missing.md").await;
+        assert!(matches!(result, Err(WeChatError::ShutDown)));
+        client.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_upload_hooks_notified_on_failure() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Default)]
+        struct CountingHooks {
+            successes: AtomicUsize,
+            failures: Arc<AtomicUsize>,
+        }
+
+        #[async_trait::async_trait]
+        impl UploadHooks for CountingHooks {
+            async fn on_success(&self, _markdown_path: &str, _report: &UploadReport) {
+                self.successes.fetch_add(1, Ordering::SeqCst);
+            }
+
+            async fn on_failure(&self, markdown_path: &str, error: &WeChatError) {
+                assert_eq!(markdown_path, "missing.md");
+                assert!(matches!(error, WeChatError::FileNotFound { .. }));
+                self.failures.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let failures = Arc::new(AtomicUsize::new(0));
+        let client = WeChatClient::new("wx1234567890123456", "12345678901234567890123456789012")
+            .await
+            .unwrap()
+            .with_hooks(CountingHooks {
+                failures: Arc::clone(&failures),
+                ..Default::default()
+            });
+
+        let result = client.upload("missing.md").await;
+        assert!(result.is_err());
+        assert_eq!(failures.load(Ordering::SeqCst), 1);
+
+        // Draft updates notify the hooks too
+        let result = client.update_draft("media_id", "missing.md").await;
+        assert!(result.is_err());
+        assert_eq!(failures.load(Ordering::SeqCst), 2);
+    }
+
+    #[cfg(feature = "server")]
+    #[tokio::test]
+    async fn test_publish_result_hook() {
+        use crate::publish::PublishFailure;
+        use crate::server::Event;
+
+        #[derive(Default)]
+        struct PublishHooks(Arc<Mutex<Vec<(String, String, PublishStatus)>>>);
+
+        #[async_trait::async_trait]
+        impl UploadHooks for PublishHooks {
+            async fn on_publish_result(
+                &self,
+                markdown_path: &str,
+                publish_id: &str,
+                status: &PublishStatus,
+            ) {
+                self.0.lock().unwrap().push((
+                    markdown_path.to_string(),
+                    publish_id.to_string(),
+                    status.clone(),
+                ));
+            }
+        }
+
+        // Serve the token, then the submitted job
+        let (base_url, _) = crate::test_server::serve(vec![
+            r#"{"access_token":"TOKEN","expires_in":7200}"#,
+            r#"{"errcode":0,"errmsg":"ok","publish_id":"100000001","msg_data_id":2247483663}"#,
+        ]);
+
+        let mut config = Config::default();
+        config.http.base_url = base_url;
+        let hooks = PublishHooks::default();
+        let results = Arc::clone(&hooks.0);
+        let client = WeChatClient::with_config(
+            "wx1234567890123456",
+            "12345678901234567890123456789012",
+            config,
+        )
+        .await
+        .unwrap()
+        .with_hooks(hooks);
+
+        let publish_id = client
+            .publish_draft("MEDIA_ID", Some(Path::new("post.md")))
+            .await
+            .unwrap();
+        assert_eq!(publish_id, "100000001");
+
+        let rejected = PublishStatus::Failed {
+            reason: PublishFailure::AuditRejected,
+            failed_articles: vec![1],
+        };
+        let finished = |publish_id: &str| Event::PublishJobFinish {
+            publish_id: publish_id.to_string(),
+            status: rejected.clone(),
+        };
+
+        // Jobs of other clients are ignored, and each job is reported once
+        assert!(!client.handle_publish_event(&finished("999")).await);
+        assert!(client.handle_publish_event(&finished("100000001")).await);
+        assert!(!client.handle_publish_event(&finished("100000001")).await);
+        assert!(!client.handle_publish_event(&Event::Unsubscribe).await);
+
+        assert_eq!(
+            *results.lock().unwrap(),
+            [("post.md".to_string(), "100000001".to_string(), rejected)]
+        );
+
+        // Like uploads, publishing stops at shutdown
+        client.shutdown().await.unwrap();
+        assert!(matches!(
+            client.publish_draft("MEDIA_ID", None).await,
+            Err(WeChatError::ShutDown)
+        ));
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_publish_jobs_are_bounded() {
+        let mut jobs = PublishJobs::default();
+        for index in 0..=MAX_PUBLISH_JOBS {
+            jobs.insert(index.to_string(), format!("{index}.md"));
+        }
+        assert_eq!(jobs.0.len(), MAX_PUBLISH_JOBS);
+        let newest = MAX_PUBLISH_JOBS.to_string();
+        assert_eq!(jobs.remove(&newest), Some(format!("{newest}.md")));
+
+        // Jobs expire after the TTL
+        if let Some(submitted) = Instant::now().checked_sub(PUBLISH_JOB_TTL) {
+            let publish_id = jobs.0.keys().next().unwrap().clone();
+            jobs.0.get_mut(&publish_id).unwrap().1 = submitted;
+            assert_eq!(jobs.remove(&publish_id), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_back_frontmatter() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let markdown_path = temp_dir.path().join("article.md");
+        std::fs::write(&markdown_path, "---\ntitle: Hello\n---\n\n# Hello\n").unwrap();
+
+        let url = Some("https://mp.weixin.qq.com/s/abc");
+        let written = write_back_frontmatter(&markdown_path, "media_1", url)
+            .await
+            .unwrap()
+            .expect("frontmatter should be updated");
+
+        let parser = MarkdownParser::new();
+        let content = parser.parse_file(&markdown_path).await.unwrap();
+        assert_eq!(content.wechat_media_id(), Some("media_1"));
+        assert_eq!(content.title.as_deref(), Some("Hello"));
+        assert_eq!(std::fs::read(&markdown_path).unwrap(), written);
+
+        // Writing the same values again leaves the file alone
+        let rewritten = write_back_frontmatter(&markdown_path, "media_1", url)
+            .await
+            .unwrap();
+        assert!(rewritten.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_with_deadline() {
+        let result = with_deadline(Some(Duration::from_millis(50)), async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(())
+        })
+        .await;
+        assert!(matches!(result, Err(WeChatError::Timeout)));
+
+        let result = with_deadline(Some(Duration::from_secs(10)), async { Ok(42) }).await;
+        assert_eq!(result.unwrap(), 42);
+
+        let result = with_deadline(None, async { Ok("done") }).await;
+        assert_eq!(result.unwrap(), "done");
+    }
+
+    #[tokio::test]
+    async fn test_upload_dir_reports_failures() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("b.md"), "# B\n\nNo cover.").unwrap();
+        std::fs::write(temp_dir.path().join("a.markdown"), "# A\n\nNo cover.").unwrap();
+        std::fs::write(temp_dir.path().join("notes.txt"), "Not markdown").unwrap();
+        std::fs::create_dir(temp_dir.path().join("drafts.md")).unwrap();
+
+        let client = WeChatClient::new("wx1234567890123456", "12345678901234567890123456789012")
+            .await
+            .unwrap();
+        let report = client
+            .upload_dir(temp_dir.path(), UploadOptions::default())
+            .await
+            .unwrap();
+
+        // Every file fails on its own, without stopping the others
+        assert!(!report.is_success());
+        assert!(report.uploaded.is_empty());
+        let failed: Vec<_> = report.failed.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(
+            failed,
+            vec![
+                temp_dir.path().join("a.markdown"),
+                temp_dir.path().join("b.md")
+            ]
+        );
+        assert!(matches!(report.failed[0].1, WeChatError::Config { .. }));
+
+        assert!(
+            client
+                .upload_dir(temp_dir.path().join("missing"), UploadOptions::default())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prepare_plans_upload() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("cover.png"), b"cover").unwrap();
+        std::fs::write(temp_dir.path().join("photo.png"), b"photo").unwrap();
+        let markdown_path = temp_dir.path().join("post.md");
+        std::fs::write(
+            &markdown_path,
+            "---\ntitle: Planned\ncover: cover.png\n---\n\n![a](photo.png)\n\n\
+             ![b](missing.png)\n\n![c](photo.png)\n\n![d](https://example.com/d.png)\n",
+        )
+        .unwrap();
+
+        let client = WeChatClient::new("wx1234567890123456", "12345678901234567890123456789012")
+            .await
+            .unwrap();
+        let prepared = client
+            .prepare(&markdown_path.display().to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(prepared.title(), "Planned");
+        let references: Vec<_> = prepared
+            .images()
+            .iter()
+            .map(|image| image.reference.as_str())
+            .collect();
+        assert_eq!(
+            references,
+            ["photo.png", "missing.png", "https://example.com/d.png"]
+        );
+        assert_eq!(prepared.images()[0].size, Some(5));
+        assert_eq!(prepared.images()[2].path, None);
+        assert_eq!(prepared.cover().reference, "cover.png");
+        assert!(prepared.html().contains("photo.png"));
+        assert!(!prepared.html().contains("placeholder"));
+
+        // The missing image is reported, and committing fails before calling WeChat
+        assert_eq!(prepared.validation().issues.len(), 1);
+        assert!(prepared.validation().issues[0].contains("missing.png"));
+        assert!(matches!(
+            prepared.commit().await,
+            Err(WeChatError::Config { .. })
+        ));
+
+        let other_cover = temp_dir.path().join("photo.png");
+        let custom = UploadOptions::default()
+            .digest("Custom summary")
+            .cover_path(&other_cover);
+        let prepared = client
+            .prepare_with_options(&markdown_path.display().to_string(), custom)
+            .await
+            .unwrap();
+        assert_eq!(prepared.digest(), "Custom summary");
+        assert_eq!(prepared.source_url(), None);
+        assert_eq!(
+            prepared.cover().path.as_deref(),
+            Some(other_cover.as_path())
+        );
+
+        std::fs::write(
+            &markdown_path,
+            "---\ntitle: Renamed\nslug: Planned Post\ncover: cover.png\n---\n\nText\n",
+        )
+        .unwrap();
+        let linked = UploadOptions::default().source_url("https://blog.example.com/{slug}");
+        let prepared = client
+            .prepare_with_options(&markdown_path.display().to_string(), linked)
+            .await
+            .unwrap();
+        assert_eq!(
+            prepared.source_url(),
+            Some("https://blog.example.com/post#wechat-slug=planned-post")
+        );
+
+        let tracked = UploadOptions::default().track_state(true);
+        assert!(
+            client
+                .prepare_with_options(&markdown_path.display().to_string(), tracked)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_upload() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("cover.png"), b"cover").unwrap();
+        std::fs::write(temp_dir.path().join("photo.png"), b"photo").unwrap();
+        let markdown_path = temp_dir.path().join("post.md");
+        let markdown = "---\ntitle: Dry\ncover: cover.png\n---\n\n# Heading\n\n![a](photo.png)\n";
+        std::fs::write(&markdown_path, markdown).unwrap();
+        let path = markdown_path.display().to_string();
+
+        let client = WeChatClient::new("wx1234567890123456", "12345678901234567890123456789012")
+            .await
+            .unwrap();
+        // Succeeds without network access; state tracking and write-back are skipped
+        let options = UploadOptions::default()
+            .track_state(true)
+            .write_back(true)
+            .dry_run(true);
+        let report = client
+            .upload_with_report(&path, options.clone())
+            .await
+            .unwrap();
+
+        assert!(report.draft_id.is_empty());
+        let plan = report.dry_run.unwrap();
+        assert!(plan.html.contains("Heading"));
+        assert!(plan.html.contains("photo.png"));
+        assert_eq!(plan.images.len(), 1);
+        assert_eq!(plan.images[0].reference, "photo.png");
+        assert_eq!(plan.cover.reference, "cover.png");
+        assert!(plan.validation.is_ok());
+
+        // Only the report carries the plan
+        assert!(matches!(
+            client.upload_with_options(&path, options).await,
+            Err(WeChatError::Config { .. })
+        ));
+        assert_eq!(std::fs::read_to_string(&markdown_path).unwrap(), markdown);
+        assert!(!temp_dir.path().join(".wechat-pub").exists());
+    }
+
+    #[tokio::test]
+    async fn test_client_builder_components() {
+        struct StaticTokens;
+
+        #[async_trait::async_trait]
+        impl TokenProvider for StaticTokens {
+            async fn get_token(&self) -> Result<String> {
+                Ok("TOKEN".to_string())
+            }
+            async fn refresh_token(&self) -> Result<String> {
+                Ok("TOKEN".to_string())
+            }
+            async fn is_token_expired(&self) -> bool {
+                false
+            }
+            async fn token_expires_at(&self) -> Option<chrono::DateTime<Utc>> {
+                None
+            }
+        }
+
+        #[derive(Clone, Default)]
+        struct OfflineTransport(Arc<std::sync::Mutex<Vec<String>>>);
+
+        impl OfflineTransport {
+            fn offline(&self, request: String) -> Result<reqwest::Response> {
+                self.0.lock().unwrap().push(request);
+                Err(WeChatError::Network {
+                    message: "offline".to_string(),
+                })
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl HttpClient for OfflineTransport {
+            async fn get_with_token(
+                &self,
+                endpoint: &str,
+                token: &str,
+            ) -> Result<reqwest::Response> {
+                self.offline(format!("GET {endpoint} {token}"))
+            }
+            async fn post_json_with_token(
+                &self,
+                endpoint: &str,
+                token: &str,
+                _body: &serde_json::Value,
+            ) -> Result<reqwest::Response> {
+                self.offline(format!("POST {endpoint} {token}"))
+            }
+            async fn upload_file(
+                &self,
+                endpoint: &str,
+                token: &str,
+                _field_name: &str,
+                _file_data: bytes::Bytes,
+                _filename: &str,
+            ) -> Result<reqwest::Response> {
+                self.offline(format!("UPLOAD {endpoint} {token}"))
+            }
+            async fn download_with_limit(&self, url: &str, _max_size: u64) -> Result<Vec<u8>> {
+                self.offline(format!("DOWNLOAD {url}")).map(|_| Vec::new())
+            }
+        }
+
+        struct ShoutingRenderer;
+
+        impl ContentRenderer for ShoutingRenderer {
+            fn render_content(
+                &self,
+                markdown: &str,
+                _theme: &str,
+                _code_theme: &str,
+                _metadata: &HashMap<String, String>,
+            ) -> Result<String> {
+                Ok(format!("<p>{}</p>", markdown.trim().to_uppercase()))
+            }
+            fn available_themes(&self) -> Vec<String> {
+                vec!["plain".to_string()]
+            }
+            fn has_theme(&self, theme: &str) -> bool {
+                theme == "plain"
+            }
+            fn validate_theme(&self, theme: &str) -> Result<()> {
+                if self.has_theme(theme) {
+                    Ok(())
+                } else {
+                    Err(WeChatError::ThemeNotFound {
+                        theme: theme.to_string(),
+                    })
+                }
+            }
+        }
+
+        // Credentials are required without a token provider
+        assert!(WeChatClient::builder().build().is_err());
+
+        let transport = OfflineTransport::default();
+        let client = WeChatClient::builder()
+            .http_client(transport.clone())
+            .token_provider(StaticTokens)
+            .renderer(ShoutingRenderer)
+            .build()
+            .unwrap();
+
+        let result = client
+            .raw_post("/cgi-bin/draft/count", serde_json::json!({}))
+            .await;
+        assert!(matches!(result, Err(WeChatError::Network { .. })));
+        assert_eq!(
+            *transport.0.lock().unwrap(),
+            ["POST /cgi-bin/draft/count TOKEN"]
+        );
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("cover.png"), b"cover").unwrap();
+        let markdown_path = temp_dir.path().join("post.md");
+        std::fs::write(
+            &markdown_path,
+            "---\ntitle: Custom\ncover: cover.png\n---\n\nhello\n",
+        )
+        .unwrap();
+        let path = markdown_path.display().to_string();
+
+        let report = client
+            .upload_with_report(&path, UploadOptions::with_theme("plain").dry_run(true))
+            .await
+            .unwrap();
+        assert!(report.dry_run.unwrap().html.contains("HELLO"));
+        assert!(matches!(
+            client
+                .upload_with_report(&path, UploadOptions::default().dry_run(true))
+                .await,
+            Err(WeChatError::ThemeNotFound { .. })
+        ));
+        assert!(matches!(
+            client
+                .upload_with_options(&path, UploadOptions::with_theme("plain").dry_run(true))
+                .await,
+            Err(WeChatError::Config { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_client_with_config() {
+        let config = Config::builder()
+            .security(
+                crate::config::SecurityConfig::builder()
+                    .max_upload_size(2 * 1024 * 1024)
+                    .build(),
+            )
+            .build();
+        let client = WeChatClient::with_config(
+            "wx1234567890123456",
+            "12345678901234567890123456789012",
+            config,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            client.http_client().config().security.max_upload_size,
+            2 * 1024 * 1024
+        );
+
+        let mut config = Config::default();
+        config.performance.max_concurrent_uploads = 0;
+        let result = WeChatClient::with_config(
+            "wx1234567890123456",
+            "12345678901234567890123456789012",
+            config,
+        )
+        .await;
+        assert!(matches!(result, Err(WeChatError::Config { .. })));
     }
 
     #[tokio::test]
@@ -659,6 +4467,17 @@ mod tests {
 
         let client = result.unwrap();
         assert!(client.available_themes().len() >= 4);
+        assert!(
+            client
+                .available_code_themes()
+                .contains(&&"github".to_string())
+        );
+        assert_eq!(client.code_theme_pairing("vscode"), Some("github"));
+        assert_eq!(client.code_theme_pairing("nonexistent"), None);
+        assert_eq!(
+            client.code_theme_pairings().len(),
+            client.available_code_themes().len()
+        );
         assert!(client.has_theme("default"));
         assert!(client.has_theme("lapis"));
         assert!(client.has_theme("maize"));
@@ -742,6 +4561,97 @@ Some article content here.
         assert!(result3.unwrap_err().to_string().contains("02-cover.png"));
     }
 
+    #[tokio::test]
+    async fn test_upload_html_requires_cover_image() {
+        let client = WeChatClient::new("wx1234567890123456", "12345678901234567890123456789012")
+            .await
+            .unwrap();
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let missing = temp_dir.path().join("missing.jpg");
+        match client.upload_html("Title", "<p>Hi</p>", &missing).await {
+            Err(WeChatError::FileNotFound { path }) => assert!(path.ends_with("missing.jpg")),
+            other => panic!("Expected FileNotFound, got {other:?}"),
+        }
+
+        let not_image = temp_dir.path().join("cover.txt");
+        std::fs::write(&not_image, "text").unwrap();
+        let result = client.upload_html("Title", "<p>Hi</p>", &not_image).await;
+        assert!(matches!(result, Err(WeChatError::Config { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_analyze_size() {
+        let client = WeChatClient::new("wx1234567890123456", "12345678901234567890123456789012")
+            .await
+            .unwrap();
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let markdown_path = temp_dir.path().join("post.md");
+        let chapter = "长文内容。".repeat(100);
+        std::fs::write(
+            &markdown_path,
+            format!(
+                "# Long\n\n## One\n\n{chapter}\n\n## Two\n\n{chapter}\n\n## Three\n\n{chapter}\n"
+            ),
+        )
+        .unwrap();
+        let markdown_path = markdown_path.to_str().unwrap();
+
+        let report = client
+            .analyze_size(markdown_path, &UploadOptions::default())
+            .await
+            .unwrap();
+        assert!(!report.exceeds_budget());
+        let titles: Vec<_> = report.sections.iter().map(|s| s.title.clone()).collect();
+        assert_eq!(
+            titles[1..],
+            [Some("One".into()), Some("Two".into()), Some("Three".into())]
+        );
+        assert!(report.sections[1].chars >= chapter.chars().count());
+
+        // Each chapter fits a budget the whole article doesn't
+        let budget = ContentBudget {
+            max_chars: report.chars - report.sections[1].chars,
+            ..ContentBudget::default()
+        };
+        let options = UploadOptions::default().content_budget(budget);
+        let report = client.analyze_size(markdown_path, &options).await.unwrap();
+        assert!(report.exceeds_budget());
+        assert!(report.suggested_parts().len() >= 2);
+        assert!(report.hint().starts_with("split it into"));
+    }
+
+    #[tokio::test]
+    async fn test_render_to_file_uses_local_image_paths() {
+        let client = WeChatClient::new("wx1234567890123456", "12345678901234567890123456789012")
+            .await
+            .unwrap();
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("diagram.png"), b"fake png").unwrap();
+        let markdown_path = temp_dir.path().join("post.md");
+        std::fs::write(
+            &markdown_path,
+            "---\ntitle: Preview\n---\n\n# Preview\n\n![Diagram](diagram.png)\n",
+        )
+        .unwrap();
+
+        // No cover is required for previews
+        let output_path = temp_dir.path().join("preview.html");
+        client
+            .render_to_file(
+                markdown_path.to_str().unwrap(),
+                &output_path,
+                UploadOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        let html = std::fs::read_to_string(&output_path).unwrap();
+        assert!(html.contains(r#"src="diagram.png""#));
+    }
+
     #[tokio::test]
     async fn test_fixture_file_parsing() {
         let client = WeChatClient::new("wx1234567890123456", "12345678901234567890123456789012")
@@ -0,0 +1,300 @@
+//! Comment moderation on published articles.
+//!
+//! Comments belong to an article of a sent message, identified by the message's
+//! `msg_data_id` (see [`BroadcastJob::msg_data_id`](crate::broadcast::BroadcastJob)) and
+//! the position of the article in it. A [`CommentManager`] opens and closes comments,
+//! lists them page by page, replies to them, elects them (shows them to all readers) and
+//! deletes them. [`WeChatClient`](crate::WeChatClient) exposes each operation, e.g.
+//! [`list_comments`](crate::WeChatClient::list_comments):
+//!
+//! ```rust,no_run
+//! use wechat_pub_rs::comments::{CommentFilter, CommentTarget};
+//!
+//! # async fn example(client: wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+//! let article = CommentTarget::new(2247483674, 0);
+//! let page = client.list_comments(article, 0, 50, CommentFilter::All).await?;
+//! for comment in page.comments {
+//!     if comment.reply.is_none() && comment.content.contains('?') {
+//!         client
+//!             .reply_to_comment(article, comment.user_comment_id, "Thanks, see the FAQ!")
+//!             .await?;
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::auth::TokenManager;
+use crate::endpoints::{self, Endpoint};
+use crate::error::{Result, WeChatError};
+use crate::http::{self, WeChatHttpClient, WeChatResponse};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use tracing::info;
+
+/// Maximum number of comments WeChat returns per page.
+pub const MAX_PAGE_SIZE: u32 = 50;
+
+/// The article of a sent message comments are managed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommentTarget {
+    /// ID of the sent message (`msg_data_id`)
+    pub msg_data_id: u64,
+    /// Position of the article in the message (0-based)
+    pub index: u32,
+}
+
+impl CommentTarget {
+    /// Creates a target for an article of a sent message.
+    pub fn new(msg_data_id: u64, index: u32) -> Self {
+        Self { msg_data_id, index }
+    }
+}
+
+/// Which comments are listed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CommentFilter {
+    /// All comments (default)
+    #[default]
+    All,
+    /// Comments that are not elected
+    Normal,
+    /// Elected comments only
+    Elected,
+}
+
+impl CommentFilter {
+    /// Gets WeChat's `type` value for the filter.
+    fn as_type(self) -> u8 {
+        match self {
+            CommentFilter::All => 0,
+            CommentFilter::Normal => 1,
+            CommentFilter::Elected => 2,
+        }
+    }
+}
+
+/// A reader's comment.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Comment {
+    /// ID of the comment within the article
+    pub user_comment_id: u64,
+    /// `openid` of the commenting follower
+    #[serde(default)]
+    pub openid: String,
+    /// When the comment was made (Unix timestamp)
+    #[serde(default)]
+    pub create_time: u64,
+    /// Text of the comment
+    #[serde(default)]
+    pub content: String,
+    /// 1 if the comment is elected, 0 otherwise
+    #[serde(default)]
+    pub comment_type: u8,
+    /// The author's reply, if any
+    #[serde(default)]
+    pub reply: Option<CommentReply>,
+}
+
+impl Comment {
+    /// Whether the comment is elected, i.e. shown to all readers.
+    pub fn is_elected(&self) -> bool {
+        self.comment_type == 1
+    }
+}
+
+/// The author's reply to a comment.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CommentReply {
+    /// Text of the reply
+    #[serde(default)]
+    pub content: String,
+    /// When the reply was made (Unix timestamp)
+    #[serde(default)]
+    pub create_time: u64,
+}
+
+/// A page of comments (`comment/list` endpoint).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CommentPage {
+    /// Total number of comments matching the filter
+    #[serde(default)]
+    pub total: u32,
+    /// Comments of the page
+    #[serde(default, rename = "comment")]
+    pub comments: Vec<Comment>,
+}
+
+/// Manages comments on published articles.
+#[derive(Debug)]
+pub struct CommentManager {
+    http_client: Arc<WeChatHttpClient>,
+    token_manager: Arc<TokenManager>,
+}
+
+impl CommentManager {
+    /// Creates a new comment manager.
+    pub fn new(http_client: Arc<WeChatHttpClient>, token_manager: Arc<TokenManager>) -> Self {
+        Self {
+            http_client,
+            token_manager,
+        }
+    }
+
+    /// Opens comments on an article.
+    pub async fn open(&self, article: CommentTarget) -> Result<()> {
+        info!("Opening comments on {article:?}");
+        self.post_unit(endpoints::COMMENT_OPEN, article_request(article))
+            .await
+    }
+
+    /// Closes comments on an article.
+    pub async fn close(&self, article: CommentTarget) -> Result<()> {
+        info!("Closing comments on {article:?}");
+        self.post_unit(endpoints::COMMENT_CLOSE, article_request(article))
+            .await
+    }
+
+    /// Lists comments on an article, `count` (at most [`MAX_PAGE_SIZE`]) starting at
+    /// `begin`.
+    pub async fn list(
+        &self,
+        article: CommentTarget,
+        begin: u32,
+        count: u32,
+        filter: CommentFilter,
+    ) -> Result<CommentPage> {
+        if count == 0 || count > MAX_PAGE_SIZE {
+            return Err(WeChatError::config_error(format!(
+                "Comment page size must be between 1 and {MAX_PAGE_SIZE}, got {count}"
+            )));
+        }
+
+        let mut request = article_request(article);
+        request["begin"] = begin.into();
+        request["count"] = count.into();
+        request["type"] = filter.as_type().into();
+        self.post(endpoints::COMMENT_LIST, request).await
+    }
+
+    /// Replies to a comment, replacing any earlier reply.
+    pub async fn reply(
+        &self,
+        article: CommentTarget,
+        comment_id: u64,
+        content: &str,
+    ) -> Result<()> {
+        info!("Replying to comment {comment_id} on {article:?}");
+        let mut request = comment_request(article, comment_id);
+        request["content"] = content.into();
+        self.post_unit(endpoints::COMMENT_REPLY_ADD, request).await
+    }
+
+    /// Deletes the reply to a comment.
+    pub async fn delete_reply(&self, article: CommentTarget, comment_id: u64) -> Result<()> {
+        info!("Deleting reply to comment {comment_id} on {article:?}");
+        self.post_unit(
+            endpoints::COMMENT_REPLY_DELETE,
+            comment_request(article, comment_id),
+        )
+        .await
+    }
+
+    /// Elects a comment, showing it to all readers.
+    pub async fn elect(&self, article: CommentTarget, comment_id: u64) -> Result<()> {
+        info!("Electing comment {comment_id} on {article:?}");
+        self.post_unit(
+            endpoints::COMMENT_ELECT,
+            comment_request(article, comment_id),
+        )
+        .await
+    }
+
+    /// Unelects a comment, showing it to its author only.
+    pub async fn unelect(&self, article: CommentTarget, comment_id: u64) -> Result<()> {
+        info!("Unelecting comment {comment_id} on {article:?}");
+        self.post_unit(
+            endpoints::COMMENT_UNELECT,
+            comment_request(article, comment_id),
+        )
+        .await
+    }
+
+    /// Deletes a comment.
+    pub async fn delete(&self, article: CommentTarget, comment_id: u64) -> Result<()> {
+        info!("Deleting comment {comment_id} on {article:?}");
+        self.post_unit(
+            endpoints::COMMENT_DELETE,
+            comment_request(article, comment_id),
+        )
+        .await
+    }
+
+    async fn post<T: DeserializeOwned + std::fmt::Debug>(
+        &self,
+        endpoint: Endpoint,
+        request: serde_json::Value,
+    ) -> Result<T> {
+        let access_token = self.token_manager.get_access_token().await?;
+        let response = self
+            .http_client
+            .post_json_with_token(endpoint.path, &access_token, &request)
+            .await?;
+
+        let response: WeChatResponse<T> = http::read_json(response).await?;
+        response.into_result()
+    }
+
+    async fn post_unit(&self, endpoint: Endpoint, request: serde_json::Value) -> Result<()> {
+        self.post::<serde_json::Value>(endpoint, request).await?;
+        Ok(())
+    }
+}
+
+/// Builds the request body identifying an article.
+fn article_request(article: CommentTarget) -> serde_json::Value {
+    serde_json::json!({ "msg_data_id": article.msg_data_id, "index": article.index })
+}
+
+/// Builds the request body identifying a comment on an article.
+fn comment_request(article: CommentTarget, comment_id: u64) -> serde_json::Value {
+    let mut request = article_request(article);
+    request["user_comment_id"] = comment_id.into();
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comment_requests() {
+        let article = CommentTarget::new(2247483674, 1);
+        assert_eq!(
+            comment_request(article, 7),
+            serde_json::json!({ "msg_data_id": 2247483674u64, "index": 1, "user_comment_id": 7 })
+        );
+        assert_eq!(CommentFilter::Elected.as_type(), 2);
+
+        let page: CommentPage = serde_json::from_str(
+            r#"{
+                "errcode": 0,
+                "total": 2,
+                "comment": [
+                    {"user_comment_id": 1, "openid": "o1", "create_time": 1700000000,
+                     "content": "Great post", "comment_type": 1,
+                     "reply": {"content": "Thanks!", "create_time": 1700000100}},
+                    {"user_comment_id": 2, "openid": "o2", "create_time": 1700000200,
+                     "content": "Question?", "comment_type": 0}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(page.total, 2);
+        assert!(page.comments[0].is_elected());
+        assert_eq!(page.comments[0].reply.as_ref().unwrap().content, "Thanks!");
+        assert!(!page.comments[1].is_elected());
+        assert!(page.comments[1].reply.is_none());
+    }
+}
@@ -37,10 +37,27 @@
 //! }
 //! ```
 
+use crate::endpoints::QuotaClass;
 use crate::error::{Result, WeChatError};
+use crate::locale::{Locale, TimeZone};
+use crate::schema::FrontmatterSchema;
+use crate::slug::SlugStrategy;
+use crate::temp::CleanupPolicy;
+#[cfg(feature = "client")]
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::time::Duration;
 
+/// Alternate WeChat API domains, in the order they are tried when the primary domain is
+/// unreachable.
+pub const FALLBACK_API_DOMAINS: &[&str] = &[
+    "https://api2.weixin.qq.com",
+    "https://sh.api.weixin.qq.com",
+    "https://sz.api.weixin.qq.com",
+];
+
 /// Main configuration structure for the WeChat SDK.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
@@ -54,6 +71,15 @@ pub struct Config {
     pub cache: CacheConfig,
     /// Retry configuration
     pub retry: RetryConfig,
+    /// Rendering configuration
+    #[serde(default)]
+    pub render: RenderConfig,
+    /// Temporary file configuration
+    #[serde(default)]
+    pub temp: TempConfig,
+    /// Locale and time zone configuration
+    #[serde(default)]
+    pub locale: LocaleConfig,
 }
 
 /// Security configuration settings.
@@ -69,6 +95,18 @@ pub struct SecurityConfig {
     pub sanitize_filenames: bool,
     /// List of blocked file extensions for security
     pub blocked_extensions: Vec<String>,
+    /// Extensions allowed in content paths although they look dangerous, e.g. `js`
+    /// for script assets of a content repository (default: none)
+    #[serde(default)]
+    pub allowed_extensions: Vec<String>,
+    /// Hidden file and directory names allowed in content paths besides `.gitignore`,
+    /// `.env` and `.dockerignore`, e.g. `.assets` (default: none)
+    #[serde(default)]
+    pub allowed_hidden_files: Vec<String>,
+    /// Directories whose files are allowed whatever their names, as long as their
+    /// extension isn't dangerous (default: none besides the system temp directories)
+    #[serde(default)]
+    pub allowed_roots: Vec<PathBuf>,
 }
 
 /// Performance configuration settings.
@@ -76,12 +114,23 @@ pub struct SecurityConfig {
 pub struct PerformanceConfig {
     /// Maximum number of concurrent uploads (default: 5)
     pub max_concurrent_uploads: usize,
+    /// Maximum number of remote images downloaded at once, independent of uploads
+    /// (default: 4)
+    #[serde(default = "default_max_concurrent_downloads")]
+    pub max_concurrent_downloads: usize,
+    /// Maximum number of Mermaid charts rendered at once across all uploads (default: 2)
+    #[serde(default = "default_max_concurrent_mermaid_renders")]
+    pub max_concurrent_mermaid_renders: usize,
     /// Cache TTL in minutes (default: 15)
     pub cache_ttl_minutes: u64,
     /// Maximum cache size in entries (default: 1000)
     pub max_cache_entries: usize,
     /// Whether to enable parallel processing (default: true)
     pub enable_parallel_processing: bool,
+    /// Maximum calls per minute for quota classes whose calls should be paced across
+    /// all tasks sharing the client (default: none)
+    #[serde(default)]
+    pub calls_per_minute: BTreeMap<QuotaClass, u32>,
 }
 
 /// HTTP client configuration settings.
@@ -93,8 +142,19 @@ pub struct HttpConfig {
     pub connect_timeout_secs: u64,
     /// Base URL for WeChat API (default: "https://api.weixin.qq.com")
     pub base_url: String,
+    /// Base URLs to fail over to when `base_url` keeps timing out (default: none)
+    #[serde(default)]
+    pub fallback_base_urls: Vec<String>,
+    /// Consecutive timeouts or connection failures before switching to the next base URL
+    /// (default: 3)
+    #[serde(default = "default_failover_threshold")]
+    pub failover_threshold: u32,
     /// User agent string for requests
     pub user_agent: String,
+    /// Extra headers sent with every request (e.g. trace headers required by a gateway).
+    /// A `User-Agent` entry takes precedence over `user_agent`.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
 }
 
 /// Cache configuration settings.
@@ -106,6 +166,10 @@ pub struct CacheConfig {
     pub enable_token_cache: bool,
     /// Cache cleanup interval in minutes (default: 60)
     pub cleanup_interval_minutes: u64,
+    /// JSON file the material cache is loaded from on first use and merged into on
+    /// shutdown or drop, so short-lived processes and accounts share it (default: none)
+    #[serde(default)]
+    pub material_cache_path: Option<PathBuf>,
 }
 
 /// Retry configuration settings.
@@ -123,6 +187,36 @@ pub struct RetryConfig {
     pub enable_jitter: bool,
 }
 
+/// Rendering configuration settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RenderConfig {
+    /// Whether unknown code highlight themes are errors instead of falling back to the
+    /// default highlight theme, like unknown themes (default: false)
+    #[serde(default)]
+    pub strict_code_themes: bool,
+    /// How documents are named in generated file names, state file keys and source URL
+    /// templates (default: file stem)
+    #[serde(default)]
+    pub slug_strategy: SlugStrategy,
+    /// Frontmatter rules every uploaded article must follow (default: none)
+    #[serde(default)]
+    pub frontmatter_schema: Option<FrontmatterSchema>,
+}
+
+/// Temporary file settings, see [`temp`](crate::temp).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TempConfig {
+    /// Directory for Mermaid sources, downloaded remote images and pipeline directories
+    /// (default: the `wechat-pub` directory in the system temp directory)
+    pub dir: Option<PathBuf>,
+    /// When temporary files are removed (default: always)
+    pub cleanup: CleanupPolicy,
+    /// Age in hours after which leftover temporary files are removed when a client is
+    /// created, never if `None` (default: never)
+    pub max_age_hours: Option<u64>,
+}
+
 impl Default for SecurityConfig {
     fn default() -> Self {
         Self {
@@ -146,6 +240,9 @@ impl Default for SecurityConfig {
                 "aspx".to_string(),
                 "jsp".to_string(),
             ],
+            allowed_extensions: Vec::new(),
+            allowed_hidden_files: Vec::new(),
+            allowed_roots: Vec::new(),
         }
     }
 }
@@ -154,20 +251,38 @@ impl Default for PerformanceConfig {
     fn default() -> Self {
         Self {
             max_concurrent_uploads: 5,
+            max_concurrent_downloads: default_max_concurrent_downloads(),
+            max_concurrent_mermaid_renders: default_max_concurrent_mermaid_renders(),
             cache_ttl_minutes: 15,
             max_cache_entries: 1000,
             enable_parallel_processing: true,
+            calls_per_minute: BTreeMap::new(),
         }
     }
 }
 
+fn default_max_concurrent_downloads() -> usize {
+    4
+}
+
+fn default_max_concurrent_mermaid_renders() -> usize {
+    2
+}
+
+fn default_failover_threshold() -> u32 {
+    3
+}
+
 impl Default for HttpConfig {
     fn default() -> Self {
         Self {
             request_timeout_secs: 30,
             connect_timeout_secs: 10,
             base_url: "https://api.weixin.qq.com".to_string(),
+            fallback_base_urls: Vec::new(),
+            failover_threshold: default_failover_threshold(),
             user_agent: format!("wechat-pub-rs/{}", env!("CARGO_PKG_VERSION")),
+            headers: BTreeMap::new(),
         }
     }
 }
@@ -178,10 +293,22 @@ impl Default for CacheConfig {
             enable_material_cache: true,
             enable_token_cache: true,
             cleanup_interval_minutes: 60,
+            material_cache_path: None,
         }
     }
 }
 
+/// Locale and time zone settings, see [`locale`](crate::locale).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LocaleConfig {
+    /// Language dates are written in, unless the upload options set a date format
+    /// (default: `zh-CN`)
+    pub locale: Locale,
+    /// Time zone dates are resolved in (default: `+08:00`, China Standard Time)
+    pub timezone: TimeZone,
+}
+
 impl Default for RetryConfig {
     fn default() -> Self {
         Self {
@@ -224,12 +351,29 @@ impl Config {
             })?;
         }
 
+        if let Ok(val) = std::env::var("WECHAT_MAX_CONCURRENT_DOWNLOADS") {
+            config.performance.max_concurrent_downloads = val.parse().map_err(|_| {
+                WeChatError::config_error("Invalid WECHAT_MAX_CONCURRENT_DOWNLOADS value")
+            })?;
+        }
+
+        if let Ok(val) = std::env::var("WECHAT_MAX_CONCURRENT_MERMAID_RENDERS") {
+            config.performance.max_concurrent_mermaid_renders = val.parse().map_err(|_| {
+                WeChatError::config_error("Invalid WECHAT_MAX_CONCURRENT_MERMAID_RENDERS value")
+            })?;
+        }
+
         if let Ok(val) = std::env::var("WECHAT_CACHE_TTL_MINUTES") {
             config.performance.cache_ttl_minutes = val
                 .parse()
                 .map_err(|_| WeChatError::config_error("Invalid WECHAT_CACHE_TTL_MINUTES value"))?;
         }
 
+        // Cache settings
+        if let Ok(val) = std::env::var("WECHAT_MATERIAL_CACHE_PATH") {
+            config.cache.material_cache_path = Some(PathBuf::from(val));
+        }
+
         // HTTP settings
         if let Ok(val) = std::env::var("WECHAT_REQUEST_TIMEOUT") {
             config.http.request_timeout_secs = val
@@ -241,6 +385,19 @@ impl Config {
             config.http.base_url = val;
         }
 
+        if let Ok(val) = std::env::var("WECHAT_FALLBACK_BASE_URLS") {
+            config.http.fallback_base_urls = val
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(String::from)
+                .collect();
+        }
+
+        if let Ok(val) = std::env::var("WECHAT_USER_AGENT") {
+            config.http.user_agent = val;
+        }
+
         // Retry settings
         if let Ok(val) = std::env::var("WECHAT_MAX_RETRIES") {
             config.retry.max_attempts = val
@@ -248,6 +405,32 @@ impl Config {
                 .map_err(|_| WeChatError::config_error("Invalid WECHAT_MAX_RETRIES value"))?;
         }
 
+        // Render settings
+        if let Ok(val) = std::env::var("WECHAT_STRICT_CODE_THEMES") {
+            config.render.strict_code_themes = val.parse().map_err(|_| {
+                WeChatError::config_error("Invalid WECHAT_STRICT_CODE_THEMES value")
+            })?;
+        }
+        if let Ok(val) = std::env::var("WECHAT_SLUG_STRATEGY") {
+            config.render.slug_strategy = val.parse()?;
+        }
+
+        // Temporary file settings
+        if let Ok(val) = std::env::var("WECHAT_TEMP_DIR") {
+            config.temp.dir = Some(PathBuf::from(val));
+        }
+        if let Ok(val) = std::env::var("WECHAT_TEMP_CLEANUP") {
+            config.temp.cleanup = val.parse()?;
+        }
+
+        // Locale settings
+        if let Ok(val) = std::env::var("WECHAT_LOCALE") {
+            config.locale.locale = val.parse()?;
+        }
+        if let Ok(val) = std::env::var("WECHAT_TIMEZONE") {
+            config.locale.timezone = val.parse()?;
+        }
+
         config.validate()?;
         Ok(config)
     }
@@ -280,6 +463,18 @@ impl Config {
             ));
         }
 
+        if self.performance.max_concurrent_downloads == 0 {
+            return Err(WeChatError::config_error(
+                "max_concurrent_downloads must be greater than 0",
+            ));
+        }
+
+        if self.performance.max_concurrent_mermaid_renders == 0 {
+            return Err(WeChatError::config_error(
+                "max_concurrent_mermaid_renders must be greater than 0",
+            ));
+        }
+
         // Validate HTTP settings
         if self.http.request_timeout_secs == 0 {
             return Err(WeChatError::config_error(
@@ -297,6 +492,26 @@ impl Config {
             return Err(WeChatError::config_error("base_url cannot be empty"));
         }
 
+        if self
+            .http
+            .fallback_base_urls
+            .iter()
+            .any(|url| url.is_empty())
+        {
+            return Err(WeChatError::config_error(
+                "fallback_base_urls cannot contain empty URLs",
+            ));
+        }
+
+        if self.http.failover_threshold == 0 {
+            return Err(WeChatError::config_error(
+                "failover_threshold must be greater than 0",
+            ));
+        }
+
+        #[cfg(feature = "client")]
+        self.http.header_map()?;
+
         // Validate retry settings
         if self.retry.max_attempts == 0 {
             return Err(WeChatError::config_error(
@@ -308,6 +523,13 @@ impl Config {
             return Err(WeChatError::config_error("backoff_factor must be >= 1.0"));
         }
 
+        // Validate temporary file settings
+        if self.temp.max_age_hours == Some(0) {
+            return Err(WeChatError::config_error(
+                "max_age_hours must be greater than 0",
+            ));
+        }
+
         Ok(())
     }
 
@@ -335,6 +557,13 @@ impl Config {
     pub fn cache_ttl(&self) -> Duration {
         Duration::from_secs(self.performance.cache_ttl_minutes * 60)
     }
+
+    /// Converts the temporary file age limit to a Duration for easier use.
+    pub fn temp_max_age(&self) -> Option<Duration> {
+        self.temp
+            .max_age_hours
+            .map(|hours| Duration::from_secs(hours * 3600))
+    }
 }
 
 /// Builder for creating Config instances.
@@ -345,6 +574,9 @@ pub struct ConfigBuilder {
     http: Option<HttpConfig>,
     cache: Option<CacheConfig>,
     retry: Option<RetryConfig>,
+    render: Option<RenderConfig>,
+    temp: Option<TempConfig>,
+    locale: Option<LocaleConfig>,
 }
 
 impl ConfigBuilder {
@@ -378,6 +610,24 @@ impl ConfigBuilder {
         self
     }
 
+    /// Sets the rendering configuration.
+    pub fn render(mut self, render: RenderConfig) -> Self {
+        self.render = Some(render);
+        self
+    }
+
+    /// Sets the temporary file configuration.
+    pub fn temp(mut self, temp: TempConfig) -> Self {
+        self.temp = Some(temp);
+        self
+    }
+
+    /// Sets the locale and time zone configuration.
+    pub fn locale(mut self, locale: LocaleConfig) -> Self {
+        self.locale = Some(locale);
+        self
+    }
+
     /// Builds the configuration.
     pub fn build(self) -> Config {
         Config {
@@ -386,6 +636,9 @@ impl ConfigBuilder {
             http: self.http.unwrap_or_default(),
             cache: self.cache.unwrap_or_default(),
             retry: self.retry.unwrap_or_default(),
+            render: self.render.unwrap_or_default(),
+            temp: self.temp.unwrap_or_default(),
+            locale: self.locale.unwrap_or_default(),
         }
     }
 }
@@ -411,6 +664,22 @@ impl HttpConfig {
     pub fn builder() -> HttpConfigBuilder {
         HttpConfigBuilder::default()
     }
+
+    /// Converts the extra headers to a header map, validating names and values.
+    #[cfg(feature = "client")]
+    pub fn header_map(&self) -> Result<HeaderMap> {
+        let mut map = HeaderMap::with_capacity(self.headers.len());
+        for (name, value) in &self.headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+                WeChatError::config_error(format!("Invalid HTTP header name: {name:?}"))
+            })?;
+            let header_value = HeaderValue::from_str(value).map_err(|_| {
+                WeChatError::config_error(format!("Invalid value for HTTP header {name}"))
+            })?;
+            map.insert(header_name, header_value);
+        }
+        Ok(map)
+    }
 }
 
 impl CacheConfig {
@@ -427,6 +696,27 @@ impl RetryConfig {
     }
 }
 
+impl TempConfig {
+    /// Creates a new temporary file config builder.
+    pub fn builder() -> TempConfigBuilder {
+        TempConfigBuilder::default()
+    }
+}
+
+impl LocaleConfig {
+    /// Creates a new locale config builder.
+    pub fn builder() -> LocaleConfigBuilder {
+        LocaleConfigBuilder::default()
+    }
+}
+
+impl RenderConfig {
+    /// Creates a new render config builder.
+    pub fn builder() -> RenderConfigBuilder {
+        RenderConfigBuilder::default()
+    }
+}
+
 /// Builder for SecurityConfig.
 #[derive(Debug, Default)]
 pub struct SecurityConfigBuilder {
@@ -435,6 +725,9 @@ pub struct SecurityConfigBuilder {
     validate_file_paths: Option<bool>,
     sanitize_filenames: Option<bool>,
     blocked_extensions: Option<Vec<String>>,
+    allowed_extensions: Option<Vec<String>>,
+    allowed_hidden_files: Option<Vec<String>>,
+    allowed_roots: Option<Vec<PathBuf>>,
 }
 
 impl SecurityConfigBuilder {
@@ -463,6 +756,21 @@ impl SecurityConfigBuilder {
         self
     }
 
+    pub fn allowed_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.allowed_extensions = Some(extensions);
+        self
+    }
+
+    pub fn allowed_hidden_files(mut self, names: Vec<String>) -> Self {
+        self.allowed_hidden_files = Some(names);
+        self
+    }
+
+    pub fn allowed_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.allowed_roots = Some(roots);
+        self
+    }
+
     pub fn build(self) -> SecurityConfig {
         let default = SecurityConfig::default();
         SecurityConfig {
@@ -477,6 +785,13 @@ impl SecurityConfigBuilder {
             blocked_extensions: self
                 .blocked_extensions
                 .unwrap_or(default.blocked_extensions),
+            allowed_extensions: self
+                .allowed_extensions
+                .unwrap_or(default.allowed_extensions),
+            allowed_hidden_files: self
+                .allowed_hidden_files
+                .unwrap_or(default.allowed_hidden_files),
+            allowed_roots: self.allowed_roots.unwrap_or(default.allowed_roots),
         }
     }
 }
@@ -485,9 +800,12 @@ impl SecurityConfigBuilder {
 #[derive(Debug, Default)]
 pub struct PerformanceConfigBuilder {
     max_concurrent_uploads: Option<usize>,
+    max_concurrent_downloads: Option<usize>,
+    max_concurrent_mermaid_renders: Option<usize>,
     cache_ttl_minutes: Option<u64>,
     max_cache_entries: Option<usize>,
     enable_parallel_processing: Option<bool>,
+    calls_per_minute: BTreeMap<QuotaClass, u32>,
 }
 
 impl PerformanceConfigBuilder {
@@ -496,6 +814,16 @@ impl PerformanceConfigBuilder {
         self
     }
 
+    pub fn max_concurrent_downloads(mut self, count: usize) -> Self {
+        self.max_concurrent_downloads = Some(count);
+        self
+    }
+
+    pub fn max_concurrent_mermaid_renders(mut self, count: usize) -> Self {
+        self.max_concurrent_mermaid_renders = Some(count);
+        self
+    }
+
     pub fn cache_ttl_minutes(mut self, minutes: u64) -> Self {
         self.cache_ttl_minutes = Some(minutes);
         self
@@ -511,17 +839,31 @@ impl PerformanceConfigBuilder {
         self
     }
 
+    /// Paces calls of a quota class (e.g. [`QuotaClass::Material`]) to at most
+    /// `calls` per minute across all tasks.
+    pub fn calls_per_minute(mut self, class: QuotaClass, calls: u32) -> Self {
+        self.calls_per_minute.insert(class, calls);
+        self
+    }
+
     pub fn build(self) -> PerformanceConfig {
         let default = PerformanceConfig::default();
         PerformanceConfig {
             max_concurrent_uploads: self
                 .max_concurrent_uploads
                 .unwrap_or(default.max_concurrent_uploads),
+            max_concurrent_downloads: self
+                .max_concurrent_downloads
+                .unwrap_or(default.max_concurrent_downloads),
+            max_concurrent_mermaid_renders: self
+                .max_concurrent_mermaid_renders
+                .unwrap_or(default.max_concurrent_mermaid_renders),
             cache_ttl_minutes: self.cache_ttl_minutes.unwrap_or(default.cache_ttl_minutes),
             max_cache_entries: self.max_cache_entries.unwrap_or(default.max_cache_entries),
             enable_parallel_processing: self
                 .enable_parallel_processing
                 .unwrap_or(default.enable_parallel_processing),
+            calls_per_minute: self.calls_per_minute,
         }
     }
 }
@@ -532,7 +874,10 @@ pub struct HttpConfigBuilder {
     request_timeout_secs: Option<u64>,
     connect_timeout_secs: Option<u64>,
     base_url: Option<String>,
+    fallback_base_urls: Vec<String>,
+    failover_threshold: Option<u32>,
     user_agent: Option<String>,
+    headers: BTreeMap<String, String>,
 }
 
 impl HttpConfigBuilder {
@@ -551,11 +896,46 @@ impl HttpConfigBuilder {
         self
     }
 
+    /// Adds a base URL to fail over to when the primary keeps timing out.
+    pub fn fallback_base_url(mut self, url: impl Into<String>) -> Self {
+        self.fallback_base_urls.push(url.into());
+        self
+    }
+
+    /// Fails over to WeChat's alternate API domains ([`FALLBACK_API_DOMAINS`]).
+    pub fn default_fallbacks(mut self) -> Self {
+        self.fallback_base_urls
+            .extend(FALLBACK_API_DOMAINS.iter().map(|url| url.to_string()));
+        self
+    }
+
+    pub fn failover_threshold(mut self, failures: u32) -> Self {
+        self.failover_threshold = Some(failures);
+        self
+    }
+
     pub fn user_agent(mut self, agent: String) -> Self {
         self.user_agent = Some(agent);
         self
     }
 
+    /// Adds a header sent with every request.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Adds headers sent with every request.
+    pub fn headers<K, V>(mut self, headers: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.headers
+            .extend(headers.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
     pub fn build(self) -> HttpConfig {
         let default = HttpConfig::default();
         HttpConfig {
@@ -566,7 +946,12 @@ impl HttpConfigBuilder {
                 .connect_timeout_secs
                 .unwrap_or(default.connect_timeout_secs),
             base_url: self.base_url.unwrap_or(default.base_url),
+            fallback_base_urls: self.fallback_base_urls,
+            failover_threshold: self
+                .failover_threshold
+                .unwrap_or(default.failover_threshold),
             user_agent: self.user_agent.unwrap_or(default.user_agent),
+            headers: self.headers,
         }
     }
 }
@@ -577,6 +962,7 @@ pub struct CacheConfigBuilder {
     enable_material_cache: Option<bool>,
     enable_token_cache: Option<bool>,
     cleanup_interval_minutes: Option<u64>,
+    material_cache_path: Option<PathBuf>,
 }
 
 impl CacheConfigBuilder {
@@ -595,6 +981,11 @@ impl CacheConfigBuilder {
         self
     }
 
+    pub fn material_cache_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.material_cache_path = Some(path.into());
+        self
+    }
+
     pub fn build(self) -> CacheConfig {
         let default = CacheConfig::default();
         CacheConfig {
@@ -607,6 +998,7 @@ impl CacheConfigBuilder {
             cleanup_interval_minutes: self
                 .cleanup_interval_minutes
                 .unwrap_or(default.cleanup_interval_minutes),
+            material_cache_path: self.material_cache_path.or(default.material_cache_path),
         }
     }
 }
@@ -659,6 +1051,108 @@ impl RetryConfigBuilder {
     }
 }
 
+/// Builder for RenderConfig.
+#[derive(Debug, Default)]
+pub struct RenderConfigBuilder {
+    strict_code_themes: Option<bool>,
+    slug_strategy: Option<SlugStrategy>,
+    frontmatter_schema: Option<FrontmatterSchema>,
+}
+
+impl RenderConfigBuilder {
+    pub fn strict_code_themes(mut self, strict: bool) -> Self {
+        self.strict_code_themes = Some(strict);
+        self
+    }
+
+    pub fn slug_strategy(mut self, strategy: SlugStrategy) -> Self {
+        self.slug_strategy = Some(strategy);
+        self
+    }
+
+    pub fn frontmatter_schema(mut self, schema: FrontmatterSchema) -> Self {
+        self.frontmatter_schema = Some(schema);
+        self
+    }
+
+    pub fn build(self) -> RenderConfig {
+        let default = RenderConfig::default();
+        RenderConfig {
+            strict_code_themes: self
+                .strict_code_themes
+                .unwrap_or(default.strict_code_themes),
+            slug_strategy: self.slug_strategy.unwrap_or(default.slug_strategy),
+            frontmatter_schema: self.frontmatter_schema.or(default.frontmatter_schema),
+        }
+    }
+}
+
+/// Builder for TempConfig.
+#[derive(Debug, Default)]
+pub struct TempConfigBuilder {
+    dir: Option<PathBuf>,
+    cleanup: Option<CleanupPolicy>,
+    max_age_hours: Option<Option<u64>>,
+}
+
+impl TempConfigBuilder {
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    pub fn cleanup(mut self, policy: CleanupPolicy) -> Self {
+        self.cleanup = Some(policy);
+        self
+    }
+
+    pub fn max_age_hours(mut self, hours: u64) -> Self {
+        self.max_age_hours = Some(Some(hours));
+        self
+    }
+
+    pub fn keep_stale_files(mut self) -> Self {
+        self.max_age_hours = Some(None);
+        self
+    }
+
+    pub fn build(self) -> TempConfig {
+        let default = TempConfig::default();
+        TempConfig {
+            dir: self.dir.or(default.dir),
+            cleanup: self.cleanup.unwrap_or(default.cleanup),
+            max_age_hours: self.max_age_hours.unwrap_or(default.max_age_hours),
+        }
+    }
+}
+
+/// Builder for LocaleConfig.
+#[derive(Debug, Default)]
+pub struct LocaleConfigBuilder {
+    locale: Option<Locale>,
+    timezone: Option<TimeZone>,
+}
+
+impl LocaleConfigBuilder {
+    pub fn locale(mut self, locale: Locale) -> Self {
+        self.locale = Some(locale);
+        self
+    }
+
+    pub fn timezone(mut self, timezone: TimeZone) -> Self {
+        self.timezone = Some(timezone);
+        self
+    }
+
+    pub fn build(self) -> LocaleConfig {
+        let default = LocaleConfig::default();
+        LocaleConfig {
+            locale: self.locale.unwrap_or(default.locale),
+            timezone: self.timezone.unwrap_or(default.timezone),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -671,8 +1165,11 @@ mod tests {
         // Test default values
         assert_eq!(config.security.max_upload_size, 10 * 1024 * 1024);
         assert_eq!(config.performance.max_concurrent_uploads, 5);
+        assert_eq!(config.performance.max_concurrent_downloads, 4);
+        assert_eq!(config.performance.max_concurrent_mermaid_renders, 2);
         assert_eq!(config.http.request_timeout_secs, 30);
         assert_eq!(config.retry.max_attempts, 3);
+        assert!(!config.render.strict_code_themes);
     }
 
     #[test]
@@ -688,6 +1185,20 @@ mod tests {
                 PerformanceConfig::builder()
                     .max_concurrent_uploads(10)
                     .cache_ttl_minutes(30)
+                    .calls_per_minute(QuotaClass::Material, 20)
+                    .build(),
+            )
+            .render(
+                RenderConfig::builder()
+                    .strict_code_themes(true)
+                    .slug_strategy(SlugStrategy::Title)
+                    .build(),
+            )
+            .temp(
+                TempConfig::builder()
+                    .dir("/srv/tmp")
+                    .cleanup(CleanupPolicy::Never)
+                    .keep_stale_files()
                     .build(),
             )
             .build();
@@ -696,6 +1207,14 @@ mod tests {
         assert!(!config.security.validate_file_paths);
         assert_eq!(config.performance.max_concurrent_uploads, 10);
         assert_eq!(config.performance.cache_ttl_minutes, 30);
+        assert!(config.render.strict_code_themes);
+        assert_eq!(config.render.slug_strategy, SlugStrategy::Title);
+        assert_eq!(config.temp.dir, Some(PathBuf::from("/srv/tmp")));
+        assert_eq!(config.temp.cleanup, CleanupPolicy::Never);
+        assert_eq!(config.temp_max_age(), None);
+
+        let json = serde_json::to_value(&config.performance.calls_per_minute).unwrap();
+        assert_eq!(json, serde_json::json!({ "material": 20 }));
     }
 
     #[test]
@@ -712,9 +1231,60 @@ mod tests {
         config.performance.max_concurrent_uploads = 25;
         assert!(config.validate().is_err());
 
+        let mut config = Config::default();
+        config.performance.max_concurrent_downloads = 0;
+        assert!(config.validate().is_err());
+
         let mut config = Config::default();
         config.retry.backoff_factor = 0.5;
         assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.http.failover_threshold = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.temp.max_age_hours = Some(0);
+        assert!(config.validate().is_err());
+
+        // Headers are validated by the HTTP client
+        #[cfg(feature = "client")]
+        {
+            let mut config = Config::default();
+            config
+                .http
+                .headers
+                .insert("X-Trace Id".to_string(), "1".to_string());
+            assert!(config.validate().is_err());
+
+            let mut config = Config::default();
+            config
+                .http
+                .headers
+                .insert("X-Trace-Id".to_string(), "line\nbreak".to_string());
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "client")]
+    fn test_http_headers() {
+        let http = HttpConfig::builder()
+            .header("X-Gateway-Key", "secret")
+            .headers([("X-Trace-Id", "abc"), ("User-Agent", "my-gateway/1.0")])
+            .build();
+        assert_eq!(http.headers.len(), 3);
+
+        let map = http.header_map().unwrap();
+        assert_eq!(map["x-gateway-key"], "secret");
+        assert_eq!(map["user-agent"], "my-gateway/1.0");
+
+        // Older config files without headers still deserialize
+        let json = r#"{"request_timeout_secs":30,"connect_timeout_secs":10,"base_url":"https://api.weixin.qq.com","user_agent":"ua"}"#;
+        let http: HttpConfig = serde_json::from_str(json).unwrap();
+        assert!(http.headers.is_empty());
+        assert!(http.fallback_base_urls.is_empty());
+        assert_eq!(http.failover_threshold, 3);
     }
 
     #[test]
@@ -726,6 +1296,7 @@ mod tests {
         assert_eq!(config.request_timeout(), Duration::from_secs(30));
         assert_eq!(config.connect_timeout(), Duration::from_secs(10));
         assert_eq!(config.cache_ttl(), Duration::from_secs(15 * 60));
+        assert_eq!(config.temp_max_age(), None);
     }
 
     #[test]
@@ -0,0 +1,297 @@
+//! Structural comparison of a local render with a remote draft.
+//!
+//! Drafts are often edited in the WeChat editor after upload, which rewrites styles and
+//! markup but rarely the text. [`diff`] reduces both sides to an outline of sections
+//! (split at headings, with their whitespace-normalized text) and image counts, then
+//! reports which sections were added, removed or changed.
+//! [`WeChatClient::diff_draft`](crate::WeChatClient::diff_draft) renders a markdown file
+//! and compares it with a draft, e.g. before overwriting a colleague's edits:
+//!
+//! ```rust,no_run
+//! # async fn example(client: wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+//! let diff = client.diff_draft("article.md", "draft_media_id").await?;
+//! if !diff.is_identical() {
+//!     println!("{diff}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use scraper::{ElementRef, Html, Node};
+use std::fmt;
+
+/// Label of the section before the first heading.
+const UNTITLED_SECTION: &str = "(before first heading)";
+
+/// A section of an [`Outline`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineSection {
+    /// Heading text, `None` for the text before the first heading
+    pub title: Option<String>,
+    /// Text of the section with whitespace collapsed, without the heading
+    pub text: String,
+}
+
+/// Sections and image count of an HTML document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outline {
+    /// Sections in document order, split at every heading
+    pub sections: Vec<OutlineSection>,
+    /// Number of `<img>` elements
+    pub images: usize,
+}
+
+impl Outline {
+    /// Builds the outline of an HTML document or fragment.
+    pub fn of(html: &str) -> Self {
+        let document = Html::parse_fragment(html);
+        let mut sections = vec![OutlineSection {
+            title: None,
+            text: String::new(),
+        }];
+        let mut images = 0;
+
+        for node in document.tree.root().descendants() {
+            match node.value() {
+                Node::Element(element) if is_heading(element.name()) => {
+                    let title = ElementRef::wrap(node)
+                        .map(|heading| normalize_text(&heading.text().collect::<String>()));
+                    sections.push(OutlineSection {
+                        title,
+                        text: String::new(),
+                    });
+                }
+                Node::Element(element) if element.name() == "img" => images += 1,
+                Node::Text(text) => {
+                    // Heading text is the section title; styles and scripts aren't content
+                    let skipped = node.ancestors().any(|ancestor| {
+                        matches!(ancestor.value(), Node::Element(element)
+                            if is_heading(element.name())
+                                || matches!(element.name(), "style" | "script"))
+                    });
+                    if !skipped && let Some(section) = sections.last_mut() {
+                        section.text.push_str(text);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for section in &mut sections {
+            section.text = normalize_text(&section.text);
+        }
+        if sections[0].text.is_empty() {
+            sections.remove(0);
+        }
+
+        Self { sections, images }
+    }
+}
+
+/// Change of a section between the local render and the draft.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SectionChange {
+    /// The section only exists locally
+    Added { title: Option<String> },
+    /// The section only exists in the draft
+    Removed { title: Option<String> },
+    /// The section exists on both sides with different text
+    Modified {
+        title: Option<String>,
+        /// Characters of the section text in the local render
+        local_chars: usize,
+        /// Characters of the section text in the draft
+        remote_chars: usize,
+    },
+}
+
+/// Structural differences between a local render and a draft.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DraftDiff {
+    /// Changed sections, in local document order followed by the sections only in the
+    /// draft
+    pub changes: Vec<SectionChange>,
+    /// Number of sections with the same text on both sides
+    pub unchanged: usize,
+    /// Number of images in the local render
+    pub local_images: usize,
+    /// Number of images in the draft
+    pub remote_images: usize,
+}
+
+impl DraftDiff {
+    /// Whether both sides have the same sections, text and image count.
+    pub fn is_identical(&self) -> bool {
+        self.changes.is_empty() && self.local_images == self.remote_images
+    }
+
+    /// Gets how many more images the local render has than the draft.
+    pub fn image_delta(&self) -> isize {
+        self.local_images as isize - self.remote_images as isize
+    }
+}
+
+impl fmt::Display for DraftDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = |title: &Option<String>| title.clone().unwrap_or(UNTITLED_SECTION.into());
+        for change in &self.changes {
+            match change {
+                SectionChange::Added { title } => writeln!(f, "+ {}", label(title))?,
+                SectionChange::Removed { title } => writeln!(f, "- {}", label(title))?,
+                SectionChange::Modified {
+                    title,
+                    local_chars,
+                    remote_chars,
+                } => writeln!(
+                    f,
+                    "~ {} ({remote_chars} -> {local_chars} characters)",
+                    label(title)
+                )?,
+            }
+        }
+        write!(
+            f,
+            "{} sections unchanged; images: {} local, {} in draft ({:+})",
+            self.unchanged,
+            self.local_images,
+            self.remote_images,
+            self.image_delta()
+        )
+    }
+}
+
+/// Compares the HTML of a local render with the HTML of a draft.
+///
+/// Sections are matched by heading text, in order, so a renamed heading shows up as one
+/// section removed and another added.
+pub fn diff(local_html: &str, remote_html: &str) -> DraftDiff {
+    let local = Outline::of(local_html);
+    let remote = Outline::of(remote_html);
+
+    let mut matched = vec![false; remote.sections.len()];
+    let mut changes = Vec::new();
+    let mut unchanged = 0;
+    for section in &local.sections {
+        let counterpart = remote
+            .sections
+            .iter()
+            .enumerate()
+            .find(|(index, other)| !matched[*index] && other.title == section.title);
+
+        match counterpart {
+            Some((index, other)) => {
+                matched[index] = true;
+                if other.text == section.text {
+                    unchanged += 1;
+                } else {
+                    changes.push(SectionChange::Modified {
+                        title: section.title.clone(),
+                        local_chars: section.text.chars().count(),
+                        remote_chars: other.text.chars().count(),
+                    });
+                }
+            }
+            None => changes.push(SectionChange::Added {
+                title: section.title.clone(),
+            }),
+        }
+    }
+    changes.extend(
+        remote
+            .sections
+            .iter()
+            .zip(matched)
+            .filter(|(_, matched)| !matched)
+            .map(|(section, _)| SectionChange::Removed {
+                title: section.title.clone(),
+            }),
+    );
+
+    DraftDiff {
+        changes,
+        unchanged,
+        local_images: local.images,
+        remote_images: remote.images,
+    }
+}
+
+fn is_heading(name: &str) -> bool {
+    matches!(name, "h1" | "h2" | "h3" | "h4" | "h5" | "h6")
+}
+
+/// Collapses runs of whitespace (including non-breaking spaces) into single spaces.
+fn normalize_text(text: &str) -> String {
+    text.split(|c: char| c.is_whitespace() || c == '\u{a0}')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outline() {
+        let outline = Outline::of(
+            r#"<style>p { color: red; }</style><p>Intro <b>text</b></p>
+            <h2 style="font-size: 20px">First</h2><p>One</p><img src="a.png">
+            <h2>Second</h2><p>Two&nbsp; words</p>"#,
+        );
+        assert_eq!(outline.images, 1);
+        assert_eq!(
+            outline.sections,
+            vec![
+                OutlineSection {
+                    title: None,
+                    text: "Intro text".to_string(),
+                },
+                OutlineSection {
+                    title: Some("First".to_string()),
+                    text: "One".to_string(),
+                },
+                OutlineSection {
+                    title: Some("Second".to_string()),
+                    text: "Two words".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let local = r#"<h2>Keep</h2><p>Same</p><h2>Edit</h2><p>New text</p>
+            <h2>Added</h2><p>More</p><img src="a.png"><img src="b.png">"#;
+        // Restyled by the editor, one section edited, one removed
+        let remote = r#"<section><h2><span>Keep</span></h2><p style="margin: 0">Same</p></section>
+            <h2>Edit</h2><p>Old</p><h2>Notes</h2><p>From a colleague</p><img src="x">"#;
+
+        let diff = diff(local, remote);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(
+            diff.changes,
+            vec![
+                SectionChange::Modified {
+                    title: Some("Edit".to_string()),
+                    local_chars: 8,
+                    remote_chars: 3,
+                },
+                SectionChange::Added {
+                    title: Some("Added".to_string()),
+                },
+                SectionChange::Removed {
+                    title: Some("Notes".to_string()),
+                },
+            ]
+        );
+        assert_eq!(diff.image_delta(), 1);
+        assert!(!diff.is_identical());
+        assert!(diff.to_string().contains("~ Edit (3 -> 8 characters)"));
+        assert!(
+            diff.to_string()
+                .ends_with("images: 2 local, 1 in draft (+1)")
+        );
+
+        assert!(super::diff(local, local).is_identical());
+    }
+}
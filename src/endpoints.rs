@@ -0,0 +1,446 @@
+//! Catalog of the WeChat API endpoints used by the SDK.
+//!
+//! Every API path the SDK calls is declared here once, together with a stable name for
+//! logs and metrics and the [`QuotaClass`] whose daily call quota it counts against.
+//! Paths are relative to the configured base URL; the access token is appended by
+//! [`WeChatHttpClient`](crate::http::WeChatHttpClient).
+//!
+//! ```rust
+//! use wechat_pub_rs::endpoints::{self, QuotaClass};
+//!
+//! assert_eq!(endpoints::DRAFT_ADD.path, "/cgi-bin/draft/add");
+//! assert_eq!(endpoints::DRAFT_ADD.quota, QuotaClass::Draft);
+//!
+//! // Paths with a query string are matched with it, or else on the path part
+//! let ticket = endpoints::lookup("/cgi-bin/ticket/getticket?type=jsapi").unwrap();
+//! assert_eq!(ticket.name, "ticket.jsapi");
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+/// Group of endpoints sharing a daily call quota.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaClass {
+    /// Access token issuance
+    Token,
+    /// JS-SDK ticket issuance
+    Ticket,
+    /// Draft box management
+    Draft,
+    /// Permanent material management
+    Material,
+    /// Published article management
+    Publish,
+    /// Mass messages, including previews
+    Message,
+    /// Comments on published articles
+    Comment,
+    /// Network diagnostics (IP lists)
+    Diagnostics,
+    /// Article and follower statistics (`datacube` API)
+    Analytics,
+    /// Web authorization of followers (`sns` API)
+    OAuth,
+}
+
+impl QuotaClass {
+    /// Gets the quota class name, for use as a metrics label.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QuotaClass::Token => "token",
+            QuotaClass::Ticket => "ticket",
+            QuotaClass::Draft => "draft",
+            QuotaClass::Material => "material",
+            QuotaClass::Publish => "publish",
+            QuotaClass::Message => "message",
+            QuotaClass::Comment => "comment",
+            QuotaClass::Diagnostics => "diagnostics",
+            QuotaClass::Analytics => "analytics",
+            QuotaClass::OAuth => "oauth",
+        }
+    }
+}
+
+/// A WeChat API endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Endpoint {
+    /// Path relative to the API base URL, possibly with a query string
+    pub path: &'static str,
+    /// Stable name used in logs and metrics labels
+    pub name: &'static str,
+    /// Quota the endpoint counts against
+    pub quota: QuotaClass,
+}
+
+impl Endpoint {
+    const fn new(path: &'static str, name: &'static str, quota: QuotaClass) -> Self {
+        Self { path, name, quota }
+    }
+}
+
+/// Gets an access token.
+pub const TOKEN: Endpoint = Endpoint::new("/cgi-bin/token", "token", QuotaClass::Token);
+
+/// Gets a JS-SDK ticket.
+pub const JSAPI_TICKET: Endpoint = Endpoint::new(
+    "/cgi-bin/ticket/getticket?type=jsapi",
+    "ticket.jsapi",
+    QuotaClass::Ticket,
+);
+
+/// Gets a card (`wx_card`) ticket.
+pub const WX_CARD_TICKET: Endpoint = Endpoint::new(
+    "/cgi-bin/ticket/getticket?type=wx_card",
+    "ticket.wx_card",
+    QuotaClass::Ticket,
+);
+
+/// Creates a draft.
+pub const DRAFT_ADD: Endpoint = Endpoint::new("/cgi-bin/draft/add", "draft.add", QuotaClass::Draft);
+
+/// Gets a draft.
+pub const DRAFT_GET: Endpoint = Endpoint::new("/cgi-bin/draft/get", "draft.get", QuotaClass::Draft);
+
+/// Updates an article of a draft.
+pub const DRAFT_UPDATE: Endpoint =
+    Endpoint::new("/cgi-bin/draft/update", "draft.update", QuotaClass::Draft);
+
+/// Deletes a draft.
+pub const DRAFT_DELETE: Endpoint =
+    Endpoint::new("/cgi-bin/draft/delete", "draft.delete", QuotaClass::Draft);
+
+/// Lists drafts.
+pub const DRAFT_BATCHGET: Endpoint = Endpoint::new(
+    "/cgi-bin/draft/batchget",
+    "draft.batchget",
+    QuotaClass::Draft,
+);
+
+/// Counts drafts.
+pub const DRAFT_COUNT: Endpoint =
+    Endpoint::new("/cgi-bin/draft/count", "draft.count", QuotaClass::Draft);
+
+/// Uploads a permanent material.
+pub const MATERIAL_ADD: Endpoint = Endpoint::new(
+    "/cgi-bin/material/add_material",
+    "material.add",
+    QuotaClass::Material,
+);
+
+/// Lists permanent materials.
+pub const MATERIAL_BATCHGET: Endpoint = Endpoint::new(
+    "/cgi-bin/material/batchget_material",
+    "material.batchget",
+    QuotaClass::Material,
+);
+
+/// Deletes a permanent material.
+pub const MATERIAL_DELETE: Endpoint = Endpoint::new(
+    "/cgi-bin/material/del_material",
+    "material.delete",
+    QuotaClass::Material,
+);
+
+/// Counts permanent materials by type.
+pub const MATERIAL_COUNT: Endpoint = Endpoint::new(
+    "/cgi-bin/material/get_materialcount",
+    "material.count",
+    QuotaClass::Material,
+);
+
+/// Lists published articles.
+pub const PUBLISH_BATCHGET: Endpoint = Endpoint::new(
+    "/cgi-bin/freepublish/batchget",
+    "publish.batchget",
+    QuotaClass::Publish,
+);
+
+/// Publishes a draft.
+pub const PUBLISH_SUBMIT: Endpoint = Endpoint::new(
+    "/cgi-bin/freepublish/submit",
+    "publish.submit",
+    QuotaClass::Publish,
+);
+
+/// Gets the status of a publish job.
+pub const PUBLISH_GET: Endpoint = Endpoint::new(
+    "/cgi-bin/freepublish/get",
+    "publish.get",
+    QuotaClass::Publish,
+);
+
+/// Gets the articles of a publish job by article ID.
+pub const PUBLISH_GETARTICLE: Endpoint = Endpoint::new(
+    "/cgi-bin/freepublish/getarticle",
+    "publish.getarticle",
+    QuotaClass::Publish,
+);
+
+/// Sends a preview of a mass message to a single follower.
+pub const MESSAGE_PREVIEW: Endpoint = Endpoint::new(
+    "/cgi-bin/message/mass/preview",
+    "message.preview",
+    QuotaClass::Message,
+);
+
+/// Sends a mass message to all followers or the followers with a tag.
+pub const MESSAGE_SENDALL: Endpoint = Endpoint::new(
+    "/cgi-bin/message/mass/sendall",
+    "message.sendall",
+    QuotaClass::Message,
+);
+
+/// Sends a mass message to a list of followers.
+pub const MESSAGE_SEND: Endpoint = Endpoint::new(
+    "/cgi-bin/message/mass/send",
+    "message.send",
+    QuotaClass::Message,
+);
+
+/// Gets the status of a mass message.
+pub const MESSAGE_GET: Endpoint = Endpoint::new(
+    "/cgi-bin/message/mass/get",
+    "message.get",
+    QuotaClass::Message,
+);
+
+/// Deletes a sent mass message.
+pub const MESSAGE_DELETE: Endpoint = Endpoint::new(
+    "/cgi-bin/message/mass/delete",
+    "message.delete",
+    QuotaClass::Message,
+);
+
+/// Sends a subscription message to a follower.
+pub const SUBSCRIBE_SEND: Endpoint = Endpoint::new(
+    "/cgi-bin/message/subscribe/bizsend",
+    "message.subscribe_send",
+    QuotaClass::Message,
+);
+
+/// Lists the account's subscription message templates.
+pub const SUBSCRIBE_TEMPLATES: Endpoint = Endpoint::new(
+    "/wxaapi/newtmpl/gettemplate",
+    "message.subscribe_templates",
+    QuotaClass::Message,
+);
+
+/// Opens comments on a published article.
+pub const COMMENT_OPEN: Endpoint =
+    Endpoint::new("/cgi-bin/comment/open", "comment.open", QuotaClass::Comment);
+
+/// Closes comments on a published article.
+pub const COMMENT_CLOSE: Endpoint = Endpoint::new(
+    "/cgi-bin/comment/close",
+    "comment.close",
+    QuotaClass::Comment,
+);
+
+/// Lists comments on a published article.
+pub const COMMENT_LIST: Endpoint =
+    Endpoint::new("/cgi-bin/comment/list", "comment.list", QuotaClass::Comment);
+
+/// Elects a comment.
+pub const COMMENT_ELECT: Endpoint = Endpoint::new(
+    "/cgi-bin/comment/markelect",
+    "comment.elect",
+    QuotaClass::Comment,
+);
+
+/// Unelects a comment.
+pub const COMMENT_UNELECT: Endpoint = Endpoint::new(
+    "/cgi-bin/comment/unmarkelect",
+    "comment.unelect",
+    QuotaClass::Comment,
+);
+
+/// Deletes a comment.
+pub const COMMENT_DELETE: Endpoint = Endpoint::new(
+    "/cgi-bin/comment/delete",
+    "comment.delete",
+    QuotaClass::Comment,
+);
+
+/// Replies to a comment.
+pub const COMMENT_REPLY_ADD: Endpoint = Endpoint::new(
+    "/cgi-bin/comment/reply/add",
+    "comment.reply_add",
+    QuotaClass::Comment,
+);
+
+/// Deletes the reply to a comment.
+pub const COMMENT_REPLY_DELETE: Endpoint = Endpoint::new(
+    "/cgi-bin/comment/reply/delete",
+    "comment.reply_delete",
+    QuotaClass::Comment,
+);
+
+/// Gets the daily call quota of an endpoint.
+pub const QUOTA_GET: Endpoint = Endpoint::new(
+    "/cgi-bin/openapi/quota/get",
+    "diagnostics.quota",
+    QuotaClass::Diagnostics,
+);
+
+/// Gets the IP ranges WeChat sends callbacks from.
+pub const CALLBACK_IP: Endpoint = Endpoint::new(
+    "/cgi-bin/getcallbackip",
+    "diagnostics.callback_ip",
+    QuotaClass::Diagnostics,
+);
+
+/// Gets the IP addresses of WeChat's API domain.
+pub const API_DOMAIN_IP: Endpoint = Endpoint::new(
+    "/cgi-bin/get_api_domain_ip",
+    "diagnostics.api_domain_ip",
+    QuotaClass::Diagnostics,
+);
+
+/// Gets daily statistics of articles.
+pub const DATACUBE_ARTICLE_SUMMARY: Endpoint = Endpoint::new(
+    "/datacube/getarticlesummary",
+    "analytics.article_summary",
+    QuotaClass::Analytics,
+);
+
+/// Gets cumulative statistics of articles by publishing day.
+pub const DATACUBE_ARTICLE_TOTAL: Endpoint = Endpoint::new(
+    "/datacube/getarticletotal",
+    "analytics.article_total",
+    QuotaClass::Analytics,
+);
+
+/// Gets daily reads of all articles by source.
+pub const DATACUBE_USER_READ: Endpoint = Endpoint::new(
+    "/datacube/getuserread",
+    "analytics.user_read",
+    QuotaClass::Analytics,
+);
+
+/// Gets daily follower gains and losses by source.
+pub const DATACUBE_USER_SUMMARY: Endpoint = Endpoint::new(
+    "/datacube/getusersummary",
+    "analytics.user_summary",
+    QuotaClass::Analytics,
+);
+
+/// Gets the daily total number of followers.
+pub const DATACUBE_USER_CUMULATE: Endpoint = Endpoint::new(
+    "/datacube/getusercumulate",
+    "analytics.user_cumulate",
+    QuotaClass::Analytics,
+);
+
+/// Exchanges a web authorization code for a web access token.
+pub const OAUTH_ACCESS_TOKEN: Endpoint = Endpoint::new(
+    "/sns/oauth2/access_token",
+    "oauth.access_token",
+    QuotaClass::OAuth,
+);
+
+/// Refreshes a web access token.
+pub const OAUTH_REFRESH_TOKEN: Endpoint = Endpoint::new(
+    "/sns/oauth2/refresh_token",
+    "oauth.refresh_token",
+    QuotaClass::OAuth,
+);
+
+/// Gets the profile of a follower with a web access token.
+pub const OAUTH_USERINFO: Endpoint =
+    Endpoint::new("/sns/userinfo", "oauth.userinfo", QuotaClass::OAuth);
+
+/// All endpoints used by the SDK.
+pub const ALL: &[Endpoint] = &[
+    TOKEN,
+    JSAPI_TICKET,
+    WX_CARD_TICKET,
+    DRAFT_ADD,
+    DRAFT_GET,
+    DRAFT_UPDATE,
+    DRAFT_DELETE,
+    DRAFT_BATCHGET,
+    DRAFT_COUNT,
+    MATERIAL_ADD,
+    MATERIAL_BATCHGET,
+    MATERIAL_DELETE,
+    MATERIAL_COUNT,
+    PUBLISH_BATCHGET,
+    PUBLISH_SUBMIT,
+    PUBLISH_GET,
+    PUBLISH_GETARTICLE,
+    MESSAGE_PREVIEW,
+    MESSAGE_SENDALL,
+    MESSAGE_SEND,
+    MESSAGE_GET,
+    MESSAGE_DELETE,
+    SUBSCRIBE_SEND,
+    SUBSCRIBE_TEMPLATES,
+    COMMENT_OPEN,
+    COMMENT_CLOSE,
+    COMMENT_LIST,
+    COMMENT_ELECT,
+    COMMENT_UNELECT,
+    COMMENT_DELETE,
+    COMMENT_REPLY_ADD,
+    COMMENT_REPLY_DELETE,
+    QUOTA_GET,
+    CALLBACK_IP,
+    API_DOMAIN_IP,
+    DATACUBE_ARTICLE_SUMMARY,
+    DATACUBE_ARTICLE_TOTAL,
+    DATACUBE_USER_READ,
+    DATACUBE_USER_SUMMARY,
+    DATACUBE_USER_CUMULATE,
+    OAUTH_ACCESS_TOKEN,
+    OAUTH_REFRESH_TOKEN,
+    OAUTH_USERINFO,
+];
+
+/// Finds the endpoint for a path: the one with the same path and query string, or else
+/// the first one with the same path.
+pub fn lookup(path: &str) -> Option<&'static Endpoint> {
+    ALL.iter()
+        .find(|endpoint| endpoint.path == path)
+        .or_else(|| {
+            let path = path.split('?').next().unwrap_or(path);
+            ALL.iter()
+                .find(|endpoint| endpoint.path.split('?').next() == Some(path))
+        })
+}
+
+/// Gets the metrics label for a path: the endpoint name, or `other` for paths outside
+/// the catalog (e.g. calls made through [`raw_post`](crate::WeChatClient::raw_post)).
+pub fn label(path: &str) -> &'static str {
+    lookup(path).map_or("other", |endpoint| endpoint.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_catalog_is_consistent() {
+        let paths: HashSet<_> = ALL.iter().map(|e| e.path).collect();
+        let names: HashSet<_> = ALL.iter().map(|e| e.name).collect();
+        assert_eq!(paths.len(), ALL.len());
+        assert_eq!(names.len(), ALL.len());
+
+        for endpoint in ALL {
+            assert!(
+                ["/cgi-bin/", "/datacube/", "/wxaapi/", "/sns/"]
+                    .iter()
+                    .any(|prefix| endpoint.path.starts_with(prefix))
+            );
+            assert_eq!(lookup(endpoint.path), Some(endpoint));
+        }
+
+        assert_eq!(label("/cgi-bin/draft/add?foo=bar"), "draft.add");
+        assert_eq!(
+            label("/cgi-bin/ticket/getticket?type=wx_card"),
+            "ticket.wx_card"
+        );
+        assert_eq!(label("/cgi-bin/freepublish/delete"), "other");
+    }
+}
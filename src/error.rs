@@ -40,6 +40,57 @@
 
 use std::fmt;
 
+/// What went wrong with an image in the upload pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImageErrorKind {
+    /// Reading a local image or downloading a remote one failed
+    Download,
+    /// The image exceeds a size limit
+    TooLarge,
+    /// The image format isn't accepted for upload
+    UnsupportedFormat,
+    /// Decoding, converting or re-encoding the image failed
+    Processing,
+    /// The image took longer than the per-image timeout
+    Timeout,
+    /// WeChat rejected the upload with an API error code
+    ApiRejected { code: i32 },
+}
+
+impl ImageErrorKind {
+    /// Whether trying the image again may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ImageErrorKind::Download | ImageErrorKind::Timeout => true,
+            ImageErrorKind::ApiRejected { code } => {
+                WeChatError::from_api_response(*code, "").is_retryable()
+            }
+            ImageErrorKind::TooLarge
+            | ImageErrorKind::UnsupportedFormat
+            | ImageErrorKind::Processing => false,
+        }
+    }
+}
+
+impl fmt::Display for ImageErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageErrorKind::Download => write!(f, "download failed"),
+            ImageErrorKind::TooLarge => write!(f, "too large"),
+            ImageErrorKind::UnsupportedFormat => write!(f, "unsupported format"),
+            ImageErrorKind::Processing => write!(f, "processing failed"),
+            ImageErrorKind::Timeout => write!(f, "timed out"),
+            ImageErrorKind::ApiRejected { code } => write!(f, "rejected by WeChat ({code})"),
+        }
+    }
+}
+
+/// Formats the markdown line of an image error, if known.
+fn line_suffix(line: &Option<usize>) -> String {
+    line.map(|line| format!(" (line {line})"))
+        .unwrap_or_default()
+}
+
 /// Result type alias for WeChat SDK operations.
 pub type Result<T> = std::result::Result<T, WeChatError>;
 
@@ -68,18 +119,32 @@ pub enum WeChatError {
     #[error("Failed to read file: {path}, reason: {reason}")]
     FileRead { path: String, reason: String },
 
+    /// Invalid or unsafe path referenced from content (not retryable)
+    #[error("Invalid path '{path}': {reason}")]
+    InvalidPath { path: String, reason: String },
+
     /// Markdown processing errors (not retryable)
     #[error("Markdown parsing failed: {reason}")]
     MarkdownParse { reason: String },
 
-    /// Image processing errors (may be retryable)
-    #[error("Image upload failed: {path}, reason: {reason}")]
-    ImageUpload { path: String, reason: String },
+    /// Image processing errors (may be retryable, depending on the kind)
+    #[error("Image upload failed: {path}{}, reason: {reason}", line_suffix(.line))]
+    ImageUpload {
+        path: String,
+        reason: String,
+        kind: ImageErrorKind,
+        /// Line of the image in the markdown file, if it came from one
+        line: Option<usize>,
+    },
 
     /// Theme system errors (not retryable)
     #[error("Theme not found: {theme}")]
     ThemeNotFound { theme: String },
 
+    /// Unknown `code:` highlight theme with strict code themes enabled (not retryable)
+    #[error("Code highlight theme not found: {theme}")]
+    CodeThemeNotFound { theme: String },
+
     #[error("Theme rendering failed: {theme}, reason: {reason}")]
     ThemeRender { theme: String, reason: String },
 
@@ -87,14 +152,70 @@ pub enum WeChatError {
     #[error("WeChat API error [{code}]: {message}")]
     WeChatApi { code: i32, message: String },
 
+    /// Draft was modified remotely since the last sync (not retryable)
+    #[error(
+        "Draft {media_id} was modified since the last sync (recorded update_time {expected}, found {actual}); use force to overwrite"
+    )]
+    DraftConflict {
+        media_id: String,
+        expected: u64,
+        actual: u64,
+    },
+
+    /// A draft with the same title exists and the upload must not overwrite it (not
+    /// retryable)
+    #[error("A draft titled '{title}' already exists: {media_id}")]
+    DraftExists { title: String, media_id: String },
+
+    /// The rendered article exceeds WeChat's content size limit (not retryable)
+    #[error("Article content is too large ({chars} characters, {bytes} bytes): {hint}")]
+    ContentTooLarge {
+        chars: usize,
+        bytes: usize,
+        hint: String,
+    },
+
+    /// The frontmatter breaks the configured schema (not retryable)
+    #[error("Frontmatter of {path} violates the schema: {}", crate::schema::describe(.violations))]
+    FrontmatterInvalid {
+        path: String,
+        violations: Vec<crate::schema::SchemaViolation>,
+    },
+
+    /// WeChat finished a publish job without publishing the articles (not retryable)
+    #[error("Publishing {publish_id} failed: {reason}")]
+    PublishFailed {
+        publish_id: String,
+        reason: crate::publish::PublishFailure,
+        /// Positions (1-based) of the articles that failed the checks
+        failed_articles: Vec<u32>,
+    },
+
     /// Configuration errors (not retryable)
     #[error("Configuration error: {message}")]
     Config { message: String },
 
+    /// A queued upload failed with a retryable error and stays in the upload queue,
+    /// which retries it later (not retryable by the caller)
+    #[error("Upload queued as {id} for a later retry: {reason}")]
+    Queued { id: String, reason: String },
+
+    /// The client was shut down with `shutdown` (not retryable)
+    #[error("Client has been shut down")]
+    ShutDown,
+
     /// JSON serialization/deserialization errors
     #[error("JSON processing failed: {message}")]
     Json { message: String },
 
+    /// API response that could not be parsed, with the start of its body (not retryable)
+    #[error("Invalid API response: {reason}; body: {body}")]
+    InvalidResponse { reason: String, body: String },
+
+    /// Callback message from WeChat that could not be parsed (not retryable)
+    #[error("Invalid callback message: {reason}")]
+    InvalidMessage { reason: String },
+
     /// I/O errors
     #[error("I/O error: {message}")]
     Io { message: String },
@@ -119,7 +240,7 @@ impl WeChatError {
             WeChatError::InvalidToken => true,
 
             // Some image upload errors might be retryable (network issues)
-            WeChatError::ImageUpload { .. } => true,
+            WeChatError::ImageUpload { kind, .. } => kind.is_retryable(),
 
             // WeChat API errors - check specific error codes
             WeChatError::WeChatApi { code, .. } => match code {
@@ -138,20 +259,47 @@ impl WeChatError {
         }
     }
 
+    /// Determines if the error means the access token was rejected as invalid or expired.
+    ///
+    /// Calls failing with such an error succeed after refreshing the token.
+    pub fn is_access_token_error(&self) -> bool {
+        match self {
+            WeChatError::InvalidToken => true,
+            WeChatError::WeChatApi { code, .. } => {
+                matches!(code, 40001 | 40014 | 42001 | 42007)
+            }
+            _ => false,
+        }
+    }
+
+    /// Determines if the error means a media ID (e.g. of a draft) doesn't exist.
+    pub fn is_invalid_media_id(&self) -> bool {
+        matches!(self, WeChatError::WeChatApi { code: 40007, .. })
+    }
+
     /// Gets the severity level of the error for logging purposes.
     pub fn severity(&self) -> ErrorSeverity {
         match self {
             WeChatError::Network { .. }
             | WeChatError::Timeout
-            | WeChatError::ImageUpload { .. } => ErrorSeverity::Warning,
+            | WeChatError::ImageUpload { .. }
+            | WeChatError::Queued { .. } => ErrorSeverity::Warning,
 
             WeChatError::InvalidToken | WeChatError::InvalidCredentials => ErrorSeverity::Error,
 
             WeChatError::FileNotFound { .. }
             | WeChatError::FileRead { .. }
+            | WeChatError::InvalidPath { .. }
             | WeChatError::MarkdownParse { .. }
             | WeChatError::ThemeNotFound { .. }
-            | WeChatError::Config { .. } => ErrorSeverity::Error,
+            | WeChatError::CodeThemeNotFound { .. }
+            | WeChatError::DraftConflict { .. }
+            | WeChatError::DraftExists { .. }
+            | WeChatError::ContentTooLarge { .. }
+            | WeChatError::FrontmatterInvalid { .. }
+            | WeChatError::PublishFailed { .. }
+            | WeChatError::Config { .. }
+            | WeChatError::ShutDown => ErrorSeverity::Error,
 
             WeChatError::WeChatApi { code, .. } => match code {
                 // Critical API errors
@@ -162,6 +310,8 @@ impl WeChatError {
 
             WeChatError::ThemeRender { .. }
             | WeChatError::Json { .. }
+            | WeChatError::InvalidResponse { .. }
+            | WeChatError::InvalidMessage { .. }
             | WeChatError::Io { .. }
             | WeChatError::Internal { .. } => ErrorSeverity::Error,
         }
@@ -175,6 +325,44 @@ impl WeChatError {
         }
     }
 
+    /// Creates an image pipeline error; see [`at_line`](Self::at_line) to add the
+    /// image's markdown line.
+    pub fn image_error(
+        path: impl Into<String>,
+        kind: ImageErrorKind,
+        reason: impl Into<String>,
+    ) -> Self {
+        WeChatError::ImageUpload {
+            path: path.into(),
+            reason: reason.into(),
+            kind,
+            line: None,
+        }
+    }
+
+    /// Sets the markdown line of an image error; other errors are returned unchanged.
+    pub fn at_line(self, line: Option<usize>) -> Self {
+        match self {
+            WeChatError::ImageUpload {
+                path, reason, kind, ..
+            } => WeChatError::ImageUpload {
+                path,
+                reason,
+                kind,
+                line,
+            },
+            other => other,
+        }
+    }
+
+    /// Gets the kind of an image pipeline error, e.g. to group the failures of a batch.
+    pub fn image_error_kind(&self) -> Option<ImageErrorKind> {
+        match self {
+            WeChatError::ImageUpload { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
+
     /// Creates a file-related error.
     pub fn file_error(path: impl Into<String>, reason: impl Into<String>) -> Self {
         WeChatError::FileRead {
@@ -183,6 +371,14 @@ impl WeChatError {
         }
     }
 
+    /// Creates an invalid path error.
+    pub fn invalid_path(path: impl Into<String>, reason: impl Into<String>) -> Self {
+        WeChatError::InvalidPath {
+            path: path.into(),
+            reason: reason.into(),
+        }
+    }
+
     /// Creates a configuration error.
     pub fn config_error(message: impl Into<String>) -> Self {
         WeChatError::Config {
@@ -272,8 +468,33 @@ impl WeChatError {
             WeChatError::InvalidToken => Some("Try refreshing the access token"),
             WeChatError::InvalidCredentials => Some("Check your app_id and app_secret"),
             WeChatError::FileNotFound { .. } => Some("Check if the file path is correct"),
+            WeChatError::InvalidPath { .. } => {
+                Some("Reference files inside the markdown file's directory")
+            }
             WeChatError::ImageUpload { .. } => Some("Check file size and format"),
             WeChatError::ThemeNotFound { .. } => Some("Use a valid theme name or 'default'"),
+            WeChatError::CodeThemeNotFound { .. } => {
+                Some("Use one of the available code themes, e.g. 'github'")
+            }
+            WeChatError::DraftConflict { .. } => {
+                Some("Review the edits made in the WeChat editor, then retry with force")
+            }
+            WeChatError::DraftExists { .. } => {
+                Some("Rename the article, delete the draft or choose another existing-draft policy")
+            }
+            WeChatError::ContentTooLarge { .. } => {
+                Some("Split the article at the suggested headings or enable split_long_articles")
+            }
+            WeChatError::FrontmatterInvalid { .. } => {
+                Some("Fix the listed frontmatter keys or adjust the configured schema")
+            }
+            WeChatError::PublishFailed { .. } => {
+                Some("Fix the failed articles in the draft and publish it again")
+            }
+            WeChatError::Queued { .. } => {
+                Some("Drain the upload queue once the network is back, or run its worker")
+            }
+            WeChatError::ShutDown => Some("Create a new client"),
             WeChatError::WeChatApi { code, .. } => match code {
                 40001 => Some("Access token expired, refresh and retry"),
                 40003 => Some("Check your openid parameter"),
@@ -286,6 +507,7 @@ impl WeChatError {
     }
 }
 
+#[cfg(feature = "client")]
 impl From<reqwest::Error> for WeChatError {
     fn from(error: reqwest::Error) -> Self {
         WeChatError::Network {
@@ -364,6 +586,23 @@ mod tests {
         assert!(!param_err.is_retryable());
     }
 
+    #[test]
+    fn test_is_invalid_media_id() {
+        assert!(WeChatError::from_api_response(40007, "invalid media_id").is_invalid_media_id());
+        assert!(
+            !WeChatError::from_api_response(45009, "api freq out of limit").is_invalid_media_id()
+        );
+        assert!(!WeChatError::Timeout.is_invalid_media_id());
+    }
+
+    #[test]
+    fn test_is_access_token_error() {
+        for code in [40001, 40014, 42001, 42007] {
+            assert!(WeChatError::from_api_response(code, "").is_access_token_error());
+        }
+        assert!(!WeChatError::from_api_response(40007, "invalid media_id").is_access_token_error());
+    }
+
     #[test]
     fn test_error_severity() {
         let network_err = WeChatError::Timeout;
@@ -376,6 +615,20 @@ mod tests {
         assert_eq!(critical_api_err.severity(), ErrorSeverity::Critical);
     }
 
+    #[test]
+    fn test_draft_conflict_error() {
+        let err = WeChatError::DraftConflict {
+            media_id: "media_1".to_string(),
+            expected: 100,
+            actual: 150,
+        };
+
+        assert!(!err.is_retryable());
+        assert_eq!(err.severity(), ErrorSeverity::Error);
+        assert!(err.to_string().contains("use force to overwrite"));
+        assert!(err.recovery_suggestion().is_some());
+    }
+
     #[test]
     fn test_error_creation_helpers() {
         let file_err = WeChatError::file_error("/path/to/file.md", "permission denied");
@@ -453,6 +706,33 @@ mod tests {
         assert!(!config_err.is_temporary());
     }
 
+    #[test]
+    fn test_image_errors() {
+        let error = WeChatError::image_error(
+            "images/huge.png",
+            ImageErrorKind::TooLarge,
+            "File too large",
+        );
+        assert_eq!(
+            error.to_string(),
+            "Image upload failed: images/huge.png, reason: File too large"
+        );
+        assert!(!error.is_retryable());
+
+        let error = error.at_line(Some(12));
+        assert_eq!(
+            error.to_string(),
+            "Image upload failed: images/huge.png (line 12), reason: File too large"
+        );
+        assert_eq!(error.image_error_kind(), Some(ImageErrorKind::TooLarge));
+
+        let rejected = ImageErrorKind::ApiRejected { code: 45009 };
+        assert!(rejected.is_retryable());
+        assert!(!ImageErrorKind::ApiRejected { code: 40005 }.is_retryable());
+        assert_eq!(rejected.to_string(), "rejected by WeChat (45009)");
+        assert_eq!(WeChatError::Timeout.image_error_kind(), None);
+    }
+
     #[test]
     fn test_recovery_suggestion() {
         // Token errors should suggest refresh
@@ -0,0 +1,151 @@
+//! Credential and environment health checks.
+//!
+//! [`WeChatClient::health_check`](crate::WeChatClient::health_check) fetches an access
+//! token, calls a cheap API endpoint and turns the outcome into a [`HealthDiagnosis`],
+//! so deployment checks can tell a missing IP whitelist entry apart from bad
+//! credentials or a network problem.
+//!
+//! ```rust,no_run
+//! use wechat_pub_rs::{WeChatClient, health::HealthDiagnosis};
+//!
+//! # async fn example() -> wechat_pub_rs::Result<()> {
+//! let client = WeChatClient::new("your_app_id", "your_app_secret").await?;
+//! let report = client.health_check().await;
+//!
+//! if let HealthDiagnosis::IpNotWhitelisted { ip } = &report.diagnosis {
+//!     eprintln!("Add {} to the IP whitelist", ip.as_deref().unwrap_or("this host"));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::WeChatError;
+use regex::Regex;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// WeChat error code returned when the caller's IP is not in the account's whitelist.
+pub const IP_NOT_WHITELISTED: i32 = 40164;
+
+/// WeChat error codes that indicate invalid app credentials.
+const CREDENTIAL_ERROR_CODES: &[i32] = &[40001, 40002, 40013, 40125, 41002, 41004];
+
+/// Extracts the rejected IP from a 40164 error message.
+static INVALID_IP_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"invalid ip ([0-9A-Fa-f:.]+)").unwrap());
+
+/// Diagnosis produced by a health check.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HealthDiagnosis {
+    /// Token fetch and API call both succeeded
+    Healthy,
+    /// The current egress IP is missing from the account's IP whitelist (errcode 40164)
+    IpNotWhitelisted {
+        /// The rejected IP, when WeChat reports it
+        ip: Option<String>,
+    },
+    /// The app ID or secret was rejected
+    InvalidCredentials { code: i32, message: String },
+    /// WeChat returned another API error
+    ApiError { code: i32, message: String },
+    /// The WeChat API could not be reached
+    Unreachable { message: String },
+}
+
+impl HealthDiagnosis {
+    /// Classifies an error from the token fetch or the API call.
+    pub fn from_error(error: &WeChatError) -> Self {
+        match error {
+            WeChatError::WeChatApi { code, message } if *code == IP_NOT_WHITELISTED => {
+                HealthDiagnosis::IpNotWhitelisted {
+                    ip: INVALID_IP_REGEX
+                        .captures(message)
+                        .map(|captures| captures[1].to_string()),
+                }
+            }
+            WeChatError::WeChatApi { code, message } if CREDENTIAL_ERROR_CODES.contains(code) => {
+                HealthDiagnosis::InvalidCredentials {
+                    code: *code,
+                    message: message.clone(),
+                }
+            }
+            WeChatError::WeChatApi { code, message } => HealthDiagnosis::ApiError {
+                code: *code,
+                message: message.clone(),
+            },
+            WeChatError::InvalidCredentials | WeChatError::InvalidToken => {
+                HealthDiagnosis::InvalidCredentials {
+                    code: 0,
+                    message: error.to_string(),
+                }
+            }
+            other => HealthDiagnosis::Unreachable {
+                message: other.to_string(),
+            },
+        }
+    }
+
+    /// Whether the check passed.
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, HealthDiagnosis::Healthy)
+    }
+}
+
+/// Result of a health check.
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    /// Whether an access token could be obtained
+    pub token_ok: bool,
+    /// Whether the API probe call succeeded
+    pub api_ok: bool,
+    /// Overall diagnosis
+    pub diagnosis: HealthDiagnosis,
+    /// Wall time spent on the check
+    pub elapsed: Duration,
+}
+
+impl HealthReport {
+    /// Whether the check passed.
+    pub fn is_healthy(&self) -> bool {
+        self.diagnosis.is_healthy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ip_whitelist_diagnosis() {
+        let error = WeChatError::from_api_response(
+            40164,
+            "invalid ip 203.0.113.7 ipv6 ::ffff:203.0.113.7, not in whitelist rid: 1234",
+        );
+
+        assert_eq!(
+            HealthDiagnosis::from_error(&error),
+            HealthDiagnosis::IpNotWhitelisted {
+                ip: Some("203.0.113.7".to_string())
+            }
+        );
+    }
+
+    #[test]
+    fn test_other_diagnoses() {
+        let error = WeChatError::from_api_response(40125, "invalid appsecret");
+        assert!(matches!(
+            HealthDiagnosis::from_error(&error),
+            HealthDiagnosis::InvalidCredentials { code: 40125, .. }
+        ));
+
+        let error = WeChatError::from_api_response(45009, "reach max api daily quota limit");
+        assert!(matches!(
+            HealthDiagnosis::from_error(&error),
+            HealthDiagnosis::ApiError { code: 45009, .. }
+        ));
+
+        let diagnosis = HealthDiagnosis::from_error(&WeChatError::Timeout);
+        assert!(matches!(diagnosis, HealthDiagnosis::Unreachable { .. }));
+        assert!(!diagnosis.is_healthy());
+    }
+}
@@ -0,0 +1,476 @@
+//! Registry of code highlight themes.
+//!
+//! Code blocks are highlighted by syntect, which writes colors as inline styles, while
+//! the page also carries a highlight.js style sheet for the code block chrome. Each code
+//! theme therefore pairs a CSS file with a syntect theme; the shipped pairs use syntect
+//! themes generated from the same colors as the CSS (`themes/highlight/syntect`), so the
+//! two always agree.
+//!
+//! Custom pairs can be registered at runtime:
+//!
+//! ```rust
+//! use wechat_pub_rs::highlight::HighlightRegistry;
+//!
+//! # fn example() -> wechat_pub_rs::Result<()> {
+//! let mut registry = HighlightRegistry::new();
+//!
+//! // Reuse a bundled syntect theme with custom CSS
+//! registry.register_pair("ocean", "pre{background:#2b303b}", "base16-ocean.dark")?;
+//! assert!(registry.contains("ocean"));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Fence languages are resolved to syntect syntaxes by [`SyntaxRegistry`], which maps
+//! languages syntect doesn't ship (e.g. `vue`, `tsx`) to close relatives and can load
+//! extra `.sublime-syntax` definitions:
+//!
+//! ```rust
+//! use wechat_pub_rs::highlight::SyntaxRegistry;
+//!
+//! let mut syntaxes = SyntaxRegistry::new();
+//! syntaxes.alias("astro", "html");
+//! assert_eq!(syntaxes.resolve("astro"), "html");
+//! assert_eq!(syntaxes.resolve("vue"), "html");
+//! ```
+
+use crate::error::{Result, WeChatError};
+use comrak::nodes::{AstNode, NodeValue};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Cursor;
+use std::path::Path;
+use syntect::dumps::{dump_binary, from_binary};
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxDefinition, SyntaxSet};
+
+// Embed all highlight CSS files at compile time
+const ATOM_ONE_DARK_CSS: &str = include_str!("../themes/highlight/atom-one-dark.min.css");
+const ATOM_ONE_LIGHT_CSS: &str = include_str!("../themes/highlight/atom-one-light.min.css");
+const DRACULA_CSS: &str = include_str!("../themes/highlight/dracula.min.css");
+const GITHUB_DARK_CSS: &str = include_str!("../themes/highlight/github-dark.min.css");
+const GITHUB_CSS: &str = include_str!("../themes/highlight/github.min.css");
+const MONOKAI_CSS: &str = include_str!("../themes/highlight/monokai.min.css");
+const SOLARIZED_DARK_CSS: &str = include_str!("../themes/highlight/solarized-dark.min.css");
+const SOLARIZED_LIGHT_CSS: &str = include_str!("../themes/highlight/solarized-light.min.css");
+const XCODE_CSS: &str = include_str!("../themes/highlight/xcode.min.css");
+
+// Embed the matching syntect themes, derived from the CSS (see themes/highlight/syntect/README.md)
+const ATOM_ONE_DARK_THEME: &str = include_str!("../themes/highlight/syntect/atom-one-dark.tmTheme");
+const ATOM_ONE_LIGHT_THEME: &str =
+    include_str!("../themes/highlight/syntect/atom-one-light.tmTheme");
+const DRACULA_THEME: &str = include_str!("../themes/highlight/syntect/dracula.tmTheme");
+const GITHUB_DARK_THEME: &str = include_str!("../themes/highlight/syntect/github-dark.tmTheme");
+const GITHUB_THEME: &str = include_str!("../themes/highlight/syntect/github.tmTheme");
+const MONOKAI_THEME: &str = include_str!("../themes/highlight/syntect/monokai.tmTheme");
+const XCODE_THEME: &str = include_str!("../themes/highlight/syntect/xcode.tmTheme");
+
+/// Fence languages without a syntect syntax, mapped to the closest syntax that has one.
+const DEFAULT_LANGUAGE_ALIASES: &[(&str, &str)] = &[
+    ("vue", "html"),
+    ("svelte", "html"),
+    ("ts", "js"),
+    ("typescript", "js"),
+    ("tsx", "js"),
+    ("jsx", "js"),
+    ("mjs", "js"),
+    ("cjs", "js"),
+    ("jsonc", "json"),
+    ("json5", "json"),
+    ("shell", "bash"),
+    ("zsh", "bash"),
+    ("console", "bash"),
+];
+
+/// Code theme used when the requested one is not registered.
+pub const DEFAULT_HIGHLIGHT_THEME: &str = "github";
+
+/// A highlight CSS file paired with the syntect theme that matches it.
+#[derive(Debug, Clone)]
+struct HighlightPair {
+    css: String,
+    syntect_theme: String,
+}
+
+/// Registry mapping code theme names to highlight CSS and syntect themes.
+#[derive(Debug, Clone)]
+pub struct HighlightRegistry {
+    pairs: HashMap<String, HighlightPair>,
+    themes: BTreeMap<String, Theme>,
+}
+
+impl HighlightRegistry {
+    /// Creates a registry with the shipped highlight themes.
+    pub fn new() -> Self {
+        let mut registry = Self {
+            pairs: HashMap::new(),
+            themes: ThemeSet::load_defaults().themes,
+        };
+
+        // (theme name, CSS, generated syntect theme)
+        let generated = [
+            ("atom-one-dark", ATOM_ONE_DARK_CSS, ATOM_ONE_DARK_THEME),
+            ("atom-one-light", ATOM_ONE_LIGHT_CSS, ATOM_ONE_LIGHT_THEME),
+            ("dracula", DRACULA_CSS, DRACULA_THEME),
+            ("github-dark", GITHUB_DARK_CSS, GITHUB_DARK_THEME),
+            ("github", GITHUB_CSS, GITHUB_THEME),
+            ("monokai", MONOKAI_CSS, MONOKAI_THEME),
+            ("xcode", XCODE_CSS, XCODE_THEME),
+        ];
+
+        for (name, css, theme) in generated {
+            registry
+                .register_tm_theme(name, css, theme)
+                .expect("Bundled syntect themes should parse");
+        }
+
+        // (theme name, CSS, syntect theme bundled with syntect)
+        let bundled = [
+            ("solarized-dark", SOLARIZED_DARK_CSS, "Solarized (dark)"),
+            ("solarized-light", SOLARIZED_LIGHT_CSS, "Solarized (light)"),
+            ("vscode", GITHUB_CSS, "github"), // vscode as an alias for github
+        ];
+
+        for (name, css, syntect_theme) in bundled {
+            registry
+                .register_pair(name, css, syntect_theme)
+                .expect("Bundled syntect themes should exist");
+        }
+
+        registry
+    }
+
+    /// Registers a code theme with its own syntect theme.
+    ///
+    /// Replaces any code theme of the same name.
+    pub fn register(&mut self, name: impl Into<String>, css: impl Into<String>, theme: Theme) {
+        let name = name.into();
+        self.themes.insert(name.clone(), theme);
+        self.pairs.insert(
+            name.clone(),
+            HighlightPair {
+                css: css.into(),
+                syntect_theme: name,
+            },
+        );
+    }
+
+    /// Registers a code theme with a syntect theme parsed from `.tmTheme` source.
+    pub fn register_tm_theme(
+        &mut self,
+        name: impl Into<String>,
+        css: impl Into<String>,
+        tm_theme: &str,
+    ) -> Result<()> {
+        let name = name.into();
+        let theme = ThemeSet::load_from_reader(&mut Cursor::new(tm_theme)).map_err(|e| {
+            WeChatError::config_error(format!("Invalid syntect theme for '{name}': {e}"))
+        })?;
+
+        self.register(name, css, theme);
+        Ok(())
+    }
+
+    /// Registers a code theme that uses an already known syntect theme.
+    ///
+    /// `syntect_theme` is either one of syntect's bundled themes (e.g. `"base16-ocean.dark"`,
+    /// `"InspiredGitHub"`) or the name of a registered code theme.
+    pub fn register_pair(
+        &mut self,
+        name: impl Into<String>,
+        css: impl Into<String>,
+        syntect_theme: &str,
+    ) -> Result<()> {
+        let name = name.into();
+        if !self.themes.contains_key(syntect_theme) {
+            return Err(WeChatError::config_error(format!(
+                "Unknown syntect theme '{syntect_theme}' for highlight theme '{name}'"
+            )));
+        }
+
+        self.pairs.insert(
+            name,
+            HighlightPair {
+                css: css.into(),
+                syntect_theme: syntect_theme.to_string(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Whether a code theme is registered.
+    pub fn contains(&self, name: &str) -> bool {
+        self.pairs.contains_key(name)
+    }
+
+    /// Gets the names of all registered code themes, sorted.
+    pub fn names(&self) -> Vec<&String> {
+        let mut names: Vec<_> = self.pairs.keys().collect();
+        names.sort();
+        names
+    }
+
+    /// Gets the highlight CSS of a code theme.
+    pub fn css(&self, name: &str) -> Option<&str> {
+        self.pairs.get(name).map(|pair| pair.css.as_str())
+    }
+
+    /// Gets the name of a code theme's syntect theme.
+    pub fn syntect_theme_name(&self, name: &str) -> Option<&str> {
+        self.pairs.get(name).map(|pair| pair.syntect_theme.as_str())
+    }
+
+    /// Gets every code theme paired with the name of its syntect theme, sorted by code
+    /// theme.
+    pub fn pairings(&self) -> Vec<(&str, &str)> {
+        let mut pairings: Vec<_> = self
+            .pairs
+            .iter()
+            .map(|(name, pair)| (name.as_str(), pair.syntect_theme.as_str()))
+            .collect();
+        pairings.sort();
+        pairings
+    }
+
+    /// Gets a code theme's syntect theme.
+    pub fn syntect_theme(&self, name: &str) -> Option<&Theme> {
+        self.syntect_theme_name(name)
+            .and_then(|syntect_theme| self.themes.get(syntect_theme))
+    }
+
+    /// Builds a theme set holding just the syntect theme of a code theme, keyed by the
+    /// code theme's name, for handing to a syntect adapter.
+    pub fn theme_set_for(&self, name: &str) -> Option<ThemeSet> {
+        let theme = self.syntect_theme(name)?.clone();
+        let mut theme_set = ThemeSet::new();
+        theme_set.themes.insert(name.to_string(), theme);
+        Some(theme_set)
+    }
+}
+
+impl Default for HighlightRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Registry resolving fence languages to syntect syntaxes.
+#[derive(Debug, Clone)]
+pub struct SyntaxRegistry {
+    aliases: HashMap<String, String>,
+    extra_syntaxes: Vec<SyntaxDefinition>,
+    /// Compressed dump of the default syntaxes plus the extra ones, if any were added
+    syntax_dump: Option<Vec<u8>>,
+}
+
+impl SyntaxRegistry {
+    /// Creates a registry with the default language aliases.
+    pub fn new() -> Self {
+        Self {
+            aliases: DEFAULT_LANGUAGE_ALIASES
+                .iter()
+                .map(|(language, syntax)| (language.to_string(), syntax.to_string()))
+                .collect(),
+            extra_syntaxes: Vec::new(),
+            syntax_dump: None,
+        }
+    }
+
+    /// Highlights code fenced as `language` with the syntax for `syntax`.
+    ///
+    /// `syntax` is anything syntect resolves for a fence: a file extension (`html`) or a
+    /// syntax name (`TypeScript`). Replaces any existing alias of `language`.
+    pub fn alias(&mut self, language: impl Into<String>, syntax: impl Into<String>) {
+        self.aliases
+            .insert(language.into().to_lowercase(), syntax.into());
+    }
+
+    /// Removes the alias of `language`.
+    pub fn remove_alias(&mut self, language: &str) {
+        self.aliases.remove(&language.to_lowercase());
+    }
+
+    /// Resolves a fence language through the aliases.
+    pub fn resolve<'a>(&'a self, language: &'a str) -> &'a str {
+        self.aliases
+            .get(&language.to_lowercase())
+            .map_or(language, String::as_str)
+    }
+
+    /// Adds a syntax from `.sublime-syntax` source.
+    ///
+    /// Aliases of the languages the syntax claims (its name and file extensions) are
+    /// removed, so e.g. loading a TypeScript syntax takes over `ts` from JavaScript.
+    pub fn add_sublime_syntax(&mut self, source: &str) -> Result<()> {
+        let syntax = SyntaxDefinition::load_from_str(source, true, None)
+            .map_err(|e| WeChatError::config_error(format!("Invalid sublime-syntax: {e}")))?;
+
+        self.aliases.retain(|language, _| {
+            !syntax.name.eq_ignore_ascii_case(language)
+                && !syntax
+                    .file_extensions
+                    .iter()
+                    .any(|extension| extension.eq_ignore_ascii_case(language))
+        });
+
+        self.extra_syntaxes.push(syntax);
+        self.rebuild();
+        Ok(())
+    }
+
+    /// Adds a syntax from a `.sublime-syntax` file.
+    pub fn load_sublime_syntax_file(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| WeChatError::file_error(path.display().to_string(), e.to_string()))?;
+
+        self.add_sublime_syntax(&source)
+    }
+
+    /// Builds the syntax set used for highlighting.
+    pub fn syntax_set(&self) -> SyntaxSet {
+        match &self.syntax_dump {
+            Some(dump) => from_binary(dump),
+            None => SyntaxSet::load_defaults_newlines(),
+        }
+    }
+
+    /// Rewrites the language of every fenced code block under `root` through the aliases.
+    pub fn resolve_code_blocks<'a>(&self, root: &'a AstNode<'a>) {
+        for node in root.descendants() {
+            if let NodeValue::CodeBlock(ref mut block) = node.data.borrow_mut().value {
+                let (language, rest) = block
+                    .info
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((block.info.as_str(), ""));
+
+                let resolved = self.resolve(language);
+                if resolved != language {
+                    block.info = format!("{resolved} {rest}").trim_end().to_string();
+                }
+            }
+        }
+    }
+
+    /// Rebuilds the syntax dump from the default syntaxes and the extra ones.
+    fn rebuild(&mut self) {
+        let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+        for syntax in &self.extra_syntaxes {
+            builder.add(syntax.clone());
+        }
+
+        self.syntax_dump = Some(dump_binary(&builder.build()));
+    }
+}
+
+impl Default for SyntaxRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntect::highlighting::Color;
+
+    #[test]
+    fn test_builtin_pairs() {
+        let registry = HighlightRegistry::new();
+
+        for name in [
+            "atom-one-dark",
+            "atom-one-light",
+            "dracula",
+            "github",
+            "github-dark",
+            "monokai",
+            "solarized-dark",
+            "solarized-light",
+            "vscode",
+            "xcode",
+        ] {
+            assert!(registry.syntect_theme(name).is_some());
+            assert!(registry.css(name).is_some());
+        }
+
+        let pairings = registry.pairings();
+        assert_eq!(pairings.len(), registry.names().len());
+        assert!(pairings.contains(&("vscode", "github")));
+        assert!(pairings.contains(&("solarized-dark", "Solarized (dark)")));
+
+        // The generated themes use the background of their CSS
+        let dracula = registry.syntect_theme("dracula").unwrap();
+        assert_eq!(
+            dracula.settings.background,
+            Some(Color {
+                r: 0x28,
+                g: 0x29,
+                b: 0x36,
+                a: 0xff
+            })
+        );
+    }
+
+    #[test]
+    fn test_custom_pairs() {
+        let mut registry = HighlightRegistry::new();
+
+        registry
+            .register_pair("ocean", "pre{}", "base16-ocean.dark")
+            .unwrap();
+        assert_eq!(
+            registry.syntect_theme_name("ocean"),
+            Some("base16-ocean.dark")
+        );
+
+        assert!(registry.register_pair("nope", "", "No Such Theme").is_err());
+        assert!(
+            registry
+                .register_tm_theme("broken", "", "not a plist")
+                .is_err()
+        );
+        assert!(!registry.contains("nope"));
+
+        registry
+            .register_tm_theme("mine", "pre{}", GITHUB_THEME)
+            .unwrap();
+        assert_eq!(registry.syntect_theme_name("mine"), Some("mine"));
+        assert!(
+            registry
+                .theme_set_for("mine")
+                .unwrap()
+                .themes
+                .contains_key("mine")
+        );
+    }
+
+    const TOY_SYNTAX: &str = r#"%YAML 1.2
+---
+name: Toy
+file_extensions: [toy, ts]
+scope: source.toy
+contexts:
+  main:
+    - match: '\b(let|fn)\b'
+      scope: keyword.control.toy
+"#;
+
+    #[test]
+    fn test_language_aliases() {
+        let mut syntaxes = SyntaxRegistry::new();
+        assert_eq!(syntaxes.resolve("TSX"), "js");
+        assert_eq!(syntaxes.resolve("rust"), "rust");
+
+        syntaxes.alias("h", "cpp");
+        assert_eq!(syntaxes.resolve("h"), "cpp");
+        syntaxes.remove_alias("h");
+        assert_eq!(syntaxes.resolve("h"), "h");
+
+        // Loading a syntax takes over the languages it claims
+        syntaxes.add_sublime_syntax(TOY_SYNTAX).unwrap();
+        assert_eq!(syntaxes.resolve("ts"), "ts");
+        assert!(syntaxes.syntax_set().find_syntax_by_token("toy").is_some());
+
+        assert!(syntaxes.add_sublime_syntax("not: [valid").is_err());
+    }
+}
@@ -6,22 +6,117 @@
 //! - Retry mechanisms with exponential backoff
 //! - Safe download limits for external content
 
-use crate::config::{Config, RetryConfig, SecurityConfig};
-use crate::error::{Result, WeChatError};
+use crate::config::{Config, HttpConfig, RetryConfig, SecurityConfig};
+use crate::endpoints;
+use crate::error::{ImageErrorKind, Result, WeChatError};
+use crate::queue::OperationQueue;
+#[cfg(feature = "recording")]
+use crate::recording::{RecordMode, Recorder};
 use crate::traits::HttpClient;
-use reqwest::{Client, Response, multipart};
-use serde::{Deserialize, Serialize};
+use bytes::Bytes;
+use reqwest::{Body, Client, Method, Response, multipart};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 use std::time::Duration;
 use tokio::time::sleep;
 use tracing::{debug, warn};
 
 // Note: RetryConfig and SecurityConfig are re-exported from config module for backward compatibility
 
+/// Number of leading bytes of a download kept in [`FileDownload::head`].
+pub const DOWNLOAD_HEAD_LEN: usize = 16;
+
+/// Size of the chunks read when streaming a file into a request body.
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A download streamed to a file.
+#[derive(Debug, Clone)]
+pub struct FileDownload {
+    /// Number of bytes written
+    pub size: u64,
+    /// BLAKE3 hash of the content, computed while downloading
+    pub hash: blake3::Hash,
+    /// The first [`DOWNLOAD_HEAD_LEN`] bytes of the content, for format detection
+    pub head: Vec<u8>,
+}
+
 /// HTTP client wrapper for WeChat API calls with automatic retry and token management.
 #[derive(Debug, Clone)]
 pub struct WeChatHttpClient {
     client: Client,
     config: Config,
+    failover: Arc<BaseUrlFailover>,
+    queue: Arc<OperationQueue>,
+    #[cfg(feature = "recording")]
+    recorder: Option<Arc<Recorder>>,
+    transport: Option<Transport>,
+}
+
+/// Custom transport requests are sent through instead of reqwest.
+#[derive(Clone)]
+struct Transport(Arc<dyn HttpClient>);
+
+impl std::fmt::Debug for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Transport")
+    }
+}
+
+/// Tracks which configured base URL is in use and switches to the next one after
+/// repeated timeouts. Shared between clones of a client.
+#[derive(Debug)]
+struct BaseUrlFailover {
+    urls: Vec<String>,
+    active: AtomicUsize,
+    failures: AtomicU32,
+    threshold: u32,
+}
+
+impl BaseUrlFailover {
+    fn new(config: &HttpConfig) -> Self {
+        let mut urls = vec![config.base_url.clone()];
+        urls.extend(config.fallback_base_urls.iter().cloned());
+        Self {
+            urls,
+            active: AtomicUsize::new(0),
+            failures: AtomicU32::new(0),
+            threshold: config.failover_threshold.max(1),
+        }
+    }
+
+    fn active(&self) -> &str {
+        &self.urls[self.active.load(Ordering::Relaxed) % self.urls.len()]
+    }
+
+    /// Records the outcome of a request sent to the active base URL.
+    ///
+    /// Any HTTP response proves the domain is reachable; only timeouts and connection
+    /// failures count towards failover.
+    fn record(&self, result: &std::result::Result<Response, reqwest::Error>) {
+        match result {
+            Ok(_) => self.failures.store(0, Ordering::Relaxed),
+            Err(e) if e.is_timeout() || e.is_connect() => self.record_failure(),
+            Err(_) => {}
+        }
+    }
+
+    fn record_failure(&self) {
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures < self.threshold || self.urls.len() < 2 {
+            return;
+        }
+
+        self.failures.store(0, Ordering::Relaxed);
+        let previous = self.active.fetch_add(1, Ordering::Relaxed) % self.urls.len();
+        warn!(
+            "{} unreachable after {} consecutive failures, failing over to {}",
+            self.urls[previous],
+            failures,
+            self.active()
+        );
+    }
 }
 
 impl WeChatHttpClient {
@@ -36,9 +131,39 @@ impl WeChatHttpClient {
             .timeout(config.request_timeout())
             .connect_timeout(config.connect_timeout())
             .user_agent(&config.http.user_agent)
+            .default_headers(config.http.header_map()?)
             .build()?;
+        let failover = Arc::new(BaseUrlFailover::new(&config.http));
+        let queue = Arc::new(OperationQueue::from_config(&config.performance));
+
+        Ok(Self {
+            client,
+            config,
+            failover,
+            queue,
+            #[cfg(feature = "recording")]
+            recorder: None,
+            transport: None,
+        })
+    }
 
-        Ok(Self { client, config })
+    /// Sends API requests and downloads through a custom [`HttpClient`] instead of
+    /// reqwest, e.g. an in-memory fake in tests or a proxying transport.
+    ///
+    /// The transport is responsible for retries; the configured retries, base URL
+    /// failover and quota pacing don't apply to its requests. Material uploads go
+    /// through [`HttpClient::upload_file`] with the `media` field.
+    pub fn with_transport(mut self, transport: Arc<dyn HttpClient>) -> Self {
+        self.transport = Some(Transport(transport));
+        self
+    }
+
+    /// Records API traffic to a fixture or replays it from one, see
+    /// [`recording`](crate::recording).
+    #[cfg(feature = "recording")]
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        self.recorder = Some(Arc::new(recorder));
+        self
     }
 
     /// Creates a new client with custom retry configuration (legacy).
@@ -59,14 +184,72 @@ impl WeChatHttpClient {
         Self::with_config(config)
     }
 
+    /// Gets the configuration of the client.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Gets the base URL requests are currently sent to: the configured `base_url`, or
+    /// one of the `fallback_base_urls` after a failover.
+    pub fn active_base_url(&self) -> &str {
+        self.failover.active()
+    }
+
+    /// Gets the queue pacing quota-expensive calls made through this client and its
+    /// clones.
+    pub fn operation_queue(&self) -> &OperationQueue {
+        &self.queue
+    }
+
+    /// Builds the full URL for an API endpoint, appending the access token.
+    ///
+    /// Endpoints may carry their own query string (e.g. `?type=jsapi`).
+    fn api_url(&self, endpoint: &str, access_token: &str) -> String {
+        let separator = if endpoint.contains('?') { '&' } else { '?' };
+        format!(
+            "{}{}{}access_token={}",
+            self.active_base_url(),
+            endpoint,
+            separator,
+            access_token
+        )
+    }
+
+    /// Makes a GET request to an API endpoint that doesn't take an access token, such as
+    /// the token endpoint itself. The endpoint includes its query string.
+    pub async fn get(&self, endpoint: &str) -> Result<Response> {
+        if let Some(Transport(transport)) = &self.transport {
+            return transport.get(endpoint).await;
+        }
+        self.execute_api(Method::GET, endpoint, None, || {
+            let url = format!("{}{}", self.active_base_url(), endpoint);
+            self.client.get(url).send()
+        })
+        .await
+    }
+
+    /// Like [`get`](Self::get), but sends the request once without retrying, for
+    /// requests that must not be repeated such as exchanging a one-time code.
+    pub async fn get_once(&self, endpoint: &str) -> Result<Response> {
+        if let Some(Transport(transport)) = &self.transport {
+            return transport.get(endpoint).await;
+        }
+        self.execute_api_attempts(Method::GET, endpoint, None, 1, || {
+            let url = format!("{}{}", self.active_base_url(), endpoint);
+            self.client.get(url).send()
+        })
+        .await
+    }
+
     /// Makes a GET request with access token.
     pub async fn get_with_token(&self, endpoint: &str, access_token: &str) -> Result<Response> {
-        let url = format!(
-            "{}{}?access_token={}",
-            self.config.http.base_url, endpoint, access_token
-        );
-        self.execute_with_retry(|| self.client.get(&url).send())
-            .await
+        if let Some(Transport(transport)) = &self.transport {
+            return transport.get_with_token(endpoint, access_token).await;
+        }
+        self.execute_api(Method::GET, endpoint, None, || {
+            self.client.get(self.api_url(endpoint, access_token)).send()
+        })
+        .await
     }
 
     /// Makes a POST request with JSON body and access token.
@@ -76,23 +259,35 @@ impl WeChatHttpClient {
         access_token: &str,
         body: &T,
     ) -> Result<Response> {
-        let url = format!(
-            "{}{}?access_token={}",
-            self.config.http.base_url, endpoint, access_token
-        );
-        self.execute_with_retry(|| self.client.post(&url).json(body).send())
-            .await
+        if let Some(Transport(transport)) = &self.transport {
+            let body = serde_json::to_value(body)?;
+            return transport
+                .post_json_with_token(endpoint, access_token, &body)
+                .await;
+        }
+        let recorded_body = self.recorded_body(body);
+        self.execute_api(Method::POST, endpoint, recorded_body, || {
+            self.client
+                .post(self.api_url(endpoint, access_token))
+                .json(body)
+                .send()
+        })
+        .await
     }
 
     /// Uploads a file using multipart form data with size validation.
+    ///
+    /// The data is shared between retry attempts rather than copied for each one.
     pub async fn upload_file(
         &self,
         endpoint: &str,
         access_token: &str,
         field_name: &str,
-        file_data: Vec<u8>,
+        file_data: impl Into<Bytes>,
         filename: &str,
     ) -> Result<Response> {
+        let file_data = file_data.into();
+
         // Validate file size
         crate::utils::validate_file_size(
             file_data.len() as u64,
@@ -103,40 +298,50 @@ impl WeChatHttpClient {
 
         // Sanitize filename for security
         let safe_filename = crate::utils::sanitize_filename(filename);
-        let url = format!(
-            "{}{}?access_token={}",
-            self.config.http.base_url, endpoint, access_token
-        );
+
+        if let Some(Transport(transport)) = &self.transport {
+            return transport
+                .upload_file(
+                    endpoint,
+                    access_token,
+                    field_name,
+                    file_data,
+                    &safe_filename,
+                )
+                .await;
+        }
 
         // Guess MIME type from safe filename
         let mime_type = mime_guess::from_path(&safe_filename)
             .first_or_octet_stream()
             .to_string();
 
-        // Clone data for each retry attempt
         let field_name = field_name.to_string();
-        let url = url.clone();
-        let client = self.client.clone();
+        let recorded_body = self.recorded_body(&serde_json::json!({ "filename": safe_filename }));
 
-        self.execute_with_retry(move || {
-            let part = multipart::Part::bytes(file_data.clone())
-                .file_name(safe_filename.clone())
-                .mime_str(&mime_type)
-                .unwrap();
+        self.execute_api(Method::POST, endpoint, recorded_body, move || {
+            let part = file_part(&file_data, &safe_filename, &mime_type);
             let form = multipart::Form::new().part(field_name.clone(), part);
-            client.post(&url).multipart(form).send()
+            self.client
+                .post(self.api_url(endpoint, access_token))
+                .multipart(form)
+                .send()
         })
         .await
     }
 
     /// Uploads a permanent material (for cover images) with size validation.
+    ///
+    /// The data is shared between retry attempts rather than copied for each one.
     pub async fn upload_material(
         &self,
         access_token: &str,
         material_type: &str,
-        file_data: Vec<u8>,
+        file_data: impl Into<Bytes>,
         filename: &str,
     ) -> Result<Response> {
+        let file_data = file_data.into();
+
         // Validate file size
         crate::utils::validate_file_size(
             file_data.len() as u64,
@@ -147,38 +352,186 @@ impl WeChatHttpClient {
 
         // Sanitize filename for security
         let safe_filename = crate::utils::sanitize_filename(filename);
-        let url = format!(
-            "{}{}?access_token={}&type={}",
-            self.config.http.base_url,
-            "/cgi-bin/material/add_material",
-            access_token,
-            material_type
-        );
+        let endpoint = format!("{}?type={}", endpoints::MATERIAL_ADD.path, material_type);
+
+        if let Some(Transport(transport)) = &self.transport {
+            return transport
+                .upload_file(&endpoint, access_token, "media", file_data, &safe_filename)
+                .await;
+        }
 
         // Guess MIME type from safe filename
         let mime_type = mime_guess::from_path(&safe_filename)
             .first_or_octet_stream()
             .to_string();
 
-        // Clone data for each retry attempt
-        let url = url.clone();
-        let client = self.client.clone();
+        let recorded_body = self.recorded_body(&serde_json::json!({ "filename": safe_filename }));
+        self.execute_api(Method::POST, &endpoint, recorded_body, || {
+            let part = file_part(&file_data, &safe_filename, &mime_type);
+            let form = multipart::Form::new().part("media", part);
+
+            self.client
+                .post(self.api_url(&endpoint, access_token))
+                .multipart(form)
+                .send()
+        })
+        .await
+    }
+
+    /// Uploads a permanent material from a file, streaming it from disk.
+    ///
+    /// Unlike [`upload_material`](Self::upload_material) the file is never held in
+    /// memory; each retry attempt reads it again.
+    pub async fn upload_material_file(
+        &self,
+        access_token: &str,
+        material_type: &str,
+        path: &Path,
+        filename: &str,
+    ) -> Result<Response> {
+        let size = tokio::fs::metadata(path)
+            .await
+            .map_err(|e| WeChatError::file_error(path.display().to_string(), e.to_string()))?
+            .len();
+
+        // Validate file size
+        crate::utils::validate_file_size(size, self.config.security.max_upload_size, "material")
+            .map_err(WeChatError::config_error)?;
+
+        // Sanitize filename for security
+        let safe_filename = crate::utils::sanitize_filename(filename);
+        let endpoint = format!("{}?type={}", endpoints::MATERIAL_ADD.path, material_type);
+
+        if let Some(Transport(transport)) = &self.transport {
+            let file_data = tokio::fs::read(path)
+                .await
+                .map_err(|e| WeChatError::file_error(path.display().to_string(), e.to_string()))?;
+            return transport
+                .upload_file(
+                    &endpoint,
+                    access_token,
+                    "media",
+                    file_data.into(),
+                    &safe_filename,
+                )
+                .await;
+        }
+
+        // Guess MIME type from safe filename
+        let mime_type = mime_guess::from_path(&safe_filename)
+            .first_or_octet_stream()
+            .to_string();
 
-        self.execute_with_retry(move || {
-            let part = multipart::Part::bytes(file_data.clone())
+        let path = path.to_owned();
+        let recorded_body = self.recorded_body(&serde_json::json!({ "filename": safe_filename }));
+        self.execute_api(Method::POST, &endpoint, recorded_body, || {
+            let part = multipart::Part::stream_with_length(file_body(path.clone()), size)
                 .file_name(safe_filename.clone())
                 .mime_str(&mime_type)
                 .unwrap();
-
             let form = multipart::Form::new().part("media", part);
 
-            client.post(&url).multipart(form).send()
+            self.client
+                .post(self.api_url(&endpoint, access_token))
+                .multipart(form)
+                .send()
         })
         .await
     }
 
+    /// Executes a WeChat API request with retries, failing over to the next base URL
+    /// when the active one keeps timing out. Every attempt waits for its turn in the
+    /// [`OperationQueue`] if the endpoint's quota class is paced.
+    ///
+    /// `operation` must build its URL from [`active_base_url`](Self::active_base_url)
+    /// on every call so that retries after a failover go to the new domain.
+    ///
+    /// With a [`Recorder`](crate::recording::Recorder) attached, the request and its
+    /// response are saved, or the response is replayed without sending the request.
+    /// `body` is the JSON body saved with the request and matched when replaying.
+    async fn execute_api<F, Fut>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+        operation: F,
+    ) -> Result<Response>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = std::result::Result<Response, reqwest::Error>>,
+    {
+        let max_attempts = self.config.retry.max_attempts;
+        self.execute_api_attempts(method, path, body, max_attempts, operation)
+            .await
+    }
+
+    /// Like [`execute_api`](Self::execute_api), making at most `max_attempts` attempts.
+    async fn execute_api_attempts<F, Fut>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+        max_attempts: u32,
+        mut operation: F,
+    ) -> Result<Response>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = std::result::Result<Response, reqwest::Error>>,
+    {
+        #[cfg(feature = "recording")]
+        if let Some(recorder) = &self.recorder
+            && recorder.mode() == RecordMode::Replay
+        {
+            return recorder.replay_response(&method, path, body).await;
+        }
+
+        let endpoint = endpoints::lookup(path);
+        let label = endpoint.map_or("other", |endpoint| endpoint.name);
+
+        let response = self
+            .execute_with_retry(label, max_attempts, || {
+                let request = operation();
+                async move {
+                    if let Some(endpoint) = endpoint {
+                        self.queue.acquire(endpoint.quota).await;
+                    }
+                    let result = request.await;
+                    self.failover.record(&result);
+                    result
+                }
+            })
+            .await?;
+
+        #[cfg(feature = "recording")]
+        if let Some(recorder) = &self.recorder {
+            return recorder
+                .record_response(&method, path, body, response)
+                .await;
+        }
+        let _ = (method, body);
+        Ok(response)
+    }
+
+    /// Converts a request body for the recorder, `None` without one.
+    fn recorded_body<T: Serialize>(&self, body: &T) -> Option<serde_json::Value> {
+        #[cfg(feature = "recording")]
+        if self.recorder.is_some() {
+            return serde_json::to_value(body).ok();
+        }
+        let _ = body;
+        None
+    }
+
     /// Executes a request with intelligent retry logic.
-    async fn execute_with_retry<F, Fut>(&self, mut operation: F) -> Result<Response>
+    ///
+    /// `label` names the request in logs (an [`endpoints`] name, or `download`). At most
+    /// `max_attempts` attempts are made, fewer for errors that allow fewer retries.
+    async fn execute_with_retry<F, Fut>(
+        &self,
+        label: &str,
+        max_attempts: u32,
+        mut operation: F,
+    ) -> Result<Response>
     where
         F: FnMut() -> Fut,
         Fut: std::future::Future<Output = std::result::Result<Response, reqwest::Error>>,
@@ -186,7 +539,7 @@ impl WeChatHttpClient {
         let mut last_error = None;
         let mut consecutive_failures = 0;
 
-        for attempt in 1..=self.config.retry.max_attempts {
+        for attempt in 1..=max_attempts {
             match operation().await {
                 Ok(response) => {
                     // Check for WeChat API errors in successful HTTP responses
@@ -195,17 +548,17 @@ impl WeChatHttpClient {
                     } else {
                         // Convert HTTP error to WeChatError
                         let status = response.status();
-                        let error_text = response
-                            .text()
-                            .await
-                            .unwrap_or_else(|_| "Unknown error".to_string());
+                        let error_text = match response.bytes().await {
+                            Ok(body) => body_snippet(&body),
+                            Err(_) => "Unknown error".to_string(),
+                        };
 
                         let error = WeChatError::Internal {
                             message: format!("HTTP {status}: {error_text}"),
                         };
 
                         // Use error-specific retry logic
-                        let max_retries = error.max_retries().min(self.config.retry.max_attempts);
+                        let max_retries = error.max_retries().min(max_attempts);
                         if attempt >= max_retries || !error.is_retryable() {
                             return Err(error);
                         }
@@ -220,7 +573,7 @@ impl WeChatHttpClient {
                     };
 
                     // Use error-specific retry logic
-                    let max_retries = error.max_retries().min(self.config.retry.max_attempts);
+                    let max_retries = error.max_retries().min(max_attempts);
                     if attempt >= max_retries || !error.is_retryable() {
                         return Err(error);
                     }
@@ -231,7 +584,7 @@ impl WeChatHttpClient {
             }
 
             // Wait before retry with intelligent backoff
-            if attempt < self.config.retry.max_attempts {
+            if attempt < max_attempts {
                 // Get delay from the last error or use base delay
                 let base_delay = last_error
                     .as_ref()
@@ -258,8 +611,12 @@ impl WeChatHttpClient {
                 );
 
                 warn!(
+                    endpoint = label,
                     "Request failed (attempt {}/{}), retrying in {:?} (consecutive failures: {})",
-                    attempt, self.config.retry.max_attempts, final_delay, consecutive_failures
+                    attempt,
+                    max_attempts,
+                    final_delay,
+                    consecutive_failures
                 );
 
                 sleep(final_delay).await;
@@ -273,8 +630,15 @@ impl WeChatHttpClient {
 
     /// Downloads content from a URL.
     pub async fn download(&self, url: &str) -> Result<Vec<u8>> {
+        if let Some(Transport(transport)) = &self.transport {
+            return transport
+                .download_with_limit(url, self.config.security.max_download_size)
+                .await;
+        }
         let response = self
-            .execute_with_retry(|| self.client.get(url).send())
+            .execute_with_retry("download", self.config.retry.max_attempts, || {
+                self.client.get(url).send()
+            })
             .await?;
 
         let bytes = response.bytes().await?;
@@ -287,20 +651,27 @@ impl WeChatHttpClient {
         let effective_max_size = max_size.min(self.config.security.max_download_size);
         use futures::StreamExt;
 
+        if let Some(Transport(transport)) = &self.transport {
+            return transport.download_with_limit(url, effective_max_size).await;
+        }
+
         let response = self
-            .execute_with_retry(|| self.client.get(url).send())
+            .execute_with_retry("download", self.config.retry.max_attempts, || {
+                self.client.get(url).send()
+            })
             .await?;
 
         // Check content length if available
         if let Some(content_length) = response.content_length()
             && content_length > effective_max_size
         {
-            return Err(WeChatError::ImageUpload {
-                path: url.to_string(),
-                reason: format!(
+            return Err(WeChatError::image_error(
+                url.to_string(),
+                ImageErrorKind::TooLarge,
+                format!(
                     "Content too large: {content_length} bytes (max: {effective_max_size} bytes)"
                 ),
-            });
+            ));
         }
 
         let mut downloaded_size = 0u64;
@@ -312,12 +683,13 @@ impl WeChatHttpClient {
             downloaded_size += chunk.len() as u64;
 
             if downloaded_size > effective_max_size {
-                return Err(WeChatError::ImageUpload {
-                    path: url.to_string(),
-                    reason: format!(
+                return Err(WeChatError::image_error(
+                    url.to_string(),
+                    ImageErrorKind::TooLarge,
+                    format!(
                         "Content too large during download: {downloaded_size} bytes (max: {effective_max_size} bytes)"
                     ),
-                });
+                ));
             }
 
             data.extend_from_slice(&chunk);
@@ -326,20 +698,157 @@ impl WeChatHttpClient {
         debug!("Downloaded {downloaded_size} bytes from {url}");
         Ok(data)
     }
+
+    /// Downloads content from a URL into a file with size limits, hashing it on the way.
+    ///
+    /// Only one chunk is held in memory at a time. The file is removed if the download
+    /// fails.
+    pub async fn download_to_file(
+        &self,
+        url: &str,
+        max_size: u64,
+        dest: &Path,
+    ) -> Result<FileDownload> {
+        let result = self.stream_to_file(url, max_size, dest).await;
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(dest).await;
+        }
+        result
+    }
+
+    async fn stream_to_file(&self, url: &str, max_size: u64, dest: &Path) -> Result<FileDownload> {
+        use futures::StreamExt;
+        use tokio::io::AsyncWriteExt;
+
+        // Use the smaller of provided max_size or security config max
+        let effective_max_size = max_size.min(self.config.security.max_download_size);
+        let write_error =
+            |e: std::io::Error| WeChatError::file_error(dest.display().to_string(), e.to_string());
+
+        if let Some(Transport(transport)) = &self.transport {
+            let data = transport
+                .download_with_limit(url, effective_max_size)
+                .await?;
+            tokio::fs::write(dest, &data).await.map_err(write_error)?;
+            return Ok(FileDownload {
+                size: data.len() as u64,
+                hash: blake3::hash(&data),
+                head: data[..DOWNLOAD_HEAD_LEN.min(data.len())].to_vec(),
+            });
+        }
+
+        let response = self
+            .execute_with_retry("download", self.config.retry.max_attempts, || {
+                self.client.get(url).send()
+            })
+            .await?;
+
+        // Check content length if available
+        if let Some(content_length) = response.content_length()
+            && content_length > effective_max_size
+        {
+            return Err(WeChatError::image_error(
+                url.to_string(),
+                ImageErrorKind::TooLarge,
+                format!(
+                    "Content too large: {content_length} bytes (max: {effective_max_size} bytes)"
+                ),
+            ));
+        }
+
+        let mut file = tokio::fs::File::create(dest).await.map_err(write_error)?;
+
+        let mut hasher = blake3::Hasher::new();
+        let mut head = Vec::with_capacity(DOWNLOAD_HEAD_LEN);
+        let mut downloaded_size = 0u64;
+        let mut stream = response.bytes_stream();
+
+        while let Some(chunk_result) = stream.next().await {
+            let chunk = chunk_result?;
+            downloaded_size += chunk.len() as u64;
+
+            if downloaded_size > effective_max_size {
+                return Err(WeChatError::image_error(
+                    url.to_string(),
+                    ImageErrorKind::TooLarge,
+                    format!(
+                        "Content too large during download: {downloaded_size} bytes (max: {effective_max_size} bytes)"
+                    ),
+                ));
+            }
+
+            let head_missing = DOWNLOAD_HEAD_LEN - head.len();
+            head.extend_from_slice(&chunk[..head_missing.min(chunk.len())]);
+            hasher.update(&chunk);
+            file.write_all(&chunk).await.map_err(write_error)?;
+        }
+
+        file.flush().await.map_err(write_error)?;
+
+        debug!(
+            "Downloaded {downloaded_size} bytes from {url} to {}",
+            dest.display()
+        );
+        Ok(FileDownload {
+            size: downloaded_size,
+            hash: hasher.finalize(),
+            head,
+        })
+    }
+}
+
+/// Streams a file as a request body, opening it when the body is first polled so each
+/// retry attempt reads the file from the start.
+fn file_body(path: PathBuf) -> Body {
+    use tokio::io::AsyncReadExt;
+
+    let stream = futures::stream::try_unfold(None, move |file: Option<tokio::fs::File>| {
+        let path = path.clone();
+        async move {
+            let mut file = match file {
+                Some(file) => file,
+                None => tokio::fs::File::open(&path).await?,
+            };
+
+            let mut buffer = vec![0; UPLOAD_CHUNK_SIZE];
+            let read = file.read(&mut buffer).await?;
+            if read == 0 {
+                return Ok::<_, std::io::Error>(None);
+            }
+
+            buffer.truncate(read);
+            Ok(Some((Bytes::from(buffer), Some(file))))
+        }
+    });
+
+    Body::wrap_stream(stream)
+}
+
+/// Builds a multipart file part. Cloning `data` only bumps a reference count, so every
+/// retry attempt sends the same buffer.
+fn file_part(data: &Bytes, filename: &str, mime_type: &str) -> multipart::Part {
+    multipart::Part::stream_with_length(Body::from(data.clone()), data.len() as u64)
+        .file_name(filename.to_string())
+        .mime_str(mime_type)
+        .unwrap()
 }
 
 // Implement the HttpClient trait for WeChatHttpClient
 #[async_trait::async_trait]
 impl HttpClient for WeChatHttpClient {
+    async fn get(&self, endpoint: &str) -> Result<reqwest::Response> {
+        self.get(endpoint).await
+    }
+
     async fn get_with_token(&self, endpoint: &str, token: &str) -> Result<reqwest::Response> {
         self.get_with_token(endpoint, token).await
     }
 
-    async fn post_json_with_token<T: serde::Serialize + Send + Sync>(
+    async fn post_json_with_token(
         &self,
         endpoint: &str,
         token: &str,
-        body: &T,
+        body: &serde_json::Value,
     ) -> Result<reqwest::Response> {
         self.post_json_with_token(endpoint, token, body).await
     }
@@ -349,7 +858,7 @@ impl HttpClient for WeChatHttpClient {
         endpoint: &str,
         token: &str,
         field_name: &str,
-        file_data: Vec<u8>,
+        file_data: Bytes,
         filename: &str,
     ) -> Result<reqwest::Response> {
         self.upload_file(endpoint, token, field_name, file_data, filename)
@@ -391,6 +900,58 @@ impl<T: std::fmt::Debug> WeChatResponse<T> {
     }
 }
 
+/// Maximum number of bytes of a response body kept in errors.
+pub const BODY_SNIPPET_LEN: usize = 512;
+
+/// Gets the start of a response body for error messages, at most [`BODY_SNIPPET_LEN`]
+/// bytes long.
+pub fn body_snippet(body: &[u8]) -> String {
+    let text = String::from_utf8_lossy(body);
+    if text.len() <= BODY_SNIPPET_LEN {
+        return text.into_owned();
+    }
+
+    let mut end = BODY_SNIPPET_LEN;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}… ({} bytes)", &text[..end], body.len())
+}
+
+/// Parses a JSON response body, keeping a snippet of the body if parsing fails.
+pub fn parse_json<T: DeserializeOwned>(body: &[u8]) -> Result<T> {
+    serde_json::from_slice(body).map_err(|e| WeChatError::InvalidResponse {
+        reason: e.to_string(),
+        body: body_snippet(body),
+    })
+}
+
+/// Reads and parses a JSON response, keeping a snippet of the body if parsing fails.
+pub async fn read_json<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let body = response.bytes().await?;
+    parse_json(&body)
+}
+
+/// Checks a raw JSON response for a WeChat API error, returning the body unchanged if
+/// there is none.
+///
+/// Endpoints that succeed either omit `errcode` or set it to 0.
+pub fn check_raw_response(body: serde_json::Value) -> Result<serde_json::Value> {
+    let errcode = body
+        .get("errcode")
+        .and_then(|code| code.as_i64())
+        .unwrap_or(0);
+    if errcode == 0 {
+        return Ok(body);
+    }
+
+    let errmsg = body
+        .get("errmsg")
+        .and_then(|msg| msg.as_str())
+        .unwrap_or_default();
+    Err(WeChatError::from_api_response(errcode as i32, errmsg))
+}
+
 /// Access token response from WeChat API.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AccessTokenResponse {
@@ -398,6 +959,19 @@ pub struct AccessTokenResponse {
     pub expires_in: u64,
 }
 
+/// JS-SDK ticket response from WeChat API (getticket endpoint).
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TicketResponse {
+    pub ticket: String,
+    pub expires_in: u64,
+}
+
+/// IP list response from WeChat API (get_api_domain_ip and getcallbackip endpoints).
+#[derive(Debug, Deserialize, Serialize)]
+pub struct IpListResponse {
+    pub ip_list: Vec<String>,
+}
+
 /// Image upload response from WeChat API (uploadimg endpoint).
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ImageUploadResponse {
@@ -427,6 +1001,101 @@ mod tests {
         assert!(client.is_ok());
     }
 
+    #[test]
+    fn test_api_url() {
+        let client = WeChatHttpClient::new().unwrap();
+        assert_eq!(
+            client.api_url(endpoints::DRAFT_ADD.path, "TOKEN"),
+            "https://api.weixin.qq.com/cgi-bin/draft/add?access_token=TOKEN"
+        );
+        assert_eq!(
+            client.api_url(endpoints::JSAPI_TICKET.path, "TOKEN"),
+            "https://api.weixin.qq.com/cgi-bin/ticket/getticket?type=jsapi&access_token=TOKEN"
+        );
+    }
+
+    #[test]
+    fn test_base_url_failover() {
+        let config = Config {
+            http: HttpConfig::builder()
+                .fallback_base_url("https://api2.weixin.qq.com")
+                .failover_threshold(2)
+                .build(),
+            ..Default::default()
+        };
+        let client = WeChatHttpClient::with_config(config).unwrap();
+        let failover = &client.failover;
+
+        failover.record_failure();
+        assert_eq!(client.active_base_url(), "https://api.weixin.qq.com");
+
+        failover.record_failure();
+        assert_eq!(client.active_base_url(), "https://api2.weixin.qq.com");
+        assert_eq!(
+            client.api_url(endpoints::DRAFT_ADD.path, "TOKEN"),
+            "https://api2.weixin.qq.com/cgi-bin/draft/add?access_token=TOKEN"
+        );
+
+        // Clones share the failover state, and the last URL wraps around to the primary
+        let clone = client.clone();
+        failover.record_failure();
+        failover.record_failure();
+        assert_eq!(clone.active_base_url(), "https://api.weixin.qq.com");
+
+        // Without fallbacks the base URL never changes
+        let client = WeChatHttpClient::new().unwrap();
+        for _ in 0..10 {
+            client.failover.record_failure();
+        }
+        assert_eq!(client.active_base_url(), "https://api.weixin.qq.com");
+    }
+
+    #[test]
+    fn test_file_part_shares_buffer() {
+        let data = Bytes::from(vec![0u8; 1024]);
+        let _part = file_part(&data, "image.png", "image/png");
+
+        // Building a part references the data instead of copying it
+        assert!(!data.is_unique());
+    }
+
+    #[tokio::test]
+    async fn test_download_to_file() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let body = b"\x89PNG\r\n\x1a\n not really a png, but long enough".to_vec();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let served = body.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                let _ = socket.read(&mut request).await;
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    served.len()
+                );
+                let _ = socket.write_all(header.as_bytes()).await;
+                let _ = socket.write_all(&served).await;
+            }
+        });
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dest = temp_dir.path().join("image.tmp");
+        let client = WeChatHttpClient::new().unwrap();
+        let url = format!("http://{addr}/image.png");
+
+        let download = client.download_to_file(&url, 1024, &dest).await.unwrap();
+        assert_eq!(download.size, body.len() as u64);
+        assert_eq!(download.hash, blake3::hash(&body));
+        assert_eq!(download.head, body[..DOWNLOAD_HEAD_LEN]);
+        assert_eq!(std::fs::read(&dest).unwrap(), body);
+
+        // Oversized downloads fail and leave no file behind
+        assert!(client.download_to_file(&url, 8, &dest).await.is_err());
+        assert!(!dest.exists());
+    }
+
     #[test]
     fn test_retry_config() {
         let config = RetryConfig::default();
@@ -451,6 +1120,45 @@ mod tests {
         assert_eq!(result.unwrap().access_token, "test_token");
     }
 
+    #[test]
+    fn test_ip_list_response_parsing() {
+        let json = r#"{"ip_list":["101.226.62.77","101.226.103.0/25"]}"#;
+        let response: WeChatResponse<IpListResponse> = serde_json::from_str(json).unwrap();
+
+        let ips = response.into_result().unwrap().ip_list;
+        assert_eq!(ips, ["101.226.62.77", "101.226.103.0/25"]);
+    }
+
+    #[test]
+    fn test_parse_errors_keep_body_snippet() {
+        let body = b"<html><body>502 Bad Gateway</body></html>";
+        match parse_json::<WeChatResponse<IpListResponse>>(body) {
+            Err(WeChatError::InvalidResponse { body: snippet, .. }) => {
+                assert_eq!(snippet.as_bytes(), body);
+            }
+            other => panic!("Expected InvalidResponse error, got {other:?}"),
+        }
+
+        let long_body = "错".repeat(BODY_SNIPPET_LEN);
+        let snippet = body_snippet(long_body.as_bytes());
+        assert!(snippet.len() < long_body.len());
+        assert!(snippet.ends_with(&format!("… ({} bytes)", long_body.len())));
+    }
+
+    #[test]
+    fn test_check_raw_response() {
+        let body = serde_json::json!({ "errcode": 0, "errmsg": "ok", "total_count": 3 });
+        assert_eq!(check_raw_response(body.clone()).unwrap(), body);
+
+        let body = serde_json::json!({ "item": [] });
+        assert_eq!(check_raw_response(body.clone()).unwrap(), body);
+
+        let body = serde_json::json!({ "errcode": 40001, "errmsg": "invalid credential" });
+        let error = check_raw_response(body).unwrap_err();
+        assert!(error.is_access_token_error());
+        assert!(matches!(error, WeChatError::WeChatApi { code: 40001, .. }));
+    }
+
     #[test]
     fn test_wechat_response_error() {
         let response: WeChatResponse<AccessTokenResponse> = WeChatResponse {
@@ -0,0 +1,774 @@
+//! Optional processing of images before upload.
+//!
+//! Phone photos carry EXIF metadata (including GPS coordinates) and store their rotation
+//! as an orientation flag rather than in the pixels. WeChat doesn't always honor the flag,
+//! so such photos can show up rotated. With [`ImageProcessing::strip_metadata`] enabled,
+//! JPEG images that carry EXIF data are decoded, rotated according to their orientation
+//! flag and re-encoded without any metadata. Other images are uploaded unchanged.
+//!
+//! iPhones save photos and screenshots as HEIC, which WeChat doesn't accept. With the
+//! `heic` feature, HEIC/HEIF images are converted to JPEG
+//! ([`ImageProcessing::convert_heic`], on by default with the feature); without it they
+//! are rejected with an error saying so instead of failing at the API.
+//!
+//! Some formats upload fine but render poorly in WeChat clients (BMP, for instance, is
+//! large and blurry when scaled). An [`ImageFormatPolicy`] restricts the formats accepted
+//! for upload, rejecting the others with an error naming the allowed formats or
+//! converting them to an allowed one.
+//!
+//! Articles with dozens of full-size photos get heavy to load. With
+//! [`ImageProcessing::max_width`], JPEG and PNG body images wider than the limit are
+//! scaled down to it, keeping their aspect ratio. The cover is never scaled, and
+//! neither is an image titled `fullres` in the markdown: `![alt](img.png "fullres")`.
+//! The title is dropped when rendering, so it doesn't show up in the article.
+//!
+//! Decoding requires the `image-processing` feature (and the `heic` feature for HEIC);
+//! without it, enabling a step makes uploads of affected images fail with a
+//! configuration error.
+//!
+//! ```rust
+//! use wechat_pub_rs::image_processing::ImageProcessing;
+//!
+//! let processing = ImageProcessing::default();
+//! assert!(!processing.is_enabled());
+//!
+//! // Images without EXIF data pass through untouched
+//! let processing = ImageProcessing::default().strip_metadata(true);
+//! let png = b"\x89PNG\r\n\x1a\n".to_vec();
+//! assert_eq!(processing.apply(png.clone(), "image.png").unwrap(), png);
+//! ```
+//!
+//! ```rust
+//! use wechat_pub_rs::image_processing::{ImageFormat, ImageFormatPolicy};
+//!
+//! // Reject BMP images outright
+//! let policy = ImageFormatPolicy::default().forbid(ImageFormat::Bmp);
+//! assert!(policy.check(b"BM\x00\x00", "scan.bmp").is_err());
+//!
+//! // Or convert anything but JPEG and PNG to JPEG
+//! let policy = ImageFormatPolicy::allow([ImageFormat::Jpeg, ImageFormat::Png])
+//!     .convert_to(ImageFormat::Jpeg);
+//! assert_eq!(policy.check(b"BM\x00\x00", "scan.bmp").unwrap(), Some(ImageFormat::Jpeg));
+//! ```
+
+use crate::error::{ImageErrorKind, Result, WeChatError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// JPEG quality used when re-encoding processed photos.
+pub const JPEG_QUALITY: u8 = 90;
+
+/// Markdown image title that exempts an image from downscaling.
+pub const FULL_RESOLUTION_TITLE: &str = "fullres";
+
+/// Processing steps applied to images before upload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageProcessing {
+    /// Whether to strip EXIF metadata, baking the orientation into the pixels
+    pub strip_metadata: bool,
+    /// Whether to convert HEIC/HEIF images to JPEG (default: on with the `heic` feature)
+    pub convert_heic: bool,
+    /// Width JPEG and PNG body images are scaled down to if they are wider
+    pub max_width: Option<u32>,
+}
+
+// Only derivable without the `heic` feature
+#[allow(clippy::derivable_impls)]
+impl Default for ImageProcessing {
+    fn default() -> Self {
+        Self {
+            strip_metadata: false,
+            convert_heic: cfg!(feature = "heic"),
+            max_width: None,
+        }
+    }
+}
+
+impl ImageProcessing {
+    /// Sets whether to strip EXIF metadata and bake in the orientation.
+    pub fn strip_metadata(mut self, enable: bool) -> Self {
+        self.strip_metadata = enable;
+        self
+    }
+
+    /// Sets whether to convert HEIC/HEIF images to JPEG.
+    pub fn convert_heic(mut self, enable: bool) -> Self {
+        self.convert_heic = enable;
+        self
+    }
+
+    /// Sets the width JPEG and PNG body images are scaled down to if they are wider,
+    /// e.g. 1080 for images shown at full width on phones.
+    pub fn max_width(mut self, width: u32) -> Self {
+        self.max_width = Some(width);
+        self
+    }
+
+    /// Gets these settings without downscaling, for the cover and images titled
+    /// [`FULL_RESOLUTION_TITLE`].
+    pub fn full_resolution(self) -> Self {
+        Self {
+            max_width: None,
+            ..self
+        }
+    }
+
+    /// Whether any processing step is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.strip_metadata || self.convert_heic || self.max_width.is_some()
+    }
+
+    /// Whether [`apply`](Self::apply) would change (or reject) the image.
+    pub fn affects(&self, data: &[u8]) -> bool {
+        is_heif(data) || (self.strip_metadata && jpeg_has_exif(data)) || self.exceeds_width(data)
+    }
+
+    /// Whether the image is wider than [`max_width`](Self::max_width).
+    fn exceeds_width(&self, data: &[u8]) -> bool {
+        self.max_width
+            .zip(image_width(data))
+            .is_some_and(|(max_width, width)| width > max_width)
+    }
+
+    /// Describes the settings that affect the output, for keying processed images in
+    /// the [`AssetStore`](crate::assets::AssetStore).
+    #[cfg(feature = "client")]
+    pub(crate) fn asset_kind(&self) -> String {
+        format!(
+            "image:strip_metadata={}:convert_heic={}:max_width={:?}:quality={JPEG_QUALITY}",
+            self.strip_metadata, self.convert_heic, self.max_width
+        )
+    }
+
+    /// Applies the enabled steps to an image. `path` is only used in errors.
+    ///
+    /// HEIC/HEIF images are rejected unless they are converted, as WeChat doesn't accept
+    /// them. Downscaling comes last, so it applies to converted and rotated images.
+    pub fn apply(&self, data: Vec<u8>, path: &str) -> Result<Vec<u8>> {
+        let data = if is_heif(&data) {
+            if !self.convert_heic {
+                return Err(WeChatError::image_error(
+                    path.to_string(),
+                    ImageErrorKind::UnsupportedFormat,
+                    "HEIC/HEIF images are not supported by WeChat; enable HEIC \
+                             conversion (requires the `heic` feature) or convert it to JPEG"
+                        .to_string(),
+                ));
+            }
+            convert_heif_to_jpeg(&data, path)?
+        } else if self.strip_metadata && jpeg_has_exif(&data) {
+            strip_jpeg_metadata(data, path)?
+        } else {
+            data
+        };
+
+        match self.max_width {
+            Some(max_width) if self.exceeds_width(&data) => downscale(&data, max_width, path),
+            _ => Ok(data),
+        }
+    }
+}
+
+/// Image formats WeChat accepts for upload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Gif,
+    Bmp,
+    Webp,
+}
+
+impl ImageFormat {
+    /// All formats, in the order they are listed in errors.
+    pub const ALL: [ImageFormat; 5] = [
+        ImageFormat::Jpeg,
+        ImageFormat::Png,
+        ImageFormat::Gif,
+        ImageFormat::Bmp,
+        ImageFormat::Webp,
+    ];
+
+    /// Detects the format of an image from its leading bytes.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else if data.starts_with(b"\x89PNG") {
+            Some(ImageFormat::Png)
+        } else if data.starts_with(b"GIF8") {
+            Some(ImageFormat::Gif)
+        } else if data.starts_with(b"BM") {
+            Some(ImageFormat::Bmp)
+        } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            Some(ImageFormat::Webp)
+        } else {
+            None
+        }
+    }
+
+    /// Gets the file extension used for images in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::Webp => "webp",
+        }
+    }
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ImageFormat::Jpeg => "JPEG",
+            ImageFormat::Png => "PNG",
+            ImageFormat::Gif => "GIF",
+            ImageFormat::Bmp => "BMP",
+            ImageFormat::Webp => "WebP",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = WeChatError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "jpeg" | "jpg" => Ok(ImageFormat::Jpeg),
+            "png" => Ok(ImageFormat::Png),
+            "gif" => Ok(ImageFormat::Gif),
+            "bmp" => Ok(ImageFormat::Bmp),
+            "webp" => Ok(ImageFormat::Webp),
+            _ => Err(WeChatError::config_error(format!(
+                "Unknown image format '{s}', expected jpeg, png, gif, bmp or webp"
+            ))),
+        }
+    }
+}
+
+/// Image formats accepted for upload, and what happens to images in other formats.
+///
+/// The default accepts every image.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageFormatPolicy {
+    /// Formats accepted for upload; `None` accepts every image, even unrecognized ones
+    pub allowed: Option<Vec<ImageFormat>>,
+    /// Format images in other formats are converted to instead of being rejected
+    /// (requires the `image-processing` feature)
+    pub convert_to: Option<ImageFormat>,
+}
+
+impl ImageFormatPolicy {
+    /// Creates a policy accepting only the given formats.
+    pub fn allow(formats: impl IntoIterator<Item = ImageFormat>) -> Self {
+        Self {
+            allowed: Some(formats.into_iter().collect()),
+            convert_to: None,
+        }
+    }
+
+    /// Stops accepting a format.
+    pub fn forbid(mut self, format: ImageFormat) -> Self {
+        self.allowed
+            .get_or_insert_with(|| ImageFormat::ALL.to_vec())
+            .retain(|&allowed| allowed != format);
+        self
+    }
+
+    /// Sets the format images in other formats are converted to.
+    pub fn convert_to(mut self, format: ImageFormat) -> Self {
+        self.convert_to = Some(format);
+        self
+    }
+
+    /// Whether an image of the given format (`None` if unrecognized) is accepted as is.
+    pub fn accepts(&self, format: Option<ImageFormat>) -> bool {
+        match (&self.allowed, format) {
+            (None, _) => true,
+            (Some(allowed), Some(format)) => allowed.contains(&format),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Checks an image against the policy, returning the format to convert it to if it
+    /// isn't accepted as is. `path` is only used in errors.
+    ///
+    /// Images that are neither accepted nor convertible are rejected with an
+    /// [`ImageUpload`](WeChatError::ImageUpload) error listing the accepted formats.
+    pub fn check(&self, data: &[u8], path: &str) -> Result<Option<ImageFormat>> {
+        let format = ImageFormat::detect(data);
+        if self.accepts(format) {
+            return Ok(None);
+        }
+        // Unrecognized images can't be decoded for conversion either
+        if let (Some(_), Some(target)) = (format, self.convert_to) {
+            return Ok(Some(target));
+        }
+
+        let allowed = self
+            .allowed
+            .iter()
+            .flatten()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let format = format.map_or_else(|| "Unrecognized".to_string(), |f| f.to_string());
+        Err(WeChatError::image_error(
+            path.to_string(),
+            ImageErrorKind::UnsupportedFormat,
+            format!(
+                "{format} images are not accepted for upload (allowed: {allowed}); convert \
+                 the image, allow the format or set a conversion format in the image \
+                 format policy"
+            ),
+        ))
+    }
+}
+
+/// Converts an image to another format. `path` is only used in errors.
+#[cfg(feature = "image-processing")]
+pub fn convert_image(data: &[u8], format: ImageFormat, path: &str) -> Result<Vec<u8>> {
+    use image::DynamicImage;
+    use image::codecs::jpeg::JpegEncoder;
+    use std::io::Cursor;
+
+    let conversion_error = |e: image::ImageError| {
+        WeChatError::image_error(
+            path.to_string(),
+            ImageErrorKind::Processing,
+            format!("Failed to convert image to {format}: {e}"),
+        )
+    };
+
+    let image = image::load_from_memory(data).map_err(conversion_error)?;
+    let mut output = Vec::with_capacity(data.len());
+    match format {
+        ImageFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let image = DynamicImage::ImageRgb8(image.into_rgb8());
+            JpegEncoder::new_with_quality(&mut output, JPEG_QUALITY)
+                .encode_image(&image)
+                .map_err(conversion_error)?;
+        }
+        ImageFormat::Png => image
+            .write_to(&mut Cursor::new(&mut output), image::ImageFormat::Png)
+            .map_err(conversion_error)?,
+        _ => {
+            return Err(WeChatError::config_error(format!(
+                "Images can only be converted to JPEG or PNG, not {format}"
+            )));
+        }
+    }
+
+    Ok(output)
+}
+
+#[cfg(not(feature = "image-processing"))]
+pub fn convert_image(_data: &[u8], format: ImageFormat, path: &str) -> Result<Vec<u8>> {
+    Err(WeChatError::config_error(format!(
+        "Converting {path} to {format} requires the `image-processing` feature"
+    )))
+}
+
+/// Whether `data` is a HEIC/HEIF image, judging by the brands of its `ftyp` box.
+pub fn is_heif(data: &[u8]) -> bool {
+    if data.len() < 12 || &data[4..8] != b"ftyp" {
+        return false;
+    }
+    match &data[8..12] {
+        b"heic" | b"heix" | b"hevc" | b"hevx" | b"heim" | b"heis" => true,
+        // The generic image brands are AVIF's major brands too; AVIF files list their own
+        // brand among the compatible ones
+        b"mif1" | b"msf1" => {
+            !compatible_brands(data).any(|brand| brand == b"avif" || brand == b"avis")
+        }
+        _ => false,
+    }
+}
+
+/// Gets the compatible brands of the `ftyp` box at the start of `data`, after its major
+/// brand and minor version; `data` holds at least the box header.
+fn compatible_brands(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    // A size of 0 means the box extends to the end of the file
+    let end = if size == 0 {
+        data.len()
+    } else {
+        size.min(data.len())
+    };
+    data.get(16..end).unwrap_or_default().chunks_exact(4)
+}
+
+/// Reads the width of a JPEG or PNG image from its header, without decoding it.
+fn image_width(data: &[u8]) -> Option<u32> {
+    match ImageFormat::detect(data)? {
+        ImageFormat::Png => {
+            // The IHDR chunk comes first and starts with the width
+            let width = data.get(16..20)?;
+            Some(u32::from_be_bytes(width.try_into().ok()?))
+        }
+        ImageFormat::Jpeg => {
+            let mut pos = 2;
+            while pos + 9 <= data.len() && data[pos] == 0xFF {
+                let marker = data[pos + 1];
+                // Start of frame markers, except DHT, JPG and DAC
+                if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+                    return Some(u16::from_be_bytes([data[pos + 7], data[pos + 8]]) as u32);
+                }
+                let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+                pos += 2 + length;
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Scales a JPEG or PNG image down to `max_width`, keeping its format and aspect ratio.
+#[cfg(feature = "image-processing")]
+fn downscale(data: &[u8], max_width: u32, path: &str) -> Result<Vec<u8>> {
+    use image::codecs::jpeg::JpegEncoder;
+    use image::imageops::FilterType;
+    use image::{DynamicImage, GenericImageView};
+    use std::io::Cursor;
+
+    let processing_error = |e: image::ImageError| {
+        WeChatError::image_error(
+            path.to_string(),
+            ImageErrorKind::Processing,
+            format!("Failed to downscale image: {e}"),
+        )
+    };
+
+    let image = image::load_from_memory(data).map_err(processing_error)?;
+    let (width, height) = image.dimensions();
+    let new_height = ((height as u64 * max_width as u64) / width as u64).max(1) as u32;
+    let image = image.resize_exact(max_width, new_height, FilterType::Lanczos3);
+
+    let mut output = Vec::new();
+    if ImageFormat::detect(data) == Some(ImageFormat::Png) {
+        image
+            .write_to(&mut Cursor::new(&mut output), image::ImageFormat::Png)
+            .map_err(processing_error)?;
+    } else {
+        // JPEG has no alpha channel
+        let image = DynamicImage::ImageRgb8(image.into_rgb8());
+        JpegEncoder::new_with_quality(&mut output, JPEG_QUALITY)
+            .encode_image(&image)
+            .map_err(processing_error)?;
+    }
+
+    Ok(output)
+}
+
+#[cfg(not(feature = "image-processing"))]
+fn downscale(_data: &[u8], _max_width: u32, path: &str) -> Result<Vec<u8>> {
+    Err(WeChatError::config_error(format!(
+        "Downscaling {path} requires the `image-processing` feature"
+    )))
+}
+
+/// Whether `data` is a JPEG image with an EXIF (APP1) segment.
+fn jpeg_has_exif(data: &[u8]) -> bool {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return false;
+    }
+
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos] == 0xFF {
+        let marker = data[pos + 1];
+        // Start of scan: no metadata segments follow
+        if marker == 0xDA {
+            break;
+        }
+        if marker == 0xE1 && data[pos + 4..].starts_with(b"Exif") {
+            return true;
+        }
+
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        pos += 2 + length;
+    }
+
+    false
+}
+
+#[cfg(feature = "image-processing")]
+fn strip_jpeg_metadata(data: Vec<u8>, path: &str) -> Result<Vec<u8>> {
+    use image::codecs::jpeg::JpegEncoder;
+    use image::{DynamicImage, ImageDecoder, ImageReader};
+    use std::io::Cursor;
+
+    let processing_error = |e: image::ImageError| {
+        WeChatError::image_error(
+            path.to_string(),
+            ImageErrorKind::Processing,
+            format!("Failed to process image: {e}"),
+        )
+    };
+
+    let mut decoder = ImageReader::new(Cursor::new(&data))
+        .with_guessed_format()
+        .map_err(|e| WeChatError::file_error(path.to_string(), e.to_string()))?
+        .into_decoder()
+        .map_err(processing_error)?;
+    let orientation = decoder.orientation().map_err(processing_error)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(processing_error)?;
+    image.apply_orientation(orientation);
+
+    // JPEG has no alpha channel
+    let image = DynamicImage::ImageRgb8(image.into_rgb8());
+    let mut output = Vec::with_capacity(data.len());
+    JpegEncoder::new_with_quality(&mut output, JPEG_QUALITY)
+        .encode_image(&image)
+        .map_err(processing_error)?;
+
+    Ok(output)
+}
+
+#[cfg(not(feature = "image-processing"))]
+fn strip_jpeg_metadata(_data: Vec<u8>, path: &str) -> Result<Vec<u8>> {
+    Err(WeChatError::config_error(format!(
+        "Stripping metadata from {path} requires the `image-processing` feature"
+    )))
+}
+
+/// Decodes the primary image of a HEIC/HEIF file and encodes it as JPEG. libheif applies
+/// the image's rotation and mirroring while decoding.
+#[cfg(feature = "heic")]
+fn convert_heif_to_jpeg(data: &[u8], path: &str) -> Result<Vec<u8>> {
+    use image::codecs::jpeg::JpegEncoder;
+    use image::{ExtendedColorType, ImageEncoder};
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let conversion_error =
+        |reason: String| WeChatError::image_error(path, ImageErrorKind::Processing, reason);
+    let heif_error =
+        |e: libheif_rs::HeifError| conversion_error(format!("Failed to decode HEIC image: {e}"));
+
+    let context = HeifContext::read_from_bytes(data).map_err(heif_error)?;
+    let handle = context.primary_image_handle().map_err(heif_error)?;
+    let image = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)
+        .map_err(heif_error)?;
+    let planes = image.planes();
+    let plane = planes
+        .interleaved
+        .ok_or_else(|| conversion_error("Decoded HEIC image has no RGB plane".to_string()))?;
+
+    // Rows may be padded; copy them into a tightly packed buffer
+    let row_len = plane.width as usize * 3;
+    let mut pixels = Vec::with_capacity(row_len * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+
+    let mut output = Vec::new();
+    JpegEncoder::new_with_quality(&mut output, JPEG_QUALITY)
+        .write_image(&pixels, plane.width, plane.height, ExtendedColorType::Rgb8)
+        .map_err(|e| conversion_error(format!("Failed to encode JPEG: {e}")))?;
+
+    Ok(output)
+}
+
+#[cfg(not(feature = "heic"))]
+fn convert_heif_to_jpeg(_data: &[u8], path: &str) -> Result<Vec<u8>> {
+    Err(WeChatError::config_error(format!(
+        "Converting {path} from HEIC requires the `heic` feature"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an EXIF APP1 segment with only an orientation tag.
+    fn exif_segment(orientation: u16) -> Vec<u8> {
+        let mut tiff = b"MM\x00\x2a\x00\x00\x00\x08".to_vec();
+        tiff.extend_from_slice(&1u16.to_be_bytes()); // one IFD entry
+        tiff.extend_from_slice(&[0x01, 0x12, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01]);
+        tiff.extend_from_slice(&orientation.to_be_bytes());
+        tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+
+        let mut segment = vec![0xFF, 0xE1];
+        segment.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        segment.extend_from_slice(b"Exif\0\0");
+        segment.extend_from_slice(&tiff);
+        segment
+    }
+
+    #[test]
+    fn test_exif_detection() {
+        let mut jpeg = vec![0xFF, 0xD8];
+        jpeg.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00]); // APP0
+        assert!(!jpeg_has_exif(&jpeg));
+
+        jpeg.extend_from_slice(&exif_segment(6));
+        assert!(jpeg_has_exif(&jpeg));
+        assert!(!jpeg_has_exif(b"\x89PNG\r\n\x1a\n"));
+
+        // Disabled processing never touches the data
+        let data = ImageProcessing::default()
+            .apply(jpeg.clone(), "a.jpg")
+            .unwrap();
+        assert_eq!(data, jpeg);
+    }
+
+    #[test]
+    fn test_heif_detection() {
+        let heic = b"\x00\x00\x00\x18ftypheic\x00\x00\x00\x00mif1heic";
+        assert!(is_heif(heic));
+        assert!(!is_heif(b"\x00\x00\x00\x1cftypavif\x00\x00\x00\x00"));
+        assert!(!is_heif(b"\x00\x00\x00\x10ftypavis\x00\x00\x00\x00"));
+        // Generic brands are HEIF unless AVIF is among the compatible brands
+        assert!(is_heif(b"\x00\x00\x00\x18ftypmif1\x00\x00\x00\x00mif1heic"));
+        assert!(!is_heif(
+            b"\x00\x00\x00\x1cftypmif1\x00\x00\x00\x00mif1avifmiaf"
+        ));
+        assert!(!is_heif(
+            b"\x00\x00\x00\x18ftypmsf1\x00\x00\x00\x00msf1avis"
+        ));
+        // Brands past the end of the box don't count
+        assert!(is_heif(b"\x00\x00\x00\x14ftypmif1\x00\x00\x00\x00mif1avif"));
+        assert!(!is_heif(b"\xFF\xD8\xFF\xE0"));
+
+        // Without conversion HEIC is rejected up front rather than uploaded
+        let result = ImageProcessing::default()
+            .convert_heic(false)
+            .apply(heic.to_vec(), "IMG_0001.HEIC");
+        match result {
+            Err(WeChatError::ImageUpload {
+                path, reason, kind, ..
+            }) => {
+                assert_eq!(path, "IMG_0001.HEIC");
+                assert!(reason.contains("heic"));
+                assert_eq!(kind, ImageErrorKind::UnsupportedFormat);
+            }
+            other => panic!("Expected ImageUpload error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_format_policy() {
+        let bmp = b"BM\x00\x00\x00\x00";
+        let png = b"\x89PNG\r\n\x1a\n";
+        assert_eq!(ImageFormat::detect(bmp), Some(ImageFormat::Bmp));
+        assert_eq!(
+            ImageFormat::detect(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some(ImageFormat::Webp)
+        );
+        assert_eq!(ImageFormat::detect(b"text"), None);
+        assert_eq!("JPG".parse::<ImageFormat>().unwrap(), ImageFormat::Jpeg);
+        assert!("tiff".parse::<ImageFormat>().is_err());
+
+        // The default accepts everything
+        let policy = ImageFormatPolicy::default();
+        assert_eq!(policy.check(bmp, "a.bmp").unwrap(), None);
+        assert_eq!(policy.check(b"text", "a.txt").unwrap(), None);
+
+        let policy = ImageFormatPolicy::default().forbid(ImageFormat::Bmp);
+        assert_eq!(policy.check(png, "a.png").unwrap(), None);
+        match policy.check(bmp, "scan.bmp") {
+            Err(WeChatError::ImageUpload {
+                path, reason, kind, ..
+            }) => {
+                assert_eq!(path, "scan.bmp");
+                assert!(reason.starts_with("BMP images are not accepted"));
+                assert!(reason.contains("allowed: JPEG, PNG, GIF, WebP"));
+                assert_eq!(kind, ImageErrorKind::UnsupportedFormat);
+            }
+            other => panic!("Expected ImageUpload error, got {other:?}"),
+        }
+
+        let policy = policy.convert_to(ImageFormat::Jpeg);
+        assert_eq!(
+            policy.check(bmp, "scan.bmp").unwrap(),
+            Some(ImageFormat::Jpeg)
+        );
+        // Unrecognized images can't be converted
+        assert!(policy.check(b"text", "a.txt").is_err());
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[test]
+    fn test_convert_image() {
+        use image::{GenericImageView, RgbImage};
+
+        let mut bmp = Vec::new();
+        RgbImage::new(4, 2)
+            .write_to(&mut std::io::Cursor::new(&mut bmp), image::ImageFormat::Bmp)
+            .unwrap();
+        assert_eq!(ImageFormat::detect(&bmp), Some(ImageFormat::Bmp));
+
+        let jpeg = convert_image(&bmp, ImageFormat::Jpeg, "scan.bmp").unwrap();
+        assert_eq!(ImageFormat::detect(&jpeg), Some(ImageFormat::Jpeg));
+        assert_eq!(image::load_from_memory(&jpeg).unwrap().dimensions(), (4, 2));
+    }
+
+    #[test]
+    fn test_image_width() {
+        let mut png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        png.extend_from_slice(&2000u32.to_be_bytes());
+        png.extend_from_slice(&500u32.to_be_bytes());
+        assert_eq!(image_width(&png), Some(2000));
+
+        // APP0 segment, then a baseline frame header of 300x1500 (height x width)
+        let jpeg = [
+            0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x01,
+            0x2C, 0x05, 0xDC,
+        ];
+        assert_eq!(image_width(&jpeg), Some(1500));
+        assert_eq!(image_width(b"GIF89a"), None);
+
+        let processing = ImageProcessing::default().max_width(1080);
+        assert!(processing.affects(&png));
+        assert!(!processing.full_resolution().affects(&png));
+        assert!(!ImageProcessing::default().max_width(2000).affects(&png));
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[test]
+    fn test_downscale() {
+        use image::{GenericImageView, RgbImage};
+
+        let mut png = Vec::new();
+        RgbImage::new(40, 20)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        let processed = ImageProcessing::default()
+            .max_width(10)
+            .apply(png, "wide.png")
+            .unwrap();
+        assert_eq!(ImageFormat::detect(&processed), Some(ImageFormat::Png));
+        let image = image::load_from_memory(&processed).unwrap();
+        assert_eq!(image.dimensions(), (10, 5));
+    }
+
+    #[cfg(feature = "image-processing")]
+    #[test]
+    fn test_orientation_is_baked_in() {
+        use image::{GenericImageView, RgbImage};
+
+        let mut jpeg = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new(&mut jpeg)
+            .encode_image(&RgbImage::new(40, 20))
+            .unwrap();
+        // Insert the EXIF segment right after the SOI marker
+        let exif = exif_segment(6);
+        jpeg.splice(2..2, exif);
+        assert!(jpeg_has_exif(&jpeg));
+
+        let processed = ImageProcessing::default()
+            .strip_metadata(true)
+            .apply(jpeg, "photo.jpg")
+            .unwrap();
+
+        assert!(!jpeg_has_exif(&processed));
+        let image = image::load_from_memory(&processed).unwrap();
+        assert_eq!(image.dimensions(), (20, 40));
+    }
+}
@@ -28,6 +28,19 @@
 //! - [`theme`] - Theme system for rendering HTML from Markdown
 //! - [`error`] - Comprehensive error types and handling
 //!
+//! ## Render-only Builds
+//!
+//! The API client is behind the default `client` feature and the renderer behind the
+//! default `render` feature, which needs no optional dependencies and is always built.
+//! With `default-features = false, features = ["render"]` the crate builds the renderer
+//! alone ([`markdown`], [`theme`], [`css_vars`], [`mermaid`] and their helpers) without
+//! reqwest or any other networking dependency, e.g. for static-site generators that want
+//! WeChat-compatible HTML.
+//!
+//! The `server` feature adds the `server` module, the receiving half of the
+//! integration: verifying callback URLs, parsing the messages and events WeChat pushes
+//! to the account's server and serializing passive replies.
+//!
 //! ## Quick Start
 //!
 //! ```rust,no_run
@@ -103,25 +116,88 @@
 //! # }
 //! ```
 
+#[cfg(feature = "client")]
+pub mod accounts;
+#[cfg(feature = "client")]
+pub mod analytics;
+pub mod assets;
+#[cfg(feature = "client")]
 pub mod auth;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "client")]
+pub mod broadcast;
+pub mod budget;
+#[cfg(feature = "client")]
 pub mod client;
+#[cfg(feature = "client")]
+pub mod comments;
 pub mod config;
 pub mod css_vars;
+pub mod diff;
+pub mod endpoints;
 pub mod error;
+pub mod health;
+pub mod highlight;
+#[cfg(feature = "client")]
 pub mod http;
+pub mod image_processing;
+pub mod locale;
 pub mod markdown;
+pub mod media_map;
 pub mod mermaid;
+#[cfg(feature = "client")]
+pub mod oauth;
+#[cfg(feature = "client")]
+pub mod overview;
+#[cfg(feature = "client")]
+pub mod pipeline;
+#[cfg(feature = "client")]
+pub mod prepare;
+pub mod profile;
+pub mod publish;
+#[cfg(feature = "client")]
+pub mod queue;
+#[cfg(feature = "recording")]
+pub mod recording;
+pub mod sanitize;
+pub mod schema;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod slug;
+pub mod state;
+#[cfg(feature = "client")]
+pub mod subscribe;
+#[cfg(feature = "client")]
+pub mod sync;
+pub mod temp;
+#[cfg(all(test, feature = "client"))]
+mod test_server;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod theme;
+#[cfg(feature = "client")]
 pub mod traits;
+#[cfg(feature = "client")]
 pub mod upload;
+#[cfg(feature = "client")]
+pub mod upload_queue;
 pub mod utils;
 
 // Re-export main types for convenience
-pub use client::{UploadOptions, WeChatClient};
+#[cfg(feature = "client")]
+pub use client::{
+    DirUploadReport, DrainReport, HtmlTransform, UploadOptions, UploadReport, WeChatClient,
+    WeChatClientBuilder,
+};
 pub use config::Config;
 pub use css_vars::CssVariableProcessor;
-pub use error::{ErrorSeverity, Result, WeChatError};
-pub use theme::BuiltinTheme;
+pub use error::{ErrorSeverity, ImageErrorKind, Result, WeChatError};
+#[cfg(feature = "client")]
+pub use pipeline::ArticlePipeline;
+pub use theme::{BuiltinTheme, ThemeId, ThemeInfo};
+#[cfg(feature = "client")]
+pub use upload::{Article, ExistingDraftPolicy};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,213 @@
+//! Locale and time zone of rendered articles.
+//!
+//! WeChat readers are mostly in China, while the machines uploading articles, CI
+//! servers in particular, often run in UTC. Dates are therefore resolved in a configured
+//! [`TimeZone`] rather than the machine's: a frontmatter `date: today` is the current
+//! date there, and timestamps like `2024-04-30T20:00:00Z` fall on the day they are there
+//! (May 1 in China). The [`Locale`] picks how dates are written unless
+//! [`UploadOptions::date_format`](crate::UploadOptions::date_format) says otherwise.
+//!
+//! Both are set client-wide with [`LocaleConfig`](crate::config::LocaleConfig), or the
+//! `WECHAT_LOCALE` and `WECHAT_TIMEZONE` environment variables:
+//!
+//! ```rust
+//! use wechat_pub_rs::config::{Config, LocaleConfig};
+//! use wechat_pub_rs::locale::{Locale, TimeZone};
+//!
+//! let config = Config::builder()
+//!     .locale(LocaleConfig::builder()
+//!         .locale(Locale::EnUs)
+//!         .timezone("-05:00".parse::<TimeZone>().unwrap())
+//!         .build())
+//!     .build();
+//! ```
+
+use crate::error::{Result, WeChatError};
+use crate::markdown::DateFormat;
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Language articles are written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Locale {
+    /// Simplified Chinese (default)
+    #[default]
+    #[serde(rename = "zh-CN")]
+    ZhCn,
+    /// American English
+    #[serde(rename = "en-US")]
+    EnUs,
+}
+
+impl Locale {
+    /// Gets the format dates are written in for this locale.
+    pub fn date_format(self) -> DateFormat {
+        match self {
+            Locale::ZhCn => DateFormat::ChineseLong,
+            Locale::EnUs => DateFormat::EnglishLong,
+        }
+    }
+}
+
+impl std::str::FromStr for Locale {
+    type Err = WeChatError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "zh" | "zh-cn" => Ok(Locale::ZhCn),
+            "en" | "en-us" => Ok(Locale::EnUs),
+            _ => Err(WeChatError::config_error(format!(
+                "Unknown locale '{s}', expected 'zh-CN' or 'en-US'"
+            ))),
+        }
+    }
+}
+
+/// Time zone dates are resolved in, as a fixed offset from UTC.
+///
+/// Parsed from `UTC`, `Z` or an offset like `+08:00`, `-0530` or `+8`; written as
+/// `+08:00`. Defaults to China Standard Time (`+08:00`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeZone(FixedOffset);
+
+impl TimeZone {
+    /// China Standard Time, UTC+8.
+    pub const CHINA: TimeZone = TimeZone(FixedOffset::east_opt(8 * 3600).unwrap());
+
+    /// Coordinated Universal Time.
+    pub const UTC: TimeZone = TimeZone(FixedOffset::east_opt(0).unwrap());
+
+    /// Creates a time zone from its offset.
+    pub fn new(offset: FixedOffset) -> Self {
+        Self(offset)
+    }
+
+    /// Gets the offset from UTC.
+    pub fn offset(self) -> FixedOffset {
+        self.0
+    }
+
+    /// Gets the current date in this time zone.
+    pub fn today(self) -> NaiveDate {
+        self.date_of(Utc::now().fixed_offset())
+    }
+
+    /// Gets the date a point in time falls on in this time zone.
+    pub fn date_of(self, datetime: DateTime<FixedOffset>) -> NaiveDate {
+        datetime.with_timezone(&self.0).date_naive()
+    }
+}
+
+impl Default for TimeZone {
+    fn default() -> Self {
+        TimeZone::CHINA
+    }
+}
+
+impl std::fmt::Display for TimeZone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::str::FromStr for TimeZone {
+    type Err = WeChatError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            WeChatError::config_error(format!(
+                "Invalid time zone '{s}', expected 'UTC' or an offset like '+08:00'"
+            ))
+        };
+
+        let trimmed = s.trim();
+        if trimmed.eq_ignore_ascii_case("utc") || trimmed.eq_ignore_ascii_case("z") {
+            return Ok(TimeZone::UTC);
+        }
+        let (sign, offset) = match trimmed.as_bytes().first() {
+            Some(b'+') => (1, &trimmed[1..]),
+            Some(b'-') => (-1, &trimmed[1..]),
+            _ => return Err(invalid()),
+        };
+        let (hours, minutes) = match offset.split_once(':') {
+            Some((hours, minutes)) => (hours, minutes),
+            None if offset.len() == 4 => offset.split_at_checked(2).ok_or_else(invalid)?,
+            None => (offset, "0"),
+        };
+        // One or two digits each, so the offset can't overflow
+        let number = |part: &str| -> Result<i32> {
+            if part.is_empty() || part.len() > 2 || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            part.parse().map_err(|_| invalid())
+        };
+        let (hours, minutes) = (number(hours)?, number(minutes)?);
+        if hours > 23 || minutes > 59 {
+            return Err(invalid());
+        }
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+            .map(TimeZone)
+            .ok_or_else(invalid)
+    }
+}
+
+impl Serialize for TimeZone {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeZone {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_zone() {
+        assert_eq!("+08:00".parse::<TimeZone>().unwrap(), TimeZone::CHINA);
+        assert_eq!("+8".parse::<TimeZone>().unwrap(), TimeZone::CHINA);
+        assert_eq!("utc".parse::<TimeZone>().unwrap(), TimeZone::UTC);
+        let india = "+0530".parse::<TimeZone>().unwrap();
+        assert_eq!(india.to_string(), "+05:30");
+        assert_eq!(
+            "-05:00"
+                .parse::<TimeZone>()
+                .unwrap()
+                .offset()
+                .local_minus_utc(),
+            -5 * 3600
+        );
+        assert!("Asia/Shanghai".parse::<TimeZone>().is_err());
+        assert!("+08:75".parse::<TimeZone>().is_err());
+        assert!("+25:00".parse::<TimeZone>().is_err());
+        // Malformed offsets are rejected instead of panicking or overflowing
+        assert!("+1é1".parse::<TimeZone>().is_err());
+        assert!("+1000000".parse::<TimeZone>().is_err());
+        assert!("+99999999999:00".parse::<TimeZone>().is_err());
+        assert!("+-5".parse::<TimeZone>().is_err());
+
+        let evening = DateTime::parse_from_rfc3339("2024-04-30T20:00:00Z").unwrap();
+        assert_eq!(
+            TimeZone::CHINA.date_of(evening),
+            NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()
+        );
+        assert_eq!(
+            TimeZone::UTC.date_of(evening),
+            NaiveDate::from_ymd_opt(2024, 4, 30).unwrap()
+        );
+
+        let json = serde_json::to_string(&india).unwrap();
+        assert_eq!(json, "\"+05:30\"");
+        assert_eq!(serde_json::from_str::<TimeZone>(&json).unwrap(), india);
+
+        assert_eq!("en_US".parse::<Locale>().unwrap(), Locale::EnUs);
+        assert_eq!(Locale::default().date_format(), DateFormat::ChineseLong);
+        assert!("fr-FR".parse::<Locale>().is_err());
+    }
+}
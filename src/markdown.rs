@@ -16,15 +16,22 @@
 //! ```yaml
 //! ---
 //! title: "Article Title"          # Article title (required for good UX)
-//! author: "Author Name"           # Author name (optional)
-//! description: "Article summary"  # Article description/digest (optional, used as WeChat article summary)
+//! author: "Author Name"           # Author name (optional; or `authors: [A, B]`, or name/link/avatar)
+//! description: "Article summary"  # Article description (optional, used as WeChat article summary)
+//! digest: "Share card summary"    # WeChat digest (optional, overrides `description` as summary)
 //! cover: "images/cover.jpg"       # Cover image path (required)
 //! theme: "lapis"                  # Theme name (optional, defaults to "default")
 //! code: "github"                  # Code highlighting theme (optional)
+//! date: 2024-05-01                # Publication date (optional, `today` for the upload date)
 //! custom_field: "custom_value"    # Any additional metadata
 //! ---
 //! ```
 //!
+//! The digest WeChat shows in share cards and article lists comes from `digest:`, then
+//! `description:`, then the first paragraph (see [`MarkdownContent::digest`]). The
+//! `<meta name="description">` of the rendered article prefers `description:` instead,
+//! so a page description and a shorter share card summary can be set side by side.
+//!
 //! ## Image Handling
 //!
 //! The module automatically detects image references in markdown:
@@ -60,12 +67,158 @@
 //! ```
 
 use crate::error::{Result, WeChatError};
-use crate::utils;
+use crate::locale::TimeZone;
+use crate::theme::ThemeId;
+use crate::utils::{self, PathPolicy};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
 use comrak::{Arena, ComrakOptions, nodes::NodeValue};
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt::Write;
 use std::path::{Path, PathBuf};
 
+/// Frontmatter key holding the media ID of the draft a file was uploaded to.
+pub const FRONTMATTER_MEDIA_ID: &str = "wechat_media_id";
+
+/// Frontmatter key holding the URL of the uploaded article.
+pub const FRONTMATTER_URL: &str = "wechat_url";
+
+/// Maximum length of the digest generated from the first paragraph.
+pub const AUTO_DIGEST_LENGTH: usize = 120;
+
+/// An article author from front matter.
+///
+/// Written as `author: Name`, as a list (`authors: [A, B]`, or one `- name` per line),
+/// or as an object with `name`, `link` and `avatar` entries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Author {
+    /// Display name
+    pub name: String,
+    /// Link to the author's page
+    pub link: Option<String>,
+    /// URL of the author's avatar
+    pub avatar: Option<String>,
+}
+
+impl Author {
+    /// Creates an author with only a name.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            link: None,
+            avatar: None,
+        }
+    }
+}
+
+/// How multiple authors are joined into the single author string WeChat accepts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorFormat {
+    /// Separator placed between names
+    pub separator: String,
+    /// Maximum number of names to list before abbreviating
+    pub max_names: Option<usize>,
+    /// Suffix appended when names were left out
+    pub overflow_suffix: String,
+}
+
+impl Default for AuthorFormat {
+    fn default() -> Self {
+        Self {
+            separator: ", ".to_string(),
+            max_names: None,
+            overflow_suffix: " et al.".to_string(),
+        }
+    }
+}
+
+impl AuthorFormat {
+    /// Sets the separator placed between names.
+    pub fn separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    /// Lists at most `max_names` names, followed by the overflow suffix.
+    pub fn max_names(mut self, max_names: usize) -> Self {
+        self.max_names = Some(max_names);
+        self
+    }
+
+    /// Sets the suffix appended when names were left out.
+    pub fn overflow_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.overflow_suffix = suffix.into();
+        self
+    }
+
+    /// Joins the authors' names, returning `None` if there are no authors.
+    pub fn format(&self, authors: &[Author]) -> Option<String> {
+        if authors.is_empty() {
+            return None;
+        }
+
+        let shown = self.max_names.unwrap_or(authors.len()).min(authors.len());
+        let names: Vec<&str> = authors[..shown].iter().map(|a| a.name.as_str()).collect();
+        let mut formatted = names.join(&self.separator);
+        if shown < authors.len() {
+            formatted.push_str(&self.overflow_suffix);
+        }
+        Some(formatted)
+    }
+}
+
+/// How the frontmatter `date` is written in rendered articles.
+///
+/// Parsed from a locale (`zh-CN`, `en-US`), `iso`, or a chrono `strftime` pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum DateFormat {
+    /// Chinese long form, e.g. `2024年5月1日`
+    #[default]
+    ChineseLong,
+    /// English long form, e.g. `May 1, 2024`
+    EnglishLong,
+    /// ISO 8601, e.g. `2024-05-01`
+    Iso,
+    /// A chrono `strftime` pattern, e.g. `%d/%m/%Y`
+    Custom(String),
+}
+
+impl DateFormat {
+    /// Writes a date in this format.
+    ///
+    /// A custom pattern chrono can't format falls back to ISO 8601.
+    pub fn format(&self, date: NaiveDate) -> String {
+        let pattern = match self {
+            DateFormat::ChineseLong => "%Y年%-m月%-d日",
+            DateFormat::EnglishLong => "%B %-d, %Y",
+            DateFormat::Iso => "%Y-%m-%d",
+            DateFormat::Custom(pattern) => pattern,
+        };
+
+        let mut formatted = String::new();
+        if write!(formatted, "{}", date.format(pattern)).is_err() {
+            return date.format("%Y-%m-%d").to_string();
+        }
+        formatted
+    }
+}
+
+impl std::str::FromStr for DateFormat {
+    type Err = WeChatError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "zh" | "zh-cn" => Ok(DateFormat::ChineseLong),
+            "en" | "en-us" => Ok(DateFormat::EnglishLong),
+            "iso" => Ok(DateFormat::Iso),
+            _ if s.contains('%') => Ok(DateFormat::Custom(s.to_string())),
+            _ => Err(WeChatError::config_error(format!(
+                "Unknown date format '{s}', expected 'zh-CN', 'en-US', 'iso' or a strftime pattern"
+            ))),
+        }
+    }
+}
+
 /// Represents an image reference found in markdown content.
 #[derive(Debug, Clone, PartialEq)]
 pub struct ImageRef {
@@ -77,6 +230,10 @@ pub struct ImageRef {
     pub position: (usize, usize),
     /// Whether this is a local file or remote URL
     pub is_local: bool,
+    /// Title of the image (`![alt](url "title")`), if any
+    pub title: Option<String>,
+    /// Line of the image in the markdown file (1-based), if known
+    pub line: Option<usize>,
 }
 
 impl ImageRef {
@@ -88,18 +245,54 @@ impl ImageRef {
             original_url: url,
             position,
             is_local,
+            title: None,
+            line: None,
         }
     }
 
+    /// Sets the title of the image.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Sets the line of the image in the markdown file.
+    pub fn with_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    /// Whether the image is exempt from downscaling, by its title
+    /// [`FULL_RESOLUTION_TITLE`](crate::image_processing::FULL_RESOLUTION_TITLE).
+    pub fn is_full_resolution(&self) -> bool {
+        self.title.as_deref() == Some(crate::image_processing::FULL_RESOLUTION_TITLE)
+    }
+
     /// Resolves the image path relative to a base directory with security validation.
+    ///
+    /// Local paths are percent-decoded and stripped of any `?query` or `#fragment`, so
+    /// `./my%20photo.png?v=2` refers to `my photo.png`. A file whose name literally
+    /// matches the original reference is still found. [`original_url`](Self::original_url)
+    /// is left untouched for URL replacement.
     pub fn resolve_path(&self, base_path: &Path) -> Result<PathBuf> {
+        self.resolve_path_with(base_path, &PathPolicy::default())
+    }
+
+    /// Resolves the image path like [`resolve_path`](Self::resolve_path), validating
+    /// local paths against a [`PathPolicy`].
+    pub fn resolve_path_with(&self, base_path: &Path, policy: &PathPolicy) -> Result<PathBuf> {
         if self.is_local {
-            utils::resolve_path(base_path, &self.original_url).map_err(|e| {
-                WeChatError::config_error(format!(
-                    "Invalid image path '{}': {}",
-                    self.original_url, e
-                ))
-            })
+            let decoded = utils::decode_local_reference(&self.original_url);
+            let path = policy.resolve(base_path, &decoded)?;
+            if decoded != self.original_url && !path.exists() {
+                // Fall back to a file literally named like the reference
+                if let Ok(literal) = policy.resolve(base_path, &self.original_url)
+                    && literal.exists()
+                {
+                    return Ok(literal);
+                }
+            }
+            Ok(path)
         } else {
             // For remote URLs, just return as-is but validate it's actually a URL
             if self.original_url.starts_with("http://") || self.original_url.starts_with("https://")
@@ -120,16 +313,22 @@ impl ImageRef {
 pub struct MarkdownContent {
     /// Article title (extracted from # heading or front matter)
     pub title: Option<String>,
-    /// Author name (from front matter)
+    /// Author names (from front matter), joined with the default [`AuthorFormat`]
     pub author: Option<String>,
-    /// Article description/digest (from front matter)
+    /// Authors (from front matter)
+    pub authors: Vec<Author>,
+    /// Article description (from front matter)
     pub description: Option<String>,
+    /// WeChat digest, overriding the description as article summary (from front matter)
+    pub digest: Option<String>,
     /// Cover image path (from front matter)
     pub cover: Option<String>,
-    /// Theme name (from front matter)
-    pub theme: Option<String>,
+    /// Theme (from front matter)
+    pub theme: Option<ThemeId>,
     /// Code syntax highlighting theme (from front matter)
     pub code: Option<String>,
+    /// Publication date (from front matter)
+    pub date: Option<NaiveDate>,
     /// Main content (markdown text)
     pub content: String,
     /// List of image references
@@ -140,6 +339,15 @@ pub struct MarkdownContent {
     pub original_text: String,
 }
 
+/// A part of the content starting at a heading, see [`MarkdownContent::sections`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// Heading text, `None` for the text before the first heading
+    pub title: Option<String>,
+    /// Markdown of the section, including its heading
+    pub markdown: String,
+}
+
 /// Helper struct for extracting summaries from markdown AST.
 struct SummaryExtractor {
     summary: String,
@@ -246,6 +454,24 @@ impl PlainTextExtractor {
 }
 
 impl MarkdownContent {
+    /// Gets the article digest: the `digest:` frontmatter entry, else the
+    /// `description:` entry, else a summary of the first paragraph of up to
+    /// [`AUTO_DIGEST_LENGTH`] characters.
+    pub fn digest(&self) -> String {
+        self.digest
+            .clone()
+            .or_else(|| self.description.clone())
+            .unwrap_or_else(|| self.get_summary(AUTO_DIGEST_LENGTH))
+    }
+
+    /// Gets the draft media ID written back into the frontmatter by a previous upload.
+    pub fn wechat_media_id(&self) -> Option<&str> {
+        self.metadata
+            .get(FRONTMATTER_MEDIA_ID)
+            .map(String::as_str)
+            .filter(|media_id| !media_id.is_empty())
+    }
+
     /// Replaces image URLs in the content with new URLs.
     pub fn replace_image_urls(&mut self, url_mapping: &HashMap<String, String>) -> Result<()> {
         // Use Cow to avoid unnecessary allocations when no changes are needed
@@ -257,20 +483,14 @@ impl MarkdownContent {
 
         for image in &sorted_images {
             if let Some(new_url) = url_mapping.get(&image.original_url) {
-                // Find and replace the image URL in markdown
-                let old_markdown = format!("![{}]({})", image.alt_text, image.original_url);
-                let new_markdown = format!("![{}]({})", image.alt_text, new_url);
+                // Match up to the end of the URL, keeping any title after it
+                let old_markdown = format!("![{}]({}", image.alt_text, image.original_url);
+                let new_markdown = format!("![{}]({}", image.alt_text, new_url);
 
                 // Only convert to owned string when we actually need to modify
-                match content {
-                    Cow::Borrowed(s) => {
-                        if s.contains(&old_markdown) {
-                            content = Cow::Owned(s.replace(&old_markdown, &new_markdown));
-                        }
-                    }
-                    Cow::Owned(ref mut s) => {
-                        *s = s.replace(&old_markdown, &new_markdown);
-                    }
+                if let Some(replaced) = replace_link_target(&content, &old_markdown, &new_markdown)
+                {
+                    content = Cow::Owned(replaced);
                 }
             }
         }
@@ -283,6 +503,36 @@ impl MarkdownContent {
         Ok(())
     }
 
+    /// Builds a URL mapping that points local images at their files on disk.
+    ///
+    /// Used for previews, where images are shown from the local filesystem instead of
+    /// being uploaded. Each local image is mapped to a path relative to `output_dir`
+    /// (the directory the rendered HTML is viewed from), falling back to the absolute
+    /// path. Remote images and paths that cannot be resolved or are rejected by `policy`
+    /// are left out.
+    pub fn local_preview_mapping(
+        &self,
+        base_path: &Path,
+        output_dir: &Path,
+        policy: &PathPolicy,
+    ) -> HashMap<String, String> {
+        let output_dir = output_dir
+            .canonicalize()
+            .unwrap_or_else(|_| output_dir.to_path_buf());
+
+        self.images
+            .iter()
+            .filter(|image| image.is_local)
+            .filter_map(|image| {
+                let path = image.resolve_path_with(base_path, policy).ok()?;
+                let path = path.canonicalize().unwrap_or(path);
+                let preview_path = utils::relative_path(&output_dir, &path)
+                    .unwrap_or_else(|| path.display().to_string());
+                Some((image.original_url.clone(), preview_path))
+            })
+            .collect()
+    }
+
     /// Gets a summary of the content (first paragraph or up to 200 characters).
     pub fn get_summary(&self, max_length: usize) -> String {
         let arena = Arena::new();
@@ -305,6 +555,58 @@ impl MarkdownContent {
         }
     }
 
+    /// Splits the content into sections at its top-level headings.
+    ///
+    /// Splits at the shallowest heading level used more than once, so a single `# Title`
+    /// above `##` chapters splits at the chapters. Text before the first split heading
+    /// becomes a section without title; it is left out if empty.
+    pub fn sections(&self) -> Vec<Section> {
+        let arena = Arena::new();
+        let options = ComrakOptions::default();
+        let root = comrak::parse_document(&arena, &self.content, &options);
+
+        let mut headings = Vec::new();
+        for node in root.children() {
+            if let NodeValue::Heading(heading) = &node.data.borrow().value {
+                let mut title = String::new();
+                TitleExtractor::collect_heading_text(node, &mut title);
+                let line = node.data.borrow().sourcepos.start.line;
+                headings.push((heading.level, line, title.trim().to_string()));
+            }
+        }
+
+        let level_count = |level| headings.iter().filter(|(l, _, _)| *l == level).count();
+        let split_level = (1..=6)
+            .find(|level| level_count(*level) > 1)
+            .or_else(|| headings.iter().map(|(level, _, _)| *level).min());
+        let splits: Vec<_> = headings
+            .into_iter()
+            .filter(|(level, _, _)| Some(*level) == split_level)
+            .collect();
+
+        let mut sections = vec![Section {
+            title: None,
+            markdown: String::new(),
+        }];
+        let mut splits = splits.into_iter().peekable();
+        for (index, line) in self.content.split_inclusive('\n').enumerate() {
+            if let Some((_, _, title)) = splits.next_if(|(_, start, _)| *start == index + 1) {
+                sections.push(Section {
+                    title: Some(title),
+                    markdown: String::new(),
+                });
+            }
+            if let Some(section) = sections.last_mut() {
+                section.markdown.push_str(line);
+            }
+        }
+
+        if sections[0].markdown.trim().is_empty() {
+            sections.remove(0);
+        }
+        sections
+    }
+
     /// Extracts plain text from markdown content.
     pub fn extract_plain_text(&self) -> String {
         let arena = Arena::new();
@@ -379,16 +681,21 @@ impl ImageExtractor {
                 let url = link.url.clone();
 
                 // Calculate approximate position based on content search
-                let position = if let Some(start) =
-                    self.source_content.find(&format!("![{alt_text}]({url})"))
-                {
-                    let end = start + format!("![{alt_text}]({url})").len();
+                let link_start = format!("![{alt_text}]({url}");
+                let position = if let Some(start) = self.source_content.find(&link_start) {
+                    let end = self.source_content[start..]
+                        .find(')')
+                        .map_or(start + link_start.len(), |close| start + close + 1);
                     (start, end)
                 } else {
                     (0, 0) // Fallback if exact match not found
                 };
 
-                let image_ref = ImageRef::new(alt_text, url, position);
+                let line = node.data.borrow().sourcepos.start.line;
+                let mut image_ref = ImageRef::new(alt_text, url, position).with_line(line);
+                if !link.title.is_empty() {
+                    image_ref = image_ref.with_title(link.title.clone());
+                }
                 self.images.push(image_ref);
             }
             _ => {
@@ -416,6 +723,7 @@ impl ImageExtractor {
 #[derive(Debug)]
 pub struct MarkdownParser {
     options: ComrakOptions<'static>,
+    timezone: TimeZone,
 }
 
 impl MarkdownParser {
@@ -428,27 +736,65 @@ impl MarkdownParser {
         options.extension.tasklist = true;
         options.parse.smart = true;
 
-        Self { options }
+        Self {
+            options,
+            timezone: TimeZone::default(),
+        }
+    }
+
+    /// Sets the time zone frontmatter dates are resolved in (default: China Standard
+    /// Time), see [`locale`](crate::locale).
+    pub fn with_timezone(mut self, timezone: TimeZone) -> Self {
+        self.timezone = timezone;
+        self
     }
 
     /// Parses markdown content from a string.
     pub fn parse(&self, markdown: &str) -> Result<MarkdownContent> {
         let (metadata, content_without_frontmatter) = self.extract_frontmatter(markdown)?;
         let title = self.extract_title(&content_without_frontmatter, &metadata);
-        let author = metadata.get("author").cloned();
-        let description = metadata.get("description").cloned();
+        let authors = parse_authors(&metadata);
+        let author = AuthorFormat::default().format(&authors);
+        let text_entry = |key: &str| {
+            metadata
+                .get(key)
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let description = text_entry("description");
+        let digest = text_entry("digest");
         let cover = metadata.get("cover").cloned();
-        let theme = metadata.get("theme").cloned();
+        let theme = metadata.get("theme").map(ThemeId::from);
         let code = metadata.get("code").cloned();
-        let images = self.extract_images(&content_without_frontmatter)?;
+        let date = metadata
+            .get("date")
+            .filter(|date| !date.is_empty())
+            .map(|date| parse_date(date, self.timezone))
+            .transpose()?;
+        // Image lines count from the top of the file, frontmatter included
+        let frontmatter_lines = markdown[..markdown.len() - content_without_frontmatter.len()]
+            .matches('\n')
+            .count();
+        let images = self
+            .extract_images(&content_without_frontmatter)?
+            .into_iter()
+            .map(|image| match image.line {
+                Some(line) => image.with_line(line + frontmatter_lines),
+                None => image,
+            })
+            .collect();
 
         Ok(MarkdownContent {
             title,
             author,
+            authors,
             description,
+            digest,
             cover,
             theme,
             code,
+            date,
             content: content_without_frontmatter,
             images,
             metadata,
@@ -469,7 +815,10 @@ impl MarkdownParser {
     }
 
     /// Extracts front matter (YAML) from markdown content.
-    fn extract_frontmatter(&self, markdown: &str) -> Result<(HashMap<String, String>, String)> {
+    pub(crate) fn extract_frontmatter(
+        &self,
+        markdown: &str,
+    ) -> Result<(HashMap<String, String>, String)> {
         let mut metadata = HashMap::new();
 
         let mut lf_char = "\n" ;
@@ -483,14 +832,35 @@ impl MarkdownParser {
                 let frontmatter = &stripped[..end_pos];
                 let content = &stripped[end_pos + 5..]; // skip "\n---\n"
 
-                // Parse YAML-like front matter (simple key: value pairs)
+                // Parse YAML-like front matter (simple key: value pairs). Indented lines
+                // under a key without a value are nested: `- item` lines are collected
+                // into a `[a, b]` list and `sub: value` lines are stored as `key.sub`.
+                let mut parent: Option<String> = None;
+                let mut items: Vec<(String, Vec<String>)> = Vec::new();
                 for line in frontmatter.lines() {
-                    if let Some((key, value)) = line.split_once(':') {
+                    let nested = line.starts_with([' ', '\t', '-']);
+                    if let (true, Some(parent)) = (nested, &parent) {
+                        let line = line.trim();
+                        if let Some(item) = line.strip_prefix('-') {
+                            let item = item.trim().trim_matches('"').to_string();
+                            match items.last_mut() {
+                                Some((key, list)) if key == parent => list.push(item),
+                                _ => items.push((parent.clone(), vec![item])),
+                            }
+                        } else if let Some((key, value)) = line.split_once(':') {
+                            let value = value.trim().trim_matches('"').to_string();
+                            metadata.insert(format!("{parent}.{}", key.trim()), value);
+                        }
+                    } else if let Some((key, value)) = line.split_once(':') {
                         let key = key.trim().to_string();
                         let value = value.trim().trim_matches('"').to_string();
+                        parent = value.is_empty().then(|| key.clone());
                         metadata.insert(key, value);
                     }
                 }
+                for (key, list) in items {
+                    metadata.insert(key, format!("[{}]", list.join(", ")));
+                }
 
                 content.to_string()
             } else {
@@ -534,9 +904,151 @@ impl Default for MarkdownParser {
     }
 }
 
+/// Collects the authors from `authors`, `author`, or an `author` object.
+fn parse_authors(metadata: &HashMap<String, String>) -> Vec<Author> {
+    let names = metadata
+        .get("authors")
+        .or_else(|| metadata.get("author"))
+        .map(|value| parse_list(value))
+        .unwrap_or_default();
+
+    if !names.is_empty() {
+        return names.into_iter().map(Author::new).collect();
+    }
+
+    match metadata.get("author.name") {
+        Some(name) if !name.is_empty() => vec![Author {
+            name: name.clone(),
+            link: metadata.get("author.link").cloned(),
+            avatar: metadata.get("author.avatar").cloned(),
+        }],
+        _ => Vec::new(),
+    }
+}
+
+/// Replaces `old` with `new` wherever it is followed by `)` or whitespace, i.e. where it
+/// ends at the end of a link's URL. Returns `None` if there is no such occurrence.
+fn replace_link_target(text: &str, old: &str, new: &str) -> Option<String> {
+    let mut replaced = String::with_capacity(text.len());
+    let mut last = 0;
+    for (start, _) in text.match_indices(old) {
+        let end = start + old.len();
+        if text[end..].starts_with(|c: char| c == ')' || c.is_whitespace()) {
+            replaced.push_str(&text[last..start]);
+            replaced.push_str(new);
+            last = end;
+        }
+    }
+    if last == 0 {
+        return None;
+    }
+    replaced.push_str(&text[last..]);
+    Some(replaced)
+}
+
+/// Parses a frontmatter date, written as `2024-05-01`, `2024/05/01`, `2024-05-01 10:30`
+/// (or `2024-05-01T10:30:00`), an RFC 3339 timestamp, a timestamp with an offset such
+/// as `2024-05-01 10:30:00 +0800`, or `today`.
+///
+/// Timestamps with an offset and `today` are resolved in `timezone`.
+pub(crate) fn parse_date(value: &str, timezone: TimeZone) -> Result<NaiveDate> {
+    if value.eq_ignore_ascii_case("today") {
+        return Ok(timezone.today());
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timezone.date_of(datetime));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S %z", "%Y-%m-%d %H:%M %z"] {
+        if let Ok(datetime) = DateTime::parse_from_str(value, format) {
+            return Ok(timezone.date_of(datetime));
+        }
+    }
+    for format in [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+    ] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok(datetime.date());
+        }
+    }
+    for format in ["%Y-%m-%d", "%Y/%m/%d"] {
+        if let Ok(date) = NaiveDate::parse_from_str(value, format) {
+            return Ok(date);
+        }
+    }
+    Err(WeChatError::MarkdownParse {
+        reason: format!("Invalid date '{value}', expected e.g. 2024-05-01 or today"),
+    })
+}
+
+/// Parses a `[a, "b"]` flow list or a single value into its non-empty items.
+pub(crate) fn parse_list(value: &str) -> Vec<String> {
+    let items = match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        Some(inner) => inner.split(',').collect(),
+        None => vec![value],
+    };
+
+    items
+        .into_iter()
+        .map(|item| item.trim().trim_matches(['"', '\'']).to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Sets `key: value` fields in the frontmatter of a markdown document.
+///
+/// Existing keys are replaced in place and new keys are appended to the end of the
+/// frontmatter; all other lines, and the document's line endings, are left untouched.
+/// A frontmatter block is added if the document has none.
+pub fn update_frontmatter(markdown: &str, fields: &[(&str, &str)]) -> String {
+    let lf_char = if markdown.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let opening = format!("---{lf_char}");
+    let closing = format!("{lf_char}---{lf_char}");
+
+    let existing = markdown.strip_prefix(&opening).and_then(|stripped| {
+        stripped
+            .find(&closing)
+            .map(|end_pos| (&stripped[..end_pos], &stripped[end_pos + closing.len()..]))
+    });
+
+    let Some((frontmatter, content)) = existing else {
+        let mut updated = opening;
+        for (key, value) in fields {
+            updated.push_str(&format!("{key}: {value}{lf_char}"));
+        }
+        updated.push_str(&format!("---{lf_char}{markdown}"));
+        return updated;
+    };
+
+    let mut lines: Vec<String> = frontmatter.split(lf_char).map(str::to_string).collect();
+    for (key, value) in fields {
+        let existing_line = lines.iter_mut().find(|line| {
+            line.split_once(':')
+                .is_some_and(|(line_key, _)| line_key.trim() == *key)
+        });
+
+        match existing_line {
+            Some(line) => {
+                let line_key = line.split_once(':').map_or("", |(k, _)| k).to_string();
+                *line = format!("{line_key}: {value}");
+            }
+            None => lines.push(format!("{key}: {value}")),
+        }
+    }
+
+    format!("{opening}{}{closing}{content}", lines.join(lf_char))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::theme::BuiltinTheme;
     use tempfile::NamedTempFile;
 
     #[test]
@@ -556,6 +1068,114 @@ mod tests {
         assert!(!remote_img.is_local);
     }
 
+    #[test]
+    fn test_image_ref_decoded_path() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let base = temp_dir.path();
+        std::fs::write(base.join("my photo.png"), b"png").unwrap();
+        std::fs::write(base.join("100%.png"), b"png").unwrap();
+
+        let encoded = ImageRef::new(
+            "x".to_string(),
+            "./my%20photo.png?v=2#top".to_string(),
+            (0, 0),
+        );
+        assert_eq!(
+            encoded.resolve_path(base).unwrap(),
+            base.join("./my photo.png")
+        );
+        assert_eq!(encoded.original_url, "./my%20photo.png?v=2#top");
+
+        // Invalid escapes are kept as written
+        let literal = ImageRef::new("x".to_string(), "100%.png".to_string(), (0, 0));
+        assert_eq!(literal.resolve_path(base).unwrap(), base.join("100%.png"));
+    }
+
+    #[test]
+    fn test_multiple_authors() {
+        let parser = MarkdownParser::new();
+
+        let content = parser
+            .parse("---\nauthors: [Alice, \"Bob\"]\n---\n\n# Title")
+            .unwrap();
+        assert_eq!(content.authors, [Author::new("Alice"), Author::new("Bob")]);
+        assert_eq!(content.author.as_deref(), Some("Alice, Bob"));
+
+        let content = parser
+            .parse("---\nauthors:\n  - Alice\n  - Bob\n  - Carol\ntitle: T\n---\n\nBody")
+            .unwrap();
+        assert_eq!(content.authors.len(), 3);
+        assert_eq!(content.title.as_deref(), Some("T"));
+        let format = AuthorFormat::default().separator(" / ").max_names(2);
+        assert_eq!(
+            format.format(&content.authors).as_deref(),
+            Some("Alice / Bob et al.")
+        );
+
+        let content = parser
+            .parse("---\nauthor:\n  name: Alice\n  link: https://example.com/alice\n---\n\nBody")
+            .unwrap();
+        assert_eq!(
+            content.authors,
+            [Author {
+                name: "Alice".to_string(),
+                link: Some("https://example.com/alice".to_string()),
+                avatar: None,
+            }]
+        );
+        assert_eq!(content.author.as_deref(), Some("Alice"));
+    }
+
+    #[test]
+    fn test_date_frontmatter() {
+        let parser = MarkdownParser::new();
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        for value in [
+            "2024-05-01",
+            "\"2024/05/01\"",
+            "2024-05-01 10:30",
+            "2024-05-01T10:30:00",
+            "2024-05-01T23:30:00+08:00",
+            "2024-05-01 23:30:00 +0800",
+            "2024-05-01 23:30 +08:00",
+        ] {
+            let content = parser
+                .parse(&format!("---\ndate: {value}\n---\n\n# Title\n"))
+                .unwrap();
+            assert_eq!(content.date, Some(date), "{value}");
+        }
+        assert_eq!(parser.parse("# Title\n").unwrap().date, None);
+        assert!(parser.parse("---\ndate: yesterday\n---\n\nText\n").is_err());
+
+        // Timestamps and `today` are resolved in the parser's time zone
+        let utc_parser = MarkdownParser::new().with_timezone(TimeZone::UTC);
+        let late = "---\ndate: 2024-05-01T23:30:00+00:00\n---\n\nText\n";
+        assert_eq!(utc_parser.parse(late).unwrap().date, Some(date));
+        assert_eq!(parser.parse(late).unwrap().date, date.succ_opt());
+        let today = utc_parser.parse("---\ndate: today\n---\n\nText\n").unwrap();
+        assert_eq!(today.date, Some(TimeZone::UTC.today()));
+
+        assert_eq!(DateFormat::default().format(date), "2024年5月1日");
+        assert_eq!(DateFormat::EnglishLong.format(date), "May 1, 2024");
+        assert_eq!(
+            "iso".parse::<DateFormat>().unwrap().format(date),
+            "2024-05-01"
+        );
+        assert_eq!(
+            "en-US".parse::<DateFormat>().unwrap(),
+            DateFormat::EnglishLong
+        );
+        assert_eq!(
+            "%d/%m/%Y".parse::<DateFormat>().unwrap().format(date),
+            "01/05/2024"
+        );
+        assert!("fr-FR".parse::<DateFormat>().is_err());
+        assert_eq!(
+            DateFormat::Custom("%Q".to_string()).format(date),
+            "2024-05-01"
+        );
+    }
+
     #[test]
     fn test_frontmatter_extraction() {
         let parser = MarkdownParser::new();
@@ -597,6 +1217,26 @@ This is the content."#;
         assert_eq!(title, None);
     }
 
+    #[test]
+    fn test_sections() {
+        let parser = MarkdownParser::new();
+        let content = parser
+            .parse("# Title\n\nIntro\n\n## One\n\nA\n\n```\n## not a heading\n```\n\n### Sub\n\n## Two\n\nB\n")
+            .unwrap();
+
+        let sections = content.sections();
+        let titles: Vec<_> = sections.iter().map(|s| s.title.as_deref()).collect();
+        assert_eq!(titles, [None, Some("One"), Some("Two")]);
+        assert_eq!(sections[0].markdown, "# Title\n\nIntro\n\n");
+        assert!(sections[1].markdown.contains("### Sub"));
+        let joined: String = sections.iter().map(|s| s.markdown.as_str()).collect();
+        assert_eq!(joined, content.content);
+
+        // Without headings everything is one section
+        let content = parser.parse("Just text\n").unwrap();
+        assert_eq!(content.sections().len(), 1);
+    }
+
     #[test]
     fn test_image_extraction() {
         let parser = MarkdownParser::new();
@@ -648,6 +1288,8 @@ More content here."#;
         assert_eq!(content.images.len(), 1);
         assert_eq!(content.images[0].alt_text, "Test");
         assert_eq!(content.images[0].original_url, "./test.jpg");
+        // Counted from the top of the file, frontmatter included
+        assert_eq!(content.images[0].line, Some(9));
     }
 
     #[test]
@@ -668,6 +1310,48 @@ More content here."#;
         assert!(content.content.contains("https://example.com/remote.png"));
     }
 
+    #[test]
+    fn test_titled_image_replacement() {
+        let parser = MarkdownParser::new();
+        let markdown = "![Wide](wide.png \"fullres\") ![Thumb](wide.png.bak) ![Plain](wide.png)";
+
+        let mut content = parser.parse(markdown).unwrap();
+        assert!(content.images[0].is_full_resolution());
+        assert_eq!(content.images[0].position, (0, 27));
+        assert!(!content.images[2].is_full_resolution());
+
+        let url_mapping =
+            HashMap::from([("wide.png".to_string(), "https://wechat.com/1".to_string())]);
+        content.replace_image_urls(&url_mapping).unwrap();
+        assert_eq!(
+            content.content,
+            "![Wide](https://wechat.com/1 \"fullres\") ![Thumb](wide.png.bak) ![Plain](https://wechat.com/1)"
+        );
+    }
+
+    #[test]
+    fn test_local_preview_mapping() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir(temp_dir.path().join("preview")).unwrap();
+        std::fs::write(temp_dir.path().join("local.jpg"), b"fake jpg").unwrap();
+
+        let parser = MarkdownParser::new();
+        let markdown = "![Alt](./local.jpg) and ![Remote](https://example.com/remote.png)";
+        let mut content = parser.parse(markdown).unwrap();
+
+        let mapping = content.local_preview_mapping(
+            temp_dir.path(),
+            &temp_dir.path().join("preview"),
+            &PathPolicy::default(),
+        );
+        assert_eq!(mapping.len(), 1);
+        assert_eq!(mapping["./local.jpg"], "../local.jpg");
+
+        content.replace_image_urls(&mapping).unwrap();
+        assert!(content.content.contains("![Alt](../local.jpg)"));
+        assert!(content.content.contains("https://example.com/remote.png"));
+    }
+
     #[test]
     fn test_summary_extraction() {
         let parser = MarkdownParser::new();
@@ -741,7 +1425,7 @@ theme: lapis
 # Content"#;
 
         let content = parser.parse(markdown_with_theme).unwrap();
-        assert_eq!(content.theme, Some("lapis".to_string()));
+        assert_eq!(content.theme, Some(ThemeId::Builtin(BuiltinTheme::Lapis)));
 
         let markdown_without_theme = r#"---
 title: Test Article
@@ -843,6 +1527,31 @@ This is the second paragraph with more content."#;
         assert_ne!(final_digest, auto_summary);
     }
 
+    #[test]
+    fn test_digest_precedence() {
+        let parser = MarkdownParser::new();
+
+        let content = parser
+            .parse(
+                "---\ndescription: Page description\ndigest: Card summary\n---\n\nFirst paragraph.",
+            )
+            .unwrap();
+        assert_eq!(content.digest, Some("Card summary".to_string()));
+        assert_eq!(content.digest(), "Card summary");
+
+        let content = parser
+            .parse("---\ndescription: Page description\ndigest: \"\"\n---\n\nFirst paragraph.")
+            .unwrap();
+        assert_eq!(content.digest, None);
+        assert_eq!(content.digest(), "Page description");
+
+        let content = parser
+            .parse("---\ndescription: \"  \"\n---\n\nFirst paragraph.")
+            .unwrap();
+        assert_eq!(content.description, None);
+        assert_eq!(content.digest(), "First paragraph.");
+    }
+
     #[test]
     fn test_markdown_parsing_with_all_frontmatter() {
         let parser = MarkdownParser::new();
@@ -877,4 +1586,37 @@ Article content with an image: ![Example](./example.jpg)
         assert_eq!(content.images.len(), 1);
         assert_eq!(content.images[0].original_url, "./example.jpg");
     }
+
+    #[test]
+    fn test_update_frontmatter() {
+        let fields = [
+            (FRONTMATTER_MEDIA_ID, "media_2"),
+            (FRONTMATTER_URL, "https://mp.weixin.qq.com/s/abc"),
+        ];
+
+        // Existing keys are replaced in place, new keys appended, other lines kept
+        let markdown =
+            "---\ntitle: \"Hello\"\n# a comment\nwechat_media_id: media_1\n---\n\n# Body\n";
+        assert_eq!(
+            update_frontmatter(markdown, &fields),
+            "---\ntitle: \"Hello\"\n# a comment\nwechat_media_id: media_2\nwechat_url: https://mp.weixin.qq.com/s/abc\n---\n\n# Body\n"
+        );
+
+        // CRLF line endings are preserved
+        let markdown = "---\r\ntitle: Hello\r\n---\r\nBody\r\n";
+        assert_eq!(
+            update_frontmatter(markdown, &fields[..1]),
+            "---\r\ntitle: Hello\r\nwechat_media_id: media_2\r\n---\r\nBody\r\n"
+        );
+
+        // A frontmatter block is added when missing, and parses back
+        let updated = update_frontmatter("# Title\n", &fields);
+        assert!(updated.ends_with("---\n# Title\n"));
+
+        let parser = MarkdownParser::new();
+        let (metadata, content) = parser.extract_frontmatter(&updated).unwrap();
+        assert_eq!(metadata[FRONTMATTER_MEDIA_ID], "media_2");
+        assert_eq!(metadata[FRONTMATTER_URL], "https://mp.weixin.qq.com/s/abc");
+        assert_eq!(content, "# Title\n");
+    }
 }
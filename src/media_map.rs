@@ -0,0 +1,129 @@
+//! Portable media ID mappings.
+//!
+//! Image deduplication and draft matching rely on in-memory caches (image hash → material,
+//! draft title → media ID) that are lost when the process exits. On CI runners with an
+//! ephemeral filesystem, [`WeChatClient::export_media_map`](crate::WeChatClient::export_media_map)
+//! and [`WeChatClient::import_media_map`](crate::WeChatClient::import_media_map) let these
+//! caches be saved as a build artifact and restored by the next run.
+//!
+//! ```json
+//! {
+//!   "materials": {
+//!     "9a0b…": { "media_id": "MEDIA_ID", "url": "http://mmbiz.qpic.cn/…" }
+//!   },
+//!   "drafts": {
+//!     "My Article": "DRAFT_MEDIA_ID"
+//!   }
+//! }
+//! ```
+
+use crate::error::{Result, WeChatError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Exported material cache and draft mappings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MediaMap {
+    /// Uploaded materials keyed by the BLAKE3 hash of the image content
+    #[serde(default)]
+    pub materials: BTreeMap<String, MaterialEntry>,
+    /// Draft media IDs keyed by article title
+    #[serde(default)]
+    pub drafts: BTreeMap<String, String>,
+}
+
+/// A permanent material uploaded to WeChat.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaterialEntry {
+    /// Media ID of the material
+    pub media_id: String,
+    /// URL of the material
+    pub url: String,
+}
+
+impl MediaMap {
+    /// Loads a media map, returning an empty map if the file doesn't exist.
+    pub async fn load(path: &Path) -> Result<Self> {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| WeChatError::file_error(path.display().to_string(), e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(WeChatError::file_error(
+                path.display().to_string(),
+                e.to_string(),
+            )),
+        }
+    }
+
+    /// Saves the media map, replacing the file atomically.
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let write_error =
+            |e: std::io::Error| WeChatError::file_error(path.display().to_string(), e.to_string());
+
+        if let Some(parent) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(write_error)?;
+        }
+
+        let json = serde_json::to_vec_pretty(self)?;
+        // Unique, so concurrent saves never write to the same temporary file
+        let tmp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        tokio::fs::write(&tmp_path, json)
+            .await
+            .map_err(write_error)?;
+        tokio::fs::rename(&tmp_path, path)
+            .await
+            .map_err(write_error)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_media_map_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("ci").join("media-map.json");
+
+        // Missing file loads as an empty map
+        assert_eq!(MediaMap::load(&path).await.unwrap(), MediaMap::default());
+
+        let mut map = MediaMap::default();
+        map.materials.insert(
+            "abc123".to_string(),
+            MaterialEntry {
+                media_id: "material_1".to_string(),
+                url: "http://mmbiz.qpic.cn/abc123".to_string(),
+            },
+        );
+        map.drafts
+            .insert("My Article".to_string(), "draft_1".to_string());
+        map.save(&path).await.unwrap();
+
+        assert_eq!(MediaMap::load(&path).await.unwrap(), map);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_saves() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("media-map.json");
+        let mut map = MediaMap::default();
+        map.drafts
+            .insert("My Article".to_string(), "draft_1".to_string());
+
+        let (first, second, third) =
+            tokio::join!(map.save(&path), map.save(&path), map.save(&path));
+        first.unwrap();
+        second.unwrap();
+        third.unwrap();
+
+        assert_eq!(MediaMap::load(&path).await.unwrap(), map);
+        let files: Vec<_> = std::fs::read_dir(temp_dir.path()).unwrap().collect();
+        assert_eq!(files.len(), 1);
+    }
+}
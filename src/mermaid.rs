@@ -2,15 +2,25 @@
 //!
 //! This module handles the detection and rendering of Mermaid charts in markdown content.
 //! It generates PNG images from Mermaid code blocks using the mermaid-cli tool.
+//!
+//! By default the images are written to `images/<slug>-N.png` next to the markdown file.
+//! [`MermaidOutput`] moves them to another directory or out of the source tree
+//! altogether, and [`MermaidNaming::Hash`] names them after the chart code instead, so
+//! unchanged charts keep their file across edits that reorder them.
 
+use crate::assets::AssetStore;
 use crate::error::{Result, WeChatError};
 use crate::markdown::ImageRef;
+use crate::temp::TempFiles;
+use crate::utils::PathPolicy;
 use regex::Regex;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::Arc;
 use tokio::fs;
 use tokio::process::Command;
-use tracing::{debug, info};
+use tokio::sync::Semaphore;
+use tracing::{debug, info, warn};
 
 /// Represents a Mermaid chart found in markdown content.
 #[derive(Debug, Clone)]
@@ -34,10 +44,51 @@ impl MermaidChart {
     }
 }
 
+/// Kind of the generated charts in the [`AssetStore`]; includes the mermaid-cli settings
+/// so that changing them renders the charts again.
+const MERMAID_ASSET_KIND: &str = "mermaid:theme=default:background=white:2400x1600@3";
+
+/// Directory in the temporary directory used by [`MermaidOutput::Temp`].
+pub const MERMAID_TEMP_DIR: &str = "wechat-pub-mermaid";
+
+/// Where generated chart images are written.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum MermaidOutput {
+    /// `images/` next to the markdown file
+    #[default]
+    ImagesDir,
+    /// A directory of its own; relative paths are relative to the markdown file's
+    /// directory and must stay inside it
+    Dir(PathBuf),
+    /// A shared directory in the temporary directory ([`MERMAID_TEMP_DIR`]), leaving
+    /// the source tree untouched; images there are always named by hash
+    Temp,
+}
+
+/// How generated chart images are named.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MermaidNaming {
+    /// `<slug>-N.png`, numbered in order of appearance
+    #[default]
+    Sequential,
+    /// `mermaid-<hash>.png`, after the chart code and rendering settings
+    Hash,
+}
+
 /// Mermaid chart processor for converting charts to images.
 pub struct MermaidProcessor {
     /// Base name for the document (used for generating image names)
     document_slug: String,
+    /// Store of previously generated charts
+    asset_store: Option<AssetStore>,
+    /// Where the images are written
+    output: MermaidOutput,
+    /// How the images are named
+    naming: MermaidNaming,
+    /// Render slots shared with other processors, unlimited if `None`
+    render_limit: Option<Arc<Semaphore>>,
+    /// Where the chart sources for mermaid-cli are written
+    temp: TempFiles,
 }
 
 impl MermaidProcessor {
@@ -47,7 +98,55 @@ impl MermaidProcessor {
     /// * `_output_dir` - Directory where images will be generated (unused but kept for API compatibility)
     /// * `document_slug` - Base name for the document (used for generating image names)
     pub fn new(_output_dir: PathBuf, document_slug: String) -> Self {
-        Self { document_slug }
+        Self {
+            document_slug,
+            asset_store: None,
+            output: MermaidOutput::default(),
+            naming: MermaidNaming::default(),
+            render_limit: None,
+            temp: TempFiles::default(),
+        }
+    }
+
+    /// Sets where the chart images are written.
+    pub fn with_output(mut self, output: MermaidOutput) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Sets how the chart images are named.
+    pub fn with_naming(mut self, naming: MermaidNaming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Writes the chart images to `dir` instead of the `images` directory next to the
+    /// markdown file, like [`MermaidOutput::Dir`].
+    ///
+    /// Gives concurrent uploads of articles with the same file name separate images.
+    pub fn with_images_dir(self, dir: PathBuf) -> Self {
+        self.with_output(MermaidOutput::Dir(dir))
+    }
+
+    /// Limits how many charts are rendered at once by all processors sharing `limit`,
+    /// one permit per mermaid-cli process.
+    pub fn with_render_limit(mut self, limit: Arc<Semaphore>) -> Self {
+        self.render_limit = Some(limit);
+        self
+    }
+
+    /// Sets where the chart sources for mermaid-cli and [`MermaidOutput::Temp`] images
+    /// are written, and when the sources are removed.
+    pub fn with_temp_files(mut self, temp: TempFiles) -> Self {
+        self.temp = temp;
+        self
+    }
+
+    /// Sets the store where generated charts are kept, so that a chart is only rendered
+    /// again when its code changes.
+    pub fn with_asset_store(mut self, store: AssetStore) -> Self {
+        self.asset_store = Some(store);
+        self
     }
 
     /// Detects Mermaid code blocks in markdown content.
@@ -116,7 +215,14 @@ impl MermaidProcessor {
         info!("Found {} Mermaid charts to process", charts.len());
 
         // Create output directory if it doesn't exist
-        let images_dir = base_path.join("images");
+        let images_dir = match &self.output {
+            MermaidOutput::ImagesDir => base_path.join("images"),
+            MermaidOutput::Dir(dir) if dir.is_relative() => {
+                PathPolicy::default().resolve(base_path, &dir.to_string_lossy())?
+            }
+            MermaidOutput::Dir(dir) => dir.clone(),
+            MermaidOutput::Temp => self.temp.dir().join(MERMAID_TEMP_DIR),
+        };
         if !images_dir.exists() {
             fs::create_dir_all(&images_dir)
                 .await
@@ -140,20 +246,27 @@ impl MermaidProcessor {
         };
 
         for (index, mut chart) in charts.into_iter().enumerate() {
-            // Generate unique filename based on document slug and chart index
-            let image_filename = format!("{}-{}.png", self.document_slug, index + 1);
+            let image_filename = self.image_filename(index, &chart.code);
             let image_path = images_dir.join(&image_filename);
-            let relative_path = format!("./images/{}", image_filename);
+            let relative_path = match &self.output {
+                MermaidOutput::ImagesDir => format!("./images/{}", image_filename),
+                MermaidOutput::Dir(dir) if dir.is_relative() => {
+                    format!("./{}/{}", dir.display(), image_filename)
+                }
+                _ => image_path.display().to_string(),
+            };
 
-            // Check if we need to regenerate the image
-            let should_regenerate = self
-                .should_regenerate_image(&image_path, source_modified)
-                .await;
+            // Check if we need to regenerate the image; a file named after the chart
+            // code is up to date by definition
+            let should_regenerate = if self.naming_by_hash() {
+                !image_path.exists()
+            } else {
+                self.should_regenerate_image(&image_path, source_modified)
+                    .await
+            };
 
             if should_regenerate {
-                // Generate the image
-                self.generate_mermaid_image(&chart.code, &image_path)
-                    .await?;
+                self.render_chart(&chart.code, &image_path).await?;
             } else {
                 info!(
                     "Skipping regeneration, image is up-to-date: {}",
@@ -190,6 +303,22 @@ impl MermaidProcessor {
         Ok((modified_content, image_refs))
     }
 
+    /// Whether images are named after their chart code.
+    fn naming_by_hash(&self) -> bool {
+        self.naming == MermaidNaming::Hash || self.output == MermaidOutput::Temp
+    }
+
+    /// Gets the file name of the image of the chart at `index`.
+    fn image_filename(&self, index: usize, mermaid_code: &str) -> String {
+        if self.naming_by_hash() {
+            let key = AssetStore::key(MERMAID_ASSET_KIND, mermaid_code.as_bytes());
+            format!("mermaid-{}.png", &key[..16])
+        } else {
+            // Unique filename based on document slug and chart index
+            format!("{}-{}.png", self.document_slug, index + 1)
+        }
+    }
+
     /// Checks if an image needs to be regenerated based on modification times.
     ///
     /// # Arguments
@@ -235,13 +364,40 @@ impl MermaidProcessor {
         source_time > image_modified
     }
 
+    /// Writes the image of a chart to `image_path`, copying it from the asset store if it
+    /// was generated before.
+    async fn render_chart(&self, mermaid_code: &str, image_path: &Path) -> Result<()> {
+        let Some(store) = &self.asset_store else {
+            return self.generate_mermaid_image(mermaid_code, image_path).await;
+        };
+
+        let key = AssetStore::key(MERMAID_ASSET_KIND, mermaid_code.as_bytes());
+        if store.copy_to(&key, image_path).await? {
+            debug!("Reused stored Mermaid image: {}", image_path.display());
+            return Ok(());
+        }
+
+        self.generate_mermaid_image(mermaid_code, image_path)
+            .await?;
+        if let Err(e) = store.put_file(&key, image_path).await {
+            warn!(
+                "Failed to store Mermaid image {}: {e}",
+                image_path.display()
+            );
+        }
+        Ok(())
+    }
+
     /// Generates a PNG image from Mermaid code using mermaid-cli.
     async fn generate_mermaid_image(&self, mermaid_code: &str, output_path: &Path) -> Result<()> {
+        let _permit = match &self.render_limit {
+            Some(limit) => Some(limit.acquire().await.map_err(|_| WeChatError::ShutDown)?),
+            None => None,
+        };
         debug!("Generating Mermaid image: {}", output_path.display());
 
         // Create a temporary file for the Mermaid code
-        let temp_dir = std::env::temp_dir();
-        let temp_input = temp_dir.join(format!("mermaid_{}.mmd", uuid::Uuid::new_v4()));
+        let temp_input = self.temp.file("mermaid", "mmd").await?;
 
         // Write Mermaid code to temporary file
         fs::write(&temp_input, mermaid_code)
@@ -272,13 +428,14 @@ impl MermaidProcessor {
             .output()
             .await
             .map_err(|e| {
+                self.temp.release(&temp_input, false);
                 WeChatError::Internal {
                     message: format!("Failed to execute mermaid-cli: {}. Make sure 'mmdc' is installed (npm install -g @mermaid-js/mermaid-cli)", e),
                 }
             })?;
 
         // Clean up temporary file
-        let _ = fs::remove_file(&temp_input).await;
+        self.temp.release(&temp_input, output.status.success());
 
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -347,6 +504,39 @@ Some text."#;
         assert_eq!(charts.len(), 0);
     }
 
+    #[test]
+    fn test_image_naming() {
+        let processor = MermaidProcessor::new(PathBuf::new(), "post".to_string());
+        assert_eq!(processor.image_filename(1, "graph LR\n"), "post-2.png");
+
+        let processor = processor.with_naming(MermaidNaming::Hash);
+        let name = processor.image_filename(1, "graph LR\n");
+        assert!(name.starts_with("mermaid-") && name.ends_with(".png"));
+        assert_eq!(name.len(), "mermaid-.png".len() + 16);
+        // Named after the code, not the position
+        assert_eq!(processor.image_filename(5, "graph LR\n"), name);
+        assert_ne!(processor.image_filename(1, "graph TD\n"), name);
+
+        // The shared temp directory always names by hash
+        let processor = MermaidProcessor::new(PathBuf::new(), "post".to_string())
+            .with_output(MermaidOutput::Temp);
+        assert_eq!(processor.image_filename(1, "graph LR\n"), name);
+    }
+
+    #[tokio::test]
+    async fn test_output_dir_stays_in_content_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let content_dir = temp_dir.path().join("posts");
+        let processor = MermaidProcessor::new(PathBuf::new(), "test-doc".to_string())
+            .with_output(MermaidOutput::Dir(PathBuf::from("../outside")));
+
+        let result = processor
+            .process_mermaid_content("```mermaid\ngraph LR\n    A --> B\n```\n", &content_dir)
+            .await;
+        assert!(matches!(result, Err(WeChatError::InvalidPath { .. })));
+        assert!(!temp_dir.path().join("outside").exists());
+    }
+
     #[tokio::test]
     async fn test_process_mermaid_content() {
         let temp_dir = TempDir::new().unwrap();
@@ -0,0 +1,330 @@
+//! Web authorization (OAuth 2.0) for pages opened in WeChat.
+//!
+//! Web apps attached to the Official Account log followers in through WeChat: the page
+//! redirects to the [authorize URL](OAuthManager::authorize_url), WeChat redirects back
+//! with a `code`, which is [exchanged](OAuthManager::exchange_code) for a web access
+//! token naming the follower's `openid`. With the [`OAuthScope::UserInfo`] scope the
+//! token also gets the follower's [profile](OAuthManager::user_info). Web access tokens
+//! are per user and unrelated to the account's access token; they last two hours and
+//! are [refreshed](OAuthManager::refresh) with their refresh token (valid for 30 days).
+//!
+//! [`WeChatClient::oauth`](crate::WeChatClient::oauth) uses the client's app credentials:
+//!
+//! ```rust,no_run
+//! use wechat_pub_rs::locale::Locale;
+//! use wechat_pub_rs::oauth::OAuthScope;
+//!
+//! # async fn example(client: wechat_pub_rs::WeChatClient, code: &str) -> wechat_pub_rs::Result<()> {
+//! let oauth = client.oauth();
+//!
+//! // 1. Send the browser to WeChat
+//! let url = oauth.authorize_url("https://example.com/callback", OAuthScope::UserInfo, "xyz");
+//!
+//! // 2. In the callback, exchange the code and get the profile
+//! let token = oauth.exchange_code(code).await?;
+//! let user = oauth.user_info(&token, Locale::ZhCn).await?;
+//! println!("{} logged in as {}", user.openid, user.nickname);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::endpoints::{self, Endpoint};
+use crate::error::Result;
+use crate::http::{self, WeChatHttpClient, WeChatResponse};
+use crate::locale::Locale;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+
+/// Page followers authorize the app on.
+pub const AUTHORIZE_URL: &str = "https://open.weixin.qq.com/connect/oauth2/authorize";
+
+/// What the app may access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OAuthScope {
+    /// The follower's `openid` only, without asking them (`snsapi_base`)
+    #[serde(rename = "snsapi_base")]
+    Base,
+    /// The follower's profile, after they agreed (`snsapi_userinfo`)
+    #[serde(rename = "snsapi_userinfo")]
+    UserInfo,
+}
+
+impl OAuthScope {
+    /// Gets the scope name used by WeChat.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OAuthScope::Base => "snsapi_base",
+            OAuthScope::UserInfo => "snsapi_userinfo",
+        }
+    }
+}
+
+/// A web access token of a follower (`sns/oauth2/access_token` endpoint).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebAccessToken {
+    /// The web access token
+    pub access_token: String,
+    /// Seconds until the access token expires
+    pub expires_in: u64,
+    /// Token to [refresh](OAuthManager::refresh) the access token with
+    pub refresh_token: String,
+    /// `openid` of the follower
+    pub openid: String,
+    /// Scopes the follower granted, comma-separated
+    #[serde(default)]
+    pub scope: String,
+    /// `unionid` of the follower, if the account is bound to an Open Platform account
+    #[serde(default)]
+    pub unionid: Option<String>,
+}
+
+/// Profile of a follower (`sns/userinfo` endpoint).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserInfo {
+    /// `openid` of the follower
+    pub openid: String,
+    /// Nickname
+    #[serde(default)]
+    pub nickname: String,
+    /// 1 for male, 2 for female, 0 if unknown (WeChat no longer reports it)
+    #[serde(default)]
+    pub sex: u8,
+    /// Province of the profile
+    #[serde(default)]
+    pub province: String,
+    /// City of the profile
+    #[serde(default)]
+    pub city: String,
+    /// Country of the profile
+    #[serde(default)]
+    pub country: String,
+    /// URL of the avatar, empty without one
+    #[serde(rename = "headimgurl", default)]
+    pub avatar_url: String,
+    /// Privileges, such as WeChat Pay cards
+    #[serde(default)]
+    pub privilege: Vec<String>,
+    /// `unionid` of the follower, if the account is bound to an Open Platform account
+    #[serde(default)]
+    pub unionid: Option<String>,
+}
+
+/// Authorizes followers on web pages with the account's app credentials.
+#[derive(Debug)]
+pub struct OAuthManager {
+    app_id: String,
+    app_secret: String,
+    http_client: Arc<WeChatHttpClient>,
+}
+
+impl OAuthManager {
+    /// Creates a new OAuth manager.
+    pub fn new(
+        app_id: impl Into<String>,
+        app_secret: impl Into<String>,
+        http_client: Arc<WeChatHttpClient>,
+    ) -> Self {
+        Self {
+            app_id: app_id.into(),
+            app_secret: app_secret.into(),
+            http_client,
+        }
+    }
+
+    /// Builds the URL to send the browser to.
+    ///
+    /// WeChat redirects to `redirect_uri` (whose domain must be configured as the
+    /// account's authorization domain) with `code` and `state` query parameters.
+    pub fn authorize_url(&self, redirect_uri: &str, scope: OAuthScope, state: &str) -> String {
+        format!(
+            "{AUTHORIZE_URL}?{}#wechat_redirect",
+            query(&[
+                ("appid", &self.app_id),
+                ("redirect_uri", redirect_uri),
+                ("response_type", "code"),
+                ("scope", scope.as_str()),
+                ("state", state),
+            ])
+        )
+    }
+
+    /// Exchanges the `code` of the authorization redirect for a web access token.
+    ///
+    /// A code can only be exchanged once, within five minutes, so the request is not
+    /// retried.
+    pub async fn exchange_code(&self, code: &str) -> Result<WebAccessToken> {
+        info!("Exchanging OAuth code for a web access token");
+        self.send_once(
+            endpoints::OAUTH_ACCESS_TOKEN,
+            &[
+                ("appid", &self.app_id),
+                ("secret", &self.app_secret),
+                ("code", code),
+                ("grant_type", "authorization_code"),
+            ],
+        )
+        .await
+    }
+
+    /// Gets a new web access token with the refresh token of an earlier one.
+    ///
+    /// The request is not retried, as a retry after a lost response could be answered
+    /// with an error for a refresh that already happened.
+    pub async fn refresh(&self, refresh_token: &str) -> Result<WebAccessToken> {
+        info!("Refreshing web access token");
+        self.send_once(
+            endpoints::OAUTH_REFRESH_TOKEN,
+            &[
+                ("appid", &self.app_id),
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+            ],
+        )
+        .await
+    }
+
+    /// Gets the profile of the follower of a web access token with the
+    /// [`OAuthScope::UserInfo`] scope, with place names in a language.
+    pub async fn user_info(&self, token: &WebAccessToken, lang: Locale) -> Result<UserInfo> {
+        let lang = match lang {
+            Locale::ZhCn => "zh_CN",
+            Locale::EnUs => "en",
+        };
+        self.get(
+            endpoints::OAUTH_USERINFO,
+            &[
+                ("access_token", &token.access_token),
+                ("openid", &token.openid),
+                ("lang", lang),
+            ],
+        )
+        .await
+    }
+
+    /// Makes a GET request to an OAuth endpoint, which takes its credentials as query
+    /// parameters instead of the account's access token.
+    async fn get<T: serde::de::DeserializeOwned + std::fmt::Debug>(
+        &self,
+        endpoint: Endpoint,
+        params: &[(&str, &str)],
+    ) -> Result<T> {
+        let path = format!("{}?{}", endpoint.path, query(params));
+        let response = self.http_client.get(&path).await?;
+        let response: WeChatResponse<T> = http::read_json(response).await?;
+        response.into_result()
+    }
+
+    /// Like [`get`](Self::get), but sends the request once without retrying.
+    async fn send_once<T: serde::de::DeserializeOwned + std::fmt::Debug>(
+        &self,
+        endpoint: Endpoint,
+        params: &[(&str, &str)],
+    ) -> Result<T> {
+        let path = format!("{}?{}", endpoint.path, query(params));
+        let response = self.http_client.get_once(&path).await?;
+        let response: WeChatResponse<T> = http::read_json(response).await?;
+        response.into_result()
+    }
+}
+
+/// Builds a query string, percent-encoding the values.
+fn query(params: &[(&str, &str)]) -> String {
+    params
+        .iter()
+        .map(|(name, value)| format!("{name}={}", percent_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Percent-encodes everything but unreserved URL characters.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_authorize_url() {
+        let http_client = Arc::new(WeChatHttpClient::new().unwrap());
+        let oauth = OAuthManager::new("wx_app", "secret", http_client);
+
+        assert_eq!(
+            oauth.authorize_url(
+                "https://example.com/callback?next=/posts/1",
+                OAuthScope::UserInfo,
+                "a b"
+            ),
+            "https://open.weixin.qq.com/connect/oauth2/authorize?appid=wx_app\
+             &redirect_uri=https%3A%2F%2Fexample.com%2Fcallback%3Fnext%3D%2Fposts%2F1\
+             &response_type=code&scope=snsapi_userinfo&state=a%20b#wechat_redirect"
+        );
+        assert_eq!(percent_encode("用户"), "%E7%94%A8%E6%88%B7");
+    }
+
+    #[test]
+    fn test_oauth_responses() {
+        let token: WeChatResponse<WebAccessToken> = serde_json::from_str(
+            r#"{"access_token":"web_token","expires_in":7200,"refresh_token":"refresh",
+            "openid":"o1","scope":"snsapi_userinfo","is_snapshotuser":1}"#,
+        )
+        .unwrap();
+        let token = token.into_result().unwrap();
+        assert_eq!(token.openid, "o1");
+        assert_eq!(token.unionid, None);
+
+        let user: UserInfo = serde_json::from_str(
+            r#"{"openid":"o1","nickname":"Reader","sex":0,"province":"","city":"",
+            "country":"","headimgurl":"https://thirdwx.qlogo.cn/mmopen/abc/132",
+            "privilege":[],"unionid":"u1"}"#,
+        )
+        .unwrap();
+        assert_eq!(user.nickname, "Reader");
+        assert_eq!(user.avatar_url, "https://thirdwx.qlogo.cn/mmopen/abc/132");
+        assert_eq!(user.unionid.as_deref(), Some("u1"));
+
+        let error: WeChatResponse<WebAccessToken> =
+            serde_json::from_str(r#"{"errcode":40029,"errmsg":"invalid code"}"#).unwrap();
+        assert!(error.into_result().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code_is_not_retried() {
+        use std::io::Read;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // Drops every connection without answering, a retryable network error
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&requests);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut buffer = [0; 4096];
+                let _ = stream.read(&mut buffer).unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        let mut config = crate::config::Config::default();
+        config.http.base_url = address;
+        config.retry.base_delay_ms = 10;
+        let http_client = Arc::new(WeChatHttpClient::with_config(config).unwrap());
+        let oauth = OAuthManager::new("wx_app", "secret", http_client);
+
+        assert!(oauth.exchange_code("code").await.is_err());
+        assert!(oauth.refresh("refresh").await.is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+}
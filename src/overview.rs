@@ -0,0 +1,136 @@
+//! Account summary for status pages and diagnostics.
+//!
+//! [`WeChatClient::account_overview`](crate::WeChatClient::account_overview) gathers
+//! material counts, the number of drafts and published articles, the remaining daily
+//! quota of the quota-expensive endpoints and the access token expiry into one
+//! [`AccountOverview`], whose `Display` output is a short human-readable summary.
+//!
+//! ```rust,no_run
+//! # async fn example(client: wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+//! let overview = client.account_overview().await?;
+//! println!("{overview}");
+//!
+//! if let Some(quota) = overview.quotas.get("draft.add") {
+//!     assert!(quota.remain > 0, "no drafts left for today");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::endpoints::{self, Endpoint};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Endpoints whose daily quota is included in an overview.
+pub const QUOTA_ENDPOINTS: &[Endpoint] = &[endpoints::DRAFT_ADD, endpoints::MATERIAL_ADD];
+
+/// Number of permanent materials by type (`get_materialcount` endpoint).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaterialCounts {
+    #[serde(rename = "image_count", default)]
+    pub image: u64,
+    #[serde(rename = "voice_count", default)]
+    pub voice: u64,
+    #[serde(rename = "video_count", default)]
+    pub video: u64,
+    #[serde(rename = "news_count", default)]
+    pub news: u64,
+}
+
+/// Daily call quota of an endpoint (`openapi/quota/get` endpoint).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    /// Calls allowed per day
+    pub daily_limit: u64,
+    /// Calls made today
+    pub used: u64,
+    /// Calls left today
+    pub remain: u64,
+}
+
+/// Summary of an account's content and API budget.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccountOverview {
+    /// Permanent materials by type
+    pub materials: MaterialCounts,
+    /// Drafts in the draft box
+    pub drafts: u64,
+    /// Articles published through the publish API
+    pub published: u64,
+    /// Daily quota by endpoint name, for the [`QUOTA_ENDPOINTS`] WeChat reported on
+    pub quotas: BTreeMap<String, QuotaUsage>,
+    /// When the cached access token expires
+    pub token_expires_at: Option<DateTime<Utc>>,
+}
+
+impl std::fmt::Display for AccountOverview {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let materials = &self.materials;
+        writeln!(
+            f,
+            "Materials: {} images, {} voices, {} videos, {} news",
+            materials.image, materials.voice, materials.video, materials.news
+        )?;
+        writeln!(f, "Drafts: {}", self.drafts)?;
+        writeln!(f, "Published: {}", self.published)?;
+        for (name, quota) in &self.quotas {
+            writeln!(
+                f,
+                "Quota {name}: {}/{} left ({} used)",
+                quota.remain, quota.daily_limit, quota.used
+            )?;
+        }
+        match self.token_expires_at {
+            Some(expires_at) => write!(f, "Token expires: {}", expires_at.to_rfc3339()),
+            None => write!(f, "Token expires: no token cached"),
+        }
+    }
+}
+
+/// Draft count response (`draft/count` endpoint).
+#[derive(Debug, Deserialize)]
+pub(crate) struct CountResponse {
+    pub total_count: u64,
+}
+
+/// Quota response (`openapi/quota/get` endpoint).
+#[derive(Debug, Deserialize)]
+pub(crate) struct QuotaResponse {
+    pub quota: QuotaUsage,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::WeChatResponse;
+
+    #[test]
+    fn test_overview_responses() {
+        let counts: WeChatResponse<MaterialCounts> = serde_json::from_str(
+            r#"{"voice_count":1,"video_count":2,"image_count":30,"news_count":4}"#,
+        )
+        .unwrap();
+        let materials = counts.into_result().unwrap();
+        assert_eq!(materials.image, 30);
+        assert_eq!(materials.news, 4);
+
+        let quota: WeChatResponse<QuotaResponse> = serde_json::from_str(
+            r#"{"errcode":0,"errmsg":"ok","quota":{"daily_limit":100,"used":1,"remain":99}}"#,
+        )
+        .unwrap();
+        let quota = quota.into_result().unwrap().quota;
+
+        let overview = AccountOverview {
+            materials,
+            drafts: 5,
+            published: 12,
+            quotas: BTreeMap::from([("draft.add".to_string(), quota)]),
+            token_expires_at: None,
+        };
+        let summary = overview.to_string();
+        assert!(summary.starts_with("Materials: 30 images, 1 voices, 2 videos, 4 news\n"));
+        assert!(summary.contains("Quota draft.add: 99/100 left (1 used)"));
+        assert!(summary.ends_with("Token expires: no token cached"));
+    }
+}
@@ -0,0 +1,171 @@
+//! Isolated upload pipelines sharing one client.
+//!
+//! A [`WeChatClient`] can upload several articles at once, but by default the Mermaid
+//! charts of an article are written to `images/<slug>-N.png` next to the markdown file,
+//! so two articles with the same file name in a shared directory overwrite each other's
+//! charts. An [`ArticlePipeline`] gives every upload its own temporary directory for
+//! generated files and removes them all when the pipeline is cleaned up, or when it is
+//! dropped as the [`CleanupPolicy`](crate::temp::CleanupPolicy) allows.
+//!
+//! ```rust,no_run
+//! use wechat_pub_rs::WeChatClient;
+//!
+//! # async fn example(client: WeChatClient) -> wechat_pub_rs::Result<()> {
+//! let first = client.pipeline()?;
+//! let second = client.pipeline()?;
+//! let (a, b) = tokio::join!(
+//!     first.upload("drafts/en/post.md"),
+//!     second.upload("drafts/zh/post.md"),
+//! );
+//! println!("Drafts: {}, {}", a?, b?);
+//!
+//! first.cleanup().await?;
+//! second.cleanup().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::client::{UploadOptions, UploadReport, WeChatClient, reject_dry_run};
+use crate::error::{Result, WeChatError};
+use crate::mermaid::MermaidProcessor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use tracing::debug;
+
+/// Uploads articles through a shared [`WeChatClient`] with per-article working
+/// directories. Created with [`WeChatClient::pipeline`].
+#[derive(Debug)]
+pub struct ArticlePipeline<'a> {
+    client: &'a WeChatClient,
+    /// Temporary directory holding one subdirectory per upload
+    work_dir: PathBuf,
+    /// Number of uploads started, for naming their directories
+    uploads: AtomicUsize,
+    /// Whether an upload failed, for the cleanup policy
+    failed: AtomicBool,
+}
+
+impl<'a> ArticlePipeline<'a> {
+    /// Creates a pipeline with a new temporary directory.
+    pub(crate) fn new(client: &'a WeChatClient) -> Result<Self> {
+        let work_dir = client.temp_files().create_dir("pipeline")?;
+        debug!("Created pipeline directory: {}", work_dir.display());
+
+        Ok(Self {
+            client,
+            work_dir,
+            uploads: AtomicUsize::new(0),
+            failed: AtomicBool::new(false),
+        })
+    }
+
+    /// Gets the temporary directory of the pipeline.
+    pub fn work_dir(&self) -> &Path {
+        &self.work_dir
+    }
+
+    /// Uploads a markdown file as a WeChat draft article with default options.
+    pub async fn upload(&self, markdown_path: &str) -> Result<String> {
+        self.upload_with_options(markdown_path, UploadOptions::default())
+            .await
+    }
+
+    /// Uploads a markdown file with custom options, returning the draft ID.
+    /// [Dry runs](UploadOptions::dry_run) are rejected, see
+    /// [`upload_with_report`](Self::upload_with_report).
+    pub async fn upload_with_options(
+        &self,
+        markdown_path: &str,
+        options: UploadOptions,
+    ) -> Result<String> {
+        reject_dry_run(&options)?;
+        let report = self.upload_with_report(markdown_path, options).await?;
+        Ok(report.draft_id)
+    }
+
+    /// Uploads a markdown file and reports what was uploaded, like
+    /// [`WeChatClient::upload_with_report`].
+    pub async fn upload_with_report(
+        &self,
+        markdown_path: &str,
+        options: UploadOptions,
+    ) -> Result<UploadReport> {
+        let article_dir = self.article_dir(Path::new(markdown_path)).await?;
+        let result = self
+            .client
+            .upload_in(markdown_path, options, Some(&article_dir))
+            .await;
+        if result.is_err() {
+            self.failed.store(true, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Removes the temporary directory with everything the uploads generated, whatever
+    /// the cleanup policy.
+    pub async fn cleanup(mut self) -> Result<()> {
+        let work_dir = std::mem::take(&mut self.work_dir);
+        match tokio::fs::remove_dir_all(&work_dir).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(WeChatError::file_error(
+                work_dir.display().to_string(),
+                e.to_string(),
+            )),
+        }
+    }
+
+    /// Creates the directory of the next upload, named after the markdown file.
+    async fn article_dir(&self, markdown_path: &Path) -> Result<PathBuf> {
+        let index = self.uploads.fetch_add(1, Ordering::Relaxed) + 1;
+        let slug = MermaidProcessor::extract_slug_from_path(markdown_path);
+        let dir = self.work_dir.join(format!("{index}-{slug}"));
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .map_err(|e| WeChatError::file_error(dir.display().to_string(), e.to_string()))?;
+        Ok(dir)
+    }
+}
+
+impl Drop for ArticlePipeline<'_> {
+    fn drop(&mut self) {
+        // Already removed by `cleanup`
+        if self.work_dir.as_os_str().is_empty() {
+            return;
+        }
+        let succeeded = !self.failed.load(Ordering::Relaxed);
+        self.client.temp_files().release(&self.work_dir, succeeded);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pipeline_directories() {
+        let client = WeChatClient::new("wx1234567890123456", "12345678901234567890123456789012")
+            .await
+            .unwrap();
+
+        let first = client.pipeline().unwrap();
+        let second = client.pipeline().unwrap();
+        assert_ne!(first.work_dir(), second.work_dir());
+        assert!(first.work_dir().is_dir());
+
+        // Articles with the same name get separate directories
+        let a = first.article_dir(Path::new("en/post.md")).await.unwrap();
+        let b = first.article_dir(Path::new("zh/post.md")).await.unwrap();
+        assert_ne!(a, b);
+        assert!(a.starts_with(first.work_dir()) && a.is_dir());
+        assert!(a.file_name().unwrap().to_string_lossy().ends_with("-post"));
+
+        let first_dir = first.work_dir().to_path_buf();
+        first.cleanup().await.unwrap();
+        assert!(!first_dir.exists());
+
+        let second_dir = second.work_dir().to_path_buf();
+        drop(second);
+        assert!(!second_dir.exists());
+    }
+}
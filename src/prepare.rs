@@ -0,0 +1,174 @@
+//! Two-phase uploads and dry runs: render and check an article first, upload it on
+//! confirmation.
+//!
+//! [`WeChatClient::prepare`] runs everything an upload does short of calling WeChat:
+//! it parses the markdown, renders Mermaid charts and the theme, and works out which
+//! images and which cover would be uploaded. The returned [`PreparedUpload`] shows the
+//! result, and [`PreparedUpload::commit`] uploads the images and creates the draft.
+//! Interactive tools can show the user exactly what will happen before anything changes
+//! on WeChat:
+//!
+//! ```rust,no_run
+//! # async fn example(client: wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+//! let prepared = client.prepare("article.md").await?;
+//! println!("{} ({} bytes of HTML)", prepared.title(), prepared.validation().bytes);
+//! for image in prepared.images() {
+//!     println!("Will upload {}", image.reference);
+//! }
+//! for issue in &prepared.validation().issues {
+//!     println!("Problem: {issue}");
+//! }
+//!
+//! if prepared.validation().is_ok() {
+//!     let report = prepared.commit().await?;
+//!     println!("Created draft {}", report.draft_id);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Like [queued uploads](crate::upload_queue), prepared uploads don't support
+//! [`track_state`](crate::UploadOptions::track_state),
+//! [`write_back`](crate::UploadOptions::write_back) or
+//! [`split_long_articles`](crate::UploadOptions::split_long_articles).
+//!
+//! Uploads with [`UploadOptions::dry_run`](crate::UploadOptions::dry_run) stop after the
+//! first phase, returning the same [`UploadPlan`] in
+//! [`UploadReport::dry_run`](crate::UploadReport::dry_run).
+
+use crate::budget::ContentBudget;
+use crate::client::{UploadReport, WeChatClient};
+use crate::error::Result;
+use crate::upload_queue::QueuedPayload;
+use std::path::{Path, PathBuf};
+
+/// An image a prepared upload will upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedImage {
+    /// The image as referenced in the markdown (or the cover path)
+    pub reference: String,
+    /// File of a local image; `None` for remote images, which are downloaded when the
+    /// upload is committed, and for local paths that were rejected
+    pub path: Option<PathBuf>,
+    /// Size of the local file in bytes, before any image processing; `None` if it isn't
+    /// a local file or doesn't exist
+    pub size: Option<u64>,
+}
+
+/// Checks run on a prepared upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Number of characters of the article's HTML, with placeholders for image URLs
+    pub chars: usize,
+    /// Size of the article's HTML in bytes, with placeholders for image URLs
+    pub bytes: usize,
+    /// Budget the HTML was checked against
+    pub budget: ContentBudget,
+    /// Problems that would make the upload fail, e.g. missing images or content over
+    /// the budget
+    pub issues: Vec<String>,
+}
+
+impl ValidationReport {
+    /// Whether no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// What uploading an article would do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadPlan {
+    /// The rendered HTML of the article, with images still pointing at their references
+    /// in the markdown (and the hero image at the cover path); they get their WeChat
+    /// URLs when uploaded
+    pub html: String,
+    /// Images to upload, in document order without duplicates
+    pub images: Vec<PlannedImage>,
+    /// Cover to upload
+    pub cover: PlannedImage,
+    /// Results of the checks run on the article
+    pub validation: ValidationReport,
+}
+
+/// A rendered article waiting to be uploaded, see [`WeChatClient::prepare`].
+#[derive(Debug)]
+pub struct PreparedUpload<'a> {
+    pub(crate) client: &'a WeChatClient,
+    pub(crate) markdown_path: PathBuf,
+    /// Directory image and cover references are relative to
+    pub(crate) base_dir: PathBuf,
+    /// What is sent on commit, with image placeholders in the article content
+    pub(crate) payload: QueuedPayload,
+    pub(crate) plan: UploadPlan,
+}
+
+impl PreparedUpload<'_> {
+    /// Gets the markdown file the article was rendered from.
+    pub fn markdown_path(&self) -> &Path {
+        &self.markdown_path
+    }
+
+    /// Gets the title of the article.
+    pub fn title(&self) -> &str {
+        &self.payload.article.title
+    }
+
+    /// Gets the author of the article.
+    pub fn author(&self) -> &str {
+        &self.payload.article.author
+    }
+
+    /// Gets the summary of the article.
+    pub fn digest(&self) -> &str {
+        &self.payload.article.digest
+    }
+
+    /// Gets the source URL of the article, with the [slug](crate::upload::Article::with_slug)
+    /// of a frontmatter `slug:` in its fragment.
+    pub fn source_url(&self) -> Option<&str> {
+        self.payload.article.content_source_url.as_deref()
+    }
+
+    /// Gets what committing the upload will do.
+    pub fn plan(&self) -> &UploadPlan {
+        &self.plan
+    }
+
+    /// Gets the rendered HTML of the article.
+    ///
+    /// Images still point at their references in the markdown (and the hero image at
+    /// the cover path); they get their WeChat URLs when the upload is committed.
+    pub fn html(&self) -> &str {
+        &self.plan.html
+    }
+
+    /// Gets the images that will be uploaded, in document order without duplicates.
+    pub fn images(&self) -> &[PlannedImage] {
+        &self.plan.images
+    }
+
+    /// Gets the cover that will be uploaded.
+    pub fn cover(&self) -> &PlannedImage {
+        &self.plan.cover
+    }
+
+    /// Gets the draft that will be updated instead of matching drafts by title, from
+    /// the frontmatter `wechat_media_id`.
+    pub fn known_media_id(&self) -> Option<&str> {
+        self.payload.known_media_id.as_deref()
+    }
+
+    /// Gets the results of the checks run on the article.
+    pub fn validation(&self) -> &ValidationReport {
+        &self.plan.validation
+    }
+
+    /// Uploads the images and cover and creates the draft, and notifies the hooks.
+    ///
+    /// Fails without calling WeChat if the [validation](Self::validation) found
+    /// problems.
+    pub async fn commit(self) -> Result<UploadReport> {
+        self.client.commit_prepared(&self).await
+    }
+}
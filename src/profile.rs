@@ -0,0 +1,164 @@
+//! Stage timings of the rendering pipeline.
+//!
+//! [`profile_render`] runs a markdown file through the same stages as an upload (without
+//! touching the network) and reports how long each one took, to find out where time goes
+//! for a given article and theme. For long posts with large themes, CSS inlining usually
+//! dominates.
+//!
+//! ```rust,no_run
+//! use wechat_pub_rs::profile::profile_render;
+//!
+//! # async fn example() -> wechat_pub_rs::Result<()> {
+//! let profile = profile_render("./article.md").await?;
+//! println!("{profile}");
+//! let (stage, time) = profile.hotspot();
+//! println!("Slowest stage: {stage} ({time:?})");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::Result;
+use crate::markdown::MarkdownParser;
+use crate::mermaid::MermaidProcessor;
+use crate::theme::ThemeManager;
+use crate::utils;
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Time spent in each stage of rendering an article.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderProfile {
+    /// Reading the file and parsing frontmatter and markdown
+    pub parse: Duration,
+    /// Rendering Mermaid charts to images
+    pub mermaid: Duration,
+    /// Converting markdown to HTML, including syntax highlighting
+    pub highlight: Duration,
+    /// Resolving CSS variables in the theme and highlight CSS
+    pub css_vars: Duration,
+    /// Templating, code block post-processing and inlining CSS into the HTML
+    pub inline: Duration,
+}
+
+impl RenderProfile {
+    /// Gets the stages with their names, in pipeline order.
+    pub fn stages(&self) -> [(&'static str, Duration); 5] {
+        [
+            ("parse", self.parse),
+            ("mermaid", self.mermaid),
+            ("highlight", self.highlight),
+            ("css_vars", self.css_vars),
+            ("inline", self.inline),
+        ]
+    }
+
+    /// Gets the total time of all stages.
+    pub fn total(&self) -> Duration {
+        self.stages().iter().map(|(_, time)| *time).sum()
+    }
+
+    /// Gets the slowest stage.
+    pub fn hotspot(&self) -> (&'static str, Duration) {
+        self.stages()
+            .into_iter()
+            .max_by_key(|(_, time)| *time)
+            .unwrap_or(("parse", Duration::ZERO))
+    }
+}
+
+impl fmt::Display for RenderProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        for (stage, time) in self.stages() {
+            let share = if total.is_zero() {
+                0.0
+            } else {
+                time.as_secs_f64() / total.as_secs_f64() * 100.0
+            };
+            writeln!(f, "{stage:<10} {time:>12.3?} {share:>5.1}%")?;
+        }
+        write!(f, "{:<10} {total:>12.3?}", "total")
+    }
+}
+
+/// Renders a markdown file with the built-in themes and reports the stage timings.
+///
+/// The theme and code theme are taken from the frontmatter, falling back to the
+/// defaults. Mermaid charts are rendered as during an upload.
+pub async fn profile_render(path: impl AsRef<Path>) -> Result<RenderProfile> {
+    profile_render_with(&ThemeManager::new(), path).await
+}
+
+/// Like [`profile_render`], but renders with the themes of `theme_manager`.
+pub async fn profile_render_with(
+    theme_manager: &ThemeManager,
+    path: impl AsRef<Path>,
+) -> Result<RenderProfile> {
+    let path = path.as_ref();
+    let mut profile = RenderProfile::default();
+
+    let start = Instant::now();
+    let mut content = MarkdownParser::new().parse_file(path).await?;
+    profile.parse = start.elapsed();
+
+    let start = Instant::now();
+    let base_dir = utils::get_base_directory(path).unwrap_or_else(|| Path::new("."));
+    let mermaid_processor = MermaidProcessor::new(
+        base_dir.to_path_buf(),
+        MermaidProcessor::extract_slug_from_path(path),
+    );
+    let (modified_content, _) = mermaid_processor
+        .process_mermaid_content_with_source_path(&content.content, base_dir, Some(path))
+        .await?;
+    content.content = modified_content;
+    profile.mermaid = start.elapsed();
+
+    let mut metadata = content.metadata.clone();
+    if let Some(title) = &content.title {
+        metadata.insert("title".to_string(), title.clone());
+    }
+    if let Some(author) = &content.author {
+        metadata.insert("author".to_string(), author.clone());
+    }
+
+    let theme = content.theme.clone().unwrap_or_default();
+    theme_manager.validate_theme(&theme)?;
+    theme_manager.render_profiled(
+        &content.content,
+        theme.as_str(),
+        content.code.as_deref().unwrap_or("vscode"),
+        &metadata,
+        &mut profile,
+    )?;
+
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_profile_render() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("article.md");
+        std::fs::write(
+            &path,
+            "---\ntitle: Profile\ntheme: lapis\n---\n\n# Heading\n\n```rust\nfn main() {}\n```\n",
+        )
+        .unwrap();
+
+        let profile = profile_render(&path).await.unwrap();
+        assert!(!profile.parse.is_zero());
+        assert!(!profile.highlight.is_zero());
+        assert!(!profile.inline.is_zero());
+        // No Mermaid charts to render
+        assert!(profile.mermaid < profile.total());
+        assert!(profile.stages().contains(&profile.hotspot()));
+
+        let report = profile.to_string();
+        assert!(report.contains("css_vars"));
+        assert!(report.ends_with(&format!("{:<10} {:>12.3?}", "total", profile.total())));
+    }
+}
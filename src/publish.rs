@@ -0,0 +1,374 @@
+//! Status of publish jobs.
+//!
+//! Publishing a draft through the `freepublish` API only starts a job: WeChat checks the
+//! articles (originality, content audit) in the background and reports the outcome
+//! under the job's `publish_id`.
+//! [`WeChatClient::publish_draft`](crate::WeChatClient::publish_draft) submits the job.
+//! [`WeChatClient::get_publish_status`](crate::WeChatClient::get_publish_status) looks a
+//! job up once; [`WeChatClient::wait_until_published`](crate::WeChatClient::wait_until_published)
+//! polls until it finished and turns a failed job into
+//! [`WeChatError::PublishFailed`](crate::WeChatError::PublishFailed):
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use wechat_pub_rs::WeChatError;
+//!
+//! # async fn example(client: wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+//! match client
+//!     .wait_until_published("2247483663_1", Duration::from_secs(300))
+//!     .await
+//! {
+//!     Ok(article) => println!("Published: {:?}", article.urls),
+//!     Err(WeChatError::PublishFailed { reason, .. }) => eprintln!("Rejected: {reason}"),
+//!     Err(e) => return Err(e),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`WeChatClient::list_published`](crate::WeChatClient::list_published) pages through
+//! the articles that are live on the account, e.g. to reconcile them with local markdown
+//! files. [`WeChatClient::get_article_url`](crate::WeChatClient::get_article_url) looks
+//! up the permanent URL of a published article by its article ID, e.g. to share it after
+//! publishing from a job whose status was not kept.
+//!
+//! With the `server` feature, the outcome can be pushed instead of polled: WeChat sends
+//! a `PublishJobFinish` event to the callback URL, and
+//! `WeChatClient::handle_publish_event` passes it to the
+//! [`on_publish_result`](crate::traits::UploadHooks::on_publish_result) hook of the
+//! client's hooks, with the markdown file the job was submitted for.
+
+#[cfg(feature = "client")]
+use serde::Deserialize;
+use std::fmt;
+
+/// An article published by a successful publish job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishedArticle {
+    /// ID of the published article, e.g. for the `freepublish/getarticle` endpoint
+    pub article_id: String,
+    /// Permanent URLs of the articles in the job, in draft order
+    pub urls: Vec<String>,
+}
+
+/// An article in the account's publish history.
+///
+/// Unlike [`PublishedArticle`], the outcome of one publish job, this describes a single
+/// article as it is live on the account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishHistoryEntry {
+    /// ID of the publish job's articles, shared by all articles published together
+    pub article_id: String,
+    /// Title of the article
+    pub title: String,
+    /// Permanent URL of the article
+    pub url: String,
+    /// Unix timestamp of the last update
+    pub update_time: u64,
+}
+
+/// Why a publish job failed, by WeChat's `publish_status` code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublishFailure {
+    /// The originality check failed (2)
+    OriginalityCheck,
+    /// Publishing failed without a specific reason (3)
+    Failed,
+    /// The platform's content audit rejected the articles (4)
+    AuditRejected,
+    /// The articles were deleted by the account after publishing (5)
+    Deleted,
+    /// The articles were banned after publishing (6)
+    Banned,
+    /// A status code this SDK doesn't know
+    Unknown(i32),
+}
+
+impl fmt::Display for PublishFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublishFailure::OriginalityCheck => write!(f, "originality check failed"),
+            PublishFailure::Failed => write!(f, "publishing failed"),
+            PublishFailure::AuditRejected => write!(f, "rejected by the content audit"),
+            PublishFailure::Deleted => write!(f, "deleted after publishing"),
+            PublishFailure::Banned => write!(f, "banned after publishing"),
+            PublishFailure::Unknown(code) => write!(f, "unknown publish status {code}"),
+        }
+    }
+}
+
+/// State of a publish job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublishStatus {
+    /// WeChat is still checking the articles
+    Publishing,
+    /// The articles are published
+    Published(PublishedArticle),
+    /// The job failed
+    Failed {
+        reason: PublishFailure,
+        /// Positions (1-based) of the articles that failed the checks
+        failed_articles: Vec<u32>,
+    },
+}
+
+impl PublishStatus {
+    /// Whether the job is finished, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, PublishStatus::Publishing)
+    }
+
+    /// Builds the state of a job from its `publish_status` code and details.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn from_code(
+        code: i32,
+        article_id: Option<String>,
+        urls: Vec<String>,
+        failed_articles: Vec<u32>,
+    ) -> Self {
+        let reason = match code {
+            0 => {
+                return PublishStatus::Published(PublishedArticle {
+                    article_id: article_id.unwrap_or_default(),
+                    urls,
+                });
+            }
+            1 => return PublishStatus::Publishing,
+            2 => PublishFailure::OriginalityCheck,
+            3 => PublishFailure::Failed,
+            4 => PublishFailure::AuditRejected,
+            5 => PublishFailure::Deleted,
+            6 => PublishFailure::Banned,
+            code => PublishFailure::Unknown(code),
+        };
+        PublishStatus::Failed {
+            reason,
+            failed_articles,
+        }
+    }
+}
+
+/// Submitted publish job (`freepublish/submit` endpoint).
+#[cfg(feature = "client")]
+#[derive(Debug, Deserialize)]
+pub(crate) struct PublishSubmitResponse {
+    pub publish_id: String,
+}
+
+/// Publish job response (`freepublish/get` endpoint).
+#[cfg(feature = "client")]
+#[derive(Debug, Deserialize)]
+pub(crate) struct PublishStatusResponse {
+    pub publish_status: i32,
+    #[serde(default)]
+    pub article_id: Option<String>,
+    #[serde(default)]
+    pub article_detail: Option<ArticleDetail>,
+    #[serde(default)]
+    pub fail_idx: Vec<u32>,
+}
+
+#[cfg(feature = "client")]
+#[derive(Debug, Deserialize)]
+pub(crate) struct ArticleDetail {
+    #[serde(default)]
+    pub item: Vec<ArticleDetailItem>,
+}
+
+#[cfg(feature = "client")]
+#[derive(Debug, Deserialize)]
+pub(crate) struct ArticleDetailItem {
+    pub article_url: String,
+}
+
+#[cfg(feature = "client")]
+impl PublishStatusResponse {
+    pub fn into_status(self) -> PublishStatus {
+        let urls = self
+            .article_detail
+            .map(|detail| detail.item.into_iter().map(|i| i.article_url).collect())
+            .unwrap_or_default();
+        PublishStatus::from_code(self.publish_status, self.article_id, urls, self.fail_idx)
+    }
+}
+
+/// Publish history page (`freepublish/batchget` endpoint).
+#[cfg(feature = "client")]
+#[derive(Debug, Deserialize)]
+pub(crate) struct PublishedListResponse {
+    #[serde(default)]
+    pub item: Vec<PublishedItem>,
+}
+
+#[cfg(feature = "client")]
+#[derive(Debug, Deserialize)]
+pub(crate) struct PublishedItem {
+    pub article_id: String,
+    #[serde(default)]
+    pub content: Option<PublishedContent>,
+    #[serde(default)]
+    pub update_time: u64,
+}
+
+#[cfg(feature = "client")]
+#[derive(Debug, Deserialize)]
+pub(crate) struct PublishedContent {
+    #[serde(default)]
+    pub news_item: Vec<PublishedNewsItem>,
+}
+
+#[cfg(feature = "client")]
+#[derive(Debug, Deserialize)]
+pub(crate) struct PublishedNewsItem {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub url: String,
+    #[serde(default)]
+    pub is_deleted: bool,
+}
+
+/// Articles of a publish job (`freepublish/getarticle` endpoint).
+#[cfg(feature = "client")]
+#[derive(Debug, Deserialize)]
+pub(crate) struct PublishedArticleResponse {
+    #[serde(default)]
+    pub news_item: Vec<PublishedNewsItem>,
+    #[serde(default)]
+    pub update_time: u64,
+}
+
+#[cfg(feature = "client")]
+impl PublishedArticleResponse {
+    /// Gets the articles of the job, skipping deleted ones.
+    pub fn into_articles(self, article_id: &str) -> Vec<PublishHistoryEntry> {
+        let update_time = self.update_time;
+        self.news_item
+            .into_iter()
+            .filter(|news| !news.is_deleted)
+            .map(|news| PublishHistoryEntry {
+                article_id: article_id.to_string(),
+                title: news.title,
+                url: news.url,
+                update_time,
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "client")]
+impl PublishedListResponse {
+    /// Flattens the publish jobs into their articles, skipping deleted ones.
+    pub fn into_articles(self) -> Vec<PublishHistoryEntry> {
+        self.item
+            .into_iter()
+            .flat_map(|item| {
+                let PublishedItem {
+                    article_id,
+                    content,
+                    update_time,
+                } = item;
+                content
+                    .map(|content| content.news_item)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|news| !news.is_deleted)
+                    .map(move |news| PublishHistoryEntry {
+                        article_id: article_id.clone(),
+                        title: news.title,
+                        url: news.url,
+                        update_time,
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, feature = "client"))]
+mod tests {
+    use super::*;
+
+    fn status(json: &str) -> PublishStatus {
+        let response: PublishStatusResponse = serde_json::from_str(json).unwrap();
+        response.into_status()
+    }
+
+    #[test]
+    fn test_publish_status() {
+        let published = status(
+            r#"{"publish_id":"100000001","publish_status":0,"article_id":"b5O2OUs25HBxRceL7hfReg-U9QGeq9zQjiDvy",
+            "article_detail":{"count":1,"item":[{"idx":1,"article_url":"https://mp.weixin.qq.com/s/abc"}]},"fail_idx":[]}"#,
+        );
+        assert_eq!(
+            published,
+            PublishStatus::Published(PublishedArticle {
+                article_id: "b5O2OUs25HBxRceL7hfReg-U9QGeq9zQjiDvy".to_string(),
+                urls: vec!["https://mp.weixin.qq.com/s/abc".to_string()],
+            })
+        );
+
+        let publishing = status(r#"{"publish_id":"100000001","publish_status":1}"#);
+        assert!(!publishing.is_finished());
+
+        let failed = status(r#"{"publish_id":"100000001","publish_status":4,"fail_idx":[2]}"#);
+        assert_eq!(
+            failed,
+            PublishStatus::Failed {
+                reason: PublishFailure::AuditRejected,
+                failed_articles: vec![2],
+            }
+        );
+        assert!(failed.is_finished());
+        assert_eq!(
+            PublishFailure::Unknown(9).to_string(),
+            "unknown publish status 9"
+        );
+    }
+
+    #[test]
+    fn test_published_list() {
+        let response: PublishedListResponse = serde_json::from_str(
+            r#"{"total_count":2,"item_count":2,"item":[
+            {"article_id":"a1","update_time":1700000000,"content":{"news_item":[
+                {"title":"First","url":"https://mp.weixin.qq.com/s/1","is_deleted":false},
+                {"title":"Gone","url":"https://mp.weixin.qq.com/s/2","is_deleted":true}]}},
+            {"article_id":"a2","update_time":1700000100,"content":{"news_item":[
+                {"title":"Second","url":"https://mp.weixin.qq.com/s/3"}]}}]}"#,
+        )
+        .unwrap();
+
+        let articles = response.into_articles();
+        assert_eq!(articles.len(), 2);
+        assert_eq!(
+            articles[0],
+            PublishHistoryEntry {
+                article_id: "a1".to_string(),
+                title: "First".to_string(),
+                url: "https://mp.weixin.qq.com/s/1".to_string(),
+                update_time: 1700000000,
+            }
+        );
+        assert_eq!(articles[1].title, "Second");
+    }
+
+    #[test]
+    fn test_published_article() {
+        let response: PublishedArticleResponse = serde_json::from_str(
+            r#"{"news_item":[
+                {"title":"Gone","url":"https://mp.weixin.qq.com/s/1","is_deleted":true},
+                {"title":"Live","url":"https://mp.weixin.qq.com/s/2","is_deleted":false}],
+            "create_time":1700000000,"update_time":1700000100}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            response.into_articles("a1"),
+            [PublishHistoryEntry {
+                article_id: "a1".to_string(),
+                title: "Live".to_string(),
+                url: "https://mp.weixin.qq.com/s/2".to_string(),
+                update_time: 1700000100,
+            }]
+        );
+    }
+}
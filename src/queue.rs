@@ -0,0 +1,143 @@
+//! Client-wide pacing of quota-expensive API calls.
+//!
+//! WeChat enforces per-minute limits on some operations (material uploads, draft
+//! creation) on top of the daily quotas. Applications uploading many articles in parallel
+//! can hit these limits even though each task on its own is well-behaved. The
+//! [`OperationQueue`] is shared by every request made through one
+//! [`WeChatHttpClient`](crate::http::WeChatHttpClient): calls to a paced [`QuotaClass`]
+//! wait for their turn, so across all tasks they go out at most at the configured rate.
+//!
+//! ```rust
+//! use wechat_pub_rs::endpoints::QuotaClass;
+//! use wechat_pub_rs::queue::OperationQueue;
+//! use std::time::Duration;
+//!
+//! let queue = OperationQueue::new();
+//! queue.pace(QuotaClass::Material, 30);
+//! assert_eq!(queue.interval(QuotaClass::Material), Some(Duration::from_secs(2)));
+//! assert_eq!(queue.interval(QuotaClass::Draft), None);
+//! ```
+
+use crate::config::PerformanceConfig;
+use crate::endpoints::QuotaClass;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::debug;
+
+/// Pacing state of one quota class.
+#[derive(Debug)]
+struct Lane {
+    interval: Duration,
+    next_slot: Option<Instant>,
+}
+
+/// Paces API calls per [`QuotaClass`]. Classes without a rate are not paced.
+#[derive(Debug, Default)]
+pub struct OperationQueue {
+    lanes: Mutex<HashMap<QuotaClass, Lane>>,
+}
+
+impl OperationQueue {
+    /// Creates a queue that doesn't pace any calls.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a queue with the rates from
+    /// [`PerformanceConfig::calls_per_minute`].
+    pub fn from_config(config: &PerformanceConfig) -> Self {
+        let queue = Self::new();
+        for (&class, &calls) in &config.calls_per_minute {
+            queue.pace(class, calls);
+        }
+        queue
+    }
+
+    /// Limits calls of a quota class to `calls_per_minute`, spread evenly over the minute.
+    /// A rate of 0 removes the limit.
+    pub fn pace(&self, class: QuotaClass, calls_per_minute: u32) {
+        let mut lanes = self.lanes.lock().unwrap();
+        if calls_per_minute == 0 {
+            lanes.remove(&class);
+            return;
+        }
+
+        let interval = Duration::from_secs(60) / calls_per_minute;
+        lanes
+            .entry(class)
+            .and_modify(|lane| lane.interval = interval)
+            .or_insert(Lane {
+                interval,
+                next_slot: None,
+            });
+    }
+
+    /// Gets the minimum interval between calls of a quota class, if it is paced.
+    pub fn interval(&self, class: QuotaClass) -> Option<Duration> {
+        self.lanes
+            .lock()
+            .unwrap()
+            .get(&class)
+            .map(|lane| lane.interval)
+    }
+
+    /// Waits until a call of the quota class may be made.
+    ///
+    /// Each caller reserves the next free slot before waiting, so concurrent callers are
+    /// served in the order they arrive.
+    pub async fn acquire(&self, class: QuotaClass) {
+        let slot = {
+            let mut lanes = self.lanes.lock().unwrap();
+            let Some(lane) = lanes.get_mut(&class) else {
+                return;
+            };
+
+            let now = Instant::now();
+            let slot = lane.next_slot.map_or(now, |next| next.max(now));
+            lane.next_slot = Some(slot + lane.interval);
+            slot
+        };
+
+        let wait = slot.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            debug!(quota = class.as_str(), "Pacing call for {wait:?}");
+            tokio::time::sleep_until(slot).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_calls_are_paced_across_tasks() {
+        let queue = Arc::new(OperationQueue::new());
+        queue.pace(QuotaClass::Material, 1200); // one call every 50ms
+
+        let start = Instant::now();
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                tokio::spawn(async move { queue.acquire(QuotaClass::Material).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(150));
+
+        // Unpaced classes never wait
+        let start = Instant::now();
+        for _ in 0..10 {
+            queue.acquire(QuotaClass::Draft).await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        queue.pace(QuotaClass::Material, 0);
+        assert_eq!(queue.interval(QuotaClass::Material), None);
+    }
+}
@@ -0,0 +1,455 @@
+//! Recording and replaying WeChat API traffic (requires the `recording` feature).
+//!
+//! A [`Recorder`] attached to the HTTP client either saves every API request with its
+//! response to a JSON fixture ("cassette"), or answers requests from such a fixture
+//! without touching the network. Record a flow once with real credentials, commit the
+//! fixture, and tests replay it offline:
+//!
+//! ```rust,no_run
+//! use wechat_pub_rs::WeChatClient;
+//! use wechat_pub_rs::recording::Recorder;
+//!
+//! # async fn example() -> wechat_pub_rs::Result<()> {
+//! // Records when WECHAT_RECORD=1 is set, replays the fixture otherwise
+//! let recorder = Recorder::from_env("tests/fixtures/upload.json").await?;
+//! let client = WeChatClient::with_recorder("wx1234567890123456", "app_secret", recorder).await?;
+//! let draft_id = client.upload("tests/fixtures/article.md").await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Credentials and tokens (`appid`, `secret`, `access_token`) are replaced with
+//! [`REDACTED`] in both the query strings and the JSON bodies before they are saved.
+//! Replayed requests are matched by method, endpoint and request body, in recorded
+//! order; file uploads save the uploaded filename as their body, and an interaction
+//! saved without a body matches any body. File downloads are not recorded.
+
+use crate::error::{Result, WeChatError};
+use bytes::Bytes;
+use reqwest::{Method, Response};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+/// Environment variable that makes [`Recorder::from_env`] record instead of replay.
+pub const RECORD_ENV: &str = "WECHAT_RECORD";
+
+/// Value replacing credentials and tokens in saved fixtures.
+pub const REDACTED: &str = "REDACTED";
+
+/// Query parameters and JSON keys whose values are never saved.
+const SECRET_KEYS: &[&str] = &["access_token", "appid", "secret"];
+
+/// Whether a [`Recorder`] saves traffic or replays it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordMode {
+    /// Send requests and save them with their responses
+    Record,
+    /// Answer requests from the fixture without sending them
+    Replay,
+}
+
+/// A request and its response, as saved in a fixture.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    /// HTTP method
+    pub method: String,
+    /// Endpoint path with its query string, without the access token
+    pub endpoint: String,
+    /// JSON request body, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<serde_json::Value>,
+    /// HTTP status code of the response
+    pub status: u16,
+    /// Response body, as JSON if it parses and as a string otherwise
+    pub response: serde_json::Value,
+}
+
+/// Contents of a fixture file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cassette {
+    interactions: Vec<Interaction>,
+}
+
+/// Records API traffic to a fixture, or replays it from one.
+#[derive(Debug)]
+pub struct Recorder {
+    mode: RecordMode,
+    path: PathBuf,
+    /// Saved interactions, with whether each was replayed already
+    interactions: Mutex<Vec<(Interaction, bool)>>,
+}
+
+impl Recorder {
+    /// Creates a recorder that saves traffic to `path`, replacing the fixture.
+    pub fn record(path: impl Into<PathBuf>) -> Self {
+        Self {
+            mode: RecordMode::Record,
+            path: path.into(),
+            interactions: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Creates a recorder that replays the fixture at `path`.
+    pub async fn replay(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let data = tokio::fs::read(&path)
+            .await
+            .map_err(|e| WeChatError::file_error(path.display().to_string(), e.to_string()))?;
+        let cassette: Cassette = serde_json::from_slice(&data)?;
+
+        Ok(Self {
+            mode: RecordMode::Replay,
+            path,
+            interactions: Mutex::new(
+                cassette
+                    .interactions
+                    .into_iter()
+                    .map(|interaction| (interaction, false))
+                    .collect(),
+            ),
+        })
+    }
+
+    /// Records to `path` if [`RECORD_ENV`] is set to `1`, and replays it otherwise.
+    pub async fn from_env(path: impl Into<PathBuf>) -> Result<Self> {
+        if std::env::var(RECORD_ENV).is_ok_and(|value| value == "1") {
+            Ok(Self::record(path))
+        } else {
+            Self::replay(path).await
+        }
+    }
+
+    /// Gets whether the recorder saves or replays traffic.
+    pub fn mode(&self) -> RecordMode {
+        self.mode
+    }
+
+    /// Gets the path of the fixture.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Gets the interactions recorded so far, or loaded from the fixture.
+    pub async fn interactions(&self) -> Vec<Interaction> {
+        let interactions = self.interactions.lock().await;
+        interactions
+            .iter()
+            .map(|(interaction, _)| interaction.clone())
+            .collect()
+    }
+
+    /// Answers a request with the next unused interaction for its method, endpoint and
+    /// body.
+    pub(crate) async fn replay_response(
+        &self,
+        method: &Method,
+        endpoint: &str,
+        request: Option<serde_json::Value>,
+    ) -> Result<Response> {
+        let endpoint = sanitize_endpoint(endpoint);
+        let request = request.map(|mut request| {
+            redact_json(&mut request);
+            request
+        });
+        let mut interactions = self.interactions.lock().await;
+        let (interaction, used) = interactions
+            .iter_mut()
+            .find(|(interaction, used)| {
+                !used
+                    && interaction.method == method.as_str()
+                    && interaction.endpoint == endpoint
+                    && (interaction.request.is_none() || interaction.request == request)
+            })
+            .ok_or_else(|| WeChatError::Internal {
+                message: format!(
+                    "No recorded response for {method} {endpoint} in {}",
+                    self.path.display()
+                ),
+            })?;
+        *used = true;
+        debug!("Replaying recorded response for {method} {endpoint}");
+
+        let body = match &interaction.response {
+            serde_json::Value::String(text) => Bytes::from(text.clone()),
+            json => Bytes::from(serde_json::to_vec(json)?),
+        };
+        build_response(interaction.status, body)
+    }
+
+    /// Saves a request with its response, returning an equivalent response to the
+    /// caller since the body is consumed.
+    pub(crate) async fn record_response(
+        &self,
+        method: &Method,
+        endpoint: &str,
+        request: Option<serde_json::Value>,
+        response: Response,
+    ) -> Result<Response> {
+        let status = response.status().as_u16();
+        let body = response.bytes().await?;
+
+        let mut response_json = serde_json::from_slice(&body)
+            .unwrap_or_else(|_| serde_json::Value::String(String::from_utf8_lossy(&body).into()));
+        redact_json(&mut response_json);
+        let request = request.map(|mut request| {
+            redact_json(&mut request);
+            request
+        });
+
+        let interaction = Interaction {
+            method: method.to_string(),
+            endpoint: sanitize_endpoint(endpoint),
+            request,
+            status,
+            response: response_json,
+        };
+
+        // Save after every interaction, so a failing flow still leaves its fixture
+        let mut interactions = self.interactions.lock().await;
+        interactions.push((interaction, false));
+        let cassette = Cassette {
+            interactions: interactions
+                .iter()
+                .map(|(interaction, _)| interaction.clone())
+                .collect(),
+        };
+        self.save(&cassette).await?;
+        drop(interactions);
+
+        build_response(status, body)
+    }
+
+    async fn save(&self, cassette: &Cassette) -> Result<()> {
+        let write_error = |e: std::io::Error| {
+            WeChatError::file_error(self.path.display().to_string(), e.to_string())
+        };
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(write_error)?;
+        }
+        let json = serde_json::to_vec_pretty(cassette)?;
+        tokio::fs::write(&self.path, json)
+            .await
+            .map_err(write_error)
+    }
+}
+
+/// Builds a response with a JSON content type from a status and body.
+fn build_response(status: u16, body: Bytes) -> Result<Response> {
+    let response = http::Response::builder()
+        .status(status)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .map_err(|e| WeChatError::Internal {
+            message: format!("Invalid recorded response: {e}"),
+        })?;
+    Ok(Response::from(response))
+}
+
+/// Redacts secret query parameters and drops the access token from an endpoint.
+fn sanitize_endpoint(endpoint: &str) -> String {
+    let Some((path, query)) = endpoint.split_once('?') else {
+        return endpoint.to_string();
+    };
+
+    let params: Vec<String> = query
+        .split('&')
+        .filter(|param| !param.starts_with("access_token="))
+        .map(|param| match param.split_once('=') {
+            Some((key, _)) if SECRET_KEYS.contains(&key) => format!("{key}={REDACTED}"),
+            _ => param.to_string(),
+        })
+        .collect();
+
+    if params.is_empty() {
+        path.to_string()
+    } else {
+        format!("{path}?{}", params.join("&"))
+    }
+}
+
+/// Replaces the values of secret keys anywhere in a JSON value.
+fn redact_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if SECRET_KEYS.contains(&key.as_str()) {
+                    *value = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(value);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::http::WeChatHttpClient;
+    use crate::test_server::serve;
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(
+            sanitize_endpoint("/cgi-bin/token?grant_type=client_credential&appid=wx1&secret=s"),
+            "/cgi-bin/token?grant_type=client_credential&appid=REDACTED&secret=REDACTED"
+        );
+        assert_eq!(
+            sanitize_endpoint("/cgi-bin/draft/add?access_token=abc"),
+            "/cgi-bin/draft/add"
+        );
+
+        let mut json = serde_json::json!({
+            "access_token": "abc",
+            "articles": [{ "title": "Hello", "secret": "s" }]
+        });
+        redact_json(&mut json);
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "access_token": REDACTED,
+                "articles": [{ "title": "Hello", "secret": REDACTED }]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let fixture = temp_dir.path().join("fixtures/flow.json");
+
+        let mut config = Config::default();
+        (config.http.base_url, _) = serve(vec![
+            r#"{"access_token":"live-token","expires_in":7200}"#,
+            r#"{"media_id":"MEDIA_ID"}"#,
+        ]);
+        let client = WeChatHttpClient::with_config(config)
+            .unwrap()
+            .with_recorder(Recorder::record(&fixture));
+
+        let response = client
+            .get("/cgi-bin/token?grant_type=client_credential&appid=wx1&secret=s")
+            .await
+            .unwrap();
+        let token: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(token["access_token"], "live-token");
+        let body = serde_json::json!({ "articles": [] });
+        client
+            .post_json_with_token("/cgi-bin/draft/add", "live-token", &body)
+            .await
+            .unwrap();
+
+        let saved = std::fs::read_to_string(&fixture).unwrap();
+        assert!(!saved.contains("live-token") && !saved.contains("wx1"));
+
+        // Replays without a server, in recorded order per endpoint
+        let recorder = Recorder::replay(&fixture).await.unwrap();
+        assert_eq!(recorder.mode(), RecordMode::Replay);
+        assert_eq!(recorder.interactions().await.len(), 2);
+        let client = WeChatHttpClient::new().unwrap().with_recorder(recorder);
+
+        let response = client
+            .post_json_with_token("/cgi-bin/draft/add", REDACTED, &body)
+            .await
+            .unwrap();
+        let draft: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(draft["media_id"], "MEDIA_ID");
+        assert!(
+            client
+                .post_json_with_token("/cgi-bin/draft/add", REDACTED, &body)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_matches_request_body() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let fixture = temp_dir.path().join("bodies.json");
+        let cassette = serde_json::json!({
+            "interactions": [
+                {
+                    "method": "POST",
+                    "endpoint": "/cgi-bin/draft/get",
+                    "request": { "media_id": "FIRST" },
+                    "status": 200,
+                    "response": { "title": "First" }
+                },
+                {
+                    "method": "POST",
+                    "endpoint": "/cgi-bin/draft/get",
+                    "request": { "media_id": "SECOND" },
+                    "status": 200,
+                    "response": { "title": "Second" }
+                }
+            ]
+        });
+        std::fs::write(&fixture, cassette.to_string()).unwrap();
+
+        let recorder = Recorder::replay(&fixture).await.unwrap();
+        let client = WeChatHttpClient::new().unwrap().with_recorder(recorder);
+
+        // Answered by body rather than by recorded order
+        for (media_id, title) in [("SECOND", "Second"), ("FIRST", "First")] {
+            let body = serde_json::json!({ "media_id": media_id });
+            let response = client
+                .post_json_with_token("/cgi-bin/draft/get", REDACTED, &body)
+                .await
+                .unwrap();
+            let draft: serde_json::Value = response.json().await.unwrap();
+            assert_eq!(draft["title"], title);
+        }
+        let body = serde_json::json!({ "media_id": "THIRD" });
+        assert!(
+            client
+                .post_json_with_token("/cgi-bin/draft/get", REDACTED, &body)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_replay() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let fixture = temp_dir.path().join("client.json");
+        let cassette = serde_json::json!({
+            "interactions": [
+                {
+                    "method": "GET",
+                    "endpoint": "/cgi-bin/token?grant_type=client_credential&appid=REDACTED&secret=REDACTED",
+                    "status": 200,
+                    "response": { "access_token": REDACTED, "expires_in": 7200 }
+                },
+                {
+                    "method": "POST",
+                    "endpoint": "/cgi-bin/draft/count",
+                    "request": {},
+                    "status": 200,
+                    "response": { "total_count": 3 }
+                }
+            ]
+        });
+        std::fs::write(&fixture, cassette.to_string()).unwrap();
+
+        let recorder = Recorder::replay(&fixture).await.unwrap();
+        let client = crate::WeChatClient::with_recorder(
+            "wx1234567890123456",
+            "12345678901234567890123456789012",
+            recorder,
+        )
+        .await
+        .unwrap();
+        let count = client
+            .raw_post("/cgi-bin/draft/count", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(count["total_count"], 3);
+    }
+}
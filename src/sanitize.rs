@@ -0,0 +1,171 @@
+//! Character normalization for the WeChat editor.
+//!
+//! The WeChat editor mangles some characters that are perfectly valid in HTML: runs of
+//! non-breaking spaces collapse, zero-width joiners are dropped (splitting emoji ZWJ
+//! sequences such as 👨‍👩‍👧 into separate glyphs) and variation selectors get lost.
+//! [`HtmlNormalization`] is applied to the rendered HTML before upload; it normalizes the
+//! text to Unicode NFC and encodes the affected characters as HTML entities, which the
+//! editor preserves.
+//!
+//! ```rust
+//! use wechat_pub_rs::sanitize::{EntityEncoding, HtmlNormalization};
+//!
+//! let normalization = HtmlNormalization::default();
+//! assert_eq!(normalization.apply("a\u{a0}\u{a0}b"), "a&nbsp;&nbsp;b");
+//!
+//! let normalization = HtmlNormalization::default().entities(EntityEncoding::Emoji);
+//! assert_eq!(normalization.apply("<p>🎉</p>"), "<p>&#x1F389;</p>");
+//! ```
+
+use std::fmt::Write;
+use unicode_normalization::{UnicodeNormalization, is_nfc};
+
+/// Which characters to encode as HTML entities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EntityEncoding {
+    /// Leave all characters as they are
+    None,
+    /// Encode invisible characters: non-breaking spaces, zero-width characters and
+    /// variation selectors
+    #[default]
+    Invisible,
+    /// Encode invisible characters and characters outside the Basic Multilingual Plane,
+    /// which includes most emoji
+    Emoji,
+    /// Encode every non-ASCII character
+    NonAscii,
+}
+
+impl EntityEncoding {
+    /// Whether `c` is encoded under this setting.
+    fn encodes(self, c: char) -> bool {
+        match self {
+            EntityEncoding::None => false,
+            EntityEncoding::Invisible => is_invisible(c),
+            EntityEncoding::Emoji => is_invisible(c) || c > '\u{FFFF}',
+            EntityEncoding::NonAscii => !c.is_ascii(),
+        }
+    }
+}
+
+/// Normalization applied to the final HTML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HtmlNormalization {
+    /// Whether to normalize the text to Unicode NFC
+    pub nfc: bool,
+    /// Which characters to encode as HTML entities
+    pub entities: EntityEncoding,
+}
+
+impl Default for HtmlNormalization {
+    fn default() -> Self {
+        Self {
+            nfc: true,
+            entities: EntityEncoding::Invisible,
+        }
+    }
+}
+
+impl HtmlNormalization {
+    /// Normalization that leaves the HTML untouched.
+    pub fn disabled() -> Self {
+        Self {
+            nfc: false,
+            entities: EntityEncoding::None,
+        }
+    }
+
+    /// Sets whether to normalize to Unicode NFC.
+    pub fn nfc(mut self, enable: bool) -> Self {
+        self.nfc = enable;
+        self
+    }
+
+    /// Sets which characters to encode as HTML entities.
+    pub fn entities(mut self, entities: EntityEncoding) -> Self {
+        self.entities = entities;
+        self
+    }
+
+    /// Applies the normalization to an HTML document.
+    ///
+    /// Entities are valid in both text and attribute values, so the whole document is
+    /// processed; the rendered HTML has no `<script>` or `<style>` contents to protect.
+    pub fn apply(&self, html: &str) -> String {
+        let normalized: String = if self.nfc && !is_nfc(html) {
+            html.nfc().collect()
+        } else {
+            html.to_string()
+        };
+
+        if self.entities == EntityEncoding::None {
+            return normalized;
+        }
+
+        let mut encoded = String::with_capacity(normalized.len());
+        for c in normalized.chars() {
+            if !self.entities.encodes(c) {
+                encoded.push(c);
+            } else if c == '\u{A0}' {
+                encoded.push_str("&nbsp;");
+            } else {
+                let _ = write!(encoded, "&#x{:X};", c as u32);
+            }
+        }
+        encoded
+    }
+}
+
+/// Whether `c` is an invisible character the editor tends to drop or collapse.
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{A0}'
+            | '\u{200B}'..='\u{200D}'
+            | '\u{2060}'
+            | '\u{FEFF}'
+            | '\u{FE00}'..='\u{FE0F}'
+            | '\u{E0020}'..='\u{E007F}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_normalization() {
+        let normalization = HtmlNormalization::default();
+
+        // Decomposed "é" is composed, nbsp runs and joiners are encoded
+        assert_eq!(normalization.apply("Cafe\u{301}"), "Café");
+        assert_eq!(
+            normalization.apply("<p>a\u{a0}\u{a0}\u{a0}b</p>"),
+            "<p>a&nbsp;&nbsp;&nbsp;b</p>"
+        );
+        assert_eq!(
+            normalization.apply("👨\u{200D}👩\u{200D}👧 ❤\u{FE0F}"),
+            "👨&#x200D;👩&#x200D;👧 ❤&#xFE0F;"
+        );
+        assert_eq!(normalization.apply("<p>中文 text</p>"), "<p>中文 text</p>");
+    }
+
+    #[test]
+    fn test_entity_encoding_levels() {
+        let html = "<p title=\"🎉\">é🎉\u{200B}</p>";
+
+        assert_eq!(HtmlNormalization::disabled().apply(html), html);
+        assert_eq!(
+            HtmlNormalization::default()
+                .entities(EntityEncoding::Emoji)
+                .apply(html),
+            "<p title=\"&#x1F389;\">é&#x1F389;&#x200B;</p>"
+        );
+        assert_eq!(
+            HtmlNormalization::default()
+                .entities(EntityEncoding::NonAscii)
+                .apply(html),
+            "<p title=\"&#x1F389;\">&#xE9;&#x1F389;&#x200B;</p>"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! Frontmatter conventions of a content repository.
+//!
+//! A [`FrontmatterSchema`] lists the keys every article must set, the themes it may use
+//! and the formats its dates must be written in. Set it as
+//! [`RenderConfig::frontmatter_schema`](crate::config::RenderConfig::frontmatter_schema)
+//! to check every upload, or call [`FrontmatterSchema::validate`] directly, e.g. in a CI
+//! lint step. All violations are reported at once, with the line of the offending key:
+//!
+//! ```rust
+//! use wechat_pub_rs::schema::FrontmatterSchema;
+//!
+//! let schema = FrontmatterSchema::new()
+//!     .require("author")
+//!     .allowed_themes(["default", "lapis"])
+//!     .date_formats(["%Y-%m-%d"]);
+//!
+//! let violations = schema.validate("---\ntitle: Hello\ntheme: neon\n---\n\nBody\n");
+//! assert_eq!(violations.len(), 2);
+//! assert_eq!(violations[1].line, Some(3));
+//! ```
+
+use crate::locale::TimeZone;
+use crate::markdown::{self, MarkdownParser};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Rules the frontmatter of every article must follow.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrontmatterSchema {
+    /// Keys that must be present with a non-empty value
+    pub required: Vec<String>,
+    /// Values the `theme` key may take, any theme if empty
+    pub allowed_themes: Vec<String>,
+    /// Keys holding dates (default: `date`)
+    pub date_keys: Vec<String>,
+    /// `chrono` formats dates must be written in, any format the parser accepts if empty
+    pub date_formats: Vec<String>,
+}
+
+impl Default for FrontmatterSchema {
+    fn default() -> Self {
+        Self {
+            required: Vec::new(),
+            allowed_themes: Vec::new(),
+            date_keys: vec!["date".to_string()],
+            date_formats: Vec::new(),
+        }
+    }
+}
+
+/// A frontmatter key breaking a [`FrontmatterSchema`] rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// The offending key
+    pub key: String,
+    /// Line of the key in the markdown file, `None` for missing keys
+    pub line: Option<usize>,
+    /// What is wrong with the key
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {line}: {}: {}", self.key, self.message),
+            None => write!(f, "{}: {}", self.key, self.message),
+        }
+    }
+}
+
+impl FrontmatterSchema {
+    /// Creates a schema without required keys or restrictions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requires a key to be present with a non-empty value.
+    pub fn require(mut self, key: impl Into<String>) -> Self {
+        self.required.push(key.into());
+        self
+    }
+
+    /// Restricts the `theme` key to the given themes.
+    pub fn allowed_themes<I, S>(mut self, themes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed_themes = themes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the keys holding dates.
+    pub fn date_keys<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.date_keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Restricts dates to the given `chrono` formats, e.g. `%Y-%m-%d`.
+    pub fn date_formats<I, S>(mut self, formats: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.date_formats = formats.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Checks the frontmatter of a markdown document and returns every violation, in the
+    /// order of the rules.
+    pub fn validate(&self, markdown: &str) -> Vec<SchemaViolation> {
+        let metadata = MarkdownParser::new()
+            .extract_frontmatter(markdown)
+            .map(|(metadata, _)| metadata)
+            .unwrap_or_default();
+        let lines = key_lines(markdown);
+        let violation = |key: &str, message: String| SchemaViolation {
+            key: key.to_string(),
+            line: lines.get(key).copied(),
+            message,
+        };
+
+        let mut violations = Vec::new();
+        for key in &self.required {
+            match metadata.get(key) {
+                None => violations.push(violation(key, "required key is missing".to_string())),
+                Some(value) if value.is_empty() || value == "[]" => {
+                    violations.push(violation(key, "required key has no value".to_string()))
+                }
+                Some(_) => {}
+            }
+        }
+
+        if !self.allowed_themes.is_empty()
+            && let Some(theme) = metadata.get("theme")
+            && !self.allowed_themes.contains(theme)
+        {
+            violations.push(violation(
+                "theme",
+                format!(
+                    "theme '{theme}' is not allowed, use one of: {}",
+                    self.allowed_themes.join(", ")
+                ),
+            ));
+        }
+
+        for key in &self.date_keys {
+            let Some(value) = metadata.get(key).filter(|value| !value.is_empty()) else {
+                continue;
+            };
+            if !self.is_valid_date(value) {
+                let expected = match self.date_formats.is_empty() {
+                    true => "e.g. 2024-05-01".to_string(),
+                    false => self.date_formats.join(" or "),
+                };
+                violations.push(violation(
+                    key,
+                    format!("invalid date '{value}', expected {expected}"),
+                ));
+            }
+        }
+
+        violations
+    }
+
+    fn is_valid_date(&self, value: &str) -> bool {
+        if self.date_formats.is_empty() {
+            return markdown::parse_date(value, TimeZone::default()).is_ok();
+        }
+        self.date_formats
+            .iter()
+            .any(|format| NaiveDate::parse_from_str(value, format).is_ok())
+    }
+}
+
+/// Joins violations into one line, for error messages.
+pub(crate) fn describe(violations: &[SchemaViolation]) -> String {
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Maps the top-level frontmatter keys to their line numbers in the file.
+fn key_lines(markdown: &str) -> HashMap<String, usize> {
+    let mut lines = markdown.lines().enumerate();
+    if lines.next().map(|(_, line)| line.trim_end()) != Some("---") {
+        return HashMap::new();
+    }
+
+    lines
+        .take_while(|(_, line)| line.trim_end() != "---")
+        .filter(|(_, line)| !line.starts_with([' ', '\t', '-']))
+        .filter_map(|(index, line)| {
+            let (key, _) = line.split_once(':')?;
+            Some((key.trim().to_string(), index + 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let markdown = "---\ntitle: Hello\nauthor:\ntheme: neon\ndate: 01.05.2024\ntags:\n  - rust\n---\n\n# Hello\n";
+        let schema = FrontmatterSchema::new()
+            .require("author")
+            .require("summary")
+            .require("tags")
+            .allowed_themes(["default", "lapis"]);
+
+        let violations = schema.validate(markdown);
+        let lines: Vec<_> = violations
+            .iter()
+            .map(|v| (v.key.as_str(), v.line))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                ("author", Some(3)),
+                ("summary", None),
+                ("theme", Some(4)),
+                ("date", Some(5)),
+            ]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "line 3: author: required key has no value"
+        );
+        assert!(describe(&violations).contains("summary: required key is missing; "));
+
+        // Restricted date formats
+        let schema = FrontmatterSchema::new().date_formats(["%Y-%m-%d"]);
+        assert!(schema.validate("---\ndate: 2024-05-01\n---\n").is_empty());
+        let violations = schema.validate("---\ndate: 2024/05/01\n---\n");
+        assert_eq!(
+            violations[0].message,
+            "invalid date '2024/05/01', expected %Y-%m-%d"
+        );
+
+        assert!(
+            FrontmatterSchema::new()
+                .validate("# No frontmatter")
+                .is_empty()
+        );
+    }
+}
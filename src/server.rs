@@ -0,0 +1,1226 @@
+//! Receiving messages and events pushed by WeChat.
+//!
+//! With a callback URL configured for the account, WeChat sends what followers do to the
+//! account's server: the messages they send, follows and unfollows, menu clicks, QR code
+//! scans and the outcome of publish jobs. This module is the receiving half of the
+//! integration and leaves the HTTP server to the application:
+//!
+//! - When the URL is configured, WeChat sends a GET request with `signature`,
+//!   `timestamp`, `nonce` and `echostr` query parameters; [`verify_url`] checks the
+//!   signature against the token configured with the URL and gets the `echostr` to
+//!   respond with.
+//! - Messages arrive as POST requests with an XML body and the same signature
+//!   parameters; [`verify_signature`] checks them and [`Message::parse`] parses the body
+//!   into a typed [`MessageKind`] or [`Event`].
+//! - The response body is either a passive [`Reply`] serialized by [`Message::reply`],
+//!   or [`NO_REPLY`].
+//!
+//! ```rust
+//! use wechat_pub_rs::server::{self, Event, Message, MessageKind, Reply};
+//!
+//! fn handle(query: &server::SignatureParams, body: &str) -> wechat_pub_rs::Result<String> {
+//!     if !query.verify("callback_token") {
+//!         return Ok(String::new());
+//!     }
+//!
+//!     let message = Message::parse(body)?;
+//!     let reply = match &message.kind {
+//!         MessageKind::Text { content } => Reply::text(format!("You said: {content}")),
+//!         MessageKind::Event(Event::Subscribe { .. }) => Reply::text("Thanks for following!"),
+//!         _ => return Ok(server::NO_REPLY.to_string()),
+//!     };
+//!     Ok(message.reply(&reply))
+//! }
+//! ```
+//!
+//! WeChat waits five seconds for the response and retries a message three times, so
+//! slow handling should answer with [`NO_REPLY`] first and use the messaging APIs
+//! afterwards; [`Message::msg_id`] tells retries apart.
+//!
+//! In safe mode (安全模式) and compatible mode, WeChat encrypts messages with the
+//! account's EncodingAESKey and signs them with a `msg_signature` query parameter.
+//! A [`MessageCrypto`] verifies, decrypts and parses them, and encrypts the replies; it
+//! handles plaintext requests too, so the handler doesn't depend on the mode:
+//!
+//! ```rust
+//! use wechat_pub_rs::server::{MessageCrypto, Reply, SignatureParams};
+//!
+//! fn handle(
+//!     crypto: &MessageCrypto,
+//!     query: &SignatureParams,
+//!     body: &str,
+//! ) -> wechat_pub_rs::Result<String> {
+//!     let message = crypto.parse(query, body)?;
+//!     crypto.reply(query, &message, &Reply::text("Received"))
+//! }
+//!
+//! let crypto = MessageCrypto::new(
+//!     "callback_token",
+//!     "abcdefghijklmnopqrstuvwxyz0123456789ABCDEFG",
+//!     "wx1234567890123456",
+//! )?;
+//! # Ok::<(), wechat_pub_rs::WeChatError>(())
+//! ```
+
+use crate::error::{Result, WeChatError};
+use crate::publish::PublishStatus;
+use crate::utils::sha1_hex;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::engine::{GeneralPurpose, GeneralPurposeConfig};
+use cbc::cipher::block_padding::NoPadding;
+use cbc::cipher::generic_array::GenericArray;
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use subtle::ConstantTimeEq;
+
+/// Response body telling WeChat the message was handled without a reply.
+pub const NO_REPLY: &str = "success";
+
+const CDATA_START: &str = "<![CDATA[";
+const CDATA_END: &str = "]]>";
+
+/// Block size WeChat pads encrypted messages to with PKCS#7, twice the AES block size.
+const PADDING_BLOCK_SIZE: usize = 32;
+/// Length of the random bytes at the start of an encrypted message.
+const RANDOM_PREFIX_LEN: usize = 16;
+
+/// Computes the signature WeChat sends with callback requests: the hex SHA-1 digest of
+/// the callback token, timestamp and nonce, sorted and joined.
+pub fn signature(token: &str, timestamp: &str, nonce: &str) -> String {
+    let mut parts = [token, timestamp, nonce];
+    parts.sort_unstable();
+    sha1_hex(parts.concat().as_bytes())
+}
+
+/// Checks the signature of a callback request against the callback token.
+pub fn verify_signature(token: &str, signature: &str, timestamp: &str, nonce: &str) -> bool {
+    signature_matches(&self::signature(token, timestamp, nonce), signature)
+}
+
+/// Compares a computed signature with a received one in constant time; the received
+/// one may be in uppercase hex.
+fn signature_matches(computed: &str, received: &str) -> bool {
+    computed
+        .as_bytes()
+        .ct_eq(received.to_ascii_lowercase().as_bytes())
+        .into()
+}
+
+/// Checks the URL verification request sent when the callback URL is configured and
+/// gets the `echostr` to respond with, `None` if the signature doesn't match.
+pub fn verify_url<'a>(
+    token: &str,
+    signature: &str,
+    timestamp: &str,
+    nonce: &str,
+    echostr: &'a str,
+) -> Option<&'a str> {
+    verify_signature(token, signature, timestamp, nonce).then_some(echostr)
+}
+
+/// Signature query parameters of a callback request, e.g. deserialized by the web
+/// framework.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+pub struct SignatureParams {
+    /// Signature of the request, see [`signature`]
+    pub signature: String,
+    /// Unix timestamp of the request
+    pub timestamp: String,
+    /// Random nonce of the request
+    pub nonce: String,
+    /// String to echo back, only sent with URL verification requests
+    #[serde(default)]
+    pub echostr: Option<String>,
+    /// `aes` for encrypted messages, see [`MessageCrypto`]
+    #[serde(default)]
+    pub encrypt_type: Option<String>,
+    /// Signature of an encrypted message, see [`MessageCrypto::msg_signature`]
+    #[serde(default)]
+    pub msg_signature: Option<String>,
+}
+
+impl SignatureParams {
+    /// Checks the signature against the callback token, see [`verify_signature`].
+    pub fn verify(&self, token: &str) -> bool {
+        verify_signature(token, &self.signature, &self.timestamp, &self.nonce)
+    }
+
+    /// Whether the message of the request is encrypted (`encrypt_type=aes`).
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypt_type
+            .as_deref()
+            .is_some_and(|encrypt_type| encrypt_type.eq_ignore_ascii_case("aes"))
+    }
+}
+
+/// A message or event pushed by WeChat.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    /// Original ID of the account (`gh_...`)
+    pub to_user: String,
+    /// `openid` of the follower
+    pub from_user: String,
+    /// Unix timestamp of the message
+    pub create_time: i64,
+    /// ID of the message, `None` for events
+    pub msg_id: Option<u64>,
+    /// What the message is
+    pub kind: MessageKind,
+    /// All fields of the message by element name, including ones without a typed
+    /// counterpart
+    pub fields: BTreeMap<String, String>,
+}
+
+/// The content of a message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageKind {
+    /// A text message
+    Text { content: String },
+    /// An image
+    Image { pic_url: String, media_id: String },
+    /// A voice message
+    Voice {
+        media_id: String,
+        /// Audio format, e.g. `amr`
+        format: String,
+        /// Speech recognition result, if enabled for the account
+        recognition: Option<String>,
+    },
+    /// A video
+    Video {
+        media_id: String,
+        thumb_media_id: String,
+    },
+    /// A short video
+    ShortVideo {
+        media_id: String,
+        thumb_media_id: String,
+    },
+    /// A location sent by the follower
+    Location {
+        latitude: f64,
+        longitude: f64,
+        /// Zoom level of the map
+        scale: u32,
+        /// Address of the location
+        label: String,
+    },
+    /// A link
+    Link {
+        title: String,
+        description: String,
+        url: String,
+    },
+    /// An event
+    Event(Event),
+    /// A message type this SDK doesn't know
+    Unknown { msg_type: String },
+}
+
+/// An event caused by a follower or the platform.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// The follower followed the account
+    Subscribe {
+        /// Scene value of the parametric QR code the follower scanned to follow
+        scene: Option<String>,
+        /// Ticket of that QR code
+        ticket: Option<String>,
+    },
+    /// The follower unfollowed the account
+    Unsubscribe,
+    /// A follower scanned a parametric QR code
+    Scan {
+        /// Scene value of the QR code
+        scene: String,
+        /// Ticket of the QR code
+        ticket: Option<String>,
+    },
+    /// The follower's location, reported when they open the account's chat
+    Location {
+        latitude: f64,
+        longitude: f64,
+        precision: f64,
+    },
+    /// The follower clicked a menu item
+    Click {
+        /// Key of the menu item
+        key: String,
+    },
+    /// The follower opened the URL of a menu item
+    View { url: String },
+    /// A publish job finished; `WeChatClient::handle_publish_event` notifies the hooks of
+    /// jobs submitted with `WeChatClient::publish_draft`
+    PublishJobFinish {
+        publish_id: String,
+        /// Outcome of the job, as returned by `WeChatClient::get_publish_status`
+        status: PublishStatus,
+    },
+    /// An event this SDK doesn't know
+    Unknown { event: String },
+}
+
+impl Message {
+    /// Parses the XML body of a callback request.
+    pub fn parse(xml: &str) -> Result<Self> {
+        let xml = xml.trim_start();
+        let xml = match xml.strip_prefix("<?") {
+            Some(declaration) => declaration
+                .split_once("?>")
+                .map(|(_, rest)| rest)
+                .ok_or_else(|| invalid("Unclosed XML declaration"))?,
+            None => xml,
+        };
+        let body = parse_elements(xml)?
+            .into_iter()
+            .find(|(name, _)| name == "xml")
+            .ok_or_else(|| invalid("Missing <xml> root element"))?
+            .1;
+        let fields: BTreeMap<String, String> = parse_elements(&body)?.into_iter().collect();
+
+        if !fields.contains_key("MsgType") && fields.contains_key("Encrypt") {
+            return Err(invalid(
+                "Encrypted message; decrypt it with MessageCrypto::parse",
+            ));
+        }
+
+        let text = |name: &str| fields.get(name).cloned().unwrap_or_default();
+        let msg_type = required(&fields, "MsgType")?;
+        let kind = match msg_type.as_str() {
+            "text" => MessageKind::Text {
+                content: text("Content"),
+            },
+            "image" => MessageKind::Image {
+                pic_url: text("PicUrl"),
+                media_id: text("MediaId"),
+            },
+            "voice" => MessageKind::Voice {
+                media_id: text("MediaId"),
+                format: text("Format"),
+                recognition: fields.get("Recognition").cloned(),
+            },
+            "video" => MessageKind::Video {
+                media_id: text("MediaId"),
+                thumb_media_id: text("ThumbMediaId"),
+            },
+            "shortvideo" => MessageKind::ShortVideo {
+                media_id: text("MediaId"),
+                thumb_media_id: text("ThumbMediaId"),
+            },
+            "location" => MessageKind::Location {
+                latitude: number(&fields, "Location_X")?,
+                longitude: number(&fields, "Location_Y")?,
+                scale: number(&fields, "Scale")?,
+                label: text("Label"),
+            },
+            "link" => MessageKind::Link {
+                title: text("Title"),
+                description: text("Description"),
+                url: text("Url"),
+            },
+            "event" => MessageKind::Event(parse_event(&fields)?),
+            _ => MessageKind::Unknown { msg_type },
+        };
+
+        Ok(Self {
+            to_user: required(&fields, "ToUserName")?,
+            from_user: required(&fields, "FromUserName")?,
+            create_time: number(&fields, "CreateTime")?,
+            msg_id: fields
+                .get("MsgId")
+                .map(|_| number(&fields, "MsgId"))
+                .transpose()?,
+            kind,
+            fields,
+        })
+    }
+
+    /// Gets a field of the message by element name, e.g. `EventKey`.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str)
+    }
+
+    /// Serializes a passive reply to the message, to be sent as the response body.
+    pub fn reply(&self, reply: &Reply) -> String {
+        reply.to_xml(
+            &self.from_user,
+            &self.to_user,
+            chrono::Utc::now().timestamp(),
+        )
+    }
+}
+
+/// Parses the fields of an event message.
+fn parse_event(fields: &BTreeMap<String, String>) -> Result<Event> {
+    let event = required(fields, "Event")?;
+    let key = fields.get("EventKey").cloned().unwrap_or_default();
+    let ticket = fields.get("Ticket").cloned();
+
+    Ok(match event.to_ascii_lowercase().as_str() {
+        "subscribe" => Event::Subscribe {
+            scene: key.strip_prefix("qrscene_").map(str::to_string),
+            ticket,
+        },
+        "unsubscribe" => Event::Unsubscribe,
+        "scan" => Event::Scan { scene: key, ticket },
+        "location" => Event::Location {
+            latitude: number(fields, "Latitude")?,
+            longitude: number(fields, "Longitude")?,
+            precision: number(fields, "Precision")?,
+        },
+        "click" => Event::Click { key },
+        "view" => Event::View { url: key },
+        "publishjobfinish" => {
+            let elements = parse_elements(&required(fields, "PublishEventInfo")?)?;
+            let failed_articles = elements
+                .iter()
+                .filter(|(name, _)| name == "fail_idx")
+                .map(|(_, index)| {
+                    index
+                        .trim()
+                        .parse()
+                        .map_err(|_| invalid(format!("Invalid <fail_idx>: {index}")))
+                })
+                .collect::<Result<_>>()?;
+            let info: BTreeMap<String, String> = elements.into_iter().collect();
+            let urls = match info.get("article_detail") {
+                Some(detail) => parse_elements(detail)?
+                    .into_iter()
+                    .filter(|(name, _)| name == "item")
+                    .map(|(_, item)| {
+                        Ok(parse_elements(&item)?
+                            .into_iter()
+                            .find(|(name, _)| name == "article_url")
+                            .map(|(_, url)| url)
+                            .unwrap_or_default())
+                    })
+                    .collect::<Result<_>>()?,
+                None => Vec::new(),
+            };
+            Event::PublishJobFinish {
+                publish_id: required(&info, "publish_id")?,
+                status: PublishStatus::from_code(
+                    number(&info, "publish_status")?,
+                    info.get("article_id").cloned(),
+                    urls,
+                    failed_articles,
+                ),
+            }
+        }
+        _ => Event::Unknown { event },
+    })
+}
+
+/// A passive reply to a message.
+///
+/// WeChat delivers the reply to the follower right away, without counting it against
+/// any quota; media IDs refer to permanent or temporary materials of the account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// A text message
+    Text { content: String },
+    /// An image
+    Image { media_id: String },
+    /// A voice message
+    Voice { media_id: String },
+    /// A video
+    Video {
+        media_id: String,
+        title: String,
+        description: String,
+    },
+    /// Article cards; WeChat shows the first one
+    News(Vec<NewsArticle>),
+}
+
+/// An article card in a [`Reply::News`] reply.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NewsArticle {
+    /// Title of the article
+    pub title: String,
+    /// Summary of the article
+    pub description: String,
+    /// URL of the cover image
+    pub pic_url: String,
+    /// URL opened when the card is tapped
+    pub url: String,
+}
+
+impl Reply {
+    /// Creates a text reply.
+    pub fn text(content: impl Into<String>) -> Self {
+        Reply::Text {
+            content: content.into(),
+        }
+    }
+
+    /// Creates an image reply.
+    pub fn image(media_id: impl Into<String>) -> Self {
+        Reply::Image {
+            media_id: media_id.into(),
+        }
+    }
+
+    /// Creates a reply with a single article card.
+    pub fn article(
+        title: impl Into<String>,
+        description: impl Into<String>,
+        pic_url: impl Into<String>,
+        url: impl Into<String>,
+    ) -> Self {
+        Reply::News(vec![NewsArticle {
+            title: title.into(),
+            description: description.into(),
+            pic_url: pic_url.into(),
+            url: url.into(),
+        }])
+    }
+
+    /// Serializes the reply from the account (`from_user`, its original ID) to a
+    /// follower (`to_user`, their `openid`).
+    pub fn to_xml(&self, to_user: &str, from_user: &str, create_time: i64) -> String {
+        let mut xml = String::from("<xml>");
+        push_text(&mut xml, "ToUserName", to_user);
+        push_text(&mut xml, "FromUserName", from_user);
+        xml.push_str(&format!("<CreateTime>{create_time}</CreateTime>"));
+
+        match self {
+            Reply::Text { content } => {
+                push_text(&mut xml, "MsgType", "text");
+                push_text(&mut xml, "Content", content);
+            }
+            Reply::Image { media_id } => {
+                push_text(&mut xml, "MsgType", "image");
+                xml.push_str("<Image>");
+                push_text(&mut xml, "MediaId", media_id);
+                xml.push_str("</Image>");
+            }
+            Reply::Voice { media_id } => {
+                push_text(&mut xml, "MsgType", "voice");
+                xml.push_str("<Voice>");
+                push_text(&mut xml, "MediaId", media_id);
+                xml.push_str("</Voice>");
+            }
+            Reply::Video {
+                media_id,
+                title,
+                description,
+            } => {
+                push_text(&mut xml, "MsgType", "video");
+                xml.push_str("<Video>");
+                push_text(&mut xml, "MediaId", media_id);
+                push_text(&mut xml, "Title", title);
+                push_text(&mut xml, "Description", description);
+                xml.push_str("</Video>");
+            }
+            Reply::News(articles) => {
+                push_text(&mut xml, "MsgType", "news");
+                xml.push_str(&format!("<ArticleCount>{}</ArticleCount>", articles.len()));
+                xml.push_str("<Articles>");
+                for article in articles {
+                    xml.push_str("<item>");
+                    push_text(&mut xml, "Title", &article.title);
+                    push_text(&mut xml, "Description", &article.description);
+                    push_text(&mut xml, "PicUrl", &article.pic_url);
+                    push_text(&mut xml, "Url", &article.url);
+                    xml.push_str("</item>");
+                }
+                xml.push_str("</Articles>");
+            }
+        }
+
+        xml.push_str("</xml>");
+        xml
+    }
+}
+
+/// Verifies, decrypts and encrypts callback messages in safe mode.
+#[derive(Debug, Clone)]
+pub struct MessageCrypto {
+    token: String,
+    key: [u8; 32],
+    app_id: String,
+}
+
+impl MessageCrypto {
+    /// Creates the crypto of an account from the token and the 43-character
+    /// EncodingAESKey configured with the callback URL.
+    pub fn new(
+        token: impl Into<String>,
+        encoding_aes_key: &str,
+        app_id: impl Into<String>,
+    ) -> Result<Self> {
+        // Keys are generated by WeChat and may have nonzero bits after the last byte
+        let engine = GeneralPurpose::new(
+            &base64::alphabet::STANDARD,
+            GeneralPurposeConfig::new().with_decode_allow_trailing_bits(true),
+        );
+        let key = (encoding_aes_key.len() == 43)
+            .then(|| engine.decode(format!("{encoding_aes_key}=")).ok())
+            .flatten()
+            .and_then(|key| <[u8; 32]>::try_from(key).ok())
+            .ok_or_else(|| {
+                WeChatError::config_error("EncodingAESKey must be 43 base64 characters")
+            })?;
+
+        Ok(Self {
+            token: token.into(),
+            key,
+            app_id: app_id.into(),
+        })
+    }
+
+    /// Computes the signature of an encrypted message: the hex SHA-1 digest of the
+    /// callback token, timestamp, nonce and encrypted message, sorted and joined.
+    pub fn msg_signature(&self, timestamp: &str, nonce: &str, encrypted: &str) -> String {
+        let mut parts = [self.token.as_str(), timestamp, nonce, encrypted];
+        parts.sort_unstable();
+        sha1_hex(parts.concat().as_bytes())
+    }
+
+    /// Verifies and parses the body of a callback request, decrypting it if the request
+    /// is [encrypted](SignatureParams::is_encrypted).
+    ///
+    /// Fails with [`WeChatError::InvalidMessage`] if a signature doesn't match or the
+    /// message was encrypted for another app ID.
+    pub fn parse(&self, params: &SignatureParams, body: &str) -> Result<Message> {
+        if !params.is_encrypted() {
+            if !params.verify(&self.token) {
+                return Err(invalid("Signature doesn't match"));
+            }
+            return Message::parse(body);
+        }
+
+        let encrypted = parse_elements(body)?
+            .into_iter()
+            .find(|(name, _)| name == "xml")
+            .map(|(_, body)| parse_elements(&body))
+            .transpose()?
+            .and_then(|fields| fields.into_iter().find(|(name, _)| name == "Encrypt"))
+            .map(|(_, encrypted)| encrypted)
+            .ok_or_else(|| invalid("Missing <Encrypt>"))?;
+        let signature = self.msg_signature(&params.timestamp, &params.nonce, &encrypted);
+        if !params
+            .msg_signature
+            .as_deref()
+            .is_some_and(|expected| signature_matches(&signature, expected))
+        {
+            return Err(invalid("msg_signature doesn't match"));
+        }
+        Message::parse(&self.decrypt(&encrypted)?)
+    }
+
+    /// Serializes a passive reply to a message, encrypting it if the request was
+    /// encrypted.
+    pub fn reply(
+        &self,
+        params: &SignatureParams,
+        message: &Message,
+        reply: &Reply,
+    ) -> Result<String> {
+        let xml = message.reply(reply);
+        if !params.is_encrypted() {
+            return Ok(xml);
+        }
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        self.encrypt_reply(&xml, &timestamp, &params.nonce)
+    }
+
+    /// Encrypts a reply and wraps it with its signature, to be sent as the response
+    /// body.
+    pub fn encrypt_reply(&self, xml: &str, timestamp: &str, nonce: &str) -> Result<String> {
+        let encrypted = self.encrypt(xml)?;
+        let signature = self.msg_signature(timestamp, nonce, &encrypted);
+
+        let mut reply = String::from("<xml>");
+        push_text(&mut reply, "Encrypt", &encrypted);
+        push_text(&mut reply, "MsgSignature", &signature);
+        reply.push_str(&format!("<TimeStamp>{timestamp}</TimeStamp>"));
+        push_text(&mut reply, "Nonce", nonce);
+        reply.push_str("</xml>");
+        Ok(reply)
+    }
+
+    /// Encrypts a message: AES-256-CBC with the first 16 bytes of the key as IV, over
+    /// 16 random bytes, the big-endian length of the message, the message and the app
+    /// ID, padded with PKCS#7 to 32 bytes; then base64-encoded.
+    ///
+    /// Fails only if the operating system's random number generator does.
+    pub fn encrypt(&self, xml: &str) -> Result<String> {
+        let mut plain = vec![0; RANDOM_PREFIX_LEN];
+        plain.reserve(4 + xml.len() + self.app_id.len() + PADDING_BLOCK_SIZE);
+        getrandom::fill(&mut plain).map_err(|e| WeChatError::Internal {
+            message: format!("Failed to generate random bytes: {e}"),
+        })?;
+        plain.extend_from_slice(&(xml.len() as u32).to_be_bytes());
+        plain.extend_from_slice(xml.as_bytes());
+        plain.extend_from_slice(self.app_id.as_bytes());
+        let padding = PADDING_BLOCK_SIZE - plain.len() % PADDING_BLOCK_SIZE;
+        plain.resize(plain.len() + padding, padding as u8);
+
+        let len = plain.len();
+        let encrypted = cbc::Encryptor::<aes::Aes256>::new(
+            GenericArray::from_slice(&self.key),
+            GenericArray::from_slice(&self.key[..16]),
+        )
+        .encrypt_padded_mut::<NoPadding>(&mut plain, len)
+        .expect("Message should be padded to the block size");
+        Ok(BASE64.encode(encrypted))
+    }
+
+    /// Decrypts a message encrypted by WeChat, checking that it was encrypted for the
+    /// account's app ID.
+    pub fn decrypt(&self, encrypted: &str) -> Result<String> {
+        let mut data = BASE64
+            .decode(encrypted.trim())
+            .map_err(|e| invalid(format!("Encrypted message is not base64: {e}")))?;
+        let plain = cbc::Decryptor::<aes::Aes256>::new(
+            GenericArray::from_slice(&self.key),
+            GenericArray::from_slice(&self.key[..16]),
+        )
+        .decrypt_padded_mut::<NoPadding>(&mut data)
+        .map_err(|_| invalid("Encrypted message is not a whole number of AES blocks"))?;
+
+        let padding = plain.last().copied().unwrap_or_default() as usize;
+        if !(1..=PADDING_BLOCK_SIZE).contains(&padding) || padding > plain.len() {
+            return Err(invalid("Invalid padding; check the EncodingAESKey"));
+        }
+        let plain = &plain[..plain.len() - padding];
+
+        let content = plain
+            .get(RANDOM_PREFIX_LEN..)
+            .filter(|content| content.len() >= 4)
+            .ok_or_else(|| invalid("Decrypted message is too short"))?;
+        let (len, content) = content.split_at(4);
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        if len > content.len() {
+            return Err(invalid("Invalid message length; check the EncodingAESKey"));
+        }
+        let (xml, app_id) = content.split_at(len);
+        if app_id != self.app_id.as_bytes() {
+            return Err(invalid(format!(
+                "Message was encrypted for app ID {}",
+                String::from_utf8_lossy(app_id)
+            )));
+        }
+        String::from_utf8(xml.to_vec()).map_err(|_| invalid("Decrypted message is not valid UTF-8"))
+    }
+}
+
+/// Appends an element with text content in a CDATA section.
+fn push_text(xml: &mut String, name: &str, value: &str) {
+    let value = value.replace(CDATA_END, "]]]]><![CDATA[>");
+    xml.push_str(&format!("<{name}>{CDATA_START}{value}{CDATA_END}</{name}>"));
+}
+
+/// Parses a sequence of XML elements into their names and contents, in order.
+///
+/// Text contents are unescaped and taken out of CDATA sections; contents with child
+/// elements are kept as XML, to be parsed by another call. Attributes, comments and
+/// mixed content are not supported, as WeChat doesn't send them.
+fn parse_elements(xml: &str) -> Result<Vec<(String, String)>> {
+    let mut elements = Vec::new();
+    let mut rest = xml.trim();
+    while !rest.is_empty() {
+        let tag = rest
+            .strip_prefix('<')
+            .ok_or_else(|| invalid(format!("Unexpected text: {}", preview(rest))))?;
+        let tag_end = tag
+            .find('>')
+            .ok_or_else(|| invalid(format!("Unclosed tag: {}", preview(rest))))?;
+        let name = &tag[..tag_end];
+        let after_tag = &tag[tag_end + 1..];
+
+        if let Some(name) = name.strip_suffix('/') {
+            elements.push((name.trim().to_string(), String::new()));
+            rest = after_tag.trim_start();
+            continue;
+        }
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+        {
+            return Err(invalid(format!("Invalid tag: <{name}>")));
+        }
+
+        let close = format!("</{name}>");
+        let cdata_len = cdata_len(after_tag);
+        let content_len = after_tag[cdata_len..]
+            .find(&close)
+            .map(|len| cdata_len + len)
+            .ok_or_else(|| invalid(format!("Missing {close}")))?;
+        elements.push((name.to_string(), decode(&after_tag[..content_len])));
+        rest = after_tag[content_len + close.len()..].trim_start();
+    }
+    Ok(elements)
+}
+
+/// Gets the length of the CDATA sections at the start of a content, so that a closing
+/// tag inside them isn't taken for the end of the element.
+fn cdata_len(content: &str) -> usize {
+    let mut len = 0;
+    while let Some(section) = content[len..].strip_prefix(CDATA_START)
+        && let Some(end) = section.find(CDATA_END)
+    {
+        len += CDATA_START.len() + end + CDATA_END.len();
+    }
+    len
+}
+
+/// Decodes the content of an element into text, keeping child elements as XML.
+fn decode(content: &str) -> String {
+    let trimmed = content.trim_start();
+    if trimmed.starts_with('<') && !trimmed.starts_with(CDATA_START) {
+        return content.to_string();
+    }
+
+    let mut text = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find(CDATA_START) {
+        text.push_str(&unescape(&rest[..start]));
+        let section = &rest[start + CDATA_START.len()..];
+        let end = section.find(CDATA_END).unwrap_or(section.len());
+        text.push_str(&section[..end]);
+        rest = section.get(end + CDATA_END.len()..).unwrap_or_default();
+    }
+    text.push_str(&unescape(rest));
+    text
+}
+
+/// Replaces predefined and numeric XML entities, keeping unknown ones.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest.find(';').map(|end| &rest[1..end]);
+        let decoded = entity.and_then(|entity| match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#')?.parse().ok())
+                .and_then(char::from_u32),
+        });
+        match (entity, decoded) {
+            (Some(entity), Some(c)) => {
+                unescaped.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Gets a field that every message of its kind has.
+fn required(fields: &BTreeMap<String, String>, name: &str) -> Result<String> {
+    fields
+        .get(name)
+        .cloned()
+        .ok_or_else(|| invalid(format!("Missing <{name}>")))
+}
+
+/// Gets a required numeric field.
+fn number<T: FromStr>(fields: &BTreeMap<String, String>, name: &str) -> Result<T> {
+    let value = required(fields, name)?;
+    value
+        .trim()
+        .parse()
+        .map_err(|_| invalid(format!("Invalid <{name}>: {value}")))
+}
+
+/// Gets the start of some XML for error messages.
+fn preview(xml: &str) -> String {
+    xml.chars().take(40).collect()
+}
+
+fn invalid(reason: impl Into<String>) -> WeChatError {
+    WeChatError::InvalidMessage {
+        reason: reason.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::publish::PublishedArticle;
+
+    #[test]
+    fn test_verify_signature() {
+        let token = "callback_token";
+        let signature = signature(token, "1714550400", "nonce42");
+        let mut parts = ["1714550400", "callback_token", "nonce42"];
+        parts.sort_unstable();
+        assert_eq!(signature, sha1_hex(parts.concat().as_bytes()));
+
+        assert!(verify_signature(token, &signature, "1714550400", "nonce42"));
+        assert!(verify_signature(
+            token,
+            &signature.to_uppercase(),
+            "1714550400",
+            "nonce42"
+        ));
+        assert!(!verify_signature(
+            token,
+            &signature,
+            "1714550401",
+            "nonce42"
+        ));
+        assert!(!verify_signature(
+            "other",
+            &signature,
+            "1714550400",
+            "nonce42"
+        ));
+
+        assert_eq!(
+            verify_url(token, &signature, "1714550400", "nonce42", "echo123"),
+            Some("echo123")
+        );
+        assert_eq!(
+            verify_url(token, "bad", "1714550400", "nonce42", "echo123"),
+            None
+        );
+
+        let params = SignatureParams {
+            signature,
+            timestamp: "1714550400".to_string(),
+            nonce: "nonce42".to_string(),
+            ..Default::default()
+        };
+        assert!(params.verify(token));
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let message = Message::parse(
+            "<xml>
+              <ToUserName><![CDATA[gh_account]]></ToUserName>
+              <FromUserName><![CDATA[o_follower]]></FromUserName>
+              <CreateTime>1348831860</CreateTime>
+              <MsgType><![CDATA[text]]></MsgType>
+              <Content><![CDATA[a </Content> b]]]]><![CDATA[>]]></Content>
+              <MsgId>1234567890123456</MsgId>
+            </xml>",
+        )
+        .unwrap();
+        assert_eq!(message.to_user, "gh_account");
+        assert_eq!(message.from_user, "o_follower");
+        assert_eq!(message.create_time, 1348831860);
+        assert_eq!(message.msg_id, Some(1234567890123456));
+        assert_eq!(
+            message.kind,
+            MessageKind::Text {
+                content: "a </Content> b]]>".to_string()
+            }
+        );
+        assert_eq!(message.field("MsgType"), Some("text"));
+
+        let message = Message::parse(
+            "<?xml version=\"1.0\"?><xml><ToUserName>gh_account</ToUserName>\
+             <FromUserName>o_follower</FromUserName><CreateTime>1</CreateTime>\
+             <MsgType>location</MsgType><Location_X>23.134521</Location_X>\
+             <Location_Y>113.358803</Location_Y><Scale>20</Scale>\
+             <Label>Tom &amp; Jerry&#39;s &#x4E2D;</Label><MsgId>2</MsgId></xml>",
+        )
+        .unwrap();
+        assert_eq!(
+            message.kind,
+            MessageKind::Location {
+                latitude: 23.134521,
+                longitude: 113.358803,
+                scale: 20,
+                label: "Tom & Jerry's 中".to_string(),
+            }
+        );
+
+        let message = Message::parse(
+            "<xml><ToUserName>gh_account</ToUserName><FromUserName>o_follower</FromUserName>\
+             <CreateTime>1</CreateTime><MsgType>miniprogrampage</MsgType><Empty/></xml>",
+        )
+        .unwrap();
+        assert_eq!(
+            message.kind,
+            MessageKind::Unknown {
+                msg_type: "miniprogrampage".to_string()
+            }
+        );
+        assert_eq!(message.msg_id, None);
+        assert_eq!(message.field("Empty"), Some(""));
+
+        for xml in [
+            "",
+            "<xml><ToUserName>gh_account</ToUserName></xml>",
+            "<xml><MsgType>text</MsgType><CreateTime>1</CreateTime>",
+            "<xml><ToUserName>a</ToUserName><FromUserName>b</FromUserName>\
+             <CreateTime>soon</CreateTime><MsgType>text</MsgType></xml>",
+            "<xml><ToUserName>gh_account</ToUserName><Encrypt>abc</Encrypt></xml>",
+        ] {
+            assert!(
+                matches!(Message::parse(xml), Err(WeChatError::InvalidMessage { .. })),
+                "{xml}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let event = |body: &str| {
+            let xml = format!(
+                "<xml><ToUserName>gh_account</ToUserName><FromUserName>o_follower</FromUserName>\
+                 <CreateTime>1</CreateTime><MsgType>event</MsgType>{body}</xml>"
+            );
+            match Message::parse(&xml).unwrap().kind {
+                MessageKind::Event(event) => event,
+                kind => panic!("Not an event: {kind:?}"),
+            }
+        };
+
+        assert_eq!(
+            event("<Event>subscribe</Event>"),
+            Event::Subscribe {
+                scene: None,
+                ticket: None
+            }
+        );
+        assert_eq!(
+            event("<Event>subscribe</Event><EventKey>qrscene_123</EventKey><Ticket>t</Ticket>"),
+            Event::Subscribe {
+                scene: Some("123".to_string()),
+                ticket: Some("t".to_string())
+            }
+        );
+        assert_eq!(event("<Event>unsubscribe</Event>"), Event::Unsubscribe);
+        assert_eq!(
+            event("<Event>SCAN</Event><EventKey>123</EventKey>"),
+            Event::Scan {
+                scene: "123".to_string(),
+                ticket: None
+            }
+        );
+        assert_eq!(
+            event("<Event>CLICK</Event><EventKey>LATEST_POSTS</EventKey>"),
+            Event::Click {
+                key: "LATEST_POSTS".to_string()
+            }
+        );
+        assert_eq!(
+            event("<Event>VIEW</Event><EventKey>https://example.com</EventKey>"),
+            Event::View {
+                url: "https://example.com".to_string()
+            }
+        );
+        assert_eq!(
+            event("<Event>TEMPLATESENDJOBFINISH</Event>"),
+            Event::Unknown {
+                event: "TEMPLATESENDJOBFINISH".to_string()
+            }
+        );
+
+        assert_eq!(
+            event(
+                "<Event><![CDATA[PUBLISHJOBFINISH]]></Event><PublishEventInfo>\
+                 <publish_id>2247503051</publish_id><publish_status>0</publish_status>\
+                 <article_id><![CDATA[b5O2OUs25HBxRceL]]></article_id>\
+                 <article_detail><count>2</count>\
+                 <item><idx>1</idx><article_url><![CDATA[https://mp.weixin.qq.com/s/a]]></article_url></item>\
+                 <item><idx>2</idx><article_url><![CDATA[https://mp.weixin.qq.com/s/b]]></article_url></item>\
+                 </article_detail></PublishEventInfo>"
+            ),
+            Event::PublishJobFinish {
+                publish_id: "2247503051".to_string(),
+                status: PublishStatus::Published(PublishedArticle {
+                    article_id: "b5O2OUs25HBxRceL".to_string(),
+                    urls: vec![
+                        "https://mp.weixin.qq.com/s/a".to_string(),
+                        "https://mp.weixin.qq.com/s/b".to_string(),
+                    ],
+                }),
+            }
+        );
+        assert_eq!(
+            event(
+                "<Event>PUBLISHJOBFINISH</Event><PublishEventInfo><publish_id>1</publish_id>\
+                 <publish_status>2</publish_status><fail_idx>1</fail_idx><fail_idx>2</fail_idx>\
+                 </PublishEventInfo>"
+            ),
+            Event::PublishJobFinish {
+                publish_id: "1".to_string(),
+                status: PublishStatus::Failed {
+                    reason: crate::publish::PublishFailure::OriginalityCheck,
+                    failed_articles: vec![1, 2],
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_reply_xml() {
+        let message = Message::parse(
+            "<xml><ToUserName>gh_account</ToUserName><FromUserName>o_follower</FromUserName>\
+             <CreateTime>1</CreateTime><MsgType>text</MsgType><Content>hi</Content></xml>",
+        )
+        .unwrap();
+
+        let reply = message.reply(&Reply::text("a]]>b"));
+        let parsed = Message::parse(&reply).unwrap();
+        assert_eq!(parsed.to_user, "o_follower");
+        assert_eq!(parsed.from_user, "gh_account");
+        assert_eq!(
+            parsed.kind,
+            MessageKind::Text {
+                content: "a]]>b".to_string()
+            }
+        );
+
+        assert_eq!(
+            Reply::image("media_1").to_xml("o_follower", "gh_account", 42),
+            "<xml><ToUserName><![CDATA[o_follower]]></ToUserName>\
+             <FromUserName><![CDATA[gh_account]]></FromUserName><CreateTime>42</CreateTime>\
+             <MsgType><![CDATA[image]]></MsgType>\
+             <Image><MediaId><![CDATA[media_1]]></MediaId></Image></xml>"
+        );
+        assert_eq!(
+            Reply::article(
+                "Title",
+                "Summary",
+                "https://example.com/cover.png",
+                "https://example.com"
+            )
+            .to_xml("o_follower", "gh_account", 42),
+            "<xml><ToUserName><![CDATA[o_follower]]></ToUserName>\
+             <FromUserName><![CDATA[gh_account]]></FromUserName><CreateTime>42</CreateTime>\
+             <MsgType><![CDATA[news]]></MsgType><ArticleCount>1</ArticleCount><Articles><item>\
+             <Title><![CDATA[Title]]></Title><Description><![CDATA[Summary]]></Description>\
+             <PicUrl><![CDATA[https://example.com/cover.png]]></PicUrl>\
+             <Url><![CDATA[https://example.com]]></Url></item></Articles></xml>"
+        );
+    }
+
+    #[test]
+    fn test_message_crypto() {
+        let crypto = MessageCrypto::new(
+            "callback_token",
+            "abcdefghijklmnopqrstuvwxyz0123456789ABCDEFG",
+            "wx1234567890123456",
+        )
+        .unwrap();
+        assert!(MessageCrypto::new("callback_token", "too_short", "wx1").is_err());
+
+        // Encrypted with OpenSSL
+        let encrypted = "Q3stYC6hdFzMh9T8HCvyDDWNB0a6CplYN5MpNQLsTmTUOUOyqdEaxgE7lna+nKT3m91EZkrr\
+                         ZaJkiMRqO6mO0b95ukwOIpXcl4n2dRNlinx/nhD+fYxFkeLDr6hvHLIfz2vvaSU/FNkD6KFJ\
+                         68esG8etMS7cXU26KdhRhcSBgV6F6Lkw2MWEFeNiCxM+v0tUbqhcg/Gt+8cZaWIFpAJMjKXb\
+                         P2l8PqTW4TibkWGnKL4MqrNpvdmXtpmlJlXdHaqDMdBKVpQ7mUpll/ay1meiGwcrh6453jOZ\
+                         Ho9SqCHVvsqBzxkHsT3JJbEYF7t6dI77FYzCKjgAfbLVu9q2VmESjv44GzLh6wI7wdo6kBy+\
+                         8E6NnafDvNzex3hLHu6NHf2u";
+        let signature = "6400a442bee4af1e393fd9c0edde23fe79cb0a6a";
+        assert_eq!(
+            crypto.msg_signature("1714550400", "nonce42", encrypted),
+            signature
+        );
+
+        let params = SignatureParams {
+            timestamp: "1714550400".to_string(),
+            nonce: "nonce42".to_string(),
+            encrypt_type: Some("aes".to_string()),
+            msg_signature: Some(signature.to_string()),
+            ..Default::default()
+        };
+        let body = format!(
+            "<xml><ToUserName><![CDATA[gh_account]]></ToUserName>\
+             <Encrypt><![CDATA[{encrypted}]]></Encrypt></xml>"
+        );
+        let message = crypto.parse(&params, &body).unwrap();
+        assert_eq!(message.from_user, "o_follower");
+        assert_eq!(
+            message.kind,
+            MessageKind::Text {
+                content: "hello".to_string()
+            }
+        );
+
+        let tampered = SignatureParams {
+            nonce: "nonce43".to_string(),
+            ..params.clone()
+        };
+        assert!(matches!(
+            crypto.parse(&tampered, &body),
+            Err(WeChatError::InvalidMessage { .. })
+        ));
+        let other_app = MessageCrypto::new(
+            "callback_token",
+            "abcdefghijklmnopqrstuvwxyz0123456789ABCDEFG",
+            "wx_other",
+        )
+        .unwrap();
+        assert!(other_app.decrypt(encrypted).is_err());
+        assert!(Message::parse(&body).is_err());
+
+        // Replies are encrypted and signed for encrypted requests
+        let reply = crypto
+            .reply(&params, &message, &Reply::text("Received"))
+            .unwrap();
+        let envelope: BTreeMap<String, String> =
+            parse_elements(&parse_elements(&reply).unwrap()[0].1)
+                .unwrap()
+                .into_iter()
+                .collect();
+        assert_eq!(envelope["Nonce"], "nonce42");
+        assert_eq!(
+            envelope["MsgSignature"],
+            crypto.msg_signature(&envelope["TimeStamp"], "nonce42", &envelope["Encrypt"])
+        );
+        let reply = Message::parse(&crypto.decrypt(&envelope["Encrypt"]).unwrap()).unwrap();
+        assert_eq!(reply.to_user, "o_follower");
+        assert_eq!(
+            reply.kind,
+            MessageKind::Text {
+                content: "Received".to_string()
+            }
+        );
+
+        // Plaintext requests are verified with the plain signature
+        let plain = SignatureParams {
+            signature: super::signature("callback_token", "1714550400", "nonce42"),
+            timestamp: "1714550400".to_string(),
+            nonce: "nonce42".to_string(),
+            ..Default::default()
+        };
+        let xml = crypto.decrypt(encrypted).unwrap();
+        assert_eq!(crypto.parse(&plain, &xml).unwrap(), message);
+        assert!(
+            Message::parse(
+                &crypto
+                    .reply(&plain, &message, &Reply::text("Received"))
+                    .unwrap()
+            )
+            .is_ok()
+        );
+        assert!(
+            crypto
+                .parse(
+                    &SignatureParams {
+                        signature: "bad".to_string(),
+                        ..plain
+                    },
+                    &xml
+                )
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Document slugs naming everything derived from a markdown file.
+//!
+//! The slug of a document names its generated Mermaid charts (`images/<slug>-N.png`),
+//! keys its entry in the state file and fills `{slug}` in
+//! [`source_url`](crate::UploadOptions::source_url) templates. Which slug a document
+//! gets is picked with a [`SlugStrategy`] in
+//! [`RenderConfig`](crate::config::RenderConfig::slug_strategy):
+//!
+//! | Strategy | `posts/hello.md` with `title: 你好世界` and `slug: greeting` |
+//! |----------|--------------------------------------------------------------|
+//! | [`FileStem`](SlugStrategy::FileStem) (default) | `hello` |
+//! | [`Frontmatter`](SlugStrategy::Frontmatter) | `greeting` |
+//! | [`Title`](SlugStrategy::Title) | `ni-hao-shi-jie` |
+//!
+//! Documents without a `slug:` or title fall back to the file stem.
+//!
+//! Whatever the strategy, a `slug:` entry also identifies the document's draft after its
+//! title changed. It is stored in the fragment of the article's source URL, so it needs
+//! a [`source_url`](crate::UploadOptions::source_url) without a fragment; without one
+//! a warning is logged and the draft is matched by title only.
+
+use crate::error::{Result, WeChatError};
+use crate::markdown::{MarkdownContent, MarkdownParser};
+use crate::mermaid::MermaidProcessor;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Frontmatter key holding the slug of a document, stored with the draft if the article
+/// has a source URL.
+pub const SLUG_KEY: &str = "slug";
+
+/// How the slug of a document is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlugStrategy {
+    /// The markdown file name without extension
+    #[default]
+    FileStem,
+    /// The `slug:` frontmatter entry
+    Frontmatter,
+    /// The title, transliterated to ASCII
+    Title,
+}
+
+impl SlugStrategy {
+    /// Gets the slug of a parsed markdown document.
+    pub fn slug(self, markdown_path: &Path, content: &MarkdownContent) -> String {
+        let slug = match self {
+            SlugStrategy::FileStem => None,
+            SlugStrategy::Frontmatter => content.metadata.get(SLUG_KEY).and_then(|s| slugify(s)),
+            SlugStrategy::Title => content.title.as_deref().and_then(slugify),
+        };
+        slug.unwrap_or_else(|| MermaidProcessor::extract_slug_from_path(markdown_path))
+    }
+
+    /// Gets the slug of a markdown document from its raw file content.
+    ///
+    /// Content that fails to parse falls back to the file stem.
+    pub fn slug_of_file(self, markdown_path: &Path, raw: &[u8]) -> String {
+        if self == SlugStrategy::FileStem {
+            return MermaidProcessor::extract_slug_from_path(markdown_path);
+        }
+        match MarkdownParser::new().parse(&String::from_utf8_lossy(raw)) {
+            Ok(content) => self.slug(markdown_path, &content),
+            Err(_) => MermaidProcessor::extract_slug_from_path(markdown_path),
+        }
+    }
+}
+
+impl std::str::FromStr for SlugStrategy {
+    type Err = WeChatError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "file" | "file_stem" | "file-stem" => Ok(SlugStrategy::FileStem),
+            "frontmatter" => Ok(SlugStrategy::Frontmatter),
+            "title" => Ok(SlugStrategy::Title),
+            _ => Err(WeChatError::config_error(format!(
+                "Unknown slug strategy '{s}', expected 'file_stem', 'frontmatter' or 'title'"
+            ))),
+        }
+    }
+}
+
+/// Turns text into a lowercase ASCII slug of letters, digits and dashes.
+///
+/// Returns `None` if nothing is left, e.g. for text made of punctuation only.
+pub fn slugify(text: &str) -> Option<String> {
+    let ascii = deunicode::deunicode(text).to_lowercase();
+    let slug = ascii
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    (!slug.is_empty()).then_some(slug)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slug_strategies() {
+        let path = Path::new("posts/hello.md");
+        let raw = "---\ntitle: 你好世界\nslug: Greeting Post\n---\n\nText\n";
+        let content = MarkdownParser::new().parse(raw).unwrap();
+
+        assert_eq!(SlugStrategy::FileStem.slug(path, &content), "hello");
+        assert_eq!(
+            SlugStrategy::Frontmatter.slug(path, &content),
+            "greeting-post"
+        );
+        assert_eq!(SlugStrategy::Title.slug(path, &content), "ni-hao-shi-jie");
+        assert_eq!(
+            SlugStrategy::Title.slug_of_file(path, raw.as_bytes()),
+            "ni-hao-shi-jie"
+        );
+
+        // Missing entries fall back to the file stem
+        let content = MarkdownParser::new().parse("Text\n").unwrap();
+        assert_eq!(SlugStrategy::Frontmatter.slug(path, &content), "hello");
+        assert_eq!(SlugStrategy::Title.slug(path, &content), "hello");
+
+        assert_eq!(
+            slugify("  Rust 2024: What's New?  ").unwrap(),
+            "rust-2024-what-s-new"
+        );
+        assert_eq!(slugify("!!!"), None);
+        assert_eq!(
+            "title".parse::<SlugStrategy>().unwrap(),
+            SlugStrategy::Title
+        );
+        assert!("random".parse::<SlugStrategy>().is_err());
+    }
+}
@@ -0,0 +1,578 @@
+//! Local publish state stored alongside the content.
+//!
+//! When enabled through [`UploadOptions::track_state`](crate::UploadOptions::track_state),
+//! the client keeps a `.wechat-pub/state.json` file in the markdown file's directory that
+//! records, per markdown file, the draft it was uploaded to, a hash of the content that
+//! was uploaded and when. This lets a re-run of a failed upload detect that the draft was
+//! in fact created on the previous attempt instead of creating a duplicate, and lets
+//! [`status`] show which files are out of date. Together with the drafts' current
+//! `update_time`, [`ConflictReport`]s also show which drafts were edited in the WeChat
+//! editor since.
+//!
+//! Files are keyed by their file name, or by their slug when the client is configured
+//! with a [`SlugStrategy`] other than the file stem, so an entry follows a renamed file.
+//!
+//! ```json
+//! {
+//!   "files": {
+//!     "article.md": {
+//!       "idempotency_key": "3f1c…",
+//!       "content_hash": "9a0b…",
+//!       "media_id": "MEDIA_ID",
+//!       "thumb_media_id": "THUMB_MEDIA_ID",
+//!       "remote_update_time": 1704067200,
+//!       "pending_since": null,
+//!       "updated_at": "2024-01-01T00:00:00Z"
+//!     }
+//!   }
+//! }
+//! ```
+
+use crate::error::{Result, WeChatError};
+use crate::slug::SlugStrategy;
+#[cfg(feature = "client")]
+use crate::upload::SyncedDraft;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Locks serializing [`PublishState::update`] per content directory.
+static UPDATE_LOCKS: LazyLock<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>> =
+    LazyLock::new(Mutex::default);
+
+/// Directory (relative to the content directory) holding the state file.
+pub const STATE_DIR: &str = ".wechat-pub";
+
+/// Name of the state file inside [`STATE_DIR`].
+pub const STATE_FILE: &str = "state.json";
+
+/// Publish state for all markdown files in one content directory.
+///
+/// A content directory is published to one account: the first tracked upload records
+/// its app ID, and tracked uploads and write-backs from other accounts are rejected,
+/// as the recorded media IDs (and those written back to the frontmatter) only exist in
+/// that account.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PublishState {
+    /// App ID of the account the drafts were uploaded to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_id: Option<String>,
+    /// Entries keyed by [`document_key`]
+    #[serde(default)]
+    pub files: BTreeMap<String, StateEntry>,
+}
+
+/// Publish state for a single markdown file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StateEntry {
+    /// Key derived from the file path and content of the last upload attempt
+    pub idempotency_key: String,
+    /// BLAKE3 hash of the markdown content of the last upload attempt
+    pub content_hash: String,
+    /// Media ID of the draft the file was last uploaded to
+    #[serde(default)]
+    pub media_id: Option<String>,
+    /// Media ID of the cover used by that draft
+    #[serde(default)]
+    pub thumb_media_id: Option<String>,
+    /// The draft's `update_time` on WeChat right after the last upload
+    #[serde(default)]
+    pub remote_update_time: Option<u64>,
+    /// Set while an upload is in flight; left behind if the attempt failed midway
+    #[serde(default)]
+    pub pending_since: Option<DateTime<Utc>>,
+    /// When the last successful upload finished
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl StateEntry {
+    /// The draft as recorded at the last sync, if any.
+    #[cfg(feature = "client")]
+    pub fn synced_draft(&self) -> Option<SyncedDraft> {
+        self.media_id.as_ref().map(|media_id| SyncedDraft {
+            media_id: media_id.clone(),
+            update_time: self.remote_update_time,
+            url: None,
+        })
+    }
+
+    /// Whether the last upload of exactly this content completed.
+    pub fn is_complete_for(&self, idempotency_key: &str) -> bool {
+        self.idempotency_key == idempotency_key
+            && self.pending_since.is_none()
+            && self.media_id.is_some()
+    }
+}
+
+impl PublishState {
+    /// Gets the path of the state file for a content directory.
+    pub fn path(content_dir: &Path) -> PathBuf {
+        content_dir.join(STATE_DIR).join(STATE_FILE)
+    }
+
+    /// Loads the state for a content directory, returning empty state if none exists.
+    pub async fn load(content_dir: &Path) -> Result<Self> {
+        let path = Self::path(content_dir);
+
+        match tokio::fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| WeChatError::file_error(path.display().to_string(), e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(WeChatError::file_error(
+                path.display().to_string(),
+                e.to_string(),
+            )),
+        }
+    }
+
+    /// Saves the state, replacing the state file atomically.
+    pub async fn save(&self, content_dir: &Path) -> Result<()> {
+        let path = Self::path(content_dir);
+        let write_error =
+            |e: std::io::Error| WeChatError::file_error(path.display().to_string(), e.to_string());
+
+        tokio::fs::create_dir_all(content_dir.join(STATE_DIR))
+            .await
+            .map_err(write_error)?;
+
+        let json = serde_json::to_vec_pretty(self)?;
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, json)
+            .await
+            .map_err(write_error)?;
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .map_err(write_error)?;
+
+        Ok(())
+    }
+
+    /// Changes the state of a content directory and saves it.
+    ///
+    /// The state file is reloaded right before the change under a lock per content
+    /// directory, so uploads running concurrently in this process don't overwrite each
+    /// other's entries.
+    pub async fn update(content_dir: &Path, change: impl FnOnce(&mut Self)) -> Result<()> {
+        let lock = {
+            let mut locks = UPDATE_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+            Arc::clone(locks.entry(content_dir.to_path_buf()).or_default())
+        };
+        let _guard = lock.lock().await;
+
+        let mut state = Self::load(content_dir).await?;
+        change(&mut state);
+        state.save(content_dir).await
+    }
+
+    /// Checks that the content directory is published to the account with `app_id`, or
+    /// not tracked for any account yet.
+    pub fn check_account(&self, app_id: &str) -> Result<()> {
+        match &self.app_id {
+            Some(owner) if owner != app_id => Err(WeChatError::config_error(format!(
+                "Content directory is published to app ID {owner}, not {app_id}; \
+                 use a content directory per account"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Gets the entry for a markdown file by its [`document_key`].
+    pub fn entry(&self, key: &str) -> Option<&StateEntry> {
+        self.files.get(key)
+    }
+
+    /// Gets the entry for a markdown file by its [`document_key`], creating an empty one
+    /// if missing.
+    pub fn entry_mut(&mut self, key: &str) -> &mut StateEntry {
+        self.files.entry(key.to_string()).or_default()
+    }
+}
+
+/// Sync status of a markdown file relative to the state file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncStatus {
+    /// Never uploaded with state tracking
+    Untracked,
+    /// Uploaded, and unchanged since
+    UpToDate,
+    /// Uploaded, but the content changed since
+    Modified,
+    /// The last upload attempt did not complete
+    Pending,
+}
+
+/// Sync status of one markdown file.
+#[derive(Debug, Clone)]
+pub struct FileStatus {
+    /// Path of the markdown file
+    pub path: PathBuf,
+    /// Status relative to the last recorded upload
+    pub status: SyncStatus,
+    /// Media ID of the draft the file was last uploaded to
+    pub media_id: Option<String>,
+    /// When the last successful upload finished
+    pub updated_at: Option<DateTime<Utc>>,
+    /// The draft's `update_time` on WeChat right after the last upload
+    pub remote_update_time: Option<u64>,
+}
+
+/// Two-way sync status of a markdown file and its draft.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictStatus {
+    /// Never uploaded with state tracking
+    Untracked,
+    /// The last upload attempt did not complete
+    Pending,
+    /// Neither the file nor the draft changed since the last upload
+    Unchanged,
+    /// The file changed since the last upload; uploading it is safe
+    LocallyModified,
+    /// The draft was edited in the WeChat editor since the last upload
+    RemotelyModified,
+    /// Both the file and the draft changed since the last upload
+    Conflicting,
+    /// The draft no longer exists (deleted or published)
+    RemoteMissing,
+}
+
+impl ConflictStatus {
+    /// Combines the local status of a file with the current `update_time` of its draft
+    /// (`None` if the draft wasn't found) and the one recorded at the last upload.
+    ///
+    /// Without a recorded `update_time` remote edits can't be detected, so the draft is
+    /// assumed unchanged.
+    pub fn new(local: SyncStatus, recorded: Option<u64>, remote: Option<u64>) -> Self {
+        let Some(remote) = remote else {
+            return match local {
+                SyncStatus::Untracked => ConflictStatus::Untracked,
+                SyncStatus::Pending => ConflictStatus::Pending,
+                SyncStatus::UpToDate | SyncStatus::Modified => ConflictStatus::RemoteMissing,
+            };
+        };
+        let remote_changed = recorded.is_some_and(|recorded| recorded != remote);
+
+        match (local, remote_changed) {
+            (SyncStatus::Untracked, _) => ConflictStatus::Untracked,
+            (SyncStatus::Pending, _) => ConflictStatus::Pending,
+            (SyncStatus::UpToDate, false) => ConflictStatus::Unchanged,
+            (SyncStatus::UpToDate, true) => ConflictStatus::RemotelyModified,
+            (SyncStatus::Modified, false) => ConflictStatus::LocallyModified,
+            (SyncStatus::Modified, true) => ConflictStatus::Conflicting,
+        }
+    }
+}
+
+/// Two-way sync status of one markdown file, with the timestamps it is based on.
+#[derive(Debug, Clone)]
+pub struct ConflictReport {
+    /// Path of the markdown file
+    pub path: PathBuf,
+    /// Status of the file and its draft
+    pub status: ConflictStatus,
+    /// Media ID of the draft the file was last uploaded to
+    pub media_id: Option<String>,
+    /// When the last successful upload finished
+    pub updated_at: Option<DateTime<Utc>>,
+    /// The draft's `update_time` recorded at the last upload
+    pub recorded_update_time: Option<u64>,
+    /// The draft's current `update_time`, if it was found
+    pub remote_update_time: Option<u64>,
+}
+
+impl ConflictReport {
+    /// Builds the reports for file statuses given the current `update_time` of drafts by
+    /// media ID.
+    pub fn from_statuses(
+        statuses: Vec<FileStatus>,
+        remote_update_times: &HashMap<String, u64>,
+    ) -> Vec<Self> {
+        statuses
+            .into_iter()
+            .map(|file| {
+                let remote_update_time = file
+                    .media_id
+                    .as_ref()
+                    .and_then(|media_id| remote_update_times.get(media_id))
+                    .copied();
+                Self {
+                    status: ConflictStatus::new(
+                        file.status,
+                        file.remote_update_time,
+                        remote_update_time,
+                    ),
+                    path: file.path,
+                    media_id: file.media_id,
+                    updated_at: file.updated_at,
+                    recorded_update_time: file.remote_update_time,
+                    remote_update_time,
+                }
+            })
+            .collect()
+    }
+}
+
+impl PublishState {
+    /// Computes the sync status of the markdown file stored under `key` with the given
+    /// content.
+    pub fn status_of(&self, key: &str, content: &[u8]) -> SyncStatus {
+        match self.entry(key) {
+            None => SyncStatus::Untracked,
+            Some(entry) if entry.pending_since.is_some() => SyncStatus::Pending,
+            Some(entry) if entry.media_id.is_none() => SyncStatus::Untracked,
+            Some(entry) if entry.content_hash == content_hash(content) => SyncStatus::UpToDate,
+            Some(_) => SyncStatus::Modified,
+        }
+    }
+
+    /// Records a completed upload of `content` from the markdown file stored under `key`
+    /// to draft `media_id`.
+    pub fn record_upload(&mut self, key: &str, content: &[u8], media_id: &str) {
+        let entry = self.entry_mut(key);
+        entry.idempotency_key = idempotency_key(key, content);
+        entry.content_hash = content_hash(content);
+        entry.media_id = Some(media_id.to_string());
+        entry.pending_since = None;
+        entry.updated_at = Some(Utc::now());
+    }
+}
+
+/// Reports the sync status of every markdown file in `content_dir`, sorted by path.
+///
+/// Files are looked up by their [`document_key`] for `strategy`.
+pub async fn status(content_dir: &Path, strategy: SlugStrategy) -> Result<Vec<FileStatus>> {
+    let state = PublishState::load(content_dir).await?;
+    let read_error = |e: std::io::Error| {
+        WeChatError::file_error(content_dir.display().to_string(), e.to_string())
+    };
+
+    let mut statuses = Vec::new();
+    let mut entries = tokio::fs::read_dir(content_dir).await.map_err(read_error)?;
+
+    while let Some(dir_entry) = entries.next_entry().await.map_err(read_error)? {
+        let path = dir_entry.path();
+        if !path.is_file() || !crate::utils::is_markdown_file(&path) {
+            continue;
+        }
+
+        let content = tokio::fs::read(&path)
+            .await
+            .map_err(|e| WeChatError::file_error(path.display().to_string(), e.to_string()))?;
+        let key = document_key(&path, &content, strategy);
+        let entry = state.entry(&key);
+
+        statuses.push(FileStatus {
+            status: state.status_of(&key, &content),
+            media_id: entry.and_then(|entry| entry.media_id.clone()),
+            updated_at: entry.and_then(|entry| entry.updated_at),
+            remote_update_time: entry.and_then(|entry| entry.remote_update_time),
+            path,
+        });
+    }
+
+    statuses.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(statuses)
+}
+
+/// Computes the BLAKE3 hash of markdown content.
+pub fn content_hash(content: &[u8]) -> String {
+    blake3::hash(content).to_hex().to_string()
+}
+
+/// Key under which a markdown file with the given content is stored: its file name for
+/// [`SlugStrategy::FileStem`], and its slug otherwise.
+pub fn document_key(markdown_path: &Path, content: &[u8], strategy: SlugStrategy) -> String {
+    match strategy {
+        SlugStrategy::FileStem => markdown_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| markdown_path.display().to_string()),
+        _ => strategy.slug_of_file(markdown_path, content),
+    }
+}
+
+/// Computes the idempotency key for uploading `content` from the markdown file stored
+/// under `key`.
+pub fn idempotency_key(key: &str, content: &[u8]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(key.as_bytes());
+    hasher.update(&[0]);
+    hasher.update(content);
+    hasher.finalize().to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idempotency_key() {
+        let file_key =
+            |path: &str| document_key(Path::new(path), b"# Hello", SlugStrategy::FileStem);
+        let key = idempotency_key(&file_key("posts/article.md"), b"# Hello");
+
+        assert_eq!(key, idempotency_key(&file_key("article.md"), b"# Hello"));
+        assert_ne!(
+            key,
+            idempotency_key(&file_key("posts/article.md"), b"# Hello, world")
+        );
+        assert_ne!(key, idempotency_key(&file_key("other.md"), b"# Hello"));
+    }
+
+    #[tokio::test]
+    async fn test_directory_status() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        for name in ["a.md", "b.md", "c.md", "d.md"] {
+            std::fs::write(dir.join(name), format!("# {name}")).unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), "not markdown").unwrap();
+
+        let mut state = PublishState::default();
+        state.record_upload("a.md", b"# a.md", "media_a");
+        state.record_upload("b.md", b"# old content", "media_b");
+        state.entry_mut("c.md").pending_since = Some(Utc::now());
+        state.save(dir).await.unwrap();
+
+        let statuses = status(dir, SlugStrategy::FileStem).await.unwrap();
+        let summary: Vec<_> = statuses
+            .iter()
+            .map(|s| (s.path.file_name().unwrap().to_str().unwrap(), s.status))
+            .collect();
+
+        assert_eq!(
+            summary,
+            [
+                ("a.md", SyncStatus::UpToDate),
+                ("b.md", SyncStatus::Modified),
+                ("c.md", SyncStatus::Pending),
+                ("d.md", SyncStatus::Untracked),
+            ]
+        );
+        assert_eq!(statuses[0].media_id.as_deref(), Some("media_a"));
+        assert!(statuses[0].updated_at.is_some());
+
+        // Keyed by title, the entry follows the file when it is renamed
+        let mut state = PublishState::default();
+        state.record_upload("a-md", b"# a.md", "media_a");
+        state.save(dir).await.unwrap();
+        std::fs::rename(dir.join("a.md"), dir.join("renamed.md")).unwrap();
+        let statuses = status(dir, SlugStrategy::Title).await.unwrap();
+        assert_eq!(statuses[3].path.file_name().unwrap(), "renamed.md");
+        assert_eq!(statuses[3].status, SyncStatus::UpToDate);
+        assert_eq!(statuses[0].status, SyncStatus::Untracked);
+    }
+
+    #[test]
+    fn test_conflict_report() {
+        let file = |name: &str, status, media_id: Option<&str>| FileStatus {
+            path: PathBuf::from(name),
+            status,
+            media_id: media_id.map(str::to_string),
+            updated_at: None,
+            remote_update_time: media_id.map(|_| 100),
+        };
+        let statuses = vec![
+            file("a.md", SyncStatus::UpToDate, Some("media_a")),
+            file("b.md", SyncStatus::Modified, Some("media_b")),
+            file("c.md", SyncStatus::UpToDate, Some("media_c")),
+            file("d.md", SyncStatus::Modified, Some("media_d")),
+            file("e.md", SyncStatus::UpToDate, Some("media_e")),
+            file("f.md", SyncStatus::Untracked, None),
+        ];
+        let remote = HashMap::from([
+            ("media_a".to_string(), 100),
+            ("media_b".to_string(), 100),
+            ("media_c".to_string(), 200),
+            ("media_d".to_string(), 200),
+        ]);
+
+        let reports = ConflictReport::from_statuses(statuses, &remote);
+        let summary: Vec<_> = reports.iter().map(|r| r.status).collect();
+        assert_eq!(
+            summary,
+            [
+                ConflictStatus::Unchanged,
+                ConflictStatus::LocallyModified,
+                ConflictStatus::RemotelyModified,
+                ConflictStatus::Conflicting,
+                ConflictStatus::RemoteMissing,
+                ConflictStatus::Untracked,
+            ]
+        );
+        assert_eq!(reports[2].recorded_update_time, Some(100));
+        assert_eq!(reports[2].remote_update_time, Some(200));
+
+        // Without a recorded update_time remote edits go unnoticed
+        assert_eq!(
+            ConflictStatus::new(SyncStatus::UpToDate, None, Some(200)),
+            ConflictStatus::Unchanged
+        );
+    }
+
+    #[tokio::test]
+    async fn test_state_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let markdown_path = temp_dir.path().join("article.md");
+        let file_key = document_key(&markdown_path, b"content", SlugStrategy::FileStem);
+        assert_eq!(file_key, "article.md");
+
+        // Missing state file loads as empty state
+        let mut state = PublishState::load(temp_dir.path()).await.unwrap();
+        assert!(state.entry(&file_key).is_none());
+
+        let key = idempotency_key(&file_key, b"content");
+        let entry = state.entry_mut(&file_key);
+        entry.idempotency_key = key.clone();
+        entry.pending_since = Some(Utc::now());
+        assert!(!entry.is_complete_for(&key));
+
+        entry.media_id = Some("media_1".to_string());
+        entry.pending_since = None;
+        assert!(entry.is_complete_for(&key));
+
+        state.save(temp_dir.path()).await.unwrap();
+        assert!(PublishState::path(temp_dir.path()).exists());
+
+        let loaded = PublishState::load(temp_dir.path()).await.unwrap();
+        assert_eq!(loaded, state);
+        assert!(loaded.entry(&file_key).unwrap().is_complete_for(&key));
+    }
+
+    #[test]
+    fn test_check_account() {
+        let mut state = PublishState::default();
+        assert!(state.check_account("wx_a").is_ok());
+
+        state.app_id = Some("wx_a".to_string());
+        assert!(state.check_account("wx_a").is_ok());
+        assert!(matches!(
+            state.check_account("wx_b"),
+            Err(WeChatError::Config { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_updates_keep_every_entry() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let updates: Vec<_> = (0..16)
+            .map(|i| {
+                let content_dir = temp_dir.path().to_path_buf();
+                tokio::spawn(async move {
+                    PublishState::update(&content_dir, |state| {
+                        state.record_upload(&format!("article-{i}.md"), b"content", "media")
+                    })
+                    .await
+                })
+            })
+            .collect();
+        for update in updates {
+            update.await.unwrap().unwrap();
+        }
+
+        let state = PublishState::load(temp_dir.path()).await.unwrap();
+        assert_eq!(state.files.len(), 16);
+    }
+}
@@ -0,0 +1,233 @@
+//! Subscription messages to single followers.
+//!
+//! Unlike [broadcasts](crate::broadcast), which send articles to many followers, a
+//! subscription message (订阅通知) is a short notification built from a template the
+//! follower subscribed to, e.g. "new article published". A [`SubscribeMessenger`] sends
+//! them (`message/subscribe/bizsend`) and lists the account's templates with the names
+//! of their fields. [`WeChatClient`](crate::WeChatClient) exposes it as
+//! [`send_subscribe_message`](crate::WeChatClient::send_subscribe_message) and
+//! [`list_subscribe_templates`](crate::WeChatClient::list_subscribe_templates):
+//!
+//! ```rust,no_run
+//! use wechat_pub_rs::subscribe::SubscribeMessage;
+//!
+//! # async fn example(client: wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+//! let message = SubscribeMessage::new("oXyz_follower_openid", "template_id")
+//!     .field("thing1", "Rust async in practice")
+//!     .field("time2", "2024-05-01 20:00")
+//!     .page("https://mp.weixin.qq.com/s/abc");
+//! client.send_subscribe_message(&message).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! WeChat only delivers a message if the follower subscribed to its template, and
+//! limits the length of each field by its type (e.g. 20 characters for `thing` fields).
+
+use crate::auth::TokenManager;
+use crate::endpoints;
+use crate::error::Result;
+use crate::http::{self, WeChatHttpClient, WeChatResponse};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+/// A subscription message to a follower.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscribeMessage {
+    /// `openid` of the follower
+    pub to_user: String,
+    /// ID of the template, see [`SubscribeMessenger::templates`]
+    pub template_id: String,
+    /// Template field values by field name, e.g. `thing1`
+    pub data: BTreeMap<String, String>,
+    /// URL opened when the message is tapped
+    pub page: Option<String>,
+    /// Mini program page opened when the message is tapped, instead of `page`
+    pub mini_program: Option<MiniProgramPage>,
+}
+
+/// A page of a mini program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MiniProgramPage {
+    /// App ID of the mini program
+    pub app_id: String,
+    /// Path of the page, e.g. `pages/index?id=1`
+    pub page_path: String,
+}
+
+impl SubscribeMessage {
+    /// Creates a message without field values.
+    pub fn new(to_user: impl Into<String>, template_id: impl Into<String>) -> Self {
+        Self {
+            to_user: to_user.into(),
+            template_id: template_id.into(),
+            data: BTreeMap::new(),
+            page: None,
+            mini_program: None,
+        }
+    }
+
+    /// Sets the value of a template field, e.g. `thing1`.
+    pub fn field(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.data.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets the URL opened when the message is tapped.
+    pub fn page(mut self, url: impl Into<String>) -> Self {
+        self.page = Some(url.into());
+        self
+    }
+
+    /// Sets the mini program page opened when the message is tapped.
+    pub fn mini_program(mut self, app_id: impl Into<String>, page_path: impl Into<String>) -> Self {
+        self.mini_program = Some(MiniProgramPage {
+            app_id: app_id.into(),
+            page_path: page_path.into(),
+        });
+        self
+    }
+}
+
+/// A subscription message template of the account.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SubscribeTemplate {
+    /// ID of the template
+    #[serde(rename = "priTmplId")]
+    pub template_id: String,
+    /// Title of the template
+    #[serde(default)]
+    pub title: String,
+    /// Layout of the message, naming its fields, e.g. `文章标题:{{thing1.DATA}}`
+    #[serde(default)]
+    pub content: String,
+    /// Example message
+    #[serde(default)]
+    pub example: String,
+    /// 2 for one-time, 3 for long-term subscriptions
+    #[serde(rename = "type", default)]
+    pub kind: u32,
+}
+
+/// Template list response (`wxaapi/newtmpl/gettemplate` endpoint).
+#[derive(Debug, Deserialize)]
+struct TemplatesResponse {
+    #[serde(default)]
+    data: Vec<SubscribeTemplate>,
+}
+
+/// Sends subscription messages.
+#[derive(Debug)]
+pub struct SubscribeMessenger {
+    http_client: Arc<WeChatHttpClient>,
+    token_manager: Arc<TokenManager>,
+}
+
+impl SubscribeMessenger {
+    /// Creates a new subscription messenger.
+    pub fn new(http_client: Arc<WeChatHttpClient>, token_manager: Arc<TokenManager>) -> Self {
+        Self {
+            http_client,
+            token_manager,
+        }
+    }
+
+    /// Sends a message to its follower.
+    pub async fn send(&self, message: &SubscribeMessage) -> Result<()> {
+        // Openids identify followers, so they stay out of the info logs
+        info!("Sending subscription message {}", message.template_id);
+        debug!("Subscription message recipient: {}", message.to_user);
+
+        let request = send_request(message);
+        let access_token = self.token_manager.get_access_token().await?;
+        let response = self
+            .http_client
+            .post_json_with_token(endpoints::SUBSCRIBE_SEND.path, &access_token, &request)
+            .await?;
+
+        let send_response: WeChatResponse<serde_json::Value> = http::read_json(response).await?;
+        send_response.into_result()?;
+        Ok(())
+    }
+
+    /// Lists the account's subscription message templates.
+    pub async fn templates(&self) -> Result<Vec<SubscribeTemplate>> {
+        let access_token = self.token_manager.get_access_token().await?;
+        let response = self
+            .http_client
+            .get_with_token(endpoints::SUBSCRIBE_TEMPLATES.path, &access_token)
+            .await?;
+
+        let templates: WeChatResponse<TemplatesResponse> = http::read_json(response).await?;
+        Ok(templates.into_result()?.data)
+    }
+}
+
+/// Builds the request body of a message.
+fn send_request(message: &SubscribeMessage) -> serde_json::Value {
+    let data: serde_json::Map<_, _> = message
+        .data
+        .iter()
+        .map(|(name, value)| (name.clone(), serde_json::json!({ "value": value })))
+        .collect();
+    let mut request = serde_json::json!({
+        "touser": message.to_user,
+        "template_id": message.template_id,
+        "data": data,
+    });
+    if let Some(page) = &message.page {
+        request["page"] = page.clone().into();
+    }
+    if let Some(mini_program) = &message.mini_program {
+        request["miniprogram"] = serde_json::json!({
+            "appid": mini_program.app_id,
+            "pagepath": mini_program.page_path,
+        });
+    }
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_request() {
+        let message = SubscribeMessage::new("o1", "tmpl")
+            .field("thing1", "New post")
+            .field("time2", "2024-05-01 20:00")
+            .page("https://mp.weixin.qq.com/s/abc");
+        assert_eq!(
+            send_request(&message),
+            serde_json::json!({
+                "touser": "o1",
+                "template_id": "tmpl",
+                "page": "https://mp.weixin.qq.com/s/abc",
+                "data": {
+                    "thing1": { "value": "New post" },
+                    "time2": { "value": "2024-05-01 20:00" },
+                },
+            })
+        );
+
+        let request = send_request(
+            &SubscribeMessage::new("o1", "tmpl").mini_program("wxapp", "pages/post?id=1"),
+        );
+        assert_eq!(
+            request["miniprogram"],
+            serde_json::json!({ "appid": "wxapp", "pagepath": "pages/post?id=1" })
+        );
+        assert!(request.get("page").is_none());
+
+        let templates: WeChatResponse<TemplatesResponse> = serde_json::from_str(
+            r#"{"errcode":0,"errmsg":"ok","data":[{"priTmplId":"tmpl","title":"文章更新通知",
+            "content":"文章标题:{{thing1.DATA}}\n","example":"文章标题:Rust\n","type":3}]}"#,
+        )
+        .unwrap();
+        let templates = templates.into_result().unwrap().data;
+        assert_eq!(templates[0].template_id, "tmpl");
+        assert_eq!(templates[0].kind, 3);
+    }
+}
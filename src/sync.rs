@@ -0,0 +1,301 @@
+//! Comparing a directory of markdown files with the drafts on WeChat.
+//!
+//! Unlike the [state file](crate::state), which only knows about uploads made with state
+//! tracking, [`WeChatClient::sync_dir`] looks at what is actually on WeChat: it renders
+//! every markdown file in a directory, lists the drafts and matches each file to a draft
+//! by the frontmatter `wechat_media_id`, then by title, then by source URL (which
+//! contains the document slug when it is configured with `{slug}`). Matched files are
+//! [diffed](crate::diff) against their draft, so drafts only restyled by the WeChat
+//! editor count as unchanged:
+//!
+//! ```rust,no_run
+//! # async fn example(client: wechat_pub_rs::WeChatClient) -> wechat_pub_rs::Result<()> {
+//! use wechat_pub_rs::sync::SyncOptions;
+//!
+//! let report = client.sync_dir("articles", SyncOptions::default()).await?;
+//! for entry in &report.entries {
+//!     println!("{:?} {}", entry.state, entry.title);
+//! }
+//!
+//! // Upload new files and update the drafts of changed ones
+//! let report = client
+//!     .sync_dir("articles", SyncOptions::default().push(true))
+//!     .await?;
+//! println!("Pushed {} files", report.pushed.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::client::UploadOptions;
+use crate::diff::{self, DraftDiff};
+use crate::error::WeChatError;
+use crate::upload::DraftInfo;
+use std::path::PathBuf;
+
+/// How a markdown file compares with the drafts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncState {
+    /// No draft matches the file
+    New,
+    /// The file renders differently from its draft
+    Changed,
+    /// The file renders like its draft
+    Unchanged,
+    /// A draft no file in the directory matches
+    RemoteOnly,
+}
+
+/// A markdown file or a draft compared by [`WeChatClient::sync_dir`].
+#[derive(Debug, Clone)]
+pub struct SyncEntry {
+    /// Path of the markdown file, `None` for remote-only drafts
+    pub path: Option<PathBuf>,
+    /// Title of the article, or of the draft's first article for remote-only drafts
+    pub title: String,
+    /// Media ID of the matching draft, `None` for new files
+    pub media_id: Option<String>,
+    /// How the file compares with the drafts
+    pub state: SyncState,
+    /// Differences between the file and its draft, for matched files
+    pub diff: Option<DraftDiff>,
+}
+
+/// Options of [`WeChatClient::sync_dir`].
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// Options the files are rendered (and pushed) with
+    pub upload: UploadOptions,
+    /// Whether to upload new files and update the drafts of changed ones
+    pub push: bool,
+}
+
+impl SyncOptions {
+    /// Sets the options the files are rendered and pushed with.
+    pub fn upload_options(mut self, options: UploadOptions) -> Self {
+        self.upload = options;
+        self
+    }
+
+    /// Sets whether to upload new files and update the drafts of changed ones.
+    ///
+    /// Unchanged files and remote-only drafts are left alone.
+    pub fn push(mut self, push: bool) -> Self {
+        self.push = push;
+        self
+    }
+}
+
+/// Outcome of [`WeChatClient::sync_dir`].
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    /// The markdown files sorted by path, followed by the remote-only drafts in list
+    /// order
+    pub entries: Vec<SyncEntry>,
+    /// Files that were pushed, with the media ID of their draft
+    pub pushed: Vec<(PathBuf, String)>,
+    /// Files that couldn't be rendered or pushed, with their errors
+    pub failed: Vec<(PathBuf, WeChatError)>,
+}
+
+impl SyncReport {
+    /// Gets the entries in a state.
+    pub fn with_state(&self, state: SyncState) -> impl Iterator<Item = &SyncEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.state == state)
+    }
+
+    /// Whether every file matches its draft and every draft has a file.
+    pub fn is_in_sync(&self) -> bool {
+        self.failed.is_empty()
+            && self
+                .entries
+                .iter()
+                .all(|entry| entry.state == SyncState::Unchanged)
+    }
+}
+
+/// A rendered markdown file to compare with the drafts.
+#[derive(Debug)]
+pub(crate) struct LocalArticle {
+    pub path: PathBuf,
+    pub title: String,
+    pub source_url: Option<String>,
+    /// Draft from the frontmatter `wechat_media_id`
+    pub known_media_id: Option<String>,
+    pub html: String,
+}
+
+/// Matches rendered files with drafts and compares them.
+///
+/// A draft matches at most one file; files are matched in order.
+pub(crate) fn compare(locals: Vec<LocalArticle>, drafts: &[DraftInfo]) -> Vec<SyncEntry> {
+    let mut matched = vec![false; drafts.len()];
+    let mut entries = Vec::new();
+
+    for local in locals {
+        let found = find_draft(&local, drafts, &matched);
+        let entry = match found {
+            Some(index) => {
+                matched[index] = true;
+                let draft = &drafts[index];
+                let diff = diff::diff(&local.html, &draft_html(draft));
+                SyncEntry {
+                    path: Some(local.path),
+                    title: local.title,
+                    media_id: Some(draft.media_id.clone()),
+                    state: if diff.is_identical() {
+                        SyncState::Unchanged
+                    } else {
+                        SyncState::Changed
+                    },
+                    diff: Some(diff),
+                }
+            }
+            None => SyncEntry {
+                path: Some(local.path),
+                title: local.title,
+                media_id: None,
+                state: SyncState::New,
+                diff: None,
+            },
+        };
+        entries.push(entry);
+    }
+
+    entries.extend(
+        drafts
+            .iter()
+            .zip(matched)
+            .filter(|(_, matched)| !matched)
+            .map(|(draft, _)| SyncEntry {
+                path: None,
+                title: draft_title(draft).to_string(),
+                media_id: Some(draft.media_id.clone()),
+                state: SyncState::RemoteOnly,
+                diff: None,
+            }),
+    );
+    entries
+}
+
+/// Gets the HTML of a draft, with the articles of a multi-article draft joined.
+pub(crate) fn draft_html(draft: &DraftInfo) -> String {
+    draft
+        .content
+        .news_item
+        .iter()
+        .map(|article| article.content.as_str())
+        .collect()
+}
+
+/// Gets the title of a draft's first article.
+fn draft_title(draft: &DraftInfo) -> &str {
+    draft
+        .content
+        .news_item
+        .first()
+        .map(|article| article.title.as_str())
+        .unwrap_or_default()
+}
+
+/// Finds the unmatched draft of a file: by media ID, then title, then source URL.
+fn find_draft(local: &LocalArticle, drafts: &[DraftInfo], matched: &[bool]) -> Option<usize> {
+    let find = |predicate: &dyn Fn(&DraftInfo) -> bool| {
+        drafts
+            .iter()
+            .enumerate()
+            .find(|(index, draft)| !matched[*index] && predicate(draft))
+            .map(|(index, _)| index)
+    };
+
+    local
+        .known_media_id
+        .as_deref()
+        .and_then(|media_id| find(&|draft| draft.media_id == media_id))
+        .or_else(|| find(&|draft| draft_title(draft) == local.title))
+        .or_else(|| {
+            let source_url = local.source_url.as_deref().filter(|url| !url.is_empty())?;
+            find(&|draft| {
+                draft
+                    .content
+                    .news_item
+                    .iter()
+                    .any(|article| article.content_source_url.as_deref() == Some(source_url))
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draft(media_id: &str, title: &str, source_url: &str, content: &str) -> DraftInfo {
+        serde_json::from_value(serde_json::json!({
+            "media_id": media_id,
+            "content": {
+                "news_item": [{
+                    "title": title,
+                    "content": content,
+                    "content_source_url": source_url
+                }]
+            },
+            "update_time": 1
+        }))
+        .unwrap()
+    }
+
+    fn local(name: &str, title: &str, media_id: Option<&str>, html: &str) -> LocalArticle {
+        LocalArticle {
+            path: PathBuf::from(name),
+            title: title.to_string(),
+            source_url: Some(format!("https://blog.example.com/{name}")),
+            known_media_id: media_id.map(str::to_string),
+            html: html.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let drafts = [
+            draft("M1", "Same", "", "<section><h2>A</h2><p>Text</p></section>"),
+            draft("M2", "Old title", "", "<h2>A</h2><p>Old</p>"),
+            draft("M3", "Renamed", "https://blog.example.com/c.md", "<p>C</p>"),
+            draft("M4", "Orphan", "", "<p>D</p>"),
+        ];
+        let locals = vec![
+            // Restyled by the editor only
+            local("a.md", "Same", None, "<h2>A</h2><p>Text</p>"),
+            // Matched by media ID despite the new title
+            local("b.md", "New title", Some("M2"), "<h2>A</h2><p>New</p>"),
+            // Matched by source URL
+            local("c.md", "Retitled", None, "<p>C</p>"),
+            local("e.md", "Fresh", None, "<p>E</p>"),
+        ];
+
+        let entries = compare(locals, &drafts);
+        let states: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.title.as_str(), entry.media_id.as_deref(), entry.state))
+            .collect();
+        assert_eq!(
+            states,
+            [
+                ("Same", Some("M1"), SyncState::Unchanged),
+                ("New title", Some("M2"), SyncState::Changed),
+                ("Retitled", Some("M3"), SyncState::Unchanged),
+                ("Fresh", None, SyncState::New),
+                ("Orphan", Some("M4"), SyncState::RemoteOnly),
+            ]
+        );
+        assert_eq!(entries[1].diff.as_ref().unwrap().changes.len(), 1);
+        assert!(entries[4].path.is_none());
+
+        let report = SyncReport {
+            entries,
+            ..Default::default()
+        };
+        assert_eq!(report.with_state(SyncState::Unchanged).count(), 2);
+        assert!(!report.is_in_sync());
+    }
+}
@@ -0,0 +1,274 @@
+//! Temporary files written while rendering and uploading.
+//!
+//! Mermaid rendering writes the chart code to a file for mermaid-cli, remote images are
+//! downloaded to files before they are uploaded, and [`ArticlePipeline`]s keep their
+//! generated charts in directories of their own. All of them go to one directory, the
+//! [`TEMP_SUBDIR`] directory in the system temp directory unless [`TempConfig::dir`]
+//! says otherwise, under names starting with [`TEMP_PREFIX`].
+//!
+//! The [`CleanupPolicy`] decides whether they are removed once they are done with, e.g.
+//! keeping the files of failed uploads for debugging. If [`TempConfig::max_age_hours`]
+//! is set, files left behind by crashed or killed processes, or kept by the policy, are
+//! removed in the background when a client is created once they are older than that:
+//!
+//! ```rust
+//! use wechat_pub_rs::config::{Config, TempConfig};
+//! use wechat_pub_rs::temp::CleanupPolicy;
+//!
+//! let config = Config::builder()
+//!     .temp(TempConfig::builder()
+//!         .dir("/var/lib/publisher/tmp")
+//!         .cleanup(CleanupPolicy::OnSuccess)
+//!         .max_age_hours(48)
+//!         .build())
+//!     .build();
+//! ```
+//!
+//! [`ArticlePipeline`]: crate::pipeline::ArticlePipeline
+//! [`TempConfig::dir`]: crate::config::TempConfig::dir
+//! [`TempConfig::max_age_hours`]: crate::config::TempConfig::max_age_hours
+
+use crate::config::TempConfig;
+use crate::error::{Result, WeChatError};
+use crate::mermaid::MERMAID_TEMP_DIR;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+/// Prefix of every temporary file and directory created by this crate.
+pub const TEMP_PREFIX: &str = "wechat-pub-";
+
+/// Directory in the system temp directory holding the temporary files when
+/// [`TempConfig::dir`](crate::config::TempConfig::dir) isn't set.
+pub const TEMP_SUBDIR: &str = "wechat-pub";
+
+/// When temporary files are removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanupPolicy {
+    /// As soon as they are done with
+    #[default]
+    Always,
+    /// Only when the step that wrote them succeeded, keeping them for debugging otherwise
+    OnSuccess,
+    /// Never; only the age-based cleanup removes them
+    Never,
+}
+
+impl CleanupPolicy {
+    /// Whether files are removed after a step that succeeded or failed.
+    pub fn removes(self, succeeded: bool) -> bool {
+        match self {
+            CleanupPolicy::Always => true,
+            CleanupPolicy::OnSuccess => succeeded,
+            CleanupPolicy::Never => false,
+        }
+    }
+}
+
+impl std::str::FromStr for CleanupPolicy {
+    type Err = WeChatError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "always" => Ok(CleanupPolicy::Always),
+            "on_success" | "on-success" => Ok(CleanupPolicy::OnSuccess),
+            "never" => Ok(CleanupPolicy::Never),
+            _ => Err(WeChatError::config_error(format!(
+                "Unknown cleanup policy '{s}', expected 'always', 'on_success' or 'never'"
+            ))),
+        }
+    }
+}
+
+/// Where temporary files are created and when they are removed.
+#[derive(Debug, Clone, Default)]
+pub struct TempFiles {
+    dir: Option<PathBuf>,
+    cleanup: CleanupPolicy,
+}
+
+impl TempFiles {
+    /// Creates temporary files as configured.
+    pub fn new(config: &TempConfig) -> Self {
+        Self {
+            dir: config.dir.clone(),
+            cleanup: config.cleanup,
+        }
+    }
+
+    /// Gets the directory temporary files are created in.
+    pub fn dir(&self) -> PathBuf {
+        self.dir
+            .clone()
+            .unwrap_or_else(|| std::env::temp_dir().join(TEMP_SUBDIR))
+    }
+
+    /// Gets the cleanup policy.
+    pub fn cleanup(&self) -> CleanupPolicy {
+        self.cleanup
+    }
+
+    /// Gets a new unique path for a temporary file, e.g. `wechat-pub-image-<uuid>.tmp`,
+    /// creating the temporary directory if needed.
+    pub async fn file(&self, kind: &str, extension: &str) -> Result<PathBuf> {
+        let dir = self.ensure_dir().await?;
+        Ok(dir.join(format!(
+            "{TEMP_PREFIX}{kind}-{}.{extension}",
+            uuid::Uuid::new_v4()
+        )))
+    }
+
+    /// Creates a new unique temporary directory, e.g. `wechat-pub-pipeline-<uuid>`.
+    pub fn create_dir(&self, kind: &str) -> Result<PathBuf> {
+        let dir = self
+            .dir()
+            .join(format!("{TEMP_PREFIX}{kind}-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| WeChatError::file_error(dir.display().to_string(), e.to_string()))?;
+        Ok(dir)
+    }
+
+    /// Removes a temporary file or directory if the cleanup policy says so.
+    ///
+    /// Failures are logged rather than returned, as this mostly runs in `Drop`.
+    pub fn release(&self, path: &Path, succeeded: bool) {
+        if !self.cleanup.removes(succeeded) {
+            debug!("Keeping temporary file: {}", path.display());
+            return;
+        }
+        let removed = if path.is_dir() {
+            std::fs::remove_dir_all(path)
+        } else {
+            std::fs::remove_file(path)
+        };
+        if let Err(e) = removed
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            warn!("Failed to remove temporary file {}: {e}", path.display());
+        }
+    }
+
+    /// Removes temporary files and directories of this crate that were last modified
+    /// more than `max_age` ago, and returns how many were removed.
+    ///
+    /// The shared directory of [`MermaidOutput::Temp`](crate::mermaid::MermaidOutput::Temp)
+    /// holds generated charts rather than temporary files and is left alone.
+    pub fn collect_garbage(&self, max_age: Duration) -> Result<usize> {
+        let dir = self.dir();
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => {
+                return Err(WeChatError::file_error(
+                    dir.display().to_string(),
+                    e.to_string(),
+                ));
+            }
+        };
+
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !name.starts_with(TEMP_PREFIX) || name == MERMAID_TEMP_DIR {
+                continue;
+            }
+            let expired = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .is_some_and(|age| age >= max_age);
+            if !expired {
+                continue;
+            }
+
+            let path = entry.path();
+            let result = if path.is_dir() {
+                std::fs::remove_dir_all(&path)
+            } else {
+                std::fs::remove_file(&path)
+            };
+            match result {
+                Ok(()) => removed += 1,
+                Err(e) => warn!(
+                    "Failed to remove stale temporary file {}: {e}",
+                    path.display()
+                ),
+            }
+        }
+
+        if removed > 0 {
+            debug!(
+                "Removed {removed} stale temporary files from {}",
+                dir.display()
+            );
+        }
+        Ok(removed)
+    }
+
+    async fn ensure_dir(&self) -> Result<PathBuf> {
+        let dir = self.dir();
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .map_err(|e| WeChatError::file_error(dir.display().to_string(), e.to_string()))?;
+        Ok(dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_temp_files() {
+        let root = tempfile::TempDir::new().unwrap();
+        let config = TempConfig {
+            dir: Some(root.path().join("tmp")),
+            cleanup: CleanupPolicy::OnSuccess,
+            max_age_hours: None,
+        };
+        let temp = TempFiles::new(&config);
+
+        let file = temp.file("image", "tmp").await.unwrap();
+        assert!(file.starts_with(root.path().join("tmp")));
+        assert!(
+            file.file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("wechat-pub-image-")
+        );
+
+        // Failed steps keep their files under `OnSuccess`
+        std::fs::write(&file, b"data").unwrap();
+        temp.release(&file, false);
+        assert!(file.exists());
+        temp.release(&file, true);
+        assert!(!file.exists());
+
+        let dir = temp.create_dir("pipeline").unwrap();
+        std::fs::write(dir.join("chart.png"), b"png").unwrap();
+        std::fs::create_dir_all(temp.dir().join(MERMAID_TEMP_DIR)).unwrap();
+        std::fs::write(temp.dir().join("unrelated.txt"), b"keep").unwrap();
+        assert_eq!(temp.collect_garbage(Duration::from_secs(3600)).unwrap(), 0);
+        assert_eq!(temp.collect_garbage(Duration::ZERO).unwrap(), 1);
+        assert!(!dir.exists());
+        assert!(temp.dir().join(MERMAID_TEMP_DIR).exists());
+        assert!(temp.dir().join("unrelated.txt").exists());
+
+        assert_eq!(
+            "on-success".parse::<CleanupPolicy>().unwrap(),
+            CleanupPolicy::OnSuccess
+        );
+        assert!("sometimes".parse::<CleanupPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_default_dir_is_crate_owned() {
+        let temp = TempFiles::new(&TempConfig::default());
+        assert_eq!(temp.dir(), std::env::temp_dir().join(TEMP_SUBDIR));
+    }
+}
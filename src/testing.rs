@@ -0,0 +1,253 @@
+//! Snapshot-testing utilities for themes (requires the `testing` feature).
+//!
+//! Custom themes are plain CSS, so a changed selector or a dependency update can silently
+//! change how articles look. These helpers render a canonical markdown fixture
+//! ([`FIXTURE_MARKDOWN`]) that exercises every element themes usually style, and compare
+//! the output against an HTML snapshot stored with the tests.
+//!
+//! Set the [`UPDATE_SNAPSHOTS_ENV`] environment variable to `1` to write missing snapshots
+//! and accept changed output; otherwise a missing snapshot fails the check like a changed
+//! one, so a mistyped path or a snapshot left out of version control isn't mistaken for
+//! a pass.
+//!
+//! ```rust,no_run
+//! use wechat_pub_rs::testing;
+//! use wechat_pub_rs::theme::{ThemeManager, ThemeTemplate};
+//!
+//! let mut manager = ThemeManager::new();
+//! let css = std::fs::read_to_string("themes/my-theme.css").unwrap();
+//! manager.add_theme(
+//!     "my-theme".to_string(),
+//!     ThemeTemplate::new(css, String::new(), "my-theme".to_string()),
+//! );
+//!
+//! testing::assert_theme_snapshot(&manager, "my-theme", "tests/snapshots/my-theme.html");
+//! ```
+
+use crate::error::{Result, WeChatError};
+use crate::highlight::DEFAULT_HIGHLIGHT_THEME;
+use crate::theme::ThemeManager;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Environment variable that makes snapshot checks write snapshots that are missing or
+/// differ.
+pub const UPDATE_SNAPSHOTS_ENV: &str = "WECHAT_UPDATE_SNAPSHOTS";
+
+/// Markdown rendered by [`render_fixture`], covering the elements themes style.
+pub const FIXTURE_MARKDOWN: &str = r#"# Heading 1
+
+Paragraph with **bold**, *italic*, ~~strikethrough~~, `inline code` and a
+[link](https://example.com). "Smart quotes" -- and dashes.
+
+## Heading 2
+
+### Heading 3
+
+#### Heading 4
+
+> A blockquote
+>
+> > with a nested quote
+
+- Unordered item
+  - Nested item
+- Another item
+
+1. Ordered item
+2. Another item
+
+- [x] Done task
+- [ ] Open task
+
+```rust
+fn main() {
+    println!("Hello, WeChat!");
+}
+```
+
+| Left | Center | Right |
+| :--- | :----: | ----: |
+| a    |   b    |     c |
+
+![Image](https://example.com/image.png)
+
+Footnote reference[^1].
+
+---
+
+[^1]: The footnote.
+"#;
+
+/// Result of comparing output against a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotStatus {
+    /// The output matches the snapshot
+    Matched,
+    /// The snapshot didn't exist and was written (see [`UPDATE_SNAPSHOTS_ENV`])
+    Created,
+    /// The snapshot doesn't exist
+    Missing,
+    /// The snapshot differed and was overwritten (see [`UPDATE_SNAPSHOTS_ENV`])
+    Updated,
+    /// The output differs from the snapshot
+    Mismatch {
+        /// First differing line (1-based, after normalization)
+        line: usize,
+        /// Line in the snapshot
+        expected: String,
+        /// Line in the output
+        actual: String,
+    },
+}
+
+/// Renders [`FIXTURE_MARKDOWN`] through a theme with fixed metadata and the default
+/// code highlight theme.
+pub fn render_fixture(manager: &ThemeManager, theme: &str) -> Result<String> {
+    let metadata = HashMap::from([
+        ("title".to_string(), "Theme Snapshot".to_string()),
+        ("author".to_string(), "wechat-pub-rs".to_string()),
+    ]);
+    manager.render(FIXTURE_MARKDOWN, theme, DEFAULT_HIGHLIGHT_THEME, &metadata)
+}
+
+/// Normalizes HTML for comparison: line endings are unified, trailing whitespace is
+/// removed and blank lines are dropped.
+pub fn normalize_html(html: &str) -> String {
+    html.lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Compares HTML against the snapshot at `path`, writing the snapshot if it is missing or
+/// differs and [`UPDATE_SNAPSHOTS_ENV`] is set to `1`.
+pub fn check_snapshot(html: &str, path: impl AsRef<Path>) -> Result<SnapshotStatus> {
+    let path = path.as_ref();
+    let actual = normalize_html(html);
+    let file_error =
+        |e: std::io::Error| WeChatError::file_error(path.display().to_string(), e.to_string());
+
+    let expected = match std::fs::read_to_string(path) {
+        Ok(expected) => normalize_html(&expected),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            if !update_requested() {
+                return Ok(SnapshotStatus::Missing);
+            }
+            write_snapshot(path, &actual)?;
+            return Ok(SnapshotStatus::Created);
+        }
+        Err(e) => return Err(file_error(e)),
+    };
+
+    if expected == actual {
+        return Ok(SnapshotStatus::Matched);
+    }
+    if update_requested() {
+        write_snapshot(path, &actual)?;
+        return Ok(SnapshotStatus::Updated);
+    }
+
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => line += 1,
+            (e, a) => {
+                return Ok(SnapshotStatus::Mismatch {
+                    line,
+                    expected: e.unwrap_or_default().to_string(),
+                    actual: a.unwrap_or_default().to_string(),
+                });
+            }
+        }
+    }
+}
+
+/// Renders the fixture through a theme and asserts it matches the snapshot at `path`.
+///
+/// # Panics
+/// Panics if rendering fails or the output differs from the snapshot.
+pub fn assert_theme_snapshot(manager: &ThemeManager, theme: &str, path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let html = render_fixture(manager, theme)
+        .unwrap_or_else(|e| panic!("Failed to render theme '{theme}': {e}"));
+
+    match check_snapshot(&html, path) {
+        Ok(SnapshotStatus::Mismatch {
+            line,
+            expected,
+            actual,
+        }) => panic!(
+            "Theme '{theme}' doesn't match snapshot {} at line {line}\n  expected: {expected}\n    actual: {actual}\nSet {UPDATE_SNAPSHOTS_ENV}=1 to accept the new output",
+            path.display()
+        ),
+        Ok(SnapshotStatus::Missing) => panic!(
+            "Snapshot {} of theme '{theme}' doesn't exist\nSet {UPDATE_SNAPSHOTS_ENV}=1 to create it",
+            path.display()
+        ),
+        Ok(_) => {}
+        Err(e) => panic!("Failed to check snapshot {}: {e}", path.display()),
+    }
+}
+
+/// Whether [`UPDATE_SNAPSHOTS_ENV`] is set to `1`.
+fn update_requested() -> bool {
+    std::env::var(UPDATE_SNAPSHOTS_ENV).is_ok_and(|value| value == "1")
+}
+
+fn write_snapshot(path: &Path, html: &str) -> Result<()> {
+    let file_error =
+        |e: std::io::Error| WeChatError::file_error(path.display().to_string(), e.to_string());
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(file_error)?;
+    }
+    std::fs::write(path, format!("{html}\n")).map_err(file_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_snapshots() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("snapshots").join("default.html");
+        let manager = ThemeManager::new();
+
+        let html = render_fixture(&manager, "default").unwrap();
+        assert!(html.contains("Theme Snapshot"));
+        assert!(html.contains("<table"));
+
+        // Missing snapshots fail instead of being written
+        assert_eq!(
+            check_snapshot(&html, &path).unwrap(),
+            SnapshotStatus::Missing
+        );
+        assert!(!path.exists());
+        let missing = std::panic::catch_unwind(|| {
+            assert_theme_snapshot(&manager, "default", &path);
+        });
+        assert!(missing.is_err());
+
+        write_snapshot(&path, &html).unwrap();
+        assert_theme_snapshot(&manager, "default", &path);
+
+        // Whitespace differences are normalized away
+        let reformatted = html.replace('\n', "  \r\n\n");
+        assert_eq!(
+            check_snapshot(&reformatted, &path).unwrap(),
+            SnapshotStatus::Matched
+        );
+
+        let lapis = render_fixture(&manager, "lapis").unwrap();
+        match check_snapshot(&lapis, &path).unwrap() {
+            SnapshotStatus::Mismatch {
+                expected, actual, ..
+            } => assert_ne!(expected, actual),
+            other => panic!("Expected mismatch, got {other:?}"),
+        }
+    }
+}
@@ -56,11 +56,20 @@
 
 use crate::css_vars::CssVariableProcessor;
 use crate::error::{Result, WeChatError};
+use crate::highlight::{DEFAULT_HIGHLIGHT_THEME, HighlightRegistry, SyntaxRegistry};
+use crate::image_processing::FULL_RESOLUTION_TITLE;
+use crate::markdown;
+use crate::profile::RenderProfile;
 use askama::Template;
 use comrak::{
-    ComrakOptions, ComrakPlugins, markdown_to_html_with_plugins, plugins::syntect::SyntectAdapter,
+    Arena, ComrakOptions, ComrakPlugins, format_html_with_plugins,
+    nodes::{AstNode, NodeValue},
+    parse_document,
+    plugins::syntect::SyntectAdapterBuilder,
 };
+use serde::Serialize;
 use std::collections::HashMap;
+use std::time::Instant;
 use tracing::warn;
 
 // Embed all theme CSS files at compile time
@@ -73,19 +82,8 @@ const PIE_CSS: &str = include_str!("../themes/pie.css");
 const PURPLE_CSS: &str = include_str!("../themes/purple.css");
 const RAINBOW_CSS: &str = include_str!("../themes/rainbow.css");
 
-// Embed all highlight CSS files at compile time
-const ATOM_ONE_DARK_CSS: &str = include_str!("../themes/highlight/atom-one-dark.min.css");
-const ATOM_ONE_LIGHT_CSS: &str = include_str!("../themes/highlight/atom-one-light.min.css");
-const DRACULA_CSS: &str = include_str!("../themes/highlight/dracula.min.css");
-const GITHUB_DARK_CSS: &str = include_str!("../themes/highlight/github-dark.min.css");
-const GITHUB_CSS: &str = include_str!("../themes/highlight/github.min.css");
-const MONOKAI_CSS: &str = include_str!("../themes/highlight/monokai.min.css");
-const SOLARIZED_DARK_CSS: &str = include_str!("../themes/highlight/solarized-dark.min.css");
-const SOLARIZED_LIGHT_CSS: &str = include_str!("../themes/highlight/solarized-light.min.css");
-const XCODE_CSS: &str = include_str!("../themes/highlight/xcode.min.css");
-
 /// Built-in theme options.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BuiltinTheme {
     /// Simple, clean default theme
     Default,
@@ -133,6 +131,82 @@ impl BuiltinTheme {
             BuiltinTheme::Rainbow,
         ]
     }
+
+    /// Gets the metadata describing this theme.
+    pub fn info(&self) -> ThemeInfo {
+        let (display_name, description, author, homepage, preview) = match self {
+            BuiltinTheme::Default => (
+                "Default",
+                "Clean, minimal design for general content",
+                None,
+                None,
+                ("#0069c2", "#333333", "#ffffff"),
+            ),
+            BuiltinTheme::Lapis => (
+                "Lapis",
+                "Elegant blue accents for technical articles",
+                Some("YiNN"),
+                Some("https://github.com/YiNNx/typora-theme-lapis"),
+                ("#4870ac", "#40464f", "#ffffff"),
+            ),
+            BuiltinTheme::Maize => (
+                "Maize",
+                "Warm yellow tones for creative content",
+                Some("BEATREE"),
+                Some("https://github.com/BEATREE/typora-maize-theme"),
+                ("#e49123", "#333333", "#fafafa"),
+            ),
+            BuiltinTheme::OrangeHeart => (
+                "Orange Heart",
+                "Orange accents for personal blogs",
+                Some("evgo2017"),
+                Some("https://github.com/evgo2017/typora-theme-orange-heart"),
+                ("#ef7060", "#222222", "#ffffff"),
+            ),
+            BuiltinTheme::PhyCat => (
+                "Phycat",
+                "Teal headings with decorative icons",
+                Some("sumruler"),
+                Some("https://github.com/sumruler/typora-theme-phycat"),
+                ("#3db8bf", "#333333", "#ffffff"),
+            ),
+            BuiltinTheme::Pie => (
+                "Pie",
+                "Sweet red accents for lifestyle content",
+                Some("kevinzhao2233"),
+                Some("https://github.com/kevinzhao2233/typora-theme-pie"),
+                ("#da282a", "#262626", "#ffffff"),
+            ),
+            BuiltinTheme::Purple => (
+                "Purple",
+                "Purple accents for creative writing",
+                Some("hliu202"),
+                Some("https://github.com/hliu202/typora-purple-theme"),
+                ("#8064a9", "#444444", "#ffffff"),
+            ),
+            BuiltinTheme::Rainbow => (
+                "Rainbow",
+                "Colorful, vibrant headings for fun content",
+                Some("thezbm"),
+                Some("https://github.com/thezbm/typora-theme-rainbow"),
+                ("#ffbfbf", "#222222", "#ffffff"),
+            ),
+        };
+        let (accent, text, background) = preview;
+
+        ThemeInfo {
+            name: self.as_str().to_string(),
+            display_name: display_name.to_string(),
+            description: description.to_string(),
+            author: author.map(str::to_string),
+            homepage: homepage.map(str::to_string),
+            preview: ThemePreview {
+                accent: accent.to_string(),
+                text: text.to_string(),
+                background: background.to_string(),
+            },
+        }
+    }
 }
 
 impl std::str::FromStr for BuiltinTheme {
@@ -155,6 +229,257 @@ impl std::str::FromStr for BuiltinTheme {
     }
 }
 
+/// Identifies a theme: one of the built-in themes or a custom theme added with
+/// [`ThemeManager::add_theme`].
+///
+/// Converting from a string picks the built-in theme of that name if there is one:
+///
+/// ```rust
+/// use wechat_pub_rs::theme::{BuiltinTheme, ThemeId};
+///
+/// assert_eq!(ThemeId::from("lapis"), ThemeId::Builtin(BuiltinTheme::Lapis));
+/// assert_eq!(ThemeId::from("brand"), ThemeId::Custom("brand".to_string()));
+/// assert_eq!(ThemeId::from(BuiltinTheme::Pie), "pie");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ThemeId {
+    /// A built-in theme
+    Builtin(BuiltinTheme),
+    /// A custom theme, by name
+    Custom(String),
+}
+
+impl ThemeId {
+    /// Gets the theme name.
+    pub fn as_str(&self) -> &str {
+        match self {
+            ThemeId::Builtin(theme) => theme.as_str(),
+            ThemeId::Custom(name) => name,
+        }
+    }
+
+    /// Whether this is a built-in theme.
+    pub fn is_builtin(&self) -> bool {
+        matches!(self, ThemeId::Builtin(_))
+    }
+}
+
+impl Default for ThemeId {
+    fn default() -> Self {
+        ThemeId::Builtin(BuiltinTheme::Default)
+    }
+}
+
+impl From<BuiltinTheme> for ThemeId {
+    fn from(theme: BuiltinTheme) -> Self {
+        ThemeId::Builtin(theme)
+    }
+}
+
+impl From<&str> for ThemeId {
+    fn from(name: &str) -> Self {
+        name.parse::<BuiltinTheme>()
+            .map(ThemeId::Builtin)
+            .unwrap_or_else(|_| ThemeId::Custom(name.to_string()))
+    }
+}
+
+impl From<String> for ThemeId {
+    fn from(name: String) -> Self {
+        match name.parse::<BuiltinTheme>() {
+            Ok(theme) => ThemeId::Builtin(theme),
+            Err(_) => ThemeId::Custom(name),
+        }
+    }
+}
+
+impl From<&String> for ThemeId {
+    fn from(name: &String) -> Self {
+        ThemeId::from(name.as_str())
+    }
+}
+
+impl std::fmt::Display for ThemeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq<str> for ThemeId {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for ThemeId {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+/// Metadata describing a theme, for presenting a theme picker.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ThemeInfo {
+    /// Name used to select the theme
+    pub name: String,
+    /// Human-readable name
+    pub display_name: String,
+    /// Short description of the theme's look
+    pub description: String,
+    /// Author of the theme
+    pub author: Option<String>,
+    /// Homepage of the theme
+    pub homepage: Option<String>,
+    /// Representative colors
+    pub preview: ThemePreview,
+}
+
+impl ThemeInfo {
+    /// Creates metadata with only a name, as used for custom themes added without metadata.
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self {
+            display_name: name.clone(),
+            name,
+            description: String::new(),
+            author: None,
+            homepage: None,
+            preview: ThemePreview::default(),
+        }
+    }
+
+    /// Sets the human-readable name.
+    pub fn display_name(mut self, display_name: impl Into<String>) -> Self {
+        self.display_name = display_name.into();
+        self
+    }
+
+    /// Sets the description.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Sets the author.
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    /// Sets the homepage.
+    pub fn homepage(mut self, homepage: impl Into<String>) -> Self {
+        self.homepage = Some(homepage.into());
+        self
+    }
+
+    /// Sets the preview colors.
+    pub fn preview(mut self, preview: ThemePreview) -> Self {
+        self.preview = preview;
+        self
+    }
+}
+
+/// Representative colors of a theme as CSS color values.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ThemePreview {
+    /// Accent color used for headings, links and emphasis
+    pub accent: String,
+    /// Body text color
+    pub text: String,
+    /// Page background color
+    pub background: String,
+}
+
+impl Default for ThemePreview {
+    fn default() -> Self {
+        Self {
+            accent: "#333333".to_string(),
+            text: "#333333".to_string(),
+            background: "#ffffff".to_string(),
+        }
+    }
+}
+
+/// Frontmatter key selecting the [`CodeBlockMode`] of a document.
+pub const CODE_WHITESPACE_KEY: &str = "code_whitespace";
+
+/// Frontmatter key enabling the lead paragraph hooks of a document (`lead: true`).
+///
+/// The first top-level paragraph gets the [`LEAD_CLASS`] class and its first letter is
+/// wrapped in a span with the [`FIRST_LETTER_CLASS`] class. CSS inlining can't apply
+/// `p:first-of-type` or `::first-letter` rules, so themes style these classes instead.
+pub const LEAD_KEY: &str = "lead";
+
+/// Class of the lead paragraph when [`LEAD_KEY`] is enabled.
+pub const LEAD_CLASS: &str = "lead";
+
+/// Class of the span around the first letter of the lead paragraph.
+pub const FIRST_LETTER_CLASS: &str = "first-letter";
+
+/// Frontmatter key showing the cover image at the top of the article (`hero: true`).
+///
+/// The client resolves the cover into [`HERO_IMAGE_KEY`]; WeChat can also show the
+/// cover natively, so turn that off with `show_cover` to avoid showing it twice.
+pub const HERO_KEY: &str = "hero";
+
+/// Metadata key holding the URL of the image rendered in the hero slot.
+///
+/// Rendered as `<figure class="article-hero">` before the content.
+pub const HERO_IMAGE_KEY: &str = "hero_image";
+
+/// Frontmatter key enabling the author and date row (`meta_row: true`).
+///
+/// Rendered as `<p class="article-meta">` with `article-author` and `article-date`
+/// spans, taken from the `author` and `date` entries.
+pub const META_ROW_KEY: &str = "meta_row";
+
+/// Frontmatter key enabling the tags row (`tags_row: true`).
+///
+/// Rendered as `<p class="article-tags">` with an `article-tag` span for every entry of
+/// the `tags` list.
+pub const TAGS_ROW_KEY: &str = "tags_row";
+
+/// Tags whose paragraphs are never the lead paragraph.
+const NESTING_TAGS: &[&str] = &[
+    "blockquote",
+    "ul",
+    "ol",
+    "table",
+    "pre",
+    "div",
+    "figure",
+    "details",
+    "section",
+];
+
+/// How line breaks and whitespace in code blocks are preserved for WeChat.
+///
+/// Selected per document with `code_whitespace: br` or `code_whitespace: pre` in the
+/// frontmatter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CodeBlockMode {
+    /// Newlines become `<br/>` tags (consecutive blank lines are collapsed)
+    #[default]
+    LineBreaks,
+    /// Newlines are kept and the block is styled `white-space: pre`, preserving blank
+    /// lines and alignment exactly; long lines scroll horizontally
+    Preformatted,
+}
+
+impl std::str::FromStr for CodeBlockMode {
+    type Err = WeChatError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "br" | "line-breaks" => Ok(CodeBlockMode::LineBreaks),
+            "pre" | "preformatted" => Ok(CodeBlockMode::Preformatted),
+            _ => Err(WeChatError::config_error(format!(
+                "Unknown {CODE_WHITESPACE_KEY} value '{s}', expected 'br' or 'pre'"
+            ))),
+        }
+    }
+}
+
 /// Askama template for rendering articles with themes.
 #[derive(Template)]
 #[template(path = "article.html")]
@@ -165,6 +490,13 @@ pub struct ArticleTemplate {
     pub content: String,
     pub theme_css: String,
     pub highlight_css: String,
+    /// Image shown above the content, omitted when empty
+    pub hero_image: String,
+    /// Whether to show the author and date row
+    pub show_meta: bool,
+    pub date: String,
+    /// Tags shown in a row above the content, omitted when empty
+    pub tags: Vec<String>,
 }
 
 /// Theme template containing CSS for styling.
@@ -200,8 +532,37 @@ impl ThemeTemplate {
     /// Renders content using this theme with inline styles for WeChat.
     ///
     /// This method processes CSS variables before inlining styles for better WeChat compatibility.
+    /// The [`CodeBlockMode`] is taken from the `code_whitespace` metadata entry.
     pub fn render(&self, content: &str, metadata: &HashMap<String, String>) -> Result<String> {
+        self.render_profiled(content, metadata, &mut RenderProfile::default())
+    }
+
+    /// Renders content like [`render`](Self::render), recording the time spent in CSS
+    /// variable processing and inlining.
+    pub(crate) fn render_profiled(
+        &self,
+        content: &str,
+        metadata: &HashMap<String, String>,
+        profile: &mut RenderProfile,
+    ) -> Result<String> {
+        let code_block_mode = metadata
+            .get(CODE_WHITESPACE_KEY)
+            .map(|mode| mode.parse::<CodeBlockMode>())
+            .transpose()?
+            .unwrap_or_default();
+        let lead = metadata_flag(metadata, LEAD_KEY)?;
+        let show_meta = metadata_flag(metadata, META_ROW_KEY)?;
+        let tags = if metadata_flag(metadata, TAGS_ROW_KEY)? {
+            metadata
+                .get("tags")
+                .map(|tags| markdown::parse_list(tags))
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+
         // Process CSS variables in both theme and highlight CSS
+        let start = Instant::now();
         let css_processor = CssVariableProcessor::new();
 
         let processed_theme_css =
@@ -218,14 +579,25 @@ impl ThemeTemplate {
                     message: format!("CSS variable processing failed for highlight CSS: {e}"),
                 })?;
 
+        profile.css_vars += start.elapsed();
+
         // Create Askama template with the processed CSS
+        let start = Instant::now();
         let template = ArticleTemplate {
             title: metadata.get("title").cloned().unwrap_or_default(),
             description: metadata.get("description").cloned().unwrap_or_default(),
             author: metadata.get("author").cloned().unwrap_or_default(),
-            content: content.to_string(),
+            content: if lead {
+                mark_lead_paragraph(content)
+            } else {
+                content.to_string()
+            },
             theme_css: processed_theme_css,
             highlight_css: processed_highlight_css,
+            hero_image: metadata.get(HERO_IMAGE_KEY).cloned().unwrap_or_default(),
+            show_meta,
+            date: metadata.get("date").cloned().unwrap_or_default(),
+            tags,
         };
 
         // Render the template to HTML
@@ -234,7 +606,8 @@ impl ThemeTemplate {
         })?;
 
         // Post-process code blocks before CSS inlining to preserve their structure
-        let html_with_protected_code = self.post_process_code_blocks(html_with_css);
+        let html_with_protected_code =
+            self.post_process_code_blocks(html_with_css, code_block_mode);
 
         // Use css-inline to convert CSS to inline styles
         let inlined_html =
@@ -244,12 +617,13 @@ impl ThemeTemplate {
 
         // remove "\n"
         let html_without_newlines = inlined_html.replace("\n", "");
+        profile.inline += start.elapsed();
         Ok(html_without_newlines)
     }
 
     /// Post-process HTML to preserve code block structure for WeChat.
     /// This function handles syntax-highlighted code blocks and preserves syntax highlighting while ensuring proper line breaks.
-    fn post_process_code_blocks(&self, html: String) -> String {
+    fn post_process_code_blocks(&self, html: String, mode: CodeBlockMode) -> String {
         use regex::Regex;
 
         // Use regex to find and replace pre > code blocks while preserving syntax highlighting
@@ -261,6 +635,13 @@ impl ThemeTemplate {
             let code_tag = &caps[2];
             let content = &caps[3];
 
+            if mode == CodeBlockMode::Preformatted {
+                let pre_tag = with_inline_style(pre_tag, "white-space: pre; overflow-x: auto");
+                let code_tag = with_inline_style(code_tag, "white-space: pre");
+                let processed_content = self.preformat_code_content(content);
+                return format!("{pre_tag}{code_tag}{processed_content}</code></pre>");
+            }
+
             // Process the content to fix newlines while preserving syntax highlighting
             let processed_content = self.process_code_content(content);
 
@@ -309,13 +690,192 @@ impl ThemeTemplate {
 
         processed
     }
+
+    /// Process code content for `white-space: pre` rendering.
+    ///
+    /// Newlines are encoded as `&#10;` so they survive the newline stripping after CSS
+    /// inlining; everything else, including runs of blank lines and spaces, is kept as is.
+    /// The final newline of the block, which would render as an extra blank line, is dropped.
+    fn preformat_code_content(&self, html_content: &str) -> String {
+        let trailing_newline = html_content
+            .rfind('\n')
+            .filter(|&pos| is_only_tags(&html_content[pos + 1..]));
+
+        let mut processed = String::with_capacity(html_content.len());
+        let mut in_tag = false;
+
+        for (pos, ch) in html_content.char_indices() {
+            match ch {
+                '\n' if Some(pos) == trailing_newline => {}
+                '<' => {
+                    in_tag = true;
+                    processed.push(ch);
+                }
+                '>' => {
+                    in_tag = false;
+                    processed.push(ch);
+                }
+                '\n' if !in_tag => processed.push_str("&#10;"),
+                _ => processed.push(ch),
+            }
+        }
+
+        processed
+    }
+}
+
+/// Whether an HTML fragment consists of tags only, without any text.
+fn is_only_tags(html: &str) -> bool {
+    let mut in_tag = false;
+    html.chars().all(|ch| {
+        match ch {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => return false,
+            _ => {}
+        }
+        true
+    })
+}
+
+/// Reads a `true`/`false` metadata entry, which is off when missing.
+pub(crate) fn metadata_flag(metadata: &HashMap<String, String>, key: &str) -> Result<bool> {
+    match metadata.get(key).map(|value| value.to_lowercase()) {
+        None => Ok(false),
+        Some(value) => match value.as_str() {
+            "true" | "yes" => Ok(true),
+            "false" | "no" => Ok(false),
+            _ => Err(WeChatError::config_error(format!(
+                "Unknown {key} value '{value}', expected 'true' or 'false'"
+            ))),
+        },
+    }
+}
+
+/// Marks the first top-level paragraph with text as the lead paragraph and wraps its
+/// first letter, see [`LEAD_KEY`].
+fn mark_lead_paragraph(html: &str) -> String {
+    let mut depth = 0usize;
+    let mut pos = 0;
+
+    while let Some(offset) = html[pos..].find('<') {
+        let start = pos + offset;
+        let Some(len) = html[start..].find('>') else {
+            break;
+        };
+        let end = start + len + 1;
+        pos = end;
+
+        let tag = &html[start + 1..end - 1];
+        let (closing, tag) = match tag.strip_prefix('/') {
+            Some(tag) => (true, tag),
+            None => (false, tag),
+        };
+        let name = tag
+            .split(|ch: char| ch.is_whitespace() || ch == '/')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        if NESTING_TAGS.contains(&name.as_str()) {
+            depth = if closing {
+                depth.saturating_sub(1)
+            } else {
+                depth + 1
+            };
+            continue;
+        }
+        if name != "p" || closing || depth > 0 {
+            continue;
+        }
+
+        let body_end = html[end..].find("</p>").map_or(html.len(), |len| end + len);
+        let Some(letter) = first_letter(&html[end..body_end]) else {
+            // Image-only paragraphs aren't lead paragraphs
+            continue;
+        };
+        let (letter_start, letter_end) = (end + letter.start, end + letter.end);
+
+        return format!(
+            "{}{}<span class=\"{FIRST_LETTER_CLASS}\">{}</span>{}",
+            &html[..start],
+            with_class(&html[start..end], LEAD_CLASS) + &html[end..letter_start],
+            &html[letter_start..letter_end],
+            &html[letter_end..]
+        );
+    }
+
+    html.to_string()
+}
+
+/// Finds the first visible character of an HTML fragment, keeping entities whole.
+fn first_letter(html: &str) -> Option<std::ops::Range<usize>> {
+    let mut in_tag = false;
+    for (pos, ch) in html.char_indices() {
+        match ch {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if in_tag || ch.is_whitespace() => {}
+            '&' => {
+                let len = html[pos..].find(';').map_or(1, |len| len + 1);
+                return Some(pos..pos + len);
+            }
+            _ => return Some(pos..pos + ch.len_utf8()),
+        }
+    }
+    None
+}
+
+/// Adds a class to an opening tag, after any existing ones.
+fn with_class(tag: &str, class: &str) -> String {
+    match tag.find("class=\"") {
+        Some(pos) => {
+            let (head, tail) = tag.split_at(pos + "class=\"".len());
+            match tail.find('"') {
+                Some(len) => format!("{head}{} {class}{}", &tail[..len], &tail[len..]),
+                None => tag.to_string(),
+            }
+        }
+        None => match tag.strip_suffix('>') {
+            Some(head) => format!("{head} class=\"{class}\">"),
+            None => tag.to_string(),
+        },
+    }
+}
+
+/// Adds inline style declarations to an opening tag, ahead of any existing ones.
+fn with_inline_style(tag: &str, declarations: &str) -> String {
+    match tag.find("style=\"") {
+        Some(pos) => {
+            let (head, tail) = tag.split_at(pos + "style=\"".len());
+            format!("{head}{declarations}; {tail}")
+        }
+        None => match tag.strip_suffix('>') {
+            Some(head) => format!("{head} style=\"{declarations}\">"),
+            None => tag.to_string(),
+        },
+    }
+}
+
+/// Drops the [`FULL_RESOLUTION_TITLE`] marker from image titles; it only tells the upload
+/// not to scale the image and would otherwise show up as the image's tooltip.
+fn strip_full_resolution_titles<'a>(root: &'a AstNode<'a>) {
+    for node in root.descendants() {
+        if let NodeValue::Image(ref mut link) = node.data.borrow_mut().value
+            && link.title == FULL_RESOLUTION_TITLE
+        {
+            link.title.clear();
+        }
+    }
 }
 
 /// Theme manager for rendering markdown with different styles.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ThemeManager {
     templates: HashMap<String, ThemeTemplate>,
-    highlight_css: HashMap<String, String>,
+    infos: HashMap<String, ThemeInfo>,
+    highlights: HighlightRegistry,
+    syntaxes: SyntaxRegistry,
     markdown_options: ComrakOptions<'static>,
 }
 
@@ -324,12 +884,13 @@ impl ThemeManager {
     pub fn new() -> Self {
         let mut manager = Self {
             templates: HashMap::new(),
-            highlight_css: HashMap::new(),
+            infos: HashMap::new(),
+            highlights: HighlightRegistry::new(),
+            syntaxes: SyntaxRegistry::new(),
             markdown_options: Self::create_markdown_options(),
         };
 
         manager.load_builtin_themes();
-        manager.load_highlight_themes();
         manager
     }
 
@@ -349,27 +910,7 @@ impl ThemeManager {
         for theme in BuiltinTheme::all() {
             let template = self.create_builtin_theme(theme);
             self.templates.insert(theme.as_str().to_string(), template);
-        }
-    }
-
-    /// Loads all highlight themes from embedded CSS.
-    fn load_highlight_themes(&mut self) {
-        // Array of (theme_name, css_content) pairs for cleaner initialization
-        let highlight_themes = [
-            ("atom-one-dark", ATOM_ONE_DARK_CSS),
-            ("atom-one-light", ATOM_ONE_LIGHT_CSS),
-            ("dracula", DRACULA_CSS),
-            ("github-dark", GITHUB_DARK_CSS),
-            ("github", GITHUB_CSS),
-            ("monokai", MONOKAI_CSS),
-            ("solarized-dark", SOLARIZED_DARK_CSS),
-            ("solarized-light", SOLARIZED_LIGHT_CSS),
-            ("xcode", XCODE_CSS),
-            ("vscode", GITHUB_CSS), // vscode as an alias for github
-        ];
-
-        for (name, css) in highlight_themes {
-            self.highlight_css.insert(name.to_string(), css.to_string());
+            self.infos.insert(theme.as_str().to_string(), theme.info());
         }
     }
 
@@ -400,6 +941,25 @@ impl ThemeManager {
         theme_name: &str,
         code_theme: &str,
         metadata: &HashMap<String, String>,
+    ) -> Result<String> {
+        self.render_profiled(
+            markdown_content,
+            theme_name,
+            code_theme,
+            metadata,
+            &mut RenderProfile::default(),
+        )
+    }
+
+    /// Renders markdown content like [`render`](Self::render), recording the time spent in
+    /// each stage.
+    pub(crate) fn render_profiled(
+        &self,
+        markdown_content: &str,
+        theme_name: &str,
+        code_theme: &str,
+        metadata: &HashMap<String, String>,
+        profile: &mut RenderProfile,
     ) -> Result<String> {
         let template =
             self.templates
@@ -408,33 +968,52 @@ impl ThemeManager {
                     theme: theme_name.to_string(),
                 })?;
 
-        // Get highlight CSS, defaulting to "vscode" if not specified or not found
-        let highlight_css = self.get_highlight_css(code_theme);
-
-        // Create syntect adapter for syntax highlighting
-        // Map our CSS theme names to syntect theme names
-        let syntect_theme_name = match code_theme {
-            "solarized-light" => Some("Solarized (light)"),
-            "solarized-dark" => Some("Solarized (dark)"),
-            "monokai" => Some("Monokai"),
-            "github" | "vscode" => Some("InspiredGitHub"),
-            "github-dark" => Some("base16-ocean.dark"),
-            "atom-one-dark" => Some("base16-ocean.dark"),
-            "atom-one-light" => Some("InspiredGitHub"),
-            "dracula" => Some("base16-ocean.dark"),
-            "xcode" => Some("InspiredGitHub"),
-            _ => None, // Use default theme
+        // Get the highlight CSS and its matching syntect theme, defaulting to "github"
+        let code_theme = if self.highlights.contains(code_theme) {
+            code_theme
+        } else {
+            warn!(
+                "Highlight theme '{code_theme}' not found, falling back to '{DEFAULT_HIGHLIGHT_THEME}'"
+            );
+            DEFAULT_HIGHLIGHT_THEME
         };
+        let highlight_css = self
+            .highlights
+            .css(code_theme)
+            .unwrap_or_default()
+            .to_string();
+
+        // Create syntect adapter for syntax highlighting with the matching syntect theme
+        let adapter = match self.highlights.theme_set_for(code_theme) {
+            Some(theme_set) => SyntectAdapterBuilder::new()
+                .theme(code_theme)
+                .theme_set(theme_set),
+            None => SyntectAdapterBuilder::new().css(),
+        };
+        let adapter = adapter.syntax_set(self.syntaxes.syntax_set()).build();
 
-        let adapter = SyntectAdapter::new(syntect_theme_name);
+        let start = Instant::now();
 
         // Set up comrak plugins with syntect adapter
         let mut plugins = ComrakPlugins::default();
         plugins.render.codefence_syntax_highlighter = Some(&adapter);
 
-        // Convert markdown to HTML using comrak with syntect
-        let html_content =
-            markdown_to_html_with_plugins(markdown_content, &self.markdown_options, &plugins);
+        // Convert markdown to HTML using comrak with syntect, resolving fence languages
+        let arena = Arena::new();
+        let root = parse_document(&arena, markdown_content, &self.markdown_options);
+        self.syntaxes.resolve_code_blocks(root);
+        strip_full_resolution_titles(root);
+
+        let mut html = Vec::new();
+        format_html_with_plugins(root, &self.markdown_options, &mut html, &plugins).map_err(
+            |e| WeChatError::Internal {
+                message: format!("Markdown rendering failed: {e}"),
+            },
+        )?;
+        let html_content = String::from_utf8(html).map_err(|e| WeChatError::Internal {
+            message: format!("Markdown rendering produced invalid UTF-8: {e}"),
+        })?;
+        profile.highlight += start.elapsed();
 
         // Create a new template with the highlight CSS
         let template_with_highlight = ThemeTemplate {
@@ -444,12 +1023,36 @@ impl ThemeManager {
         };
 
         // Apply theme template
-        template_with_highlight.render(&html_content, metadata)
+        template_with_highlight.render_profiled(&html_content, metadata, profile)
     }
 
     /// Adds a custom theme.
+    ///
+    /// The theme gets metadata with only its name; use [`add_theme_with_info`] to
+    /// describe it.
+    ///
+    /// [`add_theme_with_info`]: Self::add_theme_with_info
     pub fn add_theme(&mut self, name: String, template: ThemeTemplate) {
-        self.templates.insert(name, template);
+        let info = ThemeInfo::new(name.clone());
+        self.add_theme_with_info(template, info);
+    }
+
+    /// Adds a custom theme with metadata, registered under the metadata's name.
+    pub fn add_theme_with_info(&mut self, template: ThemeTemplate, info: ThemeInfo) {
+        self.templates.insert(info.name.clone(), template);
+        self.infos.insert(info.name.clone(), info);
+    }
+
+    /// Gets the metadata of a theme.
+    pub fn theme_info(&self, name: &str) -> Option<&ThemeInfo> {
+        self.infos.get(name)
+    }
+
+    /// Gets the metadata of all themes, sorted by name.
+    pub fn themes_with_info(&self) -> Vec<&ThemeInfo> {
+        let mut infos: Vec<_> = self.infos.values().collect();
+        infos.sort_by(|a, b| a.name.cmp(&b.name));
+        infos
     }
 
     /// Gets the list of available theme names.
@@ -462,15 +1065,55 @@ impl ThemeManager {
         self.templates.contains_key(name)
     }
 
-    /// Gets highlight CSS for a given theme, falling back to default if not found.
-    fn get_highlight_css(&self, theme: &str) -> String {
-        self.highlight_css.get(theme).cloned().unwrap_or_else(|| {
-            warn!("Highlight theme '{theme}' not found, falling back to 'github'");
-            self.highlight_css
-                .get("github")
-                .cloned()
-                .unwrap_or_default()
-        })
+    /// Checks that a theme exists, returning [`WeChatError::ThemeNotFound`] if not.
+    pub fn validate_theme(&self, theme: &ThemeId) -> Result<()> {
+        if self.has_theme(theme.as_str()) {
+            Ok(())
+        } else {
+            Err(WeChatError::ThemeNotFound {
+                theme: theme.to_string(),
+            })
+        }
+    }
+
+    /// Checks that a code highlight theme exists, returning
+    /// [`WeChatError::CodeThemeNotFound`] if not.
+    ///
+    /// Rendering falls back to the default highlight theme instead; this is for callers
+    /// that treat unknown code themes as errors.
+    pub fn validate_code_theme(&self, code_theme: &str) -> Result<()> {
+        if self.highlights.contains(code_theme) {
+            Ok(())
+        } else {
+            Err(WeChatError::CodeThemeNotFound {
+                theme: code_theme.to_string(),
+            })
+        }
+    }
+
+    /// Gets the names of all code highlight themes, sorted.
+    pub fn available_code_themes(&self) -> Vec<&String> {
+        self.highlights.names()
+    }
+
+    /// Gets the registry of code highlight themes.
+    pub fn highlights(&self) -> &HighlightRegistry {
+        &self.highlights
+    }
+
+    /// Gets the registry of code highlight themes for registering custom ones.
+    pub fn highlights_mut(&mut self) -> &mut HighlightRegistry {
+        &mut self.highlights
+    }
+
+    /// Gets the registry resolving fence languages to syntaxes.
+    pub fn syntaxes(&self) -> &SyntaxRegistry {
+        &self.syntaxes
+    }
+
+    /// Gets the registry resolving fence languages to syntaxes, e.g. to add aliases.
+    pub fn syntaxes_mut(&mut self) -> &mut SyntaxRegistry {
+        &mut self.syntaxes
     }
 }
 
@@ -519,6 +1162,180 @@ mod tests {
         assert!("nonexistent".parse::<BuiltinTheme>().is_err());
     }
 
+    #[test]
+    fn test_highlight_colors_match_css() {
+        let manager = ThemeManager::new();
+        let markdown = "```rust\nfn main() {}\n```";
+
+        // Keywords use the keyword color of the paired CSS
+        for (code_theme, keyword_color) in [("dracula", "#b45bcf"), ("monokai", "#f92672")] {
+            let html = manager
+                .render(markdown, "default", code_theme, &HashMap::new())
+                .unwrap();
+            assert!(
+                html.contains(&format!("color:{keyword_color}")),
+                "{code_theme}: {html}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_fence_language_overrides() {
+        let mut manager = ThemeManager::new();
+        let keyword = "color:#d73a49";
+
+        // tsx has no syntect syntax of its own and is highlighted as JavaScript
+        let html = manager
+            .render(
+                "```tsx\nconst x = 1;\n```",
+                "default",
+                "github",
+                &HashMap::new(),
+            )
+            .unwrap();
+        assert!(html.contains(keyword), "{html}");
+        assert!(html.contains("language-js"));
+
+        // Languages from extra syntax definitions are highlighted too
+        manager
+            .syntaxes_mut()
+            .add_sublime_syntax(
+                "name: Toy\nfile_extensions: [toy]\nscope: source.toy\ncontexts:\n  main:\n    - match: '\\blet\\b'\n      scope: keyword.control.toy\n",
+            )
+            .unwrap();
+        let html = manager
+            .render("```toy\nlet x\n```", "default", "github", &HashMap::new())
+            .unwrap();
+        assert!(html.contains(keyword), "{html}");
+    }
+
+    #[test]
+    fn test_preformatted_code_blocks() {
+        let manager = ThemeManager::new();
+        let markdown = "```python\ndef f():\n    x  = 1\n\n\n    return x\n```";
+
+        let mut metadata = HashMap::new();
+        metadata.insert(CODE_WHITESPACE_KEY.to_string(), "pre".to_string());
+        let html = manager
+            .render(markdown, "default", "github", &metadata)
+            .unwrap();
+
+        assert!(html.contains("white-space: pre"));
+        assert!(!html.contains("<br/>"));
+        // Both blank lines survive, and the trailing newline is dropped
+        assert_eq!(html.matches("&#10;").count(), 4);
+
+        metadata.insert(CODE_WHITESPACE_KEY.to_string(), "wrap".to_string());
+        assert!(
+            manager
+                .render(markdown, "default", "github", &metadata)
+                .is_err()
+        );
+
+        assert_eq!(
+            with_inline_style("<pre style=\"color:#000;\">", "white-space: pre"),
+            "<pre style=\"white-space: pre; color:#000;\">"
+        );
+        assert_eq!(
+            with_inline_style("<code class=\"language-rust\">", "white-space: pre"),
+            "<code class=\"language-rust\" style=\"white-space: pre\">"
+        );
+    }
+
+    #[test]
+    fn test_full_resolution_title_is_stripped() {
+        let manager = ThemeManager::new();
+        let html = manager
+            .render(
+                "![Wide](https://example.com/wide.png \"fullres\") ![Tip](https://example.com/tip.png \"Tip\")",
+                "default",
+                "github",
+                &HashMap::new(),
+            )
+            .unwrap();
+
+        assert!(!html.contains("fullres"));
+        assert!(html.contains("title=\"Tip\""));
+    }
+
+    #[test]
+    fn test_lead_paragraph() {
+        let html = "<h1>Title</h1>\n<blockquote>\n<p>Quote</p>\n</blockquote>\n<p><img src=\"a.png\" /></p>\n<p><strong>&quot;Once</strong> upon</p>\n<p>Second</p>\n";
+        assert_eq!(
+            mark_lead_paragraph(html),
+            "<h1>Title</h1>\n<blockquote>\n<p>Quote</p>\n</blockquote>\n<p><img src=\"a.png\" /></p>\n<p class=\"lead\"><strong><span class=\"first-letter\">&quot;</span>Once</strong> upon</p>\n<p>Second</p>\n"
+        );
+        assert_eq!(
+            mark_lead_paragraph("<ul><li>Item</li></ul>"),
+            "<ul><li>Item</li></ul>"
+        );
+        assert_eq!(
+            with_class("<p class=\"note\">", "lead"),
+            "<p class=\"note lead\">"
+        );
+
+        // Themes style the hooks through CSS inlining
+        let css = format!(
+            "p.{LEAD_CLASS} {{ font-size: 18px; }} .{FIRST_LETTER_CLASS} {{ float: left; }}"
+        );
+        let template = ThemeTemplate::new(css, String::new(), "lead".to_string());
+        let mut metadata = HashMap::new();
+        metadata.insert(LEAD_KEY.to_string(), "true".to_string());
+        let html = template.render("<p>Über alles</p>", &metadata).unwrap();
+        assert!(html.contains("font-size: 18px"));
+        assert!(html.contains("float: left"));
+        assert!(html.contains(">Ü</span>ber"));
+
+        metadata.insert(LEAD_KEY.to_string(), "false".to_string());
+        let html = template.render("<p>Über alles</p>", &metadata).unwrap();
+        assert!(!html.contains("class=\"lead\""));
+
+        metadata.insert(LEAD_KEY.to_string(), "maybe".to_string());
+        assert!(template.render("<p>Über alles</p>", &metadata).is_err());
+    }
+
+    #[test]
+    fn test_header_slots() {
+        let template = ThemeTemplate::new(String::new(), String::new(), "header".to_string());
+        let mut metadata = HashMap::new();
+        metadata.insert("author".to_string(), "Alice".to_string());
+        metadata.insert("date".to_string(), "2024-05-01".to_string());
+        metadata.insert("tags".to_string(), "[rust, \"wechat\"]".to_string());
+
+        // Slots are off by default
+        let html = template.render("<p>Body</p>", &metadata).unwrap();
+        assert!(!html.contains("article-hero"));
+        assert!(!html.contains("article-meta"));
+        assert!(!html.contains("article-tags"));
+
+        metadata.insert(
+            HERO_IMAGE_KEY.to_string(),
+            "https://example.com/a.png?x=1&y=2".to_string(),
+        );
+        metadata.insert(META_ROW_KEY.to_string(), "true".to_string());
+        metadata.insert(TAGS_ROW_KEY.to_string(), "yes".to_string());
+        let html = template.render("<p>Body</p>", &metadata).unwrap();
+        assert!(html.contains(
+            "<figure class=\"article-hero\"><img src=\"https://example.com/a.png?x=1&#38;y=2\""
+        ));
+        assert!(html.contains("<span class=\"article-author\">Alice</span><span class=\"article-date\">2024-05-01</span>"));
+        assert!(html.contains(
+            "<span class=\"article-tag\">rust</span><span class=\"article-tag\">wechat</span>"
+        ));
+        let header = html.find("article-hero").unwrap();
+        assert!(header < html.find("article-meta").unwrap());
+        assert!(html.find("article-tags").unwrap() < html.find("Body").unwrap());
+
+        // The metadata row is left out without an author or date
+        metadata.remove("author");
+        metadata.remove("date");
+        let html = template.render("<p>Body</p>", &metadata).unwrap();
+        assert!(!html.contains("article-meta"));
+
+        metadata.insert(META_ROW_KEY.to_string(), "sometimes".to_string());
+        assert!(template.render("<p>Body</p>", &metadata).is_err());
+    }
+
     #[test]
     fn test_theme_manager_creation() {
         let manager = ThemeManager::new();
@@ -531,6 +1348,68 @@ mod tests {
         assert!(themes.len() >= 4);
     }
 
+    #[test]
+    fn test_theme_id() {
+        let mut manager = ThemeManager::new();
+
+        let lapis = ThemeId::from("lapis");
+        assert_eq!(lapis, ThemeId::Builtin(BuiltinTheme::Lapis));
+        assert!(lapis.is_builtin());
+        assert!(manager.validate_theme(&lapis).is_ok());
+        assert_eq!(ThemeId::default(), "default");
+
+        let brand = ThemeId::from("brand".to_string());
+        assert_eq!(brand, ThemeId::Custom("brand".to_string()));
+        assert!(matches!(
+            manager.validate_theme(&brand),
+            Err(WeChatError::ThemeNotFound { theme }) if theme == "brand"
+        ));
+
+        manager.add_theme(
+            brand.to_string(),
+            ThemeTemplate::new(String::new(), String::new(), "brand".to_string()),
+        );
+        assert!(manager.validate_theme(&brand).is_ok());
+    }
+
+    #[test]
+    fn test_theme_info() {
+        let mut manager = ThemeManager::new();
+
+        let lapis = manager.theme_info("lapis").unwrap();
+        assert_eq!(lapis.display_name, "Lapis");
+        assert_eq!(lapis.author.as_deref(), Some("YiNN"));
+        assert_eq!(lapis.preview.accent, "#4870ac");
+        assert!(manager.theme_info("nonexistent").is_none());
+
+        manager.add_theme(
+            "plain".to_string(),
+            ThemeTemplate::new(String::new(), String::new(), "plain".to_string()),
+        );
+        manager.add_theme_with_info(
+            ThemeTemplate::new(String::new(), String::new(), "brand".to_string()),
+            ThemeInfo::new("brand")
+                .display_name("Brand")
+                .description("Company colors")
+                .author("Design Team"),
+        );
+
+        assert_eq!(manager.theme_info("plain").unwrap().display_name, "plain");
+        assert!(manager.has_theme("brand"));
+        assert_eq!(
+            manager.theme_info("brand").unwrap().description,
+            "Company colors"
+        );
+
+        let names: Vec<_> = manager
+            .themes_with_info()
+            .iter()
+            .map(|info| info.name.as_str())
+            .collect();
+        assert_eq!(names.len(), BuiltinTheme::all().len() + 2);
+        assert!(names.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
     #[test]
     fn test_theme_rendering() {
         let manager = ThemeManager::new();
@@ -6,16 +6,24 @@
 //! - Image processing and management
 //! - Content rendering and theming
 //! - Caching strategies
+//! - Upload notification hooks
 //!
 //! These traits enable better testability, modularity, and extensibility.
 
-use crate::error::Result;
+use crate::client::UploadReport;
+use crate::error::{Result, WeChatError};
+use crate::publish::PublishStatus;
+use crate::theme::{ThemeId, ThemeManager};
 use crate::upload::{Article, DraftInfo};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 
 /// Trait for managing WeChat access tokens with automatic refresh capabilities.
+///
+/// A custom provider, e.g. a token service shared by several deployments, can be set
+/// with
+/// [`WeChatClientBuilder::token_provider`](crate::client::WeChatClientBuilder::token_provider).
 #[async_trait]
 pub trait TokenProvider: Send + Sync {
     /// Gets a valid access token, refreshing if necessary.
@@ -72,6 +80,10 @@ pub trait ImageProcessor: Send + Sync {
 }
 
 /// Trait for rendering content with themes.
+///
+/// Implemented by [`ThemeManager`], which renders articles by default; a custom renderer
+/// can be set with
+/// [`WeChatClientBuilder::renderer`](crate::client::WeChatClientBuilder::renderer).
 pub trait ContentRenderer: Send + Sync {
     /// Renders markdown content to HTML with the specified theme.
     fn render_content(
@@ -92,6 +104,33 @@ pub trait ContentRenderer: Send + Sync {
     fn validate_theme(&self, theme: &str) -> Result<()>;
 }
 
+impl ContentRenderer for ThemeManager {
+    fn render_content(
+        &self,
+        markdown: &str,
+        theme: &str,
+        code_theme: &str,
+        metadata: &HashMap<String, String>,
+    ) -> Result<String> {
+        self.render(markdown, theme, code_theme, metadata)
+    }
+
+    fn available_themes(&self) -> Vec<String> {
+        ThemeManager::available_themes(self)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    fn has_theme(&self, theme: &str) -> bool {
+        ThemeManager::has_theme(self, theme)
+    }
+
+    fn validate_theme(&self, theme: &str) -> Result<()> {
+        ThemeManager::validate_theme(self, &ThemeId::from(theme))
+    }
+}
+
 /// Trait for caching strategies.
 #[async_trait]
 pub trait Cache<K, V>: Send + Sync
@@ -116,17 +155,31 @@ where
 }
 
 /// Trait for HTTP client operations.
+///
+/// Implementations can replace the built-in transport through
+/// [`WeChatClientBuilder::http_client`](crate::client::WeChatClientBuilder::http_client).
+/// Endpoints are API paths like `/cgi-bin/draft/add`, possibly with a query string;
+/// implementations add the base URL and the `access_token` query parameter.
 #[async_trait]
 pub trait HttpClient: Send + Sync {
+    /// Makes a GET request without token, e.g. to the token endpoint itself.
+    ///
+    /// Only needed without a custom [`TokenProvider`]; fails by default.
+    async fn get(&self, endpoint: &str) -> Result<reqwest::Response> {
+        Err(WeChatError::config_error(format!(
+            "HTTP client doesn't support requests without access token ({endpoint})"
+        )))
+    }
+
     /// Makes a GET request with token.
     async fn get_with_token(&self, endpoint: &str, token: &str) -> Result<reqwest::Response>;
 
     /// Makes a POST request with JSON body and token.
-    async fn post_json_with_token<T: serde::Serialize + Send + Sync>(
+    async fn post_json_with_token(
         &self,
         endpoint: &str,
         token: &str,
-        body: &T,
+        body: &serde_json::Value,
     ) -> Result<reqwest::Response>;
 
     /// Uploads a file using multipart form data.
@@ -135,7 +188,7 @@ pub trait HttpClient: Send + Sync {
         endpoint: &str,
         token: &str,
         field_name: &str,
-        file_data: Vec<u8>,
+        file_data: bytes::Bytes,
         filename: &str,
     ) -> Result<reqwest::Response>;
 
@@ -143,6 +196,33 @@ pub trait HttpClient: Send + Sync {
     async fn download_with_limit(&self, url: &str, max_size: u64) -> Result<Vec<u8>>;
 }
 
+/// Trait for reacting to the outcome of client uploads.
+///
+/// Register implementations with [`WeChatClient::with_hooks`](crate::WeChatClient::with_hooks)
+/// to send Slack, webhook or email notifications from a publishing pipeline without
+/// wrapping every call site. The calls that notify them are listed there. All methods
+/// default to doing nothing.
+#[async_trait]
+pub trait UploadHooks: Send + Sync {
+    /// Called after a markdown file has been uploaded as a draft, or has updated one; the
+    /// report's draft ID is then the updated draft.
+    async fn on_success(&self, _markdown_path: &str, _report: &UploadReport) {}
+
+    /// Called after an upload has failed.
+    async fn on_failure(&self, _markdown_path: &str, _error: &WeChatError) {}
+
+    /// Called when a publish job submitted with
+    /// [`WeChatClient::publish_draft`](crate::WeChatClient::publish_draft) finished, as
+    /// reported by the callback server, see `WeChatClient::handle_publish_event`.
+    async fn on_publish_result(
+        &self,
+        _markdown_path: &str,
+        _publish_id: &str,
+        _status: &PublishStatus,
+    ) {
+    }
+}
+
 /// Trait for parsing and processing markdown content.
 pub trait MarkdownProcessor: Send + Sync {
     /// Parses markdown content and extracts metadata.
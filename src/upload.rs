@@ -29,7 +29,7 @@
 //! - **Create**: Upload new article content as a draft
 //! - **Read**: Retrieve draft information and content
 //! - **Update**: Modify existing draft content
-//! - **Delete**: Remove drafts
+//! - **Delete**: Remove drafts, one at a time or in bulk with a dry run first
 //! - **List**: Paginated listing of all drafts
 //!
 //! ## Usage Examples
@@ -101,29 +101,344 @@
 //! - **Deduplication**: O(1) hash-based duplicate detection
 //! - **Error Recovery**: Exponential backoff with jitter for failed requests
 
+use crate::assets::AssetStore;
 use crate::auth::TokenManager;
-use crate::error::{Result, WeChatError};
-use crate::http::{DraftResponse, MaterialUploadResponse, WeChatHttpClient, WeChatResponse};
+use crate::config::Config;
+use crate::endpoints;
+use crate::error::{ImageErrorKind, Result, WeChatError};
+use crate::http::{
+    self, DraftResponse, FileDownload, MaterialUploadResponse, WeChatHttpClient, WeChatResponse,
+};
+use crate::image_processing::{self, ImageFormatPolicy, ImageProcessing, is_heif};
 use crate::markdown::ImageRef;
+use crate::media_map::MaterialEntry;
+use crate::temp::TempFiles;
+use crate::utils::{self, PathPolicy};
 use blake3;
-use futures::future::try_join_all;
+use chrono::{DateTime, Utc};
+use futures::future::{join_all, try_join_all};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::fs;
-use tokio::sync::{RwLock, Semaphore};
+use tokio::sync::{RwLock, Semaphore, SemaphorePermit};
 use tracing::{debug, info, warn};
 
-/// Maximum concurrent image uploads to prevent overwhelming the server
-const MAX_CONCURRENT_UPLOADS: usize = 5;
+/// Page size used when listing all drafts or materials.
+const LIST_PAGE_SIZE: u32 = 20;
+
+/// Prefix of the source URL fragment storing the document slug, e.g.
+/// `https://blog.example.com/posts/hello#wechat-slug=hello`.
+const SLUG_FRAGMENT: &str = "wechat-slug=";
+
+/// Default pause between two deletions of a bulk deletion.
+const DEFAULT_DELETE_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Material cache settings, taken from the [`Config`] of the HTTP client.
+#[derive(Debug, Clone, Copy)]
+struct MaterialCachePolicy {
+    /// Whether materials are cached at all
+    enabled: bool,
+    /// How long a cached material is trusted
+    ttl: Duration,
+    /// Maximum number of cached materials
+    max_entries: usize,
+}
+
+impl MaterialCachePolicy {
+    fn from_config(config: &Config) -> Self {
+        Self {
+            enabled: config.cache.enable_material_cache,
+            ttl: config.cache_ttl(),
+            max_entries: config.performance.max_cache_entries,
+        }
+    }
+}
+
+/// Material cache file: app ID → image hash → material and the UNIX time it was cached.
+///
+/// Materials only exist in the account they were uploaded to, so every account keeps
+/// its own entries and accounts can share a file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MaterialCacheFile {
+    #[serde(default)]
+    accounts: BTreeMap<String, BTreeMap<String, PersistedMaterial>>,
+}
+
+/// Material in the cache file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PersistedMaterial {
+    media_id: String,
+    url: String,
+    cached_at: u64,
+}
+
+/// Loads the material cache from [`CacheConfig::material_cache_path`] on first use, and
+/// saves it back on shutdown or when the last uploader sharing it is dropped.
+///
+/// Saving merges with the entries in the file, so processes sharing a cache file keep
+/// each other's materials.
+///
+/// [`CacheConfig::material_cache_path`]: crate::config::CacheConfig::material_cache_path
+#[derive(Debug)]
+struct CachePersistence {
+    path: PathBuf,
+    /// App ID of the account whose materials are cached
+    app_id: String,
+    cache: Arc<RwLock<HashMap<String, CachedMaterial>>>,
+    policy: MaterialCachePolicy,
+    /// Set once the file was read into `cache`
+    loaded: tokio::sync::OnceCell<()>,
+    /// Hashes of materials deleted since loading, dropped from the file when merging
+    removed: std::sync::Mutex<HashSet<String>>,
+}
+
+impl CachePersistence {
+    /// Reads the cache file into the cache once, skipping entries older than the TTL.
+    /// A missing file loads nothing.
+    async fn load(&self) {
+        self.loaded
+            .get_or_init(|| async {
+                let bytes = match fs::read(&self.path).await {
+                    Ok(bytes) => bytes,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+                    Err(e) => {
+                        warn!(
+                            "Ignoring unreadable material cache {}: {e}",
+                            self.path.display()
+                        );
+                        return;
+                    }
+                };
+                let file: MaterialCacheFile = match serde_json::from_slice(&bytes) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        warn!(
+                            "Ignoring unreadable material cache {}: {e}",
+                            self.path.display()
+                        );
+                        return;
+                    }
+                };
+
+                let materials = file.accounts.get(&self.app_id).cloned().unwrap_or_default();
+                let mut cache = self.cache.write().await;
+                let mut loaded = 0;
+                for (hash, persisted) in materials {
+                    let age = Duration::from_secs(unix_now().saturating_sub(persisted.cached_at));
+                    if age > self.policy.ttl || cache.contains_key(&hash) {
+                        continue;
+                    }
+
+                    let cached = CachedMaterial {
+                        material: MaterialItem {
+                            media_id: persisted.media_id,
+                            name: hash.clone(),
+                            update_time: persisted.cached_at,
+                            url: persisted.url,
+                        },
+                        cached_at: Instant::now().checked_sub(age).unwrap_or_else(Instant::now),
+                    };
+                    cache.insert(hash, cached);
+                    loaded += 1;
+                }
+                debug!(
+                    "Loaded {loaded} cached materials from {}",
+                    self.path.display()
+                );
+            })
+            .await;
+    }
+
+    /// Keeps deleted materials out of the file when the cache is next saved.
+    fn forget(&self, hashes: impl IntoIterator<Item = String>) {
+        self.removed.lock().unwrap().extend(hashes);
+    }
+
+    /// Captures what to write for the current contents of the cache.
+    fn snapshot(&self, cache: &HashMap<String, CachedMaterial>) -> CacheSnapshot {
+        CacheSnapshot {
+            path: self.path.clone(),
+            app_id: self.app_id.clone(),
+            materials: cache
+                .iter()
+                .map(|(hash, cached)| {
+                    let persisted = PersistedMaterial {
+                        media_id: cached.material.media_id.clone(),
+                        url: cached.material.url.clone(),
+                        cached_at: cached.material.update_time,
+                    };
+                    (hash.clone(), persisted)
+                })
+                .collect(),
+            removed: self.removed.lock().unwrap().clone(),
+            policy: self.policy,
+        }
+    }
+
+    /// Writes the cache file, merged with the entries already in it.
+    async fn save(&self) -> Result<()> {
+        self.load().await;
+        let snapshot = self.snapshot(&*self.cache.read().await);
+        snapshot.write().await
+    }
+}
+
+impl Drop for CachePersistence {
+    fn drop(&mut self) {
+        // Nothing can have changed if the file was never read
+        if self.loaded.get().is_none() {
+            return;
+        }
+        let Ok(cache) = self.cache.try_read() else {
+            warn!(
+                "Material cache is locked, not saving it to {}",
+                self.path.display()
+            );
+            return;
+        };
+        let snapshot = self.snapshot(&cache);
+
+        // Keep file I/O off the executor when dropped inside a runtime
+        let save = move || {
+            if let Err(e) = snapshot.write_blocking() {
+                warn!("Failed to save material cache: {e}");
+            }
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => drop(handle.spawn_blocking(save)),
+            Err(_) => save(),
+        }
+    }
+}
+
+/// Material cache entries of an account, ready to be merged into the cache file.
+#[derive(Debug)]
+struct CacheSnapshot {
+    path: PathBuf,
+    app_id: String,
+    materials: BTreeMap<String, PersistedMaterial>,
+    removed: HashSet<String>,
+    policy: MaterialCachePolicy,
+}
+
+impl CacheSnapshot {
+    /// Merges the snapshot into the contents of the cache file, if any.
+    ///
+    /// Entries of other accounts are kept as they are. Entries of this account that
+    /// were deleted or are older than the TTL are dropped, the snapshot's entries win
+    /// over the file's, and only the newest `max_entries` are kept.
+    fn merge(&self, existing: Option<&[u8]>) -> Result<Vec<u8>> {
+        let mut file: MaterialCacheFile = existing
+            .and_then(|bytes| match serde_json::from_slice(bytes) {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    warn!(
+                        "Replacing unreadable material cache {}: {e}",
+                        self.path.display()
+                    );
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        let now = unix_now();
+        let ttl = self.policy.ttl.as_secs();
+        let materials = file.accounts.entry(self.app_id.clone()).or_default();
+        materials.retain(|hash, persisted| {
+            !self.removed.contains(hash) && now.saturating_sub(persisted.cached_at) <= ttl
+        });
+        materials.extend(self.materials.clone());
+
+        if materials.len() > self.policy.max_entries {
+            let mut by_age: Vec<_> = materials
+                .iter()
+                .map(|(hash, persisted)| (persisted.cached_at, hash.clone()))
+                .collect();
+            by_age.sort();
+            let excess = materials.len() - self.policy.max_entries;
+            for (_, hash) in by_age.into_iter().take(excess) {
+                materials.remove(&hash);
+            }
+        }
+
+        Ok(serde_json::to_vec_pretty(&file)?)
+    }
+
+    /// Path of the file written before it replaces the cache file, unique per process.
+    fn tmp_path(&self) -> PathBuf {
+        self.path
+            .with_extension(format!("{}.tmp", std::process::id()))
+    }
+
+    fn write_error(&self, e: std::io::Error) -> WeChatError {
+        WeChatError::file_error(self.path.display().to_string(), e.to_string())
+    }
+
+    /// Merges the snapshot into the cache file, replacing it atomically.
+    async fn write(self) -> Result<()> {
+        let existing = match fs::read(&self.path).await {
+            Ok(bytes) => Some(bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(self.write_error(e)),
+        };
+        let json = self.merge(existing.as_deref())?;
+
+        if let Some(parent) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(|e| self.write_error(e))?;
+        }
+        let tmp_path = self.tmp_path();
+        fs::write(&tmp_path, json)
+            .await
+            .map_err(|e| self.write_error(e))?;
+        fs::rename(&tmp_path, &self.path)
+            .await
+            .map_err(|e| self.write_error(e))?;
+
+        debug!(
+            "Saved {} cached materials to {}",
+            self.materials.len(),
+            self.path.display()
+        );
+        Ok(())
+    }
+
+    /// Like [`write`](Self::write), blocking the current thread.
+    fn write_blocking(self) -> Result<()> {
+        let existing = match std::fs::read(&self.path) {
+            Ok(bytes) => Some(bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(self.write_error(e)),
+        };
+        let json = self.merge(existing.as_deref())?;
 
-/// Cache TTL for material lookups (5 minutes)
-const MATERIAL_CACHE_TTL: Duration = Duration::from_secs(300);
+        if let Some(parent) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| self.write_error(e))?;
+        }
+        let tmp_path = self.tmp_path();
+        std::fs::write(&tmp_path, json).map_err(|e| self.write_error(e))?;
+        std::fs::rename(&tmp_path, &self.path).map_err(|e| self.write_error(e))?;
+
+        debug!(
+            "Saved {} cached materials to {}",
+            self.materials.len(),
+            self.path.display()
+        );
+        Ok(())
+    }
+}
 
-/// Maximum number of cached materials
-const MAX_CACHE_SIZE: usize = 1000;
+/// Gets the current UNIX time in seconds.
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
 
 /// Cached material entry with timestamp
 #[derive(Debug, Clone)]
@@ -140,17 +455,84 @@ impl CachedMaterial {
         }
     }
 
-    fn is_expired(&self) -> bool {
-        self.cached_at.elapsed() > MATERIAL_CACHE_TTL
+    fn is_expired(&self, ttl: Duration) -> bool {
+        self.cached_at.elapsed() > ttl
     }
 }
 
-/// Maximum file size for images (10 MB)
-const MAX_IMAGE_SIZE: u64 = 10 * 1024 * 1024;
+/// Per-hash locks making sure only one upload of the same image content is in flight.
+///
+/// Shared by all clones of an [`ImageUploader`], so identical images referenced by
+/// different documents uploaded concurrently through one client are uploaded once: the
+/// first task uploads, the others wait and then find the result in the material cache.
+#[derive(Debug, Default, Clone)]
+struct InFlightUploads(Arc<std::sync::Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>);
+
+impl InFlightUploads {
+    /// Waits until no other upload of `hash` is in flight and claims it.
+    async fn lock(&self, hash: &str) -> InFlightGuard {
+        let lock = Arc::clone(self.0.lock().unwrap().entry(hash.to_string()).or_default());
+        let guard = lock.lock_owned().await;
+
+        InFlightGuard {
+            uploads: self.clone(),
+            hash: hash.to_string(),
+            guard: Some(guard),
+        }
+    }
+}
+
+/// Claim on an in-flight upload, released on drop.
+struct InFlightGuard {
+    uploads: InFlightUploads,
+    hash: String,
+    guard: Option<tokio::sync::OwnedMutexGuard<()>>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.guard.take();
+
+        // Forget the lock once nobody holds or waits for it
+        let mut locks = self.uploads.0.lock().unwrap();
+        if locks
+            .get(&self.hash)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.hash);
+        }
+    }
+}
+
+/// Image content waiting to be uploaded as a material.
+enum MaterialBody {
+    /// Content held in memory
+    Memory(Vec<u8>),
+    /// Content in a file, streamed from disk when uploaded
+    File(PathBuf),
+}
+
+/// A remote image downloaded to a temporary file, released on drop.
+struct TempImage {
+    path: PathBuf,
+    download: FileDownload,
+    temp: TempFiles,
+    /// Whether the image was uploaded, for the cleanup policy
+    uploaded: bool,
+}
+
+impl Drop for TempImage {
+    fn drop(&mut self) {
+        self.temp.release(&self.path, self.uploaded);
+    }
+}
 
 /// Maximum file size for streaming downloads (50 MB)
 const MAX_DOWNLOAD_SIZE: u64 = 50 * 1024 * 1024;
 
+/// Default time allowed for a single image, including download, retries and upload
+const DEFAULT_IMAGE_TIMEOUT: Duration = Duration::from_secs(120);
+
 /// Represents the result of an image upload operation.
 #[derive(Debug, Clone)]
 pub struct UploadResult {
@@ -162,27 +544,75 @@ pub struct UploadResult {
     pub url: String,
 }
 
+/// Represents the result of uploading a cover image.
+#[derive(Debug, Clone)]
+pub struct CoverUpload {
+    /// WeChat media ID for the cover (used as `thumb_media_id`)
+    pub media_id: String,
+    /// WeChat URL for the cover image
+    pub url: String,
+    /// Whether an existing material was reused instead of uploading again
+    pub reused: bool,
+}
+
+/// Outcome of uploading image data as permanent material.
+#[derive(Debug, Clone)]
+struct MaterialUpload {
+    media_id: String,
+    url: String,
+    reused: bool,
+}
+
 /// Represents a WeChat article for draft creation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Article {
     /// Article title
     pub title: String,
     /// Article author
+    #[serde(default)]
     pub author: String,
     /// Article content (HTML)
+    #[serde(default)]
     pub content: String,
     /// Content source URL (optional)
+    #[serde(default)]
     pub content_source_url: Option<String>,
     /// Digest (summary) of the article
+    #[serde(default)]
     pub digest: String,
     /// Show cover picture in content (0: no, 1: yes)
+    #[serde(default)]
     pub show_cover_pic: u8,
     /// Thumb media ID for cover image
+    #[serde(default)]
     pub thumb_media_id: Option<String>,
     /// Need open comment (0: no, 1: yes)
+    #[serde(default)]
     pub need_open_comment: u8,
     /// Only fans can comment (0: no, 1: yes)
+    #[serde(default)]
     pub only_fans_can_comment: u8,
+    /// Article type: `news` for regular articles or `newspic` for image posts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub article_type: Option<String>,
+    /// Crop of the cover for the 2.35:1 thumbnail, as `X1_Y1_X2_Y2` ratios
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pic_crop_235_1: Option<String>,
+    /// Crop of the cover for the 1:1 thumbnail, as `X1_Y1_X2_Y2` ratios
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pic_crop_1_1: Option<String>,
+    /// Article URL, as returned by the draft APIs (never sent when writing drafts)
+    #[serde(default, skip_serializing)]
+    pub url: Option<String>,
+    /// Cover image URL, as returned by the draft APIs (never sent when writing drafts)
+    #[serde(default, skip_serializing)]
+    pub thumb_url: Option<String>,
+    /// Whether the article was deleted, as returned by the draft APIs (never sent)
+    #[serde(default, skip_serializing)]
+    pub is_deleted: Option<bool>,
+    /// Fields not modeled above, kept so that newer API fields survive a round trip
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 impl Article {
@@ -198,6 +628,13 @@ impl Article {
             thumb_media_id: None,
             need_open_comment: 0,
             only_fans_can_comment: 0,
+            article_type: None,
+            pic_crop_235_1: None,
+            pic_crop_1_1: None,
+            url: None,
+            thumb_url: None,
+            is_deleted: None,
+            extra: serde_json::Map::new(),
         }
     }
 
@@ -231,6 +668,48 @@ impl Article {
         self.content_source_url = Some(url);
         self
     }
+
+    /// Sets the article type (`news` or `newspic`).
+    pub fn with_article_type(mut self, article_type: String) -> Self {
+        self.article_type = Some(article_type);
+        self
+    }
+
+    /// Sets the cover crops for the 2.35:1 and 1:1 thumbnails.
+    pub fn with_cover_crops(mut self, crop_235_1: String, crop_1_1: String) -> Self {
+        self.pic_crop_235_1 = Some(crop_235_1);
+        self.pic_crop_1_1 = Some(crop_1_1);
+        self
+    }
+
+    /// Stores the document slug in the fragment of the source URL, so the draft can be
+    /// found by [`DraftManager::find_draft_by_slug`] after its title changed.
+    ///
+    /// WeChat has no field for it, so the slug is only stored when the article has a
+    /// source URL without a fragment of its own; otherwise a warning is logged and the
+    /// draft can only be found by title.
+    pub fn with_slug(mut self, slug: &str) -> Self {
+        match &mut self.content_source_url {
+            Some(url) if !url.is_empty() && !url.contains('#') => {
+                url.push('#');
+                url.push_str(SLUG_FRAGMENT);
+                url.push_str(slug);
+            }
+            _ => warn!(
+                "Not storing slug '{slug}' of '{}': it needs a source URL without a fragment",
+                self.title
+            ),
+        }
+        self
+    }
+
+    /// Gets the document slug stored by [`Article::with_slug`].
+    pub fn slug(&self) -> Option<&str> {
+        let (_, fragment) = self.content_source_url.as_deref()?.split_once('#')?;
+        fragment
+            .strip_prefix(SLUG_FRAGMENT)
+            .filter(|slug| !slug.is_empty())
+    }
 }
 
 /// Request body for creating a draft.
@@ -239,18 +718,53 @@ struct DraftRequest {
     articles: Vec<Article>,
 }
 
+/// What to do when a draft with the same title as the uploaded article exists.
+///
+/// Drafts identified by media ID (the frontmatter `wechat_media_id` or the tracked
+/// state) are always updated; the policy only applies to drafts matched by
+/// [slug](Article::with_slug) or title.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExistingDraftPolicy {
+    /// Update the existing draft (default)
+    #[default]
+    UpdateByTitle,
+    /// Always create a new draft, for accounts that reuse titles
+    AlwaysCreateNew,
+    /// Fail with [`WeChatError::DraftExists`], for automation that must never overwrite
+    Error,
+}
+
+/// A draft as last written by this client, used to detect later remote edits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncedDraft {
+    /// Media ID of the draft
+    pub media_id: String,
+    /// The draft's `update_time` right after the write, if it could be determined
+    pub update_time: Option<u64>,
+    /// URL of the draft's first article, if it could be determined
+    pub url: Option<String>,
+}
+
 /// Draft information from WeChat API.
 #[derive(Debug, Deserialize)]
 pub struct DraftInfo {
     pub media_id: String,
     pub content: DraftContent,
+    #[serde(default)]
     pub update_time: u64,
+    /// Fields not modeled above
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Content of a draft.
 #[derive(Debug, Deserialize)]
 pub struct DraftContent {
     pub news_item: Vec<Article>,
+    /// Fields not modeled above
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 /// List drafts response.
@@ -270,6 +784,110 @@ pub struct MaterialItem {
     pub url: String,
 }
 
+impl MaterialItem {
+    /// Whether the material was uploaded by the SDK, which puts the BLAKE3 hash of the
+    /// content into file names (see [`MaterialNaming`]), rather than by a person.
+    pub fn is_content_addressed(&self) -> bool {
+        name_hash(&self.name).is_some()
+    }
+}
+
+/// Length of the shortened content hash in material names other than
+/// [`MaterialNaming::Hash`].
+pub const SHORT_HASH_LEN: usize = 12;
+
+/// Separator between the readable part of a material name and its short hash. Readable
+/// parts never contain it, since runs of `-` are collapsed, so a name like
+/// `photo-2024-abcdef012345.jpg` given by a person isn't mistaken for an SDK upload.
+const HASH_SEPARATOR: &str = "--";
+
+/// How uploaded images are named in the media library.
+///
+/// Every name carries the BLAKE3 hash of the image, in full or shortened to
+/// [`SHORT_HASH_LEN`] characters after a `--`, so that existing materials are found and
+/// reused whichever strategy they were uploaded with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaterialNaming {
+    /// `<hash>.<ext>` (default)
+    #[default]
+    Hash,
+    /// `<file name>--<short hash>.<ext>`, e.g. `diagram--3f2a9c1b04de.png`
+    OriginalName,
+    /// `<document slug>-<index>--<short hash>.<ext>`, numbering the images of a document
+    /// from 1, and `<document slug>-cover--<short hash>.<ext>` for its cover
+    SlugIndex,
+}
+
+impl MaterialNaming {
+    /// Gets the file name of a material whose content has the BLAKE3 hash `hash`.
+    ///
+    /// `label` is the readable part of the name, such as the file name; without one
+    /// (always under [`Hash`](Self::Hash)) the material is named after the hash alone.
+    pub fn file_name(self, label: Option<&str>, hash: &str, extension: &str) -> String {
+        match label.map(sanitize_label).filter(|label| !label.is_empty()) {
+            Some(label) if self != MaterialNaming::Hash => {
+                let short_hash = hash.get(..SHORT_HASH_LEN).unwrap_or(hash);
+                format!("{label}{HASH_SEPARATOR}{short_hash}.{extension}")
+            }
+            _ => format!("{hash}.{extension}"),
+        }
+    }
+}
+
+impl std::str::FromStr for MaterialNaming {
+    type Err = WeChatError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "hash" => Ok(MaterialNaming::Hash),
+            "original_name" | "original-name" => Ok(MaterialNaming::OriginalName),
+            "slug_index" | "slug-index" => Ok(MaterialNaming::SlugIndex),
+            _ => Err(WeChatError::config_error(format!(
+                "Unknown material naming '{s}', expected 'hash', 'original_name' or 'slug_index'"
+            ))),
+        }
+    }
+}
+
+/// Maximum number of characters of the readable part of a material name.
+const MAX_LABEL_CHARS: usize = 40;
+
+/// Makes a file name or slug safe for a material name: letters, digits, `_` and `-`
+/// are kept and runs of anything else become a single `-`.
+fn sanitize_label(label: &str) -> String {
+    let mut sanitized = String::new();
+    for c in label.chars() {
+        if c.is_alphanumeric() || c == '_' {
+            sanitized.push(c);
+        } else if !sanitized.ends_with('-') {
+            sanitized.push('-');
+        }
+    }
+    sanitized
+        .trim_matches('-')
+        .chars()
+        .take(MAX_LABEL_CHARS)
+        .collect::<String>()
+        .trim_end_matches('-')
+        .to_string()
+}
+
+/// Gets the content hash in a material name given by the SDK: the full hash, or the
+/// shortened one after the [`HASH_SEPARATOR`].
+fn name_hash(name: &str) -> Option<&str> {
+    let stem = name.split_once('.').map_or(name, |(stem, _)| stem);
+    let hash = match stem.rsplit_once(HASH_SEPARATOR) {
+        Some((label, hash)) if !label.is_empty() && hash.len() == SHORT_HASH_LEN => hash,
+        None if stem.len() == 64 => stem,
+        _ => return None,
+    };
+    // BLAKE3 hashes are written in lowercase hex
+    hash.bytes()
+        .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        .then_some(hash)
+}
+
 /// List materials response.
 #[derive(Debug, Deserialize)]
 pub struct MaterialListResponse {
@@ -278,28 +896,329 @@ pub struct MaterialListResponse {
     pub item: Vec<MaterialItem>,
 }
 
+/// Options of the bulk deletion helpers, such as [`DraftManager::delete_drafts_matching`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeleteOptions {
+    /// Whether to only report what would be deleted
+    pub dry_run: bool,
+    /// Pause between two deletions, on top of any pacing configured for the quota class
+    pub interval: Duration,
+}
+
+impl Default for DeleteOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            interval: DEFAULT_DELETE_INTERVAL,
+        }
+    }
+}
+
+impl DeleteOptions {
+    /// Sets whether to only report what would be deleted.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Sets the pause between two deletions.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+/// A draft or material selected by a bulk deletion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletionTarget {
+    /// Media ID of the draft or material
+    pub media_id: String,
+    /// Title of the draft's first article, or the material's file name
+    pub name: String,
+    /// Last update as a Unix timestamp
+    pub update_time: u64,
+}
+
+/// Outcome of a bulk deletion.
+#[derive(Debug, Clone, Default)]
+pub struct DeletionReport {
+    /// Whether this was a dry run that deleted nothing
+    pub dry_run: bool,
+    /// Items deleted, or that would be deleted in a dry run
+    pub deleted: Vec<DeletionTarget>,
+    /// Items whose deletion failed after retries, with the error
+    pub failed: Vec<(DeletionTarget, String)>,
+}
+
+impl std::fmt::Display for DeletionReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action = if self.dry_run {
+            "Would delete"
+        } else {
+            "Deleted"
+        };
+        for target in &self.deleted {
+            writeln!(f, "{action} {} ({})", target.media_id, target.name)?;
+        }
+        for (target, error) in &self.failed {
+            writeln!(
+                f,
+                "Failed to delete {} ({}): {error}",
+                target.media_id, target.name
+            )?;
+        }
+        write!(
+            f,
+            "{action} {} item(s), {} failed",
+            self.deleted.len(),
+            self.failed.len()
+        )
+    }
+}
+
+/// Ties an error of an image's upload task to the image and its markdown line.
+///
+/// WeChat rejecting the image becomes [`ImageErrorKind::ApiRejected`]; token and other
+/// errors unrelated to the image are returned unchanged.
+fn image_task_error(error: WeChatError, path: String, line: Option<usize>) -> WeChatError {
+    match error {
+        WeChatError::WeChatApi { code, message } if !error.is_access_token_error() => {
+            WeChatError::image_error(path, ImageErrorKind::ApiRejected { code }, message)
+                .at_line(line)
+        }
+        error => error.at_line(line),
+    }
+}
+
+/// Applies image processing on the blocking thread pool, as decoding and encoding
+/// images is CPU-bound.
+async fn apply_processing(
+    processing: ImageProcessing,
+    data: Vec<u8>,
+    path: &str,
+) -> Result<Vec<u8>> {
+    let path = path.to_string();
+    run_blocking(move || processing.apply(data, &path)).await
+}
+
+/// Runs CPU-bound image work on the blocking thread pool.
+async fn run_blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| WeChatError::Internal {
+            message: format!("Image processing task failed: {e}"),
+        })?
+}
+
+/// Key used by [`ImageUploader::upload_images`] to collapse duplicate references.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum DedupKey {
+    /// A readable local file, by the hash of its content
+    Content(blake3::Hash),
+    /// A local file that couldn't be read, by its resolved path
+    Path(PathBuf),
+    /// A remote image or an unresolvable path, by its reference
+    Reference(String),
+}
+
+/// Waits for a permit; semaphores are closed on shutdown.
+async fn acquire(semaphore: &Semaphore) -> Result<SemaphorePermit<'_>> {
+    semaphore.acquire().await.map_err(|_| WeChatError::ShutDown)
+}
+
+/// Deletes targets one at a time, pausing between deletions.
+///
+/// A deletion that still fails after the HTTP layer's retries is reported and the
+/// remaining targets are deleted anyway. A dry run only reports the targets.
+async fn delete_each<F, Fut>(
+    targets: Vec<DeletionTarget>,
+    options: DeleteOptions,
+    mut delete: F,
+) -> DeletionReport
+where
+    F: FnMut(String) -> Fut,
+    Fut: std::future::Future<Output = Result<()>>,
+{
+    let mut report = DeletionReport {
+        dry_run: options.dry_run,
+        ..Default::default()
+    };
+    if options.dry_run {
+        for target in &targets {
+            info!("Would delete {} ({})", target.media_id, target.name);
+        }
+        report.deleted = targets;
+        return report;
+    }
+
+    for (index, target) in targets.into_iter().enumerate() {
+        if index > 0 && !options.interval.is_zero() {
+            tokio::time::sleep(options.interval).await;
+        }
+        match delete(target.media_id.clone()).await {
+            Ok(()) => report.deleted.push(target),
+            Err(e) => {
+                warn!(
+                    "Failed to delete {} ({}): {e}",
+                    target.media_id, target.name
+                );
+                report.failed.push((target, e.to_string()));
+            }
+        }
+    }
+    report
+}
+
 /// Image uploader with concurrent upload capabilities and intelligent caching.
 #[derive(Debug)]
 pub struct ImageUploader {
     http_client: Arc<WeChatHttpClient>,
     token_manager: Arc<TokenManager>,
+    /// One permit per image being processed and uploaded
     semaphore: Arc<Semaphore>,
+    /// Number of permits `semaphore` was created with, all taken on shutdown
+    upload_permits: u32,
+    /// One permit per remote image being downloaded
+    downloads: Arc<Semaphore>,
     /// Cache for material lookups by hash to avoid redundant API calls
     material_cache: Arc<RwLock<HashMap<String, CachedMaterial>>>,
+    /// TTL, size limit and whether the material cache is used at all
+    cache_policy: MaterialCachePolicy,
+    /// Saves the material cache to disk once the last clone is dropped, if configured
+    persistence: Option<Arc<CachePersistence>>,
+    /// Uploads currently in flight, by content hash
+    in_flight: InFlightUploads,
+    /// Processing applied to images before upload
+    processing: ImageProcessing,
+    /// Formats accepted for upload
+    format_policy: ImageFormatPolicy,
+    /// Store of previously processed images
+    asset_store: Option<AssetStore>,
+    /// Upper bound on the wall time spent on a single image, once it holds its permits
+    image_timeout: Duration,
+    /// Where remote images are downloaded to
+    temp: TempFiles,
+    /// How uploaded images are named
+    naming: MaterialNaming,
+    /// Slug of the document the images belong to, for [`MaterialNaming::SlugIndex`]
+    document_slug: Option<String>,
 }
 
 impl ImageUploader {
     /// Creates a new image uploader.
+    ///
+    /// The material cache follows the cache settings of the HTTP client's [`Config`]
+    /// (`performance.cache_ttl_minutes`, `performance.max_cache_entries` and
+    /// `cache.enable_material_cache`). With `cache.material_cache_path` set, the cache is
+    /// loaded from that file on first use and saved back on [`shutdown`](Self::shutdown)
+    /// or when the uploader and all its clones are dropped. The file keeps the materials
+    /// of each app ID apart and saving merges with its contents, so it can be shared by
+    /// accounts and processes. Remote images are downloaded to the temporary directory
+    /// of `temp`.
     pub fn new(http_client: Arc<WeChatHttpClient>, token_manager: Arc<TokenManager>) -> Self {
+        let cache_policy = MaterialCachePolicy::from_config(http_client.config());
+        let performance = &http_client.config().performance;
+        let max_uploads = if performance.enable_parallel_processing {
+            performance.max_concurrent_uploads
+        } else {
+            1
+        };
+        let semaphore = Arc::new(Semaphore::new(max_uploads));
+        let downloads = Arc::new(Semaphore::new(performance.max_concurrent_downloads));
+        let temp = TempFiles::new(&http_client.config().temp);
+        let cache_path = http_client
+            .config()
+            .cache
+            .material_cache_path
+            .clone()
+            .filter(|_| cache_policy.enabled);
+
+        let material_cache = Arc::new(RwLock::new(HashMap::new()));
+        let persistence = cache_path.map(|path| {
+            Arc::new(CachePersistence {
+                path,
+                app_id: token_manager.app_id().to_string(),
+                cache: Arc::clone(&material_cache),
+                policy: cache_policy,
+                loaded: tokio::sync::OnceCell::new(),
+                removed: std::sync::Mutex::default(),
+            })
+        });
+
         Self {
             http_client,
             token_manager,
-            semaphore: Arc::new(Semaphore::new(MAX_CONCURRENT_UPLOADS)),
-            material_cache: Arc::new(RwLock::new(HashMap::new())),
+            semaphore,
+            upload_permits: max_uploads as u32,
+            downloads,
+            material_cache,
+            cache_policy,
+            persistence,
+            in_flight: InFlightUploads::default(),
+            processing: ImageProcessing::default(),
+            format_policy: ImageFormatPolicy::default(),
+            asset_store: None,
+            image_timeout: DEFAULT_IMAGE_TIMEOUT,
+            temp,
+            naming: MaterialNaming::default(),
+            document_slug: None,
         }
     }
 
+    /// Sets the maximum time spent on a single image.
+    ///
+    /// Request timeouts apply per attempt, so retries can multiply the wall time of a
+    /// slow download; this bounds the whole download-and-upload of one image. Time spent
+    /// waiting for a download or upload slot doesn't count against it.
+    pub fn with_image_timeout(mut self, timeout: Duration) -> Self {
+        self.image_timeout = timeout;
+        self
+    }
+
+    /// Sets the processing applied to images before they are hashed and uploaded.
+    pub fn with_image_processing(mut self, processing: ImageProcessing) -> Self {
+        self.processing = processing;
+        self
+    }
+
+    /// Sets the formats accepted for upload; images in other formats are converted or
+    /// rejected before anything is uploaded.
+    pub fn with_format_policy(mut self, policy: ImageFormatPolicy) -> Self {
+        self.format_policy = policy;
+        self
+    }
+
+    /// Sets how uploaded images are named in the media library.
+    pub fn with_material_naming(mut self, naming: MaterialNaming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Sets the slug of the document the images belong to, which names them under
+    /// [`MaterialNaming::SlugIndex`].
+    pub fn with_document_slug(mut self, slug: impl Into<String>) -> Self {
+        self.document_slug = Some(slug.into());
+        self
+    }
+
+    /// Sets the store where processed images are kept, so that images processed in an
+    /// earlier run aren't processed again.
+    pub fn with_asset_store(mut self, store: AssetStore) -> Self {
+        self.asset_store = Some(store);
+        self
+    }
+
     /// Uploads multiple images concurrently.
+    ///
+    /// References to the same content (or the same remote URL) are collapsed before
+    /// any task is spawned, so each unique image is processed once and the result is
+    /// shared by every occurrence.
+    ///
+    /// The returned `Vec` always has one entry per input reference, in the same order
+    /// as `images`, regardless of the order in which the uploads complete. Use
+    /// [`upload_images_by_url`](Self::upload_images_by_url) to look results up by URL.
     pub async fn upload_images(
         &self,
         images: Vec<ImageRef>,
@@ -309,18 +1228,46 @@ impl ImageUploader {
             return Ok(Vec::new());
         }
 
-        debug!("Uploading {} images concurrently", images.len());
+        // Collapse duplicate references, remembering which unique image each one maps to
+        let mut unique_images: Vec<ImageRef> = Vec::new();
+        let mut slots = Vec::with_capacity(images.len());
+        let mut seen: HashMap<DedupKey, usize> = HashMap::new();
+        let keys = join_all(
+            images
+                .iter()
+                .map(|image_ref| Self::dedup_key(image_ref, base_path)),
+        )
+        .await;
+
+        for (image_ref, key) in images.iter().zip(keys) {
+            let slot = *seen.entry(key).or_insert_with(|| {
+                unique_images.push(image_ref.clone());
+                unique_images.len() - 1
+            });
+            slots.push(slot);
+        }
+
+        debug!(
+            "Uploading {} unique images ({} references) concurrently",
+            unique_images.len(),
+            images.len()
+        );
 
-        // Create upload tasks
-        let tasks: Vec<_> = images
+        // Create upload tasks, numbering the images from 1 for their material names
+        let tasks: Vec<_> = unique_images
             .into_iter()
-            .map(|image_ref| {
+            .enumerate()
+            .map(|(index, image_ref)| {
                 let uploader = self.clone();
                 let base_path = base_path.to_owned();
 
-                tokio::spawn(
-                    async move { uploader.upload_single_image(image_ref, &base_path).await },
-                )
+                tokio::spawn(async move {
+                    let (path, line) = (image_ref.original_url.clone(), image_ref.line);
+                    uploader
+                        .upload_single_image(image_ref, &base_path, index + 1)
+                        .await
+                        .map_err(|e| image_task_error(e, path, line))
+                })
             })
             .collect();
 
@@ -336,72 +1283,319 @@ impl ImageUploader {
         let uploads = upload_results?;
 
         info!("Successfully uploaded {} images", uploads.len());
-        Ok(uploads)
+
+        // Fan the unique results back out so every reference gets its own entry
+        let results = images
+            .into_iter()
+            .zip(slots)
+            .map(|(image_ref, slot)| UploadResult {
+                image_ref,
+                media_id: uploads[slot].media_id.clone(),
+                url: uploads[slot].url.clone(),
+            })
+            .collect();
+
+        Ok(results)
     }
 
-    /// Uploads a single image as permanent material.
-    async fn upload_single_image(
+    /// Uploads multiple images concurrently and returns the results keyed by original URL.
+    ///
+    /// References sharing an original URL map to a single entry.
+    pub async fn upload_images_by_url(
         &self,
-        image_ref: ImageRef,
+        images: Vec<ImageRef>,
         base_path: &Path,
-    ) -> Result<UploadResult> {
-        // Acquire semaphore permit to limit concurrency
-        let _permit = self
-            .semaphore
-            .acquire()
+    ) -> Result<HashMap<String, UploadResult>> {
+        let results = self.upload_images(images, base_path).await?;
+
+        Ok(results
+            .into_iter()
+            .map(|result| (result.image_ref.original_url.clone(), result))
+            .collect())
+    }
+
+    /// Runs part of the work on an image, failing once `budget` is spent.
+    async fn within<T>(
+        &self,
+        budget: Duration,
+        path: &str,
+        work: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        tokio::time::timeout(budget, work)
             .await
-            .map_err(|e| WeChatError::Internal {
-                message: format!("Semaphore error: {e}"),
-            })?;
+            .unwrap_or_else(|_| Err(self.image_timeout_error(path)))
+    }
 
-        debug!("Processing image: {}", image_ref.original_url);
+    /// Builds the error returned when an image exceeds the per-image timeout.
+    fn image_timeout_error(&self, path: impl Into<String>) -> WeChatError {
+        WeChatError::image_error(
+            path.into(),
+            ImageErrorKind::Timeout,
+            format!("Timed out after {:?}", self.image_timeout),
+        )
+    }
 
-        // Load image data
-        let image_data = if image_ref.is_local {
-            let image_path = image_ref.resolve_path(base_path)?;
-            self.load_local_image(&image_path).await?
-        } else {
-            self.download_remote_image(&image_ref.original_url).await?
+    /// Computes the key used to collapse duplicate image references.
+    ///
+    /// Local images are keyed by the BLAKE3 hash of their content, read on the blocking
+    /// pool, so the same file under different spellings (`./a.png`, `.\a.png`) or a copy
+    /// under another name is uploaded once, while a file replaced between runs is not
+    /// mistaken for the old one. Remote images are keyed by URL.
+    async fn dedup_key(image_ref: &ImageRef, base_path: &Path) -> DedupKey {
+        if !image_ref.is_local {
+            return DedupKey::Reference(image_ref.original_url.clone());
+        }
+
+        let Ok(path) = image_ref.resolve_path(base_path) else {
+            // Unresolvable paths keep their own slot and fail in the upload task
+            return DedupKey::Reference(image_ref.original_url.clone());
         };
 
-        // Use unified upload method
-        let (media_id, url) = self
-            .upload_image_as_material(image_data, &image_ref.original_url)
-            .await?;
+        let file = path.clone();
+        let hash = tokio::task::spawn_blocking(move || {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update_reader(std::fs::File::open(file)?)?;
+            std::io::Result::Ok(hasher.finalize())
+        })
+        .await;
 
-        info!(
-            "Successfully uploaded image: {} -> {} (media_id: {})",
-            image_ref.original_url, url, media_id
-        );
+        match hash {
+            Ok(Ok(hash)) => DedupKey::Content(hash),
+            // Unreadable files are keyed by path and report their error in the upload task
+            _ => DedupKey::Path(path.canonicalize().unwrap_or(path)),
+        }
+    }
+
+    /// Uploads a single image as permanent material.
+    ///
+    /// Remote images are downloaded under a download permit first, so a burst of
+    /// downloads neither holds upload slots nor exceeds the download limit. The image
+    /// timeout only counts the time spent holding the permits, not waiting for them.
+    async fn upload_single_image(
+        &self,
+        image_ref: ImageRef,
+        base_path: &Path,
+        index: usize,
+    ) -> Result<UploadResult> {
+        let url = image_ref.original_url.clone();
+        let mut budget = self.image_timeout;
+        let image = if image_ref.is_local {
+            None
+        } else {
+            let _download = acquire(&self.downloads).await?;
+            let started = Instant::now();
+            let image = self
+                .within(budget, &url, self.download_remote_image(&url))
+                .await?;
+            budget = budget.saturating_sub(started.elapsed());
+            Some(image)
+        };
+
+        // Acquire semaphore permit to limit concurrency; it is closed on shutdown
+        let _permit = acquire(&self.semaphore).await?;
+
+        let upload = self.upload_loaded_image(image_ref, image, base_path, index);
+        self.within(budget, &url, upload).await
+    }
+
+    /// Uploads a single image once its upload permit is held; `image` is the download
+    /// of a remote image.
+    async fn upload_loaded_image(
+        &self,
+        image_ref: ImageRef,
+        image: Option<TempImage>,
+        base_path: &Path,
+        index: usize,
+    ) -> Result<UploadResult> {
+        debug!("Processing image: {}", image_ref.original_url);
+        let processing = if image_ref.is_full_resolution() {
+            self.processing.full_resolution()
+        } else {
+            self.processing
+        };
+        let label = self.material_label(&image_ref.original_url, Some(index));
+
+        // Local images are loaded into memory; remote ones are streamed to a temporary
+        // file so large downloads never sit in memory as a whole
+        let material = if let Some(mut image) = image {
+            let head = &image.download.head;
+            let material = if processing.is_enabled()
+                || is_heif(head)
+                || self
+                    .format_policy
+                    .check(head, &image_ref.original_url)?
+                    .is_some()
+            {
+                // Processing and conversion need the whole image in memory anyway
+                let image_data = fs::read(&image.path).await.map_err(|e| {
+                    WeChatError::file_error(image.path.display().to_string(), e.to_string())
+                })?;
+                self.upload_image_as_material(
+                    image_data,
+                    &image_ref.original_url,
+                    processing,
+                    label.as_deref(),
+                )
+                .await?
+            } else {
+                let extension =
+                    self.get_image_extension(&image_ref.original_url, &image.download.head);
+                self.upload_material_body(
+                    image.download.hash.to_hex().to_string(),
+                    extension,
+                    MaterialBody::File(image.path.clone()),
+                    &image_ref.original_url,
+                    label.as_deref(),
+                )
+                .await?
+            };
+            image.uploaded = true;
+            material
+        } else {
+            let image_path = image_ref.resolve_path_with(base_path, &self.path_policy())?;
+            let image_data = self.load_local_image(&image_path).await?;
+            self.upload_image_as_material(
+                image_data,
+                &image_ref.original_url,
+                processing,
+                label.as_deref(),
+            )
+            .await?
+        };
+
+        info!(
+            "Successfully uploaded image: {} -> {} (media_id: {})",
+            image_ref.original_url, material.url, material.media_id
+        );
 
         Ok(UploadResult {
             image_ref,
-            media_id,
-            url,
+            media_id: material.media_id,
+            url: material.url,
         })
     }
 
+    /// Applies the image processing, reusing the stored result of an earlier run if
+    /// there is one.
+    async fn process_image(
+        &self,
+        data: Vec<u8>,
+        original_path: &str,
+        processing: ImageProcessing,
+    ) -> Result<Vec<u8>> {
+        if !processing.affects(&data) {
+            return Ok(data);
+        }
+        let Some(store) = &self.asset_store else {
+            return apply_processing(processing, data, original_path).await;
+        };
+
+        let key = AssetStore::key(&processing.asset_kind(), &data);
+        if let Some(processed) = store.get(&key).await? {
+            debug!("Reusing processed image for {original_path}");
+            return Ok(processed);
+        }
+
+        let processed = apply_processing(processing, data, original_path).await?;
+        if let Err(e) = store.put(&key, &processed).await {
+            warn!("Failed to store processed image for {original_path}: {e}");
+        }
+        Ok(processed)
+    }
+
+    /// Rules for the paths of local images, from the security configuration.
+    fn path_policy(&self) -> PathPolicy {
+        PathPolicy::from(&self.http_client.config().security)
+    }
+
+    /// Gets the readable part of the material name of an image, by the naming strategy.
+    /// `index` is the image's 1-based position in the document, `None` for the cover.
+    fn material_label(&self, original_path: &str, index: Option<usize>) -> Option<String> {
+        match self.naming {
+            MaterialNaming::Hash => None,
+            MaterialNaming::OriginalName => {
+                let path = original_path.split(['?', '#']).next().unwrap_or_default();
+                let name = path.rsplit(['/', '\\']).next().unwrap_or_default();
+                let stem = name.split_once('.').map_or(name, |(stem, _)| stem);
+                Some(utils::decode_local_reference(stem).into_owned())
+            }
+            MaterialNaming::SlugIndex => {
+                let slug = self.document_slug.as_deref().unwrap_or("image");
+                Some(match index {
+                    Some(index) => format!("{slug}-{index}"),
+                    None => format!("{slug}-cover"),
+                })
+            }
+        }
+    }
+
     /// Unified method to upload image data as permanent material with deduplication and caching.
     async fn upload_image_as_material(
         &self,
         image_data: Vec<u8>,
         original_path: &str,
-    ) -> Result<(String, String)> {
+        processing: ImageProcessing,
+        label: Option<&str>,
+    ) -> Result<MaterialUpload> {
+        let image_data = self
+            .process_image(image_data, original_path, processing)
+            .await?;
+        let (image_data, extension) = match self.format_policy.check(&image_data, original_path)? {
+            Some(format) => {
+                debug!("Converting {original_path} to {format}");
+                let path = original_path.to_string();
+                let converted = run_blocking(move || {
+                    image_processing::convert_image(&image_data, format, &path)
+                })
+                .await?;
+                (converted, format.extension().to_string())
+            }
+            None => {
+                let extension = self.get_image_extension(original_path, &image_data);
+                (image_data, extension)
+            }
+        };
+
         // Calculate BLAKE3 hash of the image content
-        let hash = blake3::hash(&image_data);
-        let hash_str = hash.to_hex().to_string();
+        let hash_str = blake3::hash(&image_data).to_hex().to_string();
+
+        self.upload_material_body(
+            hash_str,
+            extension,
+            MaterialBody::Memory(image_data),
+            original_path,
+            label,
+        )
+        .await
+    }
+
+    /// Uploads image content with a known hash as permanent material, reusing an
+    /// existing material with the same hash if there is one. New materials are named by
+    /// the naming strategy, with `label` as their readable part.
+    async fn upload_material_body(
+        &self,
+        hash_str: String,
+        extension: String,
+        body: MaterialBody,
+        original_path: &str,
+        label: Option<&str>,
+    ) -> Result<MaterialUpload> {
         debug!("Image hash: {hash_str}");
 
+        // Wait for any concurrent upload of the same content; it will have cached its result
+        let _in_flight = self.in_flight.lock(&hash_str).await;
+
         // Check cache first for performance optimization
-        {
-            let cache = self.material_cache.read().await;
+        if self.cache_policy.enabled {
+            let cache = self.materials().await.read().await;
             if let Some(cached) = cache.get(&hash_str) {
-                if !cached.is_expired() {
+                if !cached.is_expired(self.cache_policy.ttl) {
                     debug!("Cache hit for hash: {hash_str}");
-                    return Ok((
-                        cached.material.media_id.clone(),
-                        cached.material.url.clone(),
-                    ));
+                    return Ok(MaterialUpload {
+                        media_id: cached.material.media_id.clone(),
+                        url: cached.material.url.clone(),
+                        reused: true,
+                    });
                 } else {
                     debug!("Cache entry expired for hash: {hash_str}");
                 }
@@ -414,46 +1608,44 @@ impl ImageUploader {
             info!("Image already exists with hash {hash_str}, reusing media_id: {media_id}");
 
             // Cache the found material for future lookups
-            {
-                let mut cache = self.material_cache.write().await;
-                let material_item = MaterialItem {
-                    media_id: media_id.clone(),
-                    name: hash_str.clone(),
-                    update_time: std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs(),
-                    url: existing_url.clone(),
-                };
-                cache.insert(hash_str.clone(), CachedMaterial::new(material_item));
-                debug!("Cached found material for hash: {hash_str}");
-            }
+            self.cache_material(&hash_str, &media_id, &existing_url)
+                .await;
 
-            return Ok((media_id, existing_url));
+            return Ok(MaterialUpload {
+                media_id,
+                url: existing_url,
+                reused: true,
+            });
         }
 
-        // Use hash as filename with appropriate extension
-        let extension = self.get_image_extension(original_path, &image_data);
-        let filename = format!("{hash_str}.{extension}");
+        let filename = self.naming.file_name(label, &hash_str, &extension);
         debug!("Uploading new image as permanent material with filename: {filename}");
 
         // Upload as permanent material
         let access_token = self.token_manager.get_access_token().await?;
-        let response = self
-            .http_client
-            .upload_material(&access_token, "image", image_data, &filename)
-            .await?;
+        let response = match body {
+            MaterialBody::Memory(data) => {
+                self.http_client
+                    .upload_material(&access_token, "image", data, &filename)
+                    .await?
+            }
+            MaterialBody::File(path) => {
+                self.http_client
+                    .upload_material_file(&access_token, "image", &path, &filename)
+                    .await?
+            }
+        };
 
         // Parse response - handle both direct and wrapped response formats
-        let response_text = response.text().await?;
+        let response_body = response.bytes().await?;
         let material = if let Ok(direct_response) =
-            serde_json::from_str::<MaterialUploadResponse>(&response_text)
+            serde_json::from_slice::<MaterialUploadResponse>(&response_body)
         {
             direct_response
         } else {
             // If that fails, try parsing as standard WeChat error response
             let upload_response: WeChatResponse<MaterialUploadResponse> =
-                serde_json::from_str(&response_text)?;
+                http::parse_json(&response_body)?;
             upload_response.into_result()?
         };
 
@@ -463,56 +1655,65 @@ impl ImageUploader {
         );
 
         // Cache the successful upload for future lookups
-        {
-            let mut cache = self.material_cache.write().await;
-
-            // Implement LRU eviction if cache is full
-            if cache.len() >= MAX_CACHE_SIZE {
-                // Remove 10% of oldest entries
-                let remove_count = MAX_CACHE_SIZE / 10;
-                let mut to_remove = Vec::with_capacity(remove_count);
-
-                for (hash, cached) in cache.iter() {
-                    to_remove.push((hash.clone(), cached.cached_at));
-                    if to_remove.len() >= remove_count {
-                        break;
-                    }
-                }
+        self.cache_material(&hash_str, &material.media_id, &material.url)
+            .await;
 
-                // Sort by timestamp and remove oldest
-                to_remove.sort_by_key(|(_, timestamp)| *timestamp);
-                for (hash, _) in to_remove.into_iter().take(remove_count) {
-                    cache.remove(&hash);
-                }
+        Ok(MaterialUpload {
+            media_id: material.media_id,
+            url: material.url,
+            reused: false,
+        })
+    }
 
-                debug!("Evicted {} old cache entries", remove_count);
-            }
+    /// Adds a material to the cache, evicting the oldest entries if it is full.
+    async fn cache_material(&self, hash_str: &str, media_id: &str, url: &str) {
+        if !self.cache_policy.enabled {
+            return;
+        }
 
-            // Add new material to cache
-            let material_item = MaterialItem {
-                media_id: material.media_id.clone(),
-                name: hash_str.clone(),
-                update_time: std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-                url: material.url.clone(),
-            };
+        let mut cache = self.materials().await.write().await;
+
+        // Implement LRU eviction if cache is full
+        let max_entries = self.cache_policy.max_entries;
+        if cache.len() >= max_entries && !cache.contains_key(hash_str) {
+            // Remove 10% of oldest entries
+            let remove_count = (max_entries / 10).max(1);
+            let mut by_age: Vec<_> = cache
+                .iter()
+                .map(|(hash, cached)| (hash.clone(), cached.cached_at))
+                .collect();
+
+            // Sort by timestamp and remove oldest
+            by_age.sort_by_key(|(_, timestamp)| *timestamp);
+            for (hash, _) in by_age.into_iter().take(remove_count) {
+                cache.remove(&hash);
+            }
 
-            cache.insert(hash_str.clone(), CachedMaterial::new(material_item));
-            debug!("Cached material for hash: {hash_str}");
+            debug!("Evicted {} old cache entries", remove_count);
         }
 
-        Ok((material.media_id, material.url))
+        // Add new material to cache
+        let material_item = MaterialItem {
+            media_id: media_id.to_string(),
+            name: hash_str.to_string(),
+            update_time: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            url: url.to_string(),
+        };
+
+        cache.insert(hash_str.to_string(), CachedMaterial::new(material_item));
+        debug!("Cached material for hash: {hash_str}");
     }
 
     /// Clears expired entries from the material cache.
     pub async fn clear_expired_cache(&self) {
-        let mut cache = self.material_cache.write().await;
+        let mut cache = self.materials().await.write().await;
         let initial_size = cache.len();
 
         cache.retain(|hash, cached| {
-            let keep = !cached.is_expired();
+            let keep = !cached.is_expired(self.cache_policy.ttl);
             if !keep {
                 debug!("Removing expired cache entry: {}", hash);
             }
@@ -527,28 +1728,136 @@ impl ImageUploader {
 
     /// Gets cache statistics for monitoring.
     pub async fn get_cache_stats(&self) -> (usize, usize) {
-        let cache = self.material_cache.read().await;
+        let cache = self.materials().await.read().await;
         let total = cache.len();
-        let expired = cache.values().filter(|c| c.is_expired()).count();
+        let expired = cache
+            .values()
+            .filter(|c| c.is_expired(self.cache_policy.ttl))
+            .count();
         (total, expired)
     }
 
+    /// Exports the material cache as image hash → material entries.
+    ///
+    /// Expired entries are included: permanent materials rarely go away, and the
+    /// entries are only trusted for another cache TTL once imported.
+    pub async fn export_material_cache(&self) -> BTreeMap<String, MaterialEntry> {
+        let cache = self.materials().await.read().await;
+        cache
+            .iter()
+            .map(|(hash, cached)| {
+                let entry = MaterialEntry {
+                    media_id: cached.material.media_id.clone(),
+                    url: cached.material.url.clone(),
+                };
+                (hash.clone(), entry)
+            })
+            .collect()
+    }
+
+    /// Saves the material cache to `cache.material_cache_path` right away, e.g. on
+    /// shutdown, merged with the entries already in the file. Does nothing if no path is
+    /// configured.
+    pub async fn save_material_cache(&self) -> Result<()> {
+        match &self.persistence {
+            Some(persistence) => persistence.save().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Gets the material cache, loading it from `cache.material_cache_path` on first use.
+    async fn materials(&self) -> &RwLock<HashMap<String, CachedMaterial>> {
+        if let Some(persistence) = &self.persistence {
+            persistence.load().await;
+        }
+        &self.material_cache
+    }
+
+    /// Waits for image uploads in progress, including ones spawned by cancelled
+    /// operations, then stops accepting new uploads and saves the material cache.
+    pub async fn shutdown(&self) -> Result<()> {
+        let performance = &self.http_client.config().performance;
+        // Downloads first: every finished download queues for an upload slot ahead of us
+        if let Ok(_downloads) = self
+            .downloads
+            .acquire_many(performance.max_concurrent_downloads as u32)
+            .await
+        {
+            self.downloads.close();
+        }
+        if let Ok(_permits) = self.semaphore.acquire_many(self.upload_permits).await {
+            self.semaphore.close();
+        }
+        self.save_material_cache().await
+    }
+
+    /// Seeds the material cache with previously exported entries.
+    ///
+    /// Entries already in the cache are kept. Returns the number of entries added, which
+    /// is always 0 if the material cache is disabled.
+    pub async fn import_material_cache(&self, materials: BTreeMap<String, MaterialEntry>) -> usize {
+        if !self.cache_policy.enabled {
+            return 0;
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let mut cache = self.materials().await.write().await;
+        let initial_size = cache.len();
+
+        for (hash, entry) in materials {
+            cache.entry(hash.clone()).or_insert_with(|| {
+                CachedMaterial::new(MaterialItem {
+                    media_id: entry.media_id,
+                    name: hash,
+                    update_time: now,
+                    url: entry.url,
+                })
+            });
+        }
+
+        cache.len() - initial_size
+    }
+
     /// Loads image data from local file with streaming and size validation.
+    ///
+    /// Files over the configured `security.max_upload_size` or with one of the
+    /// `security.blocked_extensions` are rejected.
     async fn load_local_image(&self, path: &Path) -> Result<Vec<u8>> {
+        let security = &self.http_client.config().security;
+        if let Some(extension) = path.extension().and_then(|extension| extension.to_str())
+            && security
+                .blocked_extensions
+                .iter()
+                .any(|blocked| blocked.eq_ignore_ascii_case(extension))
+        {
+            return Err(WeChatError::image_error(
+                path.display().to_string(),
+                ImageErrorKind::UnsupportedFormat,
+                format!("Files with the extension '{extension}' are blocked by the configuration"),
+            ));
+        }
+
         // Check file size before loading
-        let metadata = fs::metadata(path)
-            .await
-            .map_err(|e| WeChatError::ImageUpload {
-                path: path.display().to_string(),
-                reason: format!("Failed to get file metadata: {e}"),
-            })?;
+        let metadata = fs::metadata(path).await.map_err(|e| {
+            WeChatError::image_error(
+                path.display().to_string(),
+                ImageErrorKind::Download,
+                format!("Failed to get file metadata: {e}"),
+            )
+        })?;
 
         let file_size = metadata.len();
-        if file_size > MAX_IMAGE_SIZE {
-            return Err(WeChatError::ImageUpload {
-                path: path.display().to_string(),
-                reason: format!("File too large: {file_size} bytes (max: {MAX_IMAGE_SIZE} bytes)"),
-            });
+        let max_size = security.max_upload_size;
+        if file_size > max_size {
+            return Err(WeChatError::image_error(
+                path.display().to_string(),
+                ImageErrorKind::TooLarge,
+                format!("File too large: {file_size} bytes (max: {max_size} bytes)"),
+            ));
         }
 
         debug!(
@@ -557,23 +1866,40 @@ impl ImageUploader {
             file_size
         );
 
-        fs::read(path).await.map_err(|e| WeChatError::ImageUpload {
-            path: path.display().to_string(),
-            reason: format!("Failed to read local file: {e}"),
+        fs::read(path).await.map_err(|e| {
+            WeChatError::image_error(
+                path.display().to_string(),
+                ImageErrorKind::Download,
+                format!("Failed to read local file: {e}"),
+            )
         })
     }
 
-    /// Downloads image data from remote URL with optimized streaming and size validation.
-    async fn download_remote_image(&self, url: &str) -> Result<Vec<u8>> {
+    /// Downloads a remote image to a temporary file with size validation.
+    async fn download_remote_image(&self, url: &str) -> Result<TempImage> {
         debug!("Downloading remote image: {url}");
 
-        self.http_client
-            .download_with_limit(url, MAX_DOWNLOAD_SIZE)
+        let path = self.temp.file("image", "tmp").await?;
+        let download = self
+            .http_client
+            .download_to_file(url, MAX_DOWNLOAD_SIZE, &path)
             .await
-            .map_err(|e| WeChatError::ImageUpload {
-                path: url.to_string(),
-                reason: format!("Failed to download remote image: {e}"),
-            })
+            .map_err(|e| match e {
+                // Size limit errors keep their kind
+                WeChatError::ImageUpload { .. } => e,
+                e => WeChatError::image_error(
+                    url,
+                    ImageErrorKind::Download,
+                    format!("Failed to download remote image: {e}"),
+                ),
+            })?;
+
+        Ok(TempImage {
+            path,
+            download,
+            temp: self.temp.clone(),
+            uploaded: false,
+        })
     }
 
     /// Gets the image extension based on URL and content.
@@ -622,11 +1948,7 @@ impl ImageUploader {
 
         let response = self
             .http_client
-            .post_json_with_token(
-                "/cgi-bin/material/batchget_material",
-                &access_token,
-                &request,
-            )
+            .post_json_with_token(endpoints::MATERIAL_BATCHGET.path, &access_token, &request)
             .await
             .map_err(|e| {
                 warn!("Failed to list materials: {e}");
@@ -650,9 +1972,9 @@ impl ImageUploader {
         match materials_result {
             Ok(materials_response) => {
                 if let Ok(material_list) = materials_response.into_result() {
-                    // Check if any material name starts with our hash
+                    // Check if any material name carries our hash
                     for item in material_list.item {
-                        if item.name.starts_with(hash_str) {
+                        if name_hash(&item.name).is_some_and(|hash| hash_str.starts_with(hash)) {
                             info!(
                                 "Found existing material with hash {}: URL {} (media_id: {})",
                                 hash_str, item.url, item.media_id
@@ -671,28 +1993,170 @@ impl ImageUploader {
         Ok(None)
     }
 
+    /// Lists image materials with pagination, most recent first.
+    pub async fn list_materials(&self, offset: u32, count: u32) -> Result<Vec<MaterialItem>> {
+        debug!("Listing materials: offset={offset}, count={count}");
+
+        let request = serde_json::json!({
+            "type": "image",
+            "offset": offset,
+            "count": count
+        });
+        let access_token = self.token_manager.get_access_token().await?;
+
+        let response = self
+            .http_client
+            .post_json_with_token(endpoints::MATERIAL_BATCHGET.path, &access_token, &request)
+            .await?;
+
+        let list_response: WeChatResponse<MaterialListResponse> = http::read_json(response).await?;
+        Ok(list_response.into_result()?.item)
+    }
+
+    /// Deletes a permanent material and forgets it in the material cache.
+    pub async fn delete_material(&self, media_id: &str) -> Result<()> {
+        info!("Deleting material: {media_id}");
+
+        let request = serde_json::json!({ "media_id": media_id });
+        let access_token = self.token_manager.get_access_token().await?;
+
+        let response = self
+            .http_client
+            .post_json_with_token(endpoints::MATERIAL_DELETE.path, &access_token, &request)
+            .await?;
+
+        let delete_response: WeChatResponse<serde_json::Value> = http::read_json(response).await?;
+        delete_response.into_result()?;
+
+        let mut cache = self.materials().await.write().await;
+        let removed: Vec<String> = cache
+            .iter()
+            .filter(|(_, cached)| cached.material.media_id == media_id)
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        for hash in &removed {
+            cache.remove(hash);
+        }
+        if let Some(persistence) = &self.persistence {
+            persistence.forget(removed);
+        }
+        Ok(())
+    }
+
+    /// Deletes all image materials last updated before `cutoff`, one at a time.
+    ///
+    /// All materials are listed before the first deletion, so deleting doesn't shift the
+    /// pages still to be read. Use [`DeleteOptions::dry_run`] to see what would go first.
+    pub async fn delete_materials_older_than(
+        &self,
+        cutoff: DateTime<Utc>,
+        options: DeleteOptions,
+    ) -> Result<DeletionReport> {
+        let cutoff = cutoff.timestamp().max(0) as u64;
+
+        let targets: Vec<_> = self
+            .materials_matching(|material| material.update_time < cutoff)
+            .await?
+            .into_iter()
+            .map(|material| DeletionTarget {
+                media_id: material.media_id,
+                name: material.name,
+                update_time: material.update_time,
+            })
+            .collect();
+
+        info!("Found {} materials older than the cutoff", targets.len());
+        Ok(delete_each(targets, options, |media_id| async move {
+            self.delete_material(&media_id).await
+        })
+        .await)
+    }
+
+    /// Finds image materials whose name matches a pattern, where `*` matches any text
+    /// and `?` any single character, ignoring case (e.g. `banner-*.png`).
+    ///
+    /// Pages through the whole image library, most recent first. Names are only
+    /// meaningful for materials uploaded by hand, or by the SDK with a
+    /// [`MaterialNaming`] other than the default, which names uploads after their
+    /// content hash (see [`MaterialItem::is_content_addressed`]).
+    pub async fn find_materials_by_name(&self, pattern: &str) -> Result<Vec<MaterialItem>> {
+        let materials = self
+            .materials_matching(|material| utils::matches_glob(pattern, &material.name))
+            .await?;
+        debug!("Found {} materials matching '{pattern}'", materials.len());
+        Ok(materials)
+    }
+
+    /// Lists all image materials for which `predicate` returns true.
+    async fn materials_matching(
+        &self,
+        predicate: impl Fn(&MaterialItem) -> bool,
+    ) -> Result<Vec<MaterialItem>> {
+        let mut matches = Vec::new();
+        let mut offset = 0;
+        loop {
+            let materials = self.list_materials(offset, LIST_PAGE_SIZE).await?;
+            let page_len = materials.len() as u32;
+            matches.extend(materials.into_iter().filter(|material| predicate(material)));
+
+            if page_len < LIST_PAGE_SIZE {
+                break;
+            }
+            offset += page_len;
+        }
+        Ok(matches)
+    }
+
     /// Uploads a cover image as permanent material.
     pub async fn upload_cover_material(&self, cover_path: &Path) -> Result<String> {
+        Ok(self.upload_cover(cover_path).await?.media_id)
+    }
+
+    /// Uploads a cover image and reports whether an existing material was reused.
+    ///
+    /// The cover goes through the same hash-keyed material cache as body images, so a
+    /// cover that also appears in the article body is only uploaded once.
+    pub async fn upload_cover(&self, cover_path: &Path) -> Result<CoverUpload> {
         info!(
             "Uploading cover image as permanent material: {}",
             cover_path.display()
         );
 
-        // Load image data
-        let image_data = self.load_local_image(cover_path).await?;
+        let upload = async {
+            // Load image data
+            let image_data = self.load_local_image(cover_path).await?;
+
+            // Use unified upload method; covers are never downscaled
+            let original_path = cover_path.to_string_lossy();
+            self.upload_image_as_material(
+                image_data,
+                &original_path,
+                self.processing.full_resolution(),
+                self.material_label(&original_path, None).as_deref(),
+            )
+            .await
+        };
 
-        // Use unified upload method
-        let (media_id, _url) = self
-            .upload_image_as_material(image_data, &cover_path.to_string_lossy())
+        let material = self
+            .within(
+                self.image_timeout,
+                &cover_path.display().to_string(),
+                upload,
+            )
             .await?;
 
         info!(
-            "Successfully uploaded cover image: {} -> media_id: {}",
+            "Successfully uploaded cover image: {} -> media_id: {} (reused: {})",
             cover_path.display(),
-            media_id
+            material.media_id,
+            material.reused
         );
 
-        Ok(media_id)
+        Ok(CoverUpload {
+            media_id: material.media_id,
+            url: material.url,
+            reused: material.reused,
+        })
     }
 }
 
@@ -702,7 +2166,19 @@ impl Clone for ImageUploader {
             http_client: Arc::clone(&self.http_client),
             token_manager: Arc::clone(&self.token_manager),
             semaphore: Arc::clone(&self.semaphore),
+            upload_permits: self.upload_permits,
+            downloads: Arc::clone(&self.downloads),
             material_cache: Arc::clone(&self.material_cache),
+            cache_policy: self.cache_policy,
+            persistence: self.persistence.clone(),
+            in_flight: self.in_flight.clone(),
+            processing: self.processing,
+            format_policy: self.format_policy.clone(),
+            asset_store: self.asset_store.clone(),
+            image_timeout: self.image_timeout,
+            temp: self.temp.clone(),
+            naming: self.naming,
+            document_slug: self.document_slug.clone(),
         }
     }
 }
@@ -712,6 +2188,8 @@ impl Clone for ImageUploader {
 pub struct DraftManager {
     http_client: Arc<WeChatHttpClient>,
     token_manager: Arc<TokenManager>,
+    /// Media IDs of drafts written by this manager (or imported), keyed by title
+    draft_titles: RwLock<HashMap<String, String>>,
 }
 
 impl DraftManager {
@@ -720,43 +2198,281 @@ impl DraftManager {
         Self {
             http_client,
             token_manager,
+            draft_titles: RwLock::new(HashMap::new()),
         }
     }
 
     /// Creates a new draft with articles, or updates existing if title matches.
     pub async fn create_draft(&self, articles: Vec<Article>) -> Result<String> {
+        self.create_draft_with_policy(articles, ExistingDraftPolicy::UpdateByTitle)
+            .await
+    }
+
+    /// Creates a new draft with articles, handling a draft with the same title according
+    /// to `policy`.
+    pub async fn create_draft_with_policy(
+        &self,
+        articles: Vec<Article>,
+        policy: ExistingDraftPolicy,
+    ) -> Result<String> {
+        self.upsert_draft(articles, None, None, false, policy).await
+    }
+
+    /// Creates a draft or updates an existing one, guarding against overwriting edits
+    /// made elsewhere (e.g. in the WeChat web editor).
+    ///
+    /// The draft `known_media_id` is updated if given and still present; otherwise the
+    /// draft with the same title is. If that draft is the one recorded in `last_synced`
+    /// and its `update_time` has changed since, this fails with
+    /// [`WeChatError::DraftConflict`] unless `force` is set. Drafts matched by title are
+    /// handled according to `policy`. The returned [`SyncedDraft`] carries the draft's new
+    /// `update_time` and URL so they can be recorded.
+    pub async fn sync_draft(
+        &self,
+        articles: Vec<Article>,
+        known_media_id: Option<&str>,
+        last_synced: Option<&SyncedDraft>,
+        force: bool,
+        policy: ExistingDraftPolicy,
+    ) -> Result<SyncedDraft> {
+        let media_id = self
+            .upsert_draft(articles, known_media_id, last_synced, force, policy)
+            .await?;
+
+        // Record the remote update_time produced by this write
+        Ok(self.lookup_draft(&media_id).await)
+    }
+
+    /// Looks up the current `update_time` and article URL of a draft.
+    ///
+    /// Lookup failures are logged and leave the corresponding fields empty.
+    pub async fn lookup_draft(&self, media_id: &str) -> SyncedDraft {
+        let draft = match self.get_draft(media_id).await {
+            Ok(draft) => Some(draft),
+            Err(e) => {
+                warn!("Failed to look up draft {media_id}: {e}");
+                None
+            }
+        };
+
+        SyncedDraft {
+            media_id: media_id.to_string(),
+            update_time: draft
+                .as_ref()
+                .map(|draft| draft.update_time)
+                .filter(|&update_time| update_time > 0),
+            url: draft
+                .and_then(|draft| draft.content.news_item.into_iter().next())
+                .and_then(|article| article.url),
+        }
+    }
+
+    /// Checks whether updating the draft found by title would overwrite remote edits.
+    fn check_conflict(
+        found_media_id: &str,
+        found_update_time: u64,
+        last_synced: Option<&SyncedDraft>,
+        force: bool,
+    ) -> Result<()> {
+        let Some(SyncedDraft {
+            media_id,
+            update_time: Some(expected),
+            ..
+        }) = last_synced
+        else {
+            return Ok(());
+        };
+
+        if media_id != found_media_id || *expected == found_update_time {
+            return Ok(());
+        }
+
+        if force {
+            warn!(
+                "Draft {media_id} was modified since the last sync, overwriting because force is set"
+            );
+            return Ok(());
+        }
+
+        Err(WeChatError::DraftConflict {
+            media_id: media_id.clone(),
+            expected: *expected,
+            actual: found_update_time,
+        })
+    }
+
+    /// Exports the title → media ID mapping of drafts written by this manager.
+    pub async fn export_draft_titles(&self) -> BTreeMap<String, String> {
+        let draft_titles = self.draft_titles.read().await;
+        draft_titles
+            .iter()
+            .map(|(title, media_id)| (title.clone(), media_id.clone()))
+            .collect()
+    }
+
+    /// Seeds the title → media ID mapping used to find existing drafts.
+    ///
+    /// Titles already mapped are kept. Returns the number of mappings added.
+    pub async fn import_draft_titles(&self, drafts: BTreeMap<String, String>) -> usize {
+        let mut draft_titles = self.draft_titles.write().await;
+        let initial_size = draft_titles.len();
+
+        for (title, media_id) in drafts {
+            draft_titles.entry(title).or_insert(media_id);
+        }
+
+        draft_titles.len() - initial_size
+    }
+
+    /// Creates a draft, or updates an existing one, and remembers its media ID by title.
+    ///
+    /// A draft previously written with the same title is treated as known unless
+    /// `known_media_id` is given or `policy` says otherwise.
+    async fn upsert_draft(
+        &self,
+        articles: Vec<Article>,
+        known_media_id: Option<&str>,
+        last_synced: Option<&SyncedDraft>,
+        force: bool,
+        policy: ExistingDraftPolicy,
+    ) -> Result<String> {
         if articles.is_empty() {
             return Err(WeChatError::config_error(
                 "At least one article is required",
             ));
         }
 
+        let title = articles[0].title.clone();
+        let mapped_media_id = match policy {
+            ExistingDraftPolicy::AlwaysCreateNew => None,
+            _ => self.draft_titles.read().await.get(&title).cloned(),
+        };
+        if policy == ExistingDraftPolicy::Error
+            && known_media_id.is_none()
+            && let Some(media_id) = mapped_media_id
+        {
+            return Err(WeChatError::DraftExists { title, media_id });
+        }
+        let known_media_id = known_media_id.or(mapped_media_id.as_deref());
+
+        let media_id = self
+            .write_draft(articles, known_media_id, last_synced, force, policy)
+            .await?;
+
+        self.draft_titles
+            .write()
+            .await
+            .insert(title, media_id.clone());
+        Ok(media_id)
+    }
+
+    /// Creates a draft, or updates an existing one after a conflict check.
+    ///
+    /// A known media ID (e.g. from the markdown frontmatter) is updated directly; otherwise,
+    /// or if that draft no longer exists, the draft with the same slug or title is handled
+    /// according to `policy`.
+    async fn write_draft(
+        &self,
+        articles: Vec<Article>,
+        known_media_id: Option<&str>,
+        last_synced: Option<&SyncedDraft>,
+        force: bool,
+        policy: ExistingDraftPolicy,
+    ) -> Result<String> {
+        if let Some(media_id) = known_media_id {
+            info!("Updating known draft: {media_id}");
+
+            let written = match self.get_draft(media_id).await {
+                Ok(draft) => {
+                    if draft.update_time > 0 {
+                        Self::check_conflict(media_id, draft.update_time, last_synced, force)?;
+                    }
+                    let count = draft.content.news_item.len();
+                    self.rewrite_draft(media_id, count, articles.clone()).await
+                }
+                Err(e) => Err(e),
+            };
+            match written {
+                Ok(media_id) => return Ok(media_id),
+                Err(e) if e.is_invalid_media_id() => {
+                    warn!("Known draft {media_id} is gone ({e}), matching by title");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
         let title = &articles[0].title;
         info!("Processing draft with title: {title}");
 
-        // Check recent drafts for matching title
-        if let Some(existing_media_id) = self.find_draft_by_title(title).await? {
+        // Check recent drafts for the same slug, then for the same title
+        let existing = match (policy, articles[0].slug()) {
+            (ExistingDraftPolicy::AlwaysCreateNew, _) => None,
+            (_, Some(slug)) => match self.find_draft_by_slug(slug).await? {
+                Some(existing) => Some(existing),
+                None => self.find_draft_by_title(title).await?,
+            },
+            (_, None) => self.find_draft_by_title(title).await?,
+        };
+        if let Some(existing) = existing {
+            if policy == ExistingDraftPolicy::Error {
+                return Err(WeChatError::DraftExists {
+                    title: title.clone(),
+                    media_id: existing.media_id,
+                });
+            }
+
+            let existing_media_id = &existing.media_id;
             info!(
                 "Found existing draft with title '{title}', updating media_id: {existing_media_id}"
             );
 
+            Self::check_conflict(existing_media_id, existing.update_time, last_synced, force)?;
+
             // Update existing draft
-            self.update_draft(&existing_media_id, articles).await?;
-            return Ok(existing_media_id);
+            let count = existing.content.news_item.len();
+            return self.rewrite_draft(existing_media_id, count, articles).await;
         }
 
         // No existing draft found, create new one
         info!("No existing draft found, creating new draft");
+        self.add_draft(articles).await
+    }
+
+    /// Writes articles to an existing draft holding `count` articles, replacing the draft
+    /// with a new one if the number differs. Returns the media ID of the written draft.
+    async fn rewrite_draft(
+        &self,
+        media_id: &str,
+        count: usize,
+        articles: Vec<Article>,
+    ) -> Result<String> {
+        if count == articles.len() {
+            self.update_articles(media_id, &articles).await?;
+            return Ok(media_id.to_string());
+        }
 
+        info!(
+            "Draft {media_id} has {count} articles instead of {}, replacing it",
+            articles.len()
+        );
+        let new_media_id = self.add_draft(articles).await?;
+        if let Err(e) = self.delete_draft(media_id).await {
+            warn!("Failed to delete replaced draft {media_id}: {e}");
+        }
+        Ok(new_media_id)
+    }
+
+    /// Creates a new draft.
+    async fn add_draft(&self, articles: Vec<Article>) -> Result<String> {
         let request = DraftRequest { articles };
         let access_token = self.token_manager.get_access_token().await?;
 
         let response = self
             .http_client
-            .post_json_with_token("/cgi-bin/draft/add", &access_token, &request)
+            .post_json_with_token(endpoints::DRAFT_ADD.path, &access_token, &request)
             .await?;
 
-        let draft_response: WeChatResponse<DraftResponse> = response.json().await?;
+        let draft_response: WeChatResponse<DraftResponse> = http::read_json(response).await?;
         let draft = draft_response.into_result()?;
 
         info!(
@@ -766,7 +2482,7 @@ impl DraftManager {
         Ok(draft.media_id)
     }
 
-    /// Gets a draft by media ID.
+    /// Gets a draft by media ID, with its `update_time` if WeChat returns one.
     pub async fn get_draft(&self, media_id: &str) -> Result<DraftInfo> {
         debug!("Getting draft: {media_id}");
 
@@ -775,14 +2491,30 @@ impl DraftManager {
 
         let response = self
             .http_client
-            .post_json_with_token("/cgi-bin/draft/get", &access_token, &request)
+            .post_json_with_token(endpoints::DRAFT_GET.path, &access_token, &request)
             .await?;
 
-        let draft_response: WeChatResponse<DraftInfo> = response.json().await?;
-        draft_response.into_result()
+        // The response holds only the content and its times; the media ID is the one
+        // requested
+        let draft_response: WeChatResponse<DraftContent> = http::read_json(response).await?;
+        let mut content = draft_response.into_result()?;
+        let update_time = content
+            .extra
+            .remove("update_time")
+            .and_then(|update_time| update_time.as_u64())
+            .unwrap_or(0);
+        Ok(DraftInfo {
+            media_id: media_id.to_string(),
+            content,
+            update_time,
+            extra: serde_json::Map::new(),
+        })
     }
 
     /// Updates a draft.
+    ///
+    /// Every article of the draft is replaced, so the draft must hold as many articles
+    /// as given; WeChat can't add or remove articles of an existing draft.
     pub async fn update_draft(&self, media_id: &str, articles: Vec<Article>) -> Result<()> {
         if articles.is_empty() {
             return Err(WeChatError::config_error(
@@ -790,28 +2522,44 @@ impl DraftManager {
             ));
         }
 
+        let count = self.get_draft(media_id).await?.content.news_item.len();
+        if count != articles.len() {
+            return Err(WeChatError::config_error(format!(
+                "Draft {media_id} has {count} articles but the update has {}; \
+                 create a new draft instead",
+                articles.len()
+            )));
+        }
+
+        self.update_articles(media_id, &articles).await
+    }
+
+    /// Updates every article of a draft, by index.
+    async fn update_articles(&self, media_id: &str, articles: &[Article]) -> Result<()> {
         info!(
             "Updating draft {} with {} articles",
             media_id,
             articles.len()
         );
 
-        // According to WeChat API, the update request structure should wrap articles differently
-        let request = serde_json::json!({
-            "media_id": media_id,
-            "index": 0,
-            "articles": articles[0]  // WeChat expects a single article object, not an array
-        });
-
         let access_token = self.token_manager.get_access_token().await?;
+        for (index, article) in articles.iter().enumerate() {
+            // WeChat expects a single article object per index, not an array
+            let request = serde_json::json!({
+                "media_id": media_id,
+                "index": index,
+                "articles": article
+            });
 
-        let response = self
-            .http_client
-            .post_json_with_token("/cgi-bin/draft/update", &access_token, &request)
-            .await?;
+            let response = self
+                .http_client
+                .post_json_with_token(endpoints::DRAFT_UPDATE.path, &access_token, &request)
+                .await?;
 
-        let update_response: WeChatResponse<serde_json::Value> = response.json().await?;
-        update_response.into_result()?;
+            let update_response: WeChatResponse<serde_json::Value> =
+                http::read_json(response).await?;
+            update_response.into_result()?;
+        }
 
         info!("Successfully updated draft: {media_id}");
         Ok(())
@@ -826,42 +2574,157 @@ impl DraftManager {
 
         let response = self
             .http_client
-            .post_json_with_token("/cgi-bin/draft/delete", &access_token, &request)
+            .post_json_with_token(endpoints::DRAFT_DELETE.path, &access_token, &request)
             .await?;
 
-        let delete_response: WeChatResponse<serde_json::Value> = response.json().await?;
+        let delete_response: WeChatResponse<serde_json::Value> = http::read_json(response).await?;
         delete_response.into_result()?;
 
+        self.draft_titles
+            .write()
+            .await
+            .retain(|_, mapped| mapped != media_id);
+
         info!("Successfully deleted draft: {media_id}");
         Ok(())
     }
 
+    /// Sends a preview of a draft to a follower.
+    pub async fn preview_draft(&self, media_id: &str, openid: &str) -> Result<()> {
+        // Openids identify followers, so they stay out of the info logs
+        info!("Sending preview of draft {media_id}");
+        debug!("Preview recipient: {openid}");
+
+        let request = serde_json::json!({
+            "touser": openid,
+            "mpnews": { "media_id": media_id },
+            "msgtype": "mpnews"
+        });
+        let access_token = self.token_manager.get_access_token().await?;
+
+        let response = self
+            .http_client
+            .post_json_with_token(endpoints::MESSAGE_PREVIEW.path, &access_token, &request)
+            .await?;
+
+        let preview_response: WeChatResponse<serde_json::Value> = http::read_json(response).await?;
+        preview_response.into_result()?;
+        Ok(())
+    }
+
     /// Lists drafts with pagination.
     pub async fn list_drafts(&self, offset: u32, count: u32) -> Result<Vec<DraftInfo>> {
+        self.batchget_drafts(offset, count, false).await
+    }
+
+    /// Lists drafts with pagination, leaving out the articles' HTML if `no_content` is set.
+    async fn batchget_drafts(
+        &self,
+        offset: u32,
+        count: u32,
+        no_content: bool,
+    ) -> Result<Vec<DraftInfo>> {
         debug!("Listing drafts: offset={offset}, count={count}");
 
         let request = serde_json::json!({
             "offset": offset,
             "count": count,
-            "no_content": 0
+            "no_content": u8::from(no_content)
         });
 
         let access_token = self.token_manager.get_access_token().await?;
 
         let response = self
             .http_client
-            .post_json_with_token("/cgi-bin/draft/batchget", &access_token, &request)
+            .post_json_with_token(endpoints::DRAFT_BATCHGET.path, &access_token, &request)
             .await?;
 
-        let response_text = response.text().await?;
-
-        let list_response: WeChatResponse<DraftListResponse> =
-            serde_json::from_str(&response_text)?;
+        let list_response: WeChatResponse<DraftListResponse> = http::read_json(response).await?;
 
         let drafts = list_response.into_result()?;
         Ok(drafts.item)
     }
 
+    /// Lists all drafts, paging through the draft list.
+    pub async fn list_all_drafts(&self) -> Result<Vec<DraftInfo>> {
+        let mut all = Vec::new();
+        let mut offset = 0;
+        loop {
+            let drafts = self.list_drafts(offset, LIST_PAGE_SIZE).await?;
+            let page_len = drafts.len() as u32;
+            all.extend(drafts);
+
+            if page_len < LIST_PAGE_SIZE {
+                break;
+            }
+            offset += page_len;
+        }
+        Ok(all)
+    }
+
+    /// Deletes all drafts for which `predicate` returns true, one at a time.
+    ///
+    /// All drafts are listed before the first deletion, so deleting doesn't shift the
+    /// pages still to be read. Use [`DeleteOptions::dry_run`] to see what would go first.
+    pub async fn delete_drafts_matching(
+        &self,
+        predicate: impl Fn(&DraftInfo) -> bool,
+        options: DeleteOptions,
+    ) -> Result<DeletionReport> {
+        let targets: Vec<_> = self
+            .list_all_drafts()
+            .await?
+            .iter()
+            .filter(|draft| predicate(draft))
+            .map(|draft| DeletionTarget {
+                media_id: draft.media_id.clone(),
+                name: draft
+                    .content
+                    .news_item
+                    .first()
+                    .map(|article| article.title.clone())
+                    .unwrap_or_default(),
+                update_time: draft.update_time,
+            })
+            .collect();
+
+        info!("Found {} matching drafts", targets.len());
+        Ok(delete_each(targets, options, |media_id| async move {
+            self.delete_draft(&media_id).await
+        })
+        .await)
+    }
+
+    /// Gets the current `update_time` of drafts by media ID.
+    ///
+    /// Pages through the draft list until all drafts are found; drafts that no longer
+    /// exist are missing from the result.
+    pub async fn draft_update_times(
+        &self,
+        media_ids: &HashSet<String>,
+    ) -> Result<HashMap<String, u64>> {
+        let mut update_times = HashMap::new();
+        let mut offset = 0;
+        while update_times.len() < media_ids.len() {
+            // Only the update times are needed, not the articles' HTML
+            let drafts = self.batchget_drafts(offset, LIST_PAGE_SIZE, true).await?;
+            let page_len = drafts.len() as u32;
+            update_times.extend(
+                drafts
+                    .into_iter()
+                    .filter(|draft| media_ids.contains(&draft.media_id))
+                    .map(|draft| (draft.media_id, draft.update_time)),
+            );
+
+            if page_len < LIST_PAGE_SIZE {
+                break;
+            }
+            offset += page_len;
+        }
+
+        Ok(update_times)
+    }
+
     /// Creates URL mapping from upload results.
     pub fn create_url_mapping(&self, upload_results: &[UploadResult]) -> HashMap<String, String> {
         upload_results
@@ -871,7 +2734,7 @@ impl DraftManager {
     }
 
     /// Finds a draft by title in recent drafts.
-    async fn find_draft_by_title(&self, title: &str) -> Result<Option<String>> {
+    async fn find_draft_by_title(&self, title: &str) -> Result<Option<DraftInfo>> {
         debug!("Searching for draft with title: {title}");
 
         // List recent 20 drafts
@@ -889,21 +2752,171 @@ impl DraftManager {
                 && first_article.title == title
             {
                 info!("Found existing draft with matching title");
-                return Ok(Some(draft.media_id));
+                return Ok(Some(draft));
             }
         }
 
         debug!("No draft found with title: {title}");
         Ok(None)
     }
+
+    /// Finds a recent draft whose first article stores a document slug with
+    /// [`Article::with_slug`].
+    pub async fn find_draft_by_slug(&self, slug: &str) -> Result<Option<DraftInfo>> {
+        debug!("Searching for draft with slug: {slug}");
+
+        let drafts = match self.list_drafts(0, 20).await {
+            Ok(drafts) => drafts,
+            Err(e) => {
+                warn!("Failed to list drafts: {e}");
+                return Ok(None);
+            }
+        };
+
+        let found = drafts
+            .into_iter()
+            .find(|draft| draft.content.news_item.first().and_then(Article::slug) == Some(slug));
+        match found {
+            Some(draft) => {
+                info!("Found existing draft with matching slug");
+                Ok(Some(draft))
+            }
+            None => {
+                debug!("No draft found with slug: {slug}");
+                Ok(None)
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::auth::TokenManager;
+    use crate::test_server::serve;
     use std::sync::Arc;
 
+    #[test]
+    fn test_material_naming() {
+        let material = |name: &str| MaterialItem {
+            media_id: "media".to_string(),
+            name: name.to_string(),
+            update_time: 0,
+            url: String::new(),
+        };
+
+        let hash = blake3::hash(b"image").to_hex();
+        assert!(material(&format!("{hash}.png")).is_content_addressed());
+        assert!(!material("team-photo.jpg").is_content_addressed());
+        assert!(!material(&format!("{}.png", &hash[..32])).is_content_addressed());
+        // Hex suffixes of names given by hand aren't taken for a short hash
+        assert!(!material("photo-2024-abcdef012345.jpg").is_content_addressed());
+        assert!(!material("photo--ABCDEF012345.jpg").is_content_addressed());
+        assert!(!material("--abcdef012345.jpg").is_content_addressed());
+
+        // Readable names keep a short hash, which existing materials are matched by
+        let name = MaterialNaming::OriginalName.file_name(Some("My photo (1)"), &hash, "jpg");
+        assert_eq!(name, format!("My-photo-1--{}.jpg", &hash[..SHORT_HASH_LEN]));
+        assert!(material(&name).is_content_addressed());
+        assert!(hash.starts_with(name_hash(&name).unwrap()));
+        assert_eq!(
+            MaterialNaming::SlugIndex.file_name(Some("发布-2"), &hash, "png"),
+            format!("发布-2--{}.png", &hash[..SHORT_HASH_LEN])
+        );
+        assert_eq!(
+            MaterialNaming::Hash.file_name(Some("photo"), &hash, "png"),
+            format!("{hash}.png")
+        );
+        assert_eq!(
+            MaterialNaming::OriginalName.file_name(Some("..."), &hash, "png"),
+            format!("{hash}.png")
+        );
+        assert_eq!(
+            "slug-index".parse::<MaterialNaming>().unwrap(),
+            MaterialNaming::SlugIndex
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_each() {
+        let target = |media_id: &str| DeletionTarget {
+            media_id: media_id.to_string(),
+            name: format!("{media_id}.png"),
+            update_time: 100,
+        };
+        let targets = vec![target("a"), target("b"), target("c")];
+
+        // A dry run deletes nothing
+        let report = delete_each(
+            targets.clone(),
+            DeleteOptions::default().dry_run(true),
+            |_| async { panic!("dry run must not delete") },
+        )
+        .await;
+        assert_eq!(report.deleted, targets);
+        assert!(
+            report
+                .to_string()
+                .ends_with("Would delete 3 item(s), 0 failed")
+        );
+
+        // A failure doesn't stop the remaining deletions
+        let deleted = std::sync::Mutex::new(Vec::new());
+        let report = delete_each(
+            targets,
+            DeleteOptions::default().interval(Duration::ZERO),
+            |media_id| {
+                deleted.lock().unwrap().push(media_id.clone());
+                async move {
+                    if media_id == "b" {
+                        Err(WeChatError::WeChatApi {
+                            code: 40007,
+                            message: "invalid media_id".to_string(),
+                        })
+                    } else {
+                        Ok(())
+                    }
+                }
+            },
+        )
+        .await;
+        assert_eq!(*deleted.lock().unwrap(), ["a", "b", "c"]);
+        assert_eq!(report.deleted, vec![target("a"), target("c")]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, target("b"));
+        assert!(report.to_string().contains("Failed to delete b (b.png)"));
+    }
+
+    #[test]
+    fn test_draft_conflict_check() {
+        let synced = SyncedDraft {
+            media_id: "media_1".to_string(),
+            update_time: Some(100),
+            url: None,
+        };
+
+        // Unchanged since the last sync
+        assert!(DraftManager::check_conflict("media_1", 100, Some(&synced), false).is_ok());
+
+        // Edited remotely since the last sync
+        let err = DraftManager::check_conflict("media_1", 150, Some(&synced), false).unwrap_err();
+        assert!(matches!(
+            err,
+            WeChatError::DraftConflict {
+                expected: 100,
+                actual: 150,
+                ..
+            }
+        ));
+
+        // Force overrides the conflict
+        assert!(DraftManager::check_conflict("media_1", 150, Some(&synced), true).is_ok());
+
+        // A different draft, or nothing recorded, is not checked
+        assert!(DraftManager::check_conflict("media_2", 150, Some(&synced), false).is_ok());
+        assert!(DraftManager::check_conflict("media_1", 150, None, false).is_ok());
+    }
+
     #[tokio::test]
     async fn test_article_creation() {
         let article = Article::new(
@@ -943,6 +2956,164 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_article_slug() {
+        let article = |url: Option<&str>| {
+            let article = Article::new("T".to_string(), "A".to_string(), String::new());
+            match url {
+                Some(url) => article.with_source_url(url.to_string()),
+                None => article,
+            }
+        };
+
+        let stored = article(Some("https://blog.example.com/posts/hello")).with_slug("hello");
+        assert_eq!(
+            stored.content_source_url.as_deref(),
+            Some("https://blog.example.com/posts/hello#wechat-slug=hello")
+        );
+        assert_eq!(stored.slug(), Some("hello"));
+
+        // Nowhere to store it
+        assert_eq!(article(None).with_slug("hello").slug(), None);
+        let anchored = article(Some("https://example.com/#top")).with_slug("hello");
+        assert_eq!(
+            anchored.content_source_url.as_deref(),
+            Some("https://example.com/#top")
+        );
+        assert_eq!(anchored.slug(), None);
+    }
+
+    fn draft_manager(base_url: String) -> DraftManager {
+        let mut config = Config::default();
+        config.http.base_url = base_url;
+        let http_client = Arc::new(WeChatHttpClient::with_config(config).unwrap());
+        let token_manager = Arc::new(TokenManager::new(
+            "test_app_id",
+            "test_secret",
+            Arc::clone(&http_client),
+        ));
+        DraftManager::new(http_client, token_manager)
+    }
+
+    #[tokio::test]
+    async fn test_update_draft_updates_every_article() {
+        let article = |title: &str| Article::new(title.to_string(), "A".to_string(), String::new());
+        let (address, requests) = serve(vec![
+            r#"{"access_token":"token","expires_in":7200}"#,
+            r#"{"news_item":[{"title":"Part 1","author":"A","content":""},{"title":"Part 2","author":"A","content":""}]}"#,
+            r#"{"errcode":0,"errmsg":"ok"}"#,
+            r#"{"errcode":0,"errmsg":"ok"}"#,
+        ]);
+        let manager = draft_manager(address);
+
+        manager
+            .update_draft("draft_1", vec![article("Part 1"), article("Part 2")])
+            .await
+            .unwrap();
+        let updates = requests
+            .try_iter()
+            .filter(|request| request.contains("/cgi-bin/draft/update"))
+            .count();
+        assert_eq!(updates, 2);
+
+        // A draft with a different number of articles can't be updated in place
+        let (address, _) = serve(vec![
+            r#"{"access_token":"token","expires_in":7200}"#,
+            r#"{"news_item":[{"title":"Part 1","author":"A","content":""}]}"#,
+        ]);
+        let result = draft_manager(address)
+            .update_draft("draft_1", vec![article("Part 1"), article("Part 2")])
+            .await;
+        assert!(matches!(result, Err(WeChatError::Config { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_draft_gets_the_draft() {
+        let (address, requests) = serve(vec![
+            r#"{"access_token":"token","expires_in":7200}"#,
+            r#"{"news_item":[{"title":"Title","author":"A","content":"","url":"http://mp.weixin.qq.com/s/abc"}],"create_time":1704067100,"update_time":1704067200}"#,
+        ]);
+
+        let draft = draft_manager(address).lookup_draft("draft_1").await;
+        assert_eq!(draft.update_time, Some(1704067200));
+        assert_eq!(draft.url.as_deref(), Some("http://mp.weixin.qq.com/s/abc"));
+        let requests: Vec<String> = requests.try_iter().collect();
+        assert!(
+            requests
+                .iter()
+                .any(|request| request.contains("/cgi-bin/draft/get"))
+        );
+        assert!(!requests.iter().any(|request| request.contains("batchget")));
+    }
+
+    #[tokio::test]
+    async fn test_draft_update_times_skip_content() {
+        let (address, requests) = serve(vec![
+            r#"{"access_token":"token","expires_in":7200}"#,
+            r#"{"total_count":2,"item_count":2,"item":[
+                {"media_id":"draft_1","update_time":1704067200,"content":{"news_item":[{"title":"One"}]}},
+                {"media_id":"draft_2","update_time":1704067300,"content":{"news_item":[{"title":"Two"}]}}
+            ]}"#,
+        ]);
+
+        let media_ids = HashSet::from(["draft_2".to_string()]);
+        let update_times = draft_manager(address)
+            .draft_update_times(&media_ids)
+            .await
+            .unwrap();
+        assert_eq!(
+            update_times,
+            HashMap::from([("draft_2".to_string(), 1704067300)])
+        );
+
+        let batchget = requests
+            .try_iter()
+            .find(|request| request.contains("/cgi-bin/draft/batchget"))
+            .unwrap();
+        assert!(batchget.contains(r#""no_content":1"#));
+    }
+
+    #[test]
+    fn test_draft_api_fields() {
+        let json = serde_json::json!({
+            "media_id": "draft_1",
+            "update_time": 1704067200,
+            "content": {
+                "news_item": [{
+                    "title": "Title",
+                    "author": "Author",
+                    "content": "<p>Body</p>",
+                    "thumb_media_id": "thumb_1",
+                    "article_type": "news",
+                    "url": "http://mp.weixin.qq.com/s?__biz=abc",
+                    "thumb_url": "http://mmbiz.qpic.cn/thumb",
+                    "is_deleted": false,
+                    "future_field": { "enabled": true }
+                }],
+                "create_time": 1704067100
+            }
+        });
+
+        let draft: DraftInfo = serde_json::from_value(json).unwrap();
+        assert_eq!(draft.content.extra["create_time"], 1704067100);
+
+        let article = &draft.content.news_item[0];
+        assert_eq!(article.article_type.as_deref(), Some("news"));
+        assert_eq!(
+            article.thumb_url.as_deref(),
+            Some("http://mmbiz.qpic.cn/thumb")
+        );
+        assert_eq!(article.is_deleted, Some(false));
+        assert_eq!(article.digest, "");
+
+        // Read-only fields are not sent back, unknown ones are preserved
+        let sent = serde_json::to_value(article).unwrap();
+        assert!(sent.get("url").is_none());
+        assert!(sent.get("is_deleted").is_none());
+        assert_eq!(sent["future_field"]["enabled"], true);
+        assert_eq!(sent["article_type"], "news");
+    }
+
     #[tokio::test]
     async fn test_image_uploader_creation() {
         let http_client = Arc::new(WeChatHttpClient::new().unwrap());
@@ -953,10 +3124,35 @@ mod tests {
         ));
 
         let uploader = ImageUploader::new(http_client, token_manager);
+        let performance = Config::default().performance;
         assert_eq!(
             uploader.semaphore.available_permits(),
-            MAX_CONCURRENT_UPLOADS
+            performance.max_concurrent_uploads
         );
+        assert_eq!(
+            uploader.downloads.available_permits(),
+            performance.max_concurrent_downloads
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_without_parallel_processing() {
+        let mut config = Config::default();
+        config.performance.enable_parallel_processing = false;
+        let http_client = Arc::new(WeChatHttpClient::with_config(config).unwrap());
+        let token_manager = Arc::new(TokenManager::new(
+            "test_app_id",
+            "test_secret",
+            Arc::clone(&http_client),
+        ));
+
+        let uploader = ImageUploader::new(http_client, token_manager);
+        assert_eq!(uploader.semaphore.available_permits(), 1);
+        tokio::time::timeout(Duration::from_secs(5), uploader.shutdown())
+            .await
+            .expect("shutdown should not wait for permits that don't exist")
+            .unwrap();
+        assert!(uploader.semaphore.is_closed());
     }
 
     #[tokio::test]
@@ -995,6 +3191,566 @@ mod tests {
         assert_eq!(uploader.get_image_extension("noext", &png_header), "png");
     }
 
+    #[tokio::test]
+    async fn test_duplicate_image_refs_share_dedup_key() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("logo.png"), b"png").unwrap();
+        std::fs::write(temp_dir.path().join("copy.png"), b"png").unwrap();
+        std::fs::write(temp_dir.path().join("other.png"), b"other png").unwrap();
+
+        let key_of = |url: &str| {
+            let image_ref = ImageRef::new("A".to_string(), url.to_string(), (0, 0));
+            let base_path = temp_dir.path().to_path_buf();
+            async move { ImageUploader::dedup_key(&image_ref, &base_path).await }
+        };
+
+        let key = key_of("logo.png").await;
+        assert_eq!(key, DedupKey::Content(blake3::hash(b"png")));
+        assert_eq!(key, key_of("./logo.png").await);
+        assert_eq!(key, key_of(".\\logo.png").await);
+        assert_eq!(key, key_of("copy.png").await);
+        assert_ne!(key, key_of("other.png").await);
+
+        // Changing the file changes its key
+        std::fs::write(temp_dir.path().join("logo.png"), b"new png").unwrap();
+        assert_ne!(key, key_of("logo.png").await);
+
+        // Missing files keep a key of their own
+        assert!(matches!(key_of("missing.png").await, DedupKey::Path(_)));
+
+        let remote = ImageRef::new(
+            "R".to_string(),
+            "https://example.com/logo.png".to_string(),
+            (0, 0),
+        );
+        assert_eq!(
+            ImageUploader::dedup_key(&remote, temp_dir.path()).await,
+            DedupKey::Reference("https://example.com/logo.png".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cover_reuses_body_image_material() {
+        let http_client = Arc::new(WeChatHttpClient::new().unwrap());
+        let token_manager = Arc::new(TokenManager::new(
+            "test_app_id",
+            "test_secret",
+            Arc::clone(&http_client),
+        ));
+        let uploader = ImageUploader::new(http_client, token_manager);
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let image_bytes = b"\x89PNG fake cover".to_vec();
+        std::fs::write(temp_dir.path().join("cover.png"), &image_bytes).unwrap();
+
+        // Simulate the body image having been uploaded already
+        let hash = blake3::hash(&image_bytes).to_hex().to_string();
+        uploader.material_cache.write().await.insert(
+            hash.clone(),
+            CachedMaterial::new(MaterialItem {
+                media_id: "body_media_id".to_string(),
+                name: hash,
+                update_time: 0,
+                url: "https://mmbiz.qpic.cn/body".to_string(),
+            }),
+        );
+
+        let body = uploader
+            .upload_images(
+                vec![ImageRef::new(
+                    "Cover".to_string(),
+                    "cover.png".to_string(),
+                    (0, 0),
+                )],
+                temp_dir.path(),
+            )
+            .await
+            .unwrap();
+
+        // No network access is needed: the cover is served from the shared cache
+        let cover = uploader
+            .upload_cover(&temp_dir.path().join("cover.png"))
+            .await
+            .unwrap();
+
+        assert!(cover.reused);
+        assert_eq!(cover.media_id, body[0].media_id);
+        assert_eq!(cover.url, body[0].url);
+    }
+
+    #[tokio::test]
+    async fn test_media_map_export_import() {
+        let http_client = Arc::new(WeChatHttpClient::new().unwrap());
+        let token_manager = Arc::new(TokenManager::new(
+            "test_app_id",
+            "test_secret",
+            Arc::clone(&http_client),
+        ));
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let image_bytes = b"\x89PNG fake image".to_vec();
+        std::fs::write(temp_dir.path().join("image.png"), &image_bytes).unwrap();
+        let hash = blake3::hash(&image_bytes).to_hex().to_string();
+
+        // A previous run uploaded the image
+        let previous = ImageUploader::new(Arc::clone(&http_client), Arc::clone(&token_manager));
+        previous.material_cache.write().await.insert(
+            hash.clone(),
+            CachedMaterial::new(MaterialItem {
+                media_id: "media_1".to_string(),
+                name: hash.clone(),
+                update_time: 0,
+                url: "https://mmbiz.qpic.cn/image".to_string(),
+            }),
+        );
+        let materials = previous.export_material_cache().await;
+        assert_eq!(materials[&hash].media_id, "media_1");
+
+        // A fresh uploader seeded with the export reuses the material without network access
+        let uploader = ImageUploader::new(Arc::clone(&http_client), Arc::clone(&token_manager));
+        assert_eq!(uploader.import_material_cache(materials.clone()).await, 1);
+        assert_eq!(uploader.import_material_cache(materials).await, 0);
+
+        let cover = uploader
+            .upload_cover(&temp_dir.path().join("image.png"))
+            .await
+            .unwrap();
+        assert!(cover.reused);
+        assert_eq!(cover.media_id, "media_1");
+
+        // Draft mappings keep existing entries on import
+        let draft_manager = DraftManager::new(http_client, token_manager);
+        draft_manager
+            .draft_titles
+            .write()
+            .await
+            .insert("Local".to_string(), "draft_local".to_string());
+
+        let imported = BTreeMap::from([
+            ("Local".to_string(), "draft_other".to_string()),
+            ("Remote".to_string(), "draft_remote".to_string()),
+        ]);
+        assert_eq!(draft_manager.import_draft_titles(imported).await, 1);
+
+        let exported = draft_manager.export_draft_titles().await;
+        assert_eq!(exported["Local"], "draft_local");
+        assert_eq!(exported["Remote"], "draft_remote");
+    }
+
+    #[tokio::test]
+    async fn test_existing_draft_policy_error() {
+        let http_client = Arc::new(WeChatHttpClient::new().unwrap());
+        let token_manager = Arc::new(TokenManager::new(
+            "test_app_id",
+            "test_secret",
+            Arc::clone(&http_client),
+        ));
+        let draft_manager = DraftManager::new(http_client, token_manager);
+        draft_manager
+            .import_draft_titles(BTreeMap::from([(
+                "Weekly".to_string(),
+                "draft_1".to_string(),
+            )]))
+            .await;
+
+        // A draft written earlier with the same title is never overwritten
+        let article = Article::new(
+            "Weekly".to_string(),
+            "Author".to_string(),
+            "<p>Issue 2</p>".to_string(),
+        );
+        let result = draft_manager
+            .create_draft_with_policy(vec![article], ExistingDraftPolicy::Error)
+            .await;
+        match result {
+            Err(WeChatError::DraftExists { title, media_id }) => {
+                assert_eq!(title, "Weekly");
+                assert_eq!(media_id, "draft_1");
+            }
+            other => panic!("Expected DraftExists error, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_existing_draft_policy_always_create_new() {
+        let (address, requests) = serve(vec![
+            r#"{"access_token":"token","expires_in":7200}"#,
+            r#"{"media_id":"draft_2"}"#,
+        ]);
+        let draft_manager = draft_manager(address);
+        draft_manager
+            .import_draft_titles(BTreeMap::from([(
+                "Weekly".to_string(),
+                "draft_1".to_string(),
+            )]))
+            .await;
+
+        // The draft with the same title is neither looked up nor updated
+        let article = Article::new(
+            "Weekly".to_string(),
+            "Author".to_string(),
+            "<p>Issue 2</p>".to_string(),
+        );
+        let media_id = draft_manager
+            .create_draft_with_policy(vec![article], ExistingDraftPolicy::AlwaysCreateNew)
+            .await
+            .unwrap();
+        assert_eq!(media_id, "draft_2");
+
+        let paths: Vec<_> = requests
+            .try_iter()
+            .map(|request| request.split_whitespace().nth(1).unwrap_or("").to_string())
+            .collect();
+        assert_eq!(paths.len(), 2);
+        assert!(paths[1].starts_with("/cgi-bin/draft/add?"));
+        assert_eq!(
+            draft_manager.export_draft_titles().await["Weekly"],
+            "draft_2"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_known_draft_is_fetched_once() {
+        let (address, requests) = serve(vec![
+            r#"{"access_token":"token","expires_in":7200}"#,
+            r#"{"news_item":[{"title":"Weekly","author":"A","content":""}],"update_time":1704067200}"#,
+            r#"{"errcode":0,"errmsg":"ok"}"#,
+        ]);
+        let article = Article::new(
+            "Weekly".to_string(),
+            "Author".to_string(),
+            "<p>Issue 2</p>".to_string(),
+        );
+
+        // The fetched draft gives both the conflict check and the article count
+        let media_id = draft_manager(address)
+            .upsert_draft(
+                vec![article],
+                Some("draft_1"),
+                None,
+                false,
+                ExistingDraftPolicy::UpdateByTitle,
+            )
+            .await
+            .unwrap();
+        assert_eq!(media_id, "draft_1");
+
+        let paths: Vec<_> = requests
+            .try_iter()
+            .map(|request| request.split_whitespace().nth(1).unwrap_or("").to_string())
+            .collect();
+        assert_eq!(paths.len(), 3);
+        assert!(paths[1].starts_with("/cgi-bin/draft/get?"));
+        assert!(paths[2].starts_with("/cgi-bin/draft/update?"));
+    }
+
+    #[tokio::test]
+    async fn test_material_cache_follows_config() {
+        let mut config = Config::default();
+        config.performance.max_cache_entries = 2;
+        config.performance.cache_ttl_minutes = 0;
+        let http_client = Arc::new(WeChatHttpClient::with_config(config.clone()).unwrap());
+        let token_manager = Arc::new(TokenManager::new(
+            "test_app_id",
+            "test_secret",
+            Arc::clone(&http_client),
+        ));
+
+        let uploader = ImageUploader::new(Arc::clone(&http_client), Arc::clone(&token_manager));
+        for hash in ["a", "b", "c"] {
+            uploader.cache_material(hash, "media", "url").await;
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        {
+            let cache = uploader.material_cache.read().await;
+            assert_eq!(cache.len(), 2);
+            assert!(!cache.contains_key("a"));
+        }
+        // With a TTL of 0 every entry is expired right away
+        assert_eq!(uploader.get_cache_stats().await, (2, 2));
+
+        // A disabled cache is bypassed entirely
+        config.cache.enable_material_cache = false;
+        let http_client = Arc::new(WeChatHttpClient::with_config(config).unwrap());
+        let uploader = ImageUploader::new(http_client, token_manager);
+        uploader.cache_material("a", "media", "url").await;
+        let materials = BTreeMap::from([(
+            "b".to_string(),
+            MaterialEntry {
+                media_id: "media".to_string(),
+                url: "url".to_string(),
+            },
+        )]);
+        assert_eq!(uploader.import_material_cache(materials).await, 0);
+        assert_eq!(uploader.get_cache_stats().await, (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_material_cache_persists_between_runs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let cache_path = temp_dir.path().join("cache").join("materials.json");
+        let mut config = Config::default();
+        config.cache.material_cache_path = Some(cache_path.clone());
+        let http_client = Arc::new(WeChatHttpClient::with_config(config).unwrap());
+        let token_manager = Arc::new(TokenManager::new(
+            "test_app_id",
+            "test_secret",
+            Arc::clone(&http_client),
+        ));
+
+        // The cache is saved once the last clone is dropped
+        let uploader = ImageUploader::new(Arc::clone(&http_client), Arc::clone(&token_manager));
+        let clone = uploader.clone();
+        uploader.cache_material("hash", "media_1", "url_1").await;
+        drop(uploader);
+        assert!(!cache_path.exists());
+        drop(clone);
+        for _ in 0..100 {
+            if cache_path.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(cache_path.exists());
+
+        // The next run starts with the saved cache
+        let uploader = ImageUploader::new(Arc::clone(&http_client), Arc::clone(&token_manager));
+        assert_eq!(uploader.get_cache_stats().await, (1, 0));
+        assert_eq!(
+            uploader.export_material_cache().await["hash"].media_id,
+            "media_1"
+        );
+
+        // Entries older than the TTL are dropped on load
+        let stale = serde_json::json!({
+            "accounts": {
+                "test_app_id": {
+                    "old": { "media_id": "media_old", "url": "url_old", "cached_at": 0 }
+                }
+            }
+        });
+        std::fs::write(&cache_path, stale.to_string()).unwrap();
+        let fresh = ImageUploader::new(Arc::clone(&http_client), Arc::clone(&token_manager));
+        assert_eq!(fresh.get_cache_stats().await, (0, 0));
+
+        // Saving on demand writes the current cache
+        uploader.save_material_cache().await.unwrap();
+        let file: MaterialCacheFile =
+            serde_json::from_slice(&std::fs::read(&cache_path).unwrap()).unwrap();
+        assert!(file.accounts["test_app_id"].contains_key("hash"));
+        assert!(!file.accounts["test_app_id"].contains_key("old"));
+    }
+
+    #[tokio::test]
+    async fn test_material_cache_file_is_merged_on_save() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let cache_path = temp_dir.path().join("materials.json");
+        let uploader = |app_id: &str| {
+            let mut config = Config::default();
+            config.cache.material_cache_path = Some(cache_path.clone());
+            let http_client = Arc::new(WeChatHttpClient::with_config(config).unwrap());
+            let token_manager = Arc::new(TokenManager::new(
+                app_id,
+                "test_secret",
+                Arc::clone(&http_client),
+            ));
+            ImageUploader::new(http_client, token_manager)
+        };
+
+        // Uploaders of the same account, as in two processes, keep each other's entries
+        let first = uploader("wx_first");
+        let second = uploader("wx_first");
+        let other = uploader("wx_other");
+        first.cache_material("a", "media_a", "url_a").await;
+        second.cache_material("b", "media_b", "url_b").await;
+        other.cache_material("a", "media_other", "url_other").await;
+        first.save_material_cache().await.unwrap();
+        second.save_material_cache().await.unwrap();
+        other.save_material_cache().await.unwrap();
+
+        let file: MaterialCacheFile =
+            serde_json::from_slice(&std::fs::read(&cache_path).unwrap()).unwrap();
+        let first_materials = &file.accounts["wx_first"];
+        assert_eq!(first_materials.keys().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(first_materials["a"].media_id, "media_a");
+        assert_eq!(file.accounts["wx_other"]["a"].media_id, "media_other");
+
+        // Each account only loads its own entries
+        let next = uploader("wx_other");
+        let materials = next.export_material_cache().await;
+        assert_eq!(materials.len(), 1);
+        assert_eq!(materials["a"].media_id, "media_other");
+    }
+
+    #[tokio::test]
+    async fn test_upload_results_follow_input_order() {
+        let http_client = Arc::new(WeChatHttpClient::new().unwrap());
+        let token_manager = Arc::new(TokenManager::new(
+            "test_app_id",
+            "test_secret",
+            Arc::clone(&http_client),
+        ));
+        let uploader = ImageUploader::new(http_client, token_manager);
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let names = ["c.png", "a.png", "b.png"];
+        for name in names {
+            let bytes = format!("fake image {name}").into_bytes();
+            std::fs::write(temp_dir.path().join(name), &bytes).unwrap();
+
+            let hash = blake3::hash(&bytes).to_hex().to_string();
+            uploader.material_cache.write().await.insert(
+                hash.clone(),
+                CachedMaterial::new(MaterialItem {
+                    media_id: format!("media_{name}"),
+                    name: hash,
+                    update_time: 0,
+                    url: format!("https://mmbiz.qpic.cn/{name}"),
+                }),
+            );
+        }
+
+        let refs: Vec<_> = ["c.png", "a.png", "./c.png", "b.png"]
+            .iter()
+            .enumerate()
+            .map(|(i, url)| ImageRef::new(format!("img{i}"), url.to_string(), (i, i)))
+            .collect();
+
+        let results = uploader
+            .upload_images(refs.clone(), temp_dir.path())
+            .await
+            .unwrap();
+        let urls: Vec<_> = results.iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://mmbiz.qpic.cn/c.png",
+                "https://mmbiz.qpic.cn/a.png",
+                "https://mmbiz.qpic.cn/c.png",
+                "https://mmbiz.qpic.cn/b.png",
+            ]
+        );
+        for (result, image_ref) in results.iter().zip(&refs) {
+            assert_eq!(&result.image_ref, image_ref);
+        }
+
+        let by_url = uploader
+            .upload_images_by_url(refs, temp_dir.path())
+            .await
+            .unwrap();
+        assert_eq!(by_url.len(), 4);
+        assert_eq!(by_url["./c.png"].media_id, "media_c.png");
+        assert_eq!(by_url["b.png"].media_id, "media_b.png");
+    }
+
+    #[tokio::test]
+    async fn test_stuck_remote_image_times_out() {
+        // A server that accepts connections but never responds
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let http_client = Arc::new(WeChatHttpClient::new().unwrap());
+        let token_manager = Arc::new(TokenManager::new(
+            "test_app_id",
+            "test_secret",
+            Arc::clone(&http_client),
+        ));
+        let uploader = ImageUploader::new(http_client, token_manager)
+            .with_image_timeout(Duration::from_millis(200));
+
+        let image_ref = ImageRef::new(
+            "Stuck".to_string(),
+            format!("http://{addr}/stuck.png"),
+            (0, 0),
+        );
+
+        let started = Instant::now();
+        let result = uploader
+            .upload_images(vec![image_ref], Path::new("."))
+            .await;
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        match result {
+            Err(WeChatError::ImageUpload { reason, .. }) => assert!(reason.contains("Timed out")),
+            other => panic!("Expected image timeout, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_image_timeout_excludes_permit_wait() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let http_client = Arc::new(WeChatHttpClient::new().unwrap());
+        let token_manager = Arc::new(TokenManager::new(
+            "test_app_id",
+            "test_secret",
+            Arc::clone(&http_client),
+        ));
+        let uploader = ImageUploader::new(http_client, token_manager)
+            .with_image_timeout(Duration::from_millis(200));
+
+        // Hold every download slot for longer than the timeout
+        let permits = u32::try_from(uploader.downloads.available_permits()).unwrap();
+        let held = Arc::clone(&uploader.downloads)
+            .acquire_many_owned(permits)
+            .await
+            .unwrap();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            drop(held);
+        });
+
+        let image_ref = ImageRef::new(
+            "Stuck".to_string(),
+            format!("http://{addr}/stuck.png"),
+            (0, 0),
+        );
+        let started = Instant::now();
+        let result = uploader
+            .upload_images(vec![image_ref], Path::new("."))
+            .await;
+
+        // The timeout only starts once the slot is free
+        assert!(started.elapsed() >= Duration::from_millis(700));
+        match result {
+            Err(WeChatError::ImageUpload { reason, .. }) => assert!(reason.contains("Timed out")),
+            other => panic!("Expected image timeout, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_uploads_are_serialized_per_hash() {
+        let uploads = InFlightUploads::default();
+        let guard = uploads.lock("abc").await;
+
+        // A different hash is not blocked
+        drop(uploads.lock("def").await);
+
+        let waiter = tokio::spawn({
+            let uploads = uploads.clone();
+            async move { drop(uploads.lock("abc").await) }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        drop(guard);
+        waiter.await.unwrap();
+        assert!(uploads.0.lock().unwrap().is_empty());
+    }
+
     #[test]
     fn test_url_mapping_creation() {
         let http_client = Arc::new(WeChatHttpClient::new().unwrap());
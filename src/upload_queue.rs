@@ -0,0 +1,505 @@
+//! Durable queue of rendered uploads, for unreliable networks.
+//!
+//! Rendering an article with many Mermaid charts can take minutes; a connection that
+//! drops during the uploads that follow loses all of it. With an [`UploadQueue`]
+//! registered via [`WeChatClient::with_upload_queue`](crate::WeChatClient::with_upload_queue),
+//! uploads first write the rendered article to disk: the HTML with placeholders for the
+//! image URLs, a copy of every local image (including generated charts) and of the cover.
+//! Sending happens from there, so a failed attempt keeps the entry, and a later
+//! [`drain_upload_queue`](crate::WeChatClient::drain_upload_queue) or a worker running
+//! [`run_upload_queue`](crate::WeChatClient::run_upload_queue), possibly in another
+//! process, retries it without rendering again:
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use wechat_pub_rs::upload_queue::UploadQueue;
+//! use wechat_pub_rs::{WeChatClient, WeChatError};
+//!
+//! # async fn example() -> wechat_pub_rs::Result<()> {
+//! let client = WeChatClient::new("app_id", "app_secret")
+//!     .await?
+//!     .with_upload_queue(UploadQueue::open("/var/lib/publisher/queue")?);
+//!
+//! match client.upload("article.md").await {
+//!     Ok(draft_id) => println!("Created draft {draft_id}"),
+//!     Err(WeChatError::Queued { id, reason }) => println!("Will retry {id}: {reason}"),
+//!     Err(e) => return Err(e),
+//! }
+//!
+//! // Keep retrying queued uploads until the client is shut down
+//! client.run_upload_queue(Duration::from_secs(60)).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Every entry is a directory named by its ID, holding an `upload.json` manifest and
+//! the copied images. Entries are retried in the order they were queued, waiting longer
+//! after each failed attempt (see [`UploadQueue::with_backoff`]). Entries failing with an
+//! error that retrying can't fix (see [`WeChatError::is_retryable`]) are moved to the
+//! `failed` directory, where [`UploadQueue::retry_failed`] can requeue them.
+//!
+//! A queue directory should be drained by one process at a time. A process killed after
+//! the draft was created but before the entry was removed sends it again, which updates
+//! the draft under the default [`ExistingDraftPolicy`].
+
+use crate::error::{Result, WeChatError};
+use crate::image_processing::{ImageFormatPolicy, ImageProcessing};
+use crate::markdown::ImageRef;
+use crate::upload::{Article, ExistingDraftPolicy, MaterialNaming};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::{debug, info, warn};
+
+/// Name of the manifest file in every entry directory.
+pub const MANIFEST_FILE: &str = "upload.json";
+
+/// Name of the directory holding entries that failed permanently.
+pub const FAILED_DIR: &str = "failed";
+
+/// Directory in an entry holding the copied images.
+const BLOBS_DIR: &str = "blobs";
+
+/// An upload waiting in the queue, as recorded in its manifest.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedUpload {
+    /// ID of the entry, which sorts in the order entries were queued
+    pub id: String,
+    /// The markdown file the article was rendered from
+    pub markdown_path: PathBuf,
+    /// When the upload was queued
+    pub enqueued_at: DateTime<Utc>,
+    /// Number of failed attempts so far
+    #[serde(default)]
+    pub attempts: u32,
+    /// When the next attempt is due; `None` if it is due right away
+    #[serde(default)]
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    /// Error of the last failed attempt
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// What is sent
+    pub(crate) payload: QueuedPayload,
+}
+
+impl QueuedUpload {
+    /// Whether the next attempt is due at `now`.
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.next_attempt_at.is_none_or(|due| due <= now)
+    }
+}
+
+/// Rendered article and upload settings of a queued upload.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct QueuedPayload {
+    /// The article, with image placeholders in its content and no cover yet
+    pub(crate) article: Article,
+    /// Images of the article, in document order without duplicates
+    pub(crate) images: Vec<QueuedImage>,
+    /// Path of the copied cover, relative to the entry directory
+    pub(crate) cover: String,
+    /// Draft to update instead of matching drafts by title (frontmatter `wechat_media_id`)
+    #[serde(default)]
+    pub(crate) known_media_id: Option<String>,
+    #[serde(default)]
+    pub(crate) force: bool,
+    #[serde(default)]
+    pub(crate) on_existing_draft: ExistingDraftPolicy,
+    #[serde(default)]
+    pub(crate) lookup_draft_url: bool,
+    #[serde(default)]
+    pub(crate) image_processing: ImageProcessing,
+    #[serde(default)]
+    pub(crate) image_formats: ImageFormatPolicy,
+    #[serde(default)]
+    pub(crate) material_naming: MaterialNaming,
+    /// Slug of the document, which names its images under [`MaterialNaming::SlugIndex`]
+    #[serde(default)]
+    pub(crate) document_slug: Option<String>,
+}
+
+/// An image of a queued upload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct QueuedImage {
+    /// Placeholder standing for the image URL in the article content
+    pub(crate) placeholder: String,
+    /// Path of the copied image relative to the entry directory, or the URL of a
+    /// remote image
+    pub(crate) source: String,
+    /// Title of the image in the markdown, which can exempt it from downscaling
+    #[serde(default)]
+    pub(crate) title: Option<String>,
+}
+
+impl QueuedImage {
+    /// Gets the image reference uploaded for this image.
+    pub(crate) fn image_ref(&self) -> ImageRef {
+        let image = ImageRef::new(String::new(), self.source.clone(), (0, 0));
+        match &self.title {
+            Some(title) => image.with_title(title.clone()),
+            None => image,
+        }
+    }
+}
+
+/// A queue of rendered uploads stored in a directory.
+#[derive(Debug)]
+pub struct UploadQueue {
+    dir: PathBuf,
+    base_backoff: Duration,
+    max_backoff: Duration,
+    /// Held while entries are sent, so that no entry is sent twice at once
+    sending: Mutex<()>,
+}
+
+impl UploadQueue {
+    /// Opens the queue stored in a directory, creating the directory if needed.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| WeChatError::file_error(dir.display().to_string(), e.to_string()))?;
+        Ok(Self {
+            dir,
+            base_backoff: Duration::from_secs(30),
+            max_backoff: Duration::from_secs(3600),
+            sending: Mutex::new(()),
+        })
+    }
+
+    /// Sets how long entries wait after a failed attempt: `base` after the first,
+    /// doubling with every further one up to `max` (default: 30 seconds up to an hour).
+    pub fn with_backoff(mut self, base: Duration, max: Duration) -> Self {
+        self.base_backoff = base;
+        self.max_backoff = max.max(base);
+        self
+    }
+
+    /// Gets the directory the queue is stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Gets the entries waiting to be sent, oldest first.
+    pub async fn pending(&self) -> Result<Vec<QueuedUpload>> {
+        read_entries(&self.dir).await
+    }
+
+    /// Gets the entries that failed permanently, oldest first.
+    pub async fn failed(&self) -> Result<Vec<QueuedUpload>> {
+        read_entries(&self.dir.join(FAILED_DIR)).await
+    }
+
+    /// Moves a failed entry back into the queue, due right away.
+    pub async fn retry_failed(&self, id: &str) -> Result<()> {
+        let failed_dir = self.dir.join(FAILED_DIR).join(id);
+        let mut upload = read_manifest(&failed_dir).await?;
+        upload.next_attempt_at = None;
+        write_manifest(&failed_dir, &upload).await?;
+        rename(&failed_dir, &self.entry_dir(id)).await?;
+        info!("Requeued failed upload {id}");
+        Ok(())
+    }
+
+    /// Removes a waiting or failed entry without sending it.
+    pub async fn remove(&self, id: &str) -> Result<()> {
+        let _sending = self.sending.lock().await;
+        for dir in [self.entry_dir(id), self.dir.join(FAILED_DIR).join(id)] {
+            if dir.join(MANIFEST_FILE).exists() {
+                return remove_dir(&dir).await;
+            }
+        }
+        Err(WeChatError::FileNotFound {
+            path: self.entry_dir(id).display().to_string(),
+        })
+    }
+
+    /// Gets the directory of a waiting entry, which its image paths are relative to.
+    pub(crate) fn entry_dir(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    /// Waits until no other entry is being sent, and holds off others until the guard is
+    /// dropped.
+    pub(crate) async fn lock(&self) -> MutexGuard<'_, ()> {
+        self.sending.lock().await
+    }
+
+    /// Adds an upload to the queue, copying its images. `files` maps image paths
+    /// relative to the entry directory (as in the payload) to the files to copy.
+    ///
+    /// The entry is written under a temporary name and renamed once complete, so a
+    /// crash never leaves a partial entry behind in the queue.
+    pub(crate) async fn push(
+        &self,
+        markdown_path: &Path,
+        payload: QueuedPayload,
+        files: Vec<(String, PathBuf)>,
+    ) -> Result<QueuedUpload> {
+        let now = Utc::now();
+        let id = format!(
+            "{}-{}",
+            now.format("%Y%m%d%H%M%S%3f"),
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        );
+        let upload = QueuedUpload {
+            id: id.clone(),
+            markdown_path: markdown_path.to_path_buf(),
+            enqueued_at: now,
+            attempts: 0,
+            next_attempt_at: None,
+            last_error: None,
+            payload,
+        };
+
+        let partial_dir = self.dir.join(format!(".{id}.partial"));
+        create_dir(&partial_dir).await?;
+        for (name, source) in files {
+            let target = partial_dir.join(&name);
+            if let Some(parent) = target.parent() {
+                create_dir(parent).await?;
+            }
+            tokio::fs::copy(&source, &target).await.map_err(|e| {
+                WeChatError::file_error(source.display().to_string(), e.to_string())
+            })?;
+        }
+        write_manifest(&partial_dir, &upload).await?;
+        rename(&partial_dir, &self.entry_dir(&id)).await?;
+
+        info!("Queued upload of {} as {id}", markdown_path.display());
+        Ok(upload)
+    }
+
+    /// Records a failed attempt: entries failing with a retryable error wait for their
+    /// next attempt, the others are moved to the failed entries.
+    pub(crate) async fn record_failure(
+        &self,
+        upload: &mut QueuedUpload,
+        error: &WeChatError,
+    ) -> Result<()> {
+        upload.attempts += 1;
+        upload.last_error = Some(error.to_string());
+
+        let entry_dir = self.entry_dir(&upload.id);
+        if error.is_retryable() {
+            let backoff = self.backoff(upload.attempts);
+            upload.next_attempt_at = Some(Utc::now() + backoff);
+            warn!(
+                "Queued upload {} failed (attempt {}), retrying in {backoff:?}: {error}",
+                upload.id, upload.attempts
+            );
+            write_manifest(&entry_dir, upload).await
+        } else {
+            warn!("Queued upload {} failed permanently: {error}", upload.id);
+            write_manifest(&entry_dir, upload).await?;
+            let failed_dir = self.dir.join(FAILED_DIR);
+            create_dir(&failed_dir).await?;
+            rename(&entry_dir, &failed_dir.join(&upload.id)).await
+        }
+    }
+
+    /// Removes an entry that was sent.
+    pub(crate) async fn complete(&self, id: &str) -> Result<()> {
+        debug!("Removing sent upload {id} from the queue");
+        remove_dir(&self.entry_dir(id)).await
+    }
+
+    /// Gets how long an entry waits after its `attempts`th failed attempt.
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.base_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Gets the path a local image is copied to in an entry, in a directory of its own
+/// named `name` so that it keeps its file name for the material name.
+pub(crate) fn blob_path(name: &str, source: &Path) -> String {
+    match source.file_name() {
+        Some(file_name) => format!("{BLOBS_DIR}/{name}/{}", file_name.to_string_lossy()),
+        None => format!("{BLOBS_DIR}/{name}"),
+    }
+}
+
+/// Reads the manifests of the entries in a directory, sorted by ID.
+///
+/// Unreadable entries are logged and skipped, so one broken entry doesn't block the
+/// queue.
+async fn read_entries(dir: &Path) -> Result<Vec<QueuedUpload>> {
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(WeChatError::file_error(
+                dir.display().to_string(),
+                e.to_string(),
+            ));
+        }
+    };
+
+    let mut uploads = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|e| WeChatError::file_error(dir.display().to_string(), e.to_string()))?
+    {
+        let path = entry.path();
+        // Entries still being written start with a dot
+        if entry.file_name().to_string_lossy().starts_with('.')
+            || !path.join(MANIFEST_FILE).is_file()
+        {
+            continue;
+        }
+        match read_manifest(&path).await {
+            Ok(upload) => uploads.push(upload),
+            Err(e) => warn!("Skipping unreadable queue entry {}: {e}", path.display()),
+        }
+    }
+    uploads.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(uploads)
+}
+
+async fn read_manifest(entry_dir: &Path) -> Result<QueuedUpload> {
+    let path = entry_dir.join(MANIFEST_FILE);
+    let json = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|e| WeChatError::file_error(path.display().to_string(), e.to_string()))?;
+    serde_json::from_str(&json).map_err(|e| {
+        WeChatError::file_error(
+            path.display().to_string(),
+            format!("Invalid queue entry: {e}"),
+        )
+    })
+}
+
+/// Writes a manifest through a temporary file, so it is never left half-written.
+async fn write_manifest(entry_dir: &Path, upload: &QueuedUpload) -> Result<()> {
+    let path = entry_dir.join(MANIFEST_FILE);
+    let temp_path = entry_dir.join(format!("{MANIFEST_FILE}.{}.tmp", uuid::Uuid::new_v4()));
+    let json = serde_json::to_string_pretty(upload).map_err(|e| WeChatError::Json {
+        message: e.to_string(),
+    })?;
+    tokio::fs::write(&temp_path, json)
+        .await
+        .map_err(|e| WeChatError::file_error(temp_path.display().to_string(), e.to_string()))?;
+    rename(&temp_path, &path).await
+}
+
+async fn create_dir(dir: &Path) -> Result<()> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .map_err(|e| WeChatError::file_error(dir.display().to_string(), e.to_string()))
+}
+
+async fn rename(from: &Path, to: &Path) -> Result<()> {
+    tokio::fs::rename(from, to)
+        .await
+        .map_err(|e| WeChatError::file_error(from.display().to_string(), e.to_string()))
+}
+
+async fn remove_dir(dir: &Path) -> Result<()> {
+    tokio::fs::remove_dir_all(dir)
+        .await
+        .map_err(|e| WeChatError::file_error(dir.display().to_string(), e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload() -> QueuedPayload {
+        QueuedPayload {
+            article: Article::new(
+                "Title".to_string(),
+                "Author".to_string(),
+                "<p><img src=\"wechat-pub-image-0-placeholder\"></p>".to_string(),
+            ),
+            images: vec![QueuedImage {
+                placeholder: "wechat-pub-image-0-placeholder".to_string(),
+                source: "blobs/0/chart.PNG".to_string(),
+                title: Some("fullres".to_string()),
+            }],
+            cover: "blobs/cover/cover.jpg".to_string(),
+            known_media_id: None,
+            force: false,
+            on_existing_draft: ExistingDraftPolicy::AlwaysCreateNew,
+            lookup_draft_url: false,
+            image_processing: ImageProcessing::default().max_width(1080),
+            image_formats: ImageFormatPolicy::default(),
+            material_naming: MaterialNaming::OriginalName,
+            document_slug: Some("article".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload_queue() {
+        let root = tempfile::TempDir::new().unwrap();
+        std::fs::write(root.path().join("chart.PNG"), b"png").unwrap();
+        std::fs::write(root.path().join("cover.jpg"), b"jpg").unwrap();
+        let queue = UploadQueue::open(root.path().join("queue"))
+            .unwrap()
+            .with_backoff(Duration::from_secs(10), Duration::from_secs(25));
+
+        let files = vec![
+            (
+                blob_path("0", &root.path().join("chart.PNG")),
+                root.path().join("chart.PNG"),
+            ),
+            (
+                blob_path("cover", &root.path().join("cover.jpg")),
+                root.path().join("cover.jpg"),
+            ),
+        ];
+        let mut upload = queue
+            .push(Path::new("posts/article.md"), payload(), files)
+            .await
+            .unwrap();
+        let entry_dir = queue.entry_dir(&upload.id);
+        assert!(entry_dir.join("blobs/0/chart.PNG").is_file());
+        assert!(entry_dir.join("blobs/cover/cover.jpg").is_file());
+
+        // The manifest round-trips, settings included
+        let pending = queue.pending().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].markdown_path, Path::new("posts/article.md"));
+        assert_eq!(pending[0].payload.images, upload.payload.images);
+        assert_eq!(
+            pending[0].payload.image_processing,
+            ImageProcessing::default().max_width(1080)
+        );
+        assert!(pending[0].is_due(Utc::now()));
+
+        // Retryable failures back off, doubling up to the maximum
+        let network = WeChatError::Network {
+            message: "connection reset".to_string(),
+        };
+        queue.record_failure(&mut upload, &network).await.unwrap();
+        assert!(!upload.is_due(Utc::now()));
+        assert!(upload.is_due(Utc::now() + Duration::from_secs(10)));
+        assert_eq!(queue.backoff(2), Duration::from_secs(20));
+        assert_eq!(queue.backoff(3), Duration::from_secs(25));
+        let pending = queue.pending().await.unwrap();
+        assert_eq!(pending[0].attempts, 1);
+        assert_eq!(
+            pending[0].last_error.as_deref(),
+            Some(network.to_string().as_str())
+        );
+
+        // Permanent failures move the entry out of the queue until requeued
+        let rejected = WeChatError::from_api_response(45002, "content too long");
+        queue.record_failure(&mut upload, &rejected).await.unwrap();
+        assert!(queue.pending().await.unwrap().is_empty());
+        assert_eq!(queue.failed().await.unwrap().len(), 1);
+
+        queue.retry_failed(&upload.id).await.unwrap();
+        let pending = queue.pending().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert!(pending[0].is_due(Utc::now()));
+
+        queue.complete(&upload.id).await.unwrap();
+        assert!(queue.pending().await.unwrap().is_empty());
+        assert!(queue.remove(&upload.id).await.is_err());
+    }
+}
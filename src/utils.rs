@@ -3,6 +3,9 @@
 //! This module provides security-focused utilities with input validation
 //! and safe path handling to prevent common vulnerabilities.
 
+use crate::config::SecurityConfig;
+use crate::error::WeChatError;
+use std::borrow::Cow;
 use std::path::{Component, Path, PathBuf};
 use std::sync::LazyLock;
 use std::{collections::HashSet, ffi::OsStr};
@@ -11,13 +14,7 @@ use tracing::warn;
 /// Checks if a file exists and is readable with path validation.
 /// Returns false for invalid or potentially dangerous paths.
 pub async fn file_exists(path: &Path) -> bool {
-    // Validate path for security
-    if !is_safe_path(path) {
-        warn!("Unsafe path access attempt: {:?}", path);
-        return false;
-    }
-
-    tokio::fs::metadata(path).await.is_ok()
+    PathPolicy::default().file_exists(path).await
 }
 
 /// Gets the file extension from a path.
@@ -93,70 +90,232 @@ static DANGEROUS_EXTENSIONS: LazyLock<HashSet<&'static str>> = LazyLock::new(||
 });
 
 /// Validates that a path is safe to access (prevents path traversal and dangerous files).
+///
+/// Applies the default [`PathPolicy`].
 pub fn is_safe_path(path: &Path) -> bool {
-    // Allow files in system temp directories
-    if let Some(path_str) = path.to_str()
-        && (path_str.contains("/tmp/")
-            || path_str.contains("/var/folders/")
-            || path_str.contains("\\Temp\\"))
-    {
-        // Still check for dangerous extensions in temp files
-        if let Some(extension) = path.extension().and_then(OsStr::to_str)
-            && DANGEROUS_EXTENSIONS.contains(&extension.to_lowercase().as_str())
-        {
-            return false;
-        }
-        return true;
+    PathPolicy::default().is_safe(path)
+}
+
+/// Rules deciding which paths are safe to access.
+///
+/// By default, files with executable or script extensions, hidden files other than
+/// `.gitignore`, `.env` and `.dockerignore`, and Windows reserved names are rejected;
+/// in system temp directories only the extension is checked. Content repositories
+/// tripping these rules can relax them through [`SecurityConfig`]:
+///
+/// ```rust
+/// use std::path::Path;
+/// use wechat_pub_rs::config::SecurityConfig;
+/// use wechat_pub_rs::utils::PathPolicy;
+///
+/// let security = SecurityConfig::builder()
+///     .allowed_extensions(vec!["js".to_string()])
+///     .allowed_hidden_files(vec![".assets".to_string()])
+///     .build();
+/// let policy = PathPolicy::from(&security);
+/// assert!(policy.is_safe(Path::new("posts/.assets/chart.js")));
+/// assert!(!policy.is_safe(Path::new("posts/.private/notes.md")));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PathPolicy {
+    /// Dangerous extensions that are allowed anyway, lowercase without the dot
+    allowed_extensions: HashSet<String>,
+    /// Hidden file and directory names that are allowed
+    allowed_hidden_files: HashSet<String>,
+    /// Directories whose contents only have their extension checked
+    allowed_roots: Vec<PathBuf>,
+    /// Whether all paths are accepted (`validate_file_paths` is off)
+    disabled: bool,
+}
+
+impl PathPolicy {
+    /// Allows files with an otherwise blocked extension, e.g. `js`.
+    pub fn allow_extension(mut self, extension: &str) -> Self {
+        self.allowed_extensions
+            .insert(extension.trim_start_matches('.').to_lowercase());
+        self
+    }
+
+    /// Allows a hidden file or directory name, e.g. `.assets`.
+    pub fn allow_hidden_file(mut self, name: impl Into<String>) -> Self {
+        self.allowed_hidden_files.insert(name.into());
+        self
     }
-    // Check for dangerous file extensions
-    if let Some(extension) = path.extension().and_then(OsStr::to_str)
-        && DANGEROUS_EXTENSIONS.contains(&extension.to_lowercase().as_str())
-    {
-        return false;
+
+    /// Allows any file below a directory, as long as its extension isn't blocked.
+    pub fn allow_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.allowed_roots.push(root.into());
+        self
     }
 
-    // Check each component of the path
-    for component in path.components() {
-        match component {
-            Component::ParentDir => {
-                // Allow parent dir components, but they will be validated during resolution
-                continue;
+    /// Validates that a path is safe to access (prevents dangerous files).
+    pub fn is_safe(&self, path: &Path) -> bool {
+        if self.disabled {
+            return true;
+        }
+
+        // Check for dangerous file extensions, in temp files and allowed roots too
+        if let Some(extension) = path.extension().and_then(OsStr::to_str) {
+            let extension = extension.to_lowercase();
+            if DANGEROUS_EXTENSIONS.contains(extension.as_str())
+                && !self.allowed_extensions.contains(&extension)
+            {
+                return false;
             }
-            Component::Normal(name) => {
-                let name_str = name.to_string_lossy();
-
-                // Check for hidden files (starting with .)
-                if name_str.starts_with('.') && name_str.len() > 1 {
-                    // Allow common hidden files and temp file patterns
-                    if !matches!(name_str.as_ref(), ".gitignore" | ".env" | ".dockerignore")
-                        && !name_str.starts_with(".tmp")
-                    {
+        }
+
+        // Allow files in system temp directories and allowed roots
+        if let Some(path_str) = path.to_str()
+            && (path_str.contains("/tmp/")
+                || path_str.contains("/var/folders/")
+                || path_str.contains("\\Temp\\"))
+        {
+            return true;
+        }
+        if self.allowed_roots.iter().any(|root| is_below(path, root)) {
+            return true;
+        }
+
+        // Check each component of the path
+        for component in path.components() {
+            match component {
+                Component::ParentDir => {
+                    // Allow parent dir components, but they will be validated during resolution
+                    continue;
+                }
+                Component::Normal(name) => {
+                    let name_str = name.to_string_lossy();
+
+                    // Check for hidden files (starting with .)
+                    if name_str.starts_with('.') && name_str.len() > 1 {
+                        // Allow common hidden files and temp file patterns
+                        if !matches!(name_str.as_ref(), ".gitignore" | ".env" | ".dockerignore")
+                            && !name_str.starts_with(".tmp")
+                            && !self.allowed_hidden_files.contains(name_str.as_ref())
+                        {
+                            return false;
+                        }
+                    }
+
+                    // Check for null bytes and other dangerous characters
+                    if name_str.contains('\0') || name_str.contains('\x01') {
                         return false;
                     }
-                }
 
-                // Check for null bytes and other dangerous characters
-                if name_str.contains('\0') || name_str.contains('\x01') {
-                    return false;
+                    // Check for reserved names on Windows
+                    if is_reserved_name(&name_str) {
+                        return false;
+                    }
                 }
-
-                // Check for reserved names on Windows
-                if is_reserved_name(&name_str) {
-                    return false;
+                Component::RootDir | Component::CurDir => {
+                    // These are generally safe
+                    continue;
+                }
+                Component::Prefix(_) => {
+                    // Windows drive prefixes are generally safe
+                    continue;
                 }
             }
-            Component::RootDir | Component::CurDir => {
-                // These are generally safe
-                continue;
+        }
+
+        true
+    }
+
+    /// Checks if a file exists and is readable with path validation.
+    /// Returns false for invalid or potentially dangerous paths.
+    pub async fn file_exists(&self, path: &Path) -> bool {
+        // Validate path for security
+        if !self.is_safe(path) {
+            warn!("Unsafe path access attempt: {:?}", path);
+            return false;
+        }
+
+        tokio::fs::metadata(path).await.is_ok()
+    }
+
+    /// Resolves a path referenced from content against a base directory, see
+    /// [`resolve_path`].
+    pub fn resolve(&self, base_dir: &Path, reference: &str) -> crate::error::Result<PathBuf> {
+        let invalid = |reason: &str| WeChatError::invalid_path(reference, reason);
+        let normalized = normalize_separators(reference);
+        let relative = Path::new(normalized.as_ref());
+
+        if relative.is_absolute() {
+            if !self.is_safe(relative) {
+                return Err(invalid("absolute path contains unsafe components"));
             }
-            Component::Prefix(_) => {
-                // Windows drive prefixes are generally safe
-                continue;
+            return Ok(relative.to_path_buf());
+        }
+
+        // Track the depth below the base directory to reject traversal lexically
+        let mut depth = 0usize;
+        for component in relative.components() {
+            match component {
+                Component::Normal(_) => depth += 1,
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    depth = depth
+                        .checked_sub(1)
+                        .ok_or_else(|| invalid("path escapes the base directory"))?;
+                }
+                Component::RootDir | Component::Prefix(_) => {
+                    return Err(invalid("path has an unexpected root or drive prefix"));
+                }
             }
         }
+
+        let resolved = base_dir.join(relative);
+        if !self.is_safe(&resolved) {
+            return Err(invalid("path contains unsafe components"));
+        }
+
+        // Existing files may still point outside the base directory through symlinks
+        if let (Ok(canonical_resolved), Ok(canonical_base)) =
+            (resolved.canonicalize(), base_dir.canonicalize())
+            && !canonical_resolved.starts_with(&canonical_base)
+        {
+            return Err(invalid("path resolves outside the base directory"));
+        }
+
+        Ok(resolved)
     }
+}
 
-    true
+impl From<&SecurityConfig> for PathPolicy {
+    fn from(security: &SecurityConfig) -> Self {
+        let policy = PathPolicy {
+            disabled: !security.validate_file_paths,
+            ..PathPolicy::default()
+        };
+        let policy = security
+            .allowed_extensions
+            .iter()
+            .fold(policy, |policy, extension| {
+                policy.allow_extension(extension)
+            });
+        let policy = security
+            .allowed_hidden_files
+            .iter()
+            .fold(policy, |policy, name| {
+                policy.allow_hidden_file(name.clone())
+            });
+        security
+            .allowed_roots
+            .iter()
+            .fold(policy, |policy, root| policy.allow_root(root.clone()))
+    }
+}
+
+/// Checks if a path is below a directory, comparing canonical paths when the lexical
+/// comparison fails, e.g. for a relative path below an absolute root.
+fn is_below(path: &Path, root: &Path) -> bool {
+    if path.starts_with(root) {
+        return true;
+    }
+    match (path.canonicalize(), root.canonicalize()) {
+        (Ok(path), Ok(root)) => path.starts_with(root),
+        _ => false,
+    }
 }
 
 /// Checks if a filename is a Windows reserved name.
@@ -246,58 +405,142 @@ pub fn get_base_directory(file_path: &Path) -> Option<&Path> {
     file_path.parent()
 }
 
-/// Resolves relative paths against a base directory with security validation.
-/// Prevents path traversal attacks by validating the resolved path.
-pub fn resolve_path(base_dir: &Path, relative_path: &str) -> Result<PathBuf, String> {
-    let relative = Path::new(relative_path);
-
-    // Check for absolute paths
-    if relative.is_absolute() {
-        if !is_safe_path(relative) {
-            return Err("Absolute path contains unsafe components".to_string());
-        }
-        return Ok(PathBuf::from(relative_path));
+/// Joins a path referenced from content onto a base directory without validation.
+///
+/// Both `/` and `\` are accepted as separators, so references written on Windows
+/// resolve on every platform. Absolute references are returned as-is.
+pub fn join_reference(base_dir: &Path, reference: &str) -> PathBuf {
+    let reference = normalize_separators(reference);
+    let path = Path::new(reference.as_ref());
+
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        base_dir.join(path)
     }
+}
+
+/// Resolves a path referenced from content against a base directory with security
+/// validation.
+///
+/// Relative references must stay inside `base_dir`: `..` components may not climb above
+/// it, and existing files must not resolve outside it through symlinks. Separators are
+/// handled as in [`join_reference`]. Applies the default [`PathPolicy`].
+pub fn resolve_path(base_dir: &Path, reference: &str) -> crate::error::Result<PathBuf> {
+    PathPolicy::default().resolve(base_dir, reference)
+}
 
-    // Resolve relative path
-    let resolved = base_dir.join(relative_path);
+/// Prepares a local file reference taken from a markdown link for resolution.
+///
+/// Any `?query` or `#fragment` suffix is removed and percent-escapes are decoded. If
+/// the escapes are malformed or don't decode to UTF-8, the path is used as written.
+pub fn decode_local_reference(reference: &str) -> Cow<'_, str> {
+    let path = reference.split(['?', '#']).next().unwrap_or(reference);
 
-    // Validate the resolved path
-    if !is_safe_path(&resolved) {
-        return Err("Resolved path contains unsafe components".to_string());
+    if !path.contains('%') {
+        return Cow::Borrowed(path);
     }
 
-    // Ensure the resolved path is still under the base directory
-    match resolved.canonicalize() {
-        Ok(canonical_resolved) => {
-            match base_dir.canonicalize() {
-                Ok(canonical_base) => {
-                    if canonical_resolved.starts_with(&canonical_base) {
-                        Ok(resolved)
-                    } else {
-                        Err("Path traversal attempt detected".to_string())
-                    }
-                }
-                Err(_) => {
-                    // Base directory doesn't exist or can't be canonicalized
-                    // Fall back to basic validation
-                    if has_path_traversal(relative_path) {
-                        Err("Path contains traversal sequences".to_string())
-                    } else {
-                        Ok(resolved)
-                    }
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let escape = path
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match escape {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 3;
+                    continue;
                 }
+                None => return Cow::Borrowed(path),
             }
         }
-        Err(_) => {
-            // File doesn't exist yet, validate the path structure
-            if has_path_traversal(relative_path) {
-                Err("Path contains traversal sequences".to_string())
-            } else {
-                Ok(resolved)
-            }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    match String::from_utf8(decoded) {
+        Ok(decoded) => Cow::Owned(decoded),
+        Err(_) => Cow::Borrowed(path),
+    }
+}
+
+/// Converts `\` separators to the platform separator.
+fn normalize_separators(reference: &str) -> Cow<'_, str> {
+    if std::path::MAIN_SEPARATOR != '\\' && reference.contains('\\') {
+        Cow::Owned(reference.replace('\\', "/"))
+    } else {
+        Cow::Borrowed(reference)
+    }
+}
+
+/// Encodes data as a `data:` URI with the given MIME type.
+pub fn data_uri(mime_type: &str, data: &[u8]) -> String {
+    use base64::Engine;
+    format!(
+        "data:{mime_type};base64,{}",
+        base64::engine::general_purpose::STANDARD.encode(data)
+    )
+}
+
+/// Checks whether `text` matches a wildcard pattern, ignoring case. `*` matches any
+/// sequence of characters and `?` any single character.
+pub fn matches_glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    // Greedy matching, backtracking to the last `*` on a mismatch
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
         }
     }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Computes a `/`-separated path to `target` relative to the directory `from_dir`.
+/// Both paths should be absolute; returns `None` when they share no common root.
+pub fn relative_path(from_dir: &Path, target: &Path) -> Option<String> {
+    let from: Vec<_> = from_dir.components().collect();
+    let to: Vec<_> = target.components().collect();
+
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        return None;
+    }
+
+    let parts: Vec<String> = std::iter::repeat_n("..".to_string(), from.len() - common)
+        .chain(
+            to[common..]
+                .iter()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+        )
+        .collect();
+
+    Some(parts.join("/"))
+}
+
+/// Computes the lowercase hex SHA-1 digest of data, as used by WeChat signatures.
+#[cfg(any(feature = "client", feature = "server"))]
+pub(crate) fn sha1_hex(data: &[u8]) -> String {
+    use sha1::{Digest, Sha1};
+
+    format!("{:x}", Sha1::digest(data))
 }
 
 #[cfg(test)]
@@ -305,6 +548,15 @@ mod tests {
     use super::*;
     use std::path::PathBuf;
 
+    #[test]
+    fn test_data_uri() {
+        assert_eq!(
+            data_uri("image/png", b"\x89PNG"),
+            "data:image/png;base64,iVBORw=="
+        );
+        assert_eq!(data_uri("image/svg+xml", b""), "data:image/svg+xml;base64,");
+    }
+
     #[test]
     fn test_get_file_extension() {
         assert_eq!(get_file_extension(Path::new("test.md")), Some("md"));
@@ -389,6 +641,64 @@ mod tests {
         assert!(resolve_path(base, "script.bat").is_err());
     }
 
+    #[test]
+    fn test_resolve_path_separators_and_errors() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let base = temp_dir.path();
+        std::fs::create_dir(base.join("images")).unwrap();
+        std::fs::write(base.join("images").join("photo.png"), b"png").unwrap();
+
+        // Windows-style references resolve on every platform
+        let expected = base.join("images").join("photo.png");
+        let resolved = resolve_path(base, "images\\photo.png").unwrap();
+        assert_eq!(
+            resolved.canonicalize().unwrap(),
+            expected.canonicalize().unwrap()
+        );
+        assert_eq!(
+            join_reference(base, ".\\images\\photo.png")
+                .canonicalize()
+                .unwrap(),
+            expected.canonicalize().unwrap()
+        );
+
+        // Climbing back down inside the base directory is fine, escaping it is not
+        assert!(resolve_path(base, "images/../images/photo.png").is_ok());
+        match resolve_path(base, "images\\..\\..\\secret.png") {
+            Err(WeChatError::InvalidPath { path, reason }) => {
+                assert_eq!(path, "images\\..\\..\\secret.png");
+                assert!(reason.contains("escapes"));
+            }
+            other => panic!("Expected InvalidPath error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_decode_local_reference() {
+        assert_eq!(decode_local_reference("img.png"), "img.png");
+        assert_eq!(decode_local_reference("./my%20photo.png"), "./my photo.png");
+        assert_eq!(decode_local_reference("img.png?v=2"), "img.png");
+        assert_eq!(decode_local_reference("img.png#frag"), "img.png");
+        assert_eq!(decode_local_reference("%E5%9B%BE.png?x#y"), "图.png");
+        assert_eq!(decode_local_reference("100%.png"), "100%.png");
+        assert_eq!(decode_local_reference("%ff.png"), "%ff.png");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_path_non_utf8_base() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let base = temp_dir.path().join(OsStr::from_bytes(b"posts-\xff"));
+        std::fs::create_dir(&base).unwrap();
+        std::fs::write(base.join("photo.png"), b"png").unwrap();
+
+        let resolved = resolve_path(&base, "photo.png").unwrap();
+        assert_eq!(resolved, base.join("photo.png"));
+        assert!(resolved.exists());
+    }
+
     #[test]
     fn test_is_safe_path() {
         assert!(is_safe_path(Path::new("document.md")));
@@ -408,6 +718,29 @@ mod tests {
         assert!(is_safe_path(Path::new(".env")));
     }
 
+    #[test]
+    fn test_path_policy() {
+        let security = SecurityConfig::builder()
+            .allowed_extensions(vec![".JS".to_string()])
+            .allowed_hidden_files(vec![".assets".to_string()])
+            .allowed_roots(vec![PathBuf::from("/srv/content")])
+            .build();
+        let policy = PathPolicy::from(&security);
+
+        assert!(policy.is_safe(Path::new("assets/chart.js")));
+        assert!(!policy.is_safe(Path::new("assets/setup.exe")));
+        assert!(policy.is_safe(Path::new("posts/.assets/photo.png")));
+        assert!(!policy.is_safe(Path::new("posts/.drafts/photo.png")));
+
+        // Allowed roots skip the name checks but not the extension check
+        assert!(policy.is_safe(Path::new("/srv/content/.drafts/photo.png")));
+        assert!(!policy.is_safe(Path::new("/srv/content/setup.exe")));
+        assert!(!policy.is_safe(Path::new("/srv/other/.drafts/photo.png")));
+
+        let unchecked = SecurityConfig::builder().validate_file_paths(false).build();
+        assert!(PathPolicy::from(&unchecked).is_safe(Path::new(".hidden/setup.exe")));
+    }
+
     #[test]
     fn test_has_path_traversal() {
         assert!(has_path_traversal("../etc/passwd"));
@@ -450,4 +783,29 @@ mod tests {
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("image file too large"));
     }
+
+    #[test]
+    fn test_matches_glob() {
+        assert!(matches_glob("banner-*.png", "Banner-2024.PNG"));
+        assert!(matches_glob("*", ""));
+        assert!(matches_glob("a?c", "abc"));
+        assert!(matches_glob("*.jp*g", "photo.jpeg"));
+        assert!(matches_glob("*logo*", "新logo最终版.png"));
+        assert!(!matches_glob("a?c", "ac"));
+        assert!(!matches_glob("banner-*.png", "banner-2024.jpg"));
+        assert!(!matches_glob("logo", "logo.png"));
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path(Path::new("/a/b"), Path::new("/a/b/img/x.png")),
+            Some("img/x.png".to_string())
+        );
+        assert_eq!(
+            relative_path(Path::new("/a/out"), Path::new("/a/b/x.png")),
+            Some("../b/x.png".to_string())
+        );
+        assert_eq!(relative_path(Path::new("a"), Path::new("/b/x.png")), None);
+    }
 }